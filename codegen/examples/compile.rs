@@ -5,10 +5,10 @@ extern crate vicis_core;
 use std::fs::{self, File};
 use std::io::Write;
 use structopt::StructOpt;
-use vicis_codegen::lower::compile_module;
-use vicis_core::ir::function::Function;
+use vicis_codegen::lower::{compile_module_with_options, CompileOptions};
+use vicis_codegen::pass::regalloc::RegAllocKind;
 use vicis_core::ir::module::Module;
-use vicis_core::pass::transform::mem2reg::Mem2RegPass;
+use vicis_core::pass::pipeline::OptLevel;
 use vicis_core::pass::PassManager;
 
 #[derive(Debug, StructOpt)]
@@ -16,15 +16,53 @@ use vicis_core::pass::PassManager;
 pub struct Opt {
     pub ir_file: String,
 
-    #[structopt(short = "o", help = "Output file name")]
+    #[structopt(
+        short = "o",
+        help = "Output file name (an object file is emitted if it ends with .o)"
+    )]
     pub out_file: Option<String>,
 
     #[structopt(
         short = "p",
         long = "pass",
-        help = "Specify a comma-separated list of passes to run"
+        help = "Specify a comma-separated list of passes to run (e.g. mem2reg,fixed-point(sccp,dce))"
     )]
     pub pass: Option<String>,
+
+    #[structopt(
+        short = "O",
+        default_value = "0",
+        help = "Optimization level (0, 1 or 2). Ignored if -p is given"
+    )]
+    pub opt_level: OptLevel,
+
+    #[structopt(
+        long = "regalloc",
+        default_value = "linear-scan",
+        help = "Register allocator to use (linear-scan or graph-coloring)"
+    )]
+    pub regalloc: String,
+
+    #[structopt(
+        long = "disable-pass",
+        help = "Specify a comma-separated list of machine passes not to run"
+    )]
+    pub disable_pass: Option<String>,
+
+    #[structopt(
+        long = "print-before",
+        help = "Dump the module before the comma-separated machine passes (or 'all')"
+    )]
+    pub print_before: Option<String>,
+
+    #[structopt(
+        long = "print-after",
+        help = "Dump the module after the comma-separated machine passes (or 'all')"
+    )]
+    pub print_after: Option<String>,
+
+    #[structopt(long = "time-passes", help = "Print the time each machine pass took")]
+    pub time_passes: bool,
 }
 
 fn main() {
@@ -35,11 +73,11 @@ fn main() {
     let ir = fs::read_to_string(&opt.ir_file).expect("failed to load *.ll file");
     let mut module = Module::try_from(ir.as_str()).expect("failed to parse LLVM Assembly");
 
-    let mut pm = PassManager::new();
-    if let Some(pass) = opt.pass {
-        set_passes(&mut pm, pass);
-        pm.run_on_module(&mut module);
-    }
+    let mut pm = match opt.pass {
+        Some(pass) => PassManager::from_pipeline(&pass).expect("invalid pipeline"),
+        None => PassManager::with_opt_level(opt.opt_level),
+    };
+    pm.run_on_module(&mut module);
 
     #[cfg(target_arch = "x86_64")]
     let isa = {
@@ -51,22 +89,51 @@ fn main() {
         use vicis_codegen::isa::aarch64::Aarch64;
         Aarch64::default()
     };
-    let module = compile_module(&isa, &module).expect("failed to compile");
-    File::create(
-        opt.out_file
-            .unwrap_or_else(|| opt.ir_file.trim_end_matches(".ll").to_owned() + ".s"),
-    )
-    .expect("failed to create output file")
-    .write(format!("{}", module.display_asm()).as_bytes())
-    .unwrap();
-}
-
-fn set_passes(pm: &mut PassManager<Function>, pass: String) {
-    for name in pass.split(',') {
+    let regalloc = match opt.regalloc.as_str() {
+        "linear-scan" => RegAllocKind::LinearScan,
+        "graph-coloring" => RegAllocKind::GraphColoring,
+        e => panic!("unknown register allocator: {}", e),
+    };
+    let mut options = CompileOptions {
+        regalloc,
+        ..Default::default()
+    };
+    for name in opt.disable_pass.iter().flat_map(|p| p.split(',')) {
+        options.passes.remove(name).expect("unknown pass");
+    }
+    for name in opt.print_before.iter().flat_map(|p| p.split(',')) {
         match name {
-            "mem2reg" => pm.add_transform(Mem2RegPass),
-            "" => continue,
-            _ => panic!("Unknown pass: {}", name),
+            "all" => options.passes.print_before_all(),
+            name => options.passes.print_before(name),
         }
     }
+    for name in opt.print_after.iter().flat_map(|p| p.split(',')) {
+        match name {
+            "all" => options.passes.print_after_all(),
+            name => options.passes.print_after(name),
+        }
+    }
+    let module =
+        compile_module_with_options(&isa, &module, &mut options).expect("failed to compile");
+    if opt.time_passes {
+        for timing in options.passes.timings() {
+            eprintln!("{:>10.3?} {}", timing.elapsed, timing.name);
+        }
+    }
+    let out_file = opt
+        .out_file
+        .unwrap_or_else(|| opt.ir_file.trim_end_matches(".ll").to_owned() + ".s");
+
+    // Emit an object file instead of assembly if requested.
+    #[cfg(target_arch = "x86_64")]
+    if out_file.ends_with(".o") {
+        let obj = vicis_codegen::isa::x86_64::elf::write(&module).expect("failed to encode");
+        fs::write(out_file, obj).expect("failed to write output file");
+        return;
+    }
+
+    File::create(out_file)
+        .expect("failed to create output file")
+        .write_all(format!("{}", module.display_asm()).as_bytes())
+        .unwrap();
 }
@@ -0,0 +1,43 @@
+extern crate structopt;
+extern crate vicis_codegen;
+extern crate vicis_core;
+
+use std::{fs, process};
+use structopt::StructOpt;
+use vicis_codegen::{
+    isa::x86_64::{
+        jit::{JitBuilder, Value},
+        X86_64,
+    },
+    lower::compile_module,
+};
+use vicis_core::ir::module::Module;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "jit")]
+pub struct Opt {
+    pub ir_file: String,
+
+    #[structopt(long = "load")]
+    pub libs: Vec<String>,
+}
+
+fn main() {
+    env_logger::init();
+    color_backtrace::install();
+    let opt = Opt::from_args();
+    let ir = fs::read_to_string(opt.ir_file).expect("failed to load *.ll file");
+    let module = Module::try_from(ir.as_str()).expect("failed to parse LLVM Assembly");
+    let isa = X86_64::default();
+    let module = compile_module(&isa, &module).expect("failed to compile");
+    let jit = JitBuilder::new(&module)
+        .with_libs(opt.libs)
+        .build()
+        .expect("failed to load module");
+    unsafe { jit.run_constructors() };
+    let ret = unsafe { jit.call("main", &[]) }.expect("failed to call 'main'");
+    process::exit(match ret {
+        Value::Int32(i) => i,
+        _ => 0,
+    })
+}
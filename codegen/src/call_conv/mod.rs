@@ -2,4 +2,5 @@
 pub enum CallConvKind {
     SystemV,
     AAPCS64,
+    LP64D,
 }
@@ -1,9 +1,10 @@
 use id_arena::Id;
 use rustc_hash::FxHashSet;
+use vicis_core::traits::basic_block::BasicBlock as BB;
 
 pub type BasicBlockId = Id<BasicBlock>;
 
-#[derive(Default)]
+#[derive(Debug, Default)]
 pub struct BasicBlock {
     pub preds: FxHashSet<BasicBlockId>,
     pub succs: FxHashSet<BasicBlockId>,
@@ -14,3 +15,13 @@ impl BasicBlock {
         Self::default()
     }
 }
+
+impl BB for BasicBlock {
+    fn preds(&self) -> &FxHashSet<Id<Self>> {
+        &self.preds
+    }
+
+    fn succs(&self) -> &FxHashSet<Id<Self>> {
+        &self.succs
+    }
+}
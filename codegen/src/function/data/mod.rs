@@ -15,6 +15,8 @@ pub struct Data<Inst: TargetInst> {
     pub vregs: VRegs,
     pub vreg_users: VRegUsers<Inst>,
     pub used_csr: HashSet<RegUnit>,
+    /// Tables of blocks that indirect jumps (e.g. of `switch`) select from.
+    pub jump_tables: Vec<Vec<BasicBlockId>>,
 }
 
 impl<Inst: TargetInst> Default for Data<Inst> {
@@ -26,6 +28,7 @@ impl<Inst: TargetInst> Default for Data<Inst> {
             vregs: VRegs::new(),
             vreg_users: VRegUsers::new(),
             used_csr: HashSet::default(),
+            jump_tables: Vec::new(),
         }
     }
 }
@@ -59,6 +62,12 @@ impl<Inst: TargetInst> Data<Inst> {
         id
     }
 
+    /// Adds a jump table and returns its index.
+    pub fn create_jump_table(&mut self, blocks: Vec<BasicBlockId>) -> usize {
+        self.jump_tables.push(blocks);
+        self.jump_tables.len() - 1
+    }
+
     pub fn block_ref(&self, id: BasicBlockId) -> &BasicBlock {
         &self.basic_blocks[id]
     }
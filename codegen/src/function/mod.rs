@@ -5,9 +5,13 @@ pub mod layout;
 pub mod slot;
 
 use super::{call_conv::CallConvKind, isa::TargetIsa};
+use basic_block::{BasicBlock, BasicBlockId};
 use instruction::InstructionId;
 use std::fmt;
-use vicis_core::ir::{function::Function as IrFunction, types::Types};
+use vicis_core::{
+    ir::{function::Function as IrFunction, types::Types},
+    traits::basic_block::{BasicBlockData, BasicBlockLayout},
+};
 
 pub struct Function<'a, T: TargetIsa> {
     pub ir: &'a IrFunction,
@@ -26,6 +30,18 @@ impl<T: TargetIsa> Function<'_, T> {
     }
 }
 
+impl<T: TargetIsa> BasicBlockData<BasicBlock> for Function<'_, T> {
+    fn get(&self, id: BasicBlockId) -> &BasicBlock {
+        &self.data.basic_blocks[id]
+    }
+}
+
+impl<T: TargetIsa> BasicBlockLayout<BasicBlock> for Function<'_, T> {
+    fn order(&self) -> Box<dyn Iterator<Item = BasicBlockId> + '_> {
+        Box::new(self.layout.block_iter())
+    }
+}
+
 impl<T: TargetIsa> fmt::Debug for Function<'_, T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.is_declaration {
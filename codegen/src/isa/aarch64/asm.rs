@@ -1,15 +1,213 @@
-use crate::{isa::aarch64::Aarch64, module::DisplayAsm};
+use crate::{
+    function::Function,
+    isa::{
+        aarch64::{
+            instruction::{InstructionData, Opcode, Operand, OperandData},
+            register::reg_to_str,
+            Aarch64,
+        },
+        asm::print_global_variables,
+    },
+    module::{DisplayAsm, Module},
+};
 use std::fmt::{Display, Formatter, Result};
 
 impl Display for DisplayAsm<'_, Aarch64> {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        self.print(f)
+        print(f, self.0)
     }
 }
 
-impl DisplayAsm<'_, Aarch64> {
-    fn print(&self, f: &mut Formatter) -> Result {
+pub fn print(f: &mut Formatter<'_>, module: &Module<Aarch64>) -> Result {
+    writeln!(f, "  .text")?;
+
+    for (i, (_, func)) in module.functions.iter().enumerate() {
+        print_function(f, func, i)?
+    }
+
+    print_global_variables(f, module)
+}
+
+pub fn print_function(
+    f: &mut Formatter<'_>,
+    function: &Function<Aarch64>,
+    fn_idx: usize,
+) -> Result {
+    if function.is_declaration {
+        return Ok(());
+    }
+
+    if let Some(name) = &function.ir.section {
+        writeln!(f, "  .section {}", name)?;
+    } else {
         writeln!(f, "  .text")?;
-        Ok(())
+    }
+    if !function.ir.linkage.is_internal() {
+        writeln!(f, "  .globl {}", function.ir.name())?;
+    }
+    writeln!(f, "  .p2align 2")?;
+    writeln!(f, "{}:", function.ir.name())?;
+
+    for block in function.layout.block_iter() {
+        writeln!(f, ".LBL{}_{}:", fn_idx, block.index())?;
+        for inst in function.layout.inst_iter(block) {
+            let inst = function.data.inst_ref(inst);
+            print_inst(f, &inst.data, fn_idx)?;
+            writeln!(f)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn print_inst(f: &mut Formatter<'_>, inst: &InstructionData, fn_idx: usize) -> Result {
+    let ops = &inst.operands;
+    match inst.opcode {
+        Opcode::MOVZWi | Opcode::MOVZXi => {
+            write!(f, "  mov {}, #{}", operand(&ops[0], fn_idx), imm(&ops[1]))
+        }
+        // Print the value to be moved as `mov` does.
+        Opcode::MOVNWi => write!(
+            f,
+            "  mov {}, #{}",
+            operand(&ops[0], fn_idx),
+            !(imm(&ops[1]) as i32)
+        ),
+        Opcode::MOVNXi => write!(f, "  mov {}, #{}", operand(&ops[0], fn_idx), !imm(&ops[1])),
+        Opcode::MOVKWi | Opcode::MOVKXi => write!(
+            f,
+            "  movk {}, #{}, lsl #{}",
+            operand(&ops[0], fn_idx),
+            imm(&ops[1]),
+            imm(&ops[2])
+        ),
+        Opcode::STPXpre => write!(
+            f,
+            "  stp {}, {}, [{}, #{}]!",
+            operand(&ops[0], fn_idx),
+            operand(&ops[1], fn_idx),
+            operand(&ops[2], fn_idx),
+            imm(&ops[3])
+        ),
+        Opcode::STRXpre => write!(
+            f,
+            "  str {}, [{}, #{}]!",
+            operand(&ops[0], fn_idx),
+            operand(&ops[1], fn_idx),
+            imm(&ops[2])
+        ),
+        Opcode::LDPXpost => write!(
+            f,
+            "  ldp {}, {}, [{}], #{}",
+            operand(&ops[0], fn_idx),
+            operand(&ops[1], fn_idx),
+            operand(&ops[2], fn_idx),
+            imm(&ops[3])
+        ),
+        Opcode::LDRXpost => write!(
+            f,
+            "  ldr {}, [{}], #{}",
+            operand(&ops[0], fn_idx),
+            operand(&ops[1], fn_idx),
+            imm(&ops[2])
+        ),
+        opcode => {
+            write!(f, "  {}", opcode)?;
+            let mut i = 0;
+            let mut first = true;
+            while i < ops.len() {
+                if ops[i].implicit {
+                    i += 1;
+                    continue;
+                }
+                write!(f, "{}", if first { " " } else { ", " })?;
+                first = false;
+                if matches!(ops[i].data, OperandData::MemStart) {
+                    write!(f, "{}", mem_op(&ops[i + 1..i + 4]))?;
+                    i += 4;
+                } else {
+                    write!(f, "{}", operand(&ops[i], fn_idx))?;
+                    i += 1;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::MOVWrr | Self::MOVXrr | Self::MOVZWi | Self::MOVZXi => "mov",
+                Self::MOVNWi | Self::MOVNXi => "movn",
+                Self::MOVKWi | Self::MOVKXi => "movk",
+                Self::ADDWrr | Self::ADDXrr | Self::ADDWri | Self::ADDXri => "add",
+                Self::SUBWrr | Self::SUBXrr | Self::SUBWri | Self::SUBXri => "sub",
+                Self::MULWrr | Self::MULXrr => "mul",
+                Self::SXTWXr => "sxtw",
+                Self::UXTBWr => "uxtb",
+                Self::CMPWrr | Self::CMPXrr | Self::CMPWri | Self::CMPXri => "cmp",
+                Self::TSTWri => "tst",
+                Self::LDRBBui => "ldrb",
+                Self::LDRWui | Self::LDRXui | Self::LDRXpost => "ldr",
+                Self::STRBBui => "strb",
+                Self::STRWui | Self::STRXui | Self::STRXpre => "str",
+                Self::STPXpre => "stp",
+                Self::LDPXpost => "ldp",
+                Self::ADRP => "adrp",
+                Self::B => "b",
+                Self::BEQ => "b.eq",
+                Self::BNE => "b.ne",
+                Self::BLT => "b.lt",
+                Self::BLE => "b.le",
+                Self::BGT => "b.gt",
+                Self::BGE => "b.ge",
+                Self::BLO => "b.lo",
+                Self::BLS => "b.ls",
+                Self::BHI => "b.hi",
+                Self::BHS => "b.hs",
+                Self::BL => "bl",
+                Self::RET => "ret",
+                Self::Phi => "PHI",
+            }
+        )
+    }
+}
+
+fn operand(op: &Operand, fn_idx: usize) -> String {
+    match &op.data {
+        OperandData::Reg(r) => reg_to_str(r).to_owned(),
+        OperandData::VReg(r) => format!("%{}", r.0),
+        OperandData::Slot(slot) => format!("{:?}", slot),
+        OperandData::Int32(i) => format!("#{}", i),
+        OperandData::Int64(i) => format!("#{}", i),
+        OperandData::Block(block) => format!(".LBL{}_{}", fn_idx, block.index()),
+        OperandData::Label(name) => name.to_owned(),
+        OperandData::Lo12(name) => format!(":lo12:{}", name),
+        OperandData::MemStart => "".to_owned(),
+        OperandData::None => "none".to_owned(),
+    }
+}
+
+fn imm(op: &Operand) -> i64 {
+    op.data.sext_as_i64().unwrap()
+}
+
+fn mem_op(args: &[Operand]) -> String {
+    assert!(matches!(&args[0].data, &OperandData::None)); // assure slot is eliminated
+    match (&args[1].data, &args[2].data) {
+        (OperandData::None, OperandData::Reg(base)) => format!("[{}]", reg_to_str(base)),
+        (OperandData::Int32(0), OperandData::Reg(base)) => format!("[{}]", reg_to_str(base)),
+        (OperandData::Int32(imm), OperandData::Reg(base)) => {
+            format!("[{}, #{}]", reg_to_str(base), imm)
+        }
+        (OperandData::Lo12(name), OperandData::Reg(base)) => {
+            format!("[{}, :lo12:{}]", reg_to_str(base), name)
+        }
+        (_, OperandData::VReg(base)) => format!("[%{}]", base.0),
+        e => todo!("{:?}", e),
     }
 }
@@ -5,77 +5,572 @@ use crate::{
         slot::SlotId,
         Function,
     },
-    isa::TargetIsa,
+    isa::{
+        aarch64::register::{reg_to_str, GR32, GR64},
+        TargetIsa,
+    },
     register::{Reg, VReg, VRegUsers},
 };
+use std::fmt;
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct InstructionData {
-    // pub opcode: Opcode,
-// pub operands: Vec<Operand>,
+    pub opcode: Opcode,
+    pub operands: Vec<Operand>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Opcode {
+    MOVWrr,
+    MOVXrr,
+    MOVZWi,
+    MOVZXi,
+    MOVNWi,
+    MOVNXi,
+    MOVKWi,
+    MOVKXi,
+    ADDWrr,
+    ADDXrr,
+    ADDWri,
+    ADDXri,
+    SUBWrr,
+    SUBXrr,
+    SUBWri,
+    SUBXri,
+    MULWrr,
+    MULXrr,
+    SXTWXr,
+    UXTBWr,
+    CMPWrr,
+    CMPXrr,
+    CMPWri,
+    CMPXri,
+    TSTWri,
+    LDRBBui,
+    LDRWui,
+    LDRXui,
+    STRBBui,
+    STRWui,
+    STRXui,
+    STPXpre,
+    LDPXpost,
+    STRXpre,
+    LDRXpost,
+    ADRP,
+    B,
+    BEQ,
+    BNE,
+    BLT,
+    BLE,
+    BGT,
+    BGE,
+    BLO,
+    BLS,
+    BHI,
+    BHS,
+    BL,
+    RET,
+
+    // TODO
+    Phi,
+}
+
+#[derive(Clone)]
+pub struct Operand {
+    pub data: OperandData,
+    pub input: bool,
+    pub output: bool,
+    pub implicit: bool,
+}
+
+#[derive(Clone)]
+pub enum OperandData {
+    Reg(Reg),
+    VReg(VReg),
+    Int32(i32),
+    Int64(i64),
+    MemStart, // followed by: Slot, Imm, Reg(base)
+    Slot(SlotId),
+    Block(BasicBlockId),
+    Label(String),
+    Lo12(String), // The low 12 bits of the address of the label
+    None,
 }
 
 impl TargetInst for InstructionData {
-    fn input_vregs_with_indexes(&self) -> Vec<(usize, VReg)> {
-        todo!()
-    }
     fn input_vregs(&self) -> Vec<VReg> {
-        todo!()
+        self.input_vregs_with_indexes()
+            .into_iter()
+            .map(|(_, r)| r)
+            .collect()
+    }
+
+    fn input_vregs_with_indexes(&self) -> Vec<(usize, VReg)> {
+        let mut list = vec![];
+        for (i, operand) in self.operands.iter().enumerate() {
+            if let Operand {
+                data: OperandData::VReg(vr),
+                input: true,
+                ..
+            } = operand
+            {
+                list.push((i, *vr))
+            }
+        }
+        list
     }
+
     fn output_vregs(&self) -> Vec<VReg> {
-        todo!()
+        let mut vrs = vec![];
+        for operand in &self.operands {
+            if let Operand {
+                data: OperandData::VReg(vr),
+                output: true,
+                ..
+            } = operand
+            {
+                vrs.push(*vr)
+            }
+        }
+        vrs
     }
+
     fn all_vregs(&self) -> Vec<VReg> {
-        todo!()
+        let mut list = vec![];
+        for operand in &self.operands {
+            if let Operand {
+                data: OperandData::VReg(r),
+                ..
+            } = operand
+            {
+                list.push(*r)
+            }
+        }
+        list
     }
+
     fn input_regs(&self) -> Vec<Reg> {
-        todo!()
+        let mut rs = vec![];
+        for operand in &self.operands {
+            if let Operand {
+                data: OperandData::Reg(r),
+                input: true,
+                ..
+            } = operand
+            {
+                rs.push(*r)
+            }
+        }
+        rs
     }
+
     fn output_regs(&self) -> Vec<Reg> {
-        todo!()
+        let mut rs = vec![];
+        for operand in &self.operands {
+            if let Operand {
+                data: OperandData::Reg(r),
+                output: true,
+                ..
+            } = operand
+            {
+                rs.push(*r)
+            }
+        }
+        rs
     }
+
     fn all_regs(&self) -> Vec<Reg> {
-        todo!()
+        let mut list = vec![];
+        for operand in &self.operands {
+            if let Operand {
+                data: OperandData::Reg(r),
+                ..
+            } = operand
+            {
+                list.push(*r)
+            }
+        }
+        list
     }
-    fn rewrite(&mut self, _vreg: VReg, _reg: Reg) {
-        todo!()
+
+    fn rewrite(&mut self, vreg: VReg, reg: Reg) {
+        for operand in &mut self.operands {
+            match operand.data {
+                OperandData::VReg(vr) if vr == vreg => operand.data = OperandData::Reg(reg),
+                _ => {}
+            }
+        }
     }
+
     fn replace_vreg(
         &mut self,
-        _self_id: InstructionId<Self>,
-        _users: &mut VRegUsers<Self>,
-        _from: VReg,
-        _to: VReg,
+        self_id: InstructionId<Self>,
+        users: &mut VRegUsers<Self>,
+        from: VReg,
+        to: VReg,
     ) {
-        todo!()
+        let u = users.remove_use(from, self_id).unwrap();
+        users.add_use(to, self_id, u.read, u.write);
+        for operand in &mut self.operands {
+            match operand.data {
+                OperandData::VReg(r) if r == from => operand.data = OperandData::VReg(to),
+                _ => {}
+            }
+        }
     }
-    fn block_at(&self, _i: usize) -> Option<BasicBlockId> {
-        todo!()
+
+    fn block_at(&self, i: usize) -> Option<BasicBlockId> {
+        self.operands.get(i).and_then(|data| match data.data {
+            OperandData::Block(b) => Some(b),
+            _ => None,
+        })
     }
+
     fn is_copy(&self) -> bool {
-        todo!()
+        matches!(self.opcode, Opcode::MOVWrr | Opcode::MOVXrr)
     }
+
     fn is_call(&self) -> bool {
-        todo!()
+        self.opcode == Opcode::BL
     }
+
     fn is_phi(&self) -> bool {
-        todo!()
+        self.opcode == Opcode::Phi
     }
 
     fn store_vreg_to_slot<T: TargetIsa>(
-        _f: &Function<T>,
-        _vreg: VReg,
-        _slot: SlotId,
-        _block: BasicBlockId,
+        f: &Function<T>,
+        vreg: VReg,
+        slot: SlotId,
+        block: BasicBlockId,
     ) -> Instruction<Self> {
-        todo!()
+        let ty = f.data.vregs.type_for(vreg);
+        let sz = f.isa.data_layout().get_size_of(&f.types, ty);
+        Instruction::new(
+            InstructionData {
+                opcode: match sz {
+                    1 => Opcode::STRBBui,
+                    4 => Opcode::STRWui,
+                    8 => Opcode::STRXui,
+                    _ => unreachable!(),
+                },
+                operands: vec![
+                    Operand::input(vreg.into()),
+                    Operand::new(OperandData::MemStart),
+                    Operand::new(OperandData::Slot(slot)),
+                    Operand::new(OperandData::None),
+                    Operand::input(OperandData::None),
+                ],
+            },
+            block,
+        )
     }
+
     fn load_from_slot<T: TargetIsa>(
-        _f: &Function<T>,
-        _vreg: VReg,
-        _slot: SlotId,
-        _block: BasicBlockId,
+        f: &Function<T>,
+        vreg: VReg,
+        slot: SlotId,
+        block: BasicBlockId,
     ) -> Instruction<Self> {
-        todo!()
+        let ty = f.data.vregs.type_for(vreg);
+        let sz = f.isa.data_layout().get_size_of(&f.types, ty);
+        Instruction::new(
+            InstructionData {
+                opcode: match sz {
+                    1 => Opcode::LDRBBui,
+                    4 => Opcode::LDRWui,
+                    8 => Opcode::LDRXui,
+                    _ => unreachable!(),
+                },
+                operands: vec![
+                    Operand::output(vreg.into()),
+                    Operand::new(OperandData::MemStart),
+                    Operand::new(OperandData::Slot(slot)),
+                    Operand::new(OperandData::None),
+                    Operand::input(OperandData::None),
+                ],
+            },
+            block,
+        )
+    }
+}
+
+impl Opcode {
+    pub fn is_branch(&self) -> bool {
+        matches!(
+            self,
+            Self::B
+                | Self::BEQ
+                | Self::BNE
+                | Self::BLT
+                | Self::BLE
+                | Self::BGT
+                | Self::BGE
+                | Self::BLO
+                | Self::BLS
+                | Self::BHI
+                | Self::BHS
+        )
+    }
+}
+
+impl InstructionData {
+    /// Returns the instructions that move `imm` into `dst`, using a single `movz` or `movn` if
+    /// possible, and `movk`s for the remaining nonzero halfwords otherwise.
+    pub fn mov_imm(dst: OperandData, imm: i64, is_64: bool) -> Vec<Self> {
+        let (bits, halfwords) = if is_64 {
+            (imm as u64, 4)
+        } else {
+            (imm as u32 as u64, 2)
+        };
+        let mask = if is_64 { u64::MAX } else { u32::MAX as u64 };
+        let (movz, movn, movk) = if is_64 {
+            (Opcode::MOVZXi, Opcode::MOVNXi, Opcode::MOVKXi)
+        } else {
+            (Opcode::MOVZWi, Opcode::MOVNWi, Opcode::MOVKWi)
+        };
+
+        if bits >> 16 == 0 {
+            return vec![Self {
+                opcode: movz,
+                operands: vec![Operand::output(dst), Operand::new((bits as i32).into())],
+            }];
+        }
+        if (!bits & mask) >> 16 == 0 {
+            return vec![Self {
+                opcode: movn,
+                operands: vec![
+                    Operand::output(dst),
+                    Operand::new(((!bits & 0xffff) as i32).into()),
+                ],
+            }];
+        }
+
+        let mut insts = vec![Self {
+            opcode: movz,
+            operands: vec![
+                Operand::output(dst.clone()),
+                Operand::new(((bits & 0xffff) as i32).into()),
+            ],
+        }];
+        for i in 1..halfwords {
+            let halfword = (bits >> (i * 16)) & 0xffff;
+            if halfword == 0 {
+                continue;
+            }
+            insts.push(Self {
+                opcode: movk,
+                operands: vec![
+                    Operand::input_output(dst.clone()),
+                    Operand::new((halfword as i32).into()),
+                    Operand::new((i * 16).into()),
+                ],
+            });
+        }
+        insts
+    }
+}
+
+impl Operand {
+    pub fn new(data: OperandData) -> Self {
+        Self {
+            data,
+            input: false,
+            output: false,
+            implicit: false,
+        }
+    }
+
+    pub fn input(data: OperandData) -> Self {
+        Self {
+            data,
+            input: true,
+            output: false,
+            implicit: false,
+        }
+    }
+
+    pub fn output(data: OperandData) -> Self {
+        Self {
+            data,
+            input: false,
+            output: true,
+            implicit: false,
+        }
+    }
+
+    pub fn implicit_input(data: OperandData) -> Self {
+        Self {
+            data,
+            input: true,
+            output: false,
+            implicit: true,
+        }
+    }
+
+    pub fn implicit_output(data: OperandData) -> Self {
+        Self {
+            data,
+            input: false,
+            output: true,
+            implicit: true,
+        }
+    }
+
+    pub fn input_output(data: OperandData) -> Self {
+        Self {
+            data,
+            input: true,
+            output: true,
+            implicit: false,
+        }
+    }
+}
+
+impl OperandData {
+    pub fn as_reg(&self) -> &Reg {
+        match self {
+            Self::Reg(r) => r,
+            _ => todo!(),
+        }
+    }
+
+    pub fn as_vreg(&self) -> &VReg {
+        match self {
+            Self::VReg(r) => r,
+            _ => todo!(),
+        }
+    }
+
+    pub fn as_block(&self) -> &BasicBlockId {
+        match self {
+            Self::Block(b) => b,
+            _ => todo!(),
+        }
+    }
+
+    pub fn sext_as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Int64(i) => Some(*i),
+            Self::Int32(i) => Some(*i as i64),
+            _ => None,
+        }
+    }
+}
+
+impl From<VReg> for OperandData {
+    fn from(r: VReg) -> Self {
+        OperandData::VReg(r)
+    }
+}
+
+impl From<Reg> for OperandData {
+    fn from(r: Reg) -> Self {
+        OperandData::Reg(r)
+    }
+}
+
+impl From<&VReg> for OperandData {
+    fn from(r: &VReg) -> Self {
+        OperandData::VReg(*r)
+    }
+}
+
+impl From<&Reg> for OperandData {
+    fn from(r: &Reg) -> Self {
+        OperandData::Reg(*r)
+    }
+}
+
+impl From<i32> for OperandData {
+    fn from(i: i32) -> Self {
+        OperandData::Int32(i)
+    }
+}
+
+impl From<&i32> for OperandData {
+    fn from(i: &i32) -> Self {
+        OperandData::Int32(*i)
+    }
+}
+
+impl From<i64> for OperandData {
+    fn from(i: i64) -> Self {
+        OperandData::Int64(i)
+    }
+}
+
+impl From<&i64> for OperandData {
+    fn from(i: &i64) -> Self {
+        OperandData::Int64(*i)
+    }
+}
+
+impl From<GR32> for OperandData {
+    fn from(r: GR32) -> Self {
+        OperandData::Reg(r.into())
+    }
+}
+
+impl From<GR64> for OperandData {
+    fn from(r: GR64) -> Self {
+        OperandData::Reg(r.into())
+    }
+}
+
+impl fmt::Debug for InstructionData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} ", self.opcode)?;
+        for (i, op) in self.operands.iter().enumerate() {
+            write!(f, "{:?}", op)?;
+            if i < self.operands.len() - 1 {
+                write!(f, ", ")?
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut flags = vec![];
+        if self.output {
+            flags.push("def")
+        }
+        if self.input {
+            flags.push("use")
+        }
+        if self.implicit {
+            flags.push("imp")
+        }
+        write!(f, "{:?}", self.data)?;
+        if !flags.is_empty() {
+            write!(f, "<")?;
+            for (i, flag) in flags.iter().enumerate() {
+                write!(f, "{}", flag)?;
+                if i < flags.len() - 1 {
+                    write!(f, ", ")?
+                }
+            }
+            write!(f, ">")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for OperandData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reg(r) => write!(f, "{}", reg_to_str(r)),
+            Self::VReg(vr) => write!(f, "%{}", vr.0),
+            Self::Int32(i) => write!(f, "{}", i),
+            Self::Int64(i) => write!(f, "{}", i),
+            Self::MemStart => write!(f, "$MemStart$"),
+            Self::Slot(slot) => write!(f, "slot.{}", slot.index()),
+            Self::Block(id) => write!(f, "block.{}", id.index()),
+            Self::Label(name) => write!(f, "{}", name),
+            Self::Lo12(name) => write!(f, ":lo12:{}", name),
+            Self::None => write!(f, "none"),
+        }
     }
 }
@@ -0,0 +1,44 @@
+use super::{mem_operands, new_empty_inst_output};
+use crate::{
+    function::instruction::Instruction as MachInstruction,
+    isa::aarch64::{
+        instruction::{InstructionData, Opcode, Operand as MOperand},
+        Aarch64,
+    },
+    isa::TargetIsa,
+    lower::{LoweringContext, LoweringError},
+};
+use anyhow::Result;
+use vicis_core::ir::{function::instruction::InstructionId, types::Type, value::ValueId};
+
+pub fn lower_load(
+    ctx: &mut LoweringContext<Aarch64>,
+    id: InstructionId,
+    tys: &[Type],
+    addr: ValueId,
+    _align: u32,
+) -> Result<()> {
+    let src_ty = tys[0];
+    let opcode = match ctx.isa.data_layout().get_size_of(ctx.types, src_ty) {
+        1 => Opcode::LDRBBui,
+        4 => Opcode::LDRWui,
+        8 => Opcode::LDRXui,
+        _ => return Err(LoweringError::Todo("Unsupported load pattern".into()).into()),
+    };
+
+    let mem = mem_operands(ctx, tys[1], addr)?;
+    let output = new_empty_inst_output(ctx, src_ty, id);
+
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode,
+            operands: vec![MOperand::output(output.into())]
+                .into_iter()
+                .chain(mem)
+                .collect(),
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+
+    Ok(())
+}
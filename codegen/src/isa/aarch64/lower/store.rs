@@ -0,0 +1,43 @@
+use super::{get_vreg_for_val, mem_operands};
+use crate::{
+    function::instruction::Instruction as MachInstruction,
+    isa::aarch64::{
+        instruction::{InstructionData, Opcode, Operand as MOperand},
+        Aarch64,
+    },
+    isa::TargetIsa,
+    lower::{LoweringContext, LoweringError},
+};
+use anyhow::Result;
+use vicis_core::ir::{types::Type, value::ValueId};
+
+pub fn lower_store(
+    ctx: &mut LoweringContext<Aarch64>,
+    tys: &[Type],
+    args: &[ValueId],
+    _align: u32,
+) -> Result<()> {
+    let src_ty = tys[0];
+    let opcode = match ctx.isa.data_layout().get_size_of(ctx.types, src_ty) {
+        1 => Opcode::STRBBui,
+        4 => Opcode::STRWui,
+        8 => Opcode::STRXui,
+        _ => return Err(LoweringError::Todo("Unsupported store pattern".into()).into()),
+    };
+
+    let src = get_vreg_for_val(ctx, src_ty, args[0])?;
+    let mem = mem_operands(ctx, tys[1], args[1])?;
+
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode,
+            operands: vec![MOperand::input(src.into())]
+                .into_iter()
+                .chain(mem)
+                .collect(),
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+
+    Ok(())
+}
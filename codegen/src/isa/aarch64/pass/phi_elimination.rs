@@ -0,0 +1,84 @@
+use crate::{
+    function::{basic_block::BasicBlockId, instruction::Instruction, Function},
+    isa::aarch64::{
+        instruction::{InstructionData, Opcode, Operand, OperandData},
+        register::RegClass,
+        Aarch64,
+    },
+    module::Module,
+    register::Reg,
+};
+use anyhow::{bail, Result};
+use rustc_hash::FxHashMap;
+
+pub fn run_on_module(module: &mut Module<Aarch64>) -> Result<()> {
+    for (_, func) in &mut module.functions {
+        run_on_function(func)?;
+    }
+    Ok(())
+}
+
+pub fn run_on_function(function: &mut Function<Aarch64>) -> Result<()> {
+    let mut worklist = vec![];
+    let mut map: FxHashMap<Reg, Vec<(OperandData, BasicBlockId)>> = FxHashMap::default();
+
+    for block_id in function.layout.block_iter() {
+        for inst_id in function.layout.inst_iter(block_id) {
+            let inst = function.data.inst_ref(inst_id);
+            if !matches!(inst.data.opcode, Opcode::Phi) {
+                continue;
+            }
+            worklist.push(inst_id);
+            let output = *inst.data.operands[0].data.as_reg();
+            for i in (0..inst.data.operands[1..].len()).step_by(2) {
+                let val = inst.data.operands[1 + i /*+ 0*/].data.clone();
+                let block = *inst.data.operands[1 + i + 1].data.as_block();
+                map.entry(output).or_default().push((val, block));
+            }
+        }
+    }
+
+    for (output, args) in map {
+        let is_64 = output.0 == RegClass::GR64 as u16;
+        for (arg, block) in args {
+            // Copies go before the branches at the end of the block. They don't change the
+            // condition flags, so they can be placed after a comparison.
+            let mut term = function.layout.last_inst_of(block).unwrap();
+            while let Some(prev) = function.layout.prev_inst_of(term) {
+                if !function.data.inst_ref(prev).data.opcode.is_branch() {
+                    break;
+                }
+                term = prev;
+            }
+            let copies = match arg {
+                OperandData::Int32(_) | OperandData::Int64(_) => InstructionData::mov_imm(
+                    OperandData::Reg(output),
+                    arg.sext_as_i64().unwrap(),
+                    is_64,
+                ),
+                OperandData::Reg(_) => vec![InstructionData {
+                    opcode: if is_64 {
+                        Opcode::MOVXrr
+                    } else {
+                        Opcode::MOVWrr
+                    },
+                    operands: vec![
+                        Operand::output(OperandData::Reg(output)),
+                        Operand::input(arg),
+                    ],
+                }],
+                e => bail!("unsupported phi argument: {:?}", e),
+            };
+            for copy in copies {
+                let copy = function.data.create_inst(Instruction::new(copy, block));
+                function.layout.insert_inst_before(term, copy, block);
+            }
+        }
+    }
+
+    for inst_id in worklist {
+        function.remove_inst(inst_id);
+    }
+
+    Ok(())
+}
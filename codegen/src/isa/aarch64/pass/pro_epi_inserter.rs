@@ -0,0 +1,174 @@
+use crate::{
+    function::{instruction::Instruction, Function},
+    isa::aarch64::{
+        instruction::{InstructionData, Opcode, Operand},
+        register::{RegClass, GR64},
+        Aarch64,
+    },
+    module::Module,
+    register::Reg,
+};
+use anyhow::Result;
+
+pub fn run_on_module(module: &mut Module<Aarch64>) -> Result<()> {
+    for (_, func) in &mut module.functions {
+        run_on_function(func);
+    }
+    Ok(())
+}
+
+// The frame looks like below. `sp` is kept 16-byte aligned.
+//
+//   | x30 (lr)      |
+//   | x29 (fp)      | <- x29
+//   | callee-saved  |
+//   | slots         |
+//   |               | <- sp
+pub fn run_on_function(function: &mut Function<Aarch64>) {
+    if function.is_declaration {
+        return;
+    }
+
+    let size = frame_size(function) as i32;
+
+    let mut used_csr = function
+        .data
+        .used_csr
+        .clone()
+        .into_iter()
+        .map(|r| r.apply(&RegClass::GR64))
+        .collect::<Vec<_>>();
+    used_csr.sort();
+    let pairs: Vec<&[Reg]> = used_csr.chunks(2).collect();
+
+    // insert prologue
+    let entry = function.layout.first_block.unwrap();
+    let mut prologue = vec![
+        InstructionData {
+            opcode: Opcode::STPXpre,
+            operands: vec![
+                Operand::input(GR64::X29.into()),
+                Operand::input(GR64::X30.into()),
+                Operand::input_output(GR64::SP.into()),
+                Operand::new((-16).into()),
+            ],
+        },
+        InstructionData {
+            opcode: Opcode::MOVXrr,
+            operands: vec![
+                Operand::output(GR64::X29.into()),
+                Operand::input(GR64::SP.into()),
+            ],
+        },
+    ];
+    for pair in &pairs {
+        prologue.push(match pair {
+            [r1, r2] => InstructionData {
+                opcode: Opcode::STPXpre,
+                operands: vec![
+                    Operand::input(r1.into()),
+                    Operand::input(r2.into()),
+                    Operand::input_output(GR64::SP.into()),
+                    Operand::new((-16).into()),
+                ],
+            },
+            [r] => InstructionData {
+                opcode: Opcode::STRXpre,
+                operands: vec![
+                    Operand::input(r.into()),
+                    Operand::input_output(GR64::SP.into()),
+                    Operand::new((-16).into()),
+                ],
+            },
+            _ => unreachable!(),
+        })
+    }
+    prologue.append(&mut adjust_sp(Opcode::SUBXri, Opcode::SUBXrr, size));
+    for data in prologue.into_iter().rev() {
+        let inst = function.data.create_inst(Instruction::new(data, entry));
+        function.layout.insert_inst_at_start(inst, entry);
+    }
+
+    // insert epilogue
+    let mut epilogues = vec![];
+    for block in function.layout.block_iter() {
+        for inst_id in function.layout.inst_iter(block) {
+            let inst = function.data.inst_ref(inst_id);
+            if !matches!(inst.data.opcode, Opcode::RET) {
+                continue;
+            }
+            epilogues.push((block, inst_id));
+        }
+    }
+    for (block, ret_id) in epilogues {
+        let mut epilogue = adjust_sp(Opcode::ADDXri, Opcode::ADDXrr, size);
+        for pair in pairs.iter().rev() {
+            epilogue.push(match pair {
+                [r1, r2] => InstructionData {
+                    opcode: Opcode::LDPXpost,
+                    operands: vec![
+                        Operand::output(r1.into()),
+                        Operand::output(r2.into()),
+                        Operand::input_output(GR64::SP.into()),
+                        Operand::new(16.into()),
+                    ],
+                },
+                [r] => InstructionData {
+                    opcode: Opcode::LDRXpost,
+                    operands: vec![
+                        Operand::output(r.into()),
+                        Operand::input_output(GR64::SP.into()),
+                        Operand::new(16.into()),
+                    ],
+                },
+                _ => unreachable!(),
+            })
+        }
+        epilogue.push(InstructionData {
+            opcode: Opcode::LDPXpost,
+            operands: vec![
+                Operand::output(GR64::X29.into()),
+                Operand::output(GR64::X30.into()),
+                Operand::input_output(GR64::SP.into()),
+                Operand::new(16.into()),
+            ],
+        });
+        for data in epilogue {
+            let inst = function.data.create_inst(Instruction::new(data, block));
+            function.layout.insert_inst_before(ret_id, inst, block);
+        }
+    }
+}
+
+/// Returns the size of the area for the slots, which keeps `sp` 16-byte aligned.
+pub fn frame_size(function: &mut Function<Aarch64>) -> u32 {
+    let slot_size = function.slots.ensure_computed_offsets();
+    (slot_size + 15) & !15
+}
+
+/// Returns `sp = sp <op> size`. A size that doesn't fit in a 12-bit immediate is moved to `x16`.
+fn adjust_sp(op_ri: Opcode, op_rr: Opcode, size: i32) -> Vec<InstructionData> {
+    if size == 0 {
+        return vec![];
+    }
+    if (0..4096).contains(&size) {
+        return vec![InstructionData {
+            opcode: op_ri,
+            operands: vec![
+                Operand::output(GR64::SP.into()),
+                Operand::input(GR64::SP.into()),
+                Operand::new(size.into()),
+            ],
+        }];
+    }
+    let mut insts = InstructionData::mov_imm(GR64::X16.into(), size as i64, true);
+    insts.push(InstructionData {
+        opcode: op_rr,
+        operands: vec![
+            Operand::output(GR64::SP.into()),
+            Operand::input(GR64::SP.into()),
+            Operand::input(GR64::X16.into()),
+        ],
+    });
+    insts
+}
@@ -1,44 +1,229 @@
-use vicis_core::ir::types::{Type, Types};
-
 use crate::{
     call_conv::CallConvKind,
+    lower::LoweringError,
     register::{Reg, RegUnit, RegisterClass, RegisterInfo},
 };
+use anyhow::Result;
+use vicis_core::ir::types::{self, Type, Types};
 
 pub struct RegInfo;
 
+pub enum GR32 {
+    W0,
+    W1,
+    W2,
+    W3,
+    W4,
+    W5,
+    W6,
+    W7,
+    W8,
+    W9,
+    W10,
+    W11,
+    W12,
+    W13,
+    W14,
+    W15,
+    W16,
+    W17,
+    W18,
+    W19,
+    W20,
+    W21,
+    W22,
+    W23,
+    W24,
+    W25,
+    W26,
+    W27,
+    W28,
+    W29,
+    W30,
+    WSP,
+    WZR,
+}
+
+pub enum GR64 {
+    X0,
+    X1,
+    X2,
+    X3,
+    X4,
+    X5,
+    X6,
+    X7,
+    X8,
+    X9,
+    X10,
+    X11,
+    X12,
+    X13,
+    X14,
+    X15,
+    X16,
+    X17,
+    X18,
+    X19,
+    X20,
+    X21,
+    X22,
+    X23,
+    X24,
+    X25,
+    X26,
+    X27,
+    X28,
+    X29, // Frame pointer
+    X30, // Link register
+    SP,
+    XZR,
+}
+
 pub enum RegClass {
-    GPR,
+    GR32,
+    GR64,
 }
 
-impl RegisterClass for RegClass {
-    fn for_type(_types: &Types, _ty: Type) -> Self {
-        todo!()
+impl From<GR32> for Reg {
+    fn from(r: GR32) -> Self {
+        Reg(RegClass::GR32 as u16, r as u16)
     }
+}
 
-    fn gpr_list(&self) -> Vec<Reg> {
-        todo!()
+impl From<GR64> for Reg {
+    fn from(r: GR64) -> Self {
+        Reg(RegClass::GR64 as u16, r as u16)
     }
+}
 
-    fn csr_list(&self) -> Vec<Reg> {
-        todo!()
+impl From<GR32> for RegUnit {
+    fn from(r: GR32) -> Self {
+        RegUnit(RegClass::GR64 as u16, r as u16)
     }
+}
 
-    fn apply_for(&self, _ru: RegUnit) -> Reg {
-        todo!()
+impl From<GR64> for RegUnit {
+    fn from(r: GR64) -> Self {
+        RegUnit(RegClass::GR64 as u16, r as u16)
     }
 }
 
+const ARG_REGS: [RegUnit; 8] = [
+    RegUnit(RegClass::GR64 as u16, GR64::X0 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X1 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X2 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X3 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X4 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X5 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X6 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X7 as u16),
+];
+
+const CSR: [RegUnit; 11] = [
+    RegUnit(RegClass::GR64 as u16, GR64::X19 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X20 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X21 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X22 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X23 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X24 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X25 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X26 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X27 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X28 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X29 as u16),
+];
+
 impl RegisterInfo for RegInfo {
-    fn arg_reg_list(_cc: &CallConvKind) -> &'static [RegUnit] {
-        todo!()
+    fn arg_reg_list(cc: &CallConvKind) -> &'static [RegUnit] {
+        match cc {
+            CallConvKind::AAPCS64 => &ARG_REGS,
+            CallConvKind::SystemV | CallConvKind::LP64D => &[],
+        }
+    }
+
+    fn to_reg_unit(r: Reg) -> RegUnit {
+        match r {
+            Reg(/*GR32*/ 0, x) => RegUnit(RegClass::GR64 as u16, x),
+            Reg(/*GR64*/ 1, x) => RegUnit(RegClass::GR64 as u16, x),
+            _ => panic!(),
+        }
+    }
+
+    fn is_csr(r: RegUnit) -> bool {
+        CSR.contains(&r)
+    }
+}
+
+impl RegisterClass for RegClass {
+    fn for_type(types: &Types, ty: Type) -> Result<Self> {
+        match ty {
+            types::I1 | types::I8 | types::I16 | types::I32 => Ok(RegClass::GR32),
+            types::I64 => Ok(RegClass::GR64),
+            _ if ty.is_pointer(types) => Ok(RegClass::GR64),
+            e => {
+                Err(LoweringError::Todo(format!("Unsupported type: {}", types.to_string(e))).into())
+            }
+        }
     }
 
-    fn to_reg_unit(_r: Reg) -> RegUnit {
-        todo!()
+    fn gpr_list(&self) -> Vec<Reg> {
+        match self {
+            RegClass::GR32 => vec![
+                GR32::W9,
+                GR32::W10,
+                GR32::W11,
+                GR32::W12,
+                GR32::W13,
+                GR32::W14,
+                GR32::W15,
+            ]
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+            RegClass::GR64 => vec![
+                GR64::X9,
+                GR64::X10,
+                GR64::X11,
+                GR64::X12,
+                GR64::X13,
+                GR64::X14,
+                GR64::X15,
+            ]
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+        }
+    }
+
+    // NOTE: The register allocator never uses the last register (frame pointer), and reserves
+    // the second to last one for reloading spilled values.
+    fn csr_list(&self) -> Vec<Reg> {
+        CSR.iter().map(|&r| self.apply_for(r)).collect()
     }
 
-    fn is_csr(_: RegUnit) -> bool {
-        todo!()
+    fn apply_for(&self, ru: RegUnit) -> Reg {
+        match self {
+            Self::GR32 => Reg(RegClass::GR32 as u16, ru.1),
+            Self::GR64 => Reg(RegClass::GR64 as u16, ru.1),
+        }
+    }
+}
+
+pub fn reg_to_str(r: &Reg) -> &'static str {
+    let gr32 = [
+        "w0", "w1", "w2", "w3", "w4", "w5", "w6", "w7", "w8", "w9", "w10", "w11", "w12", "w13",
+        "w14", "w15", "w16", "w17", "w18", "w19", "w20", "w21", "w22", "w23", "w24", "w25", "w26",
+        "w27", "w28", "w29", "w30", "wsp", "wzr",
+    ];
+    let gr64 = [
+        "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13",
+        "x14", "x15", "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26",
+        "x27", "x28", "x29", "x30", "sp", "xzr",
+    ];
+    match r {
+        Reg(0, i) => gr32[*i as usize],
+        Reg(1, i) => gr64[*i as usize],
+        e => todo!("{:?}", e),
     }
 }
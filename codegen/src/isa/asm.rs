@@ -0,0 +1,166 @@
+use crate::{isa::TargetIsa, module::Module};
+use std::{fmt, str};
+use vicis_core::ir::{
+    module::{global_variable::GlobalVariable, linkage::Linkage, name::Name},
+    types::Typed,
+    value::{ConstantArray, ConstantInt, ConstantStruct, ConstantValue},
+};
+
+/// Prints the global variables of `module` and the `.init_array` section for its constructor.
+pub fn print_global_variables<T: TargetIsa>(
+    f: &mut fmt::Formatter<'_>,
+    module: &Module<T>,
+) -> fmt::Result {
+    let mut ctor = None;
+
+    for gv in module.ir.global_variables().values() {
+        let is_extern = matches!(
+            gv.linkage,
+            Some(Linkage::External) | Some(Linkage::ExternalWeak)
+        );
+        if is_extern {
+            continue;
+        }
+
+        if gv.init.is_none() {
+            continue;
+        }
+
+        let init = gv.init.as_ref().unwrap();
+        match init {
+            ConstantValue::Array(arr) => {
+                if matches!(gv.name, Name::Name(ref name) if name == "llvm.global_ctors") {
+                    // TODO
+                    if let ConstantValue::Struct(ConstantStruct { elems, .. }) = &arr.elems[0] {
+                        if let ConstantValue::GlobalRef(name, _) = &elems[1] {
+                            ctor = module.ir.find_function_by_name(name.as_string());
+                        }
+                    }
+                    continue;
+                }
+
+                if !arr.is_string {
+                    continue;
+                }
+
+                let mut s = vec![];
+                for elem in &arr.elems {
+                    s.push(*elem.as_int().unwrap().as_i8() as u8)
+                }
+                let s = str::from_utf8(
+                    s.into_iter()
+                        .flat_map(::std::ascii::escape_default)
+                        .collect::<Vec<u8>>()
+                        .as_slice(),
+                )
+                .unwrap()
+                .to_string();
+                let s = s.trim_end_matches("\\x00"); // TODO
+                debug!(&s);
+                writeln!(f, "{}:", gv.name.as_string())?;
+                writeln!(f, "  .string \"{}\"", s)?;
+            }
+            _ => print_data_init(f, module, gv)?,
+        }
+    }
+
+    if let Some(ctor) = ctor {
+        let ctor = &module.ir.functions()[ctor];
+        writeln!(f, "  .section .init_array")?;
+        writeln!(f, "  .quad {}", ctor.name())?;
+    }
+
+    Ok(())
+}
+
+fn print_data_init<T: TargetIsa>(
+    f: &mut fmt::Formatter<'_>,
+    module: &Module<T>,
+    gvar: &GlobalVariable,
+) -> fmt::Result {
+    match gvar.init.as_ref().unwrap() {
+        ConstantValue::AggregateZero(ty) => {
+            let size = module.isa.data_layout().get_size_of(&module.types, *ty);
+            let align = module.isa.data_layout().get_align_of(&module.types, *ty);
+            writeln!(f, "  .comm {},{},{}", gvar.name.as_string(), size, align)
+        }
+        ConstantValue::Int(i) => {
+            if !gvar.linkage.map_or(false, |l| l.is_internal()) {
+                writeln!(f, "  .globl {}", gvar.name.as_string())?;
+            }
+            let size = module.isa.data_layout().get_size_of(&module.types, i.ty());
+            writeln!(f, "{}:", gvar.name.as_string())?;
+            writeln!(
+                f,
+                "  .{sz} {i}",
+                sz = match i {
+                    ConstantInt::Int1(_) => "byte",
+                    ConstantInt::Int8(_) => "byte",
+                    ConstantInt::Int16(_) => "short",
+                    ConstantInt::Int32(_) => "long",
+                    ConstantInt::Int64(_) => "quad",
+                }
+            )?;
+            writeln!(f, "  .size {}, {}", gvar.name.as_string(), size)
+        }
+        ConstantValue::Struct(ConstantStruct {
+            elems,
+            is_packed: true,
+            ..
+        }) => {
+            writeln!(f, "{}:", gvar.name.as_string())?;
+            for elem in elems {
+                print_data_init_sub(f, module, elem)?
+            }
+            Ok(())
+        }
+        e => todo!("Unsupported initializer: {:?}", e),
+    }
+}
+
+fn print_data_init_sub<T: TargetIsa>(
+    f: &mut fmt::Formatter<'_>,
+    module: &Module<T>,
+    init: &ConstantValue,
+) -> fmt::Result {
+    match init {
+        ConstantValue::AggregateZero(ty) => {
+            let size = module.isa.data_layout().get_size_of(&module.types, *ty);
+            writeln!(f, "  .zero {}", size)
+        }
+        ConstantValue::Int(i) => {
+            writeln!(
+                f,
+                "  .{sz} {i}",
+                sz = match i {
+                    ConstantInt::Int1(_) => "byte",
+                    ConstantInt::Int8(_) => "byte",
+                    ConstantInt::Int16(_) => "short",
+                    ConstantInt::Int32(_) => "long",
+                    ConstantInt::Int64(_) => "quad",
+                }
+            )
+        }
+        ConstantValue::Struct(ConstantStruct {
+            elems,
+            is_packed: true,
+            ..
+        }) => {
+            for elem in elems {
+                print_data_init_sub(f, module, elem)?
+            }
+            Ok(())
+        }
+        ConstantValue::Array(ConstantArray {
+            elems,
+            is_string: false,
+            ..
+        }) => {
+            for elem in elems {
+                print_data_init_sub(f, module, elem)?
+            }
+            Ok(())
+        }
+        e => todo!("Unsupported initializer: {:?}", e),
+    }
+}
@@ -1,14 +1,15 @@
 pub mod aarch64;
+pub mod asm;
+pub mod riscv64;
 pub mod x86_64;
 
 use crate::{
     call_conv::CallConvKind,
     function::instruction::TargetInst,
     lower,
-    module::Module,
+    pass::Pass,
     register::{RegisterClass, RegisterInfo},
 };
-use anyhow::Result;
 use vicis_core::ir::module::data_layout::DataLayout;
 
 pub trait TargetIsa: Clone {
@@ -17,7 +18,8 @@ pub trait TargetIsa: Clone {
     type RegInfo: RegisterInfo;
     type Lower: lower::Lower<Self>;
 
-    fn module_passes() -> Vec<for<'a, 'b> fn(&'b mut Module<'a, Self>) -> Result<()>>; // TODO: Implement a pass manager for machine modules
+    /// Returns the passes run on lowered modules by default.
+    fn module_passes() -> Vec<Pass<Self>>;
     fn default_call_conv() -> CallConvKind;
     fn data_layout(&self) -> &DataLayout;
 }
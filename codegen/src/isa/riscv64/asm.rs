@@ -0,0 +1,190 @@
+use crate::{
+    function::Function,
+    isa::{
+        asm::print_global_variables,
+        riscv64::{
+            instruction::{InstructionData, Opcode, Operand, OperandData},
+            register::reg_to_str,
+            Riscv64,
+        },
+    },
+    module::{DisplayAsm, Module},
+};
+use std::fmt::{Display, Formatter, Result};
+
+impl Display for DisplayAsm<'_, Riscv64> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        print(f, self.0)
+    }
+}
+
+pub fn print(f: &mut Formatter<'_>, module: &Module<Riscv64>) -> Result {
+    writeln!(f, "  .text")?;
+
+    for (i, (_, func)) in module.functions.iter().enumerate() {
+        print_function(f, func, i)?
+    }
+
+    print_global_variables(f, module)
+}
+
+pub fn print_function(
+    f: &mut Formatter<'_>,
+    function: &Function<Riscv64>,
+    fn_idx: usize,
+) -> Result {
+    if function.is_declaration {
+        return Ok(());
+    }
+
+    if let Some(name) = &function.ir.section {
+        writeln!(f, "  .section {}", name)?;
+    } else {
+        writeln!(f, "  .text")?;
+    }
+    if !function.ir.linkage.is_internal() {
+        writeln!(f, "  .globl {}", function.ir.name())?;
+    }
+    writeln!(f, "  .p2align 2")?;
+    writeln!(f, "{}:", function.ir.name())?;
+
+    for block in function.layout.block_iter() {
+        writeln!(f, ".LBL{}_{}:", fn_idx, block.index())?;
+        for inst in function.layout.inst_iter(block) {
+            let inst = function.data.inst_ref(inst);
+            print_inst(f, &inst.data, fn_idx)?;
+            writeln!(f)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn print_inst(f: &mut Formatter<'_>, inst: &InstructionData, fn_idx: usize) -> Result {
+    let ops = &inst.operands;
+    write!(f, "  {}", inst.opcode)?;
+    let mut i = 0;
+    let mut first = true;
+    while i < ops.len() {
+        if ops[i].implicit {
+            i += 1;
+            continue;
+        }
+        write!(f, "{}", if first { " " } else { ", " })?;
+        first = false;
+        if matches!(ops[i].data, OperandData::MemStart) {
+            write!(f, "{}", mem_op(&ops[i + 1..i + 4]))?;
+            i += 4;
+        } else {
+            write!(f, "{}", operand(&ops[i], fn_idx))?;
+            i += 1;
+        }
+    }
+    Ok(())
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::MV => "mv",
+                Self::LI => "li",
+                Self::LUI => "lui",
+                Self::ADD => "add",
+                Self::ADDW => "addw",
+                Self::ADDI => "addi",
+                Self::ADDIW => "addiw",
+                Self::SUB => "sub",
+                Self::SUBW => "subw",
+                Self::MUL => "mul",
+                Self::MULW => "mulw",
+                Self::SEXTW => "sext.w",
+                Self::ANDI => "andi",
+                Self::LBU => "lbu",
+                Self::LW => "lw",
+                Self::LD => "ld",
+                Self::SB => "sb",
+                Self::SW => "sw",
+                Self::SD => "sd",
+                Self::J => "j",
+                Self::BEQ => "beq",
+                Self::BNE => "bne",
+                Self::BLT => "blt",
+                Self::BGE => "bge",
+                Self::BLTU => "bltu",
+                Self::BGEU => "bgeu",
+                Self::CALL => "call",
+                Self::RET => "ret",
+                Self::FLW => "flw",
+                Self::FLD => "fld",
+                Self::FSW => "fsw",
+                Self::FSD => "fsd",
+                Self::FMVS => "fmv.s",
+                Self::FMVD => "fmv.d",
+                Self::FMVWX => "fmv.w.x",
+                Self::FMVDX => "fmv.d.x",
+                Self::FMVXW => "fmv.x.w",
+                Self::FMVXD => "fmv.x.d",
+                Self::FADDS => "fadd.s",
+                Self::FADDD => "fadd.d",
+                Self::FSUBS => "fsub.s",
+                Self::FSUBD => "fsub.d",
+                Self::FMULS => "fmul.s",
+                Self::FMULD => "fmul.d",
+                Self::FDIVS => "fdiv.s",
+                Self::FDIVD => "fdiv.d",
+                Self::FCVTSW => "fcvt.s.w",
+                Self::FCVTSL => "fcvt.s.l",
+                Self::FCVTDW => "fcvt.d.w",
+                Self::FCVTDL => "fcvt.d.l",
+                Self::FCVTWS => "fcvt.w.s",
+                Self::FCVTLS => "fcvt.l.s",
+                Self::FCVTWD => "fcvt.w.d",
+                Self::FCVTLD => "fcvt.l.d",
+                Self::FCVTSD => "fcvt.s.d",
+                Self::FCVTDS => "fcvt.d.s",
+                Self::FEQS => "feq.s",
+                Self::FEQD => "feq.d",
+                Self::FLTS => "flt.s",
+                Self::FLTD => "flt.d",
+                Self::FLES => "fle.s",
+                Self::FLED => "fle.d",
+                Self::Phi => "PHI",
+            }
+        )
+    }
+}
+
+fn operand(op: &Operand, fn_idx: usize) -> String {
+    match &op.data {
+        OperandData::Reg(r) => reg_to_str(r).to_owned(),
+        OperandData::VReg(r) => format!("%{}", r.0),
+        OperandData::Slot(slot) => format!("{:?}", slot),
+        OperandData::Int32(i) => format!("{}", i),
+        OperandData::Int64(i) => format!("{}", i),
+        OperandData::Block(block) => format!(".LBL{}_{}", fn_idx, block.index()),
+        OperandData::Label(name) => name.to_owned(),
+        OperandData::Hi(name) => format!("%hi({})", name),
+        OperandData::Lo(name) => format!("%lo({})", name),
+        OperandData::Rtz => "rtz".to_owned(),
+        OperandData::MemStart => "".to_owned(),
+        OperandData::None => "none".to_owned(),
+    }
+}
+
+fn mem_op(args: &[Operand]) -> String {
+    assert!(matches!(&args[0].data, &OperandData::None)); // assure slot is eliminated
+    match (&args[1].data, &args[2].data) {
+        (OperandData::None, OperandData::Reg(base)) => format!("0({})", reg_to_str(base)),
+        (OperandData::Int32(imm), OperandData::Reg(base)) => {
+            format!("{}({})", imm, reg_to_str(base))
+        }
+        (OperandData::Lo(name), OperandData::Reg(base)) => {
+            format!("%lo({})({})", name, reg_to_str(base))
+        }
+        (_, OperandData::VReg(base)) => format!("(%{})", base.0),
+        e => todo!("{:?}", e),
+    }
+}
@@ -0,0 +1,1252 @@
+pub mod load;
+pub mod store;
+
+use super::Riscv64;
+use crate::{
+    function::{instruction::Instruction as MachInstruction, slot::SlotId},
+    isa::riscv64::{
+        instruction::{InstructionData, Opcode, Operand as MO, OperandData},
+        register::{RegClass, RegInfo, FPR, GPR},
+    },
+    isa::TargetIsa,
+    lower::{Lower as LowerTrait, LoweringContext, LoweringError},
+    register::{Reg, RegisterClass, RegisterInfo, VReg},
+};
+use anyhow::Result;
+use load::lower_load;
+use store::lower_store;
+use vicis_core::ir::{
+    function::{
+        basic_block::BasicBlockId,
+        data::Data as IrData,
+        instruction::{
+            Alloca, Br, Call, Cast, CondBr, FCmp, FCmpCond, FloatBinary, GetElementPtr, ICmp,
+            ICmpCond, Instruction as IrInstruction, InstructionId, IntBinary, Load,
+            Opcode as IrOpcode, Operand, Phi, Ret, Store,
+        },
+        Parameter,
+    },
+    module::name::Name,
+    types::{self, CompoundType, FunctionType, Type},
+    value::{ConstantExpr, ConstantFloat, ConstantInt, ConstantValue, Value, ValueId},
+};
+
+#[derive(Clone, Copy, Default)]
+pub struct Lower {}
+
+impl Lower {
+    pub fn new() -> Self {
+        Lower::default()
+    }
+}
+
+impl LowerTrait<Riscv64> for Lower {
+    fn lower(ctx: &mut LoweringContext<Riscv64>, inst: &IrInstruction) -> Result<()> {
+        lower(ctx, inst)
+    }
+
+    fn copy_args_to_vregs(ctx: &mut LoweringContext<Riscv64>, params: &[Parameter]) -> Result<()> {
+        let args = RegInfo::arg_reg_list(&ctx.call_conv);
+        let float_args = RegInfo::float_arg_reg_list(&ctx.call_conv);
+        let mut gpr_used = 0;
+        let mut fpr_used = 0;
+        for (i, Parameter { name: _, ty, .. }) in params.iter().enumerate() {
+            let (reg, opcode) = if ty.is_floating_point() {
+                if fpr_used == float_args.len() {
+                    return Err(LoweringError::Todo("Arguments passed on the stack".into()).into());
+                }
+                let reg = float_args[fpr_used].apply(&RegClass::FPR);
+                fpr_used += 1;
+                (reg, fp_copy_opcode(*ty))
+            } else {
+                if gpr_used == args.len() {
+                    return Err(LoweringError::Todo("Arguments passed on the stack".into()).into());
+                }
+                let reg = args[gpr_used].apply(&RegClass::for_type(ctx.types, *ty)?);
+                gpr_used += 1;
+                (reg, Opcode::MV)
+            };
+            debug!(reg);
+            // Copy reg to new vreg
+            let output = ctx.mach_data.vregs.add_vreg_data(*ty);
+            ctx.inst_seq.push(MachInstruction::new(
+                InstructionData {
+                    opcode,
+                    operands: vec![MO::output(output.into()), MO::input(reg.into())],
+                },
+                ctx.block_map[&ctx.cur_block],
+            ));
+            ctx.arg_idx_to_vreg.insert(i, output);
+        }
+        Ok(())
+    }
+}
+
+fn lower(ctx: &mut LoweringContext<Riscv64>, inst: &IrInstruction) -> Result<()> {
+    match inst.operand {
+        Operand::Alloca(Alloca {
+            ref tys,
+            ref num_elements,
+            align,
+        }) => lower_alloca(ctx, inst.id.unwrap(), tys, num_elements, align),
+        Operand::Phi(Phi {
+            ty,
+            ref args,
+            ref blocks,
+        }) => lower_phi(ctx, inst.id.unwrap(), ty, args, blocks),
+        Operand::Load(Load {
+            ref tys,
+            addr,
+            align,
+        }) => lower_load(ctx, inst.id.unwrap(), tys, addr, align),
+        Operand::Store(Store {
+            ref tys,
+            ref args,
+            align,
+        }) => lower_store(ctx, tys, args, align),
+        Operand::IntBinary(IntBinary { ty, ref args, .. }) => {
+            lower_bin(ctx, inst.id.unwrap(), inst.opcode, ty, args)
+        }
+        Operand::FloatBinary(FloatBinary { ty, ref args, .. }) => {
+            lower_float_bin(ctx, inst.id.unwrap(), inst.opcode, ty, args)
+        }
+        Operand::Cast(Cast { ref tys, arg }) if inst.opcode == IrOpcode::Sext => {
+            lower_sext(ctx, inst.id.unwrap(), tys, arg)
+        }
+        Operand::Cast(Cast { ref tys, arg }) if inst.opcode == IrOpcode::Bitcast => {
+            lower_bitcast(ctx, inst.id.unwrap(), tys, arg)
+        }
+        Operand::Cast(Cast { ref tys, arg }) if inst.opcode == IrOpcode::Zext => {
+            lower_zext(ctx, inst.id.unwrap(), tys, arg)
+        }
+        Operand::Cast(Cast { ref tys, arg })
+            if matches!(
+                inst.opcode,
+                IrOpcode::SIToFP | IrOpcode::FPToSI | IrOpcode::FPExt | IrOpcode::FPTrunc
+            ) =>
+        {
+            lower_float_cast(ctx, inst.id.unwrap(), inst.opcode, tys, arg)
+        }
+        Operand::GetElementPtr(ref gep) => lower_gep(ctx, inst.id.unwrap(), gep),
+        Operand::Br(Br { block }) => lower_br(ctx, block),
+        Operand::CondBr(CondBr { arg, blocks }) => lower_condbr(ctx, arg, blocks),
+        Operand::Call(Call {
+            ref args, ref tys, ..
+        }) => lower_call(ctx, inst.id.unwrap(), tys, args),
+        Operand::Ret(Ret { val: None, .. }) => lower_return(ctx, None),
+        Operand::Ret(Ret { val: Some(val), ty }) => lower_return(ctx, Some((ty, val))),
+        ref e => Err(LoweringError::Todo(format!("Unsupported instruction: {:?}", e)).into()),
+    }
+}
+
+fn lower_alloca(
+    ctx: &mut LoweringContext<Riscv64>,
+    id: InstructionId,
+    tys: &[Type],
+    _num_elements: &ConstantValue,
+    _align: u32,
+) -> Result<()> {
+    if let Some(slot_id) = ctx.inst_id_to_slot_id.get(&id).copied() {
+        let ty = ctx.types.base_mut().pointer(tys[0]);
+        let output = new_empty_inst_output(ctx, ty, id);
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode: Opcode::ADDI,
+                operands: vec![
+                    MO::output(output.into()),
+                    MO::new(OperandData::Slot(slot_id)),
+                    MO::new(0.into()),
+                ],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+        return Ok(());
+    }
+    let dl = ctx.isa.data_layout();
+    let sz = dl.get_size_of(ctx.types, tys[0]) as u32;
+    let align = dl.get_align_of(ctx.types, tys[0]) as u32;
+    let slot_id = ctx.slots.add_slot(tys[0], sz, align);
+    ctx.inst_id_to_slot_id.insert(id, slot_id);
+    Ok(())
+}
+
+fn lower_phi(
+    ctx: &mut LoweringContext<Riscv64>,
+    id: InstructionId,
+    ty: Type,
+    args: &[ValueId],
+    blocks: &[BasicBlockId],
+) -> Result<()> {
+    let output = new_empty_inst_output(ctx, ty, id);
+    let mut operands = vec![MO::output(output.into())];
+    for (arg, block) in args.iter().zip(blocks.iter()) {
+        operands.push(MO::input(get_operand_for_val(ctx, ty, *arg)?));
+        operands.push(MO::new(OperandData::Block(ctx.block_map[block])))
+    }
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::Phi,
+            operands,
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    Ok(())
+}
+
+fn lower_bin(
+    ctx: &mut LoweringContext<Riscv64>,
+    id: InstructionId,
+    op: IrOpcode,
+    ty: Type,
+    args: &[ValueId],
+) -> Result<()> {
+    let is_64 = is_64(ctx, ty);
+    let lhs = get_vreg_for_val(ctx, ty, args[0])?;
+    let rhs = get_operand_for_val(ctx, ty, args[1])?;
+    let output = new_empty_inst_output(ctx, ty, id);
+
+    // `addi` takes a 12-bit signed immediate, and `sub` is done by adding the negated one.
+    let imm = rhs.sext_as_i64().and_then(|i| match op {
+        IrOpcode::Add if (-2048..2048).contains(&i) => Some(i),
+        IrOpcode::Sub if (-2047..2049).contains(&i) => Some(-i),
+        _ => None,
+    });
+    let data = match imm {
+        Some(imm) => InstructionData {
+            opcode: if is_64 { Opcode::ADDI } else { Opcode::ADDIW },
+            operands: vec![
+                MO::output(output.into()),
+                MO::input(lhs.into()),
+                MO::new((imm as i32).into()),
+            ],
+        },
+        None => {
+            let rhs = get_vreg_for_val(ctx, ty, args[1])?;
+            InstructionData {
+                opcode: match (op, is_64) {
+                    (IrOpcode::Add, false) => Opcode::ADDW,
+                    (IrOpcode::Add, true) => Opcode::ADD,
+                    (IrOpcode::Sub, false) => Opcode::SUBW,
+                    (IrOpcode::Sub, true) => Opcode::SUB,
+                    (IrOpcode::Mul, false) => Opcode::MULW,
+                    (IrOpcode::Mul, true) => Opcode::MUL,
+                    (op, _) => {
+                        return Err(
+                            LoweringError::Todo(format!("Unsupported opcode: {:?}", op)).into()
+                        )
+                    }
+                },
+                operands: vec![
+                    MO::output(output.into()),
+                    MO::input(lhs.into()),
+                    MO::input(rhs.into()),
+                ],
+            }
+        }
+    };
+
+    ctx.inst_seq
+        .push(MachInstruction::new(data, ctx.block_map[&ctx.cur_block]));
+
+    Ok(())
+}
+
+fn lower_float_bin(
+    ctx: &mut LoweringContext<Riscv64>,
+    id: InstructionId,
+    op: IrOpcode,
+    ty: Type,
+    args: &[ValueId],
+) -> Result<()> {
+    let lhs = get_vreg_for_val(ctx, ty, args[0])?;
+    let rhs = get_vreg_for_val(ctx, ty, args[1])?;
+    let output = new_empty_inst_output(ctx, ty, id);
+
+    let opcode = match (op, ty.is_float()) {
+        (IrOpcode::FAdd, true) => Opcode::FADDS,
+        (IrOpcode::FAdd, false) => Opcode::FADDD,
+        (IrOpcode::FSub, true) => Opcode::FSUBS,
+        (IrOpcode::FSub, false) => Opcode::FSUBD,
+        (IrOpcode::FMul, true) => Opcode::FMULS,
+        (IrOpcode::FMul, false) => Opcode::FMULD,
+        (IrOpcode::FDiv, true) => Opcode::FDIVS,
+        (IrOpcode::FDiv, false) => Opcode::FDIVD,
+        (op, _) => return Err(LoweringError::Todo(format!("Unsupported opcode: {:?}", op)).into()),
+    };
+
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode,
+            operands: vec![
+                MO::output(output.into()),
+                MO::input(lhs.into()),
+                MO::input(rhs.into()),
+            ],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+
+    Ok(())
+}
+
+fn lower_float_cast(
+    ctx: &mut LoweringContext<Riscv64>,
+    id: InstructionId,
+    op: IrOpcode,
+    tys: &[Type; 2],
+    arg: ValueId,
+) -> Result<()> {
+    let [from, to] = *tys;
+    let opcode = match (op, from, to) {
+        (IrOpcode::SIToFP, types::I32, types::FLOAT) => Opcode::FCVTSW,
+        (IrOpcode::SIToFP, types::I64, types::FLOAT) => Opcode::FCVTSL,
+        (IrOpcode::SIToFP, types::I32, types::DOUBLE) => Opcode::FCVTDW,
+        (IrOpcode::SIToFP, types::I64, types::DOUBLE) => Opcode::FCVTDL,
+        (IrOpcode::FPToSI, types::FLOAT, types::I32) => Opcode::FCVTWS,
+        (IrOpcode::FPToSI, types::FLOAT, types::I64) => Opcode::FCVTLS,
+        (IrOpcode::FPToSI, types::DOUBLE, types::I32) => Opcode::FCVTWD,
+        (IrOpcode::FPToSI, types::DOUBLE, types::I64) => Opcode::FCVTLD,
+        (IrOpcode::FPExt, types::FLOAT, types::DOUBLE) => Opcode::FCVTDS,
+        (IrOpcode::FPTrunc, types::DOUBLE, types::FLOAT) => Opcode::FCVTSD,
+        (op, from, to) => {
+            return Err(LoweringError::Todo(format!(
+                "Unsupported cast: {:?} from {} to {}",
+                op,
+                ctx.types.to_string(from),
+                ctx.types.to_string(to)
+            ))
+            .into())
+        }
+    };
+
+    let src = get_vreg_for_val(ctx, from, arg)?;
+    let output = new_empty_inst_output(ctx, to, id);
+    let mut operands = vec![MO::output(output.into()), MO::input(src.into())];
+    // `fptosi` truncates, while `fcvt` rounds as `frm` says by default.
+    if op == IrOpcode::FPToSI {
+        operands.push(MO::new(OperandData::Rtz));
+    }
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData { opcode, operands },
+        ctx.block_map[&ctx.cur_block],
+    ));
+
+    Ok(())
+}
+
+fn lower_sext(
+    ctx: &mut LoweringContext<Riscv64>,
+    self_id: InstructionId,
+    tys: &[Type; 2],
+    arg: ValueId,
+) -> Result<()> {
+    let from = tys[0];
+    let to = tys[1];
+    if !from.is_i32() || !to.is_i64() {
+        return Err(LoweringError::Todo(format!(
+            "Unsupported sext: {} to {}",
+            ctx.types.to_string(from),
+            ctx.types.to_string(to)
+        ))
+        .into());
+    }
+
+    let val = get_vreg_for_val(ctx, from, arg)?;
+    let output = new_empty_inst_output(ctx, to, self_id);
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::SEXTW,
+            operands: vec![MO::output(output.into()), MO::input(val.into())],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    Ok(())
+}
+
+fn lower_zext(
+    ctx: &mut LoweringContext<Riscv64>,
+    self_id: InstructionId,
+    tys: &[Type; 2],
+    arg: ValueId,
+) -> Result<()> {
+    let from = tys[0];
+    let to = tys[1];
+    if !from.is_i8() || !to.is_i32() {
+        return Err(LoweringError::Todo(format!(
+            "Unsupported zext: {} to {}",
+            ctx.types.to_string(from),
+            ctx.types.to_string(to)
+        ))
+        .into());
+    }
+
+    let val = get_vreg_for_val(ctx, from, arg)?;
+    let output = new_empty_inst_output(ctx, to, self_id);
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::ANDI,
+            operands: vec![
+                MO::output(output.into()),
+                MO::input(val.into()),
+                MO::new(0xff.into()),
+            ],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    Ok(())
+}
+
+fn lower_bitcast(
+    ctx: &mut LoweringContext<Riscv64>,
+    self_id: InstructionId,
+    tys: &[Type; 2],
+    arg: ValueId,
+) -> Result<()> {
+    let from = tys[0];
+    let to = tys[1];
+    let from_sz = ctx.isa.data_layout().get_size_of(ctx.types, from);
+    let to_sz = ctx.isa.data_layout().get_size_of(ctx.types, to);
+    if from_sz != to_sz || from_sz != 8 {
+        return Err(LoweringError::Todo(format!(
+            "Unsupported bitcast: {} to {}",
+            ctx.types.to_string(from),
+            ctx.types.to_string(to)
+        ))
+        .into());
+    }
+    let opcode = match (from.is_floating_point(), to.is_floating_point()) {
+        (false, false) => Opcode::MV,
+        (false, true) => Opcode::FMVDX,
+        (true, false) => Opcode::FMVXD,
+        (true, true) => Opcode::FMVD,
+    };
+    let arg = get_vreg_for_val(ctx, from, arg)?;
+    let output = new_empty_inst_output(ctx, to, self_id);
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode,
+            operands: vec![MO::output(output.into()), MO::input(arg.into())],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    Ok(())
+}
+
+fn lower_gep(
+    ctx: &mut LoweringContext<Riscv64>,
+    self_id: InstructionId,
+    gep: &GetElementPtr,
+) -> Result<()> {
+    let slot = match &ctx.ir_data.values[gep.args[0]] {
+        Value::Instruction(id) => ctx.inst_id_to_slot_id.get(id).copied(),
+        _ => None,
+    };
+    let (offset, indices) = gep_offsets(ctx, gep)?;
+
+    let ty = ctx.ir_data.inst_ref(self_id).ty;
+    let output = new_empty_inst_output(ctx, ty, self_id);
+
+    if let (Some(slot), true, true) = (slot, indices.is_empty(), (0..2048).contains(&offset)) {
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode: Opcode::ADDI,
+                operands: vec![
+                    MO::output(output.into()),
+                    MO::new(OperandData::Slot(slot)),
+                    MO::new((offset as i32).into()),
+                ],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+        return Ok(());
+    }
+
+    let mut addr = get_vreg_for_val(ctx, gep.tys[1], gep.args[0])?;
+
+    for (mul, idx, idx_ty) in indices {
+        let mut idx = *idx.as_vreg();
+        if !idx_ty.is_i64() {
+            let sext = ctx.mach_data.vregs.add_vreg_data(types::I64);
+            ctx.inst_seq.push(MachInstruction::new(
+                InstructionData {
+                    opcode: Opcode::SEXTW,
+                    operands: vec![MO::output(sext.into()), MO::input(idx.into())],
+                },
+                ctx.block_map[&ctx.cur_block],
+            ));
+            idx = sext;
+        }
+        let mul = materialize_imm(ctx, types::I64, mul);
+        let mul_output = ctx.mach_data.vregs.add_vreg_data(types::I64);
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode: Opcode::MUL,
+                operands: vec![
+                    MO::output(mul_output.into()),
+                    MO::input(idx.into()),
+                    MO::input(mul.into()),
+                ],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+        let add_output = ctx.mach_data.vregs.add_vreg_data(ty);
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode: Opcode::ADD,
+                operands: vec![
+                    MO::output(add_output.into()),
+                    MO::input(addr.into()),
+                    MO::input(mul_output.into()),
+                ],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+        addr = add_output;
+    }
+
+    let data = if offset == 0 {
+        InstructionData {
+            opcode: Opcode::MV,
+            operands: vec![MO::output(output.into()), MO::input(addr.into())],
+        }
+    } else if (-2048..2048).contains(&offset) {
+        InstructionData {
+            opcode: Opcode::ADDI,
+            operands: vec![
+                MO::output(output.into()),
+                MO::input(addr.into()),
+                MO::new((offset as i32).into()),
+            ],
+        }
+    } else {
+        let offset = materialize_imm(ctx, types::I64, offset);
+        InstructionData {
+            opcode: Opcode::ADD,
+            operands: vec![
+                MO::output(output.into()),
+                MO::input(addr.into()),
+                MO::input(offset.into()),
+            ],
+        }
+    };
+    ctx.inst_seq
+        .push(MachInstruction::new(data, ctx.block_map[&ctx.cur_block]));
+
+    Ok(())
+}
+
+/// `(mul, idx, idx type)`
+type ScaledIndex = (i64, OperandData, Type);
+
+/// Splits the address computed by `gep` into `base + offset + mul.0*idx.0 + mul.1*idx.1 + ...`,
+/// and returns `offset` and `(mul, idx, idx type)`s.
+fn gep_offsets(
+    ctx: &mut LoweringContext<Riscv64>,
+    gep: &GetElementPtr,
+) -> Result<(i64, Vec<ScaledIndex>)> {
+    let mut offset = 0i64;
+    let mut indices = vec![];
+    let mut cur_ty = gep.tys[1];
+    for (i, (&arg, &arg_ty)) in gep.args[1..].iter().zip(gep.tys[2..].iter()).enumerate() {
+        let idx = get_operand_for_val(ctx, arg_ty, arg)?;
+        if cur_ty.is_struct(ctx.types) {
+            let layout = ctx
+                .isa
+                .data_layout
+                .new_struct_layout_for(ctx.types, cur_ty)
+                .unwrap();
+            let idx = idx.sext_as_i64().unwrap() as usize;
+            offset += layout.get_elem_offset(idx).unwrap() as i64;
+            cur_ty = ctx.types.base().element_at(cur_ty, idx).unwrap();
+        } else {
+            // NOTE: The first index steps over the source element type (`tys[0]`) since the base
+            // pointer may be opaque.
+            cur_ty = if i == 0 {
+                gep.tys[0]
+            } else {
+                ctx.types.get_element(cur_ty).unwrap()
+            };
+            let sz = ctx.isa.data_layout.get_size_of(ctx.types, cur_ty) as i64;
+            match idx.sext_as_i64() {
+                Some(idx) => offset += sz * idx,
+                None => indices.push((sz, idx, arg_ty)),
+            }
+        }
+    }
+    Ok((offset, indices))
+}
+
+/// Returns the memory operands (`MemStart` and the following three) that address `addr`.
+fn mem_operands(
+    ctx: &mut LoweringContext<Riscv64>,
+    ptr_ty: Type,
+    addr: ValueId,
+) -> Result<Vec<MO>> {
+    let mut slot = OperandData::None;
+    let mut imm = OperandData::None;
+    let mut base = OperandData::None;
+
+    match ctx.ir_data.values[addr] {
+        Value::Instruction(id) if ctx.inst_id_to_slot_id.contains_key(&id) => {
+            slot = OperandData::Slot(ctx.inst_id_to_slot_id[&id]);
+        }
+        Value::Constant(ConstantValue::GlobalRef(ref name, ty)) => {
+            let name = name.to_string().unwrap().to_owned();
+            let hi = ctx.mach_data.vregs.add_vreg_data(ty);
+            ctx.inst_seq.push(MachInstruction::new(
+                InstructionData {
+                    opcode: Opcode::LUI,
+                    operands: vec![
+                        MO::output(hi.into()),
+                        MO::new(OperandData::Hi(name.clone())),
+                    ],
+                },
+                ctx.block_map[&ctx.cur_block],
+            ));
+            imm = OperandData::Lo(name);
+            base = hi.into();
+        }
+        Value::Instruction(id) => match fold_gep_into_slot(ctx, id)? {
+            Some((s, offset)) => {
+                slot = OperandData::Slot(s);
+                imm = OperandData::Int32(offset as i32);
+            }
+            None => base = get_inst_output(ctx, ptr_ty, id)?.into(),
+        },
+        _ => base = get_vreg_for_val(ctx, ptr_ty, addr)?.into(),
+    }
+
+    Ok(vec![
+        MO::new(OperandData::MemStart),
+        MO::new(slot),
+        MO::new(imm),
+        MO::input(base),
+    ])
+}
+
+/// If `id` is a `getelementptr` only used by the current instruction that points into a stack
+/// slot at a constant offset, returns the slot and the offset.
+fn fold_gep_into_slot(
+    ctx: &mut LoweringContext<Riscv64>,
+    id: InstructionId,
+) -> Result<Option<(SlotId, i64)>> {
+    let inst = ctx.ir_data.inst_ref(id);
+    let gep = match &inst.operand {
+        Operand::GetElementPtr(gep)
+            if inst.parent == ctx.cur_block
+                && !ctx.inst_id_to_vreg.contains_key(&id)
+                && ctx.ir_data.users_of(id).len() == 1 =>
+        {
+            gep
+        }
+        _ => return Ok(None),
+    };
+    let slot = match &ctx.ir_data.values[gep.args[0]] {
+        Value::Instruction(base) => match ctx.inst_id_to_slot_id.get(base) {
+            Some(slot) => *slot,
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    let (offset, indices) = gep_offsets(ctx, gep)?;
+    if !indices.is_empty() || !(0..2048).contains(&offset) {
+        return Ok(None);
+    }
+    ctx.mark_as_merged(id);
+    Ok(Some((slot, offset)))
+}
+
+fn lower_br(ctx: &mut LoweringContext<Riscv64>, block: BasicBlockId) -> Result<()> {
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::J,
+            operands: vec![MO::new(OperandData::Block(ctx.block_map[&block]))],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    Ok(())
+}
+
+fn lower_condbr(
+    ctx: &mut LoweringContext<Riscv64>,
+    arg: ValueId,
+    blocks: [BasicBlockId; 2],
+) -> Result<()> {
+    fn is_icmp<'a>(
+        data: &'a IrData,
+        val: &Value,
+    ) -> Option<(InstructionId, &'a Type, &'a [ValueId; 2], &'a ICmpCond)> {
+        match val {
+            Value::Instruction(id) => {
+                let inst = data.inst_ref(*id);
+                match &inst.operand {
+                    Operand::ICmp(ICmp { ty, args, cond }) => Some((*id, ty, args, cond)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+    fn is_fcmp<'a>(
+        data: &'a IrData,
+        val: &Value,
+    ) -> Option<(InstructionId, &'a Type, &'a [ValueId; 2], &'a FCmpCond)> {
+        match val {
+            Value::Instruction(id) => {
+                let inst = data.inst_ref(*id);
+                match &inst.operand {
+                    Operand::FCmp(FCmp { ty, args, cond, .. }) => Some((*id, ty, args, cond)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+    fn is_trunc_from_i8(data: &IrData, val: &Value) -> Option<(InstructionId, ValueId)> {
+        match val {
+            Value::Instruction(id) => {
+                let inst = data.inst_ref(*id);
+                match &inst.operand {
+                    Operand::Cast(Cast {
+                        arg,
+                        tys: [from, to],
+                    }) if inst.opcode == IrOpcode::Trunc && from.is_i8() && to.is_i1() => {
+                        Some((*id, *arg))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    let arg = ctx.ir_data.value_ref(arg);
+
+    let (cond, lhs, rhs) = if let Some((icmp, ty, args, cond)) = is_icmp(ctx.ir_data, arg) {
+        ctx.mark_as_merged(icmp);
+        let lhs = get_reg_or_zero_for_val(ctx, *ty, args[0])?;
+        let rhs = get_reg_or_zero_for_val(ctx, *ty, args[1])?;
+        // Branches compare two registers. The missing conditions are done by swapping them.
+        match cond {
+            ICmpCond::Eq => (Opcode::BEQ, lhs, rhs),
+            ICmpCond::Ne => (Opcode::BNE, lhs, rhs),
+            ICmpCond::Slt => (Opcode::BLT, lhs, rhs),
+            ICmpCond::Sge => (Opcode::BGE, lhs, rhs),
+            ICmpCond::Sgt => (Opcode::BLT, rhs, lhs),
+            ICmpCond::Sle => (Opcode::BGE, rhs, lhs),
+            ICmpCond::Ult => (Opcode::BLTU, lhs, rhs),
+            ICmpCond::Uge => (Opcode::BGEU, lhs, rhs),
+            ICmpCond::Ugt => (Opcode::BLTU, rhs, lhs),
+            ICmpCond::Ule => (Opcode::BGEU, rhs, lhs),
+        }
+    } else if let Some((fcmp, ty, args, cond)) = is_fcmp(ctx.ir_data, arg) {
+        ctx.mark_as_merged(fcmp);
+        lower_fcmp(ctx, *ty, args, *cond)?
+    } else if let Some((trunc, src)) = is_trunc_from_i8(ctx.ir_data, arg) {
+        ctx.mark_as_merged(trunc);
+        let src = get_vreg_for_val(ctx, types::I8, src)?;
+        let bit = ctx.mach_data.vregs.add_vreg_data(types::I8);
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode: Opcode::ANDI,
+                operands: vec![
+                    MO::output(bit.into()),
+                    MO::input(src.into()),
+                    MO::new(1.into()),
+                ],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+        (
+            Opcode::BNE,
+            MO::input(bit.into()),
+            MO::new(GPR::Zero.into()),
+        )
+    } else {
+        return Err(LoweringError::Todo("Unsupported conditional br pattern".into()).into());
+    };
+
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: cond,
+            operands: vec![
+                lhs,
+                rhs,
+                MO::new(OperandData::Block(ctx.block_map[&blocks[0]])),
+            ],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::J,
+            operands: vec![MO::new(OperandData::Block(ctx.block_map[&blocks[1]]))],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+
+    Ok(())
+}
+
+/// Sets a register to the result of `feq`, `flt` or `fle`, and returns the branch that tests it.
+/// These are false if either operand is NaN, so unordered conditions branch if the opposite
+/// ordered one is false.
+fn lower_fcmp(
+    ctx: &mut LoweringContext<Riscv64>,
+    ty: Type,
+    args: &[ValueId; 2],
+    cond: FCmpCond,
+) -> Result<(Opcode, MO, MO)> {
+    let (feq, flt, fle) = if ty.is_float() {
+        (Opcode::FEQS, Opcode::FLTS, Opcode::FLES)
+    } else {
+        (Opcode::FEQD, Opcode::FLTD, Opcode::FLED)
+    };
+    let (opcode, swap, branch) = match cond {
+        FCmpCond::False => {
+            return Ok((
+                Opcode::BNE,
+                MO::new(GPR::Zero.into()),
+                MO::new(GPR::Zero.into()),
+            ))
+        }
+        FCmpCond::True => {
+            return Ok((
+                Opcode::BEQ,
+                MO::new(GPR::Zero.into()),
+                MO::new(GPR::Zero.into()),
+            ))
+        }
+        FCmpCond::Oeq => (feq, false, Opcode::BNE),
+        FCmpCond::Ogt => (flt, true, Opcode::BNE),
+        FCmpCond::Oge => (fle, true, Opcode::BNE),
+        FCmpCond::Olt => (flt, false, Opcode::BNE),
+        FCmpCond::Ole => (fle, false, Opcode::BNE),
+        FCmpCond::Une => (feq, false, Opcode::BEQ),
+        FCmpCond::Ugt => (fle, false, Opcode::BEQ),
+        FCmpCond::Uge => (flt, false, Opcode::BEQ),
+        FCmpCond::Ult => (fle, true, Opcode::BEQ),
+        FCmpCond::Ule => (flt, true, Opcode::BEQ),
+        cond => {
+            return Err(
+                LoweringError::Todo(format!("Unsupported fcmp condition: {:?}", cond)).into(),
+            )
+        }
+    };
+
+    let mut lhs = get_vreg_for_val(ctx, ty, args[0])?;
+    let mut rhs = get_vreg_for_val(ctx, ty, args[1])?;
+    if swap {
+        std::mem::swap(&mut lhs, &mut rhs)
+    }
+    let flag = ctx.mach_data.vregs.add_vreg_data(types::I8);
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode,
+            operands: vec![
+                MO::output(flag.into()),
+                MO::input(lhs.into()),
+                MO::input(rhs.into()),
+            ],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+
+    Ok((branch, MO::input(flag.into()), MO::new(GPR::Zero.into())))
+}
+
+/// Returns the operand for `val` to be compared by a branch, which is `zero` if `val` is 0.
+fn get_reg_or_zero_for_val(
+    ctx: &mut LoweringContext<Riscv64>,
+    ty: Type,
+    val: ValueId,
+) -> Result<MO> {
+    let is_zero = match ctx.ir_data.value_ref(val) {
+        Value::Constant(ConstantValue::Null(_)) => true,
+        Value::Constant(ConstantValue::Int(i)) => i.is_zero(),
+        _ => false,
+    };
+    if is_zero {
+        // `zero` is a constant, so it is not tracked as an input.
+        return Ok(MO::new(GPR::Zero.into()));
+    }
+    Ok(MO::input(get_vreg_for_val(ctx, ty, val)?.into()))
+}
+
+fn lower_call(
+    ctx: &mut LoweringContext<Riscv64>,
+    id: InstructionId,
+    tys: &[Type],
+    args: &[ValueId],
+) -> Result<()> {
+    let callee = args[0];
+    let name = match &ctx.ir_data.values[callee] {
+        Value::Constant(ConstantValue::GlobalRef(Name::Name(name), _)) => name.clone(),
+        callee => {
+            return Err(LoweringError::Todo(format!("Unsupported callee: {:?}", callee)).into())
+        }
+    };
+    log::debug!("call name: {}", name);
+
+    if name == "llvm.memcpy.p0i8.p0i8.i64" {
+        // let dst    = args[1];
+        // let src    = args[2];
+        // let len    = args[3];
+        // let is_volatile = args[4]; // TODO
+        let arg_regs = pass_args_to_regs(ctx, &tys[1..4], &args[1..4], 3)?;
+        push_call(ctx, "memcpy".to_owned(), arg_regs, GPR::A0.into());
+        return Ok(());
+    }
+
+    // Calls to variadic functions have the function type as `tys[0]`.
+    let (result_ty, fixed) = match ctx.types.get(tys[0]).as_deref() {
+        Some(CompoundType::Function(FunctionType { ret, params, .. })) => (*ret, params.len()),
+        _ => (tys[0], args.len() - 1),
+    };
+    let output = new_empty_inst_output(ctx, result_ty, id);
+
+    let arg_regs = pass_args_to_regs(ctx, &tys[1..], &args[1..], fixed)?;
+    let (result_reg, opcode): (Reg, _) = if result_ty.is_floating_point() {
+        (FPR::FA0.into(), fp_copy_opcode(result_ty))
+    } else {
+        (GPR::A0.into(), Opcode::MV)
+    };
+    push_call(ctx, name, arg_regs, result_reg);
+
+    if !ctx.ir_data.users_of(id).is_empty() {
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode,
+                operands: vec![MO::output(output.into()), MO::input(result_reg.into())],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+    }
+
+    Ok(())
+}
+
+fn push_call(ctx: &mut LoweringContext<Riscv64>, name: String, args: Vec<Reg>, result: Reg) {
+    let mut operands = vec![MO::implicit_output(result.into())];
+    operands.extend(args.into_iter().map(|r| MO::implicit_input(r.into())));
+    operands.push(MO::new(OperandData::Label(name)));
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::CALL,
+            operands,
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+}
+
+/// Moves the arguments into the argument registers, and returns the registers used. The
+/// arguments after the first `fixed` ones are variadic, and passed in integer registers even if
+/// they are floating-point values.
+fn pass_args_to_regs(
+    ctx: &mut LoweringContext<Riscv64>,
+    tys: &[Type],
+    args: &[ValueId],
+    fixed: usize,
+) -> Result<Vec<Reg>> {
+    let gpru = RegInfo::arg_reg_list(&ctx.call_conv);
+    let fpru = RegInfo::float_arg_reg_list(&ctx.call_conv);
+    let mut gpr_used = 0;
+    let mut fpr_used = 0;
+
+    let mut regs = vec![];
+    for (i, (&ty, &arg)) in tys.iter().zip(args.iter()).enumerate() {
+        let is_fpr = ty.is_floating_point() && i < fixed;
+        let (used, available) = if is_fpr {
+            (fpr_used, fpru.len())
+        } else {
+            (gpr_used, gpru.len())
+        };
+        if used == available {
+            return Err(LoweringError::Todo("Arguments passed on the stack".into()).into());
+        }
+
+        if ty.is_floating_point() {
+            let arg = get_vreg_for_val(ctx, ty, arg)?;
+            let (out, opcode) = if is_fpr {
+                fpr_used += 1;
+                (fpru[used].apply(&RegClass::FPR), fp_copy_opcode(ty))
+            } else {
+                gpr_used += 1;
+                let opcode = if ty.is_float() {
+                    Opcode::FMVXW
+                } else {
+                    Opcode::FMVXD
+                };
+                (gpru[used].apply(&RegClass::GPR), opcode)
+            };
+            ctx.inst_seq.push(MachInstruction::new(
+                InstructionData {
+                    opcode,
+                    operands: vec![MO::output(out.into()), MO::input(arg.into())],
+                },
+                ctx.block_map[&ctx.cur_block],
+            ));
+            regs.push(out);
+            continue;
+        }
+
+        let arg = get_operand_for_val(ctx, ty, arg)?;
+        let out = gpru[used].apply(&RegClass::for_type(ctx.types, ty)?);
+        gpr_used += 1;
+        match arg {
+            OperandData::Int32(_) | OperandData::Int64(_) => {
+                let imm = arg.sext_as_i64().unwrap();
+                ctx.inst_seq.push(MachInstruction::new(
+                    InstructionData::li(out.into(), imm),
+                    ctx.block_map[&ctx.cur_block],
+                ));
+            }
+            OperandData::VReg(_) => {
+                ctx.inst_seq.push(MachInstruction::new(
+                    InstructionData {
+                        opcode: Opcode::MV,
+                        operands: vec![MO::output(out.into()), MO::input(arg)],
+                    },
+                    ctx.block_map[&ctx.cur_block],
+                ));
+            }
+            e => return Err(LoweringError::Todo(format!("Unsupported argument: {:?}", e)).into()),
+        }
+        regs.push(out);
+    }
+
+    Ok(regs)
+}
+
+fn lower_return(ctx: &mut LoweringContext<Riscv64>, arg: Option<(Type, ValueId)>) -> Result<()> {
+    let mut operands = vec![];
+    if let Some((ty, value)) = arg {
+        let reg = if ty.is_floating_point() {
+            let reg: Reg = FPR::FA0.into();
+            let vreg = get_vreg_for_val(ctx, ty, value)?;
+            ctx.inst_seq.push(MachInstruction::new(
+                InstructionData {
+                    opcode: fp_copy_opcode(ty),
+                    operands: vec![MO::output(reg.into()), MO::input(vreg.into())],
+                },
+                ctx.block_map[&ctx.cur_block],
+            ));
+            reg
+        } else if ty.is_integer() || ty.is_pointer(ctx.types) {
+            let reg: Reg = GPR::A0.into();
+            match get_operand_for_val(ctx, ty, value)? {
+                OperandData::VReg(vreg) => {
+                    ctx.inst_seq.push(MachInstruction::new(
+                        InstructionData {
+                            opcode: Opcode::MV,
+                            operands: vec![MO::output(reg.into()), MO::input(vreg.into())],
+                        },
+                        ctx.block_map[&ctx.cur_block],
+                    ));
+                }
+                imm => {
+                    let imm = imm.sext_as_i64().ok_or_else(|| {
+                        LoweringError::Todo(format!("Unsupported return value: {:?}", imm))
+                    })?;
+                    ctx.inst_seq.push(MachInstruction::new(
+                        InstructionData::li(reg.into(), imm),
+                        ctx.block_map[&ctx.cur_block],
+                    ));
+                }
+            }
+            reg
+        } else {
+            return Err(LoweringError::Todo(format!(
+                "Unsupported return type: {}",
+                ctx.types.to_string(ty)
+            ))
+            .into());
+        };
+        operands.push(MO::implicit_input(reg.into()));
+    }
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::RET,
+            operands,
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    Ok(())
+}
+
+fn get_inst_output(
+    ctx: &mut LoweringContext<Riscv64>,
+    ty: Type,
+    id: InstructionId,
+) -> Result<VReg> {
+    if let Some(vreg) = ctx.inst_id_to_vreg.get(&id) {
+        return Ok(*vreg);
+    }
+
+    if ctx.ir_data.inst_ref(id).parent != ctx.cur_block {
+        // The instruction indexed as `id` must be placed in another basic block
+        let vreg = new_empty_inst_output(ctx, ty, id);
+        return Ok(vreg);
+    }
+
+    let inst = ctx.ir_data.inst_ref(id);
+    lower(ctx, inst)?;
+
+    Ok(new_empty_inst_output(ctx, ty, id))
+}
+
+fn new_empty_inst_output(ctx: &mut LoweringContext<Riscv64>, ty: Type, id: InstructionId) -> VReg {
+    if let Some(vreg) = ctx.inst_id_to_vreg.get(&id) {
+        return *vreg;
+    }
+    let vreg = ctx.mach_data.vregs.add_vreg_data(ty);
+    ctx.inst_id_to_vreg.insert(id, vreg);
+    vreg
+}
+
+fn get_operand_for_val(
+    ctx: &mut LoweringContext<Riscv64>,
+    ty: Type,
+    val: ValueId,
+) -> Result<OperandData> {
+    match ctx.ir_data.values[val] {
+        Value::Instruction(id) => Ok(get_inst_output(ctx, ty, id)?.into()),
+        Value::Argument(ref a) => Ok(ctx.arg_idx_to_vreg[&a.nth].into()),
+        Value::Constant(ref konst) => get_operand_for_const(ctx, ty, konst),
+        ref e => Err(LoweringError::Todo(format!("Unsupported value: {:?}", e)).into()),
+    }
+}
+
+fn get_operand_for_const(
+    ctx: &mut LoweringContext<Riscv64>,
+    ty: Type,
+    konst: &ConstantValue,
+) -> Result<OperandData> {
+    match konst {
+        ConstantValue::Int(ConstantInt::Int1(i)) => Ok(OperandData::Int32(*i as i32)),
+        ConstantValue::Int(ConstantInt::Int8(i)) => Ok(OperandData::Int32(*i as i32)),
+        ConstantValue::Int(ConstantInt::Int16(i)) => Ok(OperandData::Int32(*i as i32)),
+        ConstantValue::Int(ConstantInt::Int32(i)) => Ok(OperandData::Int32(*i)),
+        ConstantValue::Int(ConstantInt::Int64(i)) => Ok(OperandData::Int64(*i)),
+        // Floating-point constants are carried as their bits until they are moved to
+        // floating-point registers through integer ones.
+        ConstantValue::Float(ConstantFloat::Float(f)) => Ok(OperandData::Int32(f.to_bits() as i32)),
+        ConstantValue::Float(ConstantFloat::Double(f)) => {
+            Ok(OperandData::Int64(f.to_bits() as i64))
+        }
+        ConstantValue::Expr(ConstantExpr::GetElementPtr {
+            inbounds: _,
+            tys: _,
+            ref args,
+        }) => {
+            assert!(ty.is_pointer(ctx.types));
+            assert!(matches!(args[0], ConstantValue::GlobalRef(_, _)));
+            let all_indices_0 = args[1..]
+                .iter()
+                .all(|arg| matches!(arg, ConstantValue::Int(i) if i.is_zero()));
+            if !all_indices_0 {
+                return Err(
+                    LoweringError::Todo("Unsupported constant getelementptr".into()).into(),
+                );
+            }
+            let name = args[0].as_global_ref().as_string().clone();
+            Ok(global_address(ctx, ty, name).into())
+        }
+        ConstantValue::Expr(ConstantExpr::Bitcast {
+            tys: [from, to],
+            arg,
+        }) => {
+            assert!(from.is_pointer(ctx.types));
+            assert!(to.is_pointer(ctx.types));
+            get_operand_for_const(ctx, *to, arg)
+        }
+        ConstantValue::GlobalRef(ref name, ty) => {
+            assert!(ty.is_pointer(ctx.types));
+            let name = name.to_string().unwrap().to_owned();
+            Ok(global_address(ctx, *ty, name).into())
+        }
+        ConstantValue::Null(_) => Ok(OperandData::Int64(0)),
+        e => Err(LoweringError::Todo(format!("Unsupported constant: {:?}", e)).into()),
+    }
+}
+
+/// Computes the address of the global `name` with `lui` and `addi`.
+fn global_address(ctx: &mut LoweringContext<Riscv64>, ty: Type, name: String) -> VReg {
+    let hi = ctx.mach_data.vregs.add_vreg_data(ty);
+    let addr = ctx.mach_data.vregs.add_vreg_data(ty);
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::LUI,
+            operands: vec![
+                MO::output(hi.into()),
+                MO::new(OperandData::Hi(name.clone())),
+            ],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::ADDI,
+            operands: vec![
+                MO::output(addr.into()),
+                MO::input(hi.into()),
+                MO::new(OperandData::Lo(name)),
+            ],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    addr
+}
+
+fn get_vreg_for_val(ctx: &mut LoweringContext<Riscv64>, ty: Type, val: ValueId) -> Result<VReg> {
+    match get_operand_for_val(ctx, ty, val)? {
+        OperandData::Int32(i) if ty.is_floating_point() => Ok(materialize_float(ctx, ty, i as i64)),
+        OperandData::Int64(i) if ty.is_floating_point() => Ok(materialize_float(ctx, ty, i)),
+        OperandData::Int32(i) => Ok(materialize_imm(ctx, ty, i as i64)),
+        OperandData::Int64(i) => Ok(materialize_imm(ctx, ty, i)),
+        OperandData::VReg(vr) => Ok(vr),
+        e => Err(LoweringError::Todo(format!("Unsupported operand: {:?}", e)).into()),
+    }
+}
+
+fn materialize_imm(ctx: &mut LoweringContext<Riscv64>, ty: Type, imm: i64) -> VReg {
+    let output = ctx.mach_data.vregs.add_vreg_data(ty);
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData::li(output.into(), imm),
+        ctx.block_map[&ctx.cur_block],
+    ));
+    output
+}
+
+/// Moves the bits of a floating-point constant to a floating-point register, as there is no
+/// floating-point immediate.
+fn materialize_float(ctx: &mut LoweringContext<Riscv64>, ty: Type, bits: i64) -> VReg {
+    let bits = materialize_imm(ctx, types::I64, bits);
+    let output = ctx.mach_data.vregs.add_vreg_data(ty);
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: if ty.is_float() {
+                Opcode::FMVWX
+            } else {
+                Opcode::FMVDX
+            },
+            operands: vec![MO::output(output.into()), MO::input(bits.into())],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    output
+}
+
+/// Returns the move between floating-point registers holding `ty`.
+fn fp_copy_opcode(ty: Type) -> Opcode {
+    if ty.is_float() {
+        Opcode::FMVS
+    } else {
+        Opcode::FMVD
+    }
+}
+
+/// Returns true if values of `ty` are operated on as 64-bit values.
+fn is_64(ctx: &LoweringContext<Riscv64>, ty: Type) -> bool {
+    ty.is_pointer(ctx.types) || ctx.isa.data_layout().get_size_of(ctx.types, ty) == 8
+}
@@ -0,0 +1,54 @@
+pub mod asm;
+pub mod instruction;
+pub mod lower;
+pub mod pass;
+pub mod register;
+
+use super::TargetIsa;
+use crate::{
+    call_conv::CallConvKind,
+    pass::{regalloc, Pass},
+};
+use vicis_core::ir::module::data_layout::DataLayout;
+
+#[derive(Clone)]
+pub struct Riscv64 {
+    data_layout: DataLayout,
+}
+
+impl Default for Riscv64 {
+    fn default() -> Self {
+        Self {
+            data_layout: DataLayout("".to_string()),
+        }
+    }
+}
+
+impl TargetIsa for Riscv64 {
+    type Inst = instruction::InstructionData;
+    type Lower = lower::Lower;
+    type RegClass = register::RegClass;
+    type RegInfo = register::RegInfo;
+
+    fn module_passes() -> Vec<Pass<Self>> {
+        vec![
+            Pass::new("regalloc", regalloc::run_on_module),
+            // TODO: should be target independent
+            Pass::new("phi_elimination", pass::phi_elimination::run_on_module),
+            Pass::new(
+                "simple_reg_coalescing",
+                pass::simple_reg_coalescing::run_on_module,
+            ),
+            Pass::new("eliminate_slot", pass::eliminate_slot::run_on_module),
+            Pass::new("pro_epi_inserter", pass::pro_epi_inserter::run_on_module),
+        ]
+    }
+
+    fn default_call_conv() -> CallConvKind {
+        CallConvKind::LP64D
+    }
+
+    fn data_layout(&self) -> &DataLayout {
+        &self.data_layout
+    }
+}
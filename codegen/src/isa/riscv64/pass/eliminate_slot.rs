@@ -0,0 +1,136 @@
+use super::pro_epi_inserter::frame_size;
+use crate::{
+    function::{
+        basic_block::BasicBlockId,
+        instruction::{Instruction, InstructionId},
+        Function,
+    },
+    isa::riscv64::{
+        instruction::{InstructionData, Opcode, Operand, OperandData},
+        register::GPR,
+        Riscv64,
+    },
+    module::Module,
+};
+use anyhow::{bail, Result};
+
+pub fn run_on_module(module: &mut Module<Riscv64>) -> Result<()> {
+    for (_, func) in &mut module.functions {
+        run_on_function(func)?;
+    }
+    Ok(())
+}
+
+// Slots are addressed relative to `sp`, which stays at the bottom of the frame in the function
+// body. A slot whose offset from the top of the frame is `off` lives at `sp + size - off`.
+// Displacements that don't fit in a 12-bit signed immediate are added to `sp` in `t6`.
+pub fn run_on_function(function: &mut Function<Riscv64>) -> Result<()> {
+    let mut worklist = vec![];
+
+    for block in function.layout.block_iter() {
+        for inst_id in function.layout.inst_iter(block) {
+            let inst = function.data.inst_ref(inst_id);
+            if inst
+                .data
+                .operands
+                .iter()
+                .any(|op| matches!(op.data, OperandData::Slot(_)))
+            {
+                worklist.push(inst_id);
+            }
+        }
+    }
+
+    let size = frame_size(function) as i32;
+
+    while let Some(inst_id) = worklist.pop() {
+        let mut inst = function.data.instructions[inst_id].clone();
+
+        let mut i = 0;
+        let len = inst.data.operands.len();
+
+        while i < len {
+            match &inst.data.operands[i].data {
+                // MemStart indicates the beginning of memory arguments
+                OperandData::MemStart => {
+                    let slot = match inst.data.operands[i + 1].data {
+                        OperandData::Slot(slot) => slot,
+                        _ => {
+                            i += 4;
+                            continue;
+                        }
+                    };
+                    let off = size - function.slots.get(slot).offset as i32;
+                    let imm = match inst.data.operands[i + 2].data {
+                        OperandData::None => 0,
+                        OperandData::Int32(imm) => imm,
+                        ref e => bail!("unsupported slot displacement: {:?}", e),
+                    };
+                    let (base, disp) = if is_imm12(off + imm) {
+                        (GPR::SP, off + imm)
+                    } else {
+                        add_to_sp(function, inst_id, inst.parent, off + imm);
+                        (GPR::T6, 0)
+                    };
+                    let mem = &mut inst.data.operands[i + 1..i + 4];
+                    mem[0].data = OperandData::None;
+                    mem[1].data = OperandData::Int32(disp);
+                    mem[2].data = base.into();
+                    i += 4;
+                }
+                // The address of a slot, i.e. `addi rd, slot, imm`
+                &OperandData::Slot(slot) => {
+                    let off = size - function.slots.get(slot).offset as i32;
+                    let imm = match inst.data.operands[i + 1].data {
+                        OperandData::Int32(imm) => imm,
+                        ref e => bail!("unsupported slot displacement: {:?}", e),
+                    };
+                    inst.data.operands[i] = Operand::input(GPR::SP.into());
+                    if is_imm12(off + imm) {
+                        inst.data.operands[i + 1].data = OperandData::Int32(off + imm);
+                    } else {
+                        let li = InstructionData::li(GPR::T6.into(), (off + imm) as i64);
+                        let li = function.data.create_inst(Instruction::new(li, inst.parent));
+                        function.layout.insert_inst_before(inst_id, li, inst.parent);
+                        inst.data.opcode = Opcode::ADD;
+                        inst.data.operands[i + 1] = Operand::input(GPR::T6.into());
+                    }
+                    i += 2;
+                }
+                _ => i += 1,
+            }
+        }
+
+        function.data.instructions[inst_id] = inst;
+    }
+
+    Ok(())
+}
+
+/// Inserts `t6 = sp + disp` before `inst_id`.
+fn add_to_sp(
+    function: &mut Function<Riscv64>,
+    inst_id: InstructionId<InstructionData>,
+    block: BasicBlockId,
+    disp: i32,
+) {
+    let insts = vec![
+        InstructionData::li(GPR::T6.into(), disp as i64),
+        InstructionData {
+            opcode: Opcode::ADD,
+            operands: vec![
+                Operand::output(GPR::T6.into()),
+                Operand::input(GPR::SP.into()),
+                Operand::input(GPR::T6.into()),
+            ],
+        },
+    ];
+    for data in insts {
+        let inst = function.data.create_inst(Instruction::new(data, block));
+        function.layout.insert_inst_before(inst_id, inst, block);
+    }
+}
+
+fn is_imm12(i: i32) -> bool {
+    (-2048..2048).contains(&i)
+}
@@ -0,0 +1,4 @@
+pub mod eliminate_slot;
+pub mod phi_elimination;
+pub mod pro_epi_inserter;
+pub mod simple_reg_coalescing;
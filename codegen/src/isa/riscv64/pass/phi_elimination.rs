@@ -0,0 +1,173 @@
+use crate::{
+    function::{
+        basic_block::BasicBlockId,
+        instruction::{Instruction, InstructionId},
+        Function,
+    },
+    isa::riscv64::{
+        instruction::{InstructionData, Opcode, Operand, OperandData},
+        register::{RegClass, GPR},
+        Riscv64,
+    },
+    module::Module,
+    register::Reg,
+};
+use anyhow::{bail, Result};
+use rustc_hash::FxHashMap;
+
+pub fn run_on_module(module: &mut Module<Riscv64>) -> Result<()> {
+    for (_, func) in &mut module.functions {
+        run_on_function(func)?;
+    }
+    Ok(())
+}
+
+pub fn run_on_function(function: &mut Function<Riscv64>) -> Result<()> {
+    let mut worklist = vec![];
+    let mut map: FxHashMap<Reg, Vec<(OperandData, BasicBlockId)>> = FxHashMap::default();
+
+    for block_id in function.layout.block_iter() {
+        for inst_id in function.layout.inst_iter(block_id) {
+            let inst = function.data.inst_ref(inst_id);
+            if !matches!(inst.data.opcode, Opcode::Phi) {
+                continue;
+            }
+            worklist.push(inst_id);
+            let output = *inst.data.operands[0].data.as_reg();
+            for i in (0..inst.data.operands[1..].len()).step_by(2) {
+                let val = inst.data.operands[1 + i /*+ 0*/].data.clone();
+                let block = *inst.data.operands[1 + i + 1].data.as_block();
+                map.entry(output).or_default().push((val, block));
+            }
+        }
+    }
+
+    // Registers written by the copies in each block
+    let mut copied: FxHashMap<BasicBlockId, Vec<Reg>> = FxHashMap::default();
+
+    // Copies to floating-point registers go first, as they may clobber `t6`, which the branch
+    // operands are saved to later.
+    let (fpr, gpr): (Vec<_>, Vec<_>) = map
+        .into_iter()
+        .partition(|(output, _)| output.0 == RegClass::FPR as u16);
+    for (output, args) in fpr.into_iter().chain(gpr) {
+        let is_fpr = output.0 == RegClass::FPR as u16;
+        for (arg, block) in args {
+            let copies = if is_fpr {
+                fpr_copy(output, arg)?
+            } else {
+                match arg {
+                    OperandData::Int32(_) | OperandData::Int64(_) => vec![InstructionData::li(
+                        OperandData::Reg(output),
+                        arg.sext_as_i64().unwrap(),
+                    )],
+                    OperandData::Reg(_) => vec![InstructionData {
+                        opcode: Opcode::MV,
+                        operands: vec![
+                            Operand::output(OperandData::Reg(output)),
+                            Operand::input(arg),
+                        ],
+                    }],
+                    e => bail!("unsupported phi argument: {:?}", e),
+                }
+            };
+            let term = first_trailing_branch(function, block);
+            for copy in copies {
+                let copy = function.data.create_inst(Instruction::new(copy, block));
+                function.layout.insert_inst_before(term, copy, block);
+            }
+            if !is_fpr {
+                copied.entry(block).or_default().push(output);
+            }
+        }
+    }
+
+    for (block, outputs) in copied {
+        save_branch_operands(function, block, &outputs);
+    }
+
+    for inst_id in worklist {
+        function.remove_inst(inst_id);
+    }
+
+    Ok(())
+}
+
+/// Copies `arg` to a floating-point register. Constants are passed as their bit patterns, which
+/// have to go through `t6` since there is no floating-point immediate.
+fn fpr_copy(output: Reg, arg: OperandData) -> Result<Vec<InstructionData>> {
+    let fmv = match arg {
+        OperandData::Reg(_) => {
+            return Ok(vec![InstructionData {
+                opcode: Opcode::FMVD,
+                operands: vec![
+                    Operand::output(OperandData::Reg(output)),
+                    Operand::input(arg),
+                ],
+            }])
+        }
+        OperandData::Int32(_) => Opcode::FMVWX,
+        OperandData::Int64(_) => Opcode::FMVDX,
+        e => bail!("unsupported floating-point phi argument: {:?}", e),
+    };
+    Ok(vec![
+        InstructionData::li(GPR::T6.into(), arg.sext_as_i64().unwrap()),
+        InstructionData {
+            opcode: fmv,
+            operands: vec![
+                Operand::output(OperandData::Reg(output)),
+                Operand::input(GPR::T6.into()),
+            ],
+        },
+    ])
+}
+
+/// Returns the first of the branches at the end of `block`, before which copies are placed.
+fn first_trailing_branch(
+    function: &Function<Riscv64>,
+    block: BasicBlockId,
+) -> InstructionId<InstructionData> {
+    let mut term = function.layout.last_inst_of(block).unwrap();
+    while let Some(prev) = function.layout.prev_inst_of(term) {
+        if !function.data.inst_ref(prev).data.opcode.is_branch() {
+            break;
+        }
+        term = prev;
+    }
+    term
+}
+
+/// Unlike flags set by a comparison, the registers compared by a branch can be overwritten by
+/// the copies placed before it. Such registers are moved into the scratch registers before the
+/// copies, and the branch compares them instead.
+fn save_branch_operands(function: &mut Function<Riscv64>, block: BasicBlockId, outputs: &[Reg]) {
+    let term = first_trailing_branch(function, block);
+    let mut first_copy = term;
+    for _ in 0..outputs.len() {
+        first_copy = function.layout.prev_inst_of(first_copy).unwrap();
+    }
+
+    let mut scratches = vec![GPR::T6, GPR::T5];
+    let mut branch = Some(term);
+    while let Some(inst_id) = branch {
+        let mut inst = function.data.instructions[inst_id].clone();
+        for operand in &mut inst.data.operands {
+            let reg = match operand.data {
+                OperandData::Reg(reg) if operand.input && outputs.contains(&reg) => reg,
+                _ => continue,
+            };
+            let scratch: Reg = scratches.pop().unwrap().into();
+            let save = function.data.create_inst(Instruction::new(
+                InstructionData {
+                    opcode: Opcode::MV,
+                    operands: vec![Operand::output(scratch.into()), Operand::input(reg.into())],
+                },
+                block,
+            ));
+            function.layout.insert_inst_before(first_copy, save, block);
+            operand.data = scratch.into();
+        }
+        function.data.instructions[inst_id] = inst;
+        branch = function.layout.next_inst_of(inst_id);
+    }
+}
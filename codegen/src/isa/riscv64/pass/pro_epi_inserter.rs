@@ -0,0 +1,164 @@
+use crate::{
+    function::{instruction::Instruction, Function},
+    isa::riscv64::{
+        instruction::{InstructionData, Opcode, Operand, OperandData},
+        register::{RegClass, GPR},
+        Riscv64,
+    },
+    module::Module,
+    register::Reg,
+};
+use anyhow::Result;
+
+pub fn run_on_module(module: &mut Module<Riscv64>) -> Result<()> {
+    for (_, func) in &mut module.functions {
+        run_on_function(func);
+    }
+    Ok(())
+}
+
+// The frame looks like below. `sp` is kept 16-byte aligned.
+//
+//                   <- s0
+//   | ra            |
+//   | s0            |
+//   | callee-saved  | (including floating-point ones, saved with `fsd`)
+//   | slots         |
+//   |               | <- sp
+pub fn run_on_function(function: &mut Function<Riscv64>) {
+    if function.is_declaration {
+        return;
+    }
+
+    let size = frame_size(function) as i32;
+
+    let mut used_csr = function
+        .data
+        .used_csr
+        .clone()
+        .into_iter()
+        .map(|r| {
+            if r.0 == RegClass::FPR as u16 {
+                r.apply(&RegClass::FPR)
+            } else {
+                r.apply(&RegClass::GPR)
+            }
+        })
+        .collect::<Vec<_>>();
+    used_csr.sort();
+    let saved: Vec<Reg> = vec![GPR::RA.into(), GPR::S0.into()]
+        .into_iter()
+        .chain(used_csr)
+        .collect();
+    let save_size = (saved.len() as i32 * 8 + 15) & !15;
+
+    // insert prologue
+    let entry = function.layout.first_block.unwrap();
+    let mut prologue = vec![add_sp(-save_size)];
+    for (i, &r) in saved.iter().enumerate() {
+        prologue.push(InstructionData {
+            opcode: if r.0 == RegClass::FPR as u16 {
+                Opcode::FSD
+            } else {
+                Opcode::SD
+            },
+            operands: vec![Operand::input(r.into())]
+                .into_iter()
+                .chain(sp_relative(save_size - 8 * (i as i32 + 1)))
+                .collect(),
+        })
+    }
+    prologue.push(InstructionData {
+        opcode: Opcode::ADDI,
+        operands: vec![
+            Operand::output(GPR::S0.into()),
+            Operand::input(GPR::SP.into()),
+            Operand::new(save_size.into()),
+        ],
+    });
+    prologue.append(&mut adjust_sp(-size));
+    for data in prologue.into_iter().rev() {
+        let inst = function.data.create_inst(Instruction::new(data, entry));
+        function.layout.insert_inst_at_start(inst, entry);
+    }
+
+    // insert epilogue
+    let mut epilogues = vec![];
+    for block in function.layout.block_iter() {
+        for inst_id in function.layout.inst_iter(block) {
+            let inst = function.data.inst_ref(inst_id);
+            if !matches!(inst.data.opcode, Opcode::RET) {
+                continue;
+            }
+            epilogues.push((block, inst_id));
+        }
+    }
+    for (block, ret_id) in epilogues {
+        let mut epilogue = adjust_sp(size);
+        for (i, &r) in saved.iter().enumerate().rev() {
+            epilogue.push(InstructionData {
+                opcode: if r.0 == RegClass::FPR as u16 {
+                    Opcode::FLD
+                } else {
+                    Opcode::LD
+                },
+                operands: vec![Operand::output(r.into())]
+                    .into_iter()
+                    .chain(sp_relative(save_size - 8 * (i as i32 + 1)))
+                    .collect(),
+            })
+        }
+        epilogue.push(add_sp(save_size));
+        for data in epilogue {
+            let inst = function.data.create_inst(Instruction::new(data, block));
+            function.layout.insert_inst_before(ret_id, inst, block);
+        }
+    }
+}
+
+/// Returns the size of the area for the slots, which keeps `sp` 16-byte aligned.
+pub fn frame_size(function: &mut Function<Riscv64>) -> u32 {
+    let slot_size = function.slots.ensure_computed_offsets();
+    (slot_size + 15) & !15
+}
+
+fn add_sp(imm: i32) -> InstructionData {
+    InstructionData {
+        opcode: Opcode::ADDI,
+        operands: vec![
+            Operand::output(GPR::SP.into()),
+            Operand::input(GPR::SP.into()),
+            Operand::new(imm.into()),
+        ],
+    }
+}
+
+/// Returns the instructions that add `imm` to `sp`, using `t6` if it doesn't fit in `addi`.
+fn adjust_sp(imm: i32) -> Vec<InstructionData> {
+    if imm == 0 {
+        return vec![];
+    }
+    if (-2048..2048).contains(&imm) {
+        return vec![add_sp(imm)];
+    }
+    vec![
+        InstructionData::li(GPR::T6.into(), imm as i64),
+        InstructionData {
+            opcode: Opcode::ADD,
+            operands: vec![
+                Operand::output(GPR::SP.into()),
+                Operand::input(GPR::SP.into()),
+                Operand::input(GPR::T6.into()),
+            ],
+        },
+    ]
+}
+
+fn sp_relative(disp: i32) -> Vec<Operand> {
+    vec![
+        Operand::new(OperandData::MemStart),
+        Operand::new(OperandData::None),
+        Operand::new(disp.into()),
+        Operand::input(GPR::SP.into()),
+    ]
+}
@@ -0,0 +1,295 @@
+use crate::{
+    call_conv::CallConvKind,
+    lower::LoweringError,
+    register::{Reg, RegUnit, RegisterClass, RegisterInfo},
+};
+use anyhow::Result;
+use vicis_core::ir::types::{self, Type, Types};
+
+pub struct RegInfo;
+
+// Registers are numbered as x0..x31 and named by their ABI names.
+pub enum GPR {
+    Zero,
+    RA,
+    SP,
+    GP,
+    TP,
+    T0,
+    T1,
+    T2,
+    S0, // Frame pointer
+    S1,
+    A0,
+    A1,
+    A2,
+    A3,
+    A4,
+    A5,
+    A6,
+    A7,
+    S2,
+    S3,
+    S4,
+    S5,
+    S6,
+    S7,
+    S8,
+    S9,
+    S10,
+    S11,
+    T3,
+    T4,
+    T5, // Reserved as a scratch register
+    T6, // Reserved as a scratch register
+}
+
+// Registers are numbered as f0..f31 and named by their ABI names.
+pub enum FPR {
+    FT0,
+    FT1,
+    FT2,
+    FT3,
+    FT4,
+    FT5,
+    FT6,
+    FT7,
+    FS0,
+    FS1,
+    FA0,
+    FA1,
+    FA2,
+    FA3,
+    FA4,
+    FA5,
+    FA6,
+    FA7,
+    FS2,
+    FS3,
+    FS4,
+    FS5,
+    FS6,
+    FS7,
+    FS8,
+    FS9,
+    FS10,
+    FS11,
+    FT8,
+    FT9,
+    FT10,
+    FT11,
+}
+
+// 32-bit integers live in 64-bit registers sign-extended, so there is only one integer class.
+// `float` and `double` share the floating-point registers in the same way.
+pub enum RegClass {
+    GPR,
+    FPR,
+}
+
+impl From<GPR> for Reg {
+    fn from(r: GPR) -> Self {
+        Reg(RegClass::GPR as u16, r as u16)
+    }
+}
+
+impl From<GPR> for RegUnit {
+    fn from(r: GPR) -> Self {
+        RegUnit(RegClass::GPR as u16, r as u16)
+    }
+}
+
+impl From<FPR> for Reg {
+    fn from(r: FPR) -> Self {
+        Reg(RegClass::FPR as u16, r as u16)
+    }
+}
+
+impl From<FPR> for RegUnit {
+    fn from(r: FPR) -> Self {
+        RegUnit(RegClass::FPR as u16, r as u16)
+    }
+}
+
+const ARG_REGS: [RegUnit; 8] = [
+    RegUnit(RegClass::GPR as u16, GPR::A0 as u16),
+    RegUnit(RegClass::GPR as u16, GPR::A1 as u16),
+    RegUnit(RegClass::GPR as u16, GPR::A2 as u16),
+    RegUnit(RegClass::GPR as u16, GPR::A3 as u16),
+    RegUnit(RegClass::GPR as u16, GPR::A4 as u16),
+    RegUnit(RegClass::GPR as u16, GPR::A5 as u16),
+    RegUnit(RegClass::GPR as u16, GPR::A6 as u16),
+    RegUnit(RegClass::GPR as u16, GPR::A7 as u16),
+];
+
+const FLOAT_ARG_REGS: [RegUnit; 8] = [
+    RegUnit(RegClass::FPR as u16, FPR::FA0 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FA1 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FA2 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FA3 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FA4 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FA5 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FA6 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FA7 as u16),
+];
+
+const CSR: [RegUnit; 24] = [
+    RegUnit(RegClass::GPR as u16, GPR::S1 as u16),
+    RegUnit(RegClass::GPR as u16, GPR::S2 as u16),
+    RegUnit(RegClass::GPR as u16, GPR::S3 as u16),
+    RegUnit(RegClass::GPR as u16, GPR::S4 as u16),
+    RegUnit(RegClass::GPR as u16, GPR::S5 as u16),
+    RegUnit(RegClass::GPR as u16, GPR::S6 as u16),
+    RegUnit(RegClass::GPR as u16, GPR::S7 as u16),
+    RegUnit(RegClass::GPR as u16, GPR::S8 as u16),
+    RegUnit(RegClass::GPR as u16, GPR::S9 as u16),
+    RegUnit(RegClass::GPR as u16, GPR::S10 as u16),
+    RegUnit(RegClass::GPR as u16, GPR::S11 as u16),
+    RegUnit(RegClass::GPR as u16, GPR::S0 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FS0 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FS1 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FS2 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FS3 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FS4 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FS5 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FS6 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FS7 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FS8 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FS9 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FS10 as u16),
+    RegUnit(RegClass::FPR as u16, FPR::FS11 as u16),
+];
+
+impl RegInfo {
+    /// Registers for floating-point arguments, which are counted apart from integer ones.
+    pub fn float_arg_reg_list(cc: &CallConvKind) -> &'static [RegUnit] {
+        match cc {
+            CallConvKind::LP64D => &FLOAT_ARG_REGS,
+            CallConvKind::SystemV | CallConvKind::AAPCS64 => &[],
+        }
+    }
+}
+
+impl RegisterInfo for RegInfo {
+    fn arg_reg_list(cc: &CallConvKind) -> &'static [RegUnit] {
+        match cc {
+            CallConvKind::LP64D => &ARG_REGS,
+            CallConvKind::SystemV | CallConvKind::AAPCS64 => &[],
+        }
+    }
+
+    fn to_reg_unit(r: Reg) -> RegUnit {
+        match r {
+            Reg(/*GPR*/ 0, x) => RegUnit(RegClass::GPR as u16, x),
+            Reg(/*FPR*/ 1, x) => RegUnit(RegClass::FPR as u16, x),
+            _ => panic!(),
+        }
+    }
+
+    fn is_csr(r: RegUnit) -> bool {
+        CSR.contains(&r)
+    }
+}
+
+impl RegisterClass for RegClass {
+    fn for_type(types: &Types, ty: Type) -> Result<Self> {
+        match ty {
+            types::I1 | types::I8 | types::I16 | types::I32 | types::I64 => Ok(RegClass::GPR),
+            types::FLOAT | types::DOUBLE => Ok(RegClass::FPR),
+            _ if ty.is_pointer(types) => Ok(RegClass::GPR),
+            e => {
+                Err(LoweringError::Todo(format!("Unsupported type: {}", types.to_string(e))).into())
+            }
+        }
+    }
+
+    fn gpr_list(&self) -> Vec<Reg> {
+        match self {
+            RegClass::GPR => vec![
+                GPR::T0,
+                GPR::T1,
+                GPR::T2,
+                GPR::T3,
+                GPR::T4,
+                GPR::A0,
+                GPR::A1,
+                GPR::A2,
+                GPR::A3,
+                GPR::A4,
+                GPR::A5,
+                GPR::A6,
+                GPR::A7,
+            ]
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+            RegClass::FPR => vec![
+                FPR::FT0,
+                FPR::FT1,
+                FPR::FT2,
+                FPR::FT3,
+                FPR::FT4,
+                FPR::FT5,
+                FPR::FT6,
+                FPR::FT7,
+                FPR::FA0,
+                FPR::FA1,
+                FPR::FA2,
+                FPR::FA3,
+                FPR::FA4,
+                FPR::FA5,
+                FPR::FA6,
+                FPR::FA7,
+                FPR::FT8,
+                FPR::FT9,
+            ]
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+        }
+    }
+
+    // NOTE: The register allocator never uses the last register (frame pointer), and reserves
+    // the second to last one for reloading spilled values. `ft10` and `ft11` are not callee-saved,
+    // but come last for the same reason.
+    fn csr_list(&self) -> Vec<Reg> {
+        match self {
+            RegClass::GPR => CSR
+                .iter()
+                .filter(|r| r.0 == RegClass::GPR as u16)
+                .map(|&r| self.apply_for(r))
+                .collect(),
+            RegClass::FPR => CSR
+                .iter()
+                .filter(|r| r.0 == RegClass::FPR as u16)
+                .map(|&r| self.apply_for(r))
+                .chain([FPR::FT10.into(), FPR::FT11.into()])
+                .collect(),
+        }
+    }
+
+    fn apply_for(&self, ru: RegUnit) -> Reg {
+        match self {
+            Self::GPR => Reg(RegClass::GPR as u16, ru.1),
+            Self::FPR => Reg(RegClass::FPR as u16, ru.1),
+        }
+    }
+}
+
+pub fn reg_to_str(r: &Reg) -> &'static str {
+    let gpr = [
+        "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+        "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+        "t5", "t6",
+    ];
+    let fpr = [
+        "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7", "fs0", "fs1", "fa0", "fa1", "fa2",
+        "fa3", "fa4", "fa5", "fa6", "fa7", "fs2", "fs3", "fs4", "fs5", "fs6", "fs7", "fs8", "fs9",
+        "fs10", "fs11", "ft8", "ft9", "ft10", "ft11",
+    ];
+    match r {
+        Reg(0, i) => gpr[*i as usize],
+        Reg(1, i) => fpr[*i as usize],
+        e => todo!("{:?}", e),
+    }
+}
@@ -1,22 +1,16 @@
-use vicis_core::ir::{
-    module::{global_variable::GlobalVariable, linkage::Linkage, name::Name},
-    types::Typed,
-    value::{ConstantArray, ConstantInt, ConstantStruct, ConstantValue},
-};
-
 use crate::{
     function::Function,
     isa::{
+        asm::print_global_variables,
         x86_64::{
             instruction::{Opcode, Operand, OperandData},
             register::reg_to_str,
             X86_64,
         },
-        TargetIsa,
     },
     module::{DisplayAsm, Module},
 };
-use std::{fmt, str};
+use std::fmt;
 
 impl fmt::Display for DisplayAsm<'_, X86_64> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -32,156 +26,7 @@ pub fn print(f: &mut fmt::Formatter<'_>, module: &Module<X86_64>) -> fmt::Result
         print_function(f, func, i)?
     }
 
-    let mut ctor = None;
-
-    for gv in module.ir.global_variables().values() {
-        let is_extern = matches!(
-            gv.linkage,
-            Some(Linkage::External) | Some(Linkage::ExternalWeak)
-        );
-        if is_extern {
-            continue;
-        }
-
-        if gv.init.is_none() {
-            continue;
-        }
-
-        let init = gv.init.as_ref().unwrap();
-        match init {
-            ConstantValue::Array(arr) => {
-                if matches!(gv.name, Name::Name(ref name) if name == "llvm.global_ctors") {
-                    // TODO
-                    if let ConstantValue::Struct(ConstantStruct { elems, .. }) = &arr.elems[0] {
-                        if let ConstantValue::GlobalRef(name, _) = &elems[1] {
-                            ctor = module.ir.find_function_by_name(name.as_string());
-                        }
-                    }
-                    continue;
-                }
-
-                if !arr.is_string {
-                    continue;
-                }
-
-                let mut s = vec![];
-                for elem in &arr.elems {
-                    s.push(*elem.as_int().unwrap().as_i8() as u8)
-                }
-                let s = str::from_utf8(
-                    s.into_iter()
-                        .flat_map(::std::ascii::escape_default)
-                        .collect::<Vec<u8>>()
-                        .as_slice(),
-                )
-                .unwrap()
-                .to_string();
-                let s = s.trim_end_matches("\\x00"); // TODO
-                debug!(&s);
-                writeln!(f, "{}:", gv.name.as_string())?;
-                writeln!(f, "  .string \"{}\"", s)?;
-            }
-            _ => print_data_init(f, module, gv)?,
-        }
-    }
-
-    if let Some(ctor) = ctor {
-        let ctor = &module.ir.functions()[ctor];
-        writeln!(f, "  .section .init_array")?;
-        writeln!(f, "  .quad {}", ctor.name())?;
-    }
-
-    Ok(())
-}
-
-fn print_data_init(
-    f: &mut fmt::Formatter<'_>,
-    module: &Module<X86_64>,
-    gvar: &GlobalVariable,
-) -> fmt::Result {
-    match gvar.init.as_ref().unwrap() {
-        ConstantValue::AggregateZero(ty) => {
-            let size = module.isa.data_layout().get_size_of(&module.types, *ty);
-            let align = module.isa.data_layout().get_align_of(&module.types, *ty);
-            writeln!(f, "  .comm {},{},{}", gvar.name.as_string(), size, align)
-        }
-        ConstantValue::Int(i) => {
-            if !gvar.linkage.map_or(false, |l| l.is_internal()) {
-                writeln!(f, "  .globl {}", gvar.name.as_string())?;
-            }
-            let size = module.isa.data_layout().get_size_of(&module.types, i.ty());
-            writeln!(f, "{}:", gvar.name.as_string())?;
-            writeln!(
-                f,
-                "  .{sz} {i}",
-                sz = match i {
-                    ConstantInt::Int1(_) => "byte",
-                    ConstantInt::Int8(_) => "byte",
-                    ConstantInt::Int32(_) => "long",
-                    ConstantInt::Int64(_) => "quad",
-                }
-            )?;
-            writeln!(f, "  .size {}, {}", gvar.name.as_string(), size)
-        }
-        ConstantValue::Struct(ConstantStruct {
-            elems,
-            is_packed: true,
-            ..
-        }) => {
-            writeln!(f, "{}:", gvar.name.as_string())?;
-            for elem in elems {
-                print_data_init_sub(f, module, elem)?
-            }
-            Ok(())
-        }
-        e => todo!("Unsupported initializer: {:?}", e),
-    }
-}
-
-fn print_data_init_sub(
-    f: &mut fmt::Formatter<'_>,
-    module: &Module<X86_64>,
-    init: &ConstantValue,
-) -> fmt::Result {
-    match init {
-        ConstantValue::AggregateZero(ty) => {
-            let size = module.isa.data_layout().get_size_of(&module.types, *ty);
-            writeln!(f, "  .zero {}", size)
-        }
-        ConstantValue::Int(i) => {
-            writeln!(
-                f,
-                "  .{sz} {i}",
-                sz = match i {
-                    ConstantInt::Int1(_) => "byte",
-                    ConstantInt::Int8(_) => "byte",
-                    ConstantInt::Int32(_) => "long",
-                    ConstantInt::Int64(_) => "quad",
-                }
-            )
-        }
-        ConstantValue::Struct(ConstantStruct {
-            elems,
-            is_packed: true,
-            ..
-        }) => {
-            for elem in elems {
-                print_data_init_sub(f, module, elem)?
-            }
-            Ok(())
-        }
-        ConstantValue::Array(ConstantArray {
-            elems,
-            is_string: false,
-            ..
-        }) => {
-            for elem in elems {
-                print_data_init_sub(f, module, elem)?
-            }
-            Ok(())
-        }
-        e => todo!("Unsupported initializer: {:?}", e),
-    }
+    print_global_variables(f, module)
 }
 
 pub fn print_function(
@@ -222,7 +67,7 @@ pub fn print_function(
                     if !sz.is_empty() {
                         write!(f, " ")?;
                     }
-                    write!(f, "{}", mem_op(&inst.data.operands[i..i + 6]))?;
+                    write!(f, "{}", mem_op(&inst.data.operands[i..i + 6], fn_idx))?;
                     i += 6 - 1;
                 } else {
                     write_operand(f, &operand.data, fn_idx)?;
@@ -236,6 +81,22 @@ pub fn print_function(
         }
     }
 
+    // Entries are offsets from the table, so that the code doesn't need relocations.
+    for (i, table) in function.data.jump_tables.iter().enumerate() {
+        writeln!(f, "  .p2align 2, 0xcc")?;
+        writeln!(f, ".LJTI{}_{}:", fn_idx, i)?;
+        for block in table {
+            writeln!(
+                f,
+                "  .long .LBL{}_{}-.LJTI{}_{}",
+                fn_idx,
+                block.index(),
+                fn_idx,
+                i
+            )?;
+        }
+    }
+
     Ok(())
 }
 
@@ -247,14 +108,85 @@ impl fmt::Display for Opcode {
             match self {
                 Self::PUSH64 => "push",
                 Self::POP64 => "pop",
-                Self::ADDr64i32 | Self::ADDri32 | Self::ADDrr32 | Self::ADDrr64 => "add",
-                Self::SUBri32 | Self::SUBrr32 | Self::SUBr64i32 => "sub",
-                Self::IMULrr32 | Self::IMULrr64i32 => "imul",
+                Self::ADDr64i32
+                | Self::ADDri8
+                | Self::ADDri16
+                | Self::ADDri32
+                | Self::ADDrr8
+                | Self::ADDrr16
+                | Self::ADDrr32
+                | Self::ADDrr64 => "add",
+                Self::SUBr64i32
+                | Self::SUBri8
+                | Self::SUBri16
+                | Self::SUBri32
+                | Self::SUBrr8
+                | Self::SUBrr16
+                | Self::SUBrr32
+                | Self::SUBrr64 => "sub",
+                Self::ANDr64i32
+                | Self::ANDri8
+                | Self::ANDri16
+                | Self::ANDri32
+                | Self::ANDrr8
+                | Self::ANDrr16
+                | Self::ANDrr32
+                | Self::ANDrr64 => "and",
+                Self::ORr64i32
+                | Self::ORri8
+                | Self::ORri16
+                | Self::ORri32
+                | Self::ORrr8
+                | Self::ORrr16
+                | Self::ORrr32
+                | Self::ORrr64 => "or",
+                Self::XORr64i32
+                | Self::XORri8
+                | Self::XORri16
+                | Self::XORri32
+                | Self::XORrr8
+                | Self::XORrr16
+                | Self::XORrr32
+                | Self::XORrr64 => "xor",
+                Self::IMULrr32 | Self::IMULrr64 | Self::IMULrr32i32 | Self::IMULrr64i32 => "imul",
+                Self::NEGr32 | Self::NEGr64 => "neg",
+                Self::CDQ => "cdq",
+                Self::CQO => "cqo",
+                Self::IDIVr32 | Self::IDIVr64 => "idiv",
+                Self::DIVr32 | Self::DIVr64 => "div",
+                Self::SHLr8CL
+                | Self::SHLr16CL
+                | Self::SHLr32CL
+                | Self::SHLr64CL
+                | Self::SHLr8i8
+                | Self::SHLr16i8
+                | Self::SHLr32i8
+                | Self::SHLr64i8 => "shl",
+                Self::SHRr8CL
+                | Self::SHRr16CL
+                | Self::SHRr32CL
+                | Self::SHRr64CL
+                | Self::SHRr8i8
+                | Self::SHRr16i8
+                | Self::SHRr32i8
+                | Self::SHRr64i8 => "shr",
+                Self::SARr8CL
+                | Self::SARr16CL
+                | Self::SARr32CL
+                | Self::SARr64CL
+                | Self::SARr8i8
+                | Self::SARr16i8
+                | Self::SARr32i8
+                | Self::SARr64i8 => "sar",
                 Self::MOVrm8
+                | Self::MOVrm16
                 | Self::MOVmr8
+                | Self::MOVmr16
                 | Self::MOVmi8
+                | Self::MOVmi16
                 | Self::MOVrr32
                 | Self::MOVrr8
+                | Self::MOVrr16
                 | Self::MOVrr64
                 | Self::MOVri32
                 | Self::MOVri64
@@ -263,11 +195,54 @@ impl fmt::Display for Opcode {
                 | Self::MOVrm32
                 | Self::MOVmi32
                 | Self::MOVmi64
-                | Self::MOVmr32 => "mov",
+                | Self::MOVmr32
+                | Self::MOVZXr64r32 => "mov",
                 Self::LEArm64 => "lea",
+                Self::MOVSXr32r8 | Self::MOVSXr32r16 | Self::MOVSXr64r8 | Self::MOVSXr64r16 => {
+                    "movsx"
+                }
                 Self::MOVSXDr64r32 | Self::MOVSXDr64m32 => "movsxd",
-                Self::MOVZXr32r8 => "movzx",
-                Self::CMPri8 | Self::CMPri32 | Self::CMPrr32 => "cmp",
+                Self::MOVZXr32r8 | Self::MOVZXr32r16 => "movzx",
+                Self::CMPr64i32
+                | Self::CMPri8
+                | Self::CMPri16
+                | Self::CMPri32
+                | Self::CMPrr8
+                | Self::CMPrr16
+                | Self::CMPrr32
+                | Self::CMPrr64 => "cmp",
+                Self::TESTrr8 => "test",
+                Self::SETEr8 => "sete",
+                Self::SETNEr8 => "setne",
+                Self::SETLr8 => "setl",
+                Self::SETLEr8 => "setle",
+                Self::SETGr8 => "setg",
+                Self::SETGEr8 => "setge",
+                Self::SETBr8 => "setb",
+                Self::SETBEr8 => "setbe",
+                Self::SETAr8 => "seta",
+                Self::SETAEr8 => "setae",
+                Self::CMOVNErr32 | Self::CMOVNErr64 => "cmovne",
+                Self::MOVSSrr | Self::MOVSSrm | Self::MOVSSmr => "movss",
+                Self::MOVSDrr | Self::MOVSDrm | Self::MOVSDmr => "movsd",
+                Self::MOVDrr32 => "movd",
+                Self::MOVQrr64 => "movq",
+                Self::ADDSSrr => "addss",
+                Self::ADDSDrr => "addsd",
+                Self::SUBSSrr => "subss",
+                Self::SUBSDrr => "subsd",
+                Self::MULSSrr => "mulss",
+                Self::MULSDrr => "mulsd",
+                Self::DIVSSrr => "divss",
+                Self::DIVSDrr => "divsd",
+                Self::UCOMISSrr => "ucomiss",
+                Self::UCOMISDrr => "ucomisd",
+                Self::CVTSI2SSrr32 | Self::CVTSI2SSrr64 => "cvtsi2ss",
+                Self::CVTSI2SDrr32 | Self::CVTSI2SDrr64 => "cvtsi2sd",
+                Self::CVTTSS2SIr32r | Self::CVTTSS2SIr64r => "cvttss2si",
+                Self::CVTTSD2SIr32r | Self::CVTTSD2SIr64r => "cvttsd2si",
+                Self::CVTSS2SDrr => "cvtss2sd",
+                Self::CVTSD2SSrr => "cvtsd2ss",
                 Self::JMP => "jmp",
                 Self::JE => "je",
                 Self::JNE => "jne",
@@ -275,6 +250,13 @@ impl fmt::Display for Opcode {
                 Self::JL => "jl",
                 Self::JGE => "jge",
                 Self::JG => "jg",
+                Self::JA => "ja",
+                Self::JAE => "jae",
+                Self::JB => "jb",
+                Self::JBE => "jbe",
+                Self::JP => "jp",
+                Self::JNP => "jnp",
+                Self::JMPr64 => "jmp",
                 Self::CALL => "call",
                 Self::RET => "ret",
                 Self::Phi => "PHI",
@@ -295,6 +277,7 @@ fn write_operand(f: &mut fmt::Formatter<'_>, op: &OperandData, fn_idx: usize) ->
         OperandData::Label(name) => write!(f, "{}", name),
         OperandData::MemStart => Ok(()),
         OperandData::GlobalAddress(name) => write!(f, "offset {}", name),
+        OperandData::JumpTable(i) => write!(f, ".LJTI{}_{}", fn_idx, i),
         OperandData::None => write!(f, "none"),
     }
 }
@@ -302,14 +285,22 @@ fn write_operand(f: &mut fmt::Formatter<'_>, op: &OperandData, fn_idx: usize) ->
 fn mem_size(opcode: &Opcode) -> &'static str {
     match opcode {
         Opcode::MOVrm8 | Opcode::MOVmr8 | Opcode::MOVmi8 => "byte ptr",
-        Opcode::MOVrm32 | Opcode::MOVmi32 | Opcode::MOVmr32 | Opcode::MOVSXDr64m32 => "dword ptr",
-        Opcode::MOVmi64 | Opcode::MOVrm64 | Opcode::MOVmr64 => "qword ptr",
+        Opcode::MOVrm16 | Opcode::MOVmr16 | Opcode::MOVmi16 => "word ptr",
+        Opcode::MOVrm32
+        | Opcode::MOVmi32
+        | Opcode::MOVmr32
+        | Opcode::MOVSXDr64m32
+        | Opcode::MOVSSrm
+        | Opcode::MOVSSmr => "dword ptr",
+        Opcode::MOVmi64 | Opcode::MOVrm64 | Opcode::MOVmr64 | Opcode::MOVSDrm | Opcode::MOVSDmr => {
+            "qword ptr"
+        }
         Opcode::LEArm64 => "",
         e => todo!("{:?}", e),
     }
 }
 
-fn mem_op(args: &[Operand]) -> String {
+fn mem_op(args: &[Operand], fn_idx: usize) -> String {
     assert!(matches!(&args[1].data, &OperandData::None)); // assure slot is eliminated
     match (
         &args[0].data,
@@ -384,6 +375,15 @@ fn mem_op(args: &[Operand]) -> String {
         ) => {
             format!("[{} + {lbl}]", reg_to_str(reg1))
         }
+        (
+            OperandData::JumpTable(i),
+            OperandData::None,
+            OperandData::Reg(reg1),
+            OperandData::None,
+            OperandData::None,
+        ) => {
+            format!("[{} + .LJTI{fn_idx}_{i}]", reg_to_str(reg1))
+        }
         e => todo!("{:?}", e),
     }
 }
@@ -0,0 +1,487 @@
+use crate::{
+    isa::{
+        x86_64::{
+            encode::{encode_function, EncodingError, RelocKind, Relocation},
+            X86_64,
+        },
+        TargetIsa,
+    },
+    module::Module,
+};
+use anyhow::Result;
+use rustc_hash::FxHashMap;
+use vicis_core::ir::{
+    module::{global_variable::GlobalVariable, linkage::Linkage, name::Name},
+    value::{ConstantArray, ConstantInt, ConstantStruct, ConstantValue},
+};
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+const SHT_INIT_ARRAY: u32 = 14;
+
+pub(crate) const SHF_WRITE: u64 = 0x1;
+pub(crate) const SHF_ALLOC: u64 = 0x2;
+pub(crate) const SHF_EXECINSTR: u64 = 0x4;
+const SHF_INFO_LINK: u64 = 0x40;
+
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+
+const SHN_UNDEF: u16 = 0;
+const SHN_COMMON: u16 = 0xfff2;
+
+const EM_X86_64: u16 = 62;
+
+/// Writes `module` as an ELF relocatable object (`*.o`), which contains the same code and data as
+/// [`Module::display_asm`] would after being assembled.
+pub fn write(module: &Module<X86_64>) -> Result<Vec<u8>> {
+    Ok(build(module)?.finish())
+}
+
+/// Lays out the code and data of `module` in sections.
+pub(crate) fn build(module: &Module<X86_64>) -> Result<Object> {
+    let mut obj = Object::default();
+
+    for (_, func) in &module.functions {
+        if func.is_declaration {
+            continue;
+        }
+        let encoded = encode_function(func)?;
+        let name = func.ir.section.as_deref().unwrap_or(".text");
+        let section = obj.section(name, SHF_ALLOC | SHF_EXECINSTR, 16);
+        let offset = obj.append(section, &encoded.code, encoded.relocs);
+        obj.define(Symbol {
+            name: func.ir.name().to_owned(),
+            section: SymbolSection::Section(section),
+            value: offset as u64,
+            size: encoded.code.len() as u64,
+            global: !func.ir.linkage.is_internal(),
+            kind: STT_FUNC,
+        });
+    }
+
+    let mut ctor = None;
+
+    for gv in module.ir.global_variables().values() {
+        let is_extern = matches!(
+            gv.linkage,
+            Some(Linkage::External) | Some(Linkage::ExternalWeak)
+        );
+        let init = match &gv.init {
+            Some(init) if !is_extern => init,
+            _ => continue,
+        };
+
+        match init {
+            ConstantValue::Array(arr) => {
+                if matches!(gv.name, Name::Name(ref name) if name == "llvm.global_ctors") {
+                    // TODO: Support multiple constructors.
+                    if let ConstantValue::Struct(ConstantStruct { elems, .. }) = &arr.elems[0] {
+                        if let ConstantValue::GlobalRef(name, _) = &elems[1] {
+                            ctor = Some(name.as_string().clone());
+                        }
+                    }
+                    continue;
+                }
+
+                if !arr.is_string {
+                    continue;
+                }
+
+                // Same as `.string` with trailing NULs trimmed.
+                let mut s: Vec<u8> = arr
+                    .elems
+                    .iter()
+                    .map(|elem| *elem.as_int().unwrap().as_i8() as u8)
+                    .collect();
+                while s.last() == Some(&0) {
+                    s.pop();
+                }
+                s.push(0);
+                let section = obj.section(".rodata", SHF_ALLOC, 1);
+                obj.define_data(section, gv, &s, false);
+            }
+            _ => write_data_init(&mut obj, module, gv)?,
+        }
+    }
+
+    if let Some(ctor) = ctor {
+        let section = obj.section(".init_array", SHF_ALLOC | SHF_WRITE, 8);
+        obj.sections[section].ty = SHT_INIT_ARRAY;
+        obj.append(
+            section,
+            &[0; 8],
+            vec![Relocation {
+                offset: 0,
+                kind: RelocKind::Abs64,
+                symbol: ctor,
+                addend: 0,
+            }],
+        );
+    }
+
+    // Marks the stack as non-executable.
+    obj.section(".note.GNU-stack", 0, 1);
+
+    // Symbols referred to but not defined are external.
+    let referred: Vec<String> = obj
+        .sections
+        .iter()
+        .flat_map(|s| s.relocs.iter().map(|r| r.symbol.clone()))
+        .collect();
+    for name in referred {
+        if !obj.symbol_index.contains_key(&name) {
+            obj.define(Symbol {
+                name,
+                section: SymbolSection::Undef,
+                value: 0,
+                size: 0,
+                global: true,
+                kind: STT_NOTYPE,
+            })
+        }
+    }
+
+    Ok(obj)
+}
+
+fn write_data_init(obj: &mut Object, module: &Module<X86_64>, gvar: &GlobalVariable) -> Result<()> {
+    let dl = module.isa.data_layout();
+    match gvar.init.as_ref().unwrap() {
+        ConstantValue::AggregateZero(ty) => obj.define(Symbol {
+            name: gvar.name.as_string().clone(),
+            section: SymbolSection::Common,
+            value: dl.get_align_of(&module.types, *ty) as u64,
+            size: dl.get_size_of(&module.types, *ty) as u64,
+            global: true,
+            kind: STT_OBJECT,
+        }),
+        ConstantValue::Int(i) => {
+            let section = obj.section(".data", SHF_ALLOC | SHF_WRITE, 1);
+            let global = !gvar.linkage.is_some_and(|l| l.is_internal());
+            obj.define_data(section, gvar, &int_bytes(i), global)
+        }
+        init @ ConstantValue::Struct(ConstantStruct {
+            is_packed: true, ..
+        }) => {
+            let mut data = vec![];
+            data_init_bytes(module, init, &mut data)?;
+            let section = obj.section(".data", SHF_ALLOC | SHF_WRITE, 1);
+            obj.define_data(section, gvar, &data, false)
+        }
+        e => {
+            return Err(EncodingError::Unsupported(format!("initializer {:?}", e)).into());
+        }
+    }
+    Ok(())
+}
+
+fn data_init_bytes(
+    module: &Module<X86_64>,
+    init: &ConstantValue,
+    data: &mut Vec<u8>,
+) -> Result<()> {
+    match init {
+        ConstantValue::AggregateZero(ty) => {
+            let size = module.isa.data_layout().get_size_of(&module.types, *ty);
+            data.resize(data.len() + size, 0)
+        }
+        ConstantValue::Int(i) => data.extend(int_bytes(i)),
+        ConstantValue::Struct(ConstantStruct {
+            elems,
+            is_packed: true,
+            ..
+        })
+        | ConstantValue::Array(ConstantArray {
+            elems,
+            is_string: false,
+            ..
+        }) => {
+            for elem in elems {
+                data_init_bytes(module, elem, data)?
+            }
+        }
+        e => return Err(EncodingError::Unsupported(format!("initializer {:?}", e)).into()),
+    }
+    Ok(())
+}
+
+fn int_bytes(i: &ConstantInt) -> Vec<u8> {
+    match i {
+        ConstantInt::Int1(b) => vec![*b as u8],
+        ConstantInt::Int8(i) => i.to_le_bytes().to_vec(),
+        ConstantInt::Int16(i) => i.to_le_bytes().to_vec(),
+        ConstantInt::Int32(i) => i.to_le_bytes().to_vec(),
+        ConstantInt::Int64(i) => i.to_le_bytes().to_vec(),
+    }
+}
+
+/// Sections and symbols of a relocatable object.
+#[derive(Default)]
+pub(crate) struct Object {
+    pub sections: Vec<Section>,
+    pub symbols: Vec<Symbol>,
+    pub symbol_index: FxHashMap<String, usize>,
+}
+
+pub(crate) struct Section {
+    pub name: String,
+    pub ty: u32,
+    pub flags: u64,
+    pub align: u64,
+    pub data: Vec<u8>,
+    pub relocs: Vec<Relocation>,
+}
+
+pub(crate) struct Symbol {
+    pub name: String,
+    pub section: SymbolSection,
+    pub value: u64,
+    pub size: u64,
+    pub global: bool,
+    pub kind: u8,
+}
+
+pub(crate) enum SymbolSection {
+    Undef,
+    Common,
+    /// Index in `Object::sections`.
+    Section(usize),
+}
+
+impl Object {
+    /// Returns the section named `name`, creating it if it doesn't exist.
+    fn section(&mut self, name: &str, flags: u64, align: u64) -> usize {
+        if let Some(i) = self.sections.iter().position(|s| s.name == name) {
+            return i;
+        }
+        self.sections.push(Section {
+            name: name.to_owned(),
+            ty: SHT_PROGBITS,
+            flags,
+            align,
+            data: vec![],
+            relocs: vec![],
+        });
+        self.sections.len() - 1
+    }
+
+    /// Appends `data` to `section` and returns the offset it's placed at.
+    fn append(&mut self, section: usize, data: &[u8], relocs: Vec<Relocation>) -> usize {
+        let section = &mut self.sections[section];
+        let offset = section.data.len();
+        section.data.extend(data);
+        section
+            .relocs
+            .extend(relocs.into_iter().map(|r| Relocation {
+                offset: r.offset + offset,
+                ..r
+            }));
+        offset
+    }
+
+    fn define(&mut self, symbol: Symbol) {
+        self.symbol_index
+            .insert(symbol.name.clone(), self.symbols.len());
+        self.symbols.push(symbol)
+    }
+
+    fn define_data(&mut self, section: usize, gvar: &GlobalVariable, data: &[u8], global: bool) {
+        let offset = self.append(section, data, vec![]);
+        self.define(Symbol {
+            name: gvar.name.as_string().clone(),
+            section: SymbolSection::Section(section),
+            value: offset as u64,
+            size: data.len() as u64,
+            global,
+            kind: STT_OBJECT,
+        })
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        // Local symbols must precede global ones. The null symbol comes first.
+        let mut order: Vec<usize> = (0..self.symbols.len()).collect();
+        order.sort_by_key(|&i| self.symbols[i].global);
+        let num_locals = 1 + order.iter().filter(|&&i| !self.symbols[i].global).count();
+        let mut sym_indices = vec![0; self.symbols.len()];
+        for (i, &sym) in order.iter().enumerate() {
+            sym_indices[sym] = i + 1;
+        }
+
+        // Section indices: null, the sections with contents, the relocations of them, .symtab,
+        // .strtab and .shstrtab.
+        let with_relocs: Vec<usize> = (0..self.sections.len())
+            .filter(|&i| !self.sections[i].relocs.is_empty())
+            .collect();
+        let symtab_idx = 1 + self.sections.len() + with_relocs.len();
+
+        let mut strtab = vec![0];
+        let mut symtab = vec![0; 24];
+        for &i in &order {
+            let sym = &self.symbols[i];
+            let shndx = match sym.section {
+                SymbolSection::Undef => SHN_UNDEF,
+                SymbolSection::Common => SHN_COMMON,
+                SymbolSection::Section(s) => s as u16 + 1,
+            };
+            let bind = if sym.global { STB_GLOBAL } else { STB_LOCAL };
+            symtab.extend((strtab.len() as u32).to_le_bytes());
+            symtab.push(bind << 4 | sym.kind);
+            symtab.push(0);
+            symtab.extend(shndx.to_le_bytes());
+            symtab.extend(sym.value.to_le_bytes());
+            symtab.extend(sym.size.to_le_bytes());
+            strtab.extend(sym.name.as_bytes());
+            strtab.push(0);
+        }
+
+        let mut headers = vec![];
+        for section in &self.sections {
+            headers.push(SectionHeader {
+                name: section.name.clone(),
+                ty: section.ty,
+                flags: section.flags,
+                link: 0,
+                info: 0,
+                align: section.align,
+                entsize: 0,
+            });
+        }
+        let mut contents: Vec<Vec<u8>> = self
+            .sections
+            .iter_mut()
+            .map(|s| std::mem::take(&mut s.data))
+            .collect();
+        for &i in &with_relocs {
+            let mut rela = vec![];
+            for reloc in &self.sections[i].relocs {
+                let sym = sym_indices[self.symbol_index[&reloc.symbol]] as u64;
+                let ty = match reloc.kind {
+                    RelocKind::Abs64 => 1,
+                    RelocKind::PcRel32 => 2,
+                    RelocKind::Plt32 => 4,
+                    RelocKind::Abs32 => 10,
+                    RelocKind::Abs32S => 11,
+                };
+                rela.extend((reloc.offset as u64).to_le_bytes());
+                rela.extend((sym << 32 | ty).to_le_bytes());
+                rela.extend(reloc.addend.to_le_bytes());
+            }
+            headers.push(SectionHeader {
+                name: format!(".rela{}", self.sections[i].name),
+                ty: SHT_RELA,
+                flags: SHF_INFO_LINK,
+                link: symtab_idx as u32,
+                info: i as u32 + 1,
+                align: 8,
+                entsize: 24,
+            });
+            contents.push(rela);
+        }
+        headers.push(SectionHeader {
+            name: ".symtab".to_owned(),
+            ty: SHT_SYMTAB,
+            flags: 0,
+            link: symtab_idx as u32 + 1,
+            info: num_locals as u32,
+            align: 8,
+            entsize: 24,
+        });
+        contents.push(symtab);
+        headers.push(SectionHeader {
+            name: ".strtab".to_owned(),
+            ty: SHT_STRTAB,
+            flags: 0,
+            link: 0,
+            info: 0,
+            align: 1,
+            entsize: 0,
+        });
+        contents.push(strtab);
+        headers.push(SectionHeader {
+            name: ".shstrtab".to_owned(),
+            ty: SHT_STRTAB,
+            flags: 0,
+            link: 0,
+            info: 0,
+            align: 1,
+            entsize: 0,
+        });
+        let mut shstrtab = vec![0];
+        let mut name_offsets = vec![];
+        for header in &headers {
+            name_offsets.push(shstrtab.len() as u32);
+            shstrtab.extend(header.name.as_bytes());
+            shstrtab.push(0);
+        }
+        contents.push(shstrtab);
+
+        // ELF header, the contents of the sections, then the section header table.
+        const EHDR_SIZE: usize = 64;
+        const SHDR_SIZE: usize = 64;
+        let mut out = vec![0; EHDR_SIZE];
+        let mut offsets = vec![];
+        for (header, content) in headers.iter().zip(&contents) {
+            let align = header.align.max(1) as usize;
+            out.resize(out.len().div_ceil(align) * align, 0);
+            offsets.push(out.len());
+            out.extend(content);
+        }
+        out.resize(out.len().div_ceil(8) * 8, 0);
+        let shoff = out.len();
+
+        out.extend([0; SHDR_SIZE]);
+        for (i, header) in headers.iter().enumerate() {
+            out.extend(name_offsets[i].to_le_bytes());
+            out.extend(header.ty.to_le_bytes());
+            out.extend(header.flags.to_le_bytes());
+            out.extend(0u64.to_le_bytes()); // addr
+            out.extend((offsets[i] as u64).to_le_bytes());
+            out.extend((contents[i].len() as u64).to_le_bytes());
+            out.extend(header.link.to_le_bytes());
+            out.extend(header.info.to_le_bytes());
+            out.extend(header.align.to_le_bytes());
+            out.extend(header.entsize.to_le_bytes());
+        }
+
+        let mut ehdr = vec![];
+        ehdr.extend(b"\x7fELF");
+        ehdr.extend([
+            2, /* 64-bit */
+            1, /* little endian */
+            1, /* version */
+        ]);
+        ehdr.resize(16, 0);
+        ehdr.extend(1u16.to_le_bytes()); // ET_REL
+        ehdr.extend(EM_X86_64.to_le_bytes());
+        ehdr.extend(1u32.to_le_bytes()); // version
+        ehdr.extend(0u64.to_le_bytes()); // entry
+        ehdr.extend(0u64.to_le_bytes()); // phoff
+        ehdr.extend((shoff as u64).to_le_bytes());
+        ehdr.extend(0u32.to_le_bytes()); // flags
+        ehdr.extend((EHDR_SIZE as u16).to_le_bytes());
+        ehdr.extend(0u16.to_le_bytes()); // phentsize
+        ehdr.extend(0u16.to_le_bytes()); // phnum
+        ehdr.extend((SHDR_SIZE as u16).to_le_bytes());
+        ehdr.extend((headers.len() as u16 + 1).to_le_bytes());
+        ehdr.extend((headers.len() as u16).to_le_bytes()); // .shstrtab is the last one
+        out[..EHDR_SIZE].copy_from_slice(&ehdr);
+
+        out
+    }
+}
+
+struct SectionHeader {
+    name: String,
+    ty: u32,
+    flags: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+    entsize: u64,
+}
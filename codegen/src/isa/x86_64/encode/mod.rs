@@ -0,0 +1,856 @@
+use crate::{
+    function::{basic_block::BasicBlockId, Function},
+    isa::x86_64::{
+        instruction::{InstructionData, Opcode, OperandData},
+        register::{RegClass, GR64},
+        X86_64,
+    },
+    register::Reg,
+};
+use anyhow::Result;
+use rustc_hash::FxHashMap;
+use std::{error::Error, fmt};
+
+/// Machine code of a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedFunction {
+    pub code: Vec<u8>,
+    pub relocs: Vec<Relocation>,
+}
+
+/// A reference to a symbol in `code`, which is resolved by the linker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relocation {
+    /// Offset of the field to patch.
+    pub offset: usize,
+    pub kind: RelocKind,
+    pub symbol: String,
+    pub addend: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelocKind {
+    /// `S + A` in 64 bits (`R_X86_64_64`).
+    Abs64,
+    /// `S + A` zero-extended from 32 bits (`R_X86_64_32`).
+    Abs32,
+    /// `S + A` sign-extended from 32 bits (`R_X86_64_32S`).
+    Abs32S,
+    /// `S + A - P` in 32 bits (`R_X86_64_PC32`).
+    PcRel32,
+    /// `L + A - P` in 32 bits, where `L` is the PLT entry of the symbol (`R_X86_64_PLT32`).
+    Plt32,
+}
+
+#[derive(Debug)]
+pub enum EncodingError {
+    Unsupported(String),
+}
+
+/// A register or memory operand, encoded in ModRM (and SIB).
+enum RM<'a> {
+    Reg(u8),
+    Mem(Mem<'a>),
+}
+
+/// A memory operand, `[base + index * scale + disp + label]`.
+struct Mem<'a> {
+    label: Option<&'a str>,
+    /// A jump table of the function in place of `label`, which is always RIP-relative.
+    table: Option<usize>,
+    disp: i32,
+    base: Option<u8>,
+    index: Option<(u8, u8)>,
+    rip: bool,
+}
+
+/// A RIP-relative reference to a jump table, which is resolved once the code size is known.
+struct TableRef {
+    offset: usize,
+    table: usize,
+    addend: i64,
+}
+
+/// An instruction encoded on its own. Jumps are kept until the layout of blocks is known.
+enum Chunk {
+    Code(Vec<u8>, Vec<Relocation>, Vec<TableRef>),
+    Jump(Opcode, BasicBlockId),
+}
+
+/// Encodes `function` into machine code.
+/// Jumps to blocks are resolved, and references to symbols are left as relocations.
+/// Jump tables follow the code, holding the offsets of blocks from the table.
+pub fn encode_function(function: &Function<X86_64>) -> Result<EncodedFunction> {
+    let mut chunks = vec![];
+    let mut block_starts = vec![];
+    for block in function.layout.block_iter() {
+        block_starts.push((chunks.len(), block));
+        for inst in function.layout.inst_iter(block) {
+            let inst = &function.data.inst_ref(inst).data;
+            if let Some(target) = jump_target(inst) {
+                chunks.push(Chunk::Jump(inst.opcode, target));
+                continue;
+            }
+            let mut enc = Encoder::default();
+            enc.encode(inst)?;
+            chunks.push(Chunk::Code(enc.code, enc.relocs, enc.table_refs));
+        }
+    }
+
+    // Every jump starts in the short form, and is relaxed to the long form if its target is out of
+    // range. Relaxing a jump never shortens others, so this terminates.
+    let mut long = vec![false; chunks.len()];
+    let (offsets, block_offsets) = loop {
+        let mut offsets = Vec::with_capacity(chunks.len() + 1);
+        let mut offset = 0;
+        for (i, chunk) in chunks.iter().enumerate() {
+            offsets.push(offset);
+            offset += match chunk {
+                Chunk::Code(code, _, _) => code.len(),
+                Chunk::Jump(opcode, _) => jump_size(*opcode, long[i]),
+            };
+        }
+        offsets.push(offset);
+        let block_offsets: FxHashMap<_, _> = block_starts
+            .iter()
+            .map(|&(i, block)| (block, offsets[i]))
+            .collect();
+
+        let mut changed = false;
+        for (i, chunk) in chunks.iter().enumerate() {
+            let target = match chunk {
+                Chunk::Jump(_, target) if !long[i] => target,
+                _ => continue,
+            };
+            if i8::try_from(block_offsets[target] as i64 - offsets[i + 1] as i64).is_err() {
+                long[i] = true;
+                changed = true;
+            }
+        }
+        if !changed {
+            break (offsets, block_offsets);
+        }
+    };
+
+    let tables_start = if function.data.jump_tables.is_empty() {
+        offsets[chunks.len()]
+    } else {
+        (offsets[chunks.len()] + 3) & !3
+    };
+    let mut table_offsets = vec![];
+    let mut table_offset = tables_start;
+    for table in &function.data.jump_tables {
+        table_offsets.push(table_offset);
+        table_offset += table.len() * 4;
+    }
+
+    let mut code = Vec::with_capacity(table_offset);
+    let mut relocs = vec![];
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        match chunk {
+            Chunk::Code(mut c, rs, ts) => {
+                relocs.extend(rs.into_iter().map(|r| Relocation {
+                    offset: r.offset + offsets[i],
+                    ..r
+                }));
+                for t in ts {
+                    let rel =
+                        table_offsets[t.table] as i64 + t.addend - (offsets[i] + t.offset) as i64;
+                    c[t.offset..t.offset + 4].copy_from_slice(&(rel as i32).to_le_bytes())
+                }
+                code.extend(c)
+            }
+            Chunk::Jump(opcode, target) => {
+                let rel = block_offsets[&target] as i64 - offsets[i + 1] as i64;
+                let cc = condition_code(opcode);
+                match (long[i], cc) {
+                    (false, None) => code.extend([0xeb, rel as i8 as u8]),
+                    (false, Some(cc)) => code.extend([0x70 | cc, rel as i8 as u8]),
+                    (true, None) => code.push(0xe9),
+                    (true, Some(cc)) => code.extend([0x0f, 0x80 | cc]),
+                }
+                if long[i] {
+                    code.extend((rel as i32).to_le_bytes())
+                }
+            }
+        }
+    }
+
+    code.resize(tables_start, 0xcc);
+    for (table, &table_offset) in function.data.jump_tables.iter().zip(&table_offsets) {
+        for block in table {
+            let rel = block_offsets[block] as i64 - table_offset as i64;
+            code.extend((rel as i32).to_le_bytes())
+        }
+    }
+
+    Ok(EncodedFunction { code, relocs })
+}
+
+fn jump_target(inst: &InstructionData) -> Option<BasicBlockId> {
+    if !matches!(
+        inst.opcode,
+        Opcode::JMP
+            | Opcode::JE
+            | Opcode::JNE
+            | Opcode::JLE
+            | Opcode::JL
+            | Opcode::JGE
+            | Opcode::JG
+            | Opcode::JA
+            | Opcode::JAE
+            | Opcode::JB
+            | Opcode::JBE
+            | Opcode::JP
+            | Opcode::JNP
+    ) {
+        return None;
+    }
+    inst.operands.iter().find_map(|op| match op.data {
+        OperandData::Block(block) => Some(block),
+        _ => None,
+    })
+}
+
+fn jump_size(opcode: Opcode, long: bool) -> usize {
+    match (long, opcode) {
+        (false, _) => 2,
+        (true, Opcode::JMP) => 5,
+        (true, _) => 6,
+    }
+}
+
+fn condition_code(opcode: Opcode) -> Option<u8> {
+    match opcode {
+        Opcode::JB => Some(0x2),
+        Opcode::JAE => Some(0x3),
+        Opcode::JE => Some(0x4),
+        Opcode::JNE => Some(0x5),
+        Opcode::JBE => Some(0x6),
+        Opcode::JA => Some(0x7),
+        Opcode::JP => Some(0xa),
+        Opcode::JNP => Some(0xb),
+        Opcode::JL => Some(0xc),
+        Opcode::JGE => Some(0xd),
+        Opcode::JLE => Some(0xe),
+        Opcode::JG => Some(0xf),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct Encoder {
+    code: Vec<u8>,
+    relocs: Vec<Relocation>,
+    table_refs: Vec<TableRef>,
+}
+
+impl Encoder {
+    fn encode(&mut self, inst: &InstructionData) -> Result<()> {
+        let ops: Vec<&OperandData> = inst
+            .operands
+            .iter()
+            .filter(|op| !op.implicit)
+            .map(|op| &op.data)
+            .collect();
+
+        match inst.opcode {
+            Opcode::PUSH64 | Opcode::POP64 => {
+                let r = reg(ops[0])?;
+                let base = if inst.opcode == Opcode::PUSH64 {
+                    0x50
+                } else {
+                    0x58
+                };
+                if r >= 8 {
+                    self.code.push(0x41)
+                }
+                self.code.push(base + (r & 7))
+            }
+            Opcode::ADDri8 => self.arith_ri(1, 0, ops[0], ops[1])?,
+            Opcode::ADDri16 => self.arith_ri(2, 0, ops[0], ops[1])?,
+            Opcode::ADDri32 => self.arith_ri(4, 0, ops[0], ops[1])?,
+            Opcode::ADDr64i32 => self.arith_ri(8, 0, ops[0], ops[1])?,
+            Opcode::ORri8 => self.arith_ri(1, 1, ops[0], ops[1])?,
+            Opcode::ORri16 => self.arith_ri(2, 1, ops[0], ops[1])?,
+            Opcode::ORri32 => self.arith_ri(4, 1, ops[0], ops[1])?,
+            Opcode::ORr64i32 => self.arith_ri(8, 1, ops[0], ops[1])?,
+            Opcode::ANDri8 => self.arith_ri(1, 4, ops[0], ops[1])?,
+            Opcode::ANDri16 => self.arith_ri(2, 4, ops[0], ops[1])?,
+            Opcode::ANDri32 => self.arith_ri(4, 4, ops[0], ops[1])?,
+            Opcode::ANDr64i32 => self.arith_ri(8, 4, ops[0], ops[1])?,
+            Opcode::SUBri8 => self.arith_ri(1, 5, ops[0], ops[1])?,
+            Opcode::SUBri16 => self.arith_ri(2, 5, ops[0], ops[1])?,
+            Opcode::SUBri32 => self.arith_ri(4, 5, ops[0], ops[1])?,
+            Opcode::SUBr64i32 => self.arith_ri(8, 5, ops[0], ops[1])?,
+            Opcode::XORri8 => self.arith_ri(1, 6, ops[0], ops[1])?,
+            Opcode::XORri16 => self.arith_ri(2, 6, ops[0], ops[1])?,
+            Opcode::XORri32 => self.arith_ri(4, 6, ops[0], ops[1])?,
+            Opcode::XORr64i32 => self.arith_ri(8, 6, ops[0], ops[1])?,
+            Opcode::CMPri8 => self.arith_ri(1, 7, ops[0], ops[1])?,
+            Opcode::CMPri16 => self.arith_ri(2, 7, ops[0], ops[1])?,
+            Opcode::CMPri32 => self.arith_ri(4, 7, ops[0], ops[1])?,
+            Opcode::CMPr64i32 => self.arith_ri(8, 7, ops[0], ops[1])?,
+            Opcode::ADDrr8 => self.arith_rr(1, 0x00, ops[0], ops[1])?,
+            Opcode::ADDrr16 => self.arith_rr(2, 0x00, ops[0], ops[1])?,
+            Opcode::ADDrr32 => self.arith_rr(4, 0x00, ops[0], ops[1])?,
+            Opcode::ADDrr64 => self.arith_rr(8, 0x00, ops[0], ops[1])?,
+            Opcode::ORrr8 => self.arith_rr(1, 0x08, ops[0], ops[1])?,
+            Opcode::ORrr16 => self.arith_rr(2, 0x08, ops[0], ops[1])?,
+            Opcode::ORrr32 => self.arith_rr(4, 0x08, ops[0], ops[1])?,
+            Opcode::ORrr64 => self.arith_rr(8, 0x08, ops[0], ops[1])?,
+            Opcode::ANDrr8 => self.arith_rr(1, 0x20, ops[0], ops[1])?,
+            Opcode::ANDrr16 => self.arith_rr(2, 0x20, ops[0], ops[1])?,
+            Opcode::ANDrr32 => self.arith_rr(4, 0x20, ops[0], ops[1])?,
+            Opcode::ANDrr64 => self.arith_rr(8, 0x20, ops[0], ops[1])?,
+            Opcode::SUBrr8 => self.arith_rr(1, 0x28, ops[0], ops[1])?,
+            Opcode::SUBrr16 => self.arith_rr(2, 0x28, ops[0], ops[1])?,
+            Opcode::SUBrr32 => self.arith_rr(4, 0x28, ops[0], ops[1])?,
+            Opcode::SUBrr64 => self.arith_rr(8, 0x28, ops[0], ops[1])?,
+            Opcode::XORrr8 => self.arith_rr(1, 0x30, ops[0], ops[1])?,
+            Opcode::XORrr16 => self.arith_rr(2, 0x30, ops[0], ops[1])?,
+            Opcode::XORrr32 => self.arith_rr(4, 0x30, ops[0], ops[1])?,
+            Opcode::XORrr64 => self.arith_rr(8, 0x30, ops[0], ops[1])?,
+            Opcode::CMPrr8 => self.arith_rr(1, 0x38, ops[0], ops[1])?,
+            Opcode::CMPrr16 => self.arith_rr(2, 0x38, ops[0], ops[1])?,
+            Opcode::CMPrr32 => self.arith_rr(4, 0x38, ops[0], ops[1])?,
+            Opcode::CMPrr64 => self.arith_rr(8, 0x38, ops[0], ops[1])?,
+            Opcode::MOVrr8 => self.arith_rr(1, 0x88, ops[0], ops[1])?,
+            Opcode::MOVrr16 => self.arith_rr(2, 0x88, ops[0], ops[1])?,
+            Opcode::MOVrr32 => self.arith_rr(4, 0x88, ops[0], ops[1])?,
+            Opcode::MOVrr64 => self.arith_rr(8, 0x88, ops[0], ops[1])?,
+            Opcode::MOVZXr64r32 => self.arith_rr(4, 0x88, ops[0], ops[1])?,
+            Opcode::TESTrr8 => self.arith_rr(1, 0x84, ops[0], ops[1])?,
+            Opcode::IMULrr32 => self.rr(false, &[0x0f, 0xaf], ops[1], ops[0])?,
+            Opcode::IMULrr64 => self.rr(true, &[0x0f, 0xaf], ops[1], ops[0])?,
+            Opcode::CMOVNErr32 => self.rr(false, &[0x0f, 0x45], ops[1], ops[0])?,
+            Opcode::CMOVNErr64 => self.rr(true, &[0x0f, 0x45], ops[1], ops[0])?,
+            Opcode::MOVSXr32r8 => self.rr(false, &[0x0f, 0xbe], ops[1], ops[0])?,
+            Opcode::MOVSXr32r16 => self.rr(false, &[0x0f, 0xbf], ops[1], ops[0])?,
+            Opcode::MOVSXr64r8 => self.rr(true, &[0x0f, 0xbe], ops[1], ops[0])?,
+            Opcode::MOVSXr64r16 => self.rr(true, &[0x0f, 0xbf], ops[1], ops[0])?,
+            Opcode::MOVSXDr64r32 => self.rr(true, &[0x63], ops[1], ops[0])?,
+            Opcode::MOVZXr32r8 => self.rr(false, &[0x0f, 0xb6], ops[1], ops[0])?,
+            Opcode::MOVZXr32r16 => self.rr(false, &[0x0f, 0xb7], ops[1], ops[0])?,
+            Opcode::NEGr32 => self.modrm(false, false, &[0xf7], 3, &RM::Reg(reg(ops[0])?))?,
+            Opcode::NEGr64 => self.modrm(true, false, &[0xf7], 3, &RM::Reg(reg(ops[0])?))?,
+            Opcode::DIVr32 => self.modrm(false, false, &[0xf7], 6, &RM::Reg(reg(ops[0])?))?,
+            Opcode::DIVr64 => self.modrm(true, false, &[0xf7], 6, &RM::Reg(reg(ops[0])?))?,
+            Opcode::IDIVr32 => self.modrm(false, false, &[0xf7], 7, &RM::Reg(reg(ops[0])?))?,
+            Opcode::IDIVr64 => self.modrm(true, false, &[0xf7], 7, &RM::Reg(reg(ops[0])?))?,
+            Opcode::CDQ => self.code.push(0x99),
+            Opcode::CQO => self.code.extend([0x48, 0x99]),
+            Opcode::SHLr8CL => self.shift(1, 4, ops[0], None)?,
+            Opcode::SHLr16CL => self.shift(2, 4, ops[0], None)?,
+            Opcode::SHLr32CL => self.shift(4, 4, ops[0], None)?,
+            Opcode::SHLr64CL => self.shift(8, 4, ops[0], None)?,
+            Opcode::SHLr8i8 => self.shift(1, 4, ops[0], Some(ops[1]))?,
+            Opcode::SHLr16i8 => self.shift(2, 4, ops[0], Some(ops[1]))?,
+            Opcode::SHLr32i8 => self.shift(4, 4, ops[0], Some(ops[1]))?,
+            Opcode::SHLr64i8 => self.shift(8, 4, ops[0], Some(ops[1]))?,
+            Opcode::SHRr8CL => self.shift(1, 5, ops[0], None)?,
+            Opcode::SHRr16CL => self.shift(2, 5, ops[0], None)?,
+            Opcode::SHRr32CL => self.shift(4, 5, ops[0], None)?,
+            Opcode::SHRr64CL => self.shift(8, 5, ops[0], None)?,
+            Opcode::SHRr8i8 => self.shift(1, 5, ops[0], Some(ops[1]))?,
+            Opcode::SHRr16i8 => self.shift(2, 5, ops[0], Some(ops[1]))?,
+            Opcode::SHRr32i8 => self.shift(4, 5, ops[0], Some(ops[1]))?,
+            Opcode::SHRr64i8 => self.shift(8, 5, ops[0], Some(ops[1]))?,
+            Opcode::SARr8CL => self.shift(1, 7, ops[0], None)?,
+            Opcode::SARr16CL => self.shift(2, 7, ops[0], None)?,
+            Opcode::SARr32CL => self.shift(4, 7, ops[0], None)?,
+            Opcode::SARr64CL => self.shift(8, 7, ops[0], None)?,
+            Opcode::SARr8i8 => self.shift(1, 7, ops[0], Some(ops[1]))?,
+            Opcode::SARr16i8 => self.shift(2, 7, ops[0], Some(ops[1]))?,
+            Opcode::SARr32i8 => self.shift(4, 7, ops[0], Some(ops[1]))?,
+            Opcode::SARr64i8 => self.shift(8, 7, ops[0], Some(ops[1]))?,
+            Opcode::SETEr8
+            | Opcode::SETNEr8
+            | Opcode::SETLr8
+            | Opcode::SETLEr8
+            | Opcode::SETGr8
+            | Opcode::SETGEr8
+            | Opcode::SETBr8
+            | Opcode::SETBEr8
+            | Opcode::SETAr8
+            | Opcode::SETAEr8 => {
+                let cc = match inst.opcode {
+                    Opcode::SETBr8 => 0x2,
+                    Opcode::SETAEr8 => 0x3,
+                    Opcode::SETEr8 => 0x4,
+                    Opcode::SETNEr8 => 0x5,
+                    Opcode::SETBEr8 => 0x6,
+                    Opcode::SETAr8 => 0x7,
+                    Opcode::SETLr8 => 0xc,
+                    Opcode::SETGEr8 => 0xd,
+                    Opcode::SETLEr8 => 0xe,
+                    _ => 0xf,
+                };
+                let r = reg(ops[0])?;
+                self.modrm(false, needs_rex(ops[0]), &[0x0f, 0x90 | cc], 0, &RM::Reg(r))?
+            }
+            Opcode::IMULrr32i32 | Opcode::IMULrr64i32 => {
+                let w = inst.opcode == Opcode::IMULrr64i32;
+                let short = fits_i8(ops[2]);
+                let opcode = if short { 0x6b } else { 0x69 };
+                self.modrm(w, false, &[opcode], reg(ops[0])?, &RM::Reg(reg(ops[1])?))?;
+                if short {
+                    self.imm8(ops[2])?
+                } else {
+                    self.imm32(ops[2], RelocKind::Abs32S)?
+                }
+            }
+            Opcode::MOVri32 => {
+                let r = reg(ops[0])?;
+                if r >= 8 {
+                    self.code.push(0x41)
+                }
+                self.code.push(0xb8 + (r & 7));
+                self.imm32(ops[1], RelocKind::Abs32)?
+            }
+            Opcode::MOVri64 => {
+                let r = reg(ops[0])?;
+                if ops[1]
+                    .sext_as_i64()
+                    .is_none_or(|i| i32::try_from(i).is_ok())
+                {
+                    self.modrm(true, false, &[0xc7], 0, &RM::Reg(r))?;
+                    self.imm32(ops[1], RelocKind::Abs32S)?
+                } else {
+                    self.code.push(0x48 | (r >> 3));
+                    self.code.push(0xb8 + (r & 7));
+                    self.code
+                        .extend(ops[1].sext_as_i64().unwrap().to_le_bytes())
+                }
+            }
+            Opcode::MOVrm8 => self.rm(false, needs_rex(ops[0]), &[0x8a], ops[0], &ops[2..])?,
+            Opcode::MOVrm16 => {
+                self.code.push(0x66);
+                self.rm(false, false, &[0x8b], ops[0], &ops[2..])?
+            }
+            Opcode::MOVrm32 => self.rm(false, false, &[0x8b], ops[0], &ops[2..])?,
+            Opcode::MOVrm64 => self.rm(true, false, &[0x8b], ops[0], &ops[2..])?,
+            Opcode::MOVSXDr64m32 => self.rm(true, false, &[0x63], ops[0], &ops[2..])?,
+            Opcode::LEArm64 => self.rm(true, false, &[0x8d], ops[0], &ops[2..])?,
+            Opcode::MOVmr8 => self.rm(false, needs_rex(ops[7]), &[0x88], ops[7], &ops[1..7])?,
+            Opcode::MOVmr16 => {
+                self.code.push(0x66);
+                self.rm(false, false, &[0x89], ops[7], &ops[1..7])?
+            }
+            Opcode::MOVmr32 => self.rm(false, false, &[0x89], ops[7], &ops[1..7])?,
+            Opcode::MOVmr64 => self.rm(true, false, &[0x89], ops[7], &ops[1..7])?,
+            Opcode::MOVmi8 => {
+                self.modrm(false, false, &[0xc6], 0, &RM::Mem(mem(&ops[1..7])?))?;
+                self.imm8(ops[7])?
+            }
+            Opcode::MOVmi16 => {
+                self.code.push(0x66);
+                self.modrm(false, false, &[0xc7], 0, &RM::Mem(mem(&ops[1..7])?))?;
+                self.imm16(ops[7])?
+            }
+            Opcode::MOVmi32 => {
+                self.modrm(false, false, &[0xc7], 0, &RM::Mem(mem(&ops[1..7])?))?;
+                self.imm32(ops[7], RelocKind::Abs32S)?
+            }
+            Opcode::MOVmi64 => {
+                self.modrm(true, false, &[0xc7], 0, &RM::Mem(mem(&ops[1..7])?))?;
+                self.imm32(ops[7], RelocKind::Abs32S)?
+            }
+            Opcode::CALL => {
+                let name = match ops[0] {
+                    OperandData::Label(name) | OperandData::GlobalAddress(name) => name,
+                    _ => return Err(unsupported(inst)),
+                };
+                self.code.push(0xe8);
+                self.reloc(RelocKind::Plt32, name, 0);
+                self.code.extend([0; 4])
+            }
+            Opcode::RET => self.code.push(0xc3),
+            Opcode::JMPr64 => self.modrm(false, false, &[0xff], 4, &RM::Reg(reg(ops[0])?))?,
+            Opcode::MOVSSrr => self.sse_rr(Some(0xf3), false, 0x10, ops[0], ops[1])?,
+            Opcode::MOVSDrr => self.sse_rr(Some(0xf2), false, 0x10, ops[0], ops[1])?,
+            Opcode::MOVSSrm => self.sse_rm(Some(0xf3), 0x10, ops[0], &ops[2..])?,
+            Opcode::MOVSDrm => self.sse_rm(Some(0xf2), 0x10, ops[0], &ops[2..])?,
+            Opcode::MOVSSmr => self.sse_rm(Some(0xf3), 0x11, ops[7], &ops[1..7])?,
+            Opcode::MOVSDmr => self.sse_rm(Some(0xf2), 0x11, ops[7], &ops[1..7])?,
+            Opcode::MOVDrr32 => self.sse_rr(Some(0x66), false, 0x6e, ops[0], ops[1])?,
+            Opcode::MOVQrr64 => self.sse_rr(Some(0x66), true, 0x6e, ops[0], ops[1])?,
+            Opcode::ADDSSrr => self.sse_rr(Some(0xf3), false, 0x58, ops[0], ops[1])?,
+            Opcode::ADDSDrr => self.sse_rr(Some(0xf2), false, 0x58, ops[0], ops[1])?,
+            Opcode::SUBSSrr => self.sse_rr(Some(0xf3), false, 0x5c, ops[0], ops[1])?,
+            Opcode::SUBSDrr => self.sse_rr(Some(0xf2), false, 0x5c, ops[0], ops[1])?,
+            Opcode::MULSSrr => self.sse_rr(Some(0xf3), false, 0x59, ops[0], ops[1])?,
+            Opcode::MULSDrr => self.sse_rr(Some(0xf2), false, 0x59, ops[0], ops[1])?,
+            Opcode::DIVSSrr => self.sse_rr(Some(0xf3), false, 0x5e, ops[0], ops[1])?,
+            Opcode::DIVSDrr => self.sse_rr(Some(0xf2), false, 0x5e, ops[0], ops[1])?,
+            Opcode::UCOMISSrr => self.sse_rr(None, false, 0x2e, ops[0], ops[1])?,
+            Opcode::UCOMISDrr => self.sse_rr(Some(0x66), false, 0x2e, ops[0], ops[1])?,
+            Opcode::CVTSI2SSrr32 => self.sse_rr(Some(0xf3), false, 0x2a, ops[0], ops[1])?,
+            Opcode::CVTSI2SSrr64 => self.sse_rr(Some(0xf3), true, 0x2a, ops[0], ops[1])?,
+            Opcode::CVTSI2SDrr32 => self.sse_rr(Some(0xf2), false, 0x2a, ops[0], ops[1])?,
+            Opcode::CVTSI2SDrr64 => self.sse_rr(Some(0xf2), true, 0x2a, ops[0], ops[1])?,
+            Opcode::CVTTSS2SIr32r => self.sse_rr(Some(0xf3), false, 0x2c, ops[0], ops[1])?,
+            Opcode::CVTTSS2SIr64r => self.sse_rr(Some(0xf3), true, 0x2c, ops[0], ops[1])?,
+            Opcode::CVTTSD2SIr32r => self.sse_rr(Some(0xf2), false, 0x2c, ops[0], ops[1])?,
+            Opcode::CVTTSD2SIr64r => self.sse_rr(Some(0xf2), true, 0x2c, ops[0], ops[1])?,
+            Opcode::CVTSS2SDrr => self.sse_rr(Some(0xf3), false, 0x5a, ops[0], ops[1])?,
+            Opcode::CVTSD2SSrr => self.sse_rr(Some(0xf2), false, 0x5a, ops[0], ops[1])?,
+            Opcode::JMP
+            | Opcode::JE
+            | Opcode::JNE
+            | Opcode::JLE
+            | Opcode::JL
+            | Opcode::JGE
+            | Opcode::JG
+            | Opcode::JA
+            | Opcode::JAE
+            | Opcode::JB
+            | Opcode::JBE
+            | Opcode::JP
+            | Opcode::JNP
+            | Opcode::Phi => return Err(unsupported(inst)),
+        }
+
+        // PC-relative fields are relative to the end of the instruction, which may be followed by
+        // an immediate.
+        let end = self.code.len() as i64;
+        for reloc in &mut self.relocs {
+            if matches!(reloc.kind, RelocKind::PcRel32 | RelocKind::Plt32) {
+                reloc.addend += reloc.offset as i64 - end;
+            }
+        }
+        for table_ref in &mut self.table_refs {
+            table_ref.addend += table_ref.offset as i64 - end;
+        }
+
+        Ok(())
+    }
+
+    /// `op r/m, imm` of `size` bytes, using the short forms when possible as assemblers do.
+    fn arith_ri(&mut self, size: u8, ext: u8, dst: &OperandData, imm: &OperandData) -> Result<()> {
+        let r = reg(dst)?;
+        if size == 1 {
+            if r == 0 {
+                self.code.push(0x04 | ext << 3);
+            } else {
+                self.modrm(false, needs_rex(dst), &[0x80], ext, &RM::Reg(r))?;
+            }
+            return self.imm8(imm);
+        }
+        if size == 2 {
+            self.code.push(0x66)
+        }
+        let w = size == 8;
+        if fits_i8(imm) {
+            self.modrm(w, false, &[0x83], ext, &RM::Reg(r))?;
+            return self.imm8(imm);
+        }
+        if r == 0 {
+            // The accumulator has its own encoding.
+            if w {
+                self.code.push(0x48)
+            }
+            self.code.push(0x05 | ext << 3);
+        } else {
+            self.modrm(w, false, &[0x81], ext, &RM::Reg(r))?;
+        }
+        if size == 2 {
+            self.imm16(imm)
+        } else {
+            self.imm32(imm, RelocKind::Abs32S)
+        }
+    }
+
+    /// `op rm, reg` of `size` bytes, where `opcode8` is the opcode for 8 bits and the next one is
+    /// for the others.
+    fn arith_rr(&mut self, size: u8, opcode8: u8, rm: &OperandData, r: &OperandData) -> Result<()> {
+        if size == 2 {
+            self.code.push(0x66)
+        }
+        let opcode = if size == 1 { opcode8 } else { opcode8 + 1 };
+        self.rr(size == 8, &[opcode], rm, r)
+    }
+
+    /// Shifts `dst` of `size` bytes by `amount`, or by CL if `amount` is `None`.
+    fn shift(
+        &mut self,
+        size: u8,
+        ext: u8,
+        dst: &OperandData,
+        amount: Option<&OperandData>,
+    ) -> Result<()> {
+        if size == 2 {
+            self.code.push(0x66)
+        }
+        let one = amount.is_some_and(|a| a.sext_as_i64() == Some(1));
+        let opcode = match amount {
+            None => 0xd2,
+            Some(_) if one => 0xd0,
+            Some(_) => 0xc0,
+        } + (size != 1) as u8;
+        self.modrm(
+            size == 8,
+            needs_rex(dst),
+            &[opcode],
+            ext,
+            &RM::Reg(reg(dst)?),
+        )?;
+        match amount {
+            Some(amount) if !one => self.imm8(amount),
+            _ => Ok(()),
+        }
+    }
+
+    /// `op rm, reg` where both are registers.
+    fn rr(&mut self, w: bool, opcode: &[u8], rm: &OperandData, r: &OperandData) -> Result<()> {
+        let force_rex = needs_rex(rm) || needs_rex(r);
+        self.modrm(w, force_rex, opcode, reg(r)?, &RM::Reg(reg(rm)?))
+    }
+
+    /// `op reg, [mem]` or `op [mem], reg`.
+    fn rm(
+        &mut self,
+        w: bool,
+        force_rex: bool,
+        opcode: &[u8],
+        r: &OperandData,
+        m: &[&OperandData],
+    ) -> Result<()> {
+        self.modrm(w, force_rex, opcode, reg(r)?, &RM::Mem(mem(m)?))
+    }
+
+    /// `op reg, rm` of SSE, where both are registers and `opcode` follows 0F.
+    fn sse_rr(
+        &mut self,
+        prefix: Option<u8>,
+        w: bool,
+        opcode: u8,
+        r: &OperandData,
+        rm: &OperandData,
+    ) -> Result<()> {
+        // The mandatory prefix must come before REX.
+        self.code.extend(prefix);
+        self.modrm(w, false, &[0x0f, opcode], reg(r)?, &RM::Reg(reg(rm)?))
+    }
+
+    /// `op xmm, [mem]` or `op [mem], xmm` of SSE.
+    fn sse_rm(
+        &mut self,
+        prefix: Option<u8>,
+        opcode: u8,
+        r: &OperandData,
+        m: &[&OperandData],
+    ) -> Result<()> {
+        self.code.extend(prefix);
+        self.modrm(false, false, &[0x0f, opcode], reg(r)?, &RM::Mem(mem(m)?))
+    }
+
+    /// Emits REX (if needed), `opcode`, ModRM, SIB and displacement.
+    fn modrm(&mut self, w: bool, force_rex: bool, opcode: &[u8], r: u8, rm: &RM) -> Result<()> {
+        let (x, b) = match rm {
+            RM::Reg(rm) => (0, rm >> 3),
+            RM::Mem(m) => (
+                m.index.map_or(0, |(i, _)| i >> 3),
+                m.base.map_or(0, |b| b >> 3),
+            ),
+        };
+        let rex = (w as u8) << 3 | (r >> 3) << 2 | x << 1 | b;
+        if rex != 0 || force_rex {
+            self.code.push(0x40 | rex)
+        }
+        self.code.extend(opcode);
+
+        let r = (r & 7) << 3;
+        let m = match rm {
+            RM::Reg(rm) => {
+                self.code.push(0xc0 | r | (rm & 7));
+                return Ok(());
+            }
+            RM::Mem(m) => m,
+        };
+
+        if m.rip {
+            self.code.push(r | 0b101);
+            if let Some(table) = m.table {
+                self.table_refs.push(TableRef {
+                    offset: self.code.len(),
+                    table,
+                    addend: m.disp as i64,
+                });
+                self.code.extend([0; 4])
+            } else {
+                self.field32(m.label, m.disp, RelocKind::PcRel32);
+            }
+            return Ok(());
+        }
+
+        let base = match m.base {
+            Some(base) => base & 7,
+            None => {
+                // Absolute address.
+                let index = m.index.map_or(0b100 << 3, |(i, s)| s << 6 | (i & 7) << 3);
+                self.code.extend([r | 0b100, index | 0b101]);
+                self.field32(m.label, m.disp, RelocKind::Abs32S);
+                return Ok(());
+            }
+        };
+
+        // [rbp] and [r13] can't be encoded without displacement.
+        let (mode, disp_size) = if m.label.is_some() {
+            (0b10, 4)
+        } else if m.disp == 0 && base != 0b101 {
+            (0b00, 0)
+        } else if i8::try_from(m.disp).is_ok() {
+            (0b01, 1)
+        } else {
+            (0b10, 4)
+        };
+        if m.index.is_some() || base == 0b100 {
+            let index = m.index.map_or(0b100 << 3, |(i, s)| s << 6 | (i & 7) << 3);
+            self.code.extend([mode << 6 | r | 0b100, index | base]);
+        } else {
+            self.code.push(mode << 6 | r | base);
+        }
+        match disp_size {
+            0 => {}
+            1 => self.code.push(m.disp as i8 as u8),
+            _ => self.field32(m.label, m.disp, RelocKind::Abs32S),
+        }
+        Ok(())
+    }
+
+    /// Emits a 32-bit field holding `label + disp`.
+    fn field32(&mut self, label: Option<&str>, disp: i32, kind: RelocKind) {
+        if let Some(label) = label {
+            self.reloc(kind, label, disp as i64);
+            self.code.extend([0; 4])
+        } else {
+            self.code.extend(disp.to_le_bytes())
+        }
+    }
+
+    fn imm8(&mut self, imm: &OperandData) -> Result<()> {
+        match imm.sext_as_i64().map(i8::try_from) {
+            Some(Ok(i)) => self.code.push(i as u8),
+            _ => return Err(EncodingError::Unsupported(format!("imm8 {:?}", imm)).into()),
+        }
+        Ok(())
+    }
+
+    fn imm16(&mut self, imm: &OperandData) -> Result<()> {
+        match imm.sext_as_i64() {
+            Some(i) if (i16::MIN as i64..=u16::MAX as i64).contains(&i) => {
+                self.code.extend((i as u16).to_le_bytes())
+            }
+            _ => return Err(EncodingError::Unsupported(format!("imm16 {:?}", imm)).into()),
+        }
+        Ok(())
+    }
+
+    fn imm32(&mut self, imm: &OperandData, kind: RelocKind) -> Result<()> {
+        match imm {
+            OperandData::GlobalAddress(name) => {
+                self.reloc(kind, name, 0);
+                self.code.extend([0; 4])
+            }
+            _ => match imm.sext_as_i64().map(i32::try_from) {
+                Some(Ok(i)) => self.code.extend(i.to_le_bytes()),
+                _ => return Err(EncodingError::Unsupported(format!("imm32 {:?}", imm)).into()),
+            },
+        }
+        Ok(())
+    }
+
+    fn reloc(&mut self, kind: RelocKind, symbol: &str, addend: i64) {
+        self.relocs.push(Relocation {
+            offset: self.code.len(),
+            kind,
+            symbol: symbol.to_owned(),
+            addend,
+        })
+    }
+}
+
+/// Parses the six operands following `MemStart`.
+fn mem<'a>(ops: &[&'a OperandData]) -> Result<Mem<'a>> {
+    let (label, table) = match ops[0] {
+        OperandData::Label(name) | OperandData::GlobalAddress(name) => (Some(name.as_str()), None),
+        OperandData::JumpTable(i) => (None, Some(*i)),
+        OperandData::None => (None, None),
+        e => return Err(EncodingError::Unsupported(format!("memory label {:?}", e)).into()),
+    };
+    if !matches!(ops[1], OperandData::None) {
+        return Err(EncodingError::Unsupported("stack slot not eliminated".into()).into());
+    }
+    let disp = match ops[2] {
+        OperandData::None => 0,
+        imm => match imm.sext_as_i64().map(i32::try_from) {
+            Some(Ok(i)) => i,
+            _ => return Err(EncodingError::Unsupported(format!("disp {:?}", imm)).into()),
+        },
+    };
+    let rip = matches!(ops[3], OperandData::Reg(r) if *r == Reg::from(GR64::RIP));
+    let base = match ops[3] {
+        OperandData::None => None,
+        _ if rip => None,
+        r => Some(reg(r)?),
+    };
+    let index = match (ops[4], ops[5]) {
+        (OperandData::None, _) => None,
+        (r, scale) => {
+            let scale = match scale.sext_as_i64() {
+                Some(1) | None => 0,
+                Some(2) => 1,
+                Some(4) => 2,
+                Some(8) => 3,
+                _ => return Err(EncodingError::Unsupported(format!("scale {:?}", scale)).into()),
+            };
+            Some((reg(r)?, scale))
+        }
+    };
+    if rip && index.is_some() {
+        return Err(EncodingError::Unsupported("rip-relative address with index".into()).into());
+    }
+    if table.is_some() && !rip {
+        return Err(EncodingError::Unsupported("jump table not rip-relative".into()).into());
+    }
+    Ok(Mem {
+        label,
+        table,
+        disp,
+        base,
+        index,
+        rip,
+    })
+}
+
+/// Returns the hardware encoding of a register.
+fn reg(op: &OperandData) -> Result<u8> {
+    match op {
+        OperandData::Reg(Reg(_, i)) if *i < 16 => Ok(*i as u8),
+        e => Err(EncodingError::Unsupported(format!("register {:?}", e)).into()),
+    }
+}
+
+/// Returns true if `op` is one of SPL, BPL, SIL and DIL, which need REX to be encoded.
+fn needs_rex(op: &OperandData) -> bool {
+    matches!(op, OperandData::Reg(Reg(class, 4..=7)) if *class == RegClass::GR8 as u16)
+}
+
+fn fits_i8(imm: &OperandData) -> bool {
+    imm.sext_as_i64().is_some_and(|i| i8::try_from(i).is_ok())
+}
+
+fn unsupported(inst: &InstructionData) -> anyhow::Error {
+    EncodingError::Unsupported(format!("{:?}", inst)).into()
+}
+
+impl Error for EncodingError {}
+
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported(msg) => write!(f, "Unsupported: {}", msg),
+        }
+    }
+}
@@ -6,7 +6,7 @@ use crate::{
         Function,
     },
     isa::{
-        x86_64::register::{reg_to_str, GR32, GR64, GR8},
+        x86_64::register::{is_gpr, reg_to_str, to_reg_unit, RegClass, GR16, GR32, GR64, GR8, XMM},
         TargetIsa,
     },
     register::{Reg, VReg, VRegUsers},
@@ -24,36 +24,160 @@ pub enum Opcode {
     PUSH64,
     POP64,
     ADDr64i32,
+    ADDri8,
+    ADDri16,
     ADDri32,
+    ADDrr8,
+    ADDrr16,
     ADDrr32,
     ADDrr64,
     SUBr64i32,
+    SUBri8,
+    SUBri16,
     SUBri32,
+    SUBrr8,
+    SUBrr16,
     SUBrr32,
-    // MULri32,
+    SUBrr64,
+    ANDr64i32,
+    ANDri8,
+    ANDri16,
+    ANDri32,
+    ANDrr8,
+    ANDrr16,
+    ANDrr32,
+    ANDrr64,
+    ORr64i32,
+    ORri8,
+    ORri16,
+    ORri32,
+    ORrr8,
+    ORrr16,
+    ORrr32,
+    ORrr64,
+    XORr64i32,
+    XORri8,
+    XORri16,
+    XORri32,
+    XORrr8,
+    XORrr16,
+    XORrr32,
+    XORrr64,
     IMULrr32,
+    IMULrr64,
+    IMULrr32i32,
     IMULrr64i32,
+    NEGr32,
+    NEGr64,
+    CDQ,
+    CQO,
+    IDIVr32,
+    IDIVr64,
+    DIVr32,
+    DIVr64,
+    SHLr8CL,
+    SHLr16CL,
+    SHLr32CL,
+    SHLr64CL,
+    SHLr8i8,
+    SHLr16i8,
+    SHLr32i8,
+    SHLr64i8,
+    SHRr8CL,
+    SHRr16CL,
+    SHRr32CL,
+    SHRr64CL,
+    SHRr8i8,
+    SHRr16i8,
+    SHRr32i8,
+    SHRr64i8,
+    SARr8CL,
+    SARr16CL,
+    SARr32CL,
+    SARr64CL,
+    SARr8i8,
+    SARr16i8,
+    SARr32i8,
+    SARr64i8,
     MOVrr8,
+    MOVrr16,
     MOVrr32,
     MOVrr64,
     MOVri32,
     MOVri64,
     MOVrm8,
+    MOVrm16,
     MOVrm32,
     MOVrm64,
     MOVmi8,
+    MOVmi16,
     MOVmi32,
     MOVmi64,
     MOVmr8,
+    MOVmr16,
     MOVmr32,
     MOVmr64,
+    MOVSXr32r8,
+    MOVSXr32r16,
+    MOVSXr64r8,
+    MOVSXr64r16,
     MOVSXDr64r32,
     MOVSXDr64m32,
     MOVZXr32r8,
+    MOVZXr32r16,
+    /// `mov r32, r32`, which zero-extends to 64 bits. Unlike `MOVrr32`, this is not a copy and is
+    /// never removed even if both operands get the same register.
+    MOVZXr64r32,
     LEArm64,
+    CMPr64i32,
     CMPri8,
+    CMPri16,
     CMPri32,
+    CMPrr8,
+    CMPrr16,
     CMPrr32,
+    CMPrr64,
+    TESTrr8,
+    SETEr8,
+    SETNEr8,
+    SETLr8,
+    SETLEr8,
+    SETGr8,
+    SETGEr8,
+    SETBr8,
+    SETBEr8,
+    SETAr8,
+    SETAEr8,
+    CMOVNErr32,
+    CMOVNErr64,
+    MOVSSrr,
+    MOVSSrm,
+    MOVSSmr,
+    MOVSDrr,
+    MOVSDrm,
+    MOVSDmr,
+    MOVDrr32,
+    MOVQrr64,
+    ADDSSrr,
+    ADDSDrr,
+    SUBSSrr,
+    SUBSDrr,
+    MULSSrr,
+    MULSDrr,
+    DIVSSrr,
+    DIVSDrr,
+    UCOMISSrr,
+    UCOMISDrr,
+    CVTSI2SSrr32,
+    CVTSI2SSrr64,
+    CVTSI2SDrr32,
+    CVTSI2SDrr64,
+    CVTTSS2SIr32r,
+    CVTTSS2SIr64r,
+    CVTTSD2SIr32r,
+    CVTTSD2SIr64r,
+    CVTSS2SDrr,
+    CVTSD2SSrr,
     JMP,
     JE,
     JNE,
@@ -61,6 +185,13 @@ pub enum Opcode {
     JL,
     JGE,
     JG,
+    JA,
+    JAE,
+    JB,
+    JBE,
+    JP,
+    JNP,
+    JMPr64,
     CALL,
     RET,
 
@@ -88,6 +219,8 @@ pub enum OperandData {
     Block(BasicBlockId),
     Label(String),
     GlobalAddress(String),
+    /// The address of a jump table of the function, which is indexed into `Data::jump_tables`.
+    JumpTable(usize),
     None,
 }
 
@@ -196,9 +329,17 @@ impl TargetInst for InstructionData {
     }
 
     fn rewrite(&mut self, vreg: VReg, reg: Reg) {
-        for operand in &mut self.operands {
+        for (i, operand) in self.operands.iter_mut().enumerate() {
             match operand.data {
-                OperandData::VReg(vr) if vr == vreg => operand.data = OperandData::Reg(reg),
+                OperandData::VReg(vr) if vr == vreg => {
+                    // The width of a general-purpose register is decided by the opcode, so that
+                    // e.g. `trunc` is a copy from the lower part of a wider register.
+                    let reg = match self.opcode.gpr_class(i) {
+                        Some(rc) if is_gpr(reg) => to_reg_unit(reg).apply(&rc),
+                        _ => reg,
+                    };
+                    operand.data = OperandData::Reg(reg)
+                }
                 _ => {}
             }
         }
@@ -231,7 +372,12 @@ impl TargetInst for InstructionData {
     fn is_copy(&self) -> bool {
         matches!(
             self.opcode,
-            Opcode::MOVrr8 | Opcode::MOVrr32 | Opcode::MOVrr64
+            Opcode::MOVrr8
+                | Opcode::MOVrr16
+                | Opcode::MOVrr32
+                | Opcode::MOVrr64
+                | Opcode::MOVSSrr
+                | Opcode::MOVSDrr
         )
     }
 
@@ -251,13 +397,16 @@ impl TargetInst for InstructionData {
     ) -> Instruction<Self> {
         let ty = f.data.vregs.type_for(vreg);
         let sz = f.isa.data_layout().get_size_of(&f.types, ty);
-        assert!(sz == 4 || sz == 8);
         Instruction::new(
             InstructionData {
                 opcode: match sz {
+                    4 if ty.is_float() => Opcode::MOVSSmr,
+                    8 if ty.is_double() => Opcode::MOVSDmr,
+                    1 => Opcode::MOVmr8,
+                    2 => Opcode::MOVmr16,
                     4 => Opcode::MOVmr32,
                     8 => Opcode::MOVmr64,
-                    _ => unreachable!(),
+                    _ => panic!("Unsupported spill size: {}", sz),
                 },
                 operands: vec![
                     Operand::new(OperandData::MemStart),
@@ -282,13 +431,16 @@ impl TargetInst for InstructionData {
     ) -> Instruction<Self> {
         let ty = f.data.vregs.type_for(vreg);
         let sz = f.isa.data_layout().get_size_of(&f.types, ty);
-        assert!(sz == 4 || sz == 8);
         Instruction::new(
             InstructionData {
                 opcode: match sz {
+                    4 if ty.is_float() => Opcode::MOVSSrm,
+                    8 if ty.is_double() => Opcode::MOVSDrm,
+                    1 => Opcode::MOVrm8,
+                    2 => Opcode::MOVrm16,
                     4 => Opcode::MOVrm32,
                     8 => Opcode::MOVrm64,
-                    _ => unreachable!(),
+                    _ => panic!("Unsupported reload size: {}", sz),
                 },
                 operands: vec![
                     Operand::output(vreg.into()),
@@ -306,6 +458,145 @@ impl TargetInst for InstructionData {
     }
 }
 
+impl Opcode {
+    /// Returns the class of the general-purpose register at the `i`th operand, if the opcode
+    /// decides it. Memory operands and registers of other opcodes keep the class of the type of
+    /// their virtual registers.
+    pub fn gpr_class(&self, i: usize) -> Option<RegClass> {
+        match self {
+            Self::SHLr8CL
+            | Self::SHLr16CL
+            | Self::SHLr32CL
+            | Self::SHLr64CL
+            | Self::SHRr8CL
+            | Self::SHRr16CL
+            | Self::SHRr32CL
+            | Self::SHRr64CL
+            | Self::SARr8CL
+            | Self::SARr16CL
+            | Self::SARr32CL
+            | Self::SARr64CL
+                if i == 1 =>
+            {
+                Some(RegClass::GR8)
+            }
+            Self::ADDri8
+            | Self::ADDrr8
+            | Self::SUBri8
+            | Self::SUBrr8
+            | Self::ANDri8
+            | Self::ANDrr8
+            | Self::ORri8
+            | Self::ORrr8
+            | Self::XORri8
+            | Self::XORrr8
+            | Self::SHLr8CL
+            | Self::SHLr8i8
+            | Self::SHRr8CL
+            | Self::SHRr8i8
+            | Self::SARr8CL
+            | Self::SARr8i8
+            | Self::MOVrr8
+            | Self::CMPri8
+            | Self::CMPrr8
+            | Self::TESTrr8
+            | Self::SETEr8
+            | Self::SETNEr8
+            | Self::SETLr8
+            | Self::SETLEr8
+            | Self::SETGr8
+            | Self::SETGEr8
+            | Self::SETBr8
+            | Self::SETBEr8
+            | Self::SETAr8
+            | Self::SETAEr8 => Some(RegClass::GR8),
+            Self::ADDri16
+            | Self::ADDrr16
+            | Self::SUBri16
+            | Self::SUBrr16
+            | Self::ANDri16
+            | Self::ANDrr16
+            | Self::ORri16
+            | Self::ORrr16
+            | Self::XORri16
+            | Self::XORrr16
+            | Self::SHLr16CL
+            | Self::SHLr16i8
+            | Self::SHRr16CL
+            | Self::SHRr16i8
+            | Self::SARr16CL
+            | Self::SARr16i8
+            | Self::MOVrr16
+            | Self::CMPri16
+            | Self::CMPrr16 => Some(RegClass::GR16),
+            Self::ADDri32
+            | Self::ADDrr32
+            | Self::SUBri32
+            | Self::SUBrr32
+            | Self::ANDri32
+            | Self::ANDrr32
+            | Self::ORri32
+            | Self::ORrr32
+            | Self::XORri32
+            | Self::XORrr32
+            | Self::IMULrr32
+            | Self::IMULrr32i32
+            | Self::NEGr32
+            | Self::IDIVr32
+            | Self::DIVr32
+            | Self::SHLr32CL
+            | Self::SHLr32i8
+            | Self::SHRr32CL
+            | Self::SHRr32i8
+            | Self::SARr32CL
+            | Self::SARr32i8
+            | Self::MOVrr32
+            | Self::MOVri32
+            | Self::MOVZXr64r32
+            | Self::CMPri32
+            | Self::CMPrr32
+            | Self::CMOVNErr32 => Some(RegClass::GR32),
+            Self::ADDr64i32
+            | Self::ADDrr64
+            | Self::SUBr64i32
+            | Self::SUBrr64
+            | Self::ANDr64i32
+            | Self::ANDrr64
+            | Self::ORr64i32
+            | Self::ORrr64
+            | Self::XORr64i32
+            | Self::XORrr64
+            | Self::IMULrr64
+            | Self::IMULrr64i32
+            | Self::NEGr64
+            | Self::IDIVr64
+            | Self::DIVr64
+            | Self::SHLr64CL
+            | Self::SHLr64i8
+            | Self::SHRr64CL
+            | Self::SHRr64i8
+            | Self::SARr64CL
+            | Self::SARr64i8
+            | Self::MOVrr64
+            | Self::MOVri64
+            | Self::CMPr64i32
+            | Self::CMPrr64
+            | Self::CMOVNErr64
+            | Self::JMPr64 => Some(RegClass::GR64),
+            Self::MOVSXr32r8 | Self::MOVZXr32r8 if i == 1 => Some(RegClass::GR8),
+            Self::MOVSXr32r16 | Self::MOVZXr32r16 if i == 1 => Some(RegClass::GR16),
+            Self::MOVSXr64r8 if i == 1 => Some(RegClass::GR8),
+            Self::MOVSXr64r16 if i == 1 => Some(RegClass::GR16),
+            Self::MOVSXDr64r32 if i == 1 => Some(RegClass::GR32),
+            Self::MOVSXr32r8 | Self::MOVSXr32r16 | Self::MOVZXr32r8 | Self::MOVZXr32r16 => {
+                Some(RegClass::GR32)
+            }
+            Self::MOVSXr64r8 | Self::MOVSXr64r16 | Self::MOVSXDr64r32 => Some(RegClass::GR64),
+            _ => None,
+        }
+    }
+}
+
 impl Operand {
     pub fn new(data: OperandData) -> Self {
         Self {
@@ -334,6 +625,15 @@ impl Operand {
         }
     }
 
+    pub fn implicit_input(data: OperandData) -> Self {
+        Self {
+            data,
+            input: true,
+            output: false,
+            implicit: true,
+        }
+    }
+
     pub fn implicit_output(data: OperandData) -> Self {
         Self {
             data,
@@ -451,6 +751,12 @@ impl From<GR8> for OperandData {
     }
 }
 
+impl From<GR16> for OperandData {
+    fn from(r: GR16) -> Self {
+        OperandData::Reg(r.into())
+    }
+}
+
 impl From<GR32> for OperandData {
     fn from(r: GR32) -> Self {
         OperandData::Reg(r.into())
@@ -463,6 +769,12 @@ impl From<GR64> for OperandData {
     }
 }
 
+impl From<XMM> for OperandData {
+    fn from(r: XMM) -> Self {
+        OperandData::Reg(r.into())
+    }
+}
+
 impl fmt::Debug for InstructionData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?} ", self.opcode)?;
@@ -519,6 +831,7 @@ impl fmt::Debug for OperandData {
             Self::Block(id) => write!(f, "block.{}", id.index()),
             Self::Label(name) => write!(f, "{}", name),
             Self::GlobalAddress(name) => write!(f, "{}", name),
+            Self::JumpTable(i) => write!(f, "jump_table.{}", i),
             Self::None => write!(f, "none"),
         }
     }
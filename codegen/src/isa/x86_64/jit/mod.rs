@@ -0,0 +1,483 @@
+extern crate libc;
+extern crate libloading;
+
+use crate::{
+    isa::x86_64::{
+        elf::{self, SymbolSection, SHF_ALLOC, SHF_EXECINSTR},
+        encode::RelocKind,
+        X86_64,
+    },
+    module::Module,
+};
+use anyhow::Result;
+use rustc_hash::FxHashMap;
+use std::{error::Error, ffi, fmt, io, mem, ptr};
+use vicis_core::ir::types::{self, Type};
+
+/// Code and data of a module loaded into memory, ready to be executed.
+pub struct Jit<'a> {
+    module: &'a Module<'a, X86_64>,
+    _memory: Memory,
+    symbols: FxHashMap<String, *const u8>,
+    /// The entries of `.init_array`.
+    constructors: Vec<*const u8>,
+    _libs: Vec<libloading::Library>,
+}
+
+/// A builder for `Jit`.
+pub struct JitBuilder<'a> {
+    module: &'a Module<'a, X86_64>,
+    libs: Vec<Result<libloading::Library, libloading::Error>>,
+}
+
+/// Arguments and return values of JIT-compiled functions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Int1(bool),
+    Int8(i8),
+    Int16(i16),
+    Int32(i32),
+    Int64(i64),
+    F32(f32),
+    F64(f64),
+    Ptr(*mut u8),
+    Void,
+}
+
+#[derive(Debug)]
+pub enum JitError {
+    UndefinedSymbol(String),
+    RelocationOutOfRange(String),
+    FunctionNotFound(String),
+    InvalidArguments(String),
+    Unsupported(String),
+    Memory(io::Error),
+}
+
+/// Anonymous memory mapped for the module.
+struct Memory {
+    ptr: *mut u8,
+    len: usize,
+}
+
+/// `jmp [rip]` followed by the address to jump to. Calls to external functions go through these,
+/// since they may be too far from the module for `call rel32`.
+const STUB: [u8; 6] = [0xff, 0x25, 0, 0, 0, 0];
+const STUB_SIZE: usize = 16;
+
+/// Integer and floating-point arguments passed in registers in System V.
+const MAX_INT_ARGS: usize = 6;
+const MAX_FLOAT_ARGS: usize = 8;
+
+impl<'a> JitBuilder<'a> {
+    pub fn new(module: &'a Module<'a, X86_64>) -> Self {
+        Self {
+            module,
+            libs: vec![],
+        }
+    }
+
+    /// Adds a library to resolve external symbols from, in addition to the ones already loaded
+    /// in the process.
+    pub fn with_lib<T: AsRef<ffi::OsStr>>(mut self, lib: T) -> Self {
+        self.libs.push(unsafe { libloading::Library::new(lib) });
+        self
+    }
+
+    pub fn with_libs<T: AsRef<ffi::OsStr>>(mut self, libs: Vec<T>) -> Self {
+        for lib in libs {
+            self.libs.push(unsafe { libloading::Library::new(lib) });
+        }
+        self
+    }
+
+    /// Loads the module into executable memory. Its constructors are run separately by
+    /// `Jit::run_constructors`.
+    pub fn build(self) -> Result<Jit<'a>> {
+        let mut libs = self.libs.into_iter().collect::<Result<Vec<_>, _>>()?;
+        libs.push(libloading::os::unix::Library::this().into());
+
+        let obj = elf::build(self.module)?;
+
+        // Code comes first, followed by stubs, then data from the next page.
+        let mut len = 0;
+        let mut section_offsets = vec![None; obj.sections.len()];
+        let mut layout = |len: &mut usize, exec: bool| {
+            for (i, section) in obj.sections.iter().enumerate() {
+                if section.flags & SHF_ALLOC == 0 || (section.flags & SHF_EXECINSTR != 0) != exec {
+                    continue;
+                }
+                *len = round_up(*len, section.align.max(1) as usize);
+                section_offsets[i] = Some(*len);
+                *len += section.data.len();
+            }
+        };
+        layout(&mut len, true);
+
+        len = round_up(len, STUB_SIZE);
+        let mut stubs = FxHashMap::default();
+        for (i, symbol) in obj.symbols.iter().enumerate() {
+            let called = obj.sections.iter().any(|s| {
+                s.relocs
+                    .iter()
+                    .any(|r| r.kind == RelocKind::Plt32 && r.symbol == symbol.name)
+            });
+            if matches!(symbol.section, SymbolSection::Undef) && called {
+                stubs.insert(i, len);
+                len += STUB_SIZE;
+            }
+        }
+
+        len = round_up(len, page_size());
+        let code_len = len;
+        layout(&mut len, false);
+
+        let mut commons = FxHashMap::default();
+        for (i, symbol) in obj.symbols.iter().enumerate() {
+            if matches!(symbol.section, SymbolSection::Common) {
+                len = round_up(len, symbol.value.max(1) as usize);
+                commons.insert(i, len);
+                len += symbol.size as usize;
+            }
+        }
+
+        // Absolute 32-bit relocations need the module in the lower 2GiB. Otherwise, leave the
+        // placement to the kernel, which tends to map it near the shared libraries so that
+        // PC-relative references to external data stay in range.
+        let low = obj
+            .sections
+            .iter()
+            .flat_map(|s| &s.relocs)
+            .any(|r| matches!(r.kind, RelocKind::Abs32 | RelocKind::Abs32S));
+        let memory = Memory::new(len.max(1), low)?;
+        let base = memory.ptr;
+        for (section, offset) in obj.sections.iter().zip(&section_offsets) {
+            if let Some(offset) = offset {
+                unsafe {
+                    ptr::copy_nonoverlapping(
+                        section.data.as_ptr(),
+                        base.add(*offset),
+                        section.data.len(),
+                    )
+                };
+            }
+        }
+
+        let mut addrs = vec![ptr::null::<u8>(); obj.symbols.len()];
+        let mut symbols = FxHashMap::default();
+        for (i, symbol) in obj.symbols.iter().enumerate() {
+            addrs[i] = match symbol.section {
+                SymbolSection::Section(s) => unsafe {
+                    base.add(section_offsets[s].unwrap() + symbol.value as usize)
+                },
+                SymbolSection::Common => unsafe { base.add(commons[&i]) },
+                SymbolSection::Undef => {
+                    let name = symbol.name.as_bytes();
+                    libs.iter()
+                        .find_map(|lib| unsafe { lib.get::<*const u8>(name) }.ok())
+                        .map(|s| *s)
+                        // Defined by linkers to identify the module, e.g. for `__cxa_atexit`.
+                        .or_else(|| (symbol.name == "__dso_handle").then_some(base as *const u8))
+                        .ok_or_else(|| JitError::UndefinedSymbol(symbol.name.clone()))?
+                }
+            };
+            if !matches!(symbol.section, SymbolSection::Undef) {
+                symbols.insert(symbol.name.clone(), addrs[i]);
+            }
+        }
+
+        for (&i, &offset) in &stubs {
+            unsafe {
+                let stub = base.add(offset);
+                ptr::copy_nonoverlapping(STUB.as_ptr(), stub, STUB.len());
+                ptr::write_unaligned(stub.add(STUB.len()) as *mut u64, addrs[i] as u64);
+            }
+        }
+
+        for (section, offset) in obj.sections.iter().zip(&section_offsets) {
+            let offset = match offset {
+                Some(offset) => *offset,
+                None => continue,
+            };
+            for reloc in &section.relocs {
+                let sym = obj.symbol_index[&reloc.symbol];
+                let target = match stubs.get(&sym) {
+                    Some(&stub) if reloc.kind == RelocKind::Plt32 => base as i64 + stub as i64,
+                    _ => addrs[sym] as i64,
+                } + reloc.addend;
+                let place = unsafe { base.add(offset + reloc.offset) };
+                let out_of_range = || JitError::RelocationOutOfRange(reloc.symbol.clone());
+                unsafe {
+                    match reloc.kind {
+                        RelocKind::Abs64 => ptr::write_unaligned(place as *mut i64, target),
+                        RelocKind::Abs32 => ptr::write_unaligned(
+                            place as *mut u32,
+                            u32::try_from(target).map_err(|_| out_of_range())?,
+                        ),
+                        RelocKind::Abs32S => ptr::write_unaligned(
+                            place as *mut i32,
+                            i32::try_from(target).map_err(|_| out_of_range())?,
+                        ),
+                        RelocKind::PcRel32 | RelocKind::Plt32 => ptr::write_unaligned(
+                            place as *mut i32,
+                            i32::try_from(target - place as i64).map_err(|_| out_of_range())?,
+                        ),
+                    }
+                }
+            }
+        }
+
+        memory.protect_exec(code_len)?;
+
+        let mut constructors = vec![];
+        for (section, offset) in obj.sections.iter().zip(&section_offsets) {
+            if section.name != ".init_array" {
+                continue;
+            }
+            let offset = offset.unwrap();
+            for i in (0..section.data.len()).step_by(8) {
+                constructors
+                    .push(unsafe { ptr::read_unaligned(base.add(offset + i) as *const *const u8) });
+            }
+        }
+
+        let jit = Jit {
+            module: self.module,
+            _memory: memory,
+            symbols,
+            constructors,
+            _libs: libs,
+        };
+
+        Ok(jit)
+    }
+}
+
+impl Jit<'_> {
+    /// Returns the address of a function or a global variable defined in the module.
+    pub fn get_address(&self, name: &str) -> Option<*const u8> {
+        self.symbols.get(name).copied()
+    }
+
+    /// Runs the constructors of the module (`llvm.global_ctors`). Call it once, before calling
+    /// functions of the module that depend on them.
+    ///
+    /// # Safety
+    ///
+    /// The constructors are executed as native code, so they can do anything.
+    pub unsafe fn run_constructors(&self) {
+        for &ctor in &self.constructors {
+            mem::transmute::<*const u8, extern "C" fn()>(ctor)()
+        }
+    }
+
+    /// Calls the function named `name` with `args`, which must match its parameters.
+    ///
+    /// # Safety
+    ///
+    /// The function is executed as native code, so it can do anything.
+    pub unsafe fn call(&self, name: &str, args: &[Value]) -> Result<Value> {
+        let func = self
+            .module
+            .functions
+            .iter()
+            .map(|(_, f)| f)
+            .find(|f| !f.is_declaration && f.ir.name() == name)
+            .ok_or_else(|| JitError::FunctionNotFound(name.to_owned()))?;
+
+        let params = &func.ir.params;
+        if params.len() != args.len() {
+            return Err(JitError::InvalidArguments(format!(
+                "{} expects {} arguments, but {} given",
+                name,
+                params.len(),
+                args.len()
+            ))
+            .into());
+        }
+        // Integer arguments go to rdi, rsi, ..., and floating-point ones to xmm0-xmm7, in order
+        // of appearance. Passing all of the registers is harmless for any signature that fits.
+        let mut ints = [0u64; MAX_INT_ARGS];
+        let mut floats = [0f64; MAX_FLOAT_ARGS];
+        let (mut int_used, mut float_used) = (0, 0);
+        for (i, (param, arg)) in params.iter().zip(args).enumerate() {
+            if !self.matches(param.ty, arg) {
+                return Err(JitError::InvalidArguments(format!(
+                    "argument {} of {} should be {}, but {:?} given",
+                    i,
+                    name,
+                    self.module.types.to_string(param.ty),
+                    arg
+                ))
+                .into());
+            }
+            let float = match *arg {
+                Value::F32(f) => Some(f64::from_bits(f.to_bits() as u64)),
+                Value::F64(f) => Some(f),
+                _ => None,
+            };
+            if let Some(float) = float {
+                if float_used == MAX_FLOAT_ARGS {
+                    return Err(JitError::Unsupported(format!(
+                        "more than {} floating-point arguments",
+                        MAX_FLOAT_ARGS
+                    ))
+                    .into());
+                }
+                floats[float_used] = float;
+                float_used += 1;
+                continue;
+            }
+            if int_used == MAX_INT_ARGS {
+                return Err(JitError::Unsupported(format!(
+                    "more than {} integer arguments",
+                    MAX_INT_ARGS
+                ))
+                .into());
+            }
+            ints[int_used] = match *arg {
+                Value::Int1(b) => b as u64,
+                Value::Int8(i) => i as u64,
+                Value::Int16(i) => i as u64,
+                Value::Int32(i) => i as u64,
+                Value::Int64(i) => i as u64,
+                Value::Ptr(p) => p as u64,
+                Value::F32(_) | Value::F64(_) | Value::Void => unreachable!(),
+            };
+            int_used += 1;
+        }
+
+        let addr = self.symbols[name];
+        let ret = || -> u64 { call_native(addr, ints, floats) };
+        let float_ret = || -> f64 { call_native(addr, ints, floats) };
+        let ty = func.ir.result_ty;
+        Ok(match ty {
+            types::VOID => {
+                ret();
+                Value::Void
+            }
+            types::I1 => Value::Int1(ret() & 1 != 0),
+            types::I8 => Value::Int8(ret() as i8),
+            types::I16 => Value::Int16(ret() as i16),
+            types::I32 => Value::Int32(ret() as i32),
+            types::I64 => Value::Int64(ret() as i64),
+            types::FLOAT => Value::F32(f32::from_bits(float_ret().to_bits() as u32)),
+            types::DOUBLE => Value::F64(float_ret()),
+            _ if self.module.types.is_pointer(ty) => Value::Ptr(ret() as *mut u8),
+            _ => {
+                return Err(JitError::Unsupported(format!(
+                    "return type {}",
+                    self.module.types.to_string(ty)
+                ))
+                .into())
+            }
+        })
+    }
+
+    fn matches(&self, ty: Type, arg: &Value) -> bool {
+        match arg {
+            Value::Int1(_) => ty == types::I1,
+            Value::Int8(_) => ty == types::I8,
+            Value::Int16(_) => ty == types::I16,
+            Value::Int32(_) => ty == types::I32,
+            Value::Int64(_) => ty == types::I64,
+            Value::F32(_) => ty == types::FLOAT,
+            Value::F64(_) => ty == types::DOUBLE,
+            Value::Ptr(_) => self.module.types.is_pointer(ty),
+            Value::Void => false,
+        }
+    }
+}
+
+/// Calls the function at `addr` with all of the argument registers set, returning `rax` if `R` is
+/// `u64` or `xmm0` if `R` is `f64`.
+unsafe fn call_native<R>(
+    addr: *const u8,
+    ints: [u64; MAX_INT_ARGS],
+    floats: [f64; MAX_FLOAT_ARGS],
+) -> R {
+    type F<R> =
+        extern "C" fn(u64, u64, u64, u64, u64, u64, f64, f64, f64, f64, f64, f64, f64, f64) -> R;
+    let f: F<R> = mem::transmute(addr);
+    let [i0, i1, i2, i3, i4, i5] = ints;
+    let [f0, f1, f2, f3, f4, f5, f6, f7] = floats;
+    f(i0, i1, i2, i3, i4, i5, f0, f1, f2, f3, f4, f5, f6, f7)
+}
+
+impl Memory {
+    fn new(len: usize, low: bool) -> Result<Self> {
+        let len = round_up(len, page_size());
+        #[allow(unused_mut)]
+        let mut flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS;
+        #[cfg(target_os = "linux")]
+        if low {
+            flags |= libc::MAP_32BIT;
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = low;
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                flags,
+                -1,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(JitError::Memory(io::Error::last_os_error()).into());
+        }
+        Ok(Self {
+            ptr: ptr as *mut u8,
+            len,
+        })
+    }
+
+    /// Makes the first `len` bytes executable and read-only.
+    fn protect_exec(&self, len: usize) -> Result<()> {
+        if len == 0 {
+            return Ok(());
+        }
+        let ret = unsafe {
+            libc::mprotect(
+                self.ptr as *mut libc::c_void,
+                len,
+                libc::PROT_READ | libc::PROT_EXEC,
+            )
+        };
+        if ret != 0 {
+            return Err(JitError::Memory(io::Error::last_os_error()).into());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Memory {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len) };
+    }
+}
+
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+fn round_up(x: usize, align: usize) -> usize {
+    x.div_ceil(align) * align
+}
+
+impl Error for JitError {}
+
+impl fmt::Display for JitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UndefinedSymbol(name) => write!(f, "Undefined symbol: {}", name),
+            Self::RelocationOutOfRange(name) => write!(f, "Relocation out of range: {}", name),
+            Self::FunctionNotFound(name) => write!(f, "Function not found: {}", name),
+            Self::InvalidArguments(msg) => write!(f, "Invalid arguments: {}", msg),
+            Self::Unsupported(msg) => write!(f, "Unsupported: {}", msg),
+            Self::Memory(err) => write!(f, "Failed to map memory: {}", err),
+        }
+    }
+}
@@ -110,7 +110,10 @@ pub fn lower_load(
     let sz = ctx.isa.data_layout().get_size_of(ctx.types, src_ty);
     let output = new_empty_inst_output(ctx, src_ty, id);
     let opcode = match sz {
+        4 if src_ty.is_float() => Opcode::MOVSSrm,
+        8 if src_ty.is_double() => Opcode::MOVSDrm,
         1 => Opcode::MOVrm8,
+        2 => Opcode::MOVrm16,
         4 => Opcode::MOVrm32,
         8 => Opcode::MOVrm64,
         _ => return Err(LoweringError::Todo("Unsupported load pattern".into()).into()),
@@ -238,8 +241,23 @@ fn lower_load_gep(
             },
             ctx.block_map[&ctx.cur_block],
         )]);
+    } else if src_ty.is_float() || src_ty.is_double() {
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode: if src_ty.is_float() {
+                    Opcode::MOVSSrm
+                } else {
+                    Opcode::MOVSDrm
+                },
+                operands: vec![MOperand::output(OperandData::VReg(output))]
+                    .into_iter()
+                    .chain(mem)
+                    .collect(),
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
     } else {
-        return Err(LoweringError::Todo("Load result must be i32".into()).into());
+        return Err(LoweringError::Todo("Load result must be i32 or floating-point".into()).into());
     }
 
     Ok(())
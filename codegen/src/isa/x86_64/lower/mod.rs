@@ -5,7 +5,7 @@ use crate::{
     function::instruction::Instruction as MachInstruction,
     isa::x86_64::{
         instruction::{InstructionData, Opcode, Operand as MO, OperandData},
-        register::{RegClass, RegInfo, GR32, GR64, GR8},
+        register::{to_reg_unit, RegClass, RegInfo, GR16, GR32, GR64, GR8, XMM},
         X86_64,
     },
     isa::TargetIsa,
@@ -20,15 +20,15 @@ use vicis_core::ir::{
         basic_block::BasicBlockId,
         data::Data as IrData,
         instruction::{
-            Alloca, Br, Call, Cast, CondBr, GetElementPtr, ICmp, ICmpCond,
-            Instruction as IrInstruction, InstructionId, IntBinary, Load, Opcode as IrOpcode,
-            Operand, Phi, Ret, Store,
+            Alloca, Br, Call, Cast, CondBr, FCmp, FCmpCond, FloatBinary, GetElementPtr, ICmp,
+            ICmpCond, Instruction as IrInstruction, InstructionId, IntBinary, Load,
+            Opcode as IrOpcode, Operand, Phi, Ret, Select, Store, Switch,
         },
         Parameter,
     },
     module::name::Name,
     types::{self, CompoundType, FunctionType, Type},
-    value::{ConstantExpr, ConstantInt, ConstantValue, Value, ValueId},
+    value::{ConstantExpr, ConstantFloat, ConstantInt, ConstantValue, Value, ValueId},
 };
 
 #[derive(Clone, Copy, Default)]
@@ -47,18 +47,29 @@ impl LowerTrait<X86_64> for Lower {
 
     fn copy_args_to_vregs(ctx: &mut LoweringContext<X86_64>, params: &[Parameter]) -> Result<()> {
         let args = RegInfo::arg_reg_list(&ctx.call_conv);
-        for (gpr_used, Parameter { name: _, ty, .. }) in params.iter().enumerate() {
-            let reg = args[gpr_used].apply(&RegClass::for_type(ctx.types, *ty));
+        let float_args = RegInfo::float_arg_reg_list(&ctx.call_conv);
+        let mut gpr_used = 0;
+        let mut xmm_used = 0;
+        for (i, Parameter { name: _, ty, .. }) in params.iter().enumerate() {
+            let (reg, opcode) = if ty.is_floating_point() {
+                if xmm_used == float_args.len() {
+                    return Err(LoweringError::Todo("Arguments passed on the stack".into()).into());
+                }
+                let reg = float_args[xmm_used].apply(&RegClass::XMM);
+                xmm_used += 1;
+                (reg, fp_copy_opcode(*ty))
+            } else {
+                if gpr_used == args.len() {
+                    return Err(LoweringError::Todo("Arguments passed on the stack".into()).into());
+                }
+                let reg = args[gpr_used].apply(&RegClass::for_type(ctx.types, *ty)?);
+                gpr_used += 1;
+                assert!(ty.is_integer() || ty.is_pointer(ctx.types));
+                let sz = ctx.isa.data_layout().get_size_of(ctx.types, *ty);
+                (reg, copy_opcode(sz))
+            };
             debug!(reg);
             // Copy reg to new vreg
-            assert!(ty.is_integer() || ty.is_pointer(ctx.types));
-            let sz = ctx.isa.data_layout().get_size_of(ctx.types, *ty);
-            let opcode = match sz {
-                1 => Opcode::MOVrr8,
-                4 => Opcode::MOVrr32,
-                8 => Opcode::MOVrr64,
-                _ => todo!(),
-            };
             let output = ctx.mach_data.vregs.add_vreg_data(*ty);
             ctx.inst_seq.push(MachInstruction::new(
                 InstructionData {
@@ -67,7 +78,7 @@ impl LowerTrait<X86_64> for Lower {
                 },
                 ctx.block_map[&ctx.cur_block],
             ));
-            ctx.arg_idx_to_vreg.insert(gpr_used, output);
+            ctx.arg_idx_to_vreg.insert(i, output);
         }
         Ok(())
     }
@@ -98,6 +109,9 @@ fn lower(ctx: &mut LoweringContext<X86_64>, inst: &IrInstruction) -> Result<()>
         Operand::IntBinary(IntBinary { ty, ref args, .. }) => {
             lower_bin(ctx, inst.id.unwrap(), inst.opcode, ty, args)
         }
+        Operand::FloatBinary(FloatBinary { ty, ref args, .. }) => {
+            lower_float_bin(ctx, inst.id.unwrap(), inst.opcode, ty, args)
+        }
         Operand::Cast(Cast { ref tys, arg }) if inst.opcode == IrOpcode::Sext => {
             lower_sext(ctx, inst.id.unwrap(), tys, arg)
         }
@@ -107,9 +121,32 @@ fn lower(ctx: &mut LoweringContext<X86_64>, inst: &IrInstruction) -> Result<()>
         Operand::Cast(Cast { ref tys, arg }) if inst.opcode == IrOpcode::Zext => {
             lower_zext(ctx, inst.id.unwrap(), tys, arg)
         }
+        Operand::Cast(Cast { ref tys, arg })
+            if matches!(
+                inst.opcode,
+                IrOpcode::Trunc | IrOpcode::PtrToInt | IrOpcode::IntToPtr
+            ) =>
+        {
+            lower_int_cast(ctx, inst.id.unwrap(), tys, arg)
+        }
+        Operand::Cast(Cast { ref tys, arg })
+            if matches!(
+                inst.opcode,
+                IrOpcode::SIToFP | IrOpcode::FPToSI | IrOpcode::FPExt | IrOpcode::FPTrunc
+            ) =>
+        {
+            lower_float_cast(ctx, inst.id.unwrap(), inst.opcode, tys, arg)
+        }
+        Operand::ICmp(ICmp { ty, ref args, cond }) => {
+            lower_icmp(ctx, inst.id.unwrap(), ty, args, cond)
+        }
+        Operand::Select(Select { ref tys, ref args }) => {
+            lower_select(ctx, inst.id.unwrap(), tys, args)
+        }
         Operand::GetElementPtr(ref gep) => lower_gep(ctx, inst.id.unwrap(), gep),
         Operand::Br(Br { block }) => lower_br(ctx, block),
         Operand::CondBr(CondBr { arg, blocks }) => lower_condbr(ctx, arg, blocks),
+        Operand::Switch(ref switch) => lower_switch(ctx, switch),
         Operand::Call(Call {
             ref args, ref tys, ..
         }) => lower_call(ctx, inst.id.unwrap(), tys, args),
@@ -190,59 +227,452 @@ fn lower_bin(
     ty: Type,
     args: &[ValueId],
 ) -> Result<()> {
+    use Opcode::*;
+
+    let sz = ctx.isa.data_layout().get_size_of(ctx.types, ty);
+    if matches!(
+        op,
+        IrOpcode::SDiv | IrOpcode::SRem | IrOpcode::UDiv | IrOpcode::URem
+    ) {
+        return lower_div(ctx, id, op, ty, args);
+    }
+    if matches!(op, IrOpcode::Shl | IrOpcode::LShr | IrOpcode::AShr) {
+        return lower_shift(ctx, id, op, ty, args);
+    }
+
     let lhs = get_vreg_for_val(ctx, ty, args[0])?;
+    let rhs = get_imm_or_vreg_for_val(ctx, ty, args[1])?;
     let output = new_empty_inst_output(ctx, ty, id);
 
-    let insert_move = |ctx: &mut LoweringContext<X86_64>| {
+    // `imul` has a three-operand form for immediates.
+    if op == IrOpcode::Mul && !matches!(rhs, OperandData::VReg(_)) {
         ctx.inst_seq.push(MachInstruction::new(
             InstructionData {
-                opcode: Opcode::MOVrr32,
-                operands: vec![MO::output(output.into()), MO::input(lhs.into())],
+                opcode: if sz == 8 { IMULrr64i32 } else { IMULrr32i32 },
+                operands: vec![
+                    MO::output(output.into()),
+                    MO::input(lhs.into()),
+                    MO::new(rhs),
+                ],
             },
             ctx.block_map[&ctx.cur_block],
-        ))
-    };
+        ));
+        return Ok(());
+    }
 
-    let rhs = get_operand_for_val(ctx, ty, args[1])?;
+    let (rr, ri) = match op {
+        IrOpcode::Add => (
+            [ADDrr8, ADDrr16, ADDrr32, ADDrr64],
+            [ADDri8, ADDri16, ADDri32, ADDr64i32],
+        ),
+        IrOpcode::Sub => (
+            [SUBrr8, SUBrr16, SUBrr32, SUBrr64],
+            [SUBri8, SUBri16, SUBri32, SUBr64i32],
+        ),
+        IrOpcode::And => (
+            [ANDrr8, ANDrr16, ANDrr32, ANDrr64],
+            [ANDri8, ANDri16, ANDri32, ANDr64i32],
+        ),
+        IrOpcode::Or => (
+            [ORrr8, ORrr16, ORrr32, ORrr64],
+            [ORri8, ORri16, ORri32, ORr64i32],
+        ),
+        IrOpcode::Xor => (
+            [XORrr8, XORrr16, XORrr32, XORrr64],
+            [XORri8, XORri16, XORri32, XORr64i32],
+        ),
+        // There is no 8-bit `imul` taking two registers, and the upper bits don't matter.
+        IrOpcode::Mul => ([IMULrr32, IMULrr32, IMULrr32, IMULrr64], [IMULrr32i32; 4]),
+        op => return Err(LoweringError::Todo(format!("Unsupported opcode: {:?}", op)).into()),
+    };
 
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: copy_opcode(sz),
+            operands: vec![MO::output(output.into()), MO::input(lhs.into())],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
     let data = match rhs {
-        OperandData::Int32(rhs) => {
-            insert_move(ctx);
-            InstructionData {
-                opcode: match op {
-                    IrOpcode::Add => Opcode::ADDri32,
-                    IrOpcode::Sub => Opcode::SUBri32,
-                    // IrOpcode::Mul => Opcode::MULri32,
-                    op => {
-                        return Err(
-                            LoweringError::Todo(format!("Unsupported opcode: {:?}", op)).into()
-                        )
-                    }
+        OperandData::VReg(rhs) => InstructionData {
+            opcode: rr[width_index(sz)],
+            operands: vec![MO::input_output(output.into()), MO::input(rhs.into())],
+        },
+        rhs => InstructionData {
+            opcode: ri[width_index(sz)],
+            operands: vec![MO::input_output(output.into()), MO::new(rhs)],
+        },
+    };
+    ctx.inst_seq
+        .push(MachInstruction::new(data, ctx.block_map[&ctx.cur_block]));
+
+    Ok(())
+}
+
+/// `div` and `idiv` divide RDX:RAX, leaving the quotient in RAX and the remainder in RDX.
+/// Narrower operands are extended to 32 bits first.
+fn lower_div(
+    ctx: &mut LoweringContext<X86_64>,
+    id: InstructionId,
+    op: IrOpcode,
+    ty: Type,
+    args: &[ValueId],
+) -> Result<()> {
+    let sz = ctx.isa.data_layout().get_size_of(ctx.types, ty);
+    let signed = matches!(op, IrOpcode::SDiv | IrOpcode::SRem);
+    let mut lhs = get_vreg_for_val(ctx, ty, args[0])?;
+    let mut rhs = get_vreg_for_val(ctx, ty, args[1])?;
+    let output = new_empty_inst_output(ctx, ty, id);
+
+    if sz < 4 {
+        let opcode = match (sz, signed) {
+            (1, true) => Opcode::MOVSXr32r8,
+            (1, false) => Opcode::MOVZXr32r8,
+            (_, true) => Opcode::MOVSXr32r16,
+            (_, false) => Opcode::MOVZXr32r16,
+        };
+        for val in [&mut lhs, &mut rhs] {
+            let ext = ctx.mach_data.vregs.add_vreg_data(types::I32);
+            ctx.inst_seq.push(MachInstruction::new(
+                InstructionData {
+                    opcode,
+                    operands: vec![MO::output(ext.into()), MO::input((*val).into())],
                 },
-                operands: vec![MO::input_output(output.into()), MO::new(rhs.into())],
-            }
+                ctx.block_map[&ctx.cur_block],
+            ));
+            *val = ext;
         }
+    }
+
+    let (rax, rdx): (Reg, Reg) = if sz == 8 {
+        (GR64::RAX.into(), GR64::RDX.into())
+    } else {
+        (GR32::EAX.into(), GR32::EDX.into())
+    };
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: copy_opcode(sz.max(4)),
+            operands: vec![MO::output(rax.into()), MO::input(lhs.into())],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    ctx.inst_seq.push(MachInstruction::new(
+        match (signed, sz == 8) {
+            (true, wide) => InstructionData {
+                opcode: if wide { Opcode::CQO } else { Opcode::CDQ },
+                operands: vec![
+                    MO::implicit_output(rdx.into()),
+                    MO::implicit_input(rax.into()),
+                ],
+            },
+            (false, _) => InstructionData {
+                opcode: Opcode::MOVri32,
+                operands: vec![MO::output(GR32::EDX.into()), MO::new(0.into())],
+            },
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: match (signed, sz == 8) {
+                (true, false) => Opcode::IDIVr32,
+                (true, true) => Opcode::IDIVr64,
+                (false, false) => Opcode::DIVr32,
+                (false, true) => Opcode::DIVr64,
+            },
+            operands: vec![
+                MO::implicit_input(rax.into()),
+                MO::implicit_input(rdx.into()),
+                MO::implicit_output(rax.into()),
+                MO::implicit_output(rdx.into()),
+                MO::input(rhs.into()),
+            ],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+
+    let result = if matches!(op, IrOpcode::SDiv | IrOpcode::UDiv) {
+        rax
+    } else {
+        rdx
+    };
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: copy_opcode(sz),
+            operands: vec![
+                MO::output(output.into()),
+                MO::input(
+                    to_reg_unit(result)
+                        .apply(&RegClass::for_type(ctx.types, ty)?)
+                        .into(),
+                ),
+            ],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+
+    Ok(())
+}
+
+/// Variable shift amounts have to be in CL. Immediate ones are masked as the hardware does.
+fn lower_shift(
+    ctx: &mut LoweringContext<X86_64>,
+    id: InstructionId,
+    op: IrOpcode,
+    ty: Type,
+    args: &[ValueId],
+) -> Result<()> {
+    use Opcode::*;
+
+    let sz = ctx.isa.data_layout().get_size_of(ctx.types, ty);
+    let lhs = get_vreg_for_val(ctx, ty, args[0])?;
+    let rhs = get_operand_for_val(ctx, ty, args[1])?;
+    let output = new_empty_inst_output(ctx, ty, id);
+
+    let (cl, imm) = match op {
+        IrOpcode::Shl => (
+            [SHLr8CL, SHLr16CL, SHLr32CL, SHLr64CL],
+            [SHLr8i8, SHLr16i8, SHLr32i8, SHLr64i8],
+        ),
+        IrOpcode::LShr => (
+            [SHRr8CL, SHRr16CL, SHRr32CL, SHRr64CL],
+            [SHRr8i8, SHRr16i8, SHRr32i8, SHRr64i8],
+        ),
+        _ => (
+            [SARr8CL, SARr16CL, SARr32CL, SARr64CL],
+            [SARr8i8, SARr16i8, SARr32i8, SARr64i8],
+        ),
+    };
+
+    let amount = match rhs {
         OperandData::VReg(rhs) => {
-            insert_move(ctx);
-            InstructionData {
-                opcode: match op {
-                    IrOpcode::Add => Opcode::ADDrr32,
-                    IrOpcode::Sub => Opcode::SUBrr32,
-                    IrOpcode::Mul => Opcode::IMULrr32,
-                    op => {
-                        return Err(
-                            LoweringError::Todo(format!("Unsupported opcode: {:?}", op)).into()
-                        )
-                    }
+            ctx.inst_seq.push(MachInstruction::new(
+                InstructionData {
+                    opcode: MOVrr8,
+                    operands: vec![MO::output(GR8::CL.into()), MO::input(rhs.into())],
                 },
-                operands: vec![MO::input_output(output.into()), MO::input(rhs.into())],
-            }
+                ctx.block_map[&ctx.cur_block],
+            ));
+            None
         }
-        e => return Err(LoweringError::Todo(format!("Unsupported operand: {:?}", e)).into()),
+        imm => match imm.sext_as_i64() {
+            Some(i) => Some(OperandData::Int8((i & (sz as i64 * 8 - 1)) as i8)),
+            None => return Err(LoweringError::Todo(format!("Unsupported shift: {:?}", imm)).into()),
+        },
     };
 
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: copy_opcode(sz),
+            operands: vec![MO::output(output.into()), MO::input(lhs.into())],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    ctx.inst_seq.push(MachInstruction::new(
+        match amount {
+            Some(amount) => InstructionData {
+                opcode: imm[width_index(sz)],
+                operands: vec![MO::input_output(output.into()), MO::new(amount)],
+            },
+            None => InstructionData {
+                opcode: cl[width_index(sz)],
+                operands: vec![MO::input_output(output.into()), MO::input(GR8::CL.into())],
+            },
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+
+    Ok(())
+}
+
+fn lower_icmp(
+    ctx: &mut LoweringContext<X86_64>,
+    id: InstructionId,
+    ty: Type,
+    args: &[ValueId; 2],
+    cond: ICmpCond,
+) -> Result<()> {
+    lower_icmp_flags(ctx, ty, args)?;
+    let output = new_empty_inst_output(ctx, types::I1, id);
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: match cond {
+                ICmpCond::Eq => Opcode::SETEr8,
+                ICmpCond::Ne => Opcode::SETNEr8,
+                ICmpCond::Slt => Opcode::SETLr8,
+                ICmpCond::Sle => Opcode::SETLEr8,
+                ICmpCond::Sgt => Opcode::SETGr8,
+                ICmpCond::Sge => Opcode::SETGEr8,
+                ICmpCond::Ult => Opcode::SETBr8,
+                ICmpCond::Ule => Opcode::SETBEr8,
+                ICmpCond::Ugt => Opcode::SETAr8,
+                ICmpCond::Uge => Opcode::SETAEr8,
+            },
+            operands: vec![MO::output(output.into())],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    Ok(())
+}
+
+/// Sets the flags by comparing `args`, for a following `jcc` or `setcc`.
+fn lower_icmp_flags(
+    ctx: &mut LoweringContext<X86_64>,
+    ty: Type,
+    args: &[ValueId; 2],
+) -> Result<()> {
+    let sz = ctx.isa.data_layout().get_size_of(ctx.types, ty);
+    let lhs = get_vreg_for_val(ctx, ty, args[0])?;
+    let rhs = get_imm_or_vreg_for_val(ctx, ty, args[1])?;
+    lower_cmp(ctx, sz, lhs, rhs);
+    Ok(())
+}
+
+/// Compares `lhs` of `sz` bytes with an immediate or a virtual register.
+fn lower_cmp(ctx: &mut LoweringContext<X86_64>, sz: usize, lhs: VReg, rhs: OperandData) {
+    use Opcode::*;
+    let data = match rhs {
+        OperandData::VReg(rhs) => InstructionData {
+            opcode: [CMPrr8, CMPrr16, CMPrr32, CMPrr64][width_index(sz)],
+            operands: vec![MO::input(lhs.into()), MO::input(rhs.into())],
+        },
+        rhs => InstructionData {
+            opcode: [CMPri8, CMPri16, CMPri32, CMPr64i32][width_index(sz)],
+            operands: vec![MO::input(lhs.into()), MO::new(rhs)],
+        },
+    };
     ctx.inst_seq
         .push(MachInstruction::new(data, ctx.block_map[&ctx.cur_block]));
+}
+
+/// `cmov` has no 8-bit form, so narrow values are selected as 32-bit ones.
+fn lower_select(
+    ctx: &mut LoweringContext<X86_64>,
+    id: InstructionId,
+    tys: &[Type; 2],
+    args: &[ValueId; 3],
+) -> Result<()> {
+    let ty = tys[1];
+    if ty.is_floating_point() {
+        return Err(
+            LoweringError::Todo("Unsupported select of floating-point values".into()).into(),
+        );
+    }
+    let sz = ctx.isa.data_layout().get_size_of(ctx.types, ty);
+    let cond = get_vreg_for_val(ctx, tys[0], args[0])?;
+    let then_ = get_vreg_for_val(ctx, ty, args[1])?;
+    let else_ = get_vreg_for_val(ctx, ty, args[2])?;
+    let output = new_empty_inst_output(ctx, ty, id);
+
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: copy_opcode(sz),
+            operands: vec![MO::output(output.into()), MO::input(else_.into())],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::TESTrr8,
+            operands: vec![MO::input(cond.into()), MO::input(cond.into())],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: if sz == 8 {
+                Opcode::CMOVNErr64
+            } else {
+                Opcode::CMOVNErr32
+            },
+            operands: vec![MO::input_output(output.into()), MO::input(then_.into())],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+
+    Ok(())
+}
+
+fn lower_float_bin(
+    ctx: &mut LoweringContext<X86_64>,
+    id: InstructionId,
+    op: IrOpcode,
+    ty: Type,
+    args: &[ValueId],
+) -> Result<()> {
+    let lhs = get_vreg_for_val(ctx, ty, args[0])?;
+    let rhs = get_vreg_for_val(ctx, ty, args[1])?;
+    let output = new_empty_inst_output(ctx, ty, id);
+
+    let opcode = match (op, ty.is_float()) {
+        (IrOpcode::FAdd, true) => Opcode::ADDSSrr,
+        (IrOpcode::FAdd, false) => Opcode::ADDSDrr,
+        (IrOpcode::FSub, true) => Opcode::SUBSSrr,
+        (IrOpcode::FSub, false) => Opcode::SUBSDrr,
+        (IrOpcode::FMul, true) => Opcode::MULSSrr,
+        (IrOpcode::FMul, false) => Opcode::MULSDrr,
+        (IrOpcode::FDiv, true) => Opcode::DIVSSrr,
+        (IrOpcode::FDiv, false) => Opcode::DIVSDrr,
+        (op, _) => return Err(LoweringError::Todo(format!("Unsupported opcode: {:?}", op)).into()),
+    };
+
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: fp_copy_opcode(ty),
+            operands: vec![MO::output(output.into()), MO::input(lhs.into())],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode,
+            operands: vec![MO::input_output(output.into()), MO::input(rhs.into())],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+
+    Ok(())
+}
+
+fn lower_float_cast(
+    ctx: &mut LoweringContext<X86_64>,
+    id: InstructionId,
+    op: IrOpcode,
+    tys: &[Type; 2],
+    arg: ValueId,
+) -> Result<()> {
+    let [from, to] = *tys;
+    let opcode = match (op, from, to) {
+        (IrOpcode::SIToFP, types::I32, types::FLOAT) => Opcode::CVTSI2SSrr32,
+        (IrOpcode::SIToFP, types::I64, types::FLOAT) => Opcode::CVTSI2SSrr64,
+        (IrOpcode::SIToFP, types::I32, types::DOUBLE) => Opcode::CVTSI2SDrr32,
+        (IrOpcode::SIToFP, types::I64, types::DOUBLE) => Opcode::CVTSI2SDrr64,
+        (IrOpcode::FPToSI, types::FLOAT, types::I32) => Opcode::CVTTSS2SIr32r,
+        (IrOpcode::FPToSI, types::FLOAT, types::I64) => Opcode::CVTTSS2SIr64r,
+        (IrOpcode::FPToSI, types::DOUBLE, types::I32) => Opcode::CVTTSD2SIr32r,
+        (IrOpcode::FPToSI, types::DOUBLE, types::I64) => Opcode::CVTTSD2SIr64r,
+        (IrOpcode::FPExt, types::FLOAT, types::DOUBLE) => Opcode::CVTSS2SDrr,
+        (IrOpcode::FPTrunc, types::DOUBLE, types::FLOAT) => Opcode::CVTSD2SSrr,
+        (op, from, to) => {
+            return Err(LoweringError::Todo(format!(
+                "Unsupported cast: {:?} from {} to {}",
+                op,
+                ctx.types.to_string(from),
+                ctx.types.to_string(to)
+            ))
+            .into())
+        }
+    };
+
+    let src = get_vreg_for_val(ctx, from, arg)?;
+    let output = new_empty_inst_output(ctx, to, id);
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode,
+            operands: vec![MO::output(output.into()), MO::input(src.into())],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
 
     Ok(())
 }
@@ -255,14 +685,13 @@ fn lower_sext(
 ) -> Result<()> {
     let from = tys[0];
     let to = tys[1];
-    // TODO
-    assert!(from.is_i32());
-    assert!(to.is_i64());
+    let from_sz = ctx.isa.data_layout().get_size_of(ctx.types, from);
+    let to_sz = ctx.isa.data_layout().get_size_of(ctx.types, to);
 
     let val = match ctx.ir_data.values[arg] {
         Value::Instruction(id) => {
             let is_mergeable_load =
-                ctx.ir_data.inst_ref(id).opcode == IrOpcode::Load && from.is_i32();
+                ctx.ir_data.inst_ref(id).opcode == IrOpcode::Load && from.is_i32() && to.is_i64();
 
             if is_mergeable_load {
                 let output = new_empty_inst_output(ctx, to, self_id);
@@ -277,9 +706,47 @@ fn lower_sext(
 
     let output = new_empty_inst_output(ctx, to, self_id);
 
+    // An `i1` is 0 or 1, which is negated to 0 or -1 after being zero-extended.
+    if from.is_i1() {
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode: Opcode::MOVZXr32r8,
+                operands: vec![MO::output(output.into()), MO::input(val.into())],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode: if to_sz == 8 {
+                    Opcode::NEGr64
+                } else {
+                    Opcode::NEGr32
+                },
+                operands: vec![MO::input_output(output.into())],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+        return Ok(());
+    }
+
+    let opcode = match (from_sz, to_sz) {
+        (1, 2 | 4) => Opcode::MOVSXr32r8,
+        (1, 8) => Opcode::MOVSXr64r8,
+        (2, 4) => Opcode::MOVSXr32r16,
+        (2, 8) => Opcode::MOVSXr64r16,
+        (4, 8) => Opcode::MOVSXDr64r32,
+        _ => {
+            return Err(LoweringError::Todo(format!(
+                "Unsupported sext from {} to {}",
+                ctx.types.to_string(from),
+                ctx.types.to_string(to)
+            ))
+            .into())
+        }
+    };
     ctx.inst_seq.push(MachInstruction::new(
         InstructionData {
-            opcode: Opcode::MOVSXDr64r32,
+            opcode,
             operands: vec![MO::output(output.into()), MO::input(val.into())],
         },
         ctx.block_map[&ctx.cur_block],
@@ -296,20 +763,74 @@ fn lower_zext(
 ) -> Result<()> {
     let from = tys[0];
     let to = tys[1];
+    let from_sz = ctx.isa.data_layout().get_size_of(ctx.types, from);
+    let to_sz = ctx.isa.data_layout().get_size_of(ctx.types, to);
+
+    // An `i1` is kept as 0 or 1 in a byte. Writing a 32-bit register clears the upper half of
+    // its 64-bit register.
+    let opcode = match (from_sz, to_sz) {
+        (1, 1) => Opcode::MOVrr8,
+        (1, 2 | 4 | 8) => Opcode::MOVZXr32r8,
+        (2, 4 | 8) => Opcode::MOVZXr32r16,
+        (4, 8) => Opcode::MOVZXr64r32,
+        _ => {
+            return Err(LoweringError::Todo(format!(
+                "Unsupported zext from {} to {}",
+                ctx.types.to_string(from),
+                ctx.types.to_string(to)
+            ))
+            .into())
+        }
+    };
 
-    assert!(from.is_i8());
-    assert!(to.is_i32());
-
-    let val = get_operand_for_val(ctx, from, arg)?;
+    let val = get_vreg_for_val(ctx, from, arg)?;
     let output = new_empty_inst_output(ctx, to, self_id);
 
     ctx.inst_seq.push(MachInstruction::new(
         InstructionData {
-            opcode: Opcode::MOVZXr32r8,
+            opcode,
+            operands: vec![MO::output(output.into()), MO::input(val.into())],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+
+    Ok(())
+}
+
+/// Lowers `trunc`, `ptrtoint` and `inttoptr`. Truncation copies the low part of the register,
+/// and pointers are zero-extended from narrower integers.
+fn lower_int_cast(
+    ctx: &mut LoweringContext<X86_64>,
+    self_id: InstructionId,
+    tys: &[Type; 2],
+    arg: ValueId,
+) -> Result<()> {
+    let from = tys[0];
+    let to = tys[1];
+    let from_sz = ctx.isa.data_layout().get_size_of(ctx.types, from);
+    let to_sz = ctx.isa.data_layout().get_size_of(ctx.types, to);
+    if from_sz < to_sz {
+        return lower_zext(ctx, self_id, tys, arg);
+    }
+
+    let val = get_vreg_for_val(ctx, from, arg)?;
+    let output = new_empty_inst_output(ctx, to, self_id);
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: copy_opcode(to_sz),
             operands: vec![MO::output(output.into()), MO::input(val.into())],
         },
         ctx.block_map[&ctx.cur_block],
     ));
+    if to.is_i1() && !from.is_i1() {
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode: Opcode::ANDri8,
+                operands: vec![MO::input_output(output.into()), MO::new(1i8.into())],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+    }
 
     Ok(())
 }
@@ -355,7 +876,7 @@ fn lower_gep(
     // NOTE: addr = base + mul.0*idx.0 + mul.1*idx.1 + ...
     let mut indices = vec![]; // (mul, idx)
     let mut cur_ty = gep.tys[1];
-    for (&arg, &arg_ty) in gep.args[1..].iter().zip(gep.tys[2..].iter()) {
+    for (i, (&arg, &arg_ty)) in gep.args[1..].iter().zip(gep.tys[2..].iter()).enumerate() {
         let idx = get_operand_for_val(ctx, arg_ty, arg)?;
         if cur_ty.is_struct(ctx.types) {
             let layout = ctx
@@ -366,11 +887,17 @@ fn lower_gep(
             let idx = idx.sext_as_i64().unwrap() as usize;
             let offset = layout.get_elem_offset(idx).unwrap();
             if offset != 0 {
-                indices.push((1 as i64, OperandData::Int64(offset as i64)));
+                indices.push((1, OperandData::Int64(offset as i64)));
             }
             cur_ty = ctx.types.base().element_at(cur_ty, idx).unwrap();
         } else {
-            cur_ty = ctx.types.get_element(cur_ty).unwrap();
+            // NOTE: The first index steps over the source element type (`tys[0]`) since the base
+            // pointer may be opaque.
+            cur_ty = if i == 0 {
+                gep.tys[0]
+            } else {
+                ctx.types.get_element(cur_ty).unwrap()
+            };
             let sz = ctx.isa.data_layout.get_size_of(ctx.types, cur_ty) as i64;
             if let Some(idx) = idx.sext_as_i64() {
                 if idx != 0 {
@@ -398,19 +925,35 @@ fn lower_gep(
     match &indices[..] {
         [] => {}
         [(1, x)] if x.sext_as_i64().is_some() => {
-            mem_imm = x.to_owned();
+            // Displacement is at most 32 bits. Larger offsets are used as the index instead.
+            let offset = x.sext_as_i64().unwrap();
+            match i32::try_from(offset) {
+                Ok(offset) => mem_imm = OperandData::Int32(offset),
+                Err(_) => {
+                    let idx = ctx.mach_data.vregs.add_vreg_data(types::I64);
+                    ctx.inst_seq.push(MachInstruction::new(
+                        InstructionData {
+                            opcode: Opcode::MOVri64,
+                            operands: vec![MO::output(idx.into()), MO::new(offset.into())],
+                        },
+                        ctx.block_map[&ctx.cur_block],
+                    ));
+                    mem_ridx = idx.into();
+                    mem_mul = 1i64.into();
+                }
+            }
         }
         [(_, x)] if x.sext_as_i64().is_some() => {
             unreachable!()
         }
         [(m, x)] if matches!(m, 1 | 2 | 4 | 8) => {
             mem_ridx = x.to_owned();
-            mem_mul = (*m as i64).into();
+            mem_mul = (*m).into();
         }
         _ => simple_case = false,
     }
 
-    let ty = ctx.types.base_mut().pointer(cur_ty);
+    let ty = ctx.ir_data.inst_ref(self_id).ty;
     let output = new_empty_inst_output(ctx, ty, self_id);
 
     if simple_case {
@@ -457,7 +1000,7 @@ fn lower_gep(
                 opcode: Opcode::IMULrr64i32,
                 operands: vec![
                     MO::output(mul_output.into()),
-                    MO::input(idx.into()),
+                    MO::input(idx),
                     MO::new(OperandData::Int64(mul)),
                 ],
             },
@@ -506,6 +1049,21 @@ fn lower_condbr(
             _ => None,
         }
     }
+    fn is_fcmp<'a>(
+        data: &'a IrData,
+        val: &Value,
+    ) -> Option<(InstructionId, &'a Type, &'a [ValueId; 2], &'a FCmpCond)> {
+        match val {
+            Value::Instruction(id) => {
+                let inst = data.inst_ref(*id);
+                match &inst.operand {
+                    Operand::FCmp(FCmp { ty, args, cond, .. }) => Some((*id, ty, args, cond)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
     fn is_trunc_from_i8(data: &IrData, val: &Value) -> Option<(InstructionId, ValueId)> {
         match val {
             Value::Instruction(id) => {
@@ -524,45 +1082,11 @@ fn lower_condbr(
         }
     }
 
-    let arg = ctx.ir_data.value_ref(arg);
+    let value = ctx.ir_data.value_ref(arg);
 
-    if let Some((icmp, ty, args, cond)) = is_icmp(ctx.ir_data, arg) {
+    if let Some((icmp, ty, args, cond)) = is_icmp(ctx.ir_data, value) {
         ctx.mark_as_merged(icmp);
-        let lhs = get_vreg_for_val(ctx, *ty, args[0])?;
-        let rhs = ctx.ir_data.value_ref(args[1]);
-        match rhs {
-            Value::Constant(ConstantValue::Int(ConstantInt::Int32(rhs))) => {
-                ctx.inst_seq.push(MachInstruction::new(
-                    InstructionData {
-                        opcode: Opcode::CMPri32,
-                        operands: vec![MO::input(lhs.into()), MO::new(rhs.into())],
-                    },
-                    ctx.block_map[&ctx.cur_block],
-                ));
-            }
-            Value::Constant(ConstantValue::Null(_)) => {
-                ctx.inst_seq.push(MachInstruction::new(
-                    InstructionData {
-                        opcode: Opcode::CMPri32,
-                        operands: vec![MO::input(lhs.into()), MO::new(0.into())],
-                    },
-                    ctx.block_map[&ctx.cur_block],
-                ));
-            }
-            Value::Argument(_) | Value::Instruction(_) => {
-                assert!(ty.is_i32() || ty.is_i64());
-                let rhs = get_operand_for_val(ctx, *ty, args[1])?;
-                ctx.inst_seq.push(MachInstruction::new(
-                    InstructionData {
-                        opcode: Opcode::CMPrr32, // TODO: CMPrr64
-                        operands: vec![MO::input(lhs.into()), MO::input(rhs.into())],
-                    },
-                    ctx.block_map[&ctx.cur_block],
-                ));
-            }
-            e => return Err(LoweringError::Todo(format!("Unsupported operand: {:?}", e)).into()),
-        }
-
+        lower_icmp_flags(ctx, *ty, args)?;
         ctx.inst_seq.push(MachInstruction::new(
             InstructionData {
                 opcode: match cond {
@@ -572,17 +1096,10 @@ fn lower_condbr(
                     ICmpCond::Slt => Opcode::JL,
                     ICmpCond::Sge => Opcode::JGE,
                     ICmpCond::Sgt => Opcode::JG,
-                    // ICmpCond::Ule => Opcode::JLE,
-                    // ICmpCond::Ult => Opcode::JL,
-                    // ICmpCond::Uge => Opcode::JGE,
-                    // ICmpCond::Ugt => Opcode::JG,
-                    e => {
-                        return Err(LoweringError::Todo(format!(
-                            "Unsupported icmp condition: {:?}",
-                            e
-                        ))
-                        .into())
-                    }
+                    ICmpCond::Ule => Opcode::JBE,
+                    ICmpCond::Ult => Opcode::JB,
+                    ICmpCond::Uge => Opcode::JAE,
+                    ICmpCond::Ugt => Opcode::JA,
                 },
                 operands: vec![MO::new(OperandData::Block(ctx.block_map[&blocks[0]]))],
             },
@@ -598,7 +1115,12 @@ fn lower_condbr(
         return Ok(());
     }
 
-    if let Some((trunc, src)) = is_trunc_from_i8(ctx.ir_data, arg) {
+    if let Some((fcmp, ty, args, cond)) = is_fcmp(ctx.ir_data, value) {
+        ctx.mark_as_merged(fcmp);
+        return lower_fcmp_condbr(ctx, *ty, args, *cond, blocks);
+    }
+
+    if let Some((trunc, src)) = is_trunc_from_i8(ctx.ir_data, value) {
         ctx.mark_as_merged(trunc);
         let lhs = get_vreg_for_val(ctx, types::I8, src)?;
         ctx.inst_seq.push(MachInstruction::new(
@@ -625,7 +1147,246 @@ fn lower_condbr(
         return Ok(());
     }
 
-    Err(LoweringError::Todo("Unsupported conditional br pattern".into()).into())
+    let cond = get_vreg_for_val(ctx, types::I1, arg)?;
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::TESTrr8,
+            operands: vec![MO::input(cond.into()), MO::input(cond.into())],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::JNE,
+            operands: vec![MO::new(OperandData::Block(ctx.block_map[&blocks[0]]))],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::JMP,
+            operands: vec![MO::new(OperandData::Block(ctx.block_map[&blocks[1]]))],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    Ok(())
+}
+
+/// Dense switches jump through a table of offsets from the table, and the others compare the
+/// condition with each case in turn.
+fn lower_switch(ctx: &mut LoweringContext<X86_64>, switch: &Switch) -> Result<()> {
+    let ty = switch.cond_ty();
+    let sz = ctx.isa.data_layout().get_size_of(ctx.types, ty);
+    let mask = u64::MAX >> (64 - sz * 8);
+    let mut cases = vec![];
+    for (&case, &block) in switch.cases().iter().zip(switch.blocks()) {
+        match ctx.ir_data.value_ref(case) {
+            Value::Constant(ConstantValue::Int(i)) => {
+                cases.push((i.cast_to_i64() as u64 & mask, ctx.block_map[&block]))
+            }
+            e => return Err(LoweringError::Todo(format!("Unsupported case: {:?}", e)).into()),
+        }
+    }
+    let default = ctx.block_map[&switch.default_block()];
+    let cond = get_vreg_for_val(ctx, ty, switch.cond())?;
+
+    let min = cases.iter().map(|&(c, _)| c).min().unwrap_or(0);
+    let max = cases.iter().map(|&(c, _)| c).max().unwrap_or(0);
+    let range = max - min;
+    let is_dense = cases.len() >= 4 && range < cases.len() as u64 * 5 / 2;
+    if !is_dense || (sz == 8 && min > i32::MAX as u64) {
+        for (case, block) in cases {
+            let imm = match sz {
+                1 => OperandData::Int8(case as i8),
+                2 | 4 => OperandData::Int32(case as i32),
+                _ if i32::try_from(case as i64).is_ok() => OperandData::Int32(case as i32),
+                _ => {
+                    let tmp = ctx.mach_data.vregs.add_vreg_data(ty);
+                    ctx.inst_seq.push(MachInstruction::new(
+                        InstructionData {
+                            opcode: Opcode::MOVri64,
+                            operands: vec![MO::output(tmp.into()), MO::new((case as i64).into())],
+                        },
+                        ctx.block_map[&ctx.cur_block],
+                    ));
+                    tmp.into()
+                }
+            };
+            lower_cmp(ctx, sz, cond, imm);
+            ctx.inst_seq.push(MachInstruction::new(
+                InstructionData {
+                    opcode: Opcode::JE,
+                    operands: vec![MO::new(OperandData::Block(block))],
+                },
+                ctx.block_map[&ctx.cur_block],
+            ));
+        }
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode: Opcode::JMP,
+                operands: vec![MO::new(OperandData::Block(default))],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+        return Ok(());
+    }
+
+    let mut table = vec![default; range as usize + 1];
+    for &(case, block) in cases.iter().rev() {
+        table[(case - min) as usize] = block;
+    }
+    let table = ctx.mach_data.create_jump_table(table);
+
+    // The index is computed in 32 bits unless the condition is wider, and then zero-extended for
+    // addressing.
+    let (idx_ty, idx_sz) = if sz == 8 {
+        (types::I64, 8)
+    } else {
+        (types::I32, 4)
+    };
+    let idx = ctx.mach_data.vregs.add_vreg_data(idx_ty);
+    let push = |ctx: &mut LoweringContext<X86_64>, opcode, operands| {
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData { opcode, operands },
+            ctx.block_map[&ctx.cur_block],
+        ))
+    };
+    push(
+        ctx,
+        match sz {
+            1 => Opcode::MOVZXr32r8,
+            2 => Opcode::MOVZXr32r16,
+            sz => copy_opcode(sz),
+        },
+        vec![MO::output(idx.into()), MO::input(cond.into())],
+    );
+    if min != 0 {
+        push(
+            ctx,
+            [Opcode::SUBri32, Opcode::SUBr64i32][(idx_sz == 8) as usize],
+            vec![MO::input_output(idx.into()), MO::new((min as i32).into())],
+        );
+    }
+    lower_cmp(ctx, idx_sz, idx, OperandData::Int32(range as i32));
+    push(ctx, Opcode::JA, vec![MO::new(OperandData::Block(default))]);
+    let idx = if idx_sz == 8 {
+        idx
+    } else {
+        let idx64 = ctx.mach_data.vregs.add_vreg_data(types::I64);
+        push(
+            ctx,
+            Opcode::MOVZXr64r32,
+            vec![MO::output(idx64.into()), MO::input(idx.into())],
+        );
+        idx64
+    };
+    let base = ctx.mach_data.vregs.add_vreg_data(types::I64);
+    push(
+        ctx,
+        Opcode::LEArm64,
+        vec![
+            MO::output(base.into()),
+            MO::new(OperandData::MemStart),
+            MO::new(OperandData::JumpTable(table)),
+            MO::new(OperandData::None),
+            MO::new(OperandData::None),
+            MO::input(OperandData::Reg(GR64::RIP.into())),
+            MO::input(OperandData::None),
+            MO::new(OperandData::None),
+        ],
+    );
+    let target = ctx.mach_data.vregs.add_vreg_data(types::I64);
+    push(
+        ctx,
+        Opcode::MOVSXDr64m32,
+        vec![
+            MO::output(target.into()),
+            MO::new(OperandData::MemStart),
+            MO::new(OperandData::None),
+            MO::new(OperandData::None),
+            MO::new(OperandData::None),
+            MO::input(base.into()),
+            MO::input(idx.into()),
+            MO::new(OperandData::Int32(4)),
+        ],
+    );
+    push(
+        ctx,
+        Opcode::ADDrr64,
+        vec![MO::input_output(target.into()), MO::input(base.into())],
+    );
+    push(ctx, Opcode::JMPr64, vec![MO::input(target.into())]);
+
+    Ok(())
+}
+
+/// `ucomiss` and `ucomisd` set the flags as an unsigned comparison does, and set ZF, PF and CF
+/// all together when either operand is NaN. Every condition is tested with at most two jumps,
+/// swapping the operands where that saves one.
+fn lower_fcmp_condbr(
+    ctx: &mut LoweringContext<X86_64>,
+    ty: Type,
+    args: &[ValueId; 2],
+    cond: FCmpCond,
+    blocks: [BasicBlockId; 2],
+) -> Result<()> {
+    let [then_, else_] = blocks;
+    let (swap, jumps, fallthrough): (bool, &[(Opcode, BasicBlockId)], BasicBlockId) = match cond {
+        FCmpCond::False => (false, &[], else_),
+        FCmpCond::True => (false, &[], then_),
+        FCmpCond::Oeq => (false, &[(Opcode::JNE, else_), (Opcode::JP, else_)], then_),
+        FCmpCond::Ogt => (false, &[(Opcode::JA, then_)], else_),
+        FCmpCond::Oge => (false, &[(Opcode::JAE, then_)], else_),
+        FCmpCond::Olt => (true, &[(Opcode::JA, then_)], else_),
+        FCmpCond::Ole => (true, &[(Opcode::JAE, then_)], else_),
+        FCmpCond::One => (false, &[(Opcode::JNE, then_)], else_),
+        FCmpCond::Ord => (false, &[(Opcode::JNP, then_)], else_),
+        FCmpCond::Ueq => (false, &[(Opcode::JE, then_)], else_),
+        FCmpCond::Ugt => (true, &[(Opcode::JB, then_)], else_),
+        FCmpCond::Uge => (true, &[(Opcode::JBE, then_)], else_),
+        FCmpCond::Ult => (false, &[(Opcode::JB, then_)], else_),
+        FCmpCond::Ule => (false, &[(Opcode::JBE, then_)], else_),
+        FCmpCond::Une => (false, &[(Opcode::JNE, then_), (Opcode::JP, then_)], else_),
+        FCmpCond::Uno => (false, &[(Opcode::JP, then_)], else_),
+    };
+
+    if !jumps.is_empty() {
+        let mut lhs = get_vreg_for_val(ctx, ty, args[0])?;
+        let mut rhs = get_vreg_for_val(ctx, ty, args[1])?;
+        if swap {
+            std::mem::swap(&mut lhs, &mut rhs)
+        }
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode: if ty.is_float() {
+                    Opcode::UCOMISSrr
+                } else {
+                    Opcode::UCOMISDrr
+                },
+                operands: vec![MO::input(lhs.into()), MO::input(rhs.into())],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+    }
+
+    for &(opcode, block) in jumps {
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode,
+                operands: vec![MO::new(OperandData::Block(ctx.block_map[&block]))],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+    }
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::JMP,
+            operands: vec![MO::new(OperandData::Block(ctx.block_map[&fallthrough]))],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+
+    Ok(())
 }
 
 fn lower_call(
@@ -653,13 +1414,38 @@ fn lower_call(
     } else {
         tys[0]
     };
+    let is_var_arg = ctx.types.get(tys[0]).is_some_and(|ty| {
+        matches!(
+            &*ty,
+            CompoundType::Function(FunctionType {
+                is_var_arg: true,
+                ..
+            })
+        )
+    });
     let result_sz = ctx.isa.data_layout().get_size_of(ctx.types, result_ty);
     let output = new_empty_inst_output(ctx, result_ty, id);
 
-    pass_args_to_regs(ctx, &tys[1..], &args[1..])?;
+    let xmm_used = pass_args_to_regs(ctx, &tys[1..], &args[1..])?;
+
+    // Variadic callees take the number of vector registers used in AL.
+    if is_var_arg && xmm_used > 0 {
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode: Opcode::MOVri32,
+                operands: vec![
+                    MO::output(GR32::EAX.into()),
+                    MO::new((xmm_used as i32).into()),
+                ],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+    }
 
     let result_reg: Reg = match result_sz {
+        _ if result_ty.is_floating_point() => XMM::XMM0.into(),
         1 => GR8::AL.into(),
+        2 => GR16::AX.into(),
         4 => GR32::EAX.into(),
         8 => GR64::RAX.into(),
         _ => GR32::EAX.into(),
@@ -677,9 +1463,8 @@ fn lower_call(
 
     if !ctx.ir_data.users_of(id).is_empty() {
         let opcode = match result_sz {
-            1 => Opcode::MOVrr8,
-            4 => Opcode::MOVrr32,
-            8 => Opcode::MOVrr64,
+            _ if result_ty.is_floating_point() => fp_copy_opcode(result_ty),
+            1 | 2 | 4 | 8 => copy_opcode(result_sz),
             n => todo!("Function result in {n} bytes is not supported yet"),
         };
         ctx.inst_seq.push(MachInstruction::new(
@@ -723,27 +1508,59 @@ fn lower_call_intrinsic(
     Ok(())
 }
 
+/// Moves `args` to the argument registers, and returns how many XMM registers are used.
 fn pass_args_to_regs(
     ctx: &mut LoweringContext<X86_64>,
     tys: &[Type],
     args: &[ValueId],
-) -> Result<()> {
+) -> Result<usize> {
     let gpru = RegInfo::arg_reg_list(&ctx.call_conv);
+    let xmmu = RegInfo::float_arg_reg_list(&ctx.call_conv);
+    let mut gpr_used = 0;
+    let mut xmm_used = 0;
+
+    for (&ty, &arg) in tys.iter().zip(args.iter()) {
+        if ty.is_floating_point() {
+            let arg = get_vreg_for_val(ctx, ty, arg)?;
+            let out = xmmu[xmm_used].apply(&RegClass::XMM);
+            xmm_used += 1;
+            ctx.inst_seq.push(MachInstruction::new(
+                InstructionData {
+                    opcode: fp_copy_opcode(ty),
+                    operands: vec![MO::output(out.into()), MO::input(arg.into())],
+                },
+                ctx.block_map[&ctx.cur_block],
+            ));
+            continue;
+        }
 
-    for (gpr_used, (&ty, &arg)) in tys.iter().zip(args.iter()).enumerate() {
         let arg = get_operand_for_val(ctx, ty, arg)?;
-        let out = gpru[gpr_used].apply(&RegClass::for_type(ctx.types, ty));
+        let out = gpru[gpr_used].apply(&RegClass::for_type(ctx.types, ty)?);
+        gpr_used += 1;
         let opcode = match &arg {
             OperandData::Int64(_) => Opcode::MOVri64,
-            OperandData::Int32(_) => Opcode::MOVri32,
+            // Narrow immediates are moved to the 32-bit register, as `mov r8, imm8` would keep the
+            // upper bits.
+            OperandData::Int8(_) | OperandData::Int32(_) => {
+                let imm = arg.sext_as_i64().unwrap() as i32;
+                ctx.inst_seq.push(MachInstruction::new(
+                    InstructionData {
+                        opcode: Opcode::MOVri32,
+                        operands: vec![
+                            MO::output(to_reg_unit(out).apply(&RegClass::GR32).into()),
+                            MO::new(imm.into()),
+                        ],
+                    },
+                    ctx.block_map[&ctx.cur_block],
+                ));
+                continue;
+            }
             OperandData::Reg(_) => Opcode::MOVrr32, // TODO: FIXME
             OperandData::VReg(vreg) => {
                 let ty = ctx.mach_data.vregs.type_for(*vreg);
                 let sz = ctx.isa.data_layout().get_size_of(ctx.types, ty);
                 match sz {
-                    1 => Opcode::MOVrr8,
-                    4 => Opcode::MOVrr32,
-                    8 => Opcode::MOVrr64,
+                    1 | 2 | 4 | 8 => copy_opcode(sz),
                     e => {
                         return Err(LoweringError::Todo(format!(
                             "Unsupported argument size: {:?}",
@@ -764,15 +1581,18 @@ fn pass_args_to_regs(
         ));
     }
 
-    Ok(())
+    Ok(xmm_used)
 }
 
 fn lower_return(ctx: &mut LoweringContext<X86_64>, arg: Option<(Type, ValueId)>) -> Result<()> {
     if let Some((ty, value)) = arg {
         let vreg = get_vreg_for_val(ctx, ty, value)?;
         let sz = ctx.isa.data_layout().get_size_of(ctx.types, ty);
-        assert!(ty.is_integer() || ty.is_pointer(ctx.types));
+        assert!(ty.is_integer() || ty.is_pointer(ctx.types) || ty.is_floating_point());
         let (reg, opcode) = match sz {
+            _ if ty.is_floating_point() => (XMM::XMM0.into(), fp_copy_opcode(ty)),
+            1 => (GR8::AL.into(), Opcode::MOVrr8),
+            2 => (GR16::AX.into(), Opcode::MOVrr16),
             4 => (GR32::EAX.into(), Opcode::MOVrr32),
             8 => (GR64::RAX.into(), Opcode::MOVrr64),
             _ => todo!(),
@@ -840,8 +1660,17 @@ fn get_operand_for_const(
     konst: &ConstantValue,
 ) -> Result<OperandData> {
     match konst {
+        ConstantValue::Int(ConstantInt::Int1(i)) => Ok(OperandData::Int8(*i as i8)),
+        ConstantValue::Int(ConstantInt::Int8(i)) => Ok(OperandData::Int8(*i)),
+        ConstantValue::Int(ConstantInt::Int16(i)) => Ok(OperandData::Int32(*i as i32)),
         ConstantValue::Int(ConstantInt::Int32(i)) => Ok(OperandData::Int32(*i)),
         ConstantValue::Int(ConstantInt::Int64(i)) => Ok(OperandData::Int64(*i)),
+        // SSE has no immediate operands, so floating-point constants are carried as their bits
+        // until they are moved to XMM registers.
+        ConstantValue::Float(ConstantFloat::Float(f)) => Ok(OperandData::Int32(f.to_bits() as i32)),
+        ConstantValue::Float(ConstantFloat::Double(f)) => {
+            Ok(OperandData::Int64(f.to_bits() as i64))
+        }
         ConstantValue::Expr(ConstantExpr::GetElementPtr {
             inbounds: _,
             tys: _,
@@ -922,12 +1751,41 @@ fn get_operand_for_const(
 
 fn get_vreg_for_val(ctx: &mut LoweringContext<X86_64>, ty: Type, val: ValueId) -> Result<VReg> {
     match get_operand_for_val(ctx, ty, val)? {
-        OperandData::Int32(i) => {
+        imm @ (OperandData::Int32(_) | OperandData::Int64(_)) if ty.is_floating_point() => {
+            let (int_ty, mov, movx) = if ty.is_float() {
+                (types::I32, Opcode::MOVri32, Opcode::MOVDrr32)
+            } else {
+                (types::I64, Opcode::MOVri64, Opcode::MOVQrr64)
+            };
+            let bits = ctx.mach_data.vregs.add_vreg_data(int_ty);
+            ctx.inst_seq.push(MachInstruction::new(
+                InstructionData {
+                    opcode: mov,
+                    operands: vec![MO::output(bits.into()), MO::new(imm)],
+                },
+                ctx.block_map[&ctx.cur_block],
+            ));
             let output = ctx.mach_data.vregs.add_vreg_data(ty);
             ctx.inst_seq.push(MachInstruction::new(
                 InstructionData {
-                    opcode: Opcode::MOVri32,
-                    operands: vec![MO::output(output.into()), MO::new(i.into())],
+                    opcode: movx,
+                    operands: vec![MO::output(output.into()), MO::input(bits.into())],
+                },
+                ctx.block_map[&ctx.cur_block],
+            ));
+            Ok(output)
+        }
+        imm @ (OperandData::Int8(_) | OperandData::Int32(_) | OperandData::Int64(_)) => {
+            let output = ctx.mach_data.vregs.add_vreg_data(ty);
+            let (opcode, imm) = match imm {
+                OperandData::Int8(i) => (Opcode::MOVri32, OperandData::Int32(i as i32)),
+                OperandData::Int64(_) => (Opcode::MOVri64, imm),
+                imm => (Opcode::MOVri32, imm),
+            };
+            ctx.inst_seq.push(MachInstruction::new(
+                InstructionData {
+                    opcode,
+                    operands: vec![MO::output(output.into()), MO::new(imm)],
                 },
                 ctx.block_map[&ctx.cur_block],
             ));
@@ -937,3 +1795,57 @@ fn get_vreg_for_val(ctx: &mut LoweringContext<X86_64>, ty: Type, val: ValueId) -
         e => Err(LoweringError::Todo(format!("Unsupported operand: {:?}", e)).into()),
     }
 }
+
+/// Returns `val` as an immediate if it fits in an instruction, or in a virtual register otherwise.
+fn get_imm_or_vreg_for_val(
+    ctx: &mut LoweringContext<X86_64>,
+    ty: Type,
+    val: ValueId,
+) -> Result<OperandData> {
+    if let Value::Constant(ConstantValue::Null(_)) = ctx.ir_data.values[val] {
+        return Ok(OperandData::Int32(0));
+    }
+    match get_operand_for_val(ctx, ty, val)? {
+        OperandData::Int64(i) if i32::try_from(i).is_ok() => Ok(OperandData::Int32(i as i32)),
+        OperandData::Int64(i) => {
+            let output = ctx.mach_data.vregs.add_vreg_data(ty);
+            ctx.inst_seq.push(MachInstruction::new(
+                InstructionData {
+                    opcode: Opcode::MOVri64,
+                    operands: vec![MO::output(output.into()), MO::new(i.into())],
+                },
+                ctx.block_map[&ctx.cur_block],
+            ));
+            Ok(output.into())
+        }
+        op => Ok(op),
+    }
+}
+
+/// Returns the move between general-purpose registers of `sz` bytes.
+fn copy_opcode(sz: usize) -> Opcode {
+    match sz {
+        1 => Opcode::MOVrr8,
+        2 => Opcode::MOVrr16,
+        4 => Opcode::MOVrr32,
+        _ => Opcode::MOVrr64,
+    }
+}
+
+/// Returns the index of an opcode for `sz` bytes in `[8 bits, 16 bits, 32 bits, 64 bits]`.
+fn width_index(sz: usize) -> usize {
+    match sz {
+        1 => 0,
+        2 => 1,
+        4 => 2,
+        _ => 3,
+    }
+}
+
+fn fp_copy_opcode(ty: Type) -> Opcode {
+    if ty.is_float() {
+        Opcode::MOVSSrr
+    } else {
+        Opcode::MOVSDrr
+    }
+}
@@ -3,7 +3,7 @@ use crate::{
     function::instruction::Instruction as MachInstruction,
     isa::x86_64::{
         instruction::{InstructionData, Opcode, Operand as MOperand, OperandData},
-        lower::get_operand_for_val,
+        lower::{get_operand_for_val, get_vreg_for_val},
         X86_64,
     },
     isa::TargetIsa,
@@ -50,6 +50,10 @@ pub fn lower_store(
     let src = args[0];
     let src_ty = tys[0];
     match ctx.ir_data.value_ref(src) {
+        // Floating-point constants are stored from XMM registers.
+        Value::Constant(_) if src_ty.is_floating_point() => {
+            vreg = Some(get_vreg_for_val(ctx, src_ty, src)?)
+        }
         Value::Constant(c) => konst = Some(c),
         Value::Instruction(id) => vreg = Some(get_inst_output(ctx, tys[0], *id)?),
         Value::Argument(a) => vreg = ctx.arg_idx_to_vreg.get(&a.nth).copied(),
@@ -57,14 +61,17 @@ pub fn lower_store(
     }
 
     let sz = ctx.isa.data_layout().get_size_of(ctx.types, src_ty);
-    assert!(sz == 1 || sz == 4 || sz == 8);
+    assert!(sz == 1 || sz == 2 || sz == 4 || sz == 8);
 
     match (dst_vreg, dst_slot, vreg, konst) {
         (None, Some(slot), Some(vreg), None) => {
             ctx.inst_seq.append(&mut vec![MachInstruction::new(
                 InstructionData {
                     opcode: match sz {
+                        4 if src_ty.is_float() => Opcode::MOVSSmr,
+                        8 if src_ty.is_double() => Opcode::MOVSDmr,
                         1 => Opcode::MOVmr8,
+                        2 => Opcode::MOVmr16,
                         4 => Opcode::MOVmr32,
                         8 => Opcode::MOVmr64,
                         _ => todo!(),
@@ -88,7 +95,10 @@ pub fn lower_store(
             ctx.inst_seq.append(&mut vec![MachInstruction::new(
                 InstructionData {
                     opcode: match sz {
+                        4 if src_ty.is_float() => Opcode::MOVSSmr,
+                        8 if src_ty.is_double() => Opcode::MOVSDmr,
                         1 => Opcode::MOVmr8,
+                        2 => Opcode::MOVmr16,
                         4 => Opcode::MOVmr32,
                         8 => Opcode::MOVmr64,
                         _ => todo!(),
@@ -113,6 +123,7 @@ pub fn lower_store(
                 InstructionData {
                     opcode: match sz {
                         1 => Opcode::MOVmi8,
+                        2 => Opcode::MOVmi16,
                         4 => Opcode::MOVmi32,
                         8 => Opcode::MOVmi64,
                         _ => panic!(),
@@ -127,6 +138,7 @@ pub fn lower_store(
                         MOperand::new(OperandData::None),
                         MOperand::input(match konst {
                             ConstantValue::Int(ConstantInt::Int8(i)) => i.into(),
+                            ConstantValue::Int(ConstantInt::Int16(i)) => (*i as i32).into(),
                             ConstantValue::Int(ConstantInt::Int32(i)) => i.into(),
                             ConstantValue::Null(_) => 0i64.into(),
                             _ => panic!(),
@@ -258,7 +270,11 @@ fn lower_store_gep(
             let src = get_inst_output(ctx, src_ty, *id)?;
             ctx.inst_seq.append(&mut vec![MachInstruction::new(
                 InstructionData {
-                    opcode: Opcode::MOVmr32,
+                    opcode: if src_ty.is_float() {
+                        Opcode::MOVSSmr
+                    } else {
+                        Opcode::MOVmr32
+                    },
                     operands: mem
                         .into_iter()
                         .chain(vec![MOperand::input(src.into())].into_iter())
@@ -1,12 +1,19 @@
 pub mod asm;
+pub mod elf;
+pub mod encode;
 pub mod instruction;
+#[cfg(unix)]
+pub mod jit;
 pub mod lower;
 pub mod pass;
 pub mod register;
 
 use super::TargetIsa;
-use crate::{call_conv::CallConvKind, isa::x86_64, module::Module, pass::regalloc};
-use anyhow::Result;
+use crate::{
+    call_conv::CallConvKind,
+    isa::x86_64,
+    pass::{regalloc, Pass},
+};
 use vicis_core::ir::module::data_layout::DataLayout;
 
 #[derive(Clone)]
@@ -28,13 +35,17 @@ impl TargetIsa for X86_64 {
     type RegClass = register::RegClass;
     type RegInfo = register::RegInfo;
 
-    fn module_passes() -> Vec<for<'a, 'b> fn(&'b mut Module<'a, Self>) -> Result<()>> {
+    fn module_passes() -> Vec<Pass<Self>> {
         vec![
-            regalloc::run_on_module,
-            pass::phi_elimination::run_on_module, // TODO: should be target independent
-            pass::simple_reg_coalescing::run_on_module,
-            pass::eliminate_slot::run_on_module,
-            pass::pro_epi_inserter::run_on_module,
+            Pass::new("regalloc", regalloc::run_on_module),
+            // TODO: should be target independent
+            Pass::new("phi_elimination", pass::phi_elimination::run_on_module),
+            Pass::new(
+                "simple_reg_coalescing",
+                pass::simple_reg_coalescing::run_on_module,
+            ),
+            Pass::new("eliminate_slot", pass::eliminate_slot::run_on_module),
+            Pass::new("pro_epi_inserter", pass::pro_epi_inserter::run_on_module),
         ]
     }
 
@@ -2,22 +2,23 @@ use crate::{
     function::{basic_block::BasicBlockId, instruction::Instruction, Function},
     isa::x86_64::{
         instruction::{InstructionData, Opcode, Operand, OperandData},
+        register::{to_reg_unit, RegClass, GR32, GR64},
         X86_64,
     },
     module::Module,
     register::Reg,
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
 use rustc_hash::FxHashMap;
 
 pub fn run_on_module(module: &mut Module<X86_64>) -> Result<()> {
     for (_, func) in &mut module.functions {
-        run_on_function(func);
+        run_on_function(func)?;
     }
     Ok(())
 }
 
-pub fn run_on_function(function: &mut Function<X86_64>) {
+pub fn run_on_function(function: &mut Function<X86_64>) -> Result<()> {
     let mut worklist = vec![];
     let mut map: FxHashMap<Reg, Vec<(OperandData, BasicBlockId)>> = FxHashMap::default();
 
@@ -43,29 +44,34 @@ pub fn run_on_function(function: &mut Function<X86_64>) {
         for (arg, block) in args {
             let maybe_term = function.layout.last_inst_of(block).unwrap();
             // assert!(matches!(arg, OperandData::Int32(_)));
-            let copy = match arg {
-                OperandData::Int32(_) => Instruction::new(
-                    InstructionData {
-                        opcode: Opcode::MOVri32,
-                        operands: vec![
-                            Operand::output(OperandData::Reg(output)),
-                            Operand::new(arg),
-                        ],
-                    },
-                    block,
-                ),
-                OperandData::Reg(_) => Instruction::new(
-                    InstructionData {
-                        opcode: Opcode::MOVrr32,
-                        operands: vec![
-                            Operand::output(OperandData::Reg(output)),
-                            Operand::input(arg),
-                        ],
-                    },
-                    block,
+            if output.0 == RegClass::XMM as u16 {
+                for copy in xmm_copy(output, arg, block)? {
+                    let copy = function.data.create_inst(copy);
+                    function.layout.insert_inst_before(maybe_term, copy, block);
+                }
+                continue;
+            }
+            let (opcode, output, arg) = match arg {
+                OperandData::Int8(i) => (
+                    Opcode::MOVri32,
+                    gr32(output),
+                    Operand::new((i as i32).into()),
                 ),
-                _ => todo!(),
+                OperandData::Int32(_) if output.0 == RegClass::GR64 as u16 => {
+                    (Opcode::MOVri64, output, Operand::new(arg))
+                }
+                OperandData::Int32(_) => (Opcode::MOVri32, gr32(output), Operand::new(arg)),
+                OperandData::Int64(_) => (Opcode::MOVri64, output, Operand::new(arg)),
+                OperandData::Reg(_) => (copy_opcode(output), output, Operand::input(arg)),
+                e => bail!("unsupported phi argument: {:?}", e),
             };
+            let copy = Instruction::new(
+                InstructionData {
+                    opcode,
+                    operands: vec![Operand::output(OperandData::Reg(output)), arg],
+                },
+                block,
+            );
             let copy = function.data.create_inst(copy);
             function.layout.insert_inst_before(maybe_term, copy, block);
         }
@@ -74,4 +80,69 @@ pub fn run_on_function(function: &mut Function<X86_64>) {
     for inst_id in worklist {
         function.remove_inst(inst_id);
     }
+
+    Ok(())
+}
+
+/// Returns the move between general-purpose registers of the class of `output`.
+fn copy_opcode(output: Reg) -> Opcode {
+    match output.0 {
+        c if c == RegClass::GR8 as u16 => Opcode::MOVrr8,
+        c if c == RegClass::GR16 as u16 => Opcode::MOVrr16,
+        c if c == RegClass::GR32 as u16 => Opcode::MOVrr32,
+        _ => Opcode::MOVrr64,
+    }
+}
+
+/// Returns the 32-bit register of `r`, which immediates narrower than 64 bits are moved to.
+fn gr32(r: Reg) -> Reg {
+    to_reg_unit(r).apply(&RegClass::GR32)
+}
+
+/// Copies `arg` to an XMM register. Constants are passed as their bit patterns, which have to go
+/// through R11 since SSE has no immediate operands. R11 is never allocated to values.
+fn xmm_copy(
+    output: Reg,
+    arg: OperandData,
+    block: BasicBlockId,
+) -> Result<Vec<Instruction<InstructionData>>> {
+    let (mov, movx, scratch): (_, _, Reg) = match arg {
+        OperandData::Reg(_) => {
+            return Ok(vec![Instruction::new(
+                InstructionData {
+                    opcode: Opcode::MOVSDrr,
+                    operands: vec![
+                        Operand::output(OperandData::Reg(output)),
+                        Operand::input(arg),
+                    ],
+                },
+                block,
+            )])
+        }
+        OperandData::Int32(_) => (Opcode::MOVri32, Opcode::MOVDrr32, GR32::R11D.into()),
+        OperandData::Int64(_) => (Opcode::MOVri64, Opcode::MOVQrr64, GR64::R11.into()),
+        e => bail!("unsupported floating-point phi argument: {:?}", e),
+    };
+    Ok(vec![
+        Instruction::new(
+            InstructionData {
+                opcode: mov,
+                operands: vec![
+                    Operand::output(OperandData::Reg(scratch)),
+                    Operand::new(arg),
+                ],
+            },
+            block,
+        ),
+        Instruction::new(
+            InstructionData {
+                opcode: movx,
+                operands: vec![
+                    Operand::output(OperandData::Reg(output)),
+                    Operand::input(OperandData::Reg(scratch)),
+                ],
+            },
+            block,
+        ),
+    ])
 }
@@ -20,7 +20,12 @@ pub fn run_on_function(function: &mut Function<X86_64>) {
         for inst_id in function.layout.inst_iter(block_id) {
             let inst = function.data.inst_ref(inst_id);
             match inst.data.opcode {
-                Opcode::MOVrr32 | Opcode::MOVrr64
+                Opcode::MOVrr8
+                | Opcode::MOVrr16
+                | Opcode::MOVrr32
+                | Opcode::MOVrr64
+                | Opcode::MOVSSrr
+                | Opcode::MOVSDrr
                     if RegInfo::to_reg_unit(*inst.data.operands[0].data.as_reg())
                         == RegInfo::to_reg_unit(*inst.data.operands[1].data.as_reg()) =>
                 {
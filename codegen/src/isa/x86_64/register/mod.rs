@@ -1,22 +1,26 @@
 use crate::{
     call_conv::CallConvKind,
+    lower::LoweringError,
     register::{Reg, RegUnit, RegisterClass, RegisterInfo},
 };
+use anyhow::Result;
 use std::fmt;
 use vicis_core::ir::types::{self, Type, Types};
 
 pub struct RegInfo;
 
+// Registers of every class are numbered as in the hardware encoding, so that the same index
+// refers to the same register unit.
 pub enum GR8 {
     // TODO: AH, CH, DH, BH
     AL,
     CL,
     DL,
     BL,
+    SPL,
+    BPL,
     SIL,
     DIL,
-    BPL,
-    SPL,
     R8B,
     R9B,
     R10B,
@@ -28,6 +32,26 @@ pub enum GR8 {
     IP,
 }
 
+pub enum GR16 {
+    AX,
+    CX,
+    DX,
+    BX,
+    SP,
+    BP,
+    SI,
+    DI,
+    R8W,
+    R9W,
+    R10W,
+    R11W,
+    R12W,
+    R13W,
+    R14W,
+    R15W,
+    IP,
+}
+
 pub enum GR32 {
     EAX,
     ECX,
@@ -68,10 +92,31 @@ pub enum GR64 {
     RIP,
 }
 
+pub enum XMM {
+    XMM0,
+    XMM1,
+    XMM2,
+    XMM3,
+    XMM4,
+    XMM5,
+    XMM6,
+    XMM7,
+    XMM8,
+    XMM9,
+    XMM10,
+    XMM11,
+    XMM12,
+    XMM13,
+    XMM14,
+    XMM15,
+}
+
 pub enum RegClass {
     GR8,
+    GR16,
     GR32,
     GR64,
+    XMM,
 }
 
 impl From<GR8> for Reg {
@@ -80,6 +125,12 @@ impl From<GR8> for Reg {
     }
 }
 
+impl From<GR16> for Reg {
+    fn from(r: GR16) -> Self {
+        Reg(RegClass::GR16 as u16, r as u16)
+    }
+}
+
 impl From<GR32> for Reg {
     fn from(r: GR32) -> Self {
         Reg(RegClass::GR32 as u16, r as u16)
@@ -92,12 +143,24 @@ impl From<GR64> for Reg {
     }
 }
 
+impl From<XMM> for Reg {
+    fn from(r: XMM) -> Self {
+        Reg(RegClass::XMM as u16, r as u16)
+    }
+}
+
 impl From<GR8> for RegUnit {
     fn from(r: GR8) -> Self {
         RegUnit(RegClass::GR64 as u16, r as u16)
     }
 }
 
+impl From<GR16> for RegUnit {
+    fn from(r: GR16) -> Self {
+        RegUnit(RegClass::GR64 as u16, r as u16)
+    }
+}
+
 impl From<GR32> for RegUnit {
     fn from(r: GR32) -> Self {
         RegUnit(RegClass::GR64 as u16, r as u16)
@@ -110,6 +173,12 @@ impl From<GR64> for RegUnit {
     }
 }
 
+impl From<XMM> for RegUnit {
+    fn from(r: XMM) -> Self {
+        RegUnit(RegClass::XMM as u16, r as u16)
+    }
+}
+
 const ARG_REGS: [RegUnit; 6] = [
     RegUnit(RegClass::GR64 as u16, GR64::RDI as u16),
     RegUnit(RegClass::GR64 as u16, GR64::RSI as u16),
@@ -119,6 +188,17 @@ const ARG_REGS: [RegUnit; 6] = [
     RegUnit(RegClass::GR64 as u16, GR64::R9 as u16),
 ];
 
+const FLOAT_ARG_REGS: [RegUnit; 8] = [
+    RegUnit(RegClass::XMM as u16, XMM::XMM0 as u16),
+    RegUnit(RegClass::XMM as u16, XMM::XMM1 as u16),
+    RegUnit(RegClass::XMM as u16, XMM::XMM2 as u16),
+    RegUnit(RegClass::XMM as u16, XMM::XMM3 as u16),
+    RegUnit(RegClass::XMM as u16, XMM::XMM4 as u16),
+    RegUnit(RegClass::XMM as u16, XMM::XMM5 as u16),
+    RegUnit(RegClass::XMM as u16, XMM::XMM6 as u16),
+    RegUnit(RegClass::XMM as u16, XMM::XMM7 as u16),
+];
+
 const CSR: [RegUnit; 6] = [
     RegUnit(RegClass::GR64 as u16, GR64::RBX as u16),
     RegUnit(RegClass::GR64 as u16, GR64::R12 as u16),
@@ -128,21 +208,26 @@ const CSR: [RegUnit; 6] = [
     RegUnit(RegClass::GR64 as u16, GR64::RBP as u16),
 ];
 
+impl RegInfo {
+    /// Registers for floating-point arguments, which are counted apart from integer ones.
+    pub fn float_arg_reg_list(cc: &CallConvKind) -> &'static [RegUnit] {
+        match cc {
+            CallConvKind::SystemV => &FLOAT_ARG_REGS,
+            CallConvKind::AAPCS64 | CallConvKind::LP64D => &[],
+        }
+    }
+}
+
 impl RegisterInfo for RegInfo {
     fn arg_reg_list(cc: &CallConvKind) -> &'static [RegUnit] {
         match cc {
             CallConvKind::SystemV => &ARG_REGS,
-            CallConvKind::AAPCS64 => &[],
+            CallConvKind::AAPCS64 | CallConvKind::LP64D => &[],
         }
     }
 
     fn to_reg_unit(r: Reg) -> RegUnit {
-        match r {
-            Reg(/*GR8*/ 0, x) => RegUnit(RegClass::GR64 as u16, x),
-            Reg(/*GR32*/ 1, x) => RegUnit(RegClass::GR64 as u16, x),
-            Reg(/*GR64*/ 2, x) => RegUnit(RegClass::GR64 as u16, x),
-            _ => panic!(),
-        }
+        to_reg_unit(r)
     }
 
     fn is_csr(r: RegUnit) -> bool {
@@ -151,13 +236,17 @@ impl RegisterInfo for RegInfo {
 }
 
 impl RegisterClass for RegClass {
-    fn for_type(types: &Types, ty: Type) -> Self {
+    fn for_type(types: &Types, ty: Type) -> Result<Self> {
         match ty {
-            types::I8 => RegClass::GR8,
-            types::I32 => RegClass::GR32,
-            types::I64 => RegClass::GR64,
-            _ if ty.is_pointer(types) => RegClass::GR64,
-            e => todo!("{}", types.to_string(e)),
+            types::I1 | types::I8 => Ok(RegClass::GR8),
+            types::I16 => Ok(RegClass::GR16),
+            types::I32 => Ok(RegClass::GR32),
+            types::I64 => Ok(RegClass::GR64),
+            types::FLOAT | types::DOUBLE => Ok(RegClass::XMM),
+            _ if ty.is_pointer(types) => Ok(RegClass::GR64),
+            e => {
+                Err(LoweringError::Todo(format!("Unsupported type: {}", types.to_string(e))).into())
+            }
         }
     }
 
@@ -167,6 +256,10 @@ impl RegisterClass for RegClass {
                 .into_iter()
                 .map(Into::into)
                 .collect(),
+            RegClass::GR16 => vec![GR16::AX, GR16::CX, GR16::DX, GR16::DI, GR16::SI]
+                .into_iter()
+                .map(Into::into)
+                .collect(),
             RegClass::GR32 => vec![GR32::EAX, GR32::ECX, GR32::EDX, GR32::EDI, GR32::ESI]
                 .into_iter()
                 .map(|r| r.into())
@@ -175,6 +268,25 @@ impl RegisterClass for RegClass {
                 .into_iter()
                 .map(|r| r.into())
                 .collect(),
+            RegClass::XMM => vec![
+                XMM::XMM0,
+                XMM::XMM1,
+                XMM::XMM2,
+                XMM::XMM3,
+                XMM::XMM4,
+                XMM::XMM5,
+                XMM::XMM6,
+                XMM::XMM7,
+                XMM::XMM8,
+                XMM::XMM9,
+                XMM::XMM10,
+                XMM::XMM11,
+                XMM::XMM12,
+                XMM::XMM13,
+            ]
+            .into_iter()
+            .map(|r| r.into())
+            .collect(),
         }
     }
 
@@ -191,6 +303,17 @@ impl RegisterClass for RegClass {
             .into_iter()
             .map(Into::into)
             .collect(),
+            RegClass::GR16 => vec![
+                GR16::BX,
+                GR16::R12W,
+                GR16::R13W,
+                GR16::R14W,
+                GR16::R15W,
+                GR16::BP,
+            ]
+            .into_iter()
+            .map(Into::into)
+            .collect(),
             RegClass::GR32 => vec![
                 GR32::EBX,
                 GR32::R12D,
@@ -213,27 +336,42 @@ impl RegisterClass for RegClass {
             .into_iter()
             .map(|r| r.into())
             .collect(),
+            // No XMM register is callee-saved. These two come last so that the register
+            // allocator keeps them for reloading spilled values, as it does for RBX..RBP.
+            RegClass::XMM => vec![XMM::XMM14, XMM::XMM15]
+                .into_iter()
+                .map(|r| r.into())
+                .collect(),
         }
     }
 
     fn apply_for(&self, ru: RegUnit) -> Reg {
         match self {
             Self::GR8 => Reg(RegClass::GR8 as u16, ru.1),
+            Self::GR16 => Reg(RegClass::GR16 as u16, ru.1),
             Self::GR32 => Reg(RegClass::GR32 as u16, ru.1),
             Self::GR64 => Reg(RegClass::GR64 as u16, ru.1),
+            Self::XMM => Reg(RegClass::XMM as u16, ru.1),
         }
     }
 }
 
 pub fn to_reg_unit(r: Reg) -> RegUnit {
     match r {
-        Reg(/*GR8*/ 0, x) => RegUnit(2, x),
-        Reg(/*GR32*/ 1, x) => RegUnit(2, x),
-        Reg(/*GR64*/ 2, x) => RegUnit(2, x),
+        Reg(/*GR8*/ 0, x) => RegUnit(RegClass::GR64 as u16, x),
+        Reg(/*GR16*/ 1, x) => RegUnit(RegClass::GR64 as u16, x),
+        Reg(/*GR32*/ 2, x) => RegUnit(RegClass::GR64 as u16, x),
+        Reg(/*GR64*/ 3, x) => RegUnit(RegClass::GR64 as u16, x),
+        Reg(/*XMM*/ 4, x) => RegUnit(RegClass::XMM as u16, x),
         _ => panic!(),
     }
 }
 
+/// Returns true if `r` is a general-purpose register of any width.
+pub fn is_gpr(r: Reg) -> bool {
+    r.0 != RegClass::XMM as u16
+}
+
 impl fmt::Debug for GR64 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -282,7 +420,7 @@ impl fmt::Debug for GR32 {
                 Self::EBP => "ebp",
                 Self::ESI => "esi",
                 Self::EDI => "edi",
-                Self::R8D => "r8",
+                Self::R8D => "r8d",
                 Self::R9D => "r9d",
                 Self::R10D => "r10d",
                 Self::R11D => "r11d",
@@ -304,8 +442,12 @@ impl fmt::Display for GR32 {
 
 pub fn reg_to_str(r: &Reg) -> &'static str {
     let gr8 = [
-        "al", "cl", "dl", "ah", "ch", "dh", "bl", "bh", "sil", "dil", "bpl", "spl", "r8b", "r9b",
-        "r10b", "r11b", "r12b", "r13b", "r14b", "r15b", "ip",
+        "al", "cl", "dl", "bl", "spl", "bpl", "sil", "dil", "r8b", "r9b", "r10b", "r11b", "r12b",
+        "r13b", "r14b", "r15b", "ip",
+    ];
+    let gr16 = [
+        "ax", "cx", "dx", "bx", "sp", "bp", "si", "di", "r8w", "r9w", "r10w", "r11w", "r12w",
+        "r13w", "r14w", "r15w", "ip",
     ];
     let gr32 = [
         "eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "r8d", "r9d", "r10d", "r11d",
@@ -315,10 +457,16 @@ pub fn reg_to_str(r: &Reg) -> &'static str {
         "rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12",
         "r13", "r14", "r15", "rip",
     ];
+    let xmm = [
+        "xmm0", "xmm1", "xmm2", "xmm3", "xmm4", "xmm5", "xmm6", "xmm7", "xmm8", "xmm9", "xmm10",
+        "xmm11", "xmm12", "xmm13", "xmm14", "xmm15",
+    ];
     match r {
         Reg(0, i) => gr8[*i as usize],
-        Reg(1, i) => gr32[*i as usize],
-        Reg(2, i) => gr64[*i as usize],
+        Reg(1, i) => gr16[*i as usize],
+        Reg(2, i) => gr32[*i as usize],
+        Reg(3, i) => gr64[*i as usize],
+        Reg(4, i) => xmm[*i as usize],
         e => todo!("{:?}", e),
     }
 }
@@ -10,6 +10,7 @@ use super::{
     },
     isa::TargetIsa,
     module::Module as MachModule,
+    pass::{regalloc::RegAllocKind, PassManager},
     register::VReg,
 };
 use anyhow::Result;
@@ -54,9 +55,36 @@ pub enum LoweringError {
     Todo(String),
 }
 
+/// Options for `compile_module_with_options`.
+pub struct CompileOptions<T: TargetIsa> {
+    pub regalloc: RegAllocKind,
+
+    /// Machine passes run on the lowered module. Defaults to the target's pipeline.
+    pub passes: PassManager<T>,
+}
+
+impl<T: TargetIsa> Default for CompileOptions<T> {
+    fn default() -> Self {
+        Self {
+            regalloc: RegAllocKind::default(),
+            passes: PassManager::with_default_passes(),
+        }
+    }
+}
+
 pub fn compile_module<'a, T: TargetIsa>(
     isa: &'a T,
     module: &'a IrModule,
+) -> Result<MachModule<'a, T>> {
+    compile_module_with_options(isa, module, &mut CompileOptions::default())
+}
+
+/// Same as `compile_module`, but with `options`. Pass timings of the compilation are left in
+/// `options.passes`.
+pub fn compile_module_with_options<'a, T: TargetIsa>(
+    isa: &'a T,
+    module: &'a IrModule,
+    options: &mut CompileOptions<T>,
 ) -> Result<MachModule<'a, T>> {
     let mut functions = Arena::new();
 
@@ -69,11 +97,10 @@ pub fn compile_module<'a, T: TargetIsa>(
         functions,
         types: module.types.clone(),
         isa,
+        regalloc: options.regalloc,
     };
 
-    for pass in T::module_passes() {
-        pass(&mut mach_module)?
-    }
+    options.passes.run_on_module(&mut mach_module)?;
 
     Ok(mach_module)
 }
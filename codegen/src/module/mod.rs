@@ -1,4 +1,4 @@
-use super::{function::Function, isa::TargetIsa};
+use super::{function::Function, isa::TargetIsa, pass::regalloc::RegAllocKind};
 use id_arena::Arena;
 use std::fmt;
 use vicis_core::ir::{module::Module as IrModule, types::Types};
@@ -8,6 +8,7 @@ pub struct Module<'a, T: TargetIsa> {
     pub functions: Arena<Function<'a, T>>,
     pub types: Types,
     pub isa: &'a T,
+    pub regalloc: RegAllocKind,
 }
 
 pub struct DisplayAsm<'a, T: TargetIsa>(pub &'a Module<'a, T>);
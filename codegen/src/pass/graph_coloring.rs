@@ -0,0 +1,511 @@
+//! Graph-coloring register allocator based on iterated register coalescing
+//! (George and Appel, "Iterated Register Coalescing", 1996).
+//!
+//! Copies between virtual registers (and operands of phi nodes) are coalesced when the
+//! registers don't interfere and merging them keeps the graph colorable (Briggs' test). Both
+//! ends of a coalesced copy get the same register, so the copy is removed later by
+//! `simple_reg_coalescing`. Unlike the linear scan, virtual registers live across a call are
+//! not spilled up front but restricted to callee-saved registers.
+
+use crate::{
+    function::{instruction::TargetInst, Function},
+    isa::TargetIsa,
+    pass::{
+        liveness::{LiveSegment, Liveness},
+        regalloc, spiller,
+    },
+    register::{Reg, RegUnit, RegisterClass, RegisterInfo, VReg},
+};
+use anyhow::{bail, Result};
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::{cmp::Ordering, collections::BTreeSet};
+
+pub fn run_on_function<T: TargetIsa>(function: &mut Function<T>) -> Result<()> {
+    let mut liveness = Liveness::<T>::new();
+    liveness.analyze_function(function);
+
+    let mut all_vregs = FxHashSet::default();
+    for block_id in function.layout.block_iter() {
+        for inst_id in function.layout.inst_iter(block_id) {
+            let inst = function.data.inst_ref(inst_id);
+            for r in inst.data.all_vregs() {
+                all_vregs.insert(r);
+            }
+        }
+    }
+
+    // Virtual registers created by the spiller. They live only from a reload (or a def) to the
+    // next instruction, so spilling them again doesn't help.
+    let mut spill_regs = FxHashSet::default();
+
+    loop {
+        let mut graph = InterferenceGraph::build(function, &liveness, &all_vregs, &spill_regs)?;
+        graph.color();
+
+        let spilled = graph.spilled_vregs();
+        if spilled.is_empty() {
+            let assigned_regs = graph.assigned_regs(function)?;
+            regalloc::rewrite_vregs(function, &assigned_regs);
+            return Ok(());
+        }
+
+        for vreg in spilled {
+            if spill_regs.contains(&vreg) {
+                bail!("no register is left for a reloaded value: {:?}", vreg);
+            }
+            log::debug!("spill: {:?}", vreg);
+            let mut new_vregs = vec![];
+            spiller::Spiller::new(function, &mut liveness).spill(vreg, &mut new_vregs);
+            all_vregs.remove(&vreg);
+            spill_regs.extend(new_vregs.iter().copied());
+            all_vregs.extend(new_vregs);
+        }
+    }
+}
+
+/// Interference graph of virtual registers. A node is a virtual register, or a set of them
+/// once coalesced, and is identified by its index in `vregs`.
+struct InterferenceGraph {
+    vregs: Vec<VReg>,
+    /// Registers each node may be assigned, in the order of preference.
+    colors: Vec<Vec<RegUnit>>,
+    /// Registers each node is copied from or to.
+    preferred: Vec<Vec<RegUnit>>,
+    /// `None` if the node must not be spilled.
+    spill_costs: Vec<Option<usize>>,
+    adj_set: FxHashSet<(usize, usize)>,
+    adj_list: Vec<Vec<usize>>,
+    degree: Vec<usize>,
+    /// (destination, source)
+    moves: Vec<(usize, usize)>,
+    move_list: Vec<Vec<usize>>,
+    worklist_moves: BTreeSet<usize>,
+    active_moves: BTreeSet<usize>,
+    simplify_worklist: BTreeSet<usize>,
+    freeze_worklist: BTreeSet<usize>,
+    spill_worklist: BTreeSet<usize>,
+    alias: Vec<Option<usize>>,
+    on_stack: Vec<bool>,
+    select_stack: Vec<usize>,
+    color: Vec<Option<RegUnit>>,
+    spilled: BTreeSet<usize>,
+}
+
+impl InterferenceGraph {
+    fn build<T: TargetIsa>(
+        function: &Function<T>,
+        liveness: &Liveness<T>,
+        all_vregs: &FxHashSet<VReg>,
+        spill_regs: &FxHashSet<VReg>,
+    ) -> Result<Self> {
+        let mut vregs: Vec<VReg> = all_vregs.iter().copied().collect();
+        vregs.sort();
+        let index: FxHashMap<VReg, usize> =
+            vregs.iter().enumerate().map(|(i, &r)| (r, i)).collect();
+        let n = vregs.len();
+
+        let calls: Vec<LiveSegment> = function
+            .layout
+            .block_iter()
+            .flat_map(|block_id| function.layout.inst_iter(block_id))
+            .filter(|&inst_id| function.data.inst_ref(inst_id).data.is_call())
+            .map(|inst_id| LiveSegment::new_point(liveness.inst_to_pp[&inst_id]))
+            .collect();
+
+        let preferred = regalloc::collect_preferred_registers(function, all_vregs);
+
+        let mut graph = Self {
+            vregs,
+            colors: Vec::with_capacity(n),
+            preferred: Vec::with_capacity(n),
+            spill_costs: Vec::with_capacity(n),
+            adj_set: FxHashSet::default(),
+            adj_list: vec![vec![]; n],
+            degree: vec![0; n],
+            moves: vec![],
+            move_list: vec![vec![]; n],
+            worklist_moves: BTreeSet::new(),
+            active_moves: BTreeSet::new(),
+            simplify_worklist: BTreeSet::new(),
+            freeze_worklist: BTreeSet::new(),
+            spill_worklist: BTreeSet::new(),
+            alias: vec![None; n],
+            on_stack: vec![false; n],
+            select_stack: vec![],
+            color: vec![None; n],
+            spilled: BTreeSet::new(),
+        };
+
+        // Registers a node can't be assigned are excluded from its colors, instead of being
+        // nodes of the graph: ones holding a live value of a physical register (e.g. an
+        // argument), and caller-saved ones if the node is live across a call.
+        let mut banks = Vec::with_capacity(n);
+        for &vreg in &graph.vregs {
+            let rc = T::RegClass::for_type(&function.types, function.data.vregs.type_for(vreg))?;
+            let mut allocatable = rc.gpr_list();
+            allocatable.append(&mut rc.csr_list());
+            let _ = allocatable.pop(); // TODO: Don't used RBP.
+            let allocatable: Vec<RegUnit> = allocatable
+                .into_iter()
+                .map(T::RegInfo::to_reg_unit)
+                .collect();
+            banks.push(allocatable.first().map(|r| r.0));
+
+            let lr = &liveness.vreg_lrs_map[&vreg];
+            let across_call = calls.iter().any(|call| lr.interfere_with_segment(call));
+            graph.colors.push(
+                allocatable
+                    .into_iter()
+                    .filter(|&r| !across_call || r.is_csr::<T::RegInfo>())
+                    .filter(|r| {
+                        liveness
+                            .reg_lrs_map
+                            .get(r)
+                            .is_none_or(|reg_lr| !lr.interfere(reg_lr))
+                    })
+                    .collect(),
+            );
+
+            graph.preferred.push(
+                preferred
+                    .get(&vreg)
+                    .into_iter()
+                    .flatten()
+                    .map(|&r| T::RegInfo::to_reg_unit(r))
+                    .collect(),
+            );
+
+            graph.spill_costs.push(if spill_regs.contains(&vreg) {
+                None
+            } else {
+                Some(function.data.vreg_users.get(vreg).len())
+            });
+        }
+
+        // Sweep the live segments in the order of their start. A segment ending before the
+        // current one starts can't interfere with any later segment.
+        let mut segments = vec![];
+        for (i, vreg) in graph.vregs.iter().enumerate() {
+            for seg in &liveness.vreg_lrs_map[vreg].0 {
+                segments.push((seg, i));
+            }
+        }
+        segments.sort_by_key(|(seg, _)| seg.start);
+        let mut active: Vec<(&LiveSegment, usize)> = vec![];
+        for (seg, i) in segments {
+            active.retain(|(s, _)| s.end > seg.start);
+            for &(s, j) in &active {
+                if i != j && banks[i] == banks[j] && s.interfere(seg) {
+                    graph.add_edge(i, j);
+                }
+            }
+            active.push((seg, i));
+        }
+
+        for block_id in function.layout.block_iter() {
+            for inst_id in function.layout.inst_iter(block_id) {
+                let inst = function.data.inst_ref(inst_id);
+                if !inst.data.is_copy() && !inst.data.is_phi() {
+                    continue;
+                }
+                let outputs = inst.data.output_vregs();
+                if outputs.len() != 1 {
+                    continue;
+                }
+                let dst = index[&outputs[0]];
+                for input in inst.data.input_vregs() {
+                    let src = index[&input];
+                    if src != dst && banks[src] == banks[dst] {
+                        graph.add_move(dst, src);
+                    }
+                }
+            }
+        }
+
+        Ok(graph)
+    }
+
+    fn add_edge(&mut self, u: usize, v: usize) {
+        if u == v || !self.adj_set.insert((u, v)) {
+            return;
+        }
+        self.adj_set.insert((v, u));
+        self.adj_list[u].push(v);
+        self.adj_list[v].push(u);
+        self.degree[u] += 1;
+        self.degree[v] += 1;
+    }
+
+    fn add_move(&mut self, dst: usize, src: usize) {
+        let m = self.moves.len();
+        self.moves.push((dst, src));
+        self.move_list[dst].push(m);
+        self.move_list[src].push(m);
+        self.worklist_moves.insert(m);
+    }
+
+    /// Number of registers the node may be assigned.
+    fn k(&self, n: usize) -> usize {
+        self.colors[n].len()
+    }
+
+    fn color(&mut self) {
+        self.make_worklist();
+
+        loop {
+            if let Some(n) = self.simplify_worklist.pop_first() {
+                self.simplify(n)
+            } else if let Some(m) = self.worklist_moves.pop_first() {
+                self.coalesce(m)
+            } else if let Some(n) = self.freeze_worklist.pop_first() {
+                self.freeze(n)
+            } else if !self.spill_worklist.is_empty() {
+                self.select_spill()
+            } else {
+                break;
+            }
+        }
+
+        self.assign_colors();
+    }
+
+    fn make_worklist(&mut self) {
+        for n in 0..self.vregs.len() {
+            if self.degree[n] >= self.k(n) {
+                self.spill_worklist.insert(n);
+            } else if self.is_move_related(n) {
+                self.freeze_worklist.insert(n);
+            } else {
+                self.simplify_worklist.insert(n);
+            }
+        }
+    }
+
+    /// Returns the neighbors of `n` still in the graph.
+    fn adjacent(&self, n: usize) -> Vec<usize> {
+        self.adj_list[n]
+            .iter()
+            .copied()
+            .filter(|&m| !self.on_stack[m] && self.alias[m].is_none())
+            .collect()
+    }
+
+    /// Returns the moves of `n` that may still be coalesced.
+    fn node_moves(&self, n: usize) -> Vec<usize> {
+        self.move_list[n]
+            .iter()
+            .copied()
+            .filter(|m| self.active_moves.contains(m) || self.worklist_moves.contains(m))
+            .collect()
+    }
+
+    fn is_move_related(&self, n: usize) -> bool {
+        !self.node_moves(n).is_empty()
+    }
+
+    fn simplify(&mut self, n: usize) {
+        self.on_stack[n] = true;
+        self.select_stack.push(n);
+        for m in self.adjacent(n) {
+            self.decrement_degree(m);
+        }
+    }
+
+    fn decrement_degree(&mut self, m: usize) {
+        let d = self.degree[m];
+        self.degree[m] = d - 1;
+        if d == self.k(m) && self.spill_worklist.remove(&m) {
+            self.enable_moves(m);
+            for n in self.adjacent(m) {
+                self.enable_moves(n);
+            }
+            if self.is_move_related(m) {
+                self.freeze_worklist.insert(m);
+            } else {
+                self.simplify_worklist.insert(m);
+            }
+        }
+    }
+
+    fn enable_moves(&mut self, n: usize) {
+        for m in self.node_moves(n) {
+            if self.active_moves.remove(&m) {
+                self.worklist_moves.insert(m);
+            }
+        }
+    }
+
+    fn get_alias(&self, mut n: usize) -> usize {
+        while let Some(a) = self.alias[n] {
+            n = a
+        }
+        n
+    }
+
+    fn add_work_list(&mut self, u: usize) {
+        if !self.is_move_related(u) && self.degree[u] < self.k(u) && self.freeze_worklist.remove(&u)
+        {
+            self.simplify_worklist.insert(u);
+        }
+    }
+
+    fn coalesce(&mut self, m: usize) {
+        let (x, y) = self.moves[m];
+        let u = self.get_alias(x);
+        let v = self.get_alias(y);
+
+        if u == v {
+            self.add_work_list(u);
+        } else if self.adj_set.contains(&(u, v))
+            || !self.colors[u].iter().any(|c| self.colors[v].contains(c))
+        {
+            // Constrained
+            self.add_work_list(u);
+            self.add_work_list(v);
+        } else if self.is_conservative(u, v) {
+            self.combine(u, v);
+            self.add_work_list(u);
+        } else {
+            self.active_moves.insert(m);
+        }
+    }
+
+    /// Briggs' test: the node made by merging `u` and `v` has fewer neighbors of significant
+    /// degree than the registers it may be assigned, so it is colorable.
+    fn is_conservative(&self, u: usize, v: usize) -> bool {
+        let k = self.colors[u]
+            .iter()
+            .filter(|c| self.colors[v].contains(c))
+            .count();
+        let neighbors: BTreeSet<usize> = self
+            .adjacent(u)
+            .into_iter()
+            .chain(self.adjacent(v))
+            .collect();
+        neighbors
+            .into_iter()
+            .filter(|&t| self.degree[t] >= self.k(t))
+            .count()
+            < k
+    }
+
+    fn combine(&mut self, u: usize, v: usize) {
+        if !self.freeze_worklist.remove(&v) {
+            self.spill_worklist.remove(&v);
+        }
+        self.alias[v] = Some(u);
+
+        let moves = self.move_list[v].clone();
+        self.move_list[u].extend(moves);
+        let colors = std::mem::take(&mut self.colors[v]);
+        self.colors[u].retain(|c| colors.contains(c));
+        let preferred = std::mem::take(&mut self.preferred[v]);
+        self.preferred[u].extend(preferred);
+        self.spill_costs[u] = self.spill_costs[u]
+            .zip(self.spill_costs[v])
+            .map(|(a, b)| a + b);
+
+        self.enable_moves(v);
+        for t in self.adjacent(v) {
+            self.add_edge(t, u);
+            self.decrement_degree(t);
+        }
+        if self.degree[u] >= self.k(u) && self.freeze_worklist.remove(&u) {
+            self.spill_worklist.insert(u);
+        }
+    }
+
+    fn freeze(&mut self, u: usize) {
+        self.simplify_worklist.insert(u);
+        self.freeze_moves(u);
+    }
+
+    fn freeze_moves(&mut self, u: usize) {
+        for m in self.node_moves(u) {
+            let (x, y) = self.moves[m];
+            let v = if self.get_alias(y) == self.get_alias(u) {
+                self.get_alias(x)
+            } else {
+                self.get_alias(y)
+            };
+            self.active_moves.remove(&m);
+            self.worklist_moves.remove(&m);
+            if !self.is_move_related(v)
+                && self.degree[v] < self.k(v)
+                && self.freeze_worklist.remove(&v)
+            {
+                self.simplify_worklist.insert(v);
+            }
+        }
+    }
+
+    /// Picks the node whose spill costs the least per interfering node. It may still be
+    /// colored in `assign_colors`.
+    fn select_spill(&mut self) {
+        let n = *self
+            .spill_worklist
+            .iter()
+            .min_by(|&&a, &&b| self.compare_spill_costs(a, b))
+            .unwrap();
+        self.spill_worklist.remove(&n);
+        self.simplify_worklist.insert(n);
+        self.freeze_moves(n);
+    }
+
+    fn compare_spill_costs(&self, a: usize, b: usize) -> Ordering {
+        match (self.spill_costs[a], self.spill_costs[b]) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(x), Some(y)) => (x * self.degree[b]).cmp(&(y * self.degree[a])),
+        }
+    }
+
+    fn assign_colors(&mut self) {
+        while let Some(n) = self.select_stack.pop() {
+            let mut ok_colors = self.colors[n].clone();
+            for &w in &self.adj_list[n] {
+                if let Some(c) = self.color[self.get_alias(w)] {
+                    ok_colors.retain(|&x| x != c);
+                }
+            }
+            let color = self.preferred[n]
+                .iter()
+                .find(|c| ok_colors.contains(c))
+                .or_else(|| ok_colors.first())
+                .copied();
+            match color {
+                Some(c) => self.color[n] = Some(c),
+                None => {
+                    self.spilled.insert(n);
+                }
+            }
+        }
+
+        for n in 0..self.vregs.len() {
+            if self.alias[n].is_some() {
+                self.color[n] = self.color[self.get_alias(n)];
+            }
+        }
+    }
+
+    /// Returns the virtual registers that couldn't be colored.
+    fn spilled_vregs(&self) -> Vec<VReg> {
+        (0..self.vregs.len())
+            .filter(|&n| self.spilled.contains(&self.get_alias(n)))
+            .map(|n| self.vregs[n])
+            .collect()
+    }
+
+    fn assigned_regs<T: TargetIsa>(&self, function: &Function<T>) -> Result<FxHashMap<VReg, Reg>> {
+        self.vregs
+            .iter()
+            .zip(self.color.iter())
+            .map(|(&vreg, color)| {
+                let rc =
+                    T::RegClass::for_type(&function.types, function.data.vregs.type_for(vreg))?;
+                Ok((vreg, color.unwrap().apply(&rc)))
+            })
+            .collect()
+    }
+}
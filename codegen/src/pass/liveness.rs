@@ -1,6 +1,6 @@
 use crate::{
     function::{
-        basic_block::BasicBlockId,
+        basic_block::{BasicBlock, BasicBlockId},
         instruction::{Instruction, InstructionId, TargetInst},
         Function,
     },
@@ -10,6 +10,7 @@ use crate::{
 };
 use rustc_hash::{FxHashMap, FxHashSet};
 use std::cmp::Ordering;
+use vicis_core::pass::analysis::dataflow::{Analysis, Dataflow, Direction, UnionSet};
 
 pub struct Liveness<T: TargetIsa> {
     pub block_data: FxHashMap<BasicBlockId, BlockData>,
@@ -394,73 +395,118 @@ impl<T: TargetIsa> Liveness<T> {
     }
 
     fn visit(&mut self, func: &Function<T>) {
+        let mut live_regs = LiveRegs {
+            block_data: &self.block_data,
+            uses: FxHashMap::default(),
+            phi_uses: FxHashMap::default(),
+        };
         for block_id in func.layout.block_iter() {
+            let mut defined = FxHashSet::default();
             for inst_id in func.layout.inst_iter(block_id) {
                 let inst = func.data.inst_ref(inst_id);
-                self.visit_inst(func, inst, block_id);
+                live_regs.add_uses::<T>(inst, block_id, &mut defined);
+            }
+        }
+
+        let live = Dataflow::new(func, &live_regs);
+        let LiveRegs { phi_uses, .. } = live_regs;
+        for (block_id, data) in self.block_data.iter_mut() {
+            if let Some(live_in) = live.entry_of(*block_id) {
+                data.live_in = live_in.0.clone();
+            }
+            if let Some(live_out) = live.exit_of(*block_id) {
+                data.live_out = live_out.0.clone();
+            }
+            // Operands of phi nodes are live at the entry of the block as well.
+            for (_, input) in phi_uses.get(block_id).into_iter().flatten() {
+                if !data.def.contains(input) {
+                    data.live_in.insert(*input);
+                }
             }
         }
     }
+}
+
+/// Registers live at the entry of each block, where operands of phi nodes are live only out of
+/// their incoming blocks.
+struct LiveRegs<'a> {
+    block_data: &'a FxHashMap<BasicBlockId, BlockData>,
+    uses: FxHashMap<BasicBlockId, FxHashSet<Reg>>,
+    /// (incoming block, operand)
+    phi_uses: FxHashMap<BasicBlockId, Vec<(Option<BasicBlockId>, Reg)>>,
+}
 
-    fn visit_inst(
+impl LiveRegs<'_> {
+    /// Records the uses of `inst` that are not preceded by a definition in the same block.
+    /// `defined` holds the registers defined by the instructions visited so far in the block.
+    fn add_uses<T: TargetIsa>(
         &mut self,
-        func: &Function<T>,
         inst: &Instruction<T::Inst>,
         block_id: BasicBlockId,
+        defined: &mut FxHashSet<Reg>,
     ) {
         for (i, input) in inst.data.input_vregs_with_indexes() {
-            self.propagate_reg(
-                func,
-                Reg::Virt(input),
-                block_id,
-                if inst.data.is_phi() {
-                    inst.data.block_at(i + 1)
-                } else {
-                    None
-                },
-            );
+            if inst.data.is_phi() {
+                self.phi_uses
+                    .entry(block_id)
+                    .or_default()
+                    .push((inst.data.block_at(i + 1), Reg::Virt(input)));
+            } else if !defined.contains(&Reg::Virt(input)) {
+                self.uses
+                    .entry(block_id)
+                    .or_default()
+                    .insert(Reg::Virt(input));
+            }
         }
         for input in inst.data.input_regs() {
-            self.propagate_reg(
-                func,
-                Reg::Phys(T::RegInfo::to_reg_unit(input)),
-                block_id,
-                None,
-            );
+            let input = Reg::Phys(T::RegInfo::to_reg_unit(input));
+            if !defined.contains(&input) {
+                self.uses.entry(block_id).or_default().insert(input);
+            }
         }
+        defined.extend(inst.data.output_vregs().into_iter().map(Reg::Virt));
+        defined.extend(
+            inst.data
+                .output_regs()
+                .into_iter()
+                .map(|r| Reg::Phys(T::RegInfo::to_reg_unit(r))),
+        );
     }
+}
 
-    fn propagate_reg(
-        &mut self,
-        func: &Function<T>,
-        input: Reg,
-        block_id: BasicBlockId,
-        phi_pred: Option<BasicBlockId>,
-    ) {
-        {
-            let data = self.block_data.get_mut(&block_id).unwrap();
+impl Analysis<BasicBlock> for LiveRegs<'_> {
+    type Fact = UnionSet<Reg>;
 
-            if data.def.contains(&input) {
-                return;
-            }
+    const DIRECTION: Direction = Direction::Backward;
 
-            if !data.live_in.insert(input) {
-                return;
-            }
-        }
+    fn boundary(&self) -> Self::Fact {
+        UnionSet::default()
+    }
 
-        for pred_id in func.data.basic_blocks[block_id].preds.iter() {
-            if let Some(phi_pred) = phi_pred && *pred_id != phi_pred {
-                continue;
-            }
-            if self
-                .block_data
-                .get_mut(pred_id)
-                .unwrap()
-                .live_out
-                .insert(input)
-            {
-                self.propagate_reg(func, input, *pred_id, None);
+    fn init(&self) -> Self::Fact {
+        UnionSet::default()
+    }
+
+    fn transfer(&self, block_id: BasicBlockId, live_out: &Self::Fact) -> Self::Fact {
+        // Registers defined in the block are live at its entry only if used before the definition.
+        let def = &self.block_data[&block_id].def;
+        let uses = self.uses.get(&block_id).into_iter().flatten();
+        UnionSet(
+            live_out
+                .0
+                .iter()
+                .filter(|reg| !def.contains(reg))
+                .chain(uses)
+                .copied()
+                .collect(),
+        )
+    }
+
+    fn transfer_edge(&self, succ: BasicBlockId, pred: BasicBlockId, fact: &mut Self::Fact) {
+        let def = &self.block_data[&succ].def;
+        for (incoming, input) in self.phi_uses.get(&succ).into_iter().flatten() {
+            if incoming.map_or(true, |b| b == pred) && !def.contains(input) {
+                fact.0.insert(*input);
             }
         }
     }
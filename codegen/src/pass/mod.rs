@@ -1,3 +1,182 @@
+pub mod graph_coloring;
 pub mod liveness;
 pub mod regalloc;
 pub mod spiller;
+
+use crate::{function::Function, isa::TargetIsa, module::Module};
+use anyhow::{anyhow, Result};
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+type PassFn<T> = Box<dyn for<'a, 'b> FnMut(&'b mut Module<'a, T>) -> Result<()>>;
+
+/// A named pass on machine modules.
+pub struct Pass<T: TargetIsa> {
+    name: String,
+    run: PassFn<T>,
+}
+
+/// Time a pass took in the last `PassManager::run_on_module`.
+#[derive(Debug, Clone)]
+pub struct PassTiming {
+    pub name: String,
+    pub elapsed: Duration,
+}
+
+/// Runs machine passes in order, optionally dumping the module before and after each of them.
+pub struct PassManager<T: TargetIsa> {
+    passes: Vec<Pass<T>>,
+    print_before: Vec<String>,
+    print_after: Vec<String>,
+    print_before_all: bool,
+    print_after_all: bool,
+    dump_output: Box<dyn Write>,
+    timings: Vec<PassTiming>,
+}
+
+impl<T: TargetIsa> Pass<T> {
+    pub fn new<F>(name: impl Into<String>, run: F) -> Self
+    where
+        F: for<'a, 'b> FnMut(&'b mut Module<'a, T>) -> Result<()> + 'static,
+    {
+        Self {
+            name: name.into(),
+            run: Box::new(run),
+        }
+    }
+
+    /// Creates a pass that runs `run` on every function of a module.
+    pub fn function<F>(name: impl Into<String>, mut run: F) -> Self
+    where
+        F: for<'a, 'b> FnMut(&'b mut Function<'a, T>) -> Result<()> + 'static,
+    {
+        Self::new(name, move |module: &mut Module<T>| {
+            for (_, func) in &mut module.functions {
+                run(func)?
+            }
+            Ok(())
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<T: TargetIsa> Default for PassManager<T> {
+    fn default() -> Self {
+        Self {
+            passes: vec![],
+            print_before: vec![],
+            print_after: vec![],
+            print_before_all: false,
+            print_after_all: false,
+            dump_output: Box::new(io::stderr()),
+            timings: vec![],
+        }
+    }
+}
+
+impl<T: TargetIsa> PassManager<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a pass manager running the target's default pipeline.
+    pub fn with_default_passes() -> Self {
+        Self {
+            passes: T::module_passes(),
+            ..Self::default()
+        }
+    }
+
+    pub fn add(&mut self, pass: Pass<T>) {
+        self.passes.push(pass)
+    }
+
+    /// Inserts `pass` before the pass named `name`.
+    pub fn insert_before(&mut self, name: &str, pass: Pass<T>) -> Result<()> {
+        let i = self.position(name)?;
+        self.passes.insert(i, pass);
+        Ok(())
+    }
+
+    /// Inserts `pass` after the pass named `name`.
+    pub fn insert_after(&mut self, name: &str, pass: Pass<T>) -> Result<()> {
+        let i = self.position(name)?;
+        self.passes.insert(i + 1, pass);
+        Ok(())
+    }
+
+    /// Removes the pass named `name` from the pipeline.
+    pub fn remove(&mut self, name: &str) -> Result<Pass<T>> {
+        let i = self.position(name)?;
+        Ok(self.passes.remove(i))
+    }
+
+    pub fn pass_names(&self) -> impl Iterator<Item = &str> {
+        self.passes.iter().map(|p| p.name())
+    }
+
+    /// Dumps the module before running the pass named `name`.
+    pub fn print_before(&mut self, name: impl Into<String>) {
+        self.print_before.push(name.into())
+    }
+
+    /// Dumps the module after running the pass named `name`.
+    pub fn print_after(&mut self, name: impl Into<String>) {
+        self.print_after.push(name.into())
+    }
+
+    pub fn print_before_all(&mut self) {
+        self.print_before_all = true
+    }
+
+    pub fn print_after_all(&mut self) {
+        self.print_after_all = true
+    }
+
+    /// Sets where dumps are written. Defaults to stderr.
+    pub fn set_dump_output(&mut self, output: impl Write + 'static) {
+        self.dump_output = Box::new(output)
+    }
+
+    /// Returns the time each pass took in the last run, in the order the passes ran.
+    pub fn timings(&self) -> &[PassTiming] {
+        &self.timings
+    }
+
+    pub fn run_on_module(&mut self, module: &mut Module<T>) -> Result<()> {
+        self.timings.clear();
+
+        for pass in &mut self.passes {
+            if self.print_before_all || self.print_before.contains(&pass.name) {
+                writeln!(self.dump_output, "*** Dump Before {} ***", pass.name)?;
+                writeln!(self.dump_output, "{:?}", module)?;
+            }
+
+            let start = Instant::now();
+            (pass.run)(module)?;
+            self.timings.push(PassTiming {
+                name: pass.name.clone(),
+                elapsed: start.elapsed(),
+            });
+
+            if self.print_after_all || self.print_after.contains(&pass.name) {
+                writeln!(self.dump_output, "*** Dump After {} ***", pass.name)?;
+                writeln!(self.dump_output, "{:?}", module)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn position(&self, name: &str) -> Result<usize> {
+        self.passes
+            .iter()
+            .position(|p| p.name == name)
+            .ok_or_else(|| anyhow!("no such pass: {}", name))
+    }
+}
@@ -5,23 +5,37 @@ use crate::{
     },
     isa::TargetIsa,
     module::Module,
-    pass::liveness,
-    pass::spiller,
+    pass::{graph_coloring, liveness, spiller},
     register::{Reg, RegisterClass, RegisterInfo, VReg},
 };
 use anyhow::Result;
 use rustc_hash::{FxHashMap, FxHashSet};
 use std::collections::VecDeque;
 
+/// Register allocation algorithm used by `run_on_module`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RegAllocKind {
+    /// Linear scan. Fast, but spills every virtual register live across a call.
+    #[default]
+    LinearScan,
+
+    /// Graph coloring with iterated register coalescing. See `graph_coloring`.
+    GraphColoring,
+}
+
 pub fn run_on_module<T: TargetIsa>(module: &mut Module<T>) -> Result<()> {
+    let kind = module.regalloc;
     for (_, func) in &mut module.functions {
-        run_on_function(func);
+        match kind {
+            RegAllocKind::LinearScan => run_on_function(func)?,
+            RegAllocKind::GraphColoring => graph_coloring::run_on_function(func)?,
+        }
     }
     Ok(())
 }
 
 // Linear-scan
-pub fn run_on_function<T: TargetIsa>(function: &mut Function<T>) {
+pub fn run_on_function<T: TargetIsa>(function: &mut Function<T>) -> Result<()> {
     let mut liveness = liveness::Liveness::<T>::new();
     liveness.analyze_function(function);
 
@@ -61,19 +75,22 @@ pub fn run_on_function<T: TargetIsa>(function: &mut Function<T>) {
     // TODO: Refactoring.
     let mut spill_regs = FxHashSet::default();
     while let Some(vreg) = worklist.pop_front() {
-        let mut availables =
-            T::RegClass::for_type(&function.types, function.data.vregs.type_for(vreg)).gpr_list();
-        availables.append(
-            &mut T::RegClass::for_type(&function.types, function.data.vregs.type_for(vreg))
-                .csr_list(),
-        );
+        let rc = T::RegClass::for_type(&function.types, function.data.vregs.type_for(vreg))?;
+        let mut availables = rc.gpr_list();
+        availables.append(&mut rc.csr_list());
         let _ = availables.pop(); // TODO: Don't used RBP.
         if !spill_regs.contains(&vreg) {
             availables.pop(); // TODO
         }
 
         if let Some(preferred) = preferred.get(&vreg) {
-            availables.splice(0..0, preferred.clone());
+            // A copy may be between registers of different widths (e.g. `trunc`).
+            availables.splice(
+                0..0,
+                preferred
+                    .iter()
+                    .map(|&r| T::RegInfo::to_reg_unit(r).apply(&rc)),
+            );
         }
 
         let mut allocated = false;
@@ -98,7 +115,17 @@ pub fn run_on_function<T: TargetIsa>(function: &mut Function<T>) {
         }
     }
 
-    // Rewrite vreg for reg
+    rewrite_vregs(function, &assigned_regs);
+
+    Ok(())
+}
+
+/// Replaces virtual registers with the physical registers assigned to them, and records the
+/// callee-saved registers used in the function.
+pub fn rewrite_vregs<T: TargetIsa>(
+    function: &mut Function<T>,
+    assigned_regs: &FxHashMap<VReg, Reg>,
+) {
     let mut used_regs = FxHashSet::default();
     for block_id in function.layout.block_iter() {
         for inst_id in function.layout.inst_iter(block_id) {
@@ -152,7 +179,7 @@ pub fn collect_vregs_alive_around_call<T: TargetIsa>(
 }
 
 pub fn collect_preferred_registers<T: TargetIsa>(
-    function: &mut Function<T>,
+    function: &Function<T>,
     all_vregs: &FxHashSet<VReg>,
 ) -> FxHashMap<VReg, Vec<Reg>> {
     let mut preferred = FxHashMap::default();
@@ -45,17 +45,23 @@ impl<'a, 'b, T: TargetIsa> Spiller<'a, 'b, T> {
             .vreg_users
             .get(vreg)
             .into_iter()
-            .filter_map(|user| if user.write { Some(user.inst_id) } else { None })
+            .filter_map(|user| {
+                if user.write {
+                    Some((user.inst_id, user.read))
+                } else {
+                    None
+                }
+            })
             .collect::<Vec<_>>();
         // e.g. If 'MOV' and 'ADD' below are contained in `defs`,
         //      remove 'MOV' from `defs` to avoid an unnecessary spill.
         // 1: MOV a, b
         // 2: ADD a, c
-        defs.sort_by(|a, b| {
+        defs.sort_by(|(a, _), (b, _)| {
             let inst2pp = &self.liveness.inst_to_pp;
             inst2pp[b].cmp(&inst2pp[a])
         });
-        defs.dedup_by(|a, b| {
+        defs.dedup_by(|(a, _), (b, _)| {
             self.function
                 .layout
                 .prev_inst_of(*a)
@@ -66,7 +72,7 @@ impl<'a, 'b, T: TargetIsa> Spiller<'a, 'b, T> {
             return;
         }
 
-        for &def_id in &defs {
+        for &(def_id, read) in &defs {
             let new_vreg = self.function.data.vregs.create_from(vreg);
             new_vregs.push(new_vreg);
             let def_block;
@@ -78,6 +84,13 @@ impl<'a, 'b, T: TargetIsa> Spiller<'a, 'b, T> {
             let inst = T::Inst::store_vreg_to_slot(self.function, new_vreg, slot, def_block);
             let inst = self.function.data.create_inst(inst);
             self.insert_inst_after(def_id, inst, def_block);
+            // The def also reads the value (e.g. 'ADD a, c' above), which is no longer a use of
+            // `vreg` and so is not reloaded by `insert_reload`.
+            if read {
+                let inst = T::Inst::load_from_slot(self.function, new_vreg, slot, def_block);
+                let inst = self.function.data.create_inst(inst);
+                self.insert_inst_before(def_id, inst, def_block);
+            }
         }
     }
 
@@ -2,6 +2,7 @@ use crate::{
     call_conv::CallConvKind,
     function::instruction::{InstructionId, TargetInst},
 };
+use anyhow::Result;
 use rustc_hash::FxHashMap;
 use vicis_core::ir::types::{Type, Types};
 
@@ -43,8 +44,8 @@ pub trait RegisterInfo {
     fn is_csr(r: RegUnit) -> bool;
 }
 
-pub trait RegisterClass {
-    fn for_type(types: &Types, id: Type) -> Self;
+pub trait RegisterClass: Sized {
+    fn for_type(types: &Types, ty: Type) -> Result<Self>;
     fn gpr_list(&self) -> Vec<Reg>;
     fn csr_list(&self) -> Vec<Reg>;
     fn apply_for(&self, ru: RegUnit) -> Reg;
@@ -1,5 +1,9 @@
 use std::fs;
-use vicis_codegen::{isa::x86_64::X86_64, lower::compile_module};
+use vicis_codegen::{
+    isa::{aarch64::Aarch64, riscv64::Riscv64, x86_64::X86_64, TargetIsa},
+    lower::{compile_module, compile_module_with_options, CompileOptions},
+    pass::regalloc::RegAllocKind,
+};
 use vicis_core::ir::module::Module;
 
 macro_rules! test {
@@ -15,6 +19,38 @@ macro_rules! test {
             insta::assert_display_snapshot!(mach_module.display_asm());
         }
     };
+    ($isa:ident, $testname:ident, $name:expr, $regalloc:expr) => {
+        #[test]
+        fn $testname() {
+            let parent = "./tests/codegen/";
+            let input = format!("{}{}.ll", parent, $name);
+            let input_body = &fs::read_to_string(input).unwrap();
+            let module = Module::try_from(input_body.as_str()).unwrap();
+            let isa = $isa::default();
+            let mut options = CompileOptions {
+                regalloc: $regalloc,
+                ..Default::default()
+            };
+            let mach_module = compile_module_with_options(&isa, &module, &mut options).unwrap();
+            insta::assert_display_snapshot!(mach_module.display_asm());
+        }
+    };
+}
+
+/// Asserts that compiling floating-point code for `isa` fails with an error instead of panicking.
+fn assert_float_unsupported<T: TargetIsa>(isa: &T) {
+    for (name, ir) in [
+        ("arg", "define i32 @f(double %x) {\n  ret i32 0\n}"),
+        ("ret", "define double @f() {\n  ret double 1.0\n}"),
+    ] {
+        let module = Module::try_from(ir).unwrap();
+        assert!(compile_module(isa, &module).is_err(), "{}", name);
+    }
+    for name in ["float_arith", "float_cmp", "float_spill"] {
+        let input = fs::read_to_string(format!("./tests/codegen/{}.ll", name)).unwrap();
+        let module = Module::try_from(input.as_str()).unwrap();
+        assert!(compile_module(isa, &module).is_err(), "{}", name);
+    }
 }
 
 #[cfg(test)]
@@ -43,4 +79,114 @@ mod x86_64 {
     test!(X86_64, test_global, "global");
     test!(X86_64, test_spill, "spill");
     test!(X86_64, test_game_of_life, "game_of_life");
+    test!(X86_64, test_opaque_ptr, "opaque_ptr");
+    test!(X86_64, test_float_arith, "float_arith");
+    test!(X86_64, test_float_cmp, "float_cmp");
+    test!(X86_64, test_float_spill, "float_spill");
+    test!(X86_64, test_div, "div");
+    test!(X86_64, test_shift, "shift");
+    test!(X86_64, test_bitwise, "bitwise");
+    test!(X86_64, test_icmp, "icmp");
+    test!(X86_64, test_select, "select");
+    test!(X86_64, test_switch, "switch");
+    test!(X86_64, test_int_cast, "int_cast");
+    test!(X86_64, test_int_widths, "int_widths");
+
+    #[test]
+    fn test_stack_args_unsupported() {
+        for ir in [
+            "define i32 @f(i32 %a, i32 %b, i32 %c, i32 %d, i32 %e, i32 %f, i32 %g) {\n  ret i32 %g\n}",
+            "define double @f(double %a, double %b, double %c, double %d, double %e, double %f, double %g, double %h, double %i) {\n  ret double %i\n}",
+        ] {
+            let module = Module::try_from(ir).unwrap();
+            assert!(compile_module(&X86_64::default(), &module).is_err());
+        }
+    }
+}
+
+#[cfg(test)]
+mod aarch64 {
+    use super::*;
+
+    test!(Aarch64, test_add, "ary1");
+    test!(Aarch64, test_ary2, "ary2");
+    test!(Aarch64, test_ary3, "ary3");
+    test!(Aarch64, test_ary4, "ary4");
+    test!(Aarch64, test_ary5, "ary5");
+    test!(Aarch64, test_br, "br");
+    test!(Aarch64, test_call1, "call1");
+    test!(Aarch64, test_call2, "call2");
+    test!(Aarch64, test_condbr, "condbr");
+    test!(Aarch64, test_fibo, "fibo");
+    test!(Aarch64, test_load_add, "load_add");
+    test!(Aarch64, test_phi, "phi");
+    test!(Aarch64, test_phi2, "phi2");
+    test!(Aarch64, test_puts, "puts");
+    test!(Aarch64, test_sum, "sum");
+    test!(Aarch64, test_hello, "hello");
+    test!(Aarch64, test_addr, "addr");
+    test!(Aarch64, test_i8, "i8");
+    test!(Aarch64, test_i8_load_store, "i8_load_store");
+    test!(Aarch64, test_global, "global");
+    test!(Aarch64, test_spill, "spill");
+    test!(Aarch64, test_game_of_life, "game_of_life");
+    test!(Aarch64, test_opaque_ptr, "opaque_ptr");
+    test!(Aarch64, test_large_frame, "large_frame");
+
+    #[test]
+    fn test_float_unsupported() {
+        assert_float_unsupported(&Aarch64::default());
+    }
+}
+
+#[cfg(test)]
+mod riscv64 {
+    use super::*;
+
+    test!(Riscv64, test_add, "ary1");
+    test!(Riscv64, test_ary2, "ary2");
+    test!(Riscv64, test_ary3, "ary3");
+    test!(Riscv64, test_ary4, "ary4");
+    test!(Riscv64, test_ary5, "ary5");
+    test!(Riscv64, test_br, "br");
+    test!(Riscv64, test_call1, "call1");
+    test!(Riscv64, test_call2, "call2");
+    test!(Riscv64, test_condbr, "condbr");
+    test!(Riscv64, test_fibo, "fibo");
+    test!(Riscv64, test_load_add, "load_add");
+    test!(Riscv64, test_phi, "phi");
+    test!(Riscv64, test_phi2, "phi2");
+    test!(Riscv64, test_puts, "puts");
+    test!(Riscv64, test_sum, "sum");
+    test!(Riscv64, test_hello, "hello");
+    test!(Riscv64, test_addr, "addr");
+    test!(Riscv64, test_i8, "i8");
+    test!(Riscv64, test_i8_load_store, "i8_load_store");
+    test!(Riscv64, test_global, "global");
+    test!(Riscv64, test_spill, "spill");
+    test!(Riscv64, test_game_of_life, "game_of_life");
+    test!(Riscv64, test_opaque_ptr, "opaque_ptr");
+    test!(Riscv64, test_large_frame, "large_frame");
+    test!(Riscv64, test_float_arith, "float_arith");
+    test!(Riscv64, test_float_cmp, "float_cmp");
+    test!(Riscv64, test_float_spill, "float_spill");
+}
+
+#[cfg(test)]
+mod graph_coloring {
+    use super::*;
+    use RegAllocKind::GraphColoring;
+
+    test!(X86_64, x86_64_call2, "call2", GraphColoring);
+    test!(X86_64, x86_64_fibo, "fibo", GraphColoring);
+    test!(X86_64, x86_64_phi2, "phi2", GraphColoring);
+    test!(X86_64, x86_64_spill, "spill", GraphColoring);
+    test!(X86_64, x86_64_sum, "sum", GraphColoring);
+    test!(X86_64, x86_64_float_spill, "float_spill", GraphColoring);
+    test!(X86_64, x86_64_game_of_life, "game_of_life", GraphColoring);
+    test!(Aarch64, aarch64_fibo, "fibo", GraphColoring);
+    test!(Aarch64, aarch64_spill, "spill", GraphColoring);
+    test!(Riscv64, riscv64_fibo, "fibo", GraphColoring);
+    test!(Riscv64, riscv64_spill, "spill", GraphColoring);
+    test!(Riscv64, riscv64_float_spill, "float_spill", GraphColoring);
 }
@@ -0,0 +1,49 @@
+source_filename = "bitwise"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+define dso_local i32 @ops32(i32 %x, i32 %y) {
+  %1 = and i32 %x, %y
+  %2 = or i32 %1, 256
+  %3 = xor i32 %2, %x
+  %4 = and i32 %3, 1023
+  ret i32 %4
+}
+
+define dso_local i64 @ops64(i64 %x, i64 %y) {
+  %1 = xor i64 %x, -1
+  %2 = and i64 %1, %y
+  %3 = or i64 %2, 4294967296
+  ret i64 %3
+}
+
+define dso_local i8 @ops8(i8 %x, i8 %y) {
+  %1 = xor i8 %x, %y
+  %2 = or i8 %1, 1
+  %3 = and i8 %2, -16
+  ret i8 %3
+}
+
+define dso_local i16 @ops16(i16 %x, i16 %y) {
+  %1 = or i16 %x, %y
+  %2 = xor i16 %1, 4660
+  %3 = and i16 %2, 1023
+  ret i16 %3
+}
+
+define dso_local i32 @main() {
+  %1 = call i32 @ops32(i32 1365, i32 819)
+  %2 = call i64 @ops64(i64 255, i64 4095)
+  %3 = call i8 @ops8(i8 90, i8 -91)
+  %4 = call i16 @ops16(i16 4096, i16 17)
+  %5 = lshr i64 %2, 32
+  %6 = trunc i64 %2 to i32
+  %7 = trunc i64 %5 to i32
+  %8 = sext i8 %3 to i32
+  %9 = zext i16 %4 to i32
+  %10 = add i32 %1, %6
+  %11 = add i32 %10, %7
+  %12 = add i32 %11, %8
+  %13 = add i32 %12, %9
+  ret i32 %13
+}
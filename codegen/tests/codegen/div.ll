@@ -0,0 +1,57 @@
+source_filename = "div"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+define dso_local i32 @sdiv32(i32 %x, i32 %y) {
+  %1 = sdiv i32 %x, %y
+  ret i32 %1
+}
+
+define dso_local i32 @srem32(i32 %x, i32 %y) {
+  %1 = srem i32 %x, %y
+  ret i32 %1
+}
+
+define dso_local i64 @udiv64(i64 %x, i64 %y) {
+  %1 = udiv i64 %x, %y
+  ret i64 %1
+}
+
+define dso_local i64 @urem64(i64 %x, i64 %y) {
+  %1 = urem i64 %x, %y
+  ret i64 %1
+}
+
+define dso_local i8 @sdiv8(i8 %x, i8 %y) {
+  %1 = sdiv i8 %x, %y
+  ret i8 %1
+}
+
+define dso_local i16 @urem16(i16 %x, i16 %y) {
+  %1 = urem i16 %x, %y
+  ret i16 %1
+}
+
+define dso_local i32 @main() {
+  %1 = call i32 @sdiv32(i32 -100, i32 7)
+  %2 = call i32 @srem32(i32 -100, i32 7)
+  %3 = call i64 @udiv64(i64 -1, i64 4294967296)
+  %4 = call i64 @urem64(i64 1000000000007, i64 10)
+  %5 = call i8 @sdiv8(i8 -128, i8 3)
+  %6 = call i16 @urem16(i16 -1, i16 1000)
+  %7 = trunc i64 %3 to i32
+  %8 = trunc i64 %4 to i32
+  %9 = sext i8 %5 to i32
+  %10 = zext i16 %6 to i32
+  ; -14 + -2 * 10 + -1 + 7 * 100 + -42 * 1000 + 535 * 10000
+  %11 = mul i32 %2, 10
+  %12 = mul i32 %8, 100
+  %13 = mul i32 %9, 1000
+  %14 = mul i32 %10, 10000
+  %15 = add i32 %1, %11
+  %16 = add i32 %15, %7
+  %17 = add i32 %16, %12
+  %18 = add i32 %17, %13
+  %19 = add i32 %18, %14
+  ret i32 %19
+}
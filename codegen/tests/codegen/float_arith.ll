@@ -0,0 +1,35 @@
+source_filename = "float_arith"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+define dso_local double @poly(double %x, i32 %n, double %y) {
+  %1 = fmul double %x, %x
+  %2 = fmul double %1, 2.500000e+00
+  %3 = fadd double %2, %y
+  %4 = sitofp i32 %n to double
+  %5 = fsub double %3, %4
+  %6 = fdiv double %5, %x
+  ret double %6
+}
+
+define dso_local float @half(float %x) {
+  %1 = fmul float %x, 5.000000e-01
+  ret float %1
+}
+
+define dso_local i32 @main() {
+  %1 = alloca double, align 8
+  store double 1.500000e+00, double* %1, align 8
+  %2 = load double, double* %1, align 8
+  %3 = call double @poly(double %2, i32 3, double 4.000000e+00)
+  %4 = fptrunc double %3 to float
+  %5 = call float @half(float %4)
+  %6 = fpext float %5 to double
+  %7 = call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([4 x i8], [4 x i8]* @.str, i64 0, i64 0), double %6)
+  %8 = fptosi double %6 to i32
+  ret i32 %8
+}
+
+@.str = private unnamed_addr constant [4 x i8] c"%f\0A\00", align 1
+
+declare dso_local i32 @printf(i8*, ...)
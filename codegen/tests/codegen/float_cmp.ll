@@ -0,0 +1,49 @@
+source_filename = "float_cmp"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+define dso_local double @max(double %a, double %b) {
+entry:
+  %1 = fcmp olt double %a, %b
+  br i1 %1, label %2, label %3
+
+2:
+  br label %3
+
+3:
+  %4 = phi double [ %b, %2 ], [ %a, %entry ]
+  ret double %4
+}
+
+define dso_local i32 @is_zero(double %a) {
+  %1 = fcmp oeq double %a, 0.000000e+00
+  br i1 %1, label %2, label %3
+
+2:
+  ret i32 1
+
+3:
+  ret i32 0
+}
+
+define dso_local double @sign(double %a) {
+  %1 = fcmp une double %a, %a
+  br i1 %1, label %4, label %2
+
+2:
+  %3 = fcmp ugt double %a, 0.000000e+00
+  br i1 %3, label %5, label %6
+
+4:
+  br label %7
+
+5:
+  br label %7
+
+6:
+  br label %7
+
+7:
+  %8 = phi double [ %a, %4 ], [ 1.000000e+00, %5 ], [ -1.000000e+00, %6 ]
+  ret double %8
+}
@@ -0,0 +1,58 @@
+source_filename = "float_spill"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+define dso_local double @f(double %x) {
+  %a1 = fmul double %x, 1.000000e+00
+  %a2 = fmul double %x, 2.000000e+00
+  %a3 = fmul double %x, 3.000000e+00
+  %a4 = fmul double %x, 4.000000e+00
+  %a5 = fmul double %x, 5.000000e+00
+  %a6 = fmul double %x, 6.000000e+00
+  %a7 = fmul double %x, 7.000000e+00
+  %a8 = fmul double %x, 8.000000e+00
+  %a9 = fmul double %x, 9.000000e+00
+  %a10 = fmul double %x, 1.000000e+01
+  %a11 = fmul double %x, 1.100000e+01
+  %a12 = fmul double %x, 1.200000e+01
+  %a13 = fmul double %x, 1.300000e+01
+  %a14 = fmul double %x, 1.400000e+01
+  %a15 = fmul double %x, 1.500000e+01
+  %a16 = fmul double %x, 1.600000e+01
+  %s1 = fadd double %a1, %a2
+  %s2 = fadd double %s1, %a3
+  %s3 = fadd double %s2, %a4
+  %s4 = fadd double %s3, %a5
+  %s5 = fadd double %s4, %a6
+  %s6 = fadd double %s5, %a7
+  %s7 = fadd double %s6, %a8
+  %s8 = fadd double %s7, %a9
+  %s9 = fadd double %s8, %a10
+  %s10 = fadd double %s9, %a11
+  %s11 = fadd double %s10, %a12
+  %s12 = fadd double %s11, %a13
+  %s13 = fadd double %s12, %a14
+  %s14 = fadd double %s13, %a15
+  %s15 = fadd double %s14, %a16
+  %t1 = fmul double %s15, %a1
+  %t2 = fmul double %t1, %a2
+  %t3 = fmul double %t2, %a3
+  %t4 = fmul double %t3, %a4
+  %t5 = fmul double %t4, %a5
+  %t6 = fmul double %t5, %a6
+  %t7 = fmul double %t6, %a7
+  %t8 = fmul double %t7, %a8
+  %t9 = fmul double %t8, %a9
+  %t10 = fmul double %t9, %a10
+  %t11 = fmul double %t10, %a11
+  %t12 = fmul double %t11, %a12
+  %t13 = fmul double %t12, %a13
+  %t14 = fmul double %t13, %a14
+  %t15 = fmul double %t14, %a15
+  %t16 = fmul double %t15, %a16
+  %r = call double @g(double %t16)
+  %u = fadd double %r, %a1
+  ret double %u
+}
+
+declare dso_local double @g(double)
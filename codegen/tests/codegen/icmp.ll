@@ -0,0 +1,78 @@
+source_filename = "icmp"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+define dso_local i32 @cmp32(i32 %x, i32 %y) {
+  %1 = icmp eq i32 %x, %y
+  %2 = icmp ne i32 %x, 3
+  %3 = icmp slt i32 %x, %y
+  %4 = icmp sle i32 %x, %y
+  %5 = icmp sgt i32 %x, %y
+  %6 = icmp sge i32 %x, %y
+  %7 = icmp ult i32 %x, %y
+  %8 = icmp ule i32 %x, %y
+  %9 = icmp ugt i32 %x, %y
+  %10 = icmp uge i32 %x, %y
+  %11 = zext i1 %1 to i32
+  %12 = zext i1 %2 to i32
+  %13 = zext i1 %3 to i32
+  %14 = zext i1 %4 to i32
+  %15 = zext i1 %5 to i32
+  %16 = zext i1 %6 to i32
+  %17 = zext i1 %7 to i32
+  %18 = zext i1 %8 to i32
+  %19 = zext i1 %9 to i32
+  %20 = zext i1 %10 to i32
+  %21 = shl i32 %12, 1
+  %22 = shl i32 %13, 2
+  %23 = shl i32 %14, 3
+  %24 = shl i32 %15, 4
+  %25 = shl i32 %16, 5
+  %26 = shl i32 %17, 6
+  %27 = shl i32 %18, 7
+  %28 = shl i32 %19, 8
+  %29 = shl i32 %20, 9
+  %30 = or i32 %11, %21
+  %31 = or i32 %30, %22
+  %32 = or i32 %31, %23
+  %33 = or i32 %32, %24
+  %34 = or i32 %33, %25
+  %35 = or i32 %34, %26
+  %36 = or i32 %35, %27
+  %37 = or i32 %36, %28
+  %38 = or i32 %37, %29
+  ret i32 %38
+}
+
+define dso_local i32 @umax(i64 %x, i64 %y) {
+  %1 = icmp ugt i64 %x, %y
+  br i1 %1, label %then, label %else
+
+then:
+  ret i32 1
+
+else:
+  ret i32 2
+}
+
+define dso_local i32 @ult8(i8 %x, i8 %y) {
+  %1 = icmp ult i8 %x, %y
+  br i1 %1, label %then, label %else
+
+then:
+  ret i32 1
+
+else:
+  ret i32 2
+}
+
+define dso_local i32 @main() {
+  %1 = call i32 @cmp32(i32 -1, i32 1)
+  %2 = call i32 @umax(i64 -1, i64 1)
+  %3 = call i32 @ult8(i8 -1, i8 1)
+  %4 = mul i32 %2, 1000
+  %5 = mul i32 %3, 10000
+  %6 = add i32 %1, %4
+  %7 = add i32 %6, %5
+  ret i32 %7
+}
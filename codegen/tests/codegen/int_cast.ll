@@ -0,0 +1,50 @@
+source_filename = "int_cast"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+define dso_local i64 @widen(i8 %a, i16 %b, i32 %c) {
+  %1 = sext i8 %a to i64
+  %2 = zext i16 %b to i64
+  %3 = zext i32 %c to i64
+  %4 = sext i16 %b to i32
+  %5 = sext i32 %4 to i64
+  %6 = add i64 %1, %2
+  %7 = add i64 %6, %3
+  %8 = add i64 %7, %5
+  ret i64 %8
+}
+
+define dso_local i8 @narrow(i64 %x) {
+  %1 = trunc i64 %x to i32
+  %2 = trunc i32 %1 to i16
+  %3 = trunc i16 %2 to i8
+  %4 = trunc i8 %3 to i1
+  %5 = zext i1 %4 to i8
+  %6 = add i8 %3, %5
+  ret i8 %6
+}
+
+define dso_local i64 @ptr_roundtrip(i32* %p) {
+  %1 = ptrtoint i32* %p to i64
+  %2 = add i64 %1, 4
+  %3 = inttoptr i64 %2 to i32*
+  %4 = ptrtoint i32* %3 to i64
+  %5 = sub i64 %4, %1
+  ret i64 %5
+}
+
+define dso_local i32 @main() {
+  %1 = alloca i32, align 4
+  %2 = call i64 @widen(i8 -1, i16 -1, i32 -1)
+  %3 = call i8 @narrow(i64 4294967297)
+  %4 = call i64 @ptr_roundtrip(i32* %1)
+  %5 = sub i64 %2, 4295032828
+  %6 = trunc i64 %5 to i32
+  %7 = sext i8 %3 to i32
+  %8 = trunc i64 %4 to i32
+  %9 = mul i32 %7, 100
+  %10 = mul i32 %8, 1000
+  %11 = add i32 %6, %9
+  %12 = add i32 %11, %10
+  ret i32 %12
+}
@@ -0,0 +1,43 @@
+source_filename = "int_widths"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+define dso_local i8 @arith8(i8 %x, i8 %y) {
+  %1 = add i8 %x, %y
+  %2 = sub i8 %1, 3
+  %3 = mul i8 %2, %y
+  %4 = mul i8 %3, 5
+  ret i8 %4
+}
+
+define dso_local i16 @arith16(i16 %x, i16 %y) {
+  %1 = add i16 %x, 1000
+  %2 = sub i16 %1, %y
+  %3 = mul i16 %2, %y
+  %4 = mul i16 %3, 300
+  ret i16 %4
+}
+
+define dso_local i64 @arith64(i64 %x, i64 %y) {
+  %1 = add i64 %x, 4294967296
+  %2 = sub i64 %1, %y
+  %3 = mul i64 %2, %y
+  %4 = mul i64 %3, -3
+  ret i64 %4
+}
+
+define dso_local i32 @main() {
+  %1 = alloca i16, align 2
+  %2 = call i8 @arith8(i8 100, i8 2)
+  %3 = call i16 @arith16(i16 24, i16 3)
+  store i16 %3, i16* %1, align 2
+  %4 = load i16, i16* %1, align 2
+  %5 = call i64 @arith64(i64 1, i64 2)
+  %6 = sext i8 %2 to i32
+  %7 = sext i16 %4 to i32
+  %8 = sdiv i64 %5, 4294967296
+  %9 = trunc i64 %8 to i32
+  %10 = add i32 %6, %7
+  %11 = add i32 %10, %9
+  ret i32 %11
+}
@@ -0,0 +1,16 @@
+define dso_local i32 @main() {
+  %1 = alloca i32, align 4
+  %2 = alloca [5000 x i32], align 16
+  store i32 0, i32* %1, align 4
+  %3 = getelementptr inbounds [5000 x i32], [5000 x i32]* %2, i64 0, i64 4999
+  store i32 1, i32* %3, align 4
+  %4 = getelementptr inbounds [5000 x i32], [5000 x i32]* %2, i64 0, i64 10
+  call void @use(i32* %4)
+  call void @use(i32* %1)
+  %5 = load i32, i32* %3, align 4
+  %6 = load i32, i32* %1, align 4
+  %7 = add nsw i32 %5, %6
+  ret i32 %7
+}
+
+declare dso_local void @use(i32*)
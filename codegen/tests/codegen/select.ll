@@ -0,0 +1,35 @@
+source_filename = "select"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+define dso_local i32 @max(i32 %x, i32 %y) {
+  %1 = icmp sgt i32 %x, %y
+  %2 = select i1 %1, i32 %x, i32 %y
+  ret i32 %2
+}
+
+define dso_local i64 @abs(i64 %x) {
+  %1 = icmp slt i64 %x, 0
+  %2 = sub i64 0, %x
+  %3 = select i1 %1, i64 %2, i64 %x
+  ret i64 %3
+}
+
+define dso_local i8 @pick(i1 %c, i8 %x) {
+  %1 = select i1 %c, i8 %x, i8 7
+  ret i8 %1
+}
+
+define dso_local i32 @main() {
+  %1 = call i32 @max(i32 -5, i32 3)
+  %2 = call i64 @abs(i64 -40)
+  %3 = call i8 @pick(i1 false, i8 1)
+  %4 = trunc i64 %2 to i32
+  %5 = sext i1 true to i32
+  %6 = zext i8 %3 to i32
+  %7 = mul i32 %4, 10
+  %8 = add i32 %1, %7
+  %9 = add i32 %8, %6
+  %10 = add i32 %9, %5
+  ret i32 %10
+}
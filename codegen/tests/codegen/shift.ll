@@ -0,0 +1,49 @@
+source_filename = "shift"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+define dso_local i32 @shl32(i32 %x, i32 %n) {
+  %1 = shl i32 %x, %n
+  ret i32 %1
+}
+
+define dso_local i32 @lshr32(i32 %x, i32 %n) {
+  %1 = lshr i32 %x, %n
+  ret i32 %1
+}
+
+define dso_local i64 @ashr64(i64 %x, i64 %n) {
+  %1 = ashr i64 %x, %n
+  ret i64 %1
+}
+
+define dso_local i8 @imm8(i8 %x) {
+  %1 = shl i8 %x, 1
+  %2 = lshr i8 %1, 3
+  ret i8 %2
+}
+
+define dso_local i16 @imm16(i16 %x) {
+  %1 = ashr i16 %x, 4
+  ret i16 %1
+}
+
+define dso_local i32 @main() {
+  %1 = call i32 @shl32(i32 3, i32 4)
+  %2 = call i32 @lshr32(i32 -1, i32 28)
+  %3 = call i64 @ashr64(i64 -4096, i64 8)
+  %4 = call i8 @imm8(i8 -61)
+  %5 = call i16 @imm16(i16 -32768)
+  ; 48 + 15 * 100 + -16 * 1000 + 16 * 10 + -2048
+  %6 = trunc i64 %3 to i32
+  %7 = zext i8 %4 to i32
+  %8 = sext i16 %5 to i32
+  %9 = mul i32 %2, 100
+  %10 = mul i32 %6, 1000
+  %11 = mul i32 %7, 10
+  %12 = add i32 %1, %9
+  %13 = add i32 %12, %10
+  %14 = add i32 %13, %11
+  %15 = add i32 %14, %8
+  ret i32 %15
+}
@@ -0,0 +1,69 @@
+source_filename = "switch"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+define dso_local i32 @dense(i32 %x) {
+  switch i32 %x, label %default [
+    i32 10, label %b10
+    i32 11, label %b11
+    i32 13, label %b13
+    i32 14, label %b14
+    i32 16, label %b16
+  ]
+
+b10:
+  ret i32 1
+
+b11:
+  ret i32 2
+
+b13:
+  ret i32 3
+
+b14:
+  ret i32 4
+
+b16:
+  ret i32 5
+
+default:
+  ret i32 0
+}
+
+define dso_local i32 @sparse(i8 %x) {
+  switch i8 %x, label %default [
+    i8 -1, label %m1
+    i8 40, label %b40
+    i8 100, label %b100
+  ]
+
+m1:
+  ret i32 10
+
+b40:
+  ret i32 20
+
+b100:
+  ret i32 30
+
+default:
+  ret i32 0
+}
+
+define dso_local i32 @main() {
+  %1 = call i32 @dense(i32 13)
+  %2 = call i32 @dense(i32 12)
+  %3 = call i32 @dense(i32 16)
+  %4 = call i32 @dense(i32 9)
+  %5 = call i32 @sparse(i8 -1)
+  %6 = call i32 @sparse(i8 100)
+  %7 = call i32 @sparse(i8 41)
+  ; 3 + 0 + 5 + 0 + 10 + 30 + 0
+  %8 = add i32 %1, %2
+  %9 = add i32 %8, %3
+  %10 = add i32 %9, %4
+  %11 = add i32 %10, %5
+  %12 = add i32 %11, %6
+  %13 = add i32 %12, %7
+  ret i32 %13
+}
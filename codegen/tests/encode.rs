@@ -0,0 +1,147 @@
+use std::{fmt::Write, fs};
+use vicis_codegen::{
+    isa::x86_64::{elf, encode::encode_function, X86_64},
+    lower::compile_module,
+};
+use vicis_core::ir::module::Module;
+
+macro_rules! test {
+    ($testname:ident, $name:expr) => {
+        #[test]
+        fn $testname() {
+            let input = format!("./tests/codegen/{}.ll", $name);
+            let input_body = &fs::read_to_string(input).unwrap();
+            let module = Module::try_from(input_body.as_str()).unwrap();
+            let isa = X86_64::default();
+            let mach_module = compile_module(&isa, &module).unwrap();
+            insta::assert_snapshot!(dump(&mach_module));
+        }
+    };
+}
+
+/// Dumps the machine code and the relocations of each function.
+fn dump(module: &vicis_codegen::module::Module<X86_64>) -> String {
+    let mut out = String::new();
+    for (_, func) in &module.functions {
+        if func.is_declaration {
+            continue;
+        }
+        let encoded = encode_function(func).unwrap();
+        writeln!(out, "{}:", func.ir.name()).unwrap();
+        for (i, line) in encoded.code.chunks(16).enumerate() {
+            write!(out, "  {:04x}:", i * 16).unwrap();
+            for byte in line {
+                write!(out, " {:02x}", byte).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+        for reloc in &encoded.relocs {
+            writeln!(
+                out,
+                "  {:04x}: {:?} {}{:+}",
+                reloc.offset, reloc.kind, reloc.symbol, reloc.addend
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+test!(test_fibo, "fibo");
+test!(test_call2, "call2");
+test!(test_puts, "puts");
+test!(test_global, "global");
+test!(test_i8_load_store, "i8_load_store");
+test!(test_spill, "spill");
+test!(test_opaque_ptr, "opaque_ptr");
+test!(test_float_arith, "float_arith");
+test!(test_float_cmp, "float_cmp");
+test!(test_float_spill, "float_spill");
+test!(test_div, "div");
+test!(test_shift, "shift");
+test!(test_bitwise, "bitwise");
+test!(test_icmp, "icmp");
+test!(test_select, "select");
+test!(test_switch, "switch");
+test!(test_int_cast, "int_cast");
+test!(test_int_widths, "int_widths");
+
+#[test]
+fn long_jump() {
+    // The loop body is longer than 127 bytes, so the jumps over it can't be short.
+    let mut body = String::new();
+    for i in 0..40 {
+        writeln!(body, "  %x{} = add i32 %i, {}", i, i + 1000).unwrap();
+        writeln!(body, "  store i32 %x{}, i32* %p", i).unwrap();
+    }
+    let source = format!(
+        r#"
+define i32 @main() {{
+entry:
+  %p = alloca i32
+  br label %loop
+loop:
+  %i = phi i32 [ 0, %entry ], [ %next, %body ]
+  %c = icmp sge i32 %i, 10
+  br i1 %c, label %exit, label %body
+body:
+{}  %next = add i32 %i, 1
+  br label %loop
+exit:
+  ret i32 %i
+}}
+"#,
+        body
+    );
+    let module = Module::try_from(source.as_str()).unwrap();
+    let isa = X86_64::default();
+    let mach_module = compile_module(&isa, &module).unwrap();
+    let (_, func) = mach_module.functions.iter().next().unwrap();
+    let code = encode_function(func).unwrap().code;
+    // jge rel32 and jmp rel32 both appear.
+    assert!(code.windows(2).any(|w| w == [0x0f, 0x8d]));
+    assert!(code.contains(&0xe9));
+}
+
+#[test]
+fn elf_object() {
+    let input_body = &fs::read_to_string("./tests/codegen/hello.ll").unwrap();
+    let module = Module::try_from(input_body.as_str()).unwrap();
+    let isa = X86_64::default();
+    let mach_module = compile_module(&isa, &module).unwrap();
+    let obj = elf::write(&mach_module).unwrap();
+
+    assert_eq!(&obj[..4], b"\x7fELF");
+    assert_eq!(u16::from_le_bytes([obj[16], obj[17]]), 1); // ET_REL
+    assert_eq!(u16::from_le_bytes([obj[18], obj[19]]), 62); // EM_X86_64
+
+    let shoff = u64::from_le_bytes(obj[40..48].try_into().unwrap()) as usize;
+    let shnum = u16::from_le_bytes([obj[60], obj[61]]) as usize;
+    let shstrndx = u16::from_le_bytes([obj[62], obj[63]]) as usize;
+    let header = |i: usize| &obj[shoff + i * 64..shoff + (i + 1) * 64];
+    let u64_at = |h: &[u8], i: usize| u64::from_le_bytes(h[i..i + 8].try_into().unwrap()) as usize;
+    let shstrtab = &obj[u64_at(header(shstrndx), 24)..];
+    let names: Vec<&str> = (1..shnum)
+        .map(|i| {
+            let name = u32::from_le_bytes(header(i)[..4].try_into().unwrap()) as usize;
+            let len = shstrtab[name..].iter().position(|&c| c == 0).unwrap();
+            std::str::from_utf8(&shstrtab[name..name + len]).unwrap()
+        })
+        .collect();
+    assert_eq!(
+        names,
+        [
+            ".text.startup",
+            ".text",
+            ".rodata",
+            ".init_array",
+            ".note.GNU-stack",
+            ".rela.text.startup",
+            ".rela.text",
+            ".rela.init_array",
+            ".symtab",
+            ".strtab",
+            ".shstrtab"
+        ]
+    );
+}
@@ -0,0 +1,287 @@
+#![cfg(all(unix, target_arch = "x86_64"))]
+
+use std::fs;
+use vicis_codegen::{
+    isa::x86_64::{
+        jit::{JitBuilder, Value},
+        X86_64,
+    },
+    lower::{compile_module, compile_module_with_options, CompileOptions},
+    pass::regalloc::RegAllocKind,
+};
+use vicis_core::ir::module::Module;
+
+macro_rules! test {
+    ($testname:ident, $name:expr, $expected:expr) => {
+        test!($testname, $name, $expected, RegAllocKind::LinearScan);
+    };
+    ($testname:ident, $name:expr, $expected:expr, $regalloc:expr) => {
+        #[test]
+        fn $testname() {
+            let input = format!("./tests/codegen/{}.ll", $name);
+            let input_body = &fs::read_to_string(input).unwrap();
+            assert_eq!(run_with(input_body, "main", &[], $regalloc), $expected);
+        }
+    };
+}
+
+fn run(source: &str, name: &str, args: &[Value]) -> Value {
+    run_with(source, name, args, RegAllocKind::LinearScan)
+}
+
+fn run_with(source: &str, name: &str, args: &[Value], regalloc: RegAllocKind) -> Value {
+    let module = Module::try_from(source).unwrap();
+    let isa = X86_64::default();
+    let mut options = CompileOptions {
+        regalloc,
+        ..Default::default()
+    };
+    let mach_module = compile_module_with_options(&isa, &module, &mut options).unwrap();
+    let jit = JitBuilder::new(&mach_module).build().unwrap();
+    unsafe {
+        jit.run_constructors();
+        jit.call(name, args).unwrap()
+    }
+}
+
+test!(test_ary5, "ary5", Value::Int32(55));
+test!(test_call1, "call1", Value::Int32(1));
+test!(test_fibo, "fibo", Value::Int32(55));
+test!(test_load_add, "load_add", Value::Int32(7));
+test!(test_phi2, "phi2", Value::Int32(55));
+test!(test_puts, "puts", Value::Int32(0));
+test!(test_spill, "spill", Value::Int32(210));
+test!(test_sum, "sum", Value::Int32(55));
+test!(test_addr, "addr", Value::Int32(42));
+test!(test_float_arith, "float_arith", Value::Int32(2));
+test!(test_div, "div", Value::Int32(5308665));
+test!(test_shift, "shift", Value::Int32(-16340));
+test!(test_bitwise, "bitwise", Value::Int32(4442));
+test!(test_icmp, "icmp", Value::Int32(21782));
+test!(test_select, "select", Value::Int32(409));
+test!(test_switch, "switch", Value::Int32(48));
+test!(test_int_cast, "int_cast", Value::Int32(4200));
+test!(test_int_widths, "int_widths", Value::Int32(1357));
+
+#[test]
+fn global() {
+    let input_body = &fs::read_to_string("./tests/codegen/global.ll").unwrap();
+    assert_eq!(run(input_body, "load", &[]), Value::Int32(42));
+}
+
+#[test]
+fn args() {
+    let source = r#"
+define i32 @add(i32 %x, i32 %y) {
+  %z = add i32 %x, %y
+  ret i32 %z
+}
+"#;
+    assert_eq!(
+        run(source, "add", &[Value::Int32(40), Value::Int32(2)]),
+        Value::Int32(42)
+    );
+}
+
+// Sums 0.5 ten times, passing doubles through phis, calls and comparisons.
+const FLOAT_LOOP: &str = r#"
+define double @step(double %x, double %d) {
+  %y = fadd double %x, %d
+  ret double %y
+}
+
+define i32 @main() {
+entry:
+  br label %loop
+loop:
+  %x = phi double [ 0.000000e+00, %entry ], [ %y, %loop ]
+  %y = call double @step(double %x, double 5.000000e-01)
+  %c = fcmp olt double %y, 5.000000e+00
+  br i1 %c, label %loop, label %exit
+exit:
+  %r = fmul double %y, 2.000000e+00
+  %i = fptosi double %r to i32
+  ret i32 %i
+}
+"#;
+
+#[test]
+fn float_loop() {
+    assert_eq!(run(FLOAT_LOOP, "main", &[]), Value::Int32(10));
+}
+
+#[test]
+fn invalid_args() {
+    let source = r#"
+define i32 @id(i32 %x) {
+  ret i32 %x
+}
+"#;
+    let module = Module::try_from(source).unwrap();
+    let isa = X86_64::default();
+    let mach_module = compile_module(&isa, &module).unwrap();
+    let jit = JitBuilder::new(&mach_module).build().unwrap();
+    assert!(unsafe { jit.call("id", &[]) }.is_err());
+    assert!(unsafe { jit.call("id", &[Value::Int64(1)]) }.is_err());
+    assert!(unsafe { jit.call("undefined", &[]) }.is_err());
+    assert!(jit.get_address("id").is_some());
+}
+
+#[test]
+fn gep_large_offset() {
+    let source = r#"
+define i8* @far(i8* %p) {
+  %q = getelementptr i8, i8* %p, i64 4294967300
+  ret i8* %q
+}
+"#;
+    let p = 0x1000 as *mut u8;
+    assert_eq!(
+        run(source, "far", &[Value::Ptr(p)]),
+        Value::Ptr(p.wrapping_add(4294967300))
+    );
+}
+
+#[test]
+fn typed_args() {
+    let source = r#"
+define double @mix(i32 %a, double %b, i64 %c, double %d) {
+  %x = fadd double %b, %d
+  ret double %x
+}
+
+define float @fadd32(float %x, float %y) {
+  %z = fadd float %x, %y
+  ret float %z
+}
+
+define i16 @id16(i16 %x) {
+  ret i16 %x
+}
+"#;
+    let module = Module::try_from(source).unwrap();
+    let isa = X86_64::default();
+    let mach_module = compile_module(&isa, &module).unwrap();
+    let jit = JitBuilder::new(&mach_module).build().unwrap();
+    unsafe {
+        assert_eq!(
+            jit.call(
+                "mix",
+                &[
+                    Value::Int32(1),
+                    Value::F64(1.5),
+                    Value::Int64(2),
+                    Value::F64(2.25)
+                ]
+            )
+            .unwrap(),
+            Value::F64(3.75)
+        );
+        assert_eq!(
+            jit.call("fadd32", &[Value::F32(0.5), Value::F32(0.25)])
+                .unwrap(),
+            Value::F32(0.75)
+        );
+        assert_eq!(
+            jit.call("id16", &[Value::Int16(-3)]).unwrap(),
+            Value::Int16(-3)
+        );
+    }
+}
+
+#[test]
+fn constructors() {
+    let source = r#"
+@x = global i32 1
+@llvm.global_ctors = appending global [1 x { i32, void ()*, i8* }] [{ i32, void ()*, i8* } { i32 65535, void ()* @init, i8* null }]
+
+define internal void @init() {
+  %p = bitcast i32* @x to i32*
+  %v = load i32, i32* %p
+  %w = add i32 %v, 41
+  store i32 %w, i32* %p
+  ret void
+}
+
+define i32 @get() {
+  %v = load i32, i32* @x
+  ret i32 %v
+}
+"#;
+    let module = Module::try_from(source).unwrap();
+    let isa = X86_64::default();
+    let mach_module = compile_module(&isa, &module).unwrap();
+    let jit = JitBuilder::new(&mach_module).build().unwrap();
+    // Building doesn't run the constructors.
+    assert_eq!(unsafe { jit.call("get", &[]) }.unwrap(), Value::Int32(1));
+    unsafe { jit.run_constructors() };
+    assert_eq!(unsafe { jit.call("get", &[]) }.unwrap(), Value::Int32(42));
+}
+
+mod graph_coloring {
+    use super::*;
+    use RegAllocKind::GraphColoring;
+
+    test!(ary5, "ary5", Value::Int32(55), GraphColoring);
+    test!(call1, "call1", Value::Int32(1), GraphColoring);
+    test!(fibo, "fibo", Value::Int32(55), GraphColoring);
+    test!(load_add, "load_add", Value::Int32(7), GraphColoring);
+    test!(phi2, "phi2", Value::Int32(55), GraphColoring);
+    test!(puts, "puts", Value::Int32(0), GraphColoring);
+    test!(spill, "spill", Value::Int32(210), GraphColoring);
+    test!(sum, "sum", Value::Int32(55), GraphColoring);
+    test!(addr, "addr", Value::Int32(42), GraphColoring);
+    test!(float_arith, "float_arith", Value::Int32(2), GraphColoring);
+    test!(div, "div", Value::Int32(5308665), GraphColoring);
+    test!(shift, "shift", Value::Int32(-16340), GraphColoring);
+    test!(bitwise, "bitwise", Value::Int32(4442), GraphColoring);
+    test!(icmp, "icmp", Value::Int32(21782), GraphColoring);
+    test!(select, "select", Value::Int32(409), GraphColoring);
+    test!(switch, "switch", Value::Int32(48), GraphColoring);
+    test!(int_cast, "int_cast", Value::Int32(4200), GraphColoring);
+    test!(int_widths, "int_widths", Value::Int32(1357), GraphColoring);
+
+    #[test]
+    fn float_loop() {
+        assert_eq!(
+            run_with(FLOAT_LOOP, "main", &[], GraphColoring),
+            Value::Int32(10)
+        );
+    }
+
+    #[test]
+    fn live_across_calls() {
+        // More values are live across the calls than there are callee-saved registers.
+        let source = r#"
+define i32 @id(i32 %x) {
+  ret i32 %x
+}
+
+define i32 @main() {
+entry:
+  %a = call i32 @id(i32 1)
+  %b = call i32 @id(i32 2)
+  %c = call i32 @id(i32 3)
+  %d = call i32 @id(i32 4)
+  %e = call i32 @id(i32 5)
+  %f = call i32 @id(i32 6)
+  %g = call i32 @id(i32 7)
+  %h = call i32 @id(i32 8)
+  %i = call i32 @id(i32 100)
+  %s1 = add i32 %a, %b
+  %s2 = add i32 %s1, %c
+  %s3 = add i32 %s2, %d
+  %s4 = add i32 %s3, %e
+  %s5 = add i32 %s4, %f
+  %s6 = add i32 %s5, %g
+  %s7 = add i32 %s6, %h
+  %s8 = add i32 %s7, %i
+  ret i32 %s8
+}
+"#;
+        assert_eq!(
+            run_with(source, "main", &[], GraphColoring),
+            Value::Int32(136)
+        );
+    }
+}
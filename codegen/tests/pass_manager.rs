@@ -0,0 +1,156 @@
+use std::{cell::RefCell, io, rc::Rc};
+use vicis_codegen::{
+    function::Function,
+    isa::x86_64::X86_64,
+    lower::{compile_module_with_options, CompileOptions},
+    pass::{regalloc::RegAllocKind, Pass, PassManager},
+};
+use vicis_core::ir::module::Module;
+
+const SOURCE: &str = r#"
+define i32 @main() {
+entry:
+  br label %loop
+loop:
+  %i = phi i32 [ 0, %entry ], [ %j, %loop ]
+  %j = add i32 %i, 1
+  %c = icmp slt i32 %j, 10
+  br i1 %c, label %loop, label %exit
+exit:
+  ret i32 %j
+}
+"#;
+
+#[derive(Clone, Default)]
+struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+impl io::Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn compile(options: &mut CompileOptions<X86_64>) -> String {
+    let module = Module::try_from(SOURCE).unwrap();
+    let isa = X86_64::default();
+    let mach_module = compile_module_with_options(&isa, &module, options).unwrap();
+    mach_module.display_asm().to_string()
+}
+
+#[test]
+fn default_passes() {
+    let pm = PassManager::<X86_64>::with_default_passes();
+    assert_eq!(
+        pm.pass_names().collect::<Vec<_>>(),
+        vec![
+            "regalloc",
+            "phi_elimination",
+            "simple_reg_coalescing",
+            "eliminate_slot",
+            "pro_epi_inserter"
+        ]
+    );
+}
+
+#[test]
+fn remove_pass() {
+    // The phi and its operand are coalesced into the same register.
+    let mut options = CompileOptions {
+        regalloc: RegAllocKind::GraphColoring,
+        ..Default::default()
+    };
+    assert!(!compile(&mut options).contains("mov eax, eax"));
+
+    let mut options = CompileOptions {
+        regalloc: RegAllocKind::GraphColoring,
+        ..Default::default()
+    };
+    options.passes.remove("simple_reg_coalescing").unwrap();
+    assert!(compile(&mut options).contains("mov eax, eax"));
+
+    assert!(options.passes.remove("simple_reg_coalescing").is_err());
+}
+
+#[test]
+fn custom_pass() {
+    let num_insts = Rc::new(RefCell::new(vec![]));
+    let mut options = CompileOptions::default();
+    let n = num_insts.clone();
+    options
+        .passes
+        .insert_after(
+            "regalloc",
+            Pass::function("count_insts", move |func: &mut Function<X86_64>| {
+                let count = func
+                    .layout
+                    .block_iter()
+                    .map(|b| func.layout.inst_iter(b).count())
+                    .sum::<usize>();
+                n.borrow_mut().push(count);
+                Ok(())
+            }),
+        )
+        .unwrap();
+    assert!(options
+        .passes
+        .insert_before("no_such_pass", Pass::new("nop", |_| Ok(())))
+        .is_err());
+
+    compile(&mut options);
+    assert_eq!(num_insts.borrow().len(), 1);
+    assert!(num_insts.borrow()[0] > 0);
+    assert_eq!(
+        options.passes.pass_names().nth(1),
+        Some("count_insts"),
+        "inserted right after regalloc"
+    );
+}
+
+#[test]
+fn failing_pass() {
+    let mut options = CompileOptions::<X86_64>::default();
+    options
+        .passes
+        .add(Pass::new("fail", |_| Err(anyhow::anyhow!("failed"))));
+    let module = Module::try_from(SOURCE).unwrap();
+    let isa = X86_64::default();
+    let result = compile_module_with_options(&isa, &module, &mut options);
+    assert_eq!(result.err().unwrap().to_string(), "failed");
+}
+
+#[test]
+fn dump() {
+    let buf = SharedBuf::default();
+    let mut options = CompileOptions::default();
+    options.passes.set_dump_output(buf.clone());
+    options.passes.print_before("regalloc");
+    options.passes.print_after("pro_epi_inserter");
+    compile(&mut options);
+
+    let dump = String::from_utf8(buf.0.borrow().clone()).unwrap();
+    let before = dump.find("*** Dump Before regalloc ***").unwrap();
+    let after = dump.find("*** Dump After pro_epi_inserter ***").unwrap();
+    assert!(before < after);
+    assert_eq!(dump.matches("*** Dump").count(), 2);
+    // Virtual registers are gone after register allocation.
+    assert!(dump[before..after].contains("%0<def>"));
+    assert!(!dump[after..].contains("%0"));
+}
+
+#[test]
+fn timings() {
+    let mut options = CompileOptions::default();
+    compile(&mut options);
+    let names: Vec<&str> = options.passes.pass_names().collect();
+    let timed: Vec<&str> = options
+        .passes
+        .timings()
+        .iter()
+        .map(|t| t.name.as_str())
+        .collect();
+    assert_eq!(names, timed);
+}
@@ -0,0 +1,39 @@
+; ModuleID = 'float.c'
+source_filename = "float.c"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+@pi = dso_local global double 0x400921FB54442D18, align 8
+@half = dso_local global float 5.000000e-01, align 4
+@ld = dso_local global x86_fp80 0xK4000C000000000000000, align 16
+@q = dso_local global fp128 0xL00000000000000004000000000000000, align 16
+@h = dso_local global half 0xH3C00, align 2
+
+; Function Attrs: noinline nounwind optnone uwtable
+define dso_local double @f(double %0, float %1) #0 {
+  %3 = fadd double %0, 1.000000e+00
+  %4 = fsub nnan double %3, 0x3FB999999999999A
+  %5 = fmul fast double %4, %0
+  %6 = fdiv nsz arcp double %5, 2.000000e+00
+  %7 = frem double %6, 3.000000e+00
+  %8 = fneg double %7
+  %9 = fpext float %1 to double
+  %10 = fadd reassoc contract double %8, %9
+  %11 = fcmp olt double %10, 0.000000e+00
+  %12 = fcmp fast une double %10, %0
+  %13 = and i1 %11, %12
+  %14 = fptosi double %10 to i32
+  %15 = sitofp i32 %14 to double
+  %16 = fptrunc double %15 to float
+  %17 = fptoui float %16 to i64
+  %18 = uitofp i64 %17 to double
+  ret double %18
+}
+
+attributes #0 = { noinline nounwind optnone uwtable "frame-pointer"="all" "min-legal-vector-width"="0" "no-trapping-math"="true" "stack-protector-buffer-size"="8" "target-cpu"="x86-64" "target-features"="+cx8,+fxsr,+mmx,+sse,+sse2,+x87" "tune-cpu"="generic" }
+
+!llvm.module.flags = !{!0}
+!llvm.ident = !{!1}
+
+!0 = !{i32 1, !"wchar_size", i32 4}
+!1 = !{!"clang version 13.0.0"}
@@ -0,0 +1,19 @@
+; ModuleID = 'intops.c'
+source_filename = "intops.c"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+define dso_local i32 @ops(i32 %0, i32 %1) {
+  %3 = udiv i32 %0, %1
+  %4 = urem i32 %0, %1
+  %5 = xor i32 %3, %4
+  %6 = icmp ugt i32 %5, 7
+  %7 = select i1 %6, i32 %5, i32 %1
+  ret i32 %7
+}
+
+define dso_local i16 @half(i16 %0) {
+  %2 = add i16 %0, -1000
+  %3 = lshr exact i16 %2, 1
+  ret i16 %3
+}
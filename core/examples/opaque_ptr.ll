@@ -0,0 +1,35 @@
+; ModuleID = 'opaque_ptr.c'
+source_filename = "opaque_ptr.c"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+%struct.S = type { i32, ptr }
+
+@.str = private unnamed_addr constant [4 x i8] c"%d\0A\00", align 1
+@g = dso_local global ptr null, align 8
+
+; Function Attrs: noinline nounwind optnone uwtable
+define dso_local i32 @main() #0 {
+  %1 = alloca [3 x i32], align 4
+  %2 = alloca %struct.S, align 8
+  %3 = getelementptr inbounds [3 x i32], ptr %1, i64 0, i64 1
+  store i32 42, ptr %3, align 4
+  %4 = getelementptr inbounds %struct.S, ptr %2, i32 0, i32 1
+  store ptr %3, ptr %4, align 8
+  %5 = load ptr, ptr %4, align 8
+  %6 = load i32, ptr %5, align 4
+  store ptr %5, ptr @g, align 8
+  %7 = call i32 (ptr, ...) @printf(ptr noundef @.str, i32 noundef %6)
+  ret i32 %6
+}
+
+declare i32 @printf(ptr noundef, ...) #1
+
+attributes #0 = { noinline nounwind optnone uwtable "frame-pointer"="all" "min-legal-vector-width"="0" "no-trapping-math"="true" "stack-protector-buffer-size"="8" "target-cpu"="x86-64" "target-features"="+cx8,+fxsr,+mmx,+sse,+sse2,+x87" "tune-cpu"="generic" }
+attributes #1 = { "frame-pointer"="all" "no-trapping-math"="true" "stack-protector-buffer-size"="8" "target-cpu"="x86-64" "target-features"="+cx8,+fxsr,+mmx,+sse,+sse2,+x87" "tune-cpu"="generic" }
+
+!llvm.module.flags = !{!0}
+!llvm.ident = !{!1}
+
+!0 = !{i32 1, !"wchar_size", i32 4}
+!1 = !{!"clang version 15.0.0"}
@@ -1,10 +1,34 @@
-use std::fs::read_to_string;
+use std::fs::read;
 
-use vicis_core::ir::module::Module;
+use vicis_core::{
+    ir::module::Module,
+    parser::bitcode,
+    pass::{pipeline::OptLevel, PassManager},
+};
 
 fn main() {
-    let filename = std::env::args().nth(1).expect("expect *.ll file");
-    let source = read_to_string(filename).expect("failed to load file");
-    let m = Module::try_from(source.as_str()).expect("failed to parse file");
+    let mut filename = None;
+    let mut pm = None;
+    for arg in std::env::args().skip(1) {
+        if let Some(spec) = arg.strip_prefix("--passes=") {
+            pm = Some(PassManager::from_pipeline(spec).expect("invalid pipeline"));
+        } else if arg.starts_with("-O") {
+            let level: OptLevel = arg.parse().expect("invalid optimization level");
+            pm = Some(PassManager::with_opt_level(level));
+        } else {
+            filename = Some(arg);
+        }
+    }
+    let filename = filename.expect("expect *.ll or *.bc file");
+    let bytes = read(filename).expect("failed to load file");
+    let mut m = if bitcode::is_bitcode(&bytes) {
+        Module::try_from(bytes.as_slice()).expect("failed to parse bitcode")
+    } else {
+        let source = String::from_utf8(bytes).expect("failed to load file");
+        Module::try_from(source.as_str()).expect("failed to parse file")
+    };
+    if let Some(mut pm) = pm {
+        pm.run_on_module(&mut m);
+    }
     println!("#### Parsed result ####\n{:?}", m);
 }
@@ -0,0 +1,13 @@
+use std::fs::{read_to_string, write};
+
+use vicis_core::{ir::module::Module, writer::bitcode};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let input = args.next().expect("expect *.ll file");
+    let output = args.next().expect("expect output file");
+    let source = read_to_string(input).expect("failed to load file");
+    let m = Module::try_from(source.as_str()).expect("failed to parse file");
+    let bytes = bitcode::write(&m).expect("failed to write bitcode");
+    write(output, bytes).expect("failed to write file");
+}
@@ -1,4 +1,4 @@
-use crate::ir::value::{Value, ValueId};
+use crate::ir::value::{ArgumentValue, Value, ValueId};
 
 use super::{basic_block::BasicBlockId, instruction::builder::Builder as InstBuilder, Function};
 use rustc_hash::FxHashSet;
@@ -47,6 +47,13 @@ impl<'a> Builder<'a> {
     pub fn value<T: Into<Value>>(&mut self, val: T) -> ValueId {
         self.func.data.create_value(val.into())
     }
+
+    /// Returns the `nth` parameter of the function as a value.
+    pub fn param(&mut self, nth: usize) -> ValueId {
+        let param = &self.func.params[nth];
+        let arg = ArgumentValue::new(nth, param.ty, Some(param.name.clone()));
+        self.func.data.create_value(Value::Argument(arg))
+    }
 }
 
 impl Context {
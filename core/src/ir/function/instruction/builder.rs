@@ -1,24 +1,541 @@
-use super::{InstructionId, Opcode, Operand, Ret};
-use crate::ir::{function::builder::Builder as FuncBuilder, value::ValueId};
-// use rustc_hash::FxHashSet;
+use super::{
+    Alloca, Br, Call, Cast, CondBr, ExtractValue, FCmp, FCmpCond, FastMathFlags, FloatBinary,
+    FloatUnary, GetElementPtr, ICmp, ICmpCond, InsertValue, Instruction, InstructionId, IntBinary,
+    Invoke, LandingPad, Load, Opcode, Operand, Phi, Resume, Ret, Select, Store, Switch,
+};
+use crate::ir::{
+    function::{basic_block::BasicBlockId, builder::Builder as FuncBuilder},
+    types::{self, Type, Typed},
+    value::{ConstantInt, ConstantValue, Value, ValueId},
+};
 
+/// Creates instructions at the end of the current block of a function builder.
+///
+/// Flags apply to the next created instruction, e.g. `builder.inst().nsw().add(x, y)`, and are
+/// dropped if that instruction doesn't take them.
+/// Branches update `preds` and `succs` of the blocks involved.
 pub struct Builder<'a: 'short, 'short> {
     func_builder: &'short mut FuncBuilder<'a>,
+    nsw: bool,
+    nuw: bool,
+    exact: bool,
+    inbounds: bool,
+    fmf: FastMathFlags,
+}
+
+macro_rules! int_binary {
+    ($name:ident, $opcode:ident) => {
+        pub fn $name(&mut self, lhs: ValueId, rhs: ValueId) -> ValueId {
+            self.int_binary(Opcode::$opcode, lhs, rhs)
+        }
+    };
+}
+
+macro_rules! float_binary {
+    ($name:ident, $opcode:ident) => {
+        pub fn $name(&mut self, lhs: ValueId, rhs: ValueId) -> ValueId {
+            self.float_binary(Opcode::$opcode, lhs, rhs)
+        }
+    };
+}
+
+macro_rules! cast {
+    ($name:ident, $opcode:ident) => {
+        pub fn $name(&mut self, val: ValueId, to: Type) -> ValueId {
+            self.cast(Opcode::$opcode, val, to)
+        }
+    };
 }
 
 impl<'a: 'short, 'short> Builder<'a, 'short> {
     pub fn new(func_builder: &'short mut FuncBuilder<'a>) -> Self {
-        Self { func_builder }
+        Self {
+            func_builder,
+            nsw: false,
+            nuw: false,
+            exact: false,
+            inbounds: false,
+            fmf: FastMathFlags::default(),
+        }
+    }
+
+    pub fn nsw(mut self) -> Self {
+        self.nsw = true;
+        self
+    }
+
+    pub fn nuw(mut self) -> Self {
+        self.nuw = true;
+        self
+    }
+
+    pub fn exact(mut self) -> Self {
+        self.exact = true;
+        self
+    }
+
+    pub fn inbounds(mut self) -> Self {
+        self.inbounds = true;
+        self
+    }
+
+    pub fn fast_math(mut self, fmf: FastMathFlags) -> Self {
+        self.fmf = fmf;
+        self
+    }
+
+    pub fn alloca(&mut self, ty: Type) -> ValueId {
+        let ptr = self.func_builder.func.types.base_mut().pointer(ty);
+        let inst = Opcode::Alloca
+            .with_block(self.cur_block())
+            .with_operand(Operand::Alloca(Alloca {
+                tys: [ty, types::I32],
+                num_elements: ConstantValue::Int(ConstantInt::Int32(1)),
+                align: 0,
+            }))
+            .with_ty(ptr);
+        self.insert_value(inst)
+    }
+
+    /// Creates a phi node. Incoming edges can be added later by `add_incoming`.
+    pub fn phi(&mut self, ty: Type, incoming: &[(ValueId, BasicBlockId)]) -> ValueId {
+        let inst = Opcode::Phi
+            .with_block(self.cur_block())
+            .with_operand(Operand::Phi(Phi {
+                ty,
+                args: incoming.iter().map(|&(val, _)| val).collect(),
+                blocks: incoming.iter().map(|&(_, block)| block).collect(),
+            }))
+            .with_ty(ty);
+        self.insert_value(inst)
+    }
+
+    pub fn add_incoming(&mut self, phi: ValueId, val: ValueId, block: BasicBlockId) {
+        let data = &mut self.func_builder.func.data;
+        let phi = *data
+            .value_ref(phi)
+            .as_inst()
+            .expect("phi must be an instruction");
+        let operand = data
+            .inst_ref_mut(phi)
+            .operand
+            .as_phi_mut()
+            .expect("not a phi");
+        operand.args.push(val);
+        operand.blocks.push(block);
+        data.validate_inst_uses(phi);
+    }
+
+    pub fn load(&mut self, ty: Type, addr: ValueId) -> ValueId {
+        let addr_ty = self.value_ty(addr);
+        let inst = Opcode::Load
+            .with_block(self.cur_block())
+            .with_operand(Operand::Load(Load {
+                tys: [ty, addr_ty],
+                addr,
+                align: 0,
+            }))
+            .with_ty(ty);
+        self.insert_value(inst)
+    }
+
+    pub fn store(&mut self, val: ValueId, addr: ValueId) -> InstructionId {
+        let tys = [self.value_ty(val), self.value_ty(addr)];
+        let inst = Opcode::Store
+            .with_block(self.cur_block())
+            .with_operand(Operand::Store(Store {
+                tys,
+                args: [val, addr],
+                align: 0,
+            }));
+        self.insert(inst)
+    }
+
+    pub fn insertvalue(&mut self, aggregate: ValueId, elt: ValueId, indices: &[usize]) -> ValueId {
+        let tys = [self.value_ty(aggregate), self.value_ty(elt)];
+        let mut args = vec![aggregate, elt];
+        args.extend(indices.iter().map(|&i| self.index(i)));
+        let inst = Opcode::InsertValue
+            .with_block(self.cur_block())
+            .with_operand(Operand::InsertValue(InsertValue { tys, args }))
+            .with_ty(tys[0]);
+        self.insert_value(inst)
+    }
+
+    pub fn extractvalue(&mut self, aggregate: ValueId, indices: &[usize]) -> ValueId {
+        let ty = self.value_ty(aggregate);
+        let elem_ty = self
+            .func_builder
+            .func
+            .types
+            .base()
+            .element_at_(ty, indices.iter().copied())
+            .expect("invalid indices for extractvalue");
+        let mut args = vec![aggregate];
+        args.extend(indices.iter().map(|&i| self.index(i)));
+        let inst = Opcode::ExtractValue
+            .with_block(self.cur_block())
+            .with_operand(Operand::ExtractValue(ExtractValue { ty, args }))
+            .with_ty(elem_ty);
+        self.insert_value(inst)
+    }
+
+    int_binary!(add, Add);
+    int_binary!(sub, Sub);
+    int_binary!(mul, Mul);
+    int_binary!(sdiv, SDiv);
+    int_binary!(udiv, UDiv);
+    int_binary!(srem, SRem);
+    int_binary!(urem, URem);
+    int_binary!(and, And);
+    int_binary!(or, Or);
+    int_binary!(xor, Xor);
+    int_binary!(shl, Shl);
+    int_binary!(ashr, AShr);
+    int_binary!(lshr, LShr);
+
+    pub fn int_binary(&mut self, opcode: Opcode, lhs: ValueId, rhs: ValueId) -> ValueId {
+        let ty = self.value_ty(lhs);
+        let wraps = matches!(
+            opcode,
+            Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Shl
+        );
+        let takes_exact = matches!(
+            opcode,
+            Opcode::SDiv | Opcode::UDiv | Opcode::AShr | Opcode::LShr
+        );
+        let inst = opcode
+            .with_block(self.cur_block())
+            .with_operand(Operand::IntBinary(IntBinary {
+                ty,
+                nsw: self.nsw && wraps,
+                nuw: self.nuw && wraps,
+                exact: self.exact && takes_exact,
+                args: [lhs, rhs],
+            }))
+            .with_ty(ty);
+        self.insert_value(inst)
+    }
+
+    float_binary!(fadd, FAdd);
+    float_binary!(fsub, FSub);
+    float_binary!(fmul, FMul);
+    float_binary!(fdiv, FDiv);
+    float_binary!(frem, FRem);
+
+    pub fn float_binary(&mut self, opcode: Opcode, lhs: ValueId, rhs: ValueId) -> ValueId {
+        let ty = self.value_ty(lhs);
+        let inst = opcode
+            .with_block(self.cur_block())
+            .with_operand(Operand::FloatBinary(FloatBinary {
+                ty,
+                fmf: self.fmf,
+                args: [lhs, rhs],
+            }))
+            .with_ty(ty);
+        self.insert_value(inst)
+    }
+
+    pub fn fneg(&mut self, arg: ValueId) -> ValueId {
+        let ty = self.value_ty(arg);
+        let inst = Opcode::FNeg
+            .with_block(self.cur_block())
+            .with_operand(Operand::FloatUnary(FloatUnary {
+                ty,
+                fmf: self.fmf,
+                arg,
+            }))
+            .with_ty(ty);
+        self.insert_value(inst)
+    }
+
+    pub fn icmp(&mut self, cond: ICmpCond, lhs: ValueId, rhs: ValueId) -> ValueId {
+        let ty = self.value_ty(lhs);
+        let inst = Opcode::ICmp
+            .with_block(self.cur_block())
+            .with_operand(Operand::ICmp(ICmp {
+                ty,
+                args: [lhs, rhs],
+                cond,
+            }))
+            .with_ty(types::I1);
+        self.insert_value(inst)
+    }
+
+    pub fn fcmp(&mut self, cond: FCmpCond, lhs: ValueId, rhs: ValueId) -> ValueId {
+        let ty = self.value_ty(lhs);
+        let inst = Opcode::FCmp
+            .with_block(self.cur_block())
+            .with_operand(Operand::FCmp(FCmp {
+                ty,
+                fmf: self.fmf,
+                args: [lhs, rhs],
+                cond,
+            }))
+            .with_ty(types::I1);
+        self.insert_value(inst)
+    }
+
+    cast!(sext, Sext);
+    cast!(zext, Zext);
+    cast!(bitcast, Bitcast);
+    cast!(trunc, Trunc);
+    cast!(inttoptr, IntToPtr);
+    cast!(ptrtoint, PtrToInt);
+    cast!(fptrunc, FPTrunc);
+    cast!(fpext, FPExt);
+    cast!(fptoui, FPToUI);
+    cast!(fptosi, FPToSI);
+    cast!(uitofp, UIToFP);
+    cast!(sitofp, SIToFP);
+
+    pub fn cast(&mut self, opcode: Opcode, arg: ValueId, to: Type) -> ValueId {
+        let from = self.value_ty(arg);
+        let inst = opcode
+            .with_block(self.cur_block())
+            .with_operand(Operand::Cast(Cast {
+                tys: [from, to],
+                arg,
+            }))
+            .with_ty(to);
+        self.insert_value(inst)
+    }
+
+    /// Creates `getelementptr ty, base, indices...`.
+    pub fn gep(&mut self, ty: Type, base: ValueId, indices: &[ValueId]) -> ValueId {
+        let base_ty = self.value_ty(base);
+        let mut tys = vec![ty, base_ty];
+        tys.extend(indices.iter().map(|&idx| self.value_ty(idx)));
+        let mut args = vec![base];
+        args.extend(indices);
+
+        let types = &self.func_builder.func.types;
+        let result_ty = if types.is_opaque_pointer(base_ty) {
+            base_ty
+        } else {
+            let indices =
+                indices
+                    .iter()
+                    .map(|&idx| match self.func_builder.func.data.value_ref(idx) {
+                        Value::Constant(ConstantValue::Int(i)) => i.cast_to_usize(),
+                        _ => 0,
+                    });
+            let inner = types
+                .base()
+                .element_at_(base_ty, indices)
+                .expect("invalid indices for getelementptr");
+            types.base_mut().pointer(inner)
+        };
+
+        let inst = Opcode::GetElementPtr
+            .with_block(self.cur_block())
+            .with_operand(Operand::GetElementPtr(GetElementPtr {
+                inbounds: self.inbounds,
+                tys,
+                args,
+            }))
+            .with_ty(result_ty);
+        self.insert_value(inst)
+    }
+
+    /// Creates `select cond, then_val, else_val`.
+    pub fn select(&mut self, cond: ValueId, then_val: ValueId, else_val: ValueId) -> ValueId {
+        let cond_ty = self.value_ty(cond);
+        let ty = self.value_ty(then_val);
+        let inst = Opcode::Select
+            .with_block(self.cur_block())
+            .with_operand(Operand::Select(Select {
+                tys: [cond_ty, ty],
+                args: [cond, then_val, else_val],
+            }))
+            .with_ty(ty);
+        self.insert_value(inst)
+    }
+
+    pub fn call(&mut self, result_ty: Type, callee: ValueId, args: &[ValueId]) -> ValueId {
+        let mut tys = vec![result_ty];
+        tys.extend(args.iter().map(|&arg| self.value_ty(arg)));
+        let inst = Opcode::Call
+            .with_block(self.cur_block())
+            .with_operand(Operand::Call(Call {
+                args: [callee].iter().chain(args).copied().collect(),
+                tys,
+                param_attrs: vec![vec![]; args.len()],
+                ret_attrs: vec![],
+                func_attrs: vec![],
+            }))
+            .with_ty(result_ty);
+        self.insert_value(inst)
+    }
+
+    pub fn invoke(
+        &mut self,
+        result_ty: Type,
+        callee: ValueId,
+        args: &[ValueId],
+        normal: BasicBlockId,
+        unwind: BasicBlockId,
+    ) -> ValueId {
+        let mut tys = vec![result_ty];
+        tys.extend(args.iter().map(|&arg| self.value_ty(arg)));
+        let inst = Opcode::Invoke
+            .with_block(self.cur_block())
+            .with_operand(Operand::Invoke(Invoke {
+                args: [callee].iter().chain(args).copied().collect(),
+                tys,
+                param_attrs: vec![vec![]; args.len()],
+                ret_attrs: vec![],
+                func_attrs: vec![],
+                blocks: vec![normal, unwind],
+            }))
+            .with_ty(result_ty);
+        self.add_edge(normal);
+        self.add_edge(unwind);
+        self.insert_value(inst)
+    }
+
+    pub fn landingpad(&mut self, ty: Type, catches: &[ValueId], cleanup: bool) -> ValueId {
+        let catches = catches
+            .iter()
+            .map(|&catch| (self.value_ty(catch), catch))
+            .collect();
+        let inst = Opcode::LandingPad
+            .with_block(self.cur_block())
+            .with_operand(Operand::LandingPad(LandingPad {
+                ty,
+                catches,
+                cleanup,
+            }))
+            .with_ty(ty);
+        self.insert_value(inst)
+    }
+
+    pub fn resume(&mut self, arg: ValueId) -> InstructionId {
+        let ty = self.value_ty(arg);
+        let inst = Opcode::Resume
+            .with_block(self.cur_block())
+            .with_operand(Operand::Resume(Resume { ty, arg }));
+        self.insert(inst)
+    }
+
+    pub fn br(&mut self, block: BasicBlockId) -> InstructionId {
+        let inst = Opcode::Br
+            .with_block(self.cur_block())
+            .with_operand(Operand::Br(Br { block }));
+        self.add_edge(block);
+        self.insert(inst)
+    }
+
+    pub fn cond_br(
+        &mut self,
+        cond: ValueId,
+        iftrue: BasicBlockId,
+        iffalse: BasicBlockId,
+    ) -> InstructionId {
+        let inst = Opcode::CondBr
+            .with_block(self.cur_block())
+            .with_operand(Operand::CondBr(CondBr {
+                arg: cond,
+                blocks: [iftrue, iffalse],
+            }));
+        self.add_edge(iftrue);
+        self.add_edge(iffalse);
+        self.insert(inst)
+    }
+
+    pub fn switch(
+        &mut self,
+        cond: ValueId,
+        default: BasicBlockId,
+        cases: &[(ValueId, BasicBlockId)],
+    ) -> InstructionId {
+        let mut tys = vec![self.value_ty(cond)];
+        let mut args = vec![cond];
+        let mut blocks = vec![default];
+        for &(case, block) in cases {
+            tys.push(self.value_ty(case));
+            args.push(case);
+            blocks.push(block);
+        }
+        for &block in &blocks {
+            self.add_edge(block);
+        }
+        let inst = Opcode::Switch
+            .with_block(self.cur_block())
+            .with_operand(Operand::Switch(Switch { tys, args, blocks }));
+        self.insert(inst)
     }
 
     pub fn ret(&mut self, val: ValueId) -> InstructionId {
-        let cur_block = self.func_builder.cur_block.unwrap();
         let ty = self.func_builder.func.result_ty;
         let inst = Opcode::Ret
-            .with_block(cur_block)
+            .with_block(self.cur_block())
             .with_operand(Operand::Ret(Ret { ty, val: Some(val) }));
+        self.insert(inst)
+    }
+
+    pub fn ret_void(&mut self) -> InstructionId {
+        let inst = Opcode::Ret
+            .with_block(self.cur_block())
+            .with_operand(Operand::Ret(Ret {
+                ty: types::VOID,
+                val: None,
+            }));
+        self.insert(inst)
+    }
+
+    pub fn unreachable(&mut self) -> InstructionId {
+        let inst = Opcode::Unreachable
+            .with_block(self.cur_block())
+            .with_operand(Operand::Unreachable);
+        self.insert(inst)
+    }
+
+    fn cur_block(&self) -> BasicBlockId {
+        self.func_builder
+            .cur_block
+            .expect("no block to insert instructions into")
+    }
+
+    fn insert(&mut self, inst: Instruction) -> InstructionId {
+        self.nsw = false;
+        self.nuw = false;
+        self.exact = false;
+        self.inbounds = false;
+        self.fmf = FastMathFlags::default();
+        let cur_block = self.cur_block();
         let inst = self.func_builder.func.data.create_inst(inst);
         self.func_builder.func.layout.append_inst(inst, cur_block);
         inst
     }
+
+    fn insert_value(&mut self, inst: Instruction) -> ValueId {
+        let inst = self.insert(inst);
+        self.func_builder
+            .func
+            .data
+            .create_value(Value::Instruction(inst))
+    }
+
+    /// Adds an edge from the current block to `succ`.
+    fn add_edge(&mut self, succ: BasicBlockId) {
+        let cur_block = self.cur_block();
+        let data = &mut self.func_builder.func.data;
+        data.block_ref_mut(cur_block).succs_mut().insert(succ);
+        data.block_ref_mut(succ).preds_mut().insert(cur_block);
+    }
+
+    fn index(&mut self, i: usize) -> ValueId {
+        self.func_builder.value(ConstantInt::Int32(i as i32))
+    }
+
+    fn value_ty(&self, val: ValueId) -> Type {
+        let data = &self.func_builder.func.data;
+        match data.value_ref(val) {
+            Value::Instruction(id) => data.inst_ref(*id).ty,
+            Value::Argument(arg) => arg.ty,
+            Value::Constant(konst) => konst.ty(),
+            Value::InlineAsm(_) => panic!("inline asm has no type"),
+        }
+    }
 }
@@ -4,8 +4,9 @@ use crate::ir::{
         basic_block::BasicBlockId,
         data::Data,
         instruction::{
-            Alloca, Br, Call, Cast, CondBr, ExtractValue, GetElementPtr, ICmp, InsertValue,
-            IntBinary, Invoke, LandingPad, Load, Operand, Phi, Resume, Ret, Store,
+            Alloca, Br, Call, Cast, CondBr, ExtractValue, FCmp, FloatBinary, FloatUnary,
+            GetElementPtr, ICmp, InsertValue, IntBinary, Invoke, LandingPad, Load, Operand, Phi,
+            Resume, Ret, Select, Store,
         },
     },
     module::name::Name,
@@ -199,6 +200,27 @@ impl fmt::Display for DisplayInstruction<'_> {
                     value_string(self, args[1]),
                 )
             }
+            Operand::FloatBinary(FloatBinary { ty, fmf, args }) => {
+                write!(
+                    f,
+                    "%{dest:?} = {:?}{} {} {}, {}",
+                    self.inst.opcode,
+                    fmf,
+                    self.types.to_string(*ty),
+                    value_string(self, args[0]),
+                    value_string(self, args[1]),
+                )
+            }
+            Operand::FloatUnary(FloatUnary { ty, fmf, arg }) => {
+                write!(
+                    f,
+                    "%{dest:?} = {:?}{} {} {}",
+                    self.inst.opcode,
+                    fmf,
+                    self.types.to_string(*ty),
+                    value_string(self, *arg),
+                )
+            }
             Operand::ICmp(ICmp { ty, args, cond }) => {
                 write!(
                     f,
@@ -209,6 +231,22 @@ impl fmt::Display for DisplayInstruction<'_> {
                     value_string(self, args[1])
                 )
             }
+            Operand::FCmp(FCmp {
+                ty,
+                fmf,
+                args,
+                cond,
+            }) => {
+                write!(
+                    f,
+                    "%{dest:?} = fcmp{} {:?} {} {}, {}",
+                    fmf,
+                    cond,
+                    self.types.to_string(*ty),
+                    value_string(self, args[0]),
+                    value_string(self, args[1])
+                )
+            }
             Operand::Cast(Cast { tys, arg }) => {
                 write!(
                     f,
@@ -243,6 +281,18 @@ impl fmt::Display for DisplayInstruction<'_> {
                         .trim_end_matches(", ")
                 )
             }
+            Operand::Select(Select { tys, args }) => {
+                write!(
+                    f,
+                    "%{dest:?} = select {} {}, {} {}, {} {}",
+                    self.types.to_string(tys[0]),
+                    value_string(self, args[0]),
+                    self.types.to_string(tys[1]),
+                    value_string(self, args[1]),
+                    self.types.to_string(tys[1]),
+                    value_string(self, args[2]),
+                )
+            }
             Operand::Call(Call {
                 tys,
                 args,
@@ -39,20 +39,37 @@ pub enum Opcode {
     Sub,
     Mul,
     SDiv,
+    UDiv,
     SRem,
+    URem,
     And,
     Or,
+    Xor,
     Shl,
     AShr,
     LShr,
+    FNeg,
+    FAdd,
+    FSub,
+    FMul,
+    FDiv,
+    FRem,
     ICmp,
+    FCmp,
     Sext,
     Zext,
     Bitcast,
     Trunc,
     IntToPtr,
     PtrToInt,
+    FPTrunc,
+    FPExt,
+    FPToUI,
+    FPToSI,
+    UIToFP,
+    SIToFP,
     GetElementPtr,
+    Select,
     Call,
     Invoke,
     LandingPad,
@@ -79,6 +96,38 @@ pub enum ICmpCond {
     Sle,
 }
 
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum FCmpCond {
+    False,
+    Oeq,
+    Ogt,
+    Oge,
+    Olt,
+    Ole,
+    One,
+    Ord,
+    Ueq,
+    Ugt,
+    Uge,
+    Ult,
+    Ule,
+    Une,
+    Uno,
+    True,
+}
+
+/// Fast-math flags attached to floating-point instructions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FastMathFlags {
+    pub nnan: bool,
+    pub ninf: bool,
+    pub nsz: bool,
+    pub arcp: bool,
+    pub contract: bool,
+    pub afn: bool,
+    pub reassoc: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct Alloca {
     pub tys: [Type; 2],
@@ -109,6 +158,20 @@ pub struct IntBinary {
     pub args: [ValueId; 2],
 }
 
+#[derive(Debug, Clone)]
+pub struct FloatBinary {
+    pub ty: Type,
+    pub fmf: FastMathFlags,
+    pub args: [ValueId; 2],
+}
+
+#[derive(Debug, Clone)]
+pub struct FloatUnary {
+    pub ty: Type,
+    pub fmf: FastMathFlags,
+    pub arg: ValueId,
+}
+
 #[derive(Debug, Clone)]
 pub struct Store {
     pub tys: [Type; 2],
@@ -135,6 +198,14 @@ pub struct ICmp {
     pub cond: ICmpCond,
 }
 
+#[derive(Debug, Clone)]
+pub struct FCmp {
+    pub ty: Type,
+    pub fmf: FastMathFlags,
+    pub args: [ValueId; 2],
+    pub cond: FCmpCond,
+}
+
 #[derive(Debug, Clone)]
 pub struct Cast {
     pub tys: [Type; 2], // from, to
@@ -148,6 +219,12 @@ pub struct GetElementPtr {
     pub args: Vec<ValueId>,
 }
 
+#[derive(Debug, Clone)]
+pub struct Select {
+    pub tys: [Type; 2],     // cond, value
+    pub args: [ValueId; 3], // cond, iftrue, iffalse
+}
+
 #[derive(Debug, Clone)]
 pub struct Call {
     pub args: Vec<ValueId>, // args[0] = callee, args[1..] = arguments
@@ -210,12 +287,16 @@ pub enum Operand {
     Phi(Phi),
     Load(Load),
     IntBinary(IntBinary),
+    FloatBinary(FloatBinary),
+    FloatUnary(FloatUnary),
     Store(Store),
     InsertValue(InsertValue),
     ExtractValue(ExtractValue),
     ICmp(ICmp),
+    FCmp(FCmp),
     Cast(Cast),
     GetElementPtr(GetElementPtr),
+    Select(Select),
     Call(Call),
     Invoke(Invoke),
     LandingPad(LandingPad),
@@ -233,6 +314,7 @@ impl Instruction {
         assert_eq!(self.opcode, Opcode::Invalid);
         self.opcode = other.opcode;
         self.operand = other.operand;
+        self.ty = other.ty;
         self.dest = other.dest;
         self.parent = other.parent;
     }
@@ -336,7 +418,13 @@ impl Opcode {
     pub fn is_terminator(&self) -> bool {
         matches!(
             self,
-            Self::Ret | Self::Br | Self::CondBr | Self::Switch | Self::Invoke | Self::Resume
+            Self::Ret
+                | Self::Br
+                | Self::CondBr
+                | Self::Switch
+                | Self::Invoke
+                | Self::Resume
+                | Self::Unreachable
         )
     }
 
@@ -406,9 +494,13 @@ impl Operand {
             Self::InsertValue(InsertValue { args, .. }) => args,
             Self::ExtractValue(ExtractValue { args, .. }) => args,
             Self::IntBinary(IntBinary { args, .. }) => args,
+            Self::FloatBinary(FloatBinary { args, .. }) => args,
+            Self::FloatUnary(FloatUnary { arg, .. }) => slice::from_ref(arg),
             Self::ICmp(ICmp { args, .. }) => args,
+            Self::FCmp(FCmp { args, .. }) => args,
             Self::Cast(Cast { arg, .. }) => slice::from_ref(arg),
             Self::GetElementPtr(GetElementPtr { args, .. }) => args.as_slice(),
+            Self::Select(Select { args, .. }) => args,
             Self::Call(Call { args, .. }) | Self::Invoke(Invoke { args, .. }) => args.as_slice(),
             Self::LandingPad(LandingPad { .. }) => &[],
             Self::Resume(Resume { arg, .. }) => slice::from_ref(arg),
@@ -431,9 +523,13 @@ impl Operand {
             Self::InsertValue(InsertValue { args, .. }) => args,
             Self::ExtractValue(ExtractValue { args, .. }) => args,
             Self::IntBinary(IntBinary { args, .. }) => args,
+            Self::FloatBinary(FloatBinary { args, .. }) => args,
+            Self::FloatUnary(FloatUnary { arg, .. }) => slice::from_mut(arg),
             Self::ICmp(ICmp { args, .. }) => args,
+            Self::FCmp(FCmp { args, .. }) => args,
             Self::Cast(Cast { arg, .. }) => slice::from_mut(arg),
             Self::GetElementPtr(GetElementPtr { args, .. }) => args.as_mut_slice(),
+            Self::Select(Select { args, .. }) => args,
             Self::Call(Call { args, .. }) | Self::Invoke(Invoke { args, .. }) => args.as_mut(),
             Self::LandingPad(LandingPad { .. }) => &mut [],
             Self::Resume(Resume { arg, .. }) => slice::from_mut(arg),
@@ -455,9 +551,13 @@ impl Operand {
             Self::InsertValue(InsertValue { tys, .. }) => tys,
             Self::ExtractValue(ExtractValue { ty, .. }) => slice::from_ref(ty),
             Self::IntBinary(IntBinary { ty, .. }) => slice::from_ref(ty),
+            Self::FloatBinary(FloatBinary { ty, .. }) => slice::from_ref(ty),
+            Self::FloatUnary(FloatUnary { ty, .. }) => slice::from_ref(ty),
             Self::ICmp(ICmp { ty, .. }) => slice::from_ref(ty),
+            Self::FCmp(FCmp { ty, .. }) => slice::from_ref(ty),
             Self::Cast(Cast { tys, .. }) => tys,
             Self::GetElementPtr(GetElementPtr { tys, .. }) => tys.as_slice(),
+            Self::Select(Select { tys, .. }) => tys,
             Self::Call(Call { tys, .. }) | Self::Invoke(Invoke { tys, .. }) => tys.as_slice(),
             Self::LandingPad(LandingPad { ty, .. }) => slice::from_ref(ty),
             Self::Resume(Resume { ty, .. }) => slice::from_ref(ty),
@@ -475,6 +575,7 @@ impl Operand {
             Self::Br(Br { block }) => slice::from_ref(block),
             Self::CondBr(CondBr { blocks, .. }) => blocks,
             Self::Invoke(Invoke { blocks, .. }) => blocks,
+            Self::Switch(Switch { blocks, .. }) => blocks,
             _ => &[],
         }
     }
@@ -494,6 +595,29 @@ impl Operand {
     as_inst!(as_condbr, CondBr);
 }
 
+impl FastMathFlags {
+    /// Returns flags equivalent to `fast`.
+    pub fn fast() -> Self {
+        Self {
+            nnan: true,
+            ninf: true,
+            nsz: true,
+            arcp: true,
+            contract: true,
+            afn: true,
+            reassoc: true,
+        }
+    }
+
+    pub fn is_fast(&self) -> bool {
+        self == &Self::fast()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
 impl Alloca {
     pub fn ty(&self) -> Type {
         self.tys[0]
@@ -552,6 +676,16 @@ impl Switch {
     }
 }
 
+impl Select {
+    pub fn cond(&self) -> ValueId {
+        self.args[0]
+    }
+
+    pub fn cond_ty(&self) -> Type {
+        self.tys[0]
+    }
+}
+
 impl fmt::Debug for Opcode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -568,20 +702,37 @@ impl fmt::Debug for Opcode {
                 Opcode::Sub => "sub",
                 Opcode::Mul => "mul",
                 Opcode::SDiv => "sdiv",
+                Opcode::UDiv => "udiv",
                 Opcode::SRem => "srem",
+                Opcode::URem => "urem",
                 Opcode::And => "and",
                 Opcode::Or => "or",
+                Opcode::Xor => "xor",
                 Opcode::Shl => "shl",
                 Opcode::AShr => "ashr",
                 Opcode::LShr => "lshr",
+                Opcode::FNeg => "fneg",
+                Opcode::FAdd => "fadd",
+                Opcode::FSub => "fsub",
+                Opcode::FMul => "fmul",
+                Opcode::FDiv => "fdiv",
+                Opcode::FRem => "frem",
                 Opcode::ICmp => "icmp",
+                Opcode::FCmp => "fcmp",
                 Opcode::Sext => "sext",
                 Opcode::Zext => "zext",
                 Opcode::Bitcast => "bitcast",
                 Opcode::Trunc => "trunc",
                 Opcode::IntToPtr => "inttoptr",
                 Opcode::PtrToInt => "ptrtoint",
+                Opcode::FPTrunc => "fptrunc",
+                Opcode::FPExt => "fpext",
+                Opcode::FPToUI => "fptoui",
+                Opcode::FPToSI => "fptosi",
+                Opcode::UIToFP => "uitofp",
+                Opcode::SIToFP => "sitofp",
                 Opcode::GetElementPtr => "getelementptr",
+                Opcode::Select => "select",
                 Opcode::Call => "call",
                 Opcode::Invoke => "invoke",
                 Opcode::LandingPad => "landingpad",
@@ -616,3 +767,53 @@ impl fmt::Debug for ICmpCond {
         )
     }
 }
+
+impl fmt::Debug for FCmpCond {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::False => "false",
+                Self::Oeq => "oeq",
+                Self::Ogt => "ogt",
+                Self::Oge => "oge",
+                Self::Olt => "olt",
+                Self::Ole => "ole",
+                Self::One => "one",
+                Self::Ord => "ord",
+                Self::Ueq => "ueq",
+                Self::Ugt => "ugt",
+                Self::Uge => "uge",
+                Self::Ult => "ult",
+                Self::Ule => "ule",
+                Self::Une => "une",
+                Self::Uno => "uno",
+                Self::True => "true",
+            }
+        )
+    }
+}
+
+// Each flag is preceded by a space so that it can be put right after an opcode.
+impl fmt::Display for FastMathFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_fast() {
+            return write!(f, " fast");
+        }
+        for (flag, name) in [
+            (self.reassoc, "reassoc"),
+            (self.nnan, "nnan"),
+            (self.ninf, "ninf"),
+            (self.nsz, "nsz"),
+            (self.arcp, "arcp"),
+            (self.contract, "contract"),
+            (self.afn, "afn"),
+        ] {
+            if flag {
+                write!(f, " {}", name)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -135,6 +135,7 @@ impl<'a, 'b: 'a> FunctionAsmPrinter<'a, 'b> {
                         | Opcode::Switch
                         | Opcode::Ret
                         | Opcode::Resume
+                        | Opcode::Unreachable
                 ) || (inst
                     .operand
                     .call_result_ty()
@@ -21,7 +21,7 @@ impl DataLayout {
                 CompoundType::Array(a) => {
                     self.get_size_of(types, a.inner) * a.num_elements as usize
                 }
-                CompoundType::Pointer(_) => 8,
+                CompoundType::Pointer(_) | CompoundType::OpaquePointer(_) => 8,
                 CompoundType::Struct(s) => StructLayout::new(self, types, s).get_size(),
                 e => todo!("{:?}", e),
             },
@@ -32,6 +32,11 @@ impl DataLayout {
                 types::I16 => 2,
                 types::I32 => 4,
                 types::I64 => 8,
+                types::HALF => 2,
+                types::FLOAT => 4,
+                types::DOUBLE => 8,
+                types::FP128 => 16,
+                types::X86_FP80 => 16,
                 x => todo!("sizeof {:?}", x),
             },
         }
@@ -41,7 +46,7 @@ impl DataLayout {
         match types.get(ty) {
             Some(ty) => match &*ty {
                 CompoundType::Array(a) => self.get_size_of(types, a.inner),
-                CompoundType::Pointer(_) => 8,
+                CompoundType::Pointer(_) | CompoundType::OpaquePointer(_) => 8,
                 CompoundType::Struct(s) => StructLayout::new(self, types, s).get_align(),
                 _ => todo!(),
             },
@@ -52,6 +57,11 @@ impl DataLayout {
                 types::I16 => 2,
                 types::I32 => 4,
                 types::I64 => 8,
+                types::HALF => 2,
+                types::FLOAT => 4,
+                types::DOUBLE => 8,
+                types::FP128 => 16,
+                types::X86_FP80 => 16,
                 x => todo!("sizeof {:?}", x),
             },
         }
@@ -125,10 +135,16 @@ fn size() {
     assert_eq!(dl.get_size_of(&types, types::I16), 2);
     assert_eq!(dl.get_size_of(&types, types::I32), 4);
     assert_eq!(dl.get_size_of(&types, types::I64), 8);
+    assert_eq!(dl.get_size_of(&types, types::FLOAT), 4);
+    assert_eq!(dl.get_size_of(&types, types::DOUBLE), 8);
+    assert_eq!(dl.get_size_of(&types, types::X86_FP80), 16);
 
     let i8_ptr = types.base_mut().pointer(types::I8);
     assert_eq!(dl.get_size_of(&types, i8_ptr), 8);
 
+    let ptr = types.base_mut().opaque_pointer(0);
+    assert_eq!(dl.get_size_of(&types, ptr), 8);
+
     let i8_arr = types.base_mut().array(ArrayType::new(types::I8, 100));
     assert_eq!(dl.get_size_of(&types, i8_arr), 100);
 
@@ -165,6 +181,8 @@ fn align() {
     assert_eq!(dl.get_align_of(&types, types::I16), 2);
     assert_eq!(dl.get_align_of(&types, types::I32), 4);
     assert_eq!(dl.get_align_of(&types, types::I64), 8);
+    assert_eq!(dl.get_align_of(&types, types::FLOAT), 4);
+    assert_eq!(dl.get_align_of(&types, types::DOUBLE), 8);
 
     let i8_ptr = types.base_mut().pointer(types::I8);
     assert_eq!(dl.get_align_of(&types, i8_ptr), 8);
@@ -130,16 +130,25 @@ impl Target {
     }
 }
 
-impl<'a> TryFrom<&'a str> for Module {
-    type Error = crate::parser::assembly::Error<'a>;
+impl TryFrom<&str> for Module {
+    type Error = crate::parser::assembly::ParseError;
 
     /// Parses an LLVM Assembly string.
-    fn try_from(s: &'a str) -> Result<Self, Self::Error> {
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
         use crate::parser::assembly::module::parse;
         parse(s)
     }
 }
 
+impl TryFrom<&[u8]> for Module {
+    type Error = crate::parser::bitcode::Error;
+
+    /// Parses an LLVM bitcode file.
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        crate::parser::bitcode::parse(bytes)
+    }
+}
+
 impl fmt::Debug for Module {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "source_filename = \"{}\"", self.source_filename)?;
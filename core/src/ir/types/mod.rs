@@ -19,6 +19,11 @@ pub const I8: Type = Type(0, 2);
 pub const I16: Type = Type(0, 3);
 pub const I32: Type = Type(0, 4);
 pub const I64: Type = Type(0, 5);
+pub const HALF: Type = Type(0, 6);
+pub const FLOAT: Type = Type(0, 7);
+pub const DOUBLE: Type = Type(0, 8);
+pub const FP128: Type = Type(0, 9);
+pub const X86_FP80: Type = Type(0, 10);
 
 /// Represents a typed value.
 pub trait Typed {
@@ -38,6 +43,7 @@ pub struct TypesBase {
 #[derive(Debug)]
 struct Caches {
     pointer: Cache<PointerType>,
+    opaque_pointer: Cache<AddrSpace>,
     array: Cache<ArrayType>,
     named_struct: Cache<String>,
     named_types: Cache<Name>,
@@ -47,6 +53,7 @@ struct Caches {
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum CompoundType {
     Pointer(PointerType),
+    OpaquePointer(AddrSpace),
     Array(ArrayType),
     Function(FunctionType),
     Struct(StructType),
@@ -127,6 +134,10 @@ impl Types {
         self.base().is_pointer(ty)
     }
 
+    pub fn is_opaque_pointer(&self, ty: Type) -> bool {
+        self.base().is_opaque_pointer(ty)
+    }
+
     pub fn is_array(&self, ty: Type) -> bool {
         self.base().is_array(ty)
     }
@@ -155,6 +166,7 @@ impl Default for TypesBase {
             caches: Caches {
                 metadata: Type(arena_id, 0),
                 pointer: Cache::default(),
+                opaque_pointer: Cache::default(),
                 array: Cache::default(),
                 named_struct: Cache::default(),
                 named_types: Cache::default(),
@@ -183,7 +195,14 @@ impl TypesBase {
     }
 
     pub fn is_pointer(&self, ty: Type) -> bool {
-        matches!(self.get(ty), Some(CompoundType::Pointer(_)))
+        matches!(
+            self.get(ty),
+            Some(CompoundType::Pointer(_) | CompoundType::OpaquePointer(_))
+        )
+    }
+
+    pub fn is_opaque_pointer(&self, ty: Type) -> bool {
+        matches!(self.get(ty), Some(CompoundType::OpaquePointer(_)))
     }
 
     pub fn is_array(&self, ty: Type) -> bool {
@@ -220,6 +239,16 @@ impl TypesBase {
         ty
     }
 
+    /// Returns `ptr` (or `ptr addrspace(N)`), the pointer type that doesn't know its pointee type.
+    pub fn opaque_pointer(&mut self, addr_space: AddrSpace) -> Type {
+        if let Some(ty) = self.caches.opaque_pointer.get(&addr_space) {
+            return *ty;
+        }
+        let ty = self.new_type(CompoundType::OpaquePointer(addr_space));
+        self.caches.opaque_pointer.insert(addr_space, ty);
+        ty
+    }
+
     pub fn array(&mut self, t: ArrayType) -> Type {
         if let Some(ty) = self.caches.array.get(&t) {
             return *ty;
@@ -292,6 +321,7 @@ impl TypesBase {
     pub fn element(&self, ty: Type) -> Option<Type> {
         match self.get(ty)? {
             CompoundType::Pointer(PointerType { inner, .. }) => Some(*inner),
+            CompoundType::OpaquePointer(_) => None,
             CompoundType::Array(ArrayType { inner, .. }) => Some(*inner),
             CompoundType::Struct(_) => None,
             CompoundType::Function(_) => None,
@@ -303,6 +333,7 @@ impl TypesBase {
     pub fn element_at(&self, ty: Type, i: usize) -> Option<Type> {
         match self.get(ty)? {
             CompoundType::Pointer(PointerType { inner, .. }) => Some(*inner),
+            CompoundType::OpaquePointer(_) => None,
             CompoundType::Array(ArrayType { inner, .. }) => Some(*inner),
             CompoundType::Struct(StructType { elems, .. }) => elems.get(i).copied(),
             CompoundType::Function(_) => None,
@@ -331,6 +362,11 @@ impl TypesBase {
                 I16 => "i16".to_string(),
                 I32 => "i32".to_string(),
                 I64 => "i64".to_string(),
+                HALF => "half".to_string(),
+                FLOAT => "float".to_string(),
+                DOUBLE => "double".to_string(),
+                FP128 => "fp128".to_string(),
+                X86_FP80 => "x86_fp80".to_string(),
                 _ => todo!(),
             };
         }
@@ -343,6 +379,8 @@ impl TypesBase {
             CompoundType::Pointer(PointerType { inner, addr_space }) => {
                 format!("{} addrspace({})*", self.to_string(*inner), addr_space)
             }
+            CompoundType::OpaquePointer(0) => "ptr".to_string(),
+            CompoundType::OpaquePointer(addr_space) => format!("ptr addrspace({})", addr_space),
             CompoundType::Array(ArrayType {
                 inner,
                 num_elements,
@@ -438,10 +476,43 @@ impl Type {
         self.is_i1() || self.is_i8() || self.is_i16() || self.is_i32() || self.is_i64()
     }
 
+    pub fn is_half(&self) -> bool {
+        self == &HALF
+    }
+
+    pub fn is_float(&self) -> bool {
+        self == &FLOAT
+    }
+
+    pub fn is_double(&self) -> bool {
+        self == &DOUBLE
+    }
+
+    pub fn is_fp128(&self) -> bool {
+        self == &FP128
+    }
+
+    pub fn is_x86_fp80(&self) -> bool {
+        self == &X86_FP80
+    }
+
+    /// Returns true if `self` is one of the floating-point types.
+    pub fn is_floating_point(&self) -> bool {
+        self.is_half()
+            || self.is_float()
+            || self.is_double()
+            || self.is_fp128()
+            || self.is_x86_fp80()
+    }
+
     pub fn is_pointer(&self, types: &Types) -> bool {
         types.is_pointer(*self)
     }
 
+    pub fn is_opaque_pointer(&self, types: &Types) -> bool {
+        types.is_opaque_pointer(*self)
+    }
+
     pub fn is_array(&self, types: &Types) -> bool {
         types.is_array(*self)
     }
@@ -529,3 +600,18 @@ fn types_identity() {
 
     assert_eq!(i32_ptr_ty, i32_ptr_ty2);
 }
+
+#[test]
+fn opaque_pointer_identity() {
+    let types = Types::new();
+    let ptr = types.base_mut().opaque_pointer(0);
+    let ptr2 = types.base_mut().opaque_pointer(0);
+    let ptr_as1 = types.base_mut().opaque_pointer(1);
+    assert_eq!(ptr, ptr2);
+    assert_ne!(ptr, ptr_as1);
+    assert!(ptr.is_pointer(&types));
+    assert!(ptr.is_opaque_pointer(&types));
+    assert_eq!(types.get_element(ptr), None);
+    assert_eq!(types.to_string(ptr), "ptr");
+    assert_eq!(types.to_string(ptr_as1), "ptr addrspace(1)");
+}
@@ -10,6 +10,7 @@ pub enum ConstantValue {
     AggregateZero(Type),
     Null(Type),
     Int(ConstantInt),
+    Float(ConstantFloat),
     Array(ConstantArray),
     Struct(ConstantStruct),
     Expr(ConstantExpr), // TODO: Boxing?
@@ -20,10 +21,23 @@ pub enum ConstantValue {
 pub enum ConstantInt {
     Int1(bool),
     Int8(i8),
+    Int16(i16),
     Int32(i32),
     Int64(i64),
 }
 
+/// A floating-point constant.
+///
+/// `Half`, `Fp128` and `X86Fp80` have no native Rust counterpart, so their bit patterns are kept as is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConstantFloat {
+    Half(u16),
+    Float(f32),
+    Double(f64),
+    Fp128(u128),
+    X86Fp80(u128), // lower 80 bits are used
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct ConstantArray {
     pub ty: Type,
@@ -60,6 +74,7 @@ impl ConstantValue {
             Self::AggregateZero(_) => "zeroinitializer".to_string(),
             Self::Null(_) => "null".to_string(),
             Self::Int(i) => i.to_string(),
+            Self::Float(f) => f.to_string(),
             Self::Array(a) => a.to_string(types),
             Self::Struct(s) => s.to_string(types),
             Self::Expr(e) => e.to_string(types),
@@ -74,6 +89,13 @@ impl ConstantValue {
         }
     }
 
+    pub fn as_float(&self) -> Option<&ConstantFloat> {
+        match self {
+            Self::Float(f) => Some(f),
+            _ => None,
+        }
+    }
+
     pub fn as_global_ref(&self) -> &Name {
         match self {
             Self::GlobalRef(name, _) => name,
@@ -108,6 +130,7 @@ impl ConstantInt {
         match self {
             Self::Int1(i) => i as *const bool as *const i8,
             Self::Int8(i) => i,
+            Self::Int16(i) => i as *const i16 as *const i8,
             Self::Int32(i) => i as *const i32 as *const i8,
             Self::Int64(i) => i as *const i64 as *const i8,
         }
@@ -118,6 +141,7 @@ impl ConstantInt {
         match self {
             Self::Int1(i) => i as usize,
             Self::Int8(i) => i as usize,
+            Self::Int16(i) => i as usize,
             Self::Int32(i) => i as usize,
             Self::Int64(i) => i as usize,
         }
@@ -127,6 +151,7 @@ impl ConstantInt {
         match self {
             Self::Int1(i) => i as i64,
             Self::Int8(i) => i as i64,
+            Self::Int16(i) => i as i64,
             Self::Int32(i) => i as i64,
             Self::Int64(i) => i as i64,
         }
@@ -136,12 +161,43 @@ impl ConstantInt {
         match self {
             Self::Int1(i) => !(*i),
             Self::Int8(i) => *i == 0,
+            Self::Int16(i) => *i == 0,
             Self::Int32(i) => *i == 0,
             Self::Int64(i) => *i == 0,
         }
     }
 }
 
+impl ConstantFloat {
+    /// Returns the value as `f64` if it is exactly representable.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Float(f) => Some(*f as f64),
+            Self::Double(f) => Some(*f),
+            Self::Half(_) | Self::Fp128(_) | Self::X86Fp80(_) => None,
+        }
+    }
+
+    pub fn as_ptr(&self) -> *const i8 {
+        match self {
+            Self::Half(i) => i as *const u16 as *const i8,
+            Self::Float(f) => f as *const f32 as *const i8,
+            Self::Double(f) => f as *const f64 as *const i8,
+            Self::Fp128(i) | Self::X86Fp80(i) => i as *const u128 as *const i8,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Self::Half(i) => *i & 0x7fff == 0,
+            Self::Float(f) => *f == 0.0,
+            Self::Double(f) => *f == 0.0,
+            Self::Fp128(i) => *i & !(1 << 127) == 0,
+            Self::X86Fp80(i) => *i & !(1 << 79) == 0,
+        }
+    }
+}
+
 impl ConstantArray {
     pub fn to_string(&self, types: &Types) -> String {
         if self.is_string {
@@ -234,6 +290,7 @@ impl Typed for ConstantValue {
             Self::AggregateZero(ty) => *ty,
             Self::Null(ty) => *ty,
             Self::Int(i) => i.ty(),
+            Self::Float(f) => f.ty(),
             Self::Array(a) => a.ty(),
             Self::Struct(s) => s.ty(),
             Self::Expr(e) => e.ty(),
@@ -247,12 +304,25 @@ impl Typed for ConstantInt {
         match self {
             Self::Int1(_) => types::I1,
             Self::Int8(_) => types::I8,
+            Self::Int16(_) => types::I16,
             Self::Int32(_) => types::I32,
             Self::Int64(_) => types::I64,
         }
     }
 }
 
+impl Typed for ConstantFloat {
+    fn ty(&self) -> Type {
+        match self {
+            Self::Half(_) => types::HALF,
+            Self::Float(_) => types::FLOAT,
+            Self::Double(_) => types::DOUBLE,
+            Self::Fp128(_) => types::FP128,
+            Self::X86Fp80(_) => types::X86_FP80,
+        }
+    }
+}
+
 impl Typed for ConstantArray {
     fn ty(&self) -> Type {
         self.ty
@@ -286,6 +356,24 @@ impl From<ConstantInt> for super::Value {
     }
 }
 
+impl From<ConstantFloat> for ConstantValue {
+    fn from(f: ConstantFloat) -> Self {
+        Self::Float(f)
+    }
+}
+
+impl From<ConstantFloat> for super::Value {
+    fn from(f: ConstantFloat) -> Self {
+        Self::Constant(f.into())
+    }
+}
+
+impl From<f64> for ConstantValue {
+    fn from(f: f64) -> Self {
+        Self::Float(ConstantFloat::Double(f))
+    }
+}
+
 impl From<i32> for ConstantValue {
     fn from(i: i32) -> Self {
         Self::Int(i.into())
@@ -303,8 +391,68 @@ impl std::fmt::Display for ConstantInt {
         match self {
             Self::Int1(i) => write!(f, "{}", i),
             Self::Int8(i) => write!(f, "{}", i),
+            Self::Int16(i) => write!(f, "{}", i),
             Self::Int32(i) => write!(f, "{}", i),
             Self::Int64(i) => write!(f, "{}", i),
         }
     }
 }
+
+impl std::fmt::Display for ConstantFloat {
+    /// Follows the way LLVM prints floating-point constants: a decimal literal is used only if it
+    /// is parsed back to exactly the same value. Otherwise, a hexadecimal literal is used.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Half(i) => write!(f, "0xH{:04X}", i),
+            Self::Float(x) => write_f64(f, *x as f64),
+            Self::Double(x) => write_f64(f, *x),
+            Self::Fp128(i) => write!(f, "0xL{:016X}{:016X}", *i as u64, (*i >> 64) as u64),
+            Self::X86Fp80(i) => write!(f, "0xK{:04X}{:016X}", (*i >> 64) as u16, *i as u64),
+        }
+    }
+}
+
+fn write_f64(f: &mut std::fmt::Formatter<'_>, x: f64) -> std::fmt::Result {
+    if x.is_finite() {
+        // e.g. 1.000000e+00
+        let s = format!("{:.6e}", x);
+        let (mantissa, exp) = s.split_once('e').unwrap();
+        let exp = exp.parse::<i32>().unwrap();
+        let s = format!(
+            "{}e{}{:02}",
+            mantissa,
+            if exp < 0 { '-' } else { '+' },
+            exp.abs()
+        );
+        if s.parse::<f64>() == Ok(x) {
+            return write!(f, "{}", s);
+        }
+    }
+    write!(f, "0x{:016X}", x.to_bits())
+}
+
+#[test]
+fn print_float() {
+    assert_eq!(ConstantFloat::Double(1.0).to_string(), "1.000000e+00");
+    assert_eq!(ConstantFloat::Double(-2.5e-10).to_string(), "-2.500000e-10");
+    assert_eq!(ConstantFloat::Double(0.1).to_string(), "1.000000e-01");
+    assert_eq!(
+        ConstantFloat::Double(std::f64::consts::PI).to_string(),
+        "0x400921FB54442D18"
+    );
+    assert_eq!(ConstantFloat::Float(0.5).to_string(), "5.000000e-01");
+    assert_eq!(ConstantFloat::Float(0.1).to_string(), "0x3FB99999A0000000");
+    assert_eq!(
+        ConstantFloat::Double(f64::INFINITY).to_string(),
+        "0x7FF0000000000000"
+    );
+    assert_eq!(ConstantFloat::Half(0x3c00).to_string(), "0xH3C00");
+    assert_eq!(
+        ConstantFloat::X86Fp80(0x4000_C000000000000000).to_string(),
+        "0xK4000C000000000000000"
+    );
+    assert_eq!(
+        ConstantFloat::Fp128(0x4000_0000_0000_0000_0000_0000_0000_0000).to_string(),
+        "0xL00000000000000004000000000000000"
+    );
+}
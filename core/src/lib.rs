@@ -4,6 +4,7 @@ pub mod ir;
 pub mod parser;
 pub mod pass;
 pub mod traits;
+pub mod writer;
 
 extern crate anyhow;
 extern crate nom;
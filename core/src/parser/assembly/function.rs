@@ -101,6 +101,15 @@ pub fn parse_body<'a, 'b>(
     source: &'a str,
     ctx: &mut ParserContext<'b>,
     num_args: usize,
+) -> Result<(&'a str, ()), Error<'a>> {
+    parse_body_with(source, ctx, num_args, None)
+}
+
+fn parse_body_with<'a, 'b>(
+    source: &'a str,
+    ctx: &mut ParserContext<'b>,
+    num_args: usize,
+    mut errors: Option<&mut Vec<Error<'a>>>,
 ) -> Result<(&'a str, ()), Error<'a>> {
     let (source, _) = tuple((spaces, char('{')))(source)?;
 
@@ -121,31 +130,86 @@ pub fn parse_body<'a, 'b>(
         ctx.layout.append_block(block);
         ctx.cur_block = block;
 
-        while let Ok((source_, inst)) = super::instruction::parse(source, ctx) {
-            ctx.layout.append_inst(inst, ctx.cur_block);
-            source = source_
-        }
+        label = loop {
+            let inst_err = loop {
+                match super::instruction::parse(source, ctx) {
+                    Ok((source_, inst)) => {
+                        ctx.layout.append_inst(inst, ctx.cur_block);
+                        source = source_
+                    }
+                    Err(e) => break e,
+                }
+            };
 
-        if let Ok((source, _)) = tuple((spaces, char('}')))(source) {
-            ctx.set_blocks_info();
-            return Ok((source, ()));
-        }
+            if let Ok((source, _)) = tuple((spaces, char('}')))(source) {
+                ctx.set_blocks_info();
+                return Ok((source, ()));
+            }
 
-        // Parse label
-        if let Ok((source_, label_)) = preceded(
-            spaces,
-            terminated(super::name::parse, preceded(spaces, char(':'))),
-        )(source)
-        {
-            label = label_;
-            source = source_;
-            continue;
-        }
+            // Parse label
+            if let Ok((source_, label_)) = preceded(
+                spaces,
+                terminated(super::name::parse, preceded(spaces, char(':'))),
+            )(source)
+            {
+                source = source_;
+                break label_;
+            }
 
-        return Err(Error::Located(source, "Parse error: function body"));
+            let (rest, _) = spaces(source)?;
+            // An instruction that failed halfway tells more than the start of it does.
+            let err = match &inst_err {
+                nom::Err::Error(e) | nom::Err::Failure(e) if matches!(e.errors.first(), Some((r, _)) if r.len() < rest.len()) => {
+                    inst_err.into()
+                }
+                _ => Error::Located(rest, "instruction"),
+            };
+            if rest.is_empty() {
+                return Err(err);
+            }
+            // Skip the instruction, leaving the values it defines undefined.
+            source = super::recover(&mut errors, err, skip_instruction(rest))?;
+        };
     }
 }
 
+/// Returns `source` after the instruction at the start of it. An instruction may span several
+/// lines (e.g. `switch` with its cases), so this skips lines up to the next one that starts an
+/// instruction or a block label, or closes the function.
+fn skip_instruction(source: &str) -> &str {
+    let mut rest = super::skip_line(source);
+    while !rest.is_empty() && !starts_instruction(rest.trim_start()) {
+        rest = super::skip_line(rest);
+    }
+    rest
+}
+
+fn starts_instruction(source: &str) -> bool {
+    // Opcodes of instructions that may appear without a result.
+    const OPCODES: [&str; 12] = [
+        "store",
+        "call",
+        "tail",
+        "musttail",
+        "notail",
+        "invoke",
+        "resume",
+        "br",
+        "switch",
+        "ret",
+        "unreachable",
+        "fence",
+    ];
+    let line = source.split('\n').next().unwrap_or_default();
+    line.starts_with('}')
+        || tuple((char('%'), super::name::parse, spaces, char('=')))(line).is_ok()
+        || terminated(super::name::parse, preceded(spaces, char(':')))(line).is_ok()
+        || OPCODES.iter().any(|opcode| {
+            line.strip_prefix(opcode)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+        })
+}
+
 pub fn parse_personality<'a>(
     source: &'a str,
     types: &Types,
@@ -160,6 +224,24 @@ pub fn parse_personality<'a>(
 }
 
 pub fn parse(source: &str, types: Types) -> Result<(&str, Function), Error> {
+    parse_with(source, types, None)
+}
+
+/// Parses a function, skipping the instructions that fail to parse. Their errors are appended to
+/// `errors`.
+pub fn parse_recovering<'a>(
+    source: &'a str,
+    types: Types,
+    errors: &mut Vec<Error<'a>>,
+) -> Result<(&'a str, Function), Error<'a>> {
+    parse_with(source, types, Some(errors))
+}
+
+fn parse_with<'a>(
+    source: &'a str,
+    types: Types,
+    errors: Option<&mut Vec<Error<'a>>>,
+) -> Result<(&'a str, Function), Error<'a>> {
     let (source, define_or_declare) =
         preceded(spaces, alt((tag("define"), tag("declare"))))(source)?;
     let is_prototype = define_or_declare == "declare";
@@ -198,7 +280,7 @@ pub fn parse(source: &str, types: Types) -> Result<(&str, Function), Error> {
     }
 
     if !is_prototype {
-        source = parse_body(
+        source = parse_body_with(
             source,
             &mut ParserContext {
                 types: &types,
@@ -209,6 +291,7 @@ pub fn parse(source: &str, types: Types) -> Result<(&str, Function), Error> {
                 cur_block: dummy_block,
             },
             params.len(),
+            errors,
         )?
         .0;
     }
@@ -5,8 +5,9 @@ use super::param_attrs::parse_param_attrs;
 use super::util::{spaces, string_literal};
 use super::value::parse_constant;
 use crate::ir::function::instruction::{
-    Alloca, Br, Call, Cast, CondBr, GetElementPtr, ICmp, ICmpCond, Instruction, InstructionId,
-    IntBinary, Invoke, LandingPad, Load, Opcode, Operand, Phi, Resume, Ret, Store, Switch,
+    Alloca, Br, Call, Cast, CondBr, FCmp, FCmpCond, FastMathFlags, FloatBinary, FloatUnary,
+    GetElementPtr, ICmp, ICmpCond, Instruction, InstructionId, IntBinary, Invoke, LandingPad, Load,
+    Opcode, Operand, Phi, Resume, Ret, Select, Store, Switch,
 };
 use crate::ir::value::{ConstantValue, Value};
 use crate::ir::{
@@ -24,9 +25,9 @@ use nom::{
     bytes::complete::tag,
     character::complete::{char, digit1},
     combinator::{map, opt},
-    error::VerboseError,
+    error::{ContextError, VerboseError},
     sequence::{preceded, tuple},
-    Err::Error,
+    Err::{Error, Failure},
     IResult,
 };
 use rustc_hash::FxHashMap;
@@ -248,9 +249,12 @@ pub fn parse_add_sub_mul<'a, 'b>(
             map(tag("sub"), |_| Opcode::Sub),
             map(tag("mul"), |_| Opcode::Mul),
             map(tag("sdiv"), |_| Opcode::SDiv),
+            map(tag("udiv"), |_| Opcode::UDiv),
             map(tag("srem"), |_| Opcode::SRem),
+            map(tag("urem"), |_| Opcode::URem),
             map(tag("and"), |_| Opcode::And),
             map(tag("or"), |_| Opcode::Or),
+            map(tag("xor"), |_| Opcode::Xor),
             map(tag("shl"), |_| Opcode::Shl),
             map(tag("ashr"), |_| Opcode::AShr),
             map(tag("lshr"), |_| Opcode::LShr),
@@ -277,6 +281,82 @@ pub fn parse_add_sub_mul<'a, 'b>(
     Ok((source, inst))
 }
 
+pub fn parse_fast_math_flags(mut source: &str) -> IResult<&str, FastMathFlags, VerboseError<&str>> {
+    let mut fmf = FastMathFlags::default();
+    loop {
+        let (source_, flag) = opt(preceded(
+            spaces,
+            alt((
+                tag("nnan"),
+                tag("ninf"),
+                tag("nsz"),
+                tag("arcp"),
+                tag("contract"),
+                tag("afn"),
+                tag("reassoc"),
+                tag("fast"),
+            )),
+        ))(source)?;
+        match flag {
+            Some("nnan") => fmf.nnan = true,
+            Some("ninf") => fmf.ninf = true,
+            Some("nsz") => fmf.nsz = true,
+            Some("arcp") => fmf.arcp = true,
+            Some("contract") => fmf.contract = true,
+            Some("afn") => fmf.afn = true,
+            Some("reassoc") => fmf.reassoc = true,
+            Some(_) => fmf = FastMathFlags::fast(),
+            None => return Ok((source, fmf)),
+        }
+        source = source_;
+    }
+}
+
+pub fn parse_float_binary<'a, 'b>(
+    source: &'a str,
+    ctx: &mut ParserContext<'b>,
+) -> IResult<&'a str, Instruction, VerboseError<&'a str>> {
+    let (source, opcode) = preceded(
+        spaces,
+        alt((
+            map(tag("fadd"), |_| Opcode::FAdd),
+            map(tag("fsub"), |_| Opcode::FSub),
+            map(tag("fmul"), |_| Opcode::FMul),
+            map(tag("fdiv"), |_| Opcode::FDiv),
+            map(tag("frem"), |_| Opcode::FRem),
+        )),
+    )(source)?;
+    let (source, fmf) = parse_fast_math_flags(source)?;
+    let (source, ty) = super::types::parse(ctx.types)(source)?;
+    let (source, lhs) = super::value::parse(source, ctx, ty)?;
+    let (source, _) = preceded(spaces, char(','))(source)?;
+    let (source, rhs) = super::value::parse(source, ctx, ty)?;
+    let inst = opcode
+        .with_block(ctx.cur_block)
+        .with_operand(Operand::FloatBinary(FloatBinary {
+            ty,
+            fmf,
+            args: [lhs, rhs],
+        }))
+        .with_ty(ty);
+    Ok((source, inst))
+}
+
+pub fn parse_fneg<'a, 'b>(
+    source: &'a str,
+    ctx: &mut ParserContext<'b>,
+) -> IResult<&'a str, Instruction, VerboseError<&'a str>> {
+    let (source, _) = preceded(spaces, tag("fneg"))(source)?;
+    let (source, fmf) = parse_fast_math_flags(source)?;
+    let (source, ty) = super::types::parse(ctx.types)(source)?;
+    let (source, arg) = super::value::parse(source, ctx, ty)?;
+    let inst = Opcode::FNeg
+        .with_block(ctx.cur_block)
+        .with_operand(Operand::FloatUnary(FloatUnary { ty, fmf, arg }))
+        .with_ty(ty);
+    Ok((source, inst))
+}
+
 pub fn parse_icmp<'a, 'b>(
     source: &'a str,
     ctx: &mut ParserContext<'b>,
@@ -313,6 +393,50 @@ pub fn parse_icmp<'a, 'b>(
     Ok((source, inst))
 }
 
+pub fn parse_fcmp<'a, 'b>(
+    source: &'a str,
+    ctx: &mut ParserContext<'b>,
+) -> IResult<&'a str, Instruction, VerboseError<&'a str>> {
+    pub fn fcmp_cond(source: &str) -> IResult<&str, FCmpCond, VerboseError<&str>> {
+        alt((
+            map(tag("false"), |_| FCmpCond::False),
+            map(tag("oeq"), |_| FCmpCond::Oeq),
+            map(tag("ogt"), |_| FCmpCond::Ogt),
+            map(tag("oge"), |_| FCmpCond::Oge),
+            map(tag("olt"), |_| FCmpCond::Olt),
+            map(tag("ole"), |_| FCmpCond::Ole),
+            map(tag("one"), |_| FCmpCond::One),
+            map(tag("ord"), |_| FCmpCond::Ord),
+            map(tag("ueq"), |_| FCmpCond::Ueq),
+            map(tag("ugt"), |_| FCmpCond::Ugt),
+            map(tag("uge"), |_| FCmpCond::Uge),
+            map(tag("ult"), |_| FCmpCond::Ult),
+            map(tag("ule"), |_| FCmpCond::Ule),
+            map(tag("une"), |_| FCmpCond::Une),
+            map(tag("uno"), |_| FCmpCond::Uno),
+            map(tag("true"), |_| FCmpCond::True),
+        ))(source)
+    }
+
+    let (source, _) = preceded(spaces, tag("fcmp"))(source)?;
+    let (source, fmf) = parse_fast_math_flags(source)?;
+    let (source, cond) = preceded(spaces, fcmp_cond)(source)?;
+    let (source, ty) = super::types::parse(ctx.types)(source)?;
+    let (source, lhs) = super::value::parse(source, ctx, ty)?;
+    let (source, _) = preceded(spaces, char(','))(source)?;
+    let (source, rhs) = super::value::parse(source, ctx, ty)?;
+    let inst = Opcode::FCmp
+        .with_block(ctx.cur_block)
+        .with_operand(Operand::FCmp(FCmp {
+            ty,
+            fmf,
+            args: [lhs, rhs],
+            cond,
+        }))
+        .with_ty(types::I1);
+    Ok((source, inst))
+}
+
 pub fn parse_cast<'a, 'b>(
     source: &'a str,
     ctx: &mut ParserContext<'b>,
@@ -326,6 +450,12 @@ pub fn parse_cast<'a, 'b>(
             map(tag("trunc"), |_| Opcode::Trunc),
             map(tag("inttoptr"), |_| Opcode::IntToPtr),
             map(tag("ptrtoint"), |_| Opcode::PtrToInt),
+            map(tag("fptrunc"), |_| Opcode::FPTrunc),
+            map(tag("fpext"), |_| Opcode::FPExt),
+            map(tag("fptoui"), |_| Opcode::FPToUI),
+            map(tag("fptosi"), |_| Opcode::FPToSI),
+            map(tag("uitofp"), |_| Opcode::UIToFP),
+            map(tag("sitofp"), |_| Opcode::SIToFP),
         )),
     )(source)?;
     let (source, from) = super::types::parse(ctx.types)(source)?;
@@ -342,6 +472,29 @@ pub fn parse_cast<'a, 'b>(
     Ok((source, inst))
 }
 
+pub fn parse_select<'a, 'b>(
+    source: &'a str,
+    ctx: &mut ParserContext<'b>,
+) -> IResult<&'a str, Instruction, VerboseError<&'a str>> {
+    let (source, _) = preceded(spaces, tag("select"))(source)?;
+    let (source, cond_ty) = super::types::parse(ctx.types)(source)?;
+    let (source, cond) = super::value::parse(source, ctx, cond_ty)?;
+    let (source, _) = preceded(spaces, char(','))(source)?;
+    let (source, ty) = super::types::parse(ctx.types)(source)?;
+    let (source, then_val) = super::value::parse(source, ctx, ty)?;
+    let (source, _) = preceded(spaces, char(','))(source)?;
+    let (source, _) = super::types::parse(ctx.types)(source)?;
+    let (source, else_val) = super::value::parse(source, ctx, ty)?;
+    let inst = Opcode::Select
+        .with_block(ctx.cur_block)
+        .with_operand(Operand::Select(Select {
+            tys: [cond_ty, ty],
+            args: [cond, then_val, else_val],
+        }))
+        .with_ty(ty);
+    Ok((source, inst))
+}
+
 type CallArguments = (
     Vec<types::Type>,
     Vec<Vec<ParameterAttribute>>,
@@ -400,7 +553,10 @@ pub fn parse_getelementptr<'a, 'b>(
         }
         let inst = Opcode::GetElementPtr
             .with_block(ctx.cur_block)
-            .with_ty({
+            .with_ty(if ctx.types.is_opaque_pointer(tys[1]) {
+                // GEP on an opaque pointer results in the opaque pointer.
+                tys[1]
+            } else {
                 let inner = ctx
                     .types
                     .base()
@@ -740,6 +896,7 @@ pub fn parse<'a, 'b>(
         char('='),
     )))(source)?;
     let name = name.map(|(_, _, name, _, _)| name);
+    let mut deepest = None;
     for f in [
         parse_alloca,
         parse_phi,
@@ -748,8 +905,12 @@ pub fn parse<'a, 'b>(
         parse_insertvalue,
         parse_extractvalue,
         parse_add_sub_mul,
+        parse_float_binary,
+        parse_fneg,
         parse_icmp,
+        parse_fcmp,
         parse_cast,
+        parse_select,
         parse_getelementptr,
         parse_call,
         parse_invoke,
@@ -762,28 +923,42 @@ pub fn parse<'a, 'b>(
     ]
     .iter()
     {
-        if let Ok((source, mut inst)) = f(source, ctx) {
-            let (source, metadata) = parse_metadata_if_any(ctx.types)(source)?;
-            inst = inst.with_metadata(metadata);
-
-            if let Some(name) = name {
-                if let Some(inner) = ctx.name_to_value.get(&name) {
-                    if let value::Value::Instruction(id) = ctx.data.values[*inner] {
-                        ctx.data.replace_inst(id, inst.with_dest(name));
-                        return Ok((source, id));
-                    }
+        let (source, mut inst) = match f(source, ctx) {
+            Ok(ok) => ok,
+            Err(Error(e) | Failure(e)) => {
+                // Keep the error of the instruction that got furthest, which is likely the one
+                // written in the source.
+                let depth = |e: &VerboseError<&str>| {
+                    e.errors.first().map_or(usize::MAX, |(rest, _)| rest.len())
+                };
+                if deepest.as_ref().map_or(true, |d| depth(&e) < depth(d)) {
+                    deepest = Some(e);
+                }
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let (source, metadata) = parse_metadata_if_any(ctx.types)(source)?;
+        inst = inst.with_metadata(metadata);
+
+        if let Some(name) = name {
+            if let Some(inner) = ctx.name_to_value.get(&name) {
+                if let value::Value::Instruction(id) = ctx.data.values[*inner] {
+                    ctx.data.replace_inst(id, inst.with_dest(name));
+                    return Ok((source, id));
                 }
-
-                let id = ctx.data.create_inst(inst.with_dest(name.clone()));
-                ctx.name_to_value
-                    .insert(name, ctx.data.create_value(value::Value::Instruction(id)));
-                return Ok((source, id));
             }
 
-            return Ok((source, ctx.data.create_inst(inst)));
+            let id = ctx.data.create_inst(inst.with_dest(name.clone()));
+            ctx.name_to_value
+                .insert(name, ctx.data.create_value(value::Value::Instruction(id)));
+            return Ok((source, id));
         }
+
+        return Ok((source, ctx.data.create_inst(inst)));
     }
-    Err(Error(VerboseError { errors: vec![] }))
+    let e = deepest.unwrap_or(VerboseError { errors: vec![] });
+    Err(Error(VerboseError::add_context(source, "instruction", e)))
 }
 
 fn val2idx(val: &Value) -> usize {
@@ -14,7 +14,7 @@ pub mod util;
 pub mod value;
 pub mod visibility;
 
-use nom::error::VerboseError;
+use nom::error::{VerboseError, VerboseErrorKind};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -25,8 +25,143 @@ pub enum Error<'a> {
     Nom(nom::Err<VerboseError<&'a str>>),
 }
 
+/// A parsing error located in the source, which doesn't borrow the source.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Parsing error at {line}:{column}: expected {expected}\n{snippet}")]
+pub struct ParseError {
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column number, counted in characters.
+    pub column: usize,
+    /// What the parser expected at the location (e.g. "instruction", "type").
+    pub expected: String,
+    /// The line at the location with a caret pointing to the column.
+    pub snippet: String,
+}
+
 impl<'a> From<nom::Err<VerboseError<&'a str>>> for Error<'a> {
     fn from(err: nom::Err<VerboseError<&'a str>>) -> Self {
         Error::Nom(err)
     }
 }
+
+impl<'a> Error<'a> {
+    /// Locates `self` in `source`, which is the whole input given to the parser.
+    pub fn into_parse_error(self, source: &str) -> ParseError {
+        let (rest, expected) = match self {
+            Error::Located(rest, expected) => (rest, expected.to_string()),
+            Error::Nom(nom::Err::Incomplete(_)) => ("", "more input".to_string()),
+            Error::Nom(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+                // The innermost error comes first.
+                let rest = e.errors.first().map_or(source, |(rest, _)| *rest);
+                let expected = e
+                    .errors
+                    .iter()
+                    .find_map(|(_, kind)| match kind {
+                        VerboseErrorKind::Context(ctx) => Some(ctx.to_string()),
+                        _ => None,
+                    })
+                    .or_else(|| {
+                        e.errors.first().map(|(_, kind)| match kind {
+                            VerboseErrorKind::Char(c) => format!("'{}'", c),
+                            VerboseErrorKind::Context(ctx) => ctx.to_string(),
+                            VerboseErrorKind::Nom(kind) => kind.description().to_lowercase(),
+                        })
+                    })
+                    .unwrap_or_else(|| "valid input".to_string());
+                (rest, expected)
+            }
+        };
+        ParseError::new(source, rest, expected)
+    }
+}
+
+/// Records `err` and returns `rest` to continue parsing from, if recovering from errors (i.e.
+/// `errors` is `Some`). Otherwise, returns `err`.
+fn recover<'a>(
+    errors: &mut Option<&mut Vec<Error<'a>>>,
+    err: Error<'a>,
+    rest: &'a str,
+) -> Result<&'a str, Error<'a>> {
+    match errors {
+        Some(errors) => {
+            errors.push(err);
+            Ok(rest)
+        }
+        None => Err(err),
+    }
+}
+
+/// Returns `source` after the current line.
+fn skip_line(source: &str) -> &str {
+    source.find('\n').map_or("", |i| &source[i + 1..])
+}
+
+impl ParseError {
+    /// Creates an error at the start of `rest`, which is a suffix of `source`.
+    fn new(source: &str, rest: &str, expected: String) -> Self {
+        // Point at the first non-whitespace character, since parsers skip spaces by themselves.
+        let offset = source.len() - rest.trim_start().len();
+        let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[offset..]
+            .find('\n')
+            .map_or(source.len(), |i| offset + i);
+        let line = source[..offset].matches('\n').count() + 1;
+        let column = source[line_start..offset].chars().count() + 1;
+
+        // Keep tabs so that the caret lines up with the text.
+        let padding: String = source[line_start..offset]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let text = source[line_start..line_end].trim_end_matches('\r');
+        let number = line.to_string();
+        let margin = " ".repeat(number.len());
+        let snippet = format!("{margin} |\n{number} | {text}\n{margin} | {padding}^");
+
+        Self {
+            line,
+            column,
+            expected,
+            snippet,
+        }
+    }
+}
+
+#[test]
+fn parse_error_location() {
+    use crate::ir::module::Module;
+
+    let source = "define i32 @main() {\n  %1 = alloca i33, align 4\n  ret i32 0\n}\n";
+    let err = Module::try_from(source).unwrap_err();
+    assert_eq!((err.line, err.column), (2, 15));
+    assert_eq!(err.expected, "type");
+    assert_eq!(
+        err.snippet,
+        "  |\n2 |   %1 = alloca i33, align 4\n  |               ^"
+    );
+}
+
+#[test]
+fn parse_error_unknown_instruction() {
+    use crate::ir::module::Module;
+
+    let source = "define i32 @main() {\n\t%1 = frob i32 1, 2\n  ret i32 %1\n}\n";
+    let err = Module::try_from(source).unwrap_err();
+    assert_eq!((err.line, err.column), (2, 7));
+    assert_eq!(err.expected, "instruction");
+    assert!(err.snippet.ends_with("\n  | \t     ^"));
+}
+
+#[test]
+fn parse_error_top_level() {
+    use crate::ir::module::Module;
+
+    let err = Module::try_from("source_filename = \"a\"\nhello\n").unwrap_err();
+    assert_eq!((err.line, err.column), (2, 1));
+    assert_eq!(err.expected, "top-level entity");
+    assert_eq!(
+        err.to_string(),
+        "Parsing error at 2:1: expected top-level entity\n  |\n2 | hello\n  | ^"
+    );
+}
@@ -1,6 +1,6 @@
 use super::{
     util::{spaces, string_literal},
-    Error,
+    Error, ParseError,
 };
 use crate::ir::{
     module::{attributes::Attribute, Module},
@@ -68,8 +68,37 @@ fn parse_local_type<'a>(
     Ok((source, ()))
 }
 
-pub fn parse(mut source: &str) -> Result<Module, Error> {
+/// Parses an LLVM Assembly string into a module.
+pub fn parse(source: &str) -> Result<Module, ParseError> {
     let mut module = Module::new();
+    parse_module(source, &mut module, None).map_err(|e| e.into_parse_error(source))?;
+    Ok(module)
+}
+
+/// Parses an LLVM Assembly string, skipping instructions, global variables, functions and other
+/// top-level entities that fail to parse. Returns the module made of the rest, and the errors of
+/// everything skipped in the order of appearance.
+///
+/// Values defined by skipped instructions are left undefined, so the module is only good for
+/// inspection.
+pub fn parse_recovering(source: &str) -> (Module, Vec<ParseError>) {
+    let mut module = Module::new();
+    let mut errors = vec![];
+    if let Err(e) = parse_module(source, &mut module, Some(&mut errors)) {
+        errors.push(e)
+    }
+    let errors = errors
+        .into_iter()
+        .map(|e| e.into_parse_error(source))
+        .collect();
+    (module, errors)
+}
+
+fn parse_module<'a>(
+    mut source: &'a str,
+    module: &mut Module,
+    mut errors: Option<&mut Vec<Error<'a>>>,
+) -> Result<(), Error<'a>> {
     loop {
         source = spaces(source)?.0;
 
@@ -112,13 +141,21 @@ pub fn parse(mut source: &str) -> Result<Module, Error> {
             continue;
         }
 
-        match super::function::parse(source, module.types.clone()) {
+        let func = match &mut errors {
+            Some(errors) => super::function::parse_recovering(source, module.types.clone(), errors),
+            None => super::function::parse(source, module.types.clone()),
+        };
+        match func {
             Ok((source_, func)) => {
                 module.functions.alloc(func);
                 source = source_;
                 continue;
             }
-            Err(Error::Located(s, e)) => return Err(Error::Located(s, e)),
+            // Report errors in function headers rather than failing at the start of the function.
+            Err(e) if matches!(e, Error::Located(..)) || is_function(source) => {
+                source = super::recover(&mut errors, e, skip_function(source))?;
+                continue;
+            }
             Err(_) => {}
         }
 
@@ -128,10 +165,25 @@ pub fn parse(mut source: &str) -> Result<Module, Error> {
             continue;
         }
 
-        return Err(Error::Located(source, "Parse error: module body"));
+        let err = Error::Located(source, "top-level entity");
+        source = super::recover(&mut errors, err, super::skip_line(source))?;
     }
 
-    Ok(module)
+    Ok(())
+}
+
+fn is_function(source: &str) -> bool {
+    source.starts_with("define") || source.starts_with("declare")
+}
+
+/// Returns `source` after the function at the start of it.
+fn skip_function(source: &str) -> &str {
+    if source.starts_with("declare") {
+        return super::skip_line(source);
+    }
+    source
+        .find("\n}")
+        .map_or("", |i| super::skip_line(&source[i + 1..]))
 }
 
 macro_rules! generate_test {
@@ -160,6 +212,7 @@ generate_test!(parse_example_cast, "cast.ll");
 generate_test!(parse_example_cgep, "cgep.ll");
 generate_test!(parse_example_cgep2, "cgep2.ll");
 generate_test!(parse_example_dce, "dce.ll");
+generate_test!(parse_example_float, "float.ll");
 generate_test!(parse_example_gblvar, "gblvar.ll");
 generate_test!(parse_example_icmp, "icmp.ll");
 generate_test!(parse_example_load, "load.ll");
@@ -168,6 +221,7 @@ generate_test!(parse_example_loop2, "loop2.ll");
 generate_test!(parse_example_manyargs, "manyargs.ll");
 generate_test!(parse_example_metadata, "metadata.ll");
 generate_test!(parse_example_node, "node.ll");
+generate_test!(parse_example_opaque_ptr, "opaque_ptr.ll");
 generate_test!(parse_example_phi, "phi.ll");
 generate_test!(parse_example_phi_loop, "phi_loop.ll");
 generate_test!(parse_example_printf, "printf.ll");
@@ -252,3 +306,81 @@ fn parse_module2() {
     }
     log::debug!("result = {result:?}");
 }
+
+#[test]
+fn parse_recovering_collects_errors() {
+    let source = r#"
+@a = global i32 0
+@b = global i33 0
+
+define i32 @f() {
+  %1 = add i32 1, 2
+  %2 = frob i32 %1
+  %3 = alloca i7
+  ret i32 %1
+}
+
+define weird i32 @g() {
+  ret i32 0
+}
+
+declare i32 @h()
+"#;
+    let (module, errors) = parse_recovering(source);
+    let locations: Vec<_> = errors
+        .iter()
+        .map(|e| (e.line, e.column, e.expected.as_str()))
+        .collect();
+    assert_eq!(
+        locations,
+        vec![
+            (3, 1, "top-level entity"),
+            (7, 8, "instruction"),
+            (8, 15, "type"),
+            (12, 8, "type"),
+        ]
+    );
+    assert!(module.find_function_by_name("f").is_some());
+    assert!(module.find_function_by_name("g").is_none());
+    assert!(module.find_function_by_name("h").is_some());
+    assert_eq!(module.global_variables().len(), 1);
+    let f = module.find_function_by_name("f").unwrap();
+    let f = &module.functions()[f];
+    assert_eq!(
+        f.layout
+            .inst_iter(f.layout.get_entry_block().unwrap())
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn parse_recovering_skips_multi_line_instructions() {
+    let source = r#"
+define i32 @f(i32 %x) {
+entry:
+  switch i32 %x, label %a [
+    i32 0, label %b
+    i32 one, label %b
+    i32 2, label %b
+  ]
+a:
+  %y = phi i32 [ 0, %entry ],
+               [ %z, %b ]
+  ret i32 %y
+b:
+  %z = add i32 %x, 1
+  br label %a
+}
+"#;
+    let (module, errors) = parse_recovering(source);
+    let locations: Vec<_> = errors
+        .iter()
+        .map(|e| (e.line, e.column, e.expected.as_str()))
+        .collect();
+    assert_eq!(locations.len(), 1, "{locations:?}");
+    assert_eq!(locations[0].0, 6);
+    let f = module.find_function_by_name("f").unwrap();
+    let f = &module.functions()[f];
+    assert_eq!(f.layout.block_iter().count(), 3);
+}
@@ -1,11 +1,14 @@
-use crate::ir::types::{ArrayType, FunctionType, Type, Types, I1, I16, I32, I64, I8, VOID};
+use crate::ir::types::{
+    ArrayType, FunctionType, Type, Types, DOUBLE, FLOAT, FP128, HALF, I1, I16, I32, I64, I8, VOID,
+    X86_FP80,
+};
 use nom::{
     branch::alt,
     bytes::complete::tag,
     character::complete::{char, digit1},
-    combinator::map,
-    error::VerboseError,
-    sequence::preceded,
+    combinator::{cut, map, map_res, opt},
+    error::{context, VerboseError},
+    sequence::{delimited, preceded},
     IResult,
 };
 
@@ -23,22 +26,32 @@ pub fn parse<'a: 'b, 'b>(
             parse_struct(source, types, true)?
         } else if let Ok((source, _)) = preceded(spaces, tag("opaque"))(source) {
             return Ok((source, types.base_mut().anonymous_struct(vec![], false)));
+        } else if let Ok((source, _)) = preceded(spaces, tag("ptr"))(source) {
+            parse_opaque_pointer(source, types)?
         } else if let Ok((source, name)) =
             preceded(spaces, preceded(char('%'), super::name::parse))(source)
         {
             (source, types.base_mut().empty_named_type(name))
         } else {
-            preceded(
-                spaces,
-                alt((
-                    map(tag("metadata"), |_| types.metadata()),
-                    map(tag("void"), |_| VOID),
-                    map(tag("i64"), |_| I64),
-                    map(tag("i32"), |_| I32),
-                    map(tag("i16"), |_| I16),
-                    map(tag("i8"), |_| I8),
-                    map(tag("i1"), |_| I1),
-                )),
+            context(
+                "type",
+                preceded(
+                    spaces,
+                    alt((
+                        map(tag("metadata"), |_| types.metadata()),
+                        map(tag("void"), |_| VOID),
+                        map(tag("i64"), |_| I64),
+                        map(tag("i32"), |_| I32),
+                        map(tag("i16"), |_| I16),
+                        map(tag("i8"), |_| I8),
+                        map(tag("i1"), |_| I1),
+                        map(tag("half"), |_| HALF),
+                        map(tag("float"), |_| FLOAT),
+                        map(tag("double"), |_| DOUBLE),
+                        map(tag("fp128"), |_| FP128),
+                        map(tag("x86_fp80"), |_| X86_FP80),
+                    )),
+                ),
             )(source)?
         };
 
@@ -63,6 +76,30 @@ pub fn parse<'a: 'b, 'b>(
     }
 }
 
+fn parse_opaque_pointer<'a>(
+    source: &'a str,
+    types: &Types,
+) -> IResult<&'a str, Type, VerboseError<&'a str>> {
+    let (source, addr_space) = opt(preceded(
+        spaces,
+        preceded(
+            tag("addrspace"),
+            cut(preceded(
+                spaces,
+                delimited(
+                    char('('),
+                    context("address space", map_res(digit1, |n: &str| n.parse::<u32>())),
+                    char(')'),
+                ),
+            )),
+        ),
+    ))(source)?;
+    Ok((
+        source,
+        types.base_mut().opaque_pointer(addr_space.unwrap_or(0)),
+    ))
+}
+
 fn parse_array<'a>(
     source: &'a str,
     types: &Types,
@@ -140,6 +177,33 @@ fn parse_func_type<'a>(
     Ok((source, func_ty))
 }
 
+#[test]
+fn test_floating_point() {
+    let types = Types::default();
+    for (source, expected) in [
+        ("half", HALF),
+        ("float", FLOAT),
+        ("double", DOUBLE),
+        ("fp128", FP128),
+        ("x86_fp80", X86_FP80),
+    ] {
+        let (_, ty) = parse(&types)(source).unwrap();
+        assert_eq!(ty, expected);
+        assert_eq!(types.to_string(ty), source);
+    }
+}
+
+#[test]
+fn test_opaque_pointer() {
+    let types = Types::default();
+    let (_, ty) = parse(&types)("ptr").unwrap();
+    assert!(types.is_opaque_pointer(ty));
+    assert_eq!(types.to_string(ty), "ptr");
+    let (_, ty) = parse(&types)("ptr addrspace(1)").unwrap();
+    assert_eq!(types.to_string(ty), "ptr addrspace(1)");
+    assert!(parse(&types)("ptr addrspace(99999999999)").is_err());
+}
+
 #[test]
 fn test_metadata() {
     let types = Types::default();
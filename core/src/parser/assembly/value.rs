@@ -3,17 +3,18 @@ use super::{
     util::{spaces, string_literal},
 };
 use crate::ir::{
-    types::{Type, Types, I1, I32, I64, I8},
+    types::{Type, Types, DOUBLE, FLOAT, FP128, HALF, I1, I16, I32, I64, I8, X86_FP80},
     value::{
-        ConstantArray, ConstantExpr, ConstantInt, ConstantStruct, ConstantValue, Value, ValueId,
+        ConstantArray, ConstantExpr, ConstantFloat, ConstantInt, ConstantStruct, ConstantValue,
+        Value, ValueId,
     },
 };
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::complete::{char, digit1},
-    combinator::{opt, recognize},
-    error::VerboseError,
+    character::complete::{char, digit1, hex_digit1, one_of},
+    combinator::{map_res, opt, recognize},
+    error::{context, VerboseError, VerboseErrorKind},
     sequence::{preceded, tuple},
     IResult,
 };
@@ -32,6 +33,11 @@ pub fn parse_constant<'a>(
     if let Ok((source, _)) = preceded(spaces, tag("zeroinitializer"))(source) {
         return Ok((source, ConstantValue::AggregateZero(ty)));
     }
+    if ty.is_floating_point() {
+        if let Ok((source, f)) = parse_constant_float(source, ty) {
+            return Ok((source, f.into()));
+        }
+    }
     if let Ok((source, id)) = parse_constant_int(source, ty) {
         return Ok((source, id.into()));
     }
@@ -61,6 +67,7 @@ pub fn parse_constant_int(
     let val = match ty {
         I1 => ConstantInt::Int1(num == "true"),
         I8 => ConstantInt::Int8(num.parse::<i8>().unwrap()),
+        I16 => ConstantInt::Int16(num.parse::<i16>().unwrap()),
         I32 => ConstantInt::Int32(num.parse::<i32>().unwrap()),
         I64 => ConstantInt::Int64(num.parse::<i64>().unwrap()),
         _ => todo!(),
@@ -68,6 +75,57 @@ pub fn parse_constant_int(
     Ok((source, val))
 }
 
+/// Parses a floating-point literal of type `ty`.
+/// Both decimal (e.g. `1.5e+00`) and hexadecimal (e.g. `0x3FF8000000000000`, `0xH3C00`) forms are
+/// accepted.
+pub fn parse_constant_float(
+    source: &str,
+    ty: Type,
+) -> IResult<&str, ConstantFloat, VerboseError<&str>> {
+    if let Ok((source, (kind, hex))) = preceded(
+        spaces,
+        preceded(tag("0x"), tuple((opt(one_of("HKL")), hex_digit1))),
+    )(source)
+    {
+        let bits = u128::from_str_radix(hex, 16).map_err(|_| float_literal_error(source))?;
+        let val = match (kind, ty) {
+            (None, DOUBLE) => ConstantFloat::Double(f64::from_bits(bits as u64)),
+            (None, FLOAT) => ConstantFloat::Float(f64::from_bits(bits as u64) as f32),
+            (Some('H'), HALF) => ConstantFloat::Half(bits as u16),
+            // NOTE: The lower 64 bits come first.
+            (Some('L'), FP128) => ConstantFloat::Fp128(bits.rotate_left(64)),
+            (Some('K'), X86_FP80) => ConstantFloat::X86Fp80(bits),
+            _ => return Err(float_literal_error(source)),
+        };
+        return Ok((source, val));
+    }
+
+    let (source, num) = preceded(
+        spaces,
+        map_res(
+            recognize(tuple((
+                opt(one_of("+-")),
+                digit1,
+                opt(tuple((char('.'), opt(digit1)))),
+                opt(tuple((one_of("eE"), opt(one_of("+-")), digit1))),
+            ))),
+            |num: &str| num.parse::<f64>(),
+        ),
+    )(source)?;
+    let val = match ty {
+        DOUBLE => ConstantFloat::Double(num),
+        FLOAT => ConstantFloat::Float(num as f32),
+        _ => return Err(float_literal_error(source)),
+    };
+    Ok((source, val))
+}
+
+fn float_literal_error(source: &str) -> nom::Err<VerboseError<&str>> {
+    nom::Err::Error(VerboseError {
+        errors: vec![(source, VerboseErrorKind::Context("floating-point literal"))],
+    })
+}
+
 pub fn parse_constant_array<'a>(
     source: &'a str,
     types: &Types,
@@ -237,5 +295,5 @@ pub fn parse<'a, 'b>(
         return Ok((source, id));
     }
 
-    parse_local(source, ctx, ty)
+    context("value", |source| parse_local(source, ctx, ty))(source)
 }
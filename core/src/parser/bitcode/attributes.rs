@@ -0,0 +1,279 @@
+use super::{bitstream::Block, Error};
+use crate::ir::{
+    function::param_attrs::ParameterAttribute, module::attributes::Attribute, types::Type,
+};
+use rustc_hash::FxHashMap;
+
+const PARAMATTR_CODE_ENTRY: u32 = 2;
+const PARAMATTR_GRP_CODE_ENTRY: u32 = 3;
+
+/// The index of the attribute set applied to the function itself.
+pub const FUNCTION_INDEX: u64 = 0xFFFF_FFFF;
+
+/// Enum attribute kinds that apply to functions, keyed by their bitcode attribute kind.
+pub static FUNC_ATTR_KINDS: &[(u64, Attribute)] = &[
+    (2, Attribute::AlwaysInline),
+    (4, Attribute::InlineHint),
+    (6, Attribute::MinimizeSize),
+    (7, Attribute::Naked),
+    (10, Attribute::NoBuiltin),
+    (12, Attribute::NoDuplicate),
+    (13, Attribute::NoImplicitFloat),
+    (14, Attribute::NoInline),
+    (15, Attribute::NonLazyBind),
+    (16, Attribute::NoRedZone),
+    (17, Attribute::NoReturn),
+    (18, Attribute::NoUnwind),
+    (19, Attribute::OptSize),
+    (20, Attribute::ReadNone),
+    (21, Attribute::ReadOnly),
+    (23, Attribute::ReturnsTwice),
+    (26, Attribute::StackProtect),
+    (27, Attribute::StackProtectReq),
+    (28, Attribute::StackProtectStrong),
+    (30, Attribute::SanitizeAddress),
+    (31, Attribute::SanitizeThread),
+    (32, Attribute::SanitizeMemory),
+    (33, Attribute::UWTable),
+    (35, Attribute::Builtin),
+    (36, Attribute::Cold),
+    (37, Attribute::OptNone),
+    (40, Attribute::JumpTable),
+    (43, Attribute::Convergent),
+    (44, Attribute::SafeStack),
+    (45, Attribute::ArgMemOnly),
+    (48, Attribute::NoRecurse),
+    (49, Attribute::InaccessibleMemOnly),
+    (50, Attribute::InaccessibleMemOrArgMemOnly),
+    (52, Attribute::WriteOnly),
+    (53, Attribute::Speculatable),
+    (54, Attribute::StrictFP),
+    (55, Attribute::SanitizeHWAddress),
+    (56, Attribute::NoCFCheck),
+    (57, Attribute::OptForFuzzing),
+    (58, Attribute::ShadowCallStack),
+    (59, Attribute::SpeculativeLoadHardening),
+    (61, Attribute::WillReturn),
+    (62, Attribute::NoFree),
+    (63, Attribute::NoSync),
+    (64, Attribute::SanitizeMemTag),
+    (70, Attribute::MustProgress),
+];
+
+/// Enum attribute kinds that apply to parameters and return values.
+pub static PARAM_ATTR_KINDS: &[(u64, ParameterAttribute)] = &[
+    (3, ParameterAttribute::ByVal),
+    (5, ParameterAttribute::InReg),
+    (8, ParameterAttribute::Nest),
+    (9, ParameterAttribute::NoAlias),
+    (11, ParameterAttribute::NoCapture),
+    (21, ParameterAttribute::ReadOnly),
+    (22, ParameterAttribute::Returned),
+    (24, ParameterAttribute::SignExt),
+    (34, ParameterAttribute::ZeroExt),
+    (38, ParameterAttribute::InAlloca),
+    (39, ParameterAttribute::NonNull),
+    (46, ParameterAttribute::SwiftSelf),
+    (47, ParameterAttribute::SwiftError),
+    (52, ParameterAttribute::WriteOnly),
+    (60, ParameterAttribute::ImmArg),
+    (62, ParameterAttribute::NoFree),
+    (68, ParameterAttribute::NoUndef),
+];
+
+pub const KIND_ALIGNMENT: u64 = 1;
+pub const KIND_STACK_ALIGNMENT: u64 = 25;
+pub const KIND_STRUCT_RET: u64 = 29;
+pub const KIND_DEREFERENCEABLE: u64 = 41;
+pub const KIND_DEREFERENCEABLE_OR_NULL: u64 = 42;
+pub const KIND_ALLOC_SIZE: u64 = 51;
+
+/// An attribute as stored in an attribute group, before it's known where it applies.
+#[derive(Debug, Clone)]
+pub enum RawAttr {
+    Enum(u64),
+    Int(u64, u64),
+    Str(String, String),
+    Type(u64, Option<u64>),
+}
+
+#[derive(Debug, Default)]
+pub struct AttributeTable {
+    /// Group id -> (index, attributes)
+    pub groups: FxHashMap<u64, (u64, Vec<RawAttr>)>,
+    /// Attribute lists referred by functions and call sites. Each of them is a list of group ids.
+    pub lists: Vec<Vec<u64>>,
+}
+
+/// Attributes of a function or a call site, split by where they apply.
+#[derive(Default)]
+pub struct AttributeList {
+    pub func: Option<u64>, // group id
+    pub ret: Vec<ParameterAttribute>,
+    pub params: FxHashMap<u64, Vec<ParameterAttribute>>, // param index (from 0) -> attributes
+}
+
+impl AttributeTable {
+    pub fn parse_groups(&mut self, block: &Block) -> Result<(), Error> {
+        for record in block.records() {
+            if record.code != PARAMATTR_GRP_CODE_ENTRY {
+                continue;
+            }
+            let grp_id = record.op(0)?;
+            let index = record.op(1)?;
+            let mut attrs = vec![];
+            let mut i = 2;
+            while i < record.ops.len() {
+                let attr = match record.ops[i] {
+                    0 => {
+                        i += 2;
+                        RawAttr::Enum(record.op(i - 1)?)
+                    }
+                    1 => {
+                        i += 3;
+                        RawAttr::Int(record.op(i - 2)?, record.op(i - 1)?)
+                    }
+                    k @ (3 | 4) => {
+                        let (kind, next) = c_string(&record.ops, i + 1);
+                        let (value, next) = if k == 4 {
+                            c_string(&record.ops, next)
+                        } else {
+                            ("".to_string(), next)
+                        };
+                        i = next;
+                        RawAttr::Str(kind, value)
+                    }
+                    5 => {
+                        i += 2;
+                        RawAttr::Type(record.op(i - 1)?, None)
+                    }
+                    6 => {
+                        i += 3;
+                        RawAttr::Type(record.op(i - 2)?, Some(record.op(i - 1)?))
+                    }
+                    _ => return Err(Error::Malformed("unknown attribute encoding")),
+                };
+                attrs.push(attr)
+            }
+            self.groups.insert(grp_id, (index, attrs));
+        }
+        Ok(())
+    }
+
+    pub fn parse_lists(&mut self, block: &Block) {
+        for record in block.records() {
+            if record.code == PARAMATTR_CODE_ENTRY {
+                self.lists.push(record.ops.clone())
+            }
+        }
+    }
+
+    /// Returns the attribute list referred by `id`, where `0` means no attributes.
+    pub fn list(
+        &self,
+        id: u64,
+        type_of: impl Fn(u64) -> Result<Type, Error>,
+    ) -> Result<AttributeList, Error> {
+        let mut list = AttributeList::default();
+        if id == 0 {
+            return Ok(list);
+        }
+        let groups = self
+            .lists
+            .get(id as usize - 1)
+            .ok_or(Error::Malformed("invalid attribute list id"))?;
+        for grp_id in groups {
+            let (index, attrs) = self
+                .groups
+                .get(grp_id)
+                .ok_or(Error::Malformed("invalid attribute group id"))?;
+            match *index {
+                FUNCTION_INDEX => list.func = Some(*grp_id),
+                0 => list.ret = param_attrs(attrs, &type_of)?,
+                i => {
+                    list.params.insert(i - 1, param_attrs(attrs, &type_of)?);
+                }
+            }
+        }
+        Ok(list)
+    }
+
+    /// Returns the function attributes in the group `grp_id`.
+    pub fn func_attrs(&self, grp_id: u64) -> Vec<Attribute> {
+        let mut attrs = vec![];
+        for attr in self.groups.get(&grp_id).map_or(&[][..], |(_, a)| a) {
+            match attr {
+                RawAttr::Enum(kind) => match FUNC_ATTR_KINDS.iter().find(|(k, _)| k == kind) {
+                    Some((_, attr)) => attrs.push(attr.clone()),
+                    None => log::debug!("unknown function attribute kind: {}", kind),
+                },
+                RawAttr::Int(KIND_STACK_ALIGNMENT, v) => attrs.push(Attribute::AlignStack(*v)),
+                RawAttr::Int(KIND_ALLOC_SIZE, v) => attrs.push(Attribute::AllocSize {
+                    elt_size: (v >> 32) as u32,
+                    num_elts: match *v as u32 {
+                        u32::MAX => None,
+                        n => Some(n),
+                    },
+                }),
+                RawAttr::Str(kind, value) => attrs.push(Attribute::StringAttribute {
+                    kind: kind.clone(),
+                    value: value.clone(),
+                }),
+                attr => log::debug!("unknown function attribute: {:?}", attr),
+            }
+        }
+        attrs
+    }
+}
+
+fn param_attrs(
+    attrs: &[RawAttr],
+    type_of: impl Fn(u64) -> Result<Type, Error>,
+) -> Result<Vec<ParameterAttribute>, Error> {
+    let mut list = vec![];
+    for attr in attrs {
+        list.push(match attr {
+            RawAttr::Enum(kind) | RawAttr::Type(kind, None) if *kind == KIND_STRUCT_RET => {
+                ParameterAttribute::SRet(None)
+            }
+            RawAttr::Type(KIND_STRUCT_RET, Some(ty)) => {
+                ParameterAttribute::SRet(Some(type_of(*ty)?))
+            }
+            RawAttr::Enum(kind) | RawAttr::Type(kind, _) => {
+                match PARAM_ATTR_KINDS.iter().find(|(k, _)| k == kind) {
+                    Some((_, attr)) => attr.clone(),
+                    None => {
+                        log::debug!("unknown parameter attribute kind: {}", kind);
+                        continue;
+                    }
+                }
+            }
+            RawAttr::Int(KIND_ALIGNMENT, v) => ParameterAttribute::Alignment(*v),
+            RawAttr::Int(KIND_DEREFERENCEABLE, v) => ParameterAttribute::Dereferenceable(*v),
+            RawAttr::Int(KIND_DEREFERENCEABLE_OR_NULL, v) => {
+                ParameterAttribute::DereferenceableOrNull(*v)
+            }
+            RawAttr::Str(kind, value) => ParameterAttribute::StringAttribute {
+                kind: kind.clone(),
+                value: value.clone(),
+            },
+            attr => {
+                log::debug!("unknown parameter attribute: {:?}", attr);
+                continue;
+            }
+        })
+    }
+    Ok(list)
+}
+
+/// Reads a null-terminated string from `ops[start..]`.
+/// Returns the string and the position right after the terminator.
+fn c_string(ops: &[u64], start: usize) -> (String, usize) {
+    let rest = ops.get(start..).unwrap_or(&[]);
+    let len = rest.iter().position(|&c| c == 0).unwrap_or(rest.len());
+    let bytes: Vec<u8> = rest[..len].iter().map(|&c| c as u8).collect();
+    (
+        String::from_utf8_lossy(&bytes).into_owned(),
+        start + len + 1,
+    )
+}
@@ -0,0 +1,392 @@
+//! A reader for the LLVM bitstream container format.
+//!
+//! The whole stream is decoded eagerly into a tree of [`Block`]s holding [`Record`]s, so that the
+//! IR decoders in the sibling modules don't have to deal with abbreviations or bit offsets.
+//! See <https://llvm.org/docs/BitCodeFormat.html> for the format itself.
+
+use super::Error;
+use rustc_hash::FxHashMap;
+
+pub const BLOCKINFO_BLOCK_ID: u32 = 0;
+
+const END_BLOCK: u64 = 0;
+const ENTER_SUBBLOCK: u64 = 1;
+const DEFINE_ABBREV: u64 = 2;
+const UNABBREV_RECORD: u64 = 3;
+
+const BLOCKINFO_CODE_SETBID: u32 = 1;
+
+/// How deep blocks may nest. LLVM itself never nests more than a few levels.
+const MAX_BLOCK_DEPTH: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub id: u32,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Entry {
+    Record(Record),
+    Block(Block),
+}
+
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub code: u32,
+    pub ops: Vec<u64>,
+    pub blob: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone)]
+enum AbbrevOp {
+    Literal(u64),
+    Fixed(u32),
+    Vbr(u32),
+    Array,
+    Char6,
+    Blob,
+}
+
+type Abbrev = Vec<AbbrevOp>;
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    pos: usize, // in bits
+}
+
+struct Reader<'a> {
+    bits: BitReader<'a>,
+    block_info: FxHashMap<u32, Vec<Abbrev>>,
+    depth: usize,
+}
+
+/// Decodes the top-level blocks of `bytes`, which must not include the magic number.
+pub fn parse(bytes: &[u8]) -> Result<Vec<Block>, Error> {
+    let mut reader = Reader {
+        bits: BitReader { bytes, pos: 0 },
+        block_info: FxHashMap::default(),
+        depth: 0,
+    };
+    let mut blocks = vec![];
+    // Trailing bytes (e.g. padding of a wrapper) can't form a complete abbreviation id.
+    while reader.bits.remaining() >= 32 {
+        match reader.bits.read(2)? {
+            ENTER_SUBBLOCK => {
+                if let Some(block) = reader.block()? {
+                    blocks.push(block)
+                }
+            }
+            _ => return Err(Error::Malformed("expected a block at the top level")),
+        }
+    }
+    Ok(blocks)
+}
+
+impl Block {
+    pub fn records(&self) -> impl Iterator<Item = &Record> {
+        self.entries.iter().filter_map(|e| match e {
+            Entry::Record(r) => Some(r),
+            _ => None,
+        })
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.entries.iter().filter_map(|e| match e {
+            Entry::Block(b) => Some(b),
+            _ => None,
+        })
+    }
+}
+
+impl Record {
+    /// Interprets the operands starting at `start` as a string.
+    pub fn string(&self, start: usize) -> String {
+        let bytes = self.ops.get(start..).unwrap_or(&[]);
+        String::from_utf8_lossy(&bytes.iter().map(|&c| c as u8).collect::<Vec<_>>()).into_owned()
+    }
+
+    pub fn op(&self, i: usize) -> Result<u64, Error> {
+        self.ops
+            .get(i)
+            .copied()
+            .ok_or(Error::Malformed("too few record operands"))
+    }
+}
+
+impl<'a> Reader<'a> {
+    /// Reads the rest of a block whose ENTER_SUBBLOCK abbreviation id has already been read.
+    /// BLOCKINFO blocks are consumed here and don't show up in the result.
+    fn block(&mut self) -> Result<Option<Block>, Error> {
+        if self.depth == MAX_BLOCK_DEPTH {
+            return Err(Error::Malformed("blocks nested too deeply"));
+        }
+        self.depth += 1;
+        let block = self.block_body();
+        self.depth -= 1;
+        block
+    }
+
+    fn block_body(&mut self) -> Result<Option<Block>, Error> {
+        let id = self.bits.read_vbr(8)? as u32;
+        let abbrev_width = self.bits.read_vbr(4)? as u32;
+        self.bits.align32();
+        let _num_words = self.bits.read(32)?;
+
+        let mut abbrevs = self.block_info.get(&id).cloned().unwrap_or_default();
+        let mut entries = vec![];
+        let mut cur_bid = None; // Only used in BLOCKINFO
+
+        loop {
+            match self.bits.read(abbrev_width)? {
+                END_BLOCK => {
+                    self.bits.align32();
+                    break;
+                }
+                ENTER_SUBBLOCK => {
+                    if let Some(block) = self.block()? {
+                        entries.push(Entry::Block(block))
+                    }
+                }
+                DEFINE_ABBREV => {
+                    let abbrev = self.define_abbrev()?;
+                    if id == BLOCKINFO_BLOCK_ID {
+                        let bid = cur_bid.ok_or(Error::Malformed("abbreviation before SETBID"))?;
+                        self.block_info.entry(bid).or_default().push(abbrev);
+                    } else {
+                        abbrevs.push(abbrev)
+                    }
+                }
+                UNABBREV_RECORD => {
+                    let code = self.bits.read_vbr(6)? as u32;
+                    let num_ops = self.bits.read_vbr(6)?;
+                    // Don't trust `num_ops` for preallocation. Reading past the end fails anyway.
+                    let mut ops = vec![];
+                    for _ in 0..num_ops {
+                        ops.push(self.bits.read_vbr(6)?);
+                    }
+                    entries.push(Entry::Record(Record {
+                        code,
+                        ops,
+                        blob: None,
+                    }))
+                }
+                abbrev_id => {
+                    let abbrev = abbrevs
+                        .get(abbrev_id as usize - 4)
+                        .ok_or(Error::Malformed("undefined abbreviation"))?
+                        .clone();
+                    entries.push(Entry::Record(self.abbreviated_record(&abbrev)?))
+                }
+            }
+
+            if id == BLOCKINFO_BLOCK_ID {
+                if let Some(Entry::Record(r)) = entries.last() {
+                    if r.code == BLOCKINFO_CODE_SETBID {
+                        cur_bid = Some(r.op(0)? as u32);
+                    }
+                }
+            }
+        }
+
+        if id == BLOCKINFO_BLOCK_ID {
+            return Ok(None);
+        }
+
+        Ok(Some(Block { id, entries }))
+    }
+
+    fn define_abbrev(&mut self) -> Result<Abbrev, Error> {
+        let num_ops = self.bits.read_vbr(5)?;
+        let mut abbrev = vec![];
+        for _ in 0..num_ops {
+            let is_literal = self.bits.read(1)? == 1;
+            if is_literal {
+                abbrev.push(AbbrevOp::Literal(self.bits.read_vbr(8)?));
+                continue;
+            }
+            abbrev.push(match self.bits.read(3)? {
+                1 => AbbrevOp::Fixed(self.width()?),
+                2 => AbbrevOp::Vbr(self.width()?),
+                3 => AbbrevOp::Array,
+                4 => AbbrevOp::Char6,
+                5 => AbbrevOp::Blob,
+                _ => return Err(Error::Malformed("unknown abbreviation operand encoding")),
+            })
+        }
+        Ok(abbrev)
+    }
+
+    fn width(&mut self) -> Result<u32, Error> {
+        match self.bits.read_vbr(5)? {
+            w @ 0..=64 => Ok(w as u32),
+            _ => Err(Error::Malformed("abbreviation operand wider than 64 bits")),
+        }
+    }
+
+    fn abbreviated_record(&mut self, abbrev: &Abbrev) -> Result<Record, Error> {
+        let mut vals = vec![];
+        let mut blob = None;
+        let mut i = 0;
+        while i < abbrev.len() {
+            match &abbrev[i] {
+                AbbrevOp::Array => {
+                    let elem = abbrev
+                        .get(i + 1)
+                        .ok_or(Error::Malformed("array without element type"))?;
+                    let len = self.bits.read_vbr(6)?;
+                    // Elements may take no bits at all (e.g. literals), so bound `len` by the
+                    // rest of the stream to keep a corrupt length from exhausting memory.
+                    if len > self.bits.remaining() as u64 {
+                        return Err(Error::UnexpectedEof);
+                    }
+                    for _ in 0..len {
+                        vals.push(self.scalar(elem)?);
+                    }
+                    i += 1; // The element type is consumed as well
+                }
+                AbbrevOp::Blob => {
+                    let len = self.bits.read_vbr(6)? as usize;
+                    self.bits.align32();
+                    blob = Some(self.bits.read_bytes(len)?);
+                    self.bits.align32();
+                }
+                op => vals.push(self.scalar(op)?),
+            }
+            i += 1;
+        }
+        if vals.is_empty() {
+            return Err(Error::Malformed("abbreviated record without code"));
+        }
+        let code = vals.remove(0) as u32;
+        Ok(Record {
+            code,
+            ops: vals,
+            blob,
+        })
+    }
+
+    fn scalar(&mut self, op: &AbbrevOp) -> Result<u64, Error> {
+        match *op {
+            AbbrevOp::Literal(v) => Ok(v),
+            AbbrevOp::Fixed(w) => self.bits.read(w),
+            AbbrevOp::Vbr(w) => self.bits.read_vbr(w),
+            AbbrevOp::Char6 => Ok(decode_char6(self.bits.read(6)? as u8) as u64),
+            AbbrevOp::Array | AbbrevOp::Blob => {
+                Err(Error::Malformed("array or blob used as an array element"))
+            }
+        }
+    }
+}
+
+impl<'a> BitReader<'a> {
+    fn remaining(&self) -> usize {
+        (self.bytes.len() * 8).saturating_sub(self.pos)
+    }
+
+    fn read(&mut self, width: u32) -> Result<u64, Error> {
+        if width == 0 {
+            return Ok(0);
+        }
+        if width > 64 || self.remaining() < width as usize {
+            return Err(Error::UnexpectedEof);
+        }
+        let mut val = 0u64;
+        let mut read = 0;
+        while read < width {
+            let byte = self.bytes[self.pos / 8] as u64;
+            let offset = (self.pos % 8) as u32;
+            let n = (8 - offset).min(width - read);
+            let bits = (byte >> offset) & ((1 << n) - 1);
+            val |= bits << read;
+            read += n;
+            self.pos += n as usize;
+        }
+        Ok(val)
+    }
+
+    fn read_vbr(&mut self, width: u32) -> Result<u64, Error> {
+        if width == 0 {
+            return Ok(0);
+        }
+        if width > 64 {
+            return Err(Error::Malformed("VBR wider than 64 bits"));
+        }
+        let hi = 1 << (width - 1);
+        let mut val = 0u64;
+        let mut shift = 0u32;
+        loop {
+            let piece = self.read(width)?;
+            if shift < 64 {
+                val |= (piece & (hi - 1)) << shift;
+            }
+            if piece & hi == 0 {
+                return Ok(val);
+            }
+            shift = shift.saturating_add(width - 1);
+        }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>, Error> {
+        debug_assert!(self.pos & 7 == 0);
+        let start = self.pos / 8;
+        let bytes = self
+            .bytes
+            .get(start..start.checked_add(len).ok_or(Error::UnexpectedEof)?)
+            .ok_or(Error::UnexpectedEof)?;
+        self.pos += len * 8;
+        Ok(bytes.to_vec())
+    }
+
+    fn align32(&mut self) {
+        self.pos = (self.pos + 31) & !31;
+    }
+}
+
+/// Reads `count` VBR6 values from `bytes`, as used for the string lengths in METADATA_STRINGS.
+pub fn vbr6_array(bytes: &[u8], count: usize) -> Result<Vec<u64>, Error> {
+    let mut bits = BitReader { bytes, pos: 0 };
+    (0..count).map(|_| bits.read_vbr(6)).collect()
+}
+
+fn decode_char6(v: u8) -> u8 {
+    match v {
+        0..=25 => b'a' + v,
+        26..=51 => b'A' + v - 26,
+        52..=61 => b'0' + v - 52,
+        62 => b'.',
+        _ => b'_',
+    }
+}
+
+/// Decodes a sign-rotated VBR value, where the sign is kept in the lowest bit.
+pub fn decode_signed(v: u64) -> i64 {
+    if v & 1 == 0 {
+        (v >> 1) as i64
+    } else if v != 1 {
+        -((v >> 1) as i64)
+    } else {
+        i64::MIN
+    }
+}
+
+#[test]
+fn read_bits() {
+    let mut bits = BitReader {
+        bytes: &[0b1010_1101, 0b0000_0011],
+        pos: 0,
+    };
+    assert_eq!(bits.read(3).unwrap(), 0b101);
+    assert_eq!(bits.read(7).unwrap(), 0b11_10101);
+    assert!(bits.read(7).is_err());
+}
+
+#[test]
+fn read_vbr() {
+    // Two vbr5 chunks: 0b1_0011 (continued) and 0b0_0001
+    let mut bits = BitReader {
+        bytes: &[0b0011_0011, 0b0000_0000],
+        pos: 0,
+    };
+    assert_eq!(bits.read_vbr(5).unwrap(), 0b1_0011);
+}
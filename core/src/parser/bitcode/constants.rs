@@ -0,0 +1,356 @@
+use super::{
+    bitstream::{decode_signed, Block, Record},
+    Error,
+};
+use crate::ir::{
+    function::instruction::Opcode,
+    module::name::Name,
+    types::{
+        CompoundType, Type, Types, DOUBLE, FLOAT, FP128, HALF, I1, I16, I32, I64, I8, X86_FP80,
+    },
+    value::{
+        ConstantArray, ConstantExpr, ConstantFloat, ConstantInt, ConstantStruct, ConstantValue,
+        InlineAsm, ValueId,
+    },
+};
+
+const SETTYPE: u32 = 1;
+const NULL: u32 = 2;
+const UNDEF: u32 = 3;
+const INTEGER: u32 = 4;
+const FLOAT_: u32 = 6;
+const AGGREGATE: u32 = 7;
+const STRING: u32 = 8;
+const CSTRING: u32 = 9;
+const CE_CAST: u32 = 11;
+const CE_GEP: u32 = 12;
+const CE_INBOUNDS_GEP: u32 = 20;
+const DATA: u32 = 22;
+const CE_GEP_WITH_INRANGE_INDEX: u32 = 24;
+const POISON: u32 = 26;
+const INLINEASM_OLD3: u32 = 28;
+const INLINEASM: u32 = 30;
+
+/// How deep constants may nest. A constant that (indirectly) contains itself exceeds it.
+const MAX_CONSTANT_DEPTH: usize = 256;
+
+/// An entry of the value table, which is shared by global values, constants, arguments and
+/// instructions.
+#[derive(Debug, Clone)]
+pub enum ValueEntry {
+    /// A global variable or a function. The type is the pointer type of the global value.
+    Global(Name, Type),
+    /// A constant, decoded on demand since it may refer to values defined after it.
+    Constant(Type, Record),
+    /// An argument or an instruction in the function being decoded.
+    Local(ValueId, Type),
+}
+
+pub struct Context<'a> {
+    pub types: &'a Types,
+    pub type_list: &'a [Type],
+    pub values: &'a [ValueEntry],
+}
+
+/// Appends the constants in a CONSTANTS_BLOCK to `values`.
+pub fn parse(block: &Block, type_list: &[Type], values: &mut Vec<ValueEntry>) -> Result<(), Error> {
+    let mut ty = None;
+    for record in block.records() {
+        if record.code == SETTYPE {
+            ty = Some(
+                *type_list
+                    .get(record.op(0)? as usize)
+                    .ok_or(Error::Malformed("invalid type id"))?,
+            );
+            continue;
+        }
+        let ty = ty.ok_or(Error::Malformed("constant without type"))?;
+        values.push(ValueEntry::Constant(ty, record.clone()))
+    }
+    Ok(())
+}
+
+impl<'a> Context<'a> {
+    pub fn ty(&self, id: u64) -> Result<Type, Error> {
+        self.type_list
+            .get(id as usize)
+            .copied()
+            .ok_or(Error::Malformed("invalid type id"))
+    }
+
+    pub fn type_of(&self, id: u64) -> Result<Type, Error> {
+        match self.values.get(id as usize) {
+            Some(ValueEntry::Global(_, ty))
+            | Some(ValueEntry::Constant(ty, _))
+            | Some(ValueEntry::Local(_, ty)) => Ok(*ty),
+            None => Err(Error::Malformed("invalid value id")),
+        }
+    }
+
+    /// Returns the inline assembly `id` refers to, if so.
+    pub fn inline_asm(&self, id: u64) -> Option<InlineAsm> {
+        let record = match self.values.get(id as usize) {
+            Some(ValueEntry::Constant(_, r)) if matches!(r.code, INLINEASM | INLINEASM_OLD3) => r,
+            _ => return None,
+        };
+        // INLINEASM: [fnty, flags, asmstr size, asmstr..., constraint size, constraint...]
+        let ops = if record.code == INLINEASM {
+            record.ops.get(1..)?
+        } else {
+            &record.ops[..]
+        };
+        let flags = *ops.first()?;
+        let body_len = usize::try_from(*ops.get(1)?).ok()?;
+        let body = ops.get(2..2usize.checked_add(body_len)?)?;
+        let rest = &ops[2 + body_len..];
+        let constraints_len = usize::try_from(*rest.first()?).ok()?;
+        let constraints = rest.get(1..1usize.checked_add(constraints_len)?)?;
+        let string = |s: &[u64]| {
+            String::from_utf8_lossy(&s.iter().map(|&c| c as u8).collect::<Vec<_>>()).into_owned()
+        };
+        // Same as the textual parser, which keeps the asm string in `constraints`.
+        Some(InlineAsm {
+            body: string(constraints),
+            constraints: string(body),
+            sideeffect: flags & 1 == 1,
+        })
+    }
+
+    pub fn constant(&self, id: u64) -> Result<ConstantValue, Error> {
+        self.constant_at(id, 0)
+    }
+
+    fn constant_at(&self, id: u64, depth: usize) -> Result<ConstantValue, Error> {
+        if depth == MAX_CONSTANT_DEPTH {
+            return Err(Error::Malformed("constants nested too deeply"));
+        }
+        let constant = |id| self.constant_at(id, depth + 1);
+        let (ty, record) = match self.values.get(id as usize) {
+            Some(ValueEntry::Global(name, ty)) => {
+                return Ok(ConstantValue::GlobalRef(name.clone(), *ty))
+            }
+            Some(ValueEntry::Constant(ty, record)) => (*ty, record),
+            Some(ValueEntry::Local(..)) => return Err(Error::Malformed("expected a constant")),
+            None => return Err(Error::Malformed("invalid value id")),
+        };
+
+        match record.code {
+            NULL => self.null(ty),
+            UNDEF | POISON => Ok(ConstantValue::Undef(ty)),
+            INTEGER => int(ty, decode_signed(record.op(0)?)),
+            FLOAT_ => self.float(ty, record),
+            AGGREGATE => {
+                let elems = record
+                    .ops
+                    .iter()
+                    .map(|&id| constant(id))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.aggregate(ty, elems, false)
+            }
+            STRING | CSTRING => {
+                let mut elems: Vec<_> = record
+                    .ops
+                    .iter()
+                    .map(|&c| ConstantValue::Int(ConstantInt::Int8(c as u8 as i8)))
+                    .collect();
+                if record.code == CSTRING {
+                    elems.push(ConstantValue::Int(ConstantInt::Int8(0)))
+                }
+                self.aggregate(ty, elems, true)
+            }
+            DATA => {
+                let elem_ty = self
+                    .types
+                    .get_element(ty)
+                    .ok_or(Error::Malformed("data constant of non-array type"))?;
+                let elems = record
+                    .ops
+                    .iter()
+                    .map(|&v| {
+                        if elem_ty.is_floating_point() {
+                            self.float(
+                                elem_ty,
+                                &Record {
+                                    code: FLOAT_,
+                                    ops: vec![v],
+                                    blob: None,
+                                },
+                            )
+                        } else {
+                            int(elem_ty, v as i64)
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.aggregate(ty, elems, false)
+            }
+            CE_CAST => {
+                if cast_opcode(record.op(0)?) != Some(Opcode::Bitcast) {
+                    return Err(Error::Unsupported(
+                        "constant cast expression other than bitcast".to_string(),
+                    ));
+                }
+                Ok(ConstantValue::Expr(ConstantExpr::Bitcast {
+                    tys: [self.ty(record.op(1)?)?, ty],
+                    arg: Box::new(constant(record.op(2)?)?),
+                }))
+            }
+            CE_GEP | CE_INBOUNDS_GEP | CE_GEP_WITH_INRANGE_INDEX => {
+                // [pointee type, flags (only with inrange index)?, (type, value)...]
+                let (inbounds, source_ty, pairs) = match record.code {
+                    CE_GEP_WITH_INRANGE_INDEX => {
+                        (record.op(1)? & 1 == 1, record.op(0)?, &record.ops[2..])
+                    }
+                    _ if record.ops.len() % 2 == 1 => (
+                        record.code == CE_INBOUNDS_GEP,
+                        record.ops[0],
+                        &record.ops[1..],
+                    ),
+                    _ => {
+                        return Err(Error::Unsupported(
+                            "constant getelementptr without source element type".to_string(),
+                        ))
+                    }
+                };
+                let mut tys = vec![self.ty(source_ty)?];
+                let mut args = vec![];
+                for pair in pairs.chunks(2) {
+                    if pair.len() != 2 {
+                        return Err(Error::Malformed("invalid constant getelementptr"));
+                    }
+                    tys.push(self.ty(pair[0])?);
+                    args.push(constant(pair[1])?);
+                }
+                Ok(ConstantValue::Expr(ConstantExpr::GetElementPtr {
+                    inbounds,
+                    tys,
+                    args,
+                }))
+            }
+            code => Err(Error::Unsupported(format!("constant code {}", code))),
+        }
+    }
+
+    fn null(&self, ty: Type) -> Result<ConstantValue, Error> {
+        if ty.is_integer() {
+            return int(ty, 0);
+        }
+        if ty.is_floating_point() {
+            return self.float(
+                ty,
+                &Record {
+                    code: FLOAT_,
+                    ops: vec![0, 0],
+                    blob: None,
+                },
+            );
+        }
+        if ty.is_pointer(self.types) {
+            return Ok(ConstantValue::Null(ty));
+        }
+        Ok(ConstantValue::AggregateZero(ty))
+    }
+
+    fn float(&self, ty: Type, record: &Record) -> Result<ConstantValue, Error> {
+        let bits = record.op(0)?;
+        let f = match ty {
+            HALF => ConstantFloat::Half(bits as u16),
+            FLOAT => ConstantFloat::Float(f32::from_bits(bits as u32)),
+            DOUBLE => ConstantFloat::Double(f64::from_bits(bits)),
+            X86_FP80 => {
+                // The record holds [upper 64 bits, lower 16 bits] of the 80-bit value.
+                let (hi, lo) = (record.op(0)?, record.op(1)?);
+                let bits = ((lo & 0xffff) | (hi << 16)) as u128 | ((hi >> 48) as u128) << 64;
+                ConstantFloat::X86Fp80(bits)
+            }
+            FP128 => ConstantFloat::Fp128(bits as u128 | (record.op(1)? as u128) << 64),
+            _ => {
+                return Err(Error::Malformed(
+                    "float constant of non floating-point type",
+                ))
+            }
+        };
+        Ok(ConstantValue::Float(f))
+    }
+
+    fn aggregate(
+        &self,
+        ty: Type,
+        elems: Vec<ConstantValue>,
+        is_string: bool,
+    ) -> Result<ConstantValue, Error> {
+        match &*self
+            .types
+            .get(ty)
+            .ok_or(Error::Malformed("aggregate constant of primitive type"))?
+        {
+            CompoundType::Array(a) => Ok(ConstantValue::Array(ConstantArray {
+                ty,
+                elem_ty: a.inner,
+                elems,
+                is_string,
+            })),
+            CompoundType::Struct(s) => Ok(ConstantValue::Struct(ConstantStruct {
+                ty,
+                elems_ty: s.elems.clone(),
+                elems,
+                is_packed: s.is_packed,
+            })),
+            _ => Err(Error::Unsupported("vector constant".to_string())),
+        }
+    }
+}
+
+pub fn int(ty: Type, v: i64) -> Result<ConstantValue, Error> {
+    Ok(ConstantValue::Int(match ty {
+        I1 => ConstantInt::Int1(v & 1 == 1),
+        I8 => ConstantInt::Int8(v as i8),
+        I16 => ConstantInt::Int16(v as i16),
+        I32 => ConstantInt::Int32(v as i32),
+        I64 => ConstantInt::Int64(v),
+        _ => {
+            return Err(Error::Unsupported(
+                "integer constant of this width".to_string(),
+            ))
+        }
+    }))
+}
+
+pub fn cast_opcode(code: u64) -> Option<Opcode> {
+    Some(match code {
+        0 => Opcode::Trunc,
+        1 => Opcode::Zext,
+        2 => Opcode::Sext,
+        3 => Opcode::FPToUI,
+        4 => Opcode::FPToSI,
+        5 => Opcode::UIToFP,
+        6 => Opcode::SIToFP,
+        7 => Opcode::FPTrunc,
+        8 => Opcode::FPExt,
+        9 => Opcode::PtrToInt,
+        10 => Opcode::IntToPtr,
+        11 => Opcode::Bitcast,
+        _ => return None,
+    })
+}
+
+#[test]
+fn self_referential_constant() {
+    use crate::ir::types::ArrayType;
+
+    let types = Types::new();
+    let ty = types.base_mut().array(ArrayType::new(I32, 1));
+    let values = [ValueEntry::Constant(
+        ty,
+        Record {
+            code: AGGREGATE,
+            ops: vec![0],
+            blob: None,
+        },
+    )];
+    let ctx = Context {
+        types: &types,
+        type_list: &[],
+        values: &values,
+    };
+    assert!(matches!(ctx.constant(0), Err(Error::Malformed(_))));
+}
@@ -0,0 +1,887 @@
+use super::{
+    attributes::AttributeTable,
+    bitstream::{decode_signed, Block, Entry, Record},
+    constants::{self, cast_opcode, ValueEntry},
+    metadata::{Attachment, MetadataTable},
+    types::function_type,
+    Error, CONSTANTS_BLOCK_ID, METADATA_ATTACHMENT_ID, METADATA_BLOCK_ID, VALUE_SYMTAB_BLOCK_ID,
+};
+use crate::ir::{
+    function::{
+        basic_block::BasicBlockId,
+        data::Data,
+        instruction::{
+            Alloca, Br, Call, Cast, CondBr, ExtractValue, FCmp, FCmpCond, FastMathFlags,
+            FloatBinary, FloatUnary, GetElementPtr, ICmp, ICmpCond, InsertValue, Instruction,
+            InstructionId, IntBinary, Invoke, LandingPad, Load, Opcode, Operand, Phi, Resume, Ret,
+            Select, Store, Switch,
+        },
+        Function,
+    },
+    module::{attributes::Attribute, name::Name},
+    types::{Type, Types, I1, I32, VOID},
+    value::{ArgumentValue, ConstantInt, ConstantValue, Value, ValueId},
+};
+use rustc_hash::FxHashMap;
+
+const DECLAREBLOCKS: u32 = 1;
+const BINOP: u32 = 2;
+const CAST: u32 = 3;
+const RET: u32 = 10;
+const BR: u32 = 11;
+const SWITCH: u32 = 12;
+const INVOKE: u32 = 13;
+const UNREACHABLE: u32 = 15;
+const PHI: u32 = 16;
+const ALLOCA: u32 = 19;
+const LOAD: u32 = 20;
+const EXTRACTVAL: u32 = 26;
+const INSERTVAL: u32 = 27;
+const CMP2: u32 = 28;
+const VSELECT: u32 = 29;
+const CALL: u32 = 34;
+const RESUME: u32 = 39;
+const GEP: u32 = 43;
+const STORE: u32 = 44;
+const LANDINGPAD: u32 = 47;
+const UNOP: u32 = 56;
+
+const VST_ENTRY: u32 = 1;
+const VST_BBENTRY: u32 = 2;
+
+const CALL_EXPLICIT_TYPE: u64 = 1 << 15;
+const CALL_FMF: u64 = 1 << 17;
+const INVOKE_EXPLICIT_TYPE: u64 = 1 << 13;
+
+/// Metadata attached to a function and its instructions, which is resolved once the whole module
+/// has been read.
+#[derive(Default)]
+pub struct Attachments {
+    pub func: Attachment,
+    pub insts: Vec<(InstructionId, Attachment)>,
+}
+
+struct Decoder<'a> {
+    types: &'a Types,
+    type_list: &'a [Type],
+    values: &'a mut Vec<ValueEntry>,
+    attrs: &'a AttributeTable,
+    data: &'a mut Data,
+    blocks: Vec<BasicBlockId>,
+    cur_block: BasicBlockId,
+    /// Placeholders for the values used before their definition.
+    forward: FxHashMap<u64, (ValueId, InstructionId)>,
+}
+
+/// Decodes a FUNCTION_BLOCK into `func`, whose prototype must already be set.
+/// The entries appended to `values` and the metadata ids local to the function are discarded on
+/// return.
+pub fn parse(
+    block: &Block,
+    func: &mut Function,
+    type_list: &[Type],
+    values: &mut Vec<ValueEntry>,
+    attrs: &AttributeTable,
+    metadata: &mut MetadataTable,
+) -> Result<Attachments, Error> {
+    let num_values = values.len();
+    let num_metadata_ids = metadata.enter();
+    let result = parse_body(block, func, type_list, values, attrs, metadata);
+    values.truncate(num_values);
+    metadata.leave(num_metadata_ids);
+    result
+}
+
+fn parse_body(
+    block: &Block,
+    func: &mut Function,
+    type_list: &[Type],
+    values: &mut Vec<ValueEntry>,
+    attrs: &AttributeTable,
+    metadata: &mut MetadataTable,
+) -> Result<Attachments, Error> {
+    let first_arg = values.len();
+    let mut args = vec![];
+    for (i, param) in func.params.iter().enumerate() {
+        let arg = func
+            .data
+            .create_value(Value::Argument(ArgumentValue::new(i, param.ty, None)));
+        values.push(ValueEntry::Local(arg, param.ty));
+        args.push(arg);
+    }
+
+    let dummy_block = func.data.create_block();
+    let mut d = Decoder {
+        types: &func.types,
+        type_list,
+        values,
+        attrs,
+        data: &mut func.data,
+        blocks: vec![],
+        cur_block: dummy_block,
+        forward: FxHashMap::default(),
+    };
+    let mut block_index = 0;
+    let mut insts = vec![];
+    let mut value_ids = FxHashMap::default();
+    let mut symtab = None;
+    let mut attachments = None;
+
+    for entry in &block.entries {
+        let record = match entry {
+            Entry::Block(block) => {
+                match block.id {
+                    CONSTANTS_BLOCK_ID => constants::parse(block, type_list, d.values)?,
+                    METADATA_BLOCK_ID => metadata.parse(block, &d.ctx())?,
+                    METADATA_ATTACHMENT_ID => attachments = Some(block),
+                    VALUE_SYMTAB_BLOCK_ID => symtab = Some(block),
+                    _ => {}
+                }
+                continue;
+            }
+            Entry::Record(record) => record,
+        };
+
+        if record.code == DECLAREBLOCKS {
+            for _ in 0..record.op(0)? {
+                let block = d.data.create_block();
+                func.layout.append_block(block);
+                d.blocks.push(block);
+            }
+            d.cur_block = *d
+                .blocks
+                .first()
+                .ok_or(Error::Malformed("function without blocks"))?;
+            continue;
+        }
+
+        if d.cur_block == dummy_block {
+            return Err(Error::Malformed("instruction outside blocks"));
+        }
+
+        let value_id = d.next_id();
+        let (inst, value_ty) = d.instruction(record)?;
+        let is_terminator = inst.opcode.is_terminator();
+        let id = match value_ty {
+            Some(ty) => {
+                let (value, id) = match d.forward.remove(&value_id) {
+                    Some((value, id)) => {
+                        d.data.replace_inst(id, inst);
+                        (value, id)
+                    }
+                    None => {
+                        let id = d.data.create_inst(inst);
+                        (d.data.create_value(Value::Instruction(id)), id)
+                    }
+                };
+                d.values.push(ValueEntry::Local(value, ty));
+                value_ids.insert(id, value_id);
+                id
+            }
+            None => d.data.create_inst(inst),
+        };
+        func.layout.append_inst(id, d.cur_block);
+        insts.push(id);
+
+        if is_terminator {
+            block_index += 1;
+            if let Some(&next) = d.blocks.get(block_index) {
+                d.cur_block = next;
+            }
+        }
+    }
+
+    let Decoder {
+        blocks, forward, ..
+    } = d;
+    if !forward.is_empty() {
+        return Err(Error::Malformed("reference to an undefined value"));
+    }
+
+    // Name arguments, blocks and instructions. Unnamed ones are numbered just like in the textual
+    // form.
+    let mut value_names = FxHashMap::default();
+    let mut block_names = FxHashMap::default();
+    for record in symtab.iter().flat_map(|b| b.records()) {
+        match record.code {
+            VST_ENTRY => {
+                value_names.insert(record.op(0)?, record.string(1));
+            }
+            VST_BBENTRY => {
+                block_names.insert(record.op(0)?, record.string(1));
+            }
+            _ => {}
+        }
+    }
+    let mut next_number = 0;
+    let mut name = |name: Option<&String>| match name {
+        Some(name) => Name::Name(name.clone()),
+        None => {
+            next_number += 1;
+            Name::Number(next_number - 1)
+        }
+    };
+    for (i, (param, arg)) in func.params.iter_mut().zip(args).enumerate() {
+        param.name = name(value_names.get(&((first_arg + i) as u64)));
+        func.data.values[arg] =
+            Value::Argument(ArgumentValue::new(i, param.ty, Some(param.name.clone())));
+    }
+    for (i, &block) in blocks.iter().enumerate() {
+        func.data.basic_blocks[block].name = Some(name(block_names.get(&(i as u64))));
+        for inst in func.layout.inst_iter(block) {
+            if let Some(value_id) = value_ids.get(&inst) {
+                func.data.instructions[inst].dest = Some(name(value_names.get(value_id)));
+            }
+        }
+    }
+
+    set_blocks_info(func);
+
+    let mut result = Attachments::default();
+    if let Some(block) = attachments {
+        let (func_attachment, inst_attachments) = metadata.parse_attachments(block)?;
+        result.func = func_attachment;
+        let mut inst_attachments: Vec<_> = inst_attachments.into_iter().collect();
+        inst_attachments.sort_by_key(|(i, _)| *i);
+        for (i, attachment) in inst_attachments {
+            let inst = *insts
+                .get(i)
+                .ok_or(Error::Malformed("invalid instruction id"))?;
+            result.insts.push((inst, attachment))
+        }
+    }
+    Ok(result)
+}
+
+impl<'a> Decoder<'a> {
+    fn ctx(&self) -> constants::Context<'_> {
+        constants::Context {
+            types: self.types,
+            type_list: self.type_list,
+            values: self.values,
+        }
+    }
+
+    fn next_id(&self) -> u64 {
+        self.values.len() as u64
+    }
+
+    fn ty(&self, id: u64) -> Result<Type, Error> {
+        self.ctx().ty(id)
+    }
+
+    fn block(&self, id: u64) -> Result<BasicBlockId, Error> {
+        self.blocks
+            .get(id as usize)
+            .copied()
+            .ok_or(Error::Malformed("invalid basic block id"))
+    }
+
+    /// Reads a value id relative to the current instruction.
+    fn operand(&self, record: &Record, i: &mut usize) -> Result<u64, Error> {
+        let v = record.op(*i)?;
+        *i += 1;
+        Ok((self.next_id() as u32).wrapping_sub(v as u32) as u64)
+    }
+
+    /// Reads a value id relative to the current instruction, followed by its type if the value
+    /// is not defined yet.
+    fn operand_with_type(&self, record: &Record, i: &mut usize) -> Result<(u64, Type), Error> {
+        let id = self.operand(record, i)?;
+        if id < self.next_id() {
+            return Ok((id, self.ctx().type_of(id)?));
+        }
+        let ty = self.ty(record.op(*i)?)?;
+        *i += 1;
+        Ok((id, ty))
+    }
+
+    /// Returns the value for the absolute value id `id`. Constants are created on every use.
+    fn value(&mut self, id: u64) -> Result<ValueId, Error> {
+        let value = match self.values.get(id as usize) {
+            Some(ValueEntry::Local(value, _)) => return Ok(*value),
+            Some(ValueEntry::Global(..)) | Some(ValueEntry::Constant(..)) => {
+                match self.ctx().inline_asm(id) {
+                    Some(asm) => Value::InlineAsm(asm),
+                    None => Value::Constant(self.ctx().constant(id)?),
+                }
+            }
+            None => {
+                if let Some((value, _)) = self.forward.get(&id) {
+                    return Ok(*value);
+                }
+                let dummy = self
+                    .data
+                    .create_inst(Opcode::Invalid.with_block(self.cur_block));
+                let value = self.data.create_value(Value::Instruction(dummy));
+                self.forward.insert(id, (value, dummy));
+                return Ok(value);
+            }
+        };
+        Ok(self.data.create_value(value))
+    }
+
+    fn value_type_pair(
+        &mut self,
+        record: &Record,
+        i: &mut usize,
+    ) -> Result<(ValueId, Type), Error> {
+        let (id, ty) = self.operand_with_type(record, i)?;
+        Ok((self.value(id)?, ty))
+    }
+
+    fn relative_value(&mut self, record: &Record, i: &mut usize) -> Result<ValueId, Error> {
+        let id = self.operand(record, i)?;
+        self.value(id)
+    }
+
+    fn i32_value(&mut self, v: u64) -> ValueId {
+        self.data
+            .create_value(Value::Constant(ConstantValue::Int(ConstantInt::Int32(
+                v as i32,
+            ))))
+    }
+
+    fn index_of(&self, value: ValueId) -> usize {
+        match self.data.value_ref(value) {
+            Value::Constant(ConstantValue::Int(i)) => i.cast_to_usize(),
+            _ => 0,
+        }
+    }
+
+    /// Decodes an instruction record. Returns the instruction and the type of its result if the
+    /// instruction defines a value.
+    fn instruction(&mut self, record: &Record) -> Result<(Instruction, Option<Type>), Error> {
+        let block = self.cur_block;
+        let mut i = 0;
+        let (inst, value_ty) = match record.code {
+            BINOP => {
+                let (lhs, ty) = self.value_type_pair(record, &mut i)?;
+                let rhs = self.relative_value(record, &mut i)?;
+                let opcode = record.op(i)?;
+                let flags = record.ops.get(i + 1).copied().unwrap_or(0);
+                let inst = if ty.is_floating_point() {
+                    let opcode = match opcode {
+                        0 => Opcode::FAdd,
+                        1 => Opcode::FSub,
+                        2 => Opcode::FMul,
+                        4 => Opcode::FDiv,
+                        6 => Opcode::FRem,
+                        _ => return Err(Error::Malformed("invalid floating-point binary opcode")),
+                    };
+                    opcode
+                        .with_block(block)
+                        .with_operand(Operand::FloatBinary(FloatBinary {
+                            ty,
+                            fmf: fast_math_flags(flags),
+                            args: [lhs, rhs],
+                        }))
+                } else {
+                    let opcode = match opcode {
+                        0 => Opcode::Add,
+                        1 => Opcode::Sub,
+                        2 => Opcode::Mul,
+                        3 => Opcode::UDiv,
+                        4 => Opcode::SDiv,
+                        5 => Opcode::URem,
+                        6 => Opcode::SRem,
+                        7 => Opcode::Shl,
+                        8 => Opcode::LShr,
+                        9 => Opcode::AShr,
+                        10 => Opcode::And,
+                        11 => Opcode::Or,
+                        12 => Opcode::Xor,
+                        _ => return Err(Error::Malformed("invalid binary opcode")),
+                    };
+                    // Bit 0 is `exact` for divisions and right shifts, or `nuw` for the others.
+                    let exact_op = matches!(
+                        opcode,
+                        Opcode::UDiv | Opcode::SDiv | Opcode::LShr | Opcode::AShr
+                    );
+                    let overflow_op = matches!(
+                        opcode,
+                        Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Shl
+                    );
+                    opcode
+                        .with_block(block)
+                        .with_operand(Operand::IntBinary(IntBinary {
+                            ty,
+                            nuw: overflow_op && flags & 1 != 0,
+                            nsw: overflow_op && flags & 2 != 0,
+                            exact: exact_op && flags & 1 != 0,
+                            args: [lhs, rhs],
+                        }))
+                };
+                (inst.with_ty(ty), Some(ty))
+            }
+            UNOP => {
+                let (arg, ty) = self.value_type_pair(record, &mut i)?;
+                if record.op(i)? != 0 {
+                    return Err(Error::Malformed("invalid unary opcode"));
+                }
+                let fmf = fast_math_flags(record.ops.get(i + 1).copied().unwrap_or(0));
+                let inst = Opcode::FNeg
+                    .with_block(block)
+                    .with_operand(Operand::FloatUnary(FloatUnary { ty, fmf, arg }))
+                    .with_ty(ty);
+                (inst, Some(ty))
+            }
+            CAST => {
+                let (arg, from) = self.value_type_pair(record, &mut i)?;
+                let to = self.ty(record.op(i)?)?;
+                let opcode = cast_opcode(record.op(i + 1)?)
+                    .ok_or_else(|| Error::Unsupported("cast opcode".to_string()))?;
+                let inst = opcode
+                    .with_block(block)
+                    .with_operand(Operand::Cast(Cast {
+                        tys: [from, to],
+                        arg,
+                    }))
+                    .with_ty(to);
+                (inst, Some(to))
+            }
+            GEP => {
+                let inbounds = record.op(0)? != 0;
+                let mut tys = vec![self.ty(record.op(1)?)?];
+                let mut args = vec![];
+                i = 2;
+                while i < record.ops.len() {
+                    let (arg, ty) = self.value_type_pair(record, &mut i)?;
+                    tys.push(ty);
+                    args.push(arg);
+                }
+                if args.is_empty() {
+                    return Err(Error::Malformed("getelementptr without operands"));
+                }
+                let ty = if self.types.is_opaque_pointer(tys[1]) {
+                    tys[1]
+                } else {
+                    let indices: Vec<_> = args[1..].iter().map(|&a| self.index_of(a)).collect();
+                    let inner = self
+                        .types
+                        .base()
+                        .element_at_(tys[1], indices.into_iter())
+                        .ok_or(Error::Malformed("invalid getelementptr indices"))?;
+                    self.types.base_mut().pointer(inner)
+                };
+                let inst = Opcode::GetElementPtr
+                    .with_block(block)
+                    .with_operand(Operand::GetElementPtr(GetElementPtr {
+                        inbounds,
+                        tys,
+                        args,
+                    }))
+                    .with_ty(ty);
+                (inst, Some(ty))
+            }
+            EXTRACTVAL => {
+                let (agg, agg_ty) = self.value_type_pair(record, &mut i)?;
+                let indices = &record.ops[i..];
+                let ty = self
+                    .types
+                    .base()
+                    .element_at_(agg_ty, indices.iter().map(|&i| i as usize))
+                    .ok_or(Error::Malformed("invalid extractvalue indices"))?;
+                let mut args = vec![agg];
+                args.extend(indices.iter().map(|&idx| self.i32_value(idx)));
+                let inst = Opcode::ExtractValue
+                    .with_block(block)
+                    .with_operand(Operand::ExtractValue(ExtractValue { ty: agg_ty, args }))
+                    .with_ty(ty);
+                (inst, Some(ty))
+            }
+            INSERTVAL => {
+                let (agg, agg_ty) = self.value_type_pair(record, &mut i)?;
+                let (elt, elt_ty) = self.value_type_pair(record, &mut i)?;
+                let mut args = vec![agg, elt];
+                for &idx in &record.ops[i..] {
+                    args.push(self.i32_value(idx))
+                }
+                let inst = Opcode::InsertValue
+                    .with_block(block)
+                    .with_operand(Operand::InsertValue(InsertValue {
+                        tys: [agg_ty, elt_ty],
+                        args,
+                    }))
+                    .with_ty(agg_ty);
+                (inst, Some(agg_ty))
+            }
+            CMP2 => {
+                let (lhs, ty) = self.value_type_pair(record, &mut i)?;
+                let rhs = self.relative_value(record, &mut i)?;
+                let pred = record.op(i)?;
+                let inst = match (fcmp_cond(pred), icmp_cond(pred)) {
+                    (Some(cond), _) => {
+                        Opcode::FCmp
+                            .with_block(block)
+                            .with_operand(Operand::FCmp(FCmp {
+                                ty,
+                                fmf: fast_math_flags(record.ops.get(i + 1).copied().unwrap_or(0)),
+                                args: [lhs, rhs],
+                                cond,
+                            }))
+                    }
+                    (_, Some(cond)) => {
+                        Opcode::ICmp
+                            .with_block(block)
+                            .with_operand(Operand::ICmp(ICmp {
+                                ty,
+                                args: [lhs, rhs],
+                                cond,
+                            }))
+                    }
+                    _ => return Err(Error::Malformed("invalid comparison predicate")),
+                };
+                (inst.with_ty(I1), Some(I1))
+            }
+            VSELECT => {
+                // [ty, opval, opval, predty, pred]
+                let (then_val, ty) = self.value_type_pair(record, &mut i)?;
+                let else_val = self.relative_value(record, &mut i)?;
+                let (cond, cond_ty) = self.value_type_pair(record, &mut i)?;
+                let inst = Opcode::Select
+                    .with_block(block)
+                    .with_operand(Operand::Select(Select {
+                        tys: [cond_ty, ty],
+                        args: [cond, then_val, else_val],
+                    }))
+                    .with_ty(ty);
+                (inst, Some(ty))
+            }
+            ALLOCA => {
+                // [allocated type, size type, size, align]
+                let flags = record.op(3)?;
+                let ty = self.ty(record.op(0)?)?;
+                let ty = if flags & (1 << 6) != 0 {
+                    ty
+                } else {
+                    self.types
+                        .get_element(ty)
+                        .ok_or(Error::Malformed("alloca of non-pointer type"))?
+                };
+                let num_elements = self.ctx().constant(record.op(2)?)?;
+                let align = decode_align((flags & 0x1f) | ((flags >> 8) & 0x7) << 5)?;
+                let ptr_ty = self.types.base_mut().pointer(ty);
+                let inst = Opcode::Alloca
+                    .with_block(block)
+                    .with_operand(Operand::Alloca(Alloca {
+                        tys: [ty, I32],
+                        num_elements,
+                        align,
+                    }))
+                    .with_ty(ptr_ty);
+                (inst, Some(ptr_ty))
+            }
+            LOAD => {
+                let (addr, addr_ty) = self.value_type_pair(record, &mut i)?;
+                let ty = if record.ops.len() - i == 3 {
+                    i += 1;
+                    self.ty(record.op(i - 1)?)?
+                } else {
+                    self.types
+                        .get_element(addr_ty)
+                        .ok_or(Error::Malformed("load from non-pointer type"))?
+                };
+                let inst = Opcode::Load
+                    .with_block(block)
+                    .with_operand(Operand::Load(Load {
+                        tys: [ty, addr_ty],
+                        addr,
+                        align: decode_align(record.op(i)?)?,
+                    }))
+                    .with_ty(ty);
+                (inst, Some(ty))
+            }
+            STORE => {
+                let (ptr, ptr_ty) = self.value_type_pair(record, &mut i)?;
+                let (val, val_ty) = self.value_type_pair(record, &mut i)?;
+                let inst = Opcode::Store
+                    .with_block(block)
+                    .with_operand(Operand::Store(Store {
+                        tys: [val_ty, ptr_ty],
+                        args: [val, ptr],
+                        align: decode_align(record.op(i)?)?,
+                    }));
+                (inst, None)
+            }
+            PHI => {
+                let ty = self.ty(record.op(0)?)?;
+                let mut args = vec![];
+                let mut blocks = vec![];
+                for pair in record.ops[1..].chunks_exact(2) {
+                    let id = (self.next_id() as i64).wrapping_sub(decode_signed(pair[0]));
+                    args.push(self.value(id as u64)?);
+                    blocks.push(self.block(pair[1])?);
+                }
+                let inst = Opcode::Phi
+                    .with_block(block)
+                    .with_operand(Operand::Phi(Phi { ty, args, blocks }))
+                    .with_ty(ty);
+                (inst, Some(ty))
+            }
+            CALL | INVOKE => return self.call(record),
+            LANDINGPAD => {
+                let ty = self.ty(record.op(0)?)?;
+                let cleanup = record.op(1)? != 0;
+                let mut catches = vec![];
+                i = 3;
+                for _ in 0..record.op(2)? {
+                    if record.op(i)? != 0 {
+                        return Err(Error::Unsupported("landingpad filter clause".to_string()));
+                    }
+                    i += 1;
+                    let (arg, ty) = self.value_type_pair(record, &mut i)?;
+                    catches.push((ty, arg));
+                }
+                let inst = Opcode::LandingPad
+                    .with_block(block)
+                    .with_operand(Operand::LandingPad(LandingPad {
+                        ty,
+                        catches,
+                        cleanup,
+                    }))
+                    .with_ty(ty);
+                (inst, Some(ty))
+            }
+            RESUME => {
+                let (arg, ty) = self.value_type_pair(record, &mut i)?;
+                let inst = Opcode::Resume
+                    .with_block(block)
+                    .with_operand(Operand::Resume(Resume { ty, arg }));
+                (inst, None)
+            }
+            RET => {
+                let ret = if record.ops.is_empty() {
+                    Ret {
+                        ty: VOID,
+                        val: None,
+                    }
+                } else {
+                    let (val, ty) = self.value_type_pair(record, &mut i)?;
+                    Ret { ty, val: Some(val) }
+                };
+                let inst = Opcode::Ret
+                    .with_block(block)
+                    .with_operand(Operand::Ret(ret));
+                (inst, None)
+            }
+            BR => {
+                let inst = if record.ops.len() == 1 {
+                    Opcode::Br.with_block(block).with_operand(Operand::Br(Br {
+                        block: self.block(record.op(0)?)?,
+                    }))
+                } else {
+                    let blocks = [self.block(record.op(0)?)?, self.block(record.op(1)?)?];
+                    i = 2;
+                    let arg = self.relative_value(record, &mut i)?;
+                    Opcode::CondBr
+                        .with_block(block)
+                        .with_operand(Operand::CondBr(CondBr { arg, blocks }))
+                };
+                (inst, None)
+            }
+            SWITCH => {
+                let cond_ty = self.ty(record.op(0)?)?;
+                i = 1;
+                let cond = self.relative_value(record, &mut i)?;
+                let mut tys = vec![cond_ty];
+                let mut args = vec![cond];
+                let mut blocks = vec![self.block(record.op(2)?)?];
+                for pair in record.ops[3..].chunks_exact(2) {
+                    tys.push(cond_ty);
+                    args.push(self.value(pair[0])?);
+                    blocks.push(self.block(pair[1])?);
+                }
+                let inst = Opcode::Switch
+                    .with_block(block)
+                    .with_operand(Operand::Switch(Switch { tys, args, blocks }));
+                (inst, None)
+            }
+            UNREACHABLE => (
+                Opcode::Unreachable
+                    .with_block(block)
+                    .with_operand(Operand::Unreachable),
+                None,
+            ),
+            code => return Err(Error::Unsupported(format!("instruction code {}", code))),
+        };
+        Ok((inst, value_ty))
+    }
+
+    /// Decodes CALL and INVOKE, which share most of their layouts:
+    /// CALL: [attrs, cc, fmf?, fnty?, callee, args...]
+    /// INVOKE: [attrs, cc, normal, unwind, fnty?, callee, args...]
+    fn call(&mut self, record: &Record) -> Result<(Instruction, Option<Type>), Error> {
+        let is_invoke = record.code == INVOKE;
+        let list = record.op(0)?;
+        let cc = record.op(1)?;
+        let mut i = 2;
+        let mut blocks = vec![];
+        let explicit_type = if is_invoke {
+            blocks.push(self.block(record.op(2)?)?);
+            blocks.push(self.block(record.op(3)?)?);
+            i = 4;
+            cc & INVOKE_EXPLICIT_TYPE != 0
+        } else {
+            if cc & CALL_FMF != 0 {
+                i += 1; // Fast-math flags on calls aren't supported in the IR
+            }
+            cc & CALL_EXPLICIT_TYPE != 0
+        };
+        let fnty = if explicit_type {
+            i += 1;
+            Some(self.ty(record.op(i - 1)?)?)
+        } else {
+            None
+        };
+        let (callee_id, callee_ty) = self.operand_with_type(record, &mut i)?;
+        let fnty = match fnty {
+            Some(fnty) => fnty,
+            None => self
+                .types
+                .get_element(callee_ty)
+                .ok_or(Error::Malformed("callee of non-pointer type"))?,
+        };
+        let func_ty = function_type(self.types, fnty)?;
+        // The textual form shows the whole function type only if the callee is variadic.
+        let ty = if func_ty.is_var_arg {
+            fnty
+        } else {
+            func_ty.ret
+        };
+
+        let callee = match self.values.get(callee_id as usize) {
+            Some(ValueEntry::Global(name, _)) => self
+                .data
+                .create_value(Value::Constant(ConstantValue::GlobalRef(name.clone(), ty))),
+            _ => self.value(callee_id)?,
+        };
+        let mut args = vec![callee];
+        let mut tys = vec![ty];
+        for &param_ty in &func_ty.params {
+            args.push(self.relative_value(record, &mut i)?);
+            tys.push(param_ty);
+        }
+        if func_ty.is_var_arg {
+            while i < record.ops.len() {
+                let (arg, ty) = self.value_type_pair(record, &mut i)?;
+                args.push(arg);
+                tys.push(ty);
+            }
+        }
+
+        let mut list = self.attrs.list(list, |id| self.ty(id))?;
+        let param_attrs = (0..args.len() as u64 - 1)
+            .map(|i| list.params.remove(&i).unwrap_or_default())
+            .collect();
+        let func_attrs = list
+            .func
+            .map_or(vec![], |grp| vec![Attribute::Ref(grp as u32)]);
+        let operand = if is_invoke {
+            Operand::Invoke(Invoke {
+                args,
+                tys,
+                param_attrs,
+                ret_attrs: list.ret,
+                func_attrs,
+                blocks,
+            })
+        } else {
+            Operand::Call(Call {
+                args,
+                tys,
+                param_attrs,
+                ret_attrs: list.ret,
+                func_attrs,
+            })
+        };
+        let opcode = if is_invoke {
+            Opcode::Invoke
+        } else {
+            Opcode::Call
+        };
+        let inst = opcode
+            .with_block(self.cur_block)
+            .with_operand(operand)
+            .with_ty(ty);
+        Ok((inst, (!func_ty.ret.is_void()).then_some(func_ty.ret)))
+    }
+}
+
+fn set_blocks_info(func: &mut Function) {
+    for block_id in func.layout.block_iter() {
+        let br = match func.layout.block_node(block_id).last_inst() {
+            Some(br) => &func.data.instructions[*br],
+            None => continue,
+        };
+        if !br.opcode.is_terminator() {
+            continue;
+        }
+        for &block in br.operand.blocks() {
+            func.data.basic_blocks[br.parent].succs.insert(block);
+            func.data.basic_blocks[block].preds.insert(br.parent);
+        }
+    }
+}
+
+/// Decodes an alignment stored as `log2(align) + 1`, where `0` means no alignment is specified.
+pub fn decode_align(v: u64) -> Result<u32, Error> {
+    match v {
+        0 => Ok(0),
+        1..=32 => Ok(1 << (v - 1)),
+        _ => Err(Error::Malformed("alignment too large")),
+    }
+}
+
+fn fast_math_flags(v: u64) -> FastMathFlags {
+    if v & 1 != 0 {
+        return FastMathFlags::fast();
+    }
+    FastMathFlags {
+        nnan: v & (1 << 1) != 0,
+        ninf: v & (1 << 2) != 0,
+        nsz: v & (1 << 3) != 0,
+        arcp: v & (1 << 4) != 0,
+        contract: v & (1 << 5) != 0,
+        afn: v & (1 << 6) != 0,
+        reassoc: v & (1 << 7) != 0,
+    }
+}
+
+fn icmp_cond(pred: u64) -> Option<ICmpCond> {
+    Some(match pred {
+        32 => ICmpCond::Eq,
+        33 => ICmpCond::Ne,
+        34 => ICmpCond::Ugt,
+        35 => ICmpCond::Uge,
+        36 => ICmpCond::Ult,
+        37 => ICmpCond::Ule,
+        38 => ICmpCond::Sgt,
+        39 => ICmpCond::Sge,
+        40 => ICmpCond::Slt,
+        41 => ICmpCond::Sle,
+        _ => return None,
+    })
+}
+
+fn fcmp_cond(pred: u64) -> Option<FCmpCond> {
+    Some(match pred {
+        0 => FCmpCond::False,
+        1 => FCmpCond::Oeq,
+        2 => FCmpCond::Ogt,
+        3 => FCmpCond::Oge,
+        4 => FCmpCond::Olt,
+        5 => FCmpCond::Ole,
+        6 => FCmpCond::One,
+        7 => FCmpCond::Ord,
+        8 => FCmpCond::Uno,
+        9 => FCmpCond::Ueq,
+        10 => FCmpCond::Ugt,
+        11 => FCmpCond::Uge,
+        12 => FCmpCond::Ult,
+        13 => FCmpCond::Ule,
+        14 => FCmpCond::Une,
+        15 => FCmpCond::True,
+        _ => return None,
+    })
+}
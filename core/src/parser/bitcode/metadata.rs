@@ -0,0 +1,261 @@
+use super::{
+    bitstream::{vbr6_array, Block},
+    constants, Error,
+};
+use crate::ir::{
+    module::{metadata::Metadata, name::Name},
+    value::ConstantValue,
+};
+use rustc_hash::FxHashMap;
+
+const STRING_OLD: u32 = 1;
+const VALUE: u32 = 2;
+const NODE: u32 = 3;
+const NAME: u32 = 4;
+const DISTINCT_NODE: u32 = 5;
+const KIND: u32 = 6;
+const NAMED_NODE: u32 = 10;
+const ATTACHMENT: u32 = 11;
+const STRINGS: u32 = 35;
+const GLOBAL_DECL_ATTACHMENT: u32 = 36;
+const INDEX_OFFSET: u32 = 38;
+const INDEX: u32 = 39;
+
+/// A metadata slot, addressed by its id in the bitcode.
+#[derive(Debug)]
+enum Slot {
+    String(String),
+    Value(ConstantValue),
+    Node(Vec<usize>, bool),
+}
+
+/// Metadata read from METADATA blocks.
+///
+/// The ids in the bitcode don't match the numbers that appear in the textual form (`!N`), so
+/// nodes are numbered in [`MetadataTable::finish`] once all the references to them are known.
+/// Metadata ids are only valid until the end of the function they appear in, hence `ids` maps
+/// the ids currently in scope to `slots`, which keeps every slot ever read.
+#[derive(Debug, Default)]
+pub struct MetadataTable {
+    slots: Vec<Slot>,
+    ids: Vec<usize>,
+    named: Vec<(String, Vec<usize>)>,
+    kinds: FxHashMap<u64, String>,
+}
+
+/// Metadata attached to an instruction, as `(kind, slot)` pairs.
+pub type Attachment = Vec<(String, usize)>;
+
+/// The number (`!N`) assigned to each slot.
+pub type Numbers = FxHashMap<usize, usize>;
+
+impl MetadataTable {
+    /// Parses a METADATA block, either at the module level or in a function block.
+    pub fn parse(&mut self, block: &Block, ctx: &constants::Context) -> Result<(), Error> {
+        let first_slot = self.slots.len();
+        let first_named = self.named.len();
+        let mut name = None;
+        for record in block.records() {
+            if matches!(record.code, INDEX_OFFSET | INDEX) {
+                continue;
+            }
+            if !matches!(
+                record.code,
+                NAME | NAMED_NODE | KIND | GLOBAL_DECL_ATTACHMENT
+            ) {
+                // Every other record defines metadata ids.
+                self.ids.push(self.slots.len());
+            }
+            match record.code {
+                STRING_OLD => self.slots.push(Slot::String(record.string(0))),
+                STRINGS => {
+                    let count = record.op(0)? as usize;
+                    let offset = record.op(1)? as usize;
+                    let blob = record
+                        .blob
+                        .as_ref()
+                        .ok_or(Error::Malformed("METADATA_STRINGS without blob"))?;
+                    let lens = vbr6_array(blob, count)?;
+                    let mut chars = blob.get(offset..).ok_or(Error::UnexpectedEof)?;
+                    // The first id has already been pushed.
+                    self.ids.pop();
+                    for len in lens {
+                        self.ids.push(self.slots.len());
+                        let (s, rest) = chars.split_at((len as usize).min(chars.len()));
+                        self.slots
+                            .push(Slot::String(String::from_utf8_lossy(s).into_owned()));
+                        chars = rest;
+                    }
+                }
+                VALUE => {
+                    let val = ctx.constant(record.op(1)?)?;
+                    self.slots.push(Slot::Value(val))
+                }
+                NODE | DISTINCT_NODE => {
+                    let ops = record
+                        .ops
+                        .iter()
+                        .map(|&id| match id {
+                            0 => Err(Error::Unsupported("null metadata operand".to_string())),
+                            id => Ok(id as usize - 1),
+                        })
+                        .collect::<Result<_, _>>()?;
+                    self.slots
+                        .push(Slot::Node(ops, record.code == DISTINCT_NODE))
+                }
+                NAME => name = Some(record.string(0)),
+                NAMED_NODE => {
+                    let name = name
+                        .take()
+                        .ok_or(Error::Malformed("named metadata without name"))?;
+                    let ops = record.ops.iter().map(|&id| id as usize).collect();
+                    self.named.push((name, ops))
+                }
+                KIND => {
+                    self.kinds.insert(record.op(0)?, record.string(1));
+                }
+                GLOBAL_DECL_ATTACHMENT => {}
+                code => {
+                    return Err(Error::Unsupported(format!("metadata code {}", code)));
+                }
+            }
+        }
+
+        // Operands may refer to ids defined later in the block, so they are resolved at the end.
+        for slot in &mut self.slots[first_slot..] {
+            if let Slot::Node(ops, _) = slot {
+                for op in ops {
+                    *op = *self
+                        .ids
+                        .get(*op)
+                        .ok_or(Error::Malformed("invalid metadata id"))?;
+                }
+            }
+        }
+        for (_, ops) in &mut self.named[first_named..] {
+            for op in ops {
+                *op = *self
+                    .ids
+                    .get(*op)
+                    .ok_or(Error::Malformed("invalid metadata id"))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses a METADATA_KIND block.
+    pub fn parse_kinds(&mut self, block: &Block) -> Result<(), Error> {
+        for record in block.records() {
+            if record.code == KIND {
+                self.kinds.insert(record.op(0)?, record.string(1));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the number of metadata ids in scope, to be passed to [`MetadataTable::leave`]
+    /// when the function being decoded ends.
+    pub fn enter(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn leave(&mut self, num_ids: usize) {
+        self.ids.truncate(num_ids)
+    }
+
+    /// Parses METADATA_ATTACHMENT in a function block.
+    /// Returns the attachments to the function itself and to each instruction (by its index).
+    pub fn parse_attachments(
+        &self,
+        block: &Block,
+    ) -> Result<(Attachment, FxHashMap<usize, Attachment>), Error> {
+        let mut func = vec![];
+        let mut insts = FxHashMap::default();
+        for record in block.records() {
+            if record.code != ATTACHMENT {
+                continue;
+            }
+            let (attachment, pairs) = if record.ops.len() % 2 == 0 {
+                (&mut func, &record.ops[..])
+            } else {
+                let inst = record.op(0)? as usize;
+                (insts.entry(inst).or_insert_with(Vec::new), &record.ops[1..])
+            };
+            for pair in pairs.chunks_exact(2) {
+                let kind = self
+                    .kinds
+                    .get(&pair[0])
+                    .ok_or(Error::Malformed("unknown metadata kind"))?;
+                let slot = *self
+                    .ids
+                    .get(pair[1] as usize)
+                    .ok_or(Error::Malformed("invalid metadata id"))?;
+                attachment.push((kind.clone(), slot));
+            }
+        }
+        Ok((func, insts))
+    }
+
+    /// Numbers the metadata nodes the same way as the textual form does: nodes reachable from named
+    /// metadata come first, followed by the ones reachable from `roots` in the given order.
+    /// Returns the metadata of the module and the numbers assigned to each slot.
+    pub fn finish(
+        self,
+        roots: impl Iterator<Item = usize>,
+    ) -> Result<(FxHashMap<Name, Metadata>, Numbers), Error> {
+        let mut numbers = FxHashMap::default();
+        let named_roots: Vec<_> = self.named.iter().flat_map(|(_, ops)| ops.clone()).collect();
+        for slot in named_roots
+            .into_iter()
+            .chain(roots)
+            .chain(0..self.slots.len())
+        {
+            self.number(slot, &mut numbers)?;
+        }
+
+        // Only nodes are numbered. Named metadata and node operands referring to anything else
+        // come from corrupt input.
+        let node_number = |op: &usize| {
+            numbers
+                .get(op)
+                .map(|&n| Metadata::Name(Name::Number(n)))
+                .ok_or(Error::Malformed("invalid metadata node id"))
+        };
+        let mut metas = FxHashMap::default();
+        for (name, ops) in &self.named {
+            let ops = ops.iter().map(node_number).collect::<Result<_, _>>()?;
+            metas.insert(Name::Name(name.clone()), Metadata::Node(ops, false));
+        }
+        for (slot, &number) in &numbers {
+            if let Some(Slot::Node(ops, distinct)) = self.slots.get(*slot) {
+                let ops = ops
+                    .iter()
+                    .map(|op| match self.slots.get(*op) {
+                        Some(Slot::String(s)) => Ok(Metadata::String(s.clone())),
+                        Some(Slot::Value(v)) => Ok(Metadata::Const(v.clone())),
+                        _ => node_number(op),
+                    })
+                    .collect::<Result<_, _>>()?;
+                metas.insert(Name::Number(number), Metadata::Node(ops, *distinct));
+            }
+        }
+
+        Ok((metas, numbers))
+    }
+
+    fn number(&self, slot: usize, numbers: &mut Numbers) -> Result<(), Error> {
+        let ops = match self.slots.get(slot) {
+            Some(Slot::Node(ops, _)) => ops,
+            Some(_) => return Ok(()),
+            None => return Err(Error::Malformed("invalid metadata id")),
+        };
+        if numbers.contains_key(&slot) {
+            return Ok(());
+        }
+        numbers.insert(slot, numbers.len());
+        for &op in ops {
+            self.number(op, numbers)?;
+        }
+        Ok(())
+    }
+}
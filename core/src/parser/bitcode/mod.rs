@@ -1,34 +1,77 @@
+//! A reader for LLVM bitcode (`.bc`) files.
+//!
+//! Only bitcode emitted by LLVM 4.0 or later (i.e. module version 2 with a string table) is
+//! accepted.
+
+pub mod bitstream;
 pub mod module;
 
-use anyhow::Result;
-use std::io::{BufRead, Cursor, Read};
+pub(crate) mod attributes;
+mod constants;
+mod function;
+mod metadata;
+mod types;
 
-pub fn parse<T>(cursor: &mut Cursor<T>) -> Result<()>
-where
-    T: BufRead + Read + PartialEq + AsRef<[u8]>,
-{
-    if !parse_magic(cursor, b"\x42\x43\xc0\xde")? {
-        return Err(anyhow::anyhow!("Invalid magic number"));
-    }
-    Ok(())
+use crate::ir::module::Module;
+use thiserror::Error;
+
+pub(crate) const MAGIC: &[u8] = b"BC\xc0\xde";
+const WRAPPER_MAGIC: &[u8] = b"\xde\xc0\x17\x0b";
+
+pub(crate) const MODULE_BLOCK_ID: u32 = 8;
+pub(crate) const PARAMATTR_BLOCK_ID: u32 = 9;
+pub(crate) const PARAMATTR_GROUP_BLOCK_ID: u32 = 10;
+pub(crate) const CONSTANTS_BLOCK_ID: u32 = 11;
+pub(crate) const FUNCTION_BLOCK_ID: u32 = 12;
+pub(crate) const VALUE_SYMTAB_BLOCK_ID: u32 = 14;
+pub(crate) const METADATA_BLOCK_ID: u32 = 15;
+pub(crate) const METADATA_ATTACHMENT_ID: u32 = 16;
+pub(crate) const TYPE_BLOCK_ID_NEW: u32 = 17;
+pub(crate) const METADATA_KIND_BLOCK_ID: u32 = 22;
+pub(crate) const STRTAB_BLOCK_ID: u32 = 23;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Invalid magic number")]
+    InvalidMagic,
+    #[error("Unexpected end of bitstream")]
+    UnexpectedEof,
+    #[error("Malformed bitcode: {0}")]
+    Malformed(&'static str),
+    #[error("Unsupported bitcode feature: {0}")]
+    Unsupported(String),
 }
 
-pub fn parse_magic<T>(cursor: &mut Cursor<T>, magic: &[u8]) -> Result<bool>
-where
-    T: BufRead + Read + PartialEq + AsRef<[u8]>,
-{
-    let mut buf = vec![0; magic.len()];
-    cursor.read_exact(&mut buf)?;
-    Ok(buf == magic)
+/// Parses an LLVM bitcode file.
+pub fn parse(bytes: &[u8]) -> Result<Module, Error> {
+    let bytes = strip_wrapper(bytes)?;
+    match bytes.strip_prefix(MAGIC) {
+        Some(stream) => module::parse(&bitstream::parse(stream)?),
+        None => Err(Error::InvalidMagic),
+    }
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+/// Returns `true` if `bytes` looks like LLVM bitcode.
+pub fn is_bitcode(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC) || bytes.starts_with(WRAPPER_MAGIC)
+}
 
-    #[test]
-    fn test1() {
-        let mut cursor = Cursor::new(b"\x42\x43\xc0\xde".as_slice());
-        parse(&mut cursor).unwrap();
+/// Strips the wrapper header emitted for Darwin targets, if any.
+fn strip_wrapper(bytes: &[u8]) -> Result<&[u8], Error> {
+    if !bytes.starts_with(WRAPPER_MAGIC) {
+        return Ok(bytes);
     }
+    let field = |i: usize| -> Result<usize, Error> {
+        let b = bytes.get(i * 4..i * 4 + 4).ok_or(Error::UnexpectedEof)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    };
+    let (offset, size) = (field(2)?, field(3)?);
+    let end = offset.checked_add(size).ok_or(Error::UnexpectedEof)?;
+    bytes.get(offset..end).ok_or(Error::UnexpectedEof)
+}
+
+#[test]
+fn invalid_magic() {
+    assert!(matches!(parse(b"BC\xc0\xdf"), Err(Error::InvalidMagic)));
+    assert!(matches!(parse(b"BC\xc0\xde"), Err(Error::Malformed(_))));
 }
@@ -0,0 +1,399 @@
+use super::{
+    attributes::AttributeTable,
+    bitstream::{Block, Entry, Record},
+    constants::{self, ValueEntry},
+    function::{self, decode_align, Attachments},
+    metadata::MetadataTable,
+    types::{self, function_type},
+    Error, CONSTANTS_BLOCK_ID, FUNCTION_BLOCK_ID, METADATA_BLOCK_ID, METADATA_KIND_BLOCK_ID,
+    MODULE_BLOCK_ID, PARAMATTR_BLOCK_ID, PARAMATTR_GROUP_BLOCK_ID, STRTAB_BLOCK_ID,
+    TYPE_BLOCK_ID_NEW,
+};
+use crate::ir::{
+    function::{data::Data, instruction::Operand, layout::Layout, Function, Parameter},
+    module::{
+        attributes::Attribute, global_variable::GlobalVariable, linkage::Linkage,
+        metadata::Metadata, name::Name, preemption_specifier::PreemptionSpecifier,
+        unnamed_addr::UnnamedAddr, visibility::Visibility, Module,
+    },
+    types::{PointerType, Type},
+};
+use rustc_hash::FxHashMap;
+
+const VERSION: u32 = 1;
+const TRIPLE: u32 = 2;
+const DATALAYOUT: u32 = 3;
+const SECTIONNAME: u32 = 5;
+const GLOBALVAR: u32 = 7;
+const FUNCTION: u32 = 8;
+const ALIAS_OLD: u32 = 9;
+const ALIAS: u32 = 14;
+const IFUNC: u32 = 15;
+const SOURCE_FILENAME: u32 = 16;
+
+/// Decodes the top-level blocks of a bitcode file into a [`Module`].
+pub fn parse(blocks: &[Block]) -> Result<Module, Error> {
+    let module_block = blocks
+        .iter()
+        .find(|b| b.id == MODULE_BLOCK_ID)
+        .ok_or(Error::Malformed("no module block"))?;
+    let strtab = blocks
+        .iter()
+        .filter(|b| b.id == STRTAB_BLOCK_ID)
+        .flat_map(|b| b.records())
+        .find_map(|r| r.blob.as_deref())
+        .unwrap_or(&[]);
+
+    let mut module = Module::new();
+    let mut type_list = vec![];
+    let mut attrs = AttributeTable::default();
+    let mut metadata = MetadataTable::default();
+    let mut values = vec![];
+    let mut sections = vec![];
+    let mut global_values = vec![];
+    let mut bodies = vec![];
+    let mut num_unnamed = 0;
+
+    for entry in &module_block.entries {
+        let record = match entry {
+            Entry::Block(block) => {
+                match block.id {
+                    TYPE_BLOCK_ID_NEW => type_list = types::parse(block, &module.types)?,
+                    PARAMATTR_GROUP_BLOCK_ID => attrs.parse_groups(block)?,
+                    PARAMATTR_BLOCK_ID => attrs.parse_lists(block),
+                    CONSTANTS_BLOCK_ID => constants::parse(block, &type_list, &mut values)?,
+                    METADATA_KIND_BLOCK_ID => metadata.parse_kinds(block)?,
+                    METADATA_BLOCK_ID => metadata.parse(
+                        block,
+                        &constants::Context {
+                            types: &module.types,
+                            type_list: &type_list,
+                            values: &values,
+                        },
+                    )?,
+                    FUNCTION_BLOCK_ID => bodies.push(block),
+                    _ => {}
+                }
+                continue;
+            }
+            Entry::Record(record) => record,
+        };
+
+        match record.code {
+            VERSION => {
+                let version = record.op(0)?;
+                if version != 2 {
+                    return Err(Error::Unsupported(format!("module version {}", version)));
+                }
+            }
+            TRIPLE => module.target.triple = record.string(0),
+            DATALAYOUT => module.target.datalayout = record.string(0).into(),
+            SOURCE_FILENAME => module.source_filename = record.string(0),
+            SECTIONNAME => sections.push(record.string(0)),
+            GLOBALVAR | FUNCTION => {
+                let offset = record.op(0)? as usize;
+                let name = strtab
+                    .get(offset..offset + record.op(1)? as usize)
+                    .ok_or(Error::Malformed("invalid string table reference"))?;
+                let name = if name.is_empty() {
+                    num_unnamed += 1;
+                    Name::Number(num_unnamed - 1)
+                } else {
+                    Name::Name(String::from_utf8_lossy(name).into_owned())
+                };
+                let ty = type_list
+                    .get(record.op(2)? as usize)
+                    .copied()
+                    .ok_or(Error::Malformed("invalid type id"))?;
+                let ptr_ty = if record.code == GLOBALVAR {
+                    let flags = record.op(3)?;
+                    if flags & 2 == 0 {
+                        ty
+                    } else {
+                        pointer(&module, ty, flags >> 2)
+                    }
+                } else {
+                    pointer(&module, ty, opt(record, 18))
+                };
+                values.push(ValueEntry::Global(name.clone(), ptr_ty));
+                global_values.push((name, ty, record));
+            }
+            ALIAS_OLD | ALIAS | IFUNC => {
+                return Err(Error::Unsupported("aliases and ifuncs".to_string()))
+            }
+            _ => {}
+        }
+    }
+
+    let ctx = constants::Context {
+        types: &module.types,
+        type_list: &type_list,
+        values: &values,
+    };
+    let mut funcs = vec![];
+    for (name, ty, record) in global_values {
+        if record.code == GLOBALVAR {
+            let gv = global_variable(&ctx, name, ty, record)?;
+            module.global_variables.insert(gv.name.clone(), gv);
+        } else {
+            let is_prototype = record.op(4)? != 0;
+            let func = function(&ctx, &attrs, &sections, name, ty, record)?;
+            funcs.push((func, is_prototype));
+        }
+    }
+
+    let mut bodies = bodies.into_iter();
+    let mut attachments = vec![];
+    for (func, _) in funcs.iter_mut().filter(|(_, is_prototype)| !is_prototype) {
+        let body = bodies
+            .next()
+            .ok_or(Error::Malformed("missing function body"))?;
+        attachments.push(function::parse(
+            body,
+            func,
+            &type_list,
+            &mut values,
+            &attrs,
+            &mut metadata,
+        )?);
+    }
+
+    let mut funcs: Vec<_> = funcs.into_iter().map(|(func, _)| func).collect();
+    module.metas = attach_metadata(&mut funcs, metadata, attachments)?;
+    module.attributes = number_attributes(&mut funcs, &attrs);
+    for func in funcs {
+        module.functions.alloc(func);
+    }
+
+    Ok(module)
+}
+
+fn global_variable(
+    ctx: &constants::Context,
+    name: Name,
+    ty: Type,
+    record: &Record,
+) -> Result<GlobalVariable, Error> {
+    // [strtab offset, strtab size, type, flags, initid, linkage, alignment, section, visibility,
+    //  threadlocal, unnamed_addr, externally_initialized, dllstorageclass, comdat, attributes,
+    //  dso_local]
+    let flags = record.op(3)?;
+    let ty = if flags & 2 == 0 {
+        ctx.types
+            .get_element(ty)
+            .ok_or(Error::Malformed("global variable of non-pointer type"))?
+    } else {
+        ty
+    };
+    let init = match record.op(4)? {
+        0 => None,
+        id => Some(ctx.constant(id - 1)?),
+    };
+    let linkage = linkage(record.op(5)?);
+    let visibility = visibility(opt(record, 8))?;
+    Ok(GlobalVariable {
+        name,
+        // The textual form omits `external` for definitions
+        linkage: match linkage {
+            Linkage::External if init.is_some() => None,
+            linkage => Some(linkage),
+        },
+        preemption_specifier: (opt(record, 15) != 0 && !is_implicit_dso_local(linkage, visibility))
+            .then_some(PreemptionSpecifier::DsoLocal),
+        visibility: (visibility != Visibility::Default).then_some(visibility),
+        unnamed_addr: unnamed_addr(opt(record, 10)),
+        is_constant: flags & 1 == 1,
+        ty,
+        init,
+        align: decode_align(record.op(6)?)?,
+    })
+}
+
+fn function(
+    ctx: &constants::Context,
+    attrs: &AttributeTable,
+    sections: &[String],
+    name: Name,
+    ty: Type,
+    record: &Record,
+) -> Result<Function, Error> {
+    // [strtab offset, strtab size, type, callingconv, isproto, linkage, paramattrs, alignment,
+    //  section, visibility, gc, unnamed_addr, prologuedata, dllstorageclass, comdat, prefixdata,
+    //  personalityfn, dso_local, addrspace]
+    let func_ty = function_type(ctx.types, ty)?;
+    let mut list = attrs.list(record.op(6)?, |id| ctx.ty(id))?;
+    let params = func_ty
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, &ty)| Parameter {
+            name: Name::Number(i + 1),
+            ty,
+            attrs: list.params.remove(&(i as u64)).unwrap_or_default(),
+        })
+        .collect();
+    let linkage = linkage(record.op(5)?);
+    let visibility = visibility(opt(record, 9))?;
+    let section = match opt(record, 8) {
+        0 => None,
+        id => Some(
+            sections
+                .get(id as usize - 1)
+                .cloned()
+                .ok_or(Error::Malformed("invalid section id"))?,
+        ),
+    };
+    let personality = match opt(record, 16) {
+        0 => None,
+        id => Some((ctx.type_of(id - 1)?, ctx.constant(id - 1)?)),
+    };
+    Ok(Function {
+        name: match name {
+            Name::Name(name) => name,
+            Name::Number(n) => n.to_string(),
+        },
+        is_var_arg: func_ty.is_var_arg,
+        result_ty: func_ty.ret,
+        params,
+        linkage,
+        preemption_specifier: if opt(record, 17) != 0 && !is_implicit_dso_local(linkage, visibility)
+        {
+            PreemptionSpecifier::DsoLocal
+        } else {
+            PreemptionSpecifier::DsoPreemptable
+        },
+        visibility,
+        unnamed_addr: unnamed_addr(opt(record, 11)),
+        func_attrs: list
+            .func
+            .map_or(vec![], |grp| vec![Attribute::Ref(grp as u32)]),
+        ret_attrs: list.ret,
+        section,
+        personality,
+        data: Data::new(),
+        layout: Layout::new(),
+        types: ctx.types.clone(),
+    })
+}
+
+/// Numbers metadata nodes in the order they're referred to, and attaches them to instructions.
+fn attach_metadata(
+    funcs: &mut [Function],
+    metadata: MetadataTable,
+    attachments: Vec<Attachments>,
+) -> Result<FxHashMap<Name, Metadata>, Error> {
+    let roots: Vec<_> = attachments
+        .iter()
+        .flat_map(|a| {
+            a.func
+                .iter()
+                .chain(a.insts.iter().flat_map(|(_, a)| a.iter()))
+                .map(|(_, slot)| *slot)
+        })
+        .collect();
+    let (metas, numbers) = metadata.finish(roots.into_iter())?;
+
+    let defined = funcs.iter_mut().filter(|f| !f.is_prototype());
+    for (func, attachments) in defined.zip(attachments) {
+        for (inst, attachment) in attachments.insts {
+            for (kind, slot) in attachment {
+                let number = *numbers
+                    .get(&slot)
+                    .ok_or(Error::Malformed("metadata attachment to a non-node"))?;
+                func.data.instructions[inst]
+                    .metadata
+                    .insert(kind, Metadata::Name(Name::Number(number)));
+            }
+        }
+    }
+
+    Ok(metas)
+}
+
+/// Renumbers attribute groups the same way as the textual form does: groups of functions come
+/// first, followed by groups of call sites.
+fn number_attributes(
+    funcs: &mut [Function],
+    attrs: &AttributeTable,
+) -> FxHashMap<u32, Vec<Attribute>> {
+    let mut numbers = FxHashMap::default();
+    let mut number = |attrs: &mut Vec<Attribute>| {
+        for attr in attrs {
+            if let Attribute::Ref(grp) = attr {
+                let len = numbers.len() as u32;
+                *grp = *numbers.entry(*grp).or_insert(len);
+            }
+        }
+    };
+
+    for func in funcs.iter_mut() {
+        number(&mut func.func_attrs)
+    }
+    for func in funcs.iter_mut() {
+        for block in func.layout.block_iter() {
+            for inst in func.layout.inst_iter(block) {
+                match &mut func.data.instructions[inst].operand {
+                    Operand::Call(call) => number(&mut call.func_attrs),
+                    Operand::Invoke(invoke) => number(&mut invoke.func_attrs),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    numbers
+        .into_iter()
+        .map(|(grp, number)| (number, attrs.func_attrs(grp as u64)))
+        .collect()
+}
+
+fn pointer(module: &Module, inner: Type, addr_space: u64) -> Type {
+    module.types.base_mut().pointer(PointerType {
+        inner,
+        addr_space: addr_space as u32,
+    })
+}
+
+/// Returns the `i`-th operand, which defaults to zero in older bitcode.
+fn opt(record: &Record, i: usize) -> u64 {
+    record.ops.get(i).copied().unwrap_or(0)
+}
+
+fn linkage(v: u64) -> Linkage {
+    match v {
+        2 => Linkage::Appending,
+        3 => Linkage::Internal,
+        7 => Linkage::ExternalWeak,
+        8 => Linkage::Common,
+        9 | 13 | 14 => Linkage::Private,
+        12 => Linkage::AvailableExternally,
+        1 | 16 => Linkage::WeakAny,
+        10 | 17 => Linkage::WeakODR,
+        4 | 18 => Linkage::LinkOnceAny,
+        11 | 19 => Linkage::LinkOnceODR,
+        _ => Linkage::External,
+    }
+}
+
+fn visibility(v: u64) -> Result<Visibility, Error> {
+    match v {
+        0 => Ok(Visibility::Default),
+        1 => Ok(Visibility::Hidden),
+        2 => Ok(Visibility::Protected),
+        _ => Err(Error::Malformed("invalid visibility")),
+    }
+}
+
+fn unnamed_addr(v: u64) -> Option<UnnamedAddr> {
+    match v {
+        1 => Some(UnnamedAddr::Global),
+        2 => Some(UnnamedAddr::Local),
+        _ => None,
+    }
+}
+
+/// Returns `true` if `dso_local` is implied, in which case the textual form omits it.
+fn is_implicit_dso_local(linkage: Linkage, visibility: Visibility) -> bool {
+    matches!(linkage, Linkage::Internal | Linkage::Private)
+        || (visibility != Visibility::Default && !matches!(linkage, Linkage::ExternalWeak))
+}
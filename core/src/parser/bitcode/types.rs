@@ -0,0 +1,153 @@
+use super::{bitstream::Block, Error};
+use crate::ir::{
+    module::name::Name,
+    types::{
+        ArrayType, CompoundType, FunctionType, PointerType, Type, Types, DOUBLE, FLOAT, FP128,
+        HALF, I1, I16, I32, I64, I8, VOID, X86_FP80,
+    },
+};
+
+const NUMENTRY: u32 = 1;
+const VOID_: u32 = 2;
+const FLOAT_: u32 = 3;
+const DOUBLE_: u32 = 4;
+const LABEL: u32 = 5;
+const OPAQUE: u32 = 6;
+const INTEGER: u32 = 7;
+const POINTER: u32 = 8;
+const HALF_: u32 = 10;
+const ARRAY: u32 = 11;
+const X86_FP80_: u32 = 13;
+const FP128_: u32 = 14;
+const METADATA: u32 = 16;
+const STRUCT_ANON: u32 = 18;
+const STRUCT_NAME: u32 = 19;
+const STRUCT_NAMED: u32 = 20;
+const FUNCTION: u32 = 21;
+const OPAQUE_POINTER: u32 = 25;
+
+/// Decodes a TYPE_BLOCK_ID_NEW block into a table indexed by type ids.
+pub fn parse(block: &Block, types: &Types) -> Result<Vec<Type>, Error> {
+    // Named structs may be referred to before their definition, so they are registered first.
+    let mut names = vec![];
+    let mut name = None;
+    for record in block.records() {
+        match record.code {
+            NUMENTRY => {}
+            STRUCT_NAME => name = Some(record.string(0)),
+            OPAQUE | STRUCT_NAMED => names.push(name.take()),
+            _ => names.push(None),
+        }
+    }
+    let table_entry = |id: u64, table: &[Type]| -> Result<Type, Error> {
+        if let Some(&ty) = table.get(id as usize) {
+            return Ok(ty);
+        }
+        match names.get(id as usize) {
+            Some(Some(name)) => Ok(types.base_mut().empty_named_type(struct_name(name))),
+            _ => Err(Error::Malformed("invalid type id")),
+        }
+    };
+
+    let mut table = vec![];
+    for record in block.records() {
+        let ty = match record.code {
+            NUMENTRY | STRUCT_NAME => continue,
+            VOID_ | LABEL => VOID,
+            HALF_ => HALF,
+            FLOAT_ => FLOAT,
+            DOUBLE_ => DOUBLE,
+            X86_FP80_ => X86_FP80,
+            FP128_ => FP128,
+            METADATA => types.metadata(),
+            INTEGER => match record.op(0)? {
+                1 => I1,
+                8 => I8,
+                16 => I16,
+                32 => I32,
+                64 => I64,
+                w => return Err(Error::Unsupported(format!("i{} type", w))),
+            },
+            POINTER => {
+                let inner = table_entry(record.op(0)?, &table)?;
+                let addr_space = record.ops.get(1).copied().unwrap_or(0) as u32;
+                types.base_mut().pointer(PointerType { inner, addr_space })
+            }
+            OPAQUE_POINTER => types.base_mut().opaque_pointer(record.op(0)? as u32),
+            ARRAY => {
+                let inner = table_entry(record.op(1)?, &table)?;
+                types
+                    .base_mut()
+                    .array(ArrayType::new(inner, record.op(0)? as u32))
+            }
+            FUNCTION => {
+                let ret = table_entry(record.op(1)?, &table)?;
+                let params = record
+                    .ops
+                    .get(2..)
+                    .ok_or(Error::Malformed("too few record operands"))?
+                    .iter()
+                    .map(|&id| table_entry(id, &table))
+                    .collect::<Result<_, _>>()?;
+                types
+                    .base_mut()
+                    .function(FunctionType::new(ret, params, record.op(0)? != 0))
+            }
+            STRUCT_ANON => {
+                let elems = record
+                    .ops
+                    .get(1..)
+                    .ok_or(Error::Malformed("too few record operands"))?
+                    .iter()
+                    .map(|&id| table_entry(id, &table))
+                    .collect::<Result<_, _>>()?;
+                types.base_mut().anonymous_struct(elems, record.op(0)? != 0)
+            }
+            OPAQUE | STRUCT_NAMED => {
+                let (elems, is_packed) = if record.code == OPAQUE {
+                    (vec![], false)
+                } else {
+                    let elems = record
+                        .ops
+                        .get(1..)
+                        .ok_or(Error::Malformed("too few record operands"))?
+                        .iter()
+                        .map(|&id| table_entry(id, &table))
+                        .collect::<Result<_, _>>()?;
+                    (elems, record.op(0)? != 0)
+                };
+                let ty = types.base_mut().anonymous_struct(elems, is_packed);
+                match names.get(table.len()).and_then(|n| n.as_ref()) {
+                    Some(name) => {
+                        let name = struct_name(name);
+                        types.base_mut().change_to_named_type(ty, name.clone());
+                        types.base_mut().empty_named_type(name)
+                    }
+                    None => ty,
+                }
+            }
+            code => return Err(Error::Unsupported(format!("type code {}", code))),
+        };
+        table.push(ty)
+    }
+
+    Ok(table)
+}
+
+/// Returns the parameter types of `ty` if it's a function type.
+pub fn function_type(types: &Types, ty: Type) -> Result<FunctionType, Error> {
+    match &*types
+        .get(ty)
+        .ok_or(Error::Malformed("expected a function type"))?
+    {
+        CompoundType::Function(f) => Ok(f.clone()),
+        _ => Err(Error::Malformed("expected a function type")),
+    }
+}
+
+fn struct_name(name: &str) -> Name {
+    match name.parse::<usize>() {
+        Ok(n) => Name::Number(n),
+        Err(_) => Name::Name(name.to_string()),
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{
+    ir::{
+        function::FunctionId,
+        module::Module,
+        value::{ConstantValue, Value},
+    },
+    pass::AnalysisPass,
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::any::Any;
+
+/// Computes the direct calls between the functions of a module.
+/// The result is stored as `CallGraph`.
+pub struct CallGraphPass;
+
+/// Direct callees and callers of each function. Calls through function pointers are not
+/// included.
+#[derive(Debug, Default)]
+pub struct CallGraph {
+    callees: FxHashMap<FunctionId, Vec<FunctionId>>,
+    callers: FxHashMap<FunctionId, Vec<FunctionId>>,
+}
+
+impl CallGraph {
+    pub fn new(module: &Module) -> Self {
+        let ids: FxHashMap<&str, FunctionId> = module
+            .functions()
+            .iter()
+            .map(|(id, func)| (func.name().as_str(), id))
+            .collect();
+        let mut graph = Self::default();
+
+        for (caller, func) in module.functions() {
+            let mut callees = vec![];
+            for block_id in func.layout.block_iter() {
+                for inst_id in func.layout.inst_iter(block_id) {
+                    let inst = func.data.inst_ref(inst_id);
+                    if !inst.opcode.is_call() && !inst.opcode.is_invoke() {
+                        continue;
+                    }
+                    let callee = match func.data.value_ref(inst.operand.args()[0]) {
+                        Value::Constant(ConstantValue::GlobalRef(name, _)) => name
+                            .to_string()
+                            .and_then(|name| ids.get(name.as_str()).copied()),
+                        _ => None,
+                    };
+                    if let Some(callee) = callee {
+                        if !callees.contains(&callee) {
+                            callees.push(callee)
+                        }
+                    }
+                }
+            }
+            for &callee in &callees {
+                graph.callers.entry(callee).or_default().push(caller);
+            }
+            graph.callees.insert(caller, callees);
+        }
+
+        graph
+    }
+
+    /// Returns the functions `id` calls directly, in the order they first appear.
+    pub fn callees(&self, id: FunctionId) -> &[FunctionId] {
+        self.callees.get(&id).map_or(&[], |c| c.as_slice())
+    }
+
+    /// Returns the functions calling `id` directly.
+    pub fn callers(&self, id: FunctionId) -> &[FunctionId] {
+        self.callers.get(&id).map_or(&[], |c| c.as_slice())
+    }
+
+    /// Returns `true` if `id` can reach itself through direct calls.
+    pub fn is_recursive(&self, id: FunctionId) -> bool {
+        let mut visited = FxHashSet::default();
+        let mut worklist = self.callees(id).to_vec();
+        while let Some(callee) = worklist.pop() {
+            if callee == id {
+                return true;
+            }
+            if visited.insert(callee) {
+                worklist.extend_from_slice(self.callees(callee));
+            }
+        }
+        false
+    }
+}
+
+impl AnalysisPass<Module> for CallGraphPass {
+    fn run_on(&self, module: &Module, result: &mut Box<dyn Any>) {
+        *result = Box::new(CallGraph::new(module));
+    }
+}
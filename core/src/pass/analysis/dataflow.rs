@@ -0,0 +1,197 @@
+use crate::traits::basic_block::{BasicBlock, BasicBlockData, BasicBlockLayout};
+use id_arena::Id;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::{collections::BTreeSet, hash::Hash};
+
+/// Values of a dataflow analysis, which are merged where control flow joins.
+pub trait Lattice: Clone + PartialEq {
+    /// Merges `other` into `self`.
+    fn meet(&mut self, other: &Self);
+}
+
+/// A set merged by union, for "may" analyses (e.g. liveness, reaching definitions).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnionSet<T: Hash + Eq>(pub FxHashSet<T>);
+
+/// A set merged by intersection, for "must" analyses (e.g. available expressions).
+///
+/// `Full` is the set of everything, which is the identity of intersection. Blocks that aren't
+/// visited yet should start with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntersectionSet<T: Hash + Eq> {
+    Full,
+    Set(FxHashSet<T>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+/// A dataflow analysis over basic blocks.
+pub trait Analysis<BB: BasicBlock> {
+    type Fact: Lattice;
+
+    const DIRECTION: Direction;
+
+    /// The fact flowing into the entry block for forward analyses, or into blocks without
+    /// successors for backward ones.
+    fn boundary(&self) -> Self::Fact;
+
+    /// The fact every other block starts with, which is usually the identity of `meet`.
+    fn init(&self) -> Self::Fact;
+
+    /// Computes the fact flowing out of `block` from the one flowing into it. For backward
+    /// analyses, `input` is the fact at the exit of `block` and the result is the one at the entry.
+    fn transfer(&self, block: Id<BB>, input: &Self::Fact) -> Self::Fact;
+
+    /// Adjusts the fact flowing along the edge from `from` to `to`, in the direction of the
+    /// analysis (e.g. to add the operands of phi nodes). Does nothing by default.
+    fn transfer_edge(&self, _from: Id<BB>, _to: Id<BB>, _fact: &mut Self::Fact) {}
+}
+
+/// The facts at the entry and the exit of each basic block, in program order.
+#[derive(Debug)]
+pub struct Dataflow<BB: BasicBlock, F> {
+    entry: FxHashMap<Id<BB>, F>,
+    exit: FxHashMap<Id<BB>, F>,
+}
+
+impl<BB: BasicBlock, F> Dataflow<BB, F> {
+    /// Runs `analysis` on `f` until the facts reach the fixed point.
+    pub fn new<A, G>(f: &G, analysis: &A) -> Self
+    where
+        A: Analysis<BB, Fact = F>,
+        F: Lattice,
+        G: BasicBlockData<BB> + BasicBlockLayout<BB>,
+    {
+        let forward = A::DIRECTION == Direction::Forward;
+        let mut order = reverse_post_order(f);
+        if !forward {
+            order.reverse();
+        }
+        let index: FxHashMap<Id<BB>, usize> =
+            order.iter().enumerate().map(|(i, &b)| (b, i)).collect();
+        let entry = order.first().copied();
+
+        // Facts flowing into and out of each block, in the direction of the analysis.
+        let mut input = FxHashMap::default();
+        let mut output: FxHashMap<Id<BB>, F> =
+            order.iter().map(|&b| (b, analysis.init())).collect();
+        let mut worklist: BTreeSet<usize> = (0..order.len()).collect();
+
+        while let Some(i) = worklist.pop_first() {
+            let block = order[i];
+            let (preds, succs) = if forward {
+                (f.get(block).preds(), f.get(block).succs())
+            } else {
+                (f.get(block).succs(), f.get(block).preds())
+            };
+
+            let is_boundary = if forward {
+                Some(block) == entry
+            } else {
+                preds.is_empty()
+            };
+            let mut fact = if is_boundary {
+                analysis.boundary()
+            } else {
+                analysis.init()
+            };
+            for pred in preds {
+                if let Some(out) = output.get(pred) {
+                    let mut out = out.clone();
+                    analysis.transfer_edge(*pred, block, &mut out);
+                    fact.meet(&out);
+                }
+            }
+
+            let out = analysis.transfer(block, &fact);
+            input.insert(block, fact);
+            if output[&block] != out {
+                output.insert(block, out);
+                worklist.extend(succs.iter().filter_map(|s| index.get(s)));
+            }
+        }
+
+        let (entry, exit) = if forward {
+            (input, output)
+        } else {
+            (output, input)
+        };
+        Self { entry, exit }
+    }
+
+    /// Returns the fact at the entry of `block`.
+    pub fn entry_of(&self, block: Id<BB>) -> Option<&F> {
+        self.entry.get(&block)
+    }
+
+    /// Returns the fact at the exit of `block`.
+    pub fn exit_of(&self, block: Id<BB>) -> Option<&F> {
+        self.exit.get(&block)
+    }
+}
+
+/// Returns the basic blocks of `f` in reverse post order, followed by the unreachable ones in the
+/// layout order.
+pub fn reverse_post_order<BB: BasicBlock, F: BasicBlockData<BB> + BasicBlockLayout<BB>>(
+    f: &F,
+) -> Vec<Id<BB>> {
+    let mut order = vec![];
+    let mut visited = FxHashSet::default();
+    if let Some(entry) = f.order().next() {
+        // (block, whether its successors are visited)
+        let mut stack = vec![(entry, false)];
+        while let Some((block, done)) = stack.pop() {
+            if done {
+                order.push(block);
+                continue;
+            }
+            if !visited.insert(block) {
+                continue;
+            }
+            stack.push((block, true));
+            for &succ in f.get(block).succs() {
+                if !visited.contains(&succ) {
+                    stack.push((succ, false));
+                }
+            }
+        }
+    }
+    order.reverse();
+    order.extend(f.order().filter(|b| !visited.contains(b)));
+    order
+}
+
+impl<T: Hash + Eq> Default for UnionSet<T> {
+    fn default() -> Self {
+        Self(FxHashSet::default())
+    }
+}
+
+impl<T: Hash + Eq + Clone> Lattice for UnionSet<T> {
+    fn meet(&mut self, other: &Self) {
+        self.0.extend(other.0.iter().cloned())
+    }
+}
+
+impl<T: Hash + Eq> IntersectionSet<T> {
+    pub fn contains(&self, x: &T) -> bool {
+        match self {
+            Self::Full => true,
+            Self::Set(set) => set.contains(x),
+        }
+    }
+}
+
+impl<T: Hash + Eq + Clone> Lattice for IntersectionSet<T> {
+    fn meet(&mut self, other: &Self) {
+        match (&mut *self, other) {
+            (_, Self::Full) => {}
+            (Self::Full, other) => *self = other.clone(),
+            (Self::Set(set), Self::Set(other)) => set.retain(|x| other.contains(x)),
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use super::dom_tree::DominatorTree;
+use crate::{
+    ir::function::{basic_block::BasicBlock as IrBasicBlock, Function},
+    pass::AnalysisPass,
+    traits::basic_block::{BasicBlock, BasicBlockData, BasicBlockLayout},
+};
+use id_arena::{Arena, Id};
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::any::Any;
+
+/// Computes the loops of a function.
+/// The result is stored as `LoopInfo<BasicBlock>`.
+pub struct LoopInfoPass;
+
+pub type LoopId<BB> = Id<Loop<BB>>;
+
+/// The natural loops of a function, forming a loop nesting forest.
+#[derive(Debug)]
+pub struct LoopInfo<BB: BasicBlock> {
+    loops: Arena<Loop<BB>>,
+    top_level: Vec<LoopId<BB>>,
+    /// The innermost loop containing each block.
+    innermost: FxHashMap<Id<BB>, LoopId<BB>>,
+}
+
+/// A natural loop, which is the set of blocks that can reach a back edge to the header without
+/// going through the header.
+#[derive(Debug)]
+pub struct Loop<BB: BasicBlock> {
+    header: Id<BB>,
+    /// All the blocks in the loop including the header and the ones in the nested loops.
+    blocks: FxHashSet<Id<BB>>,
+    /// Blocks in the loop branching back to the header.
+    latches: FxHashSet<Id<BB>>,
+    /// Blocks in the loop branching to a block outside the loop.
+    exiting: FxHashSet<Id<BB>>,
+    /// Blocks outside the loop branched to from the loop.
+    exits: FxHashSet<Id<BB>>,
+    preheader: Option<Id<BB>>,
+    parent: Option<LoopId<BB>>,
+    children: Vec<LoopId<BB>>,
+    depth: usize,
+}
+
+impl<BB: BasicBlock> LoopInfo<BB> {
+    pub fn new<F: BasicBlockData<BB> + BasicBlockLayout<BB>>(f: &F) -> Self {
+        if f.order().next().is_none() {
+            // `f` is a declaration.
+            return Self {
+                loops: Arena::new(),
+                top_level: vec![],
+                innermost: FxHashMap::default(),
+            };
+        }
+        Self::with_dom_tree(f, &DominatorTree::new(f))
+    }
+
+    pub fn with_dom_tree<F: BasicBlockData<BB> + BasicBlockLayout<BB>>(
+        f: &F,
+        dom_tree: &DominatorTree<BB>,
+    ) -> Self {
+        // Back edges go from latches to headers that dominate them.
+        let mut headers = vec![];
+        let mut latches: FxHashMap<Id<BB>, FxHashSet<Id<BB>>> = FxHashMap::default();
+        for block in f.order() {
+            if dom_tree.level_of(block).is_none() {
+                // `block` is unreachable.
+                continue;
+            }
+            for &succ in f.get(block).succs() {
+                if dom_tree.dominates(succ, block) {
+                    if !latches.contains_key(&succ) {
+                        headers.push(succ);
+                    }
+                    latches.entry(succ).or_default().insert(block);
+                }
+            }
+        }
+
+        // Allocate the loops in the order of their headers in the layout, which `loops` returns.
+        let position: FxHashMap<Id<BB>, usize> =
+            f.order().enumerate().map(|(i, block)| (block, i)).collect();
+        headers.sort_by_key(|header| position[header]);
+
+        let mut loops = Arena::new();
+        let mut ids: Vec<LoopId<BB>> = headers
+            .into_iter()
+            .map(|header| {
+                let latches = latches.remove(&header).unwrap();
+                loops.alloc(Loop::new(f, dom_tree, header, latches))
+            })
+            .collect();
+
+        // Loops with different headers are either disjoint or nested, so the parent of a loop is
+        // the smallest loop containing its header.
+        ids.sort_by_key(|&id| loops[id].blocks.len());
+        for (i, &id) in ids.iter().enumerate() {
+            let header = loops[id].header;
+            let parent = ids[i + 1..]
+                .iter()
+                .copied()
+                .find(|&p| loops[p].blocks.contains(&header));
+            loops[id].parent = parent;
+            if let Some(parent) = parent {
+                loops[parent].children.push(id);
+            }
+        }
+
+        let mut innermost = FxHashMap::default();
+        for &id in &ids {
+            for &block in &loops[id].blocks {
+                innermost.entry(block).or_insert(id);
+            }
+        }
+
+        let mut top_level: Vec<_> = ids
+            .iter()
+            .copied()
+            .filter(|&id| loops[id].parent.is_none())
+            .collect();
+        top_level.sort_by_key(|&id| id.index());
+        for &id in &top_level {
+            set_depth(&mut loops, id, 1);
+        }
+
+        Self {
+            loops,
+            top_level,
+            innermost,
+        }
+    }
+
+    pub fn get(&self, id: LoopId<BB>) -> &Loop<BB> {
+        &self.loops[id]
+    }
+
+    /// Returns all the loops in the order their headers appear in the layout.
+    pub fn loops(&self) -> impl Iterator<Item = (LoopId<BB>, &Loop<BB>)> {
+        self.loops.iter()
+    }
+
+    pub fn top_level_loops(&self) -> &[LoopId<BB>] {
+        &self.top_level
+    }
+
+    /// Returns the innermost loop containing `block`.
+    pub fn loop_of(&self, block: Id<BB>) -> Option<LoopId<BB>> {
+        self.innermost.get(&block).copied()
+    }
+
+    /// Returns the number of loops containing `block`, which is 0 if it's not in any loop.
+    pub fn depth_of(&self, block: Id<BB>) -> usize {
+        self.loop_of(block).map_or(0, |id| self.loops[id].depth)
+    }
+
+    pub fn is_header(&self, block: Id<BB>) -> bool {
+        self.loop_of(block)
+            .is_some_and(|id| self.loops[id].header == block)
+    }
+}
+
+impl<BB: BasicBlock> Loop<BB> {
+    fn new<F: BasicBlockData<BB>>(
+        f: &F,
+        dom_tree: &DominatorTree<BB>,
+        header: Id<BB>,
+        latches: FxHashSet<Id<BB>>,
+    ) -> Self {
+        let mut blocks = FxHashSet::default();
+        blocks.insert(header);
+        let mut worklist: Vec<_> = latches.iter().copied().collect();
+        while let Some(block) = worklist.pop() {
+            if dom_tree.level_of(block).is_some() && blocks.insert(block) {
+                worklist.extend(f.get(block).preds().iter().copied());
+            }
+        }
+
+        let mut exiting = FxHashSet::default();
+        let mut exits = FxHashSet::default();
+        for &block in &blocks {
+            for succ in f.get(block).succs() {
+                if !blocks.contains(succ) {
+                    exiting.insert(block);
+                    exits.insert(*succ);
+                }
+            }
+        }
+
+        // The preheader is the only block entering the loop, which only branches to the header.
+        let mut entering = f
+            .get(header)
+            .preds()
+            .iter()
+            .filter(|pred| !blocks.contains(pred));
+        let preheader = match (entering.next(), entering.next()) {
+            (Some(&pred), None) if f.get(pred).succs().len() == 1 => Some(pred),
+            _ => None,
+        };
+
+        Self {
+            header,
+            blocks,
+            latches,
+            exiting,
+            exits,
+            preheader,
+            parent: None,
+            children: vec![],
+            depth: 0,
+        }
+    }
+
+    pub fn header(&self) -> Id<BB> {
+        self.header
+    }
+
+    pub fn blocks(&self) -> &FxHashSet<Id<BB>> {
+        &self.blocks
+    }
+
+    pub fn contains(&self, block: Id<BB>) -> bool {
+        self.blocks.contains(&block)
+    }
+
+    pub fn latches(&self) -> &FxHashSet<Id<BB>> {
+        &self.latches
+    }
+
+    pub fn exiting_blocks(&self) -> &FxHashSet<Id<BB>> {
+        &self.exiting
+    }
+
+    pub fn exit_blocks(&self) -> &FxHashSet<Id<BB>> {
+        &self.exits
+    }
+
+    pub fn preheader(&self) -> Option<Id<BB>> {
+        self.preheader
+    }
+
+    pub fn parent(&self) -> Option<LoopId<BB>> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[LoopId<BB>] {
+        &self.children
+    }
+
+    /// Returns the nesting depth of the loop, which is 1 for outermost loops.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+fn set_depth<BB: BasicBlock>(loops: &mut Arena<Loop<BB>>, id: LoopId<BB>, depth: usize) {
+    loops[id].depth = depth;
+    for child in loops[id].children.clone() {
+        set_depth(loops, child, depth + 1);
+    }
+}
+
+impl AnalysisPass<Function> for LoopInfoPass {
+    fn run_on(&self, func: &Function, result: &mut Box<dyn Any>) {
+        *result = Box::new(LoopInfo::<IrBasicBlock>::new(func));
+    }
+}
@@ -1 +1,5 @@
+pub mod call_graph;
+pub mod dataflow;
 pub mod dom_tree;
+pub mod loop_info;
+pub mod verifier;
@@ -0,0 +1,739 @@
+use super::dom_tree::DominatorTree;
+use crate::{
+    ir::{
+        function::{
+            basic_block::{BasicBlock, BasicBlockId},
+            instruction::{Instruction, InstructionId, Opcode, Operand},
+            Function,
+        },
+        module::Module,
+        types::{self, Type, Typed},
+        value::{ConstantValue, Value, ValueId},
+    },
+    pass::AnalysisPass,
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::{any::Any, fmt};
+use thiserror::Error;
+
+/// Checks that a function is well formed.
+/// The result is stored as `VerifierResult`.
+pub struct VerifierPass;
+
+/// Diagnostics reported by `VerifierPass`. Empty if the function is well formed.
+pub struct VerifierResult(pub Vec<Diagnostic>);
+
+/// A problem found by the verifier, located at a block and/or an instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub func: String,
+    pub block: Option<BasicBlockId>,
+    pub inst: Option<InstructionId>,
+    pub kind: DiagnosticKind,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    #[error("block is empty")]
+    EmptyBlock,
+
+    #[error("block does not end with a terminator")]
+    MissingTerminator,
+
+    #[error("terminator is not at the end of the block")]
+    TerminatorNotAtEnd,
+
+    #[error("instruction's parent is not the block containing it")]
+    ParentMismatch,
+
+    #[error("invalid instruction")]
+    InvalidInstruction,
+
+    #[error("successors of the block do not match its terminator")]
+    SuccsMismatch,
+
+    #[error("predecessors of the block do not match the terminators branching to it")]
+    PredsMismatch,
+
+    #[error("phi node is not at the beginning of the block")]
+    PhiNotAtBeginning,
+
+    #[error("phi node has {args} incoming values but {blocks} incoming blocks")]
+    PhiArityMismatch { args: usize, blocks: usize },
+
+    #[error("phi incoming blocks do not match the predecessors of the block")]
+    PhiIncomingMismatch,
+
+    #[error("operand refers to instruction {0:?} which is not in the function")]
+    UndefinedValue(usize),
+
+    #[error("definition of instruction {0:?} does not dominate this use")]
+    NotDominated(usize),
+
+    #[error("type mismatch: expected `{expected}` but found `{found}`")]
+    TypeMismatch { expected: String, found: String },
+
+    #[error("users map of instruction {0:?} does not contain this use")]
+    MissingUser(usize),
+
+    #[error("users map contains instruction {0:?} which does not use this instruction")]
+    StaleUser(usize),
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "in function `{}`", self.func)?;
+        if let Some(block) = self.block {
+            write!(f, ", block {}", block.index())?;
+        }
+        if let Some(inst) = self.inst {
+            write!(f, ", instruction {}", inst.index())?;
+        }
+        write!(f, ": {}", self.kind)
+    }
+}
+
+impl AnalysisPass<Function> for VerifierPass {
+    fn run_on(&self, func: &Function, result: &mut Box<dyn Any>) {
+        *result = Box::new(VerifierResult(verify_function(func)));
+    }
+}
+
+/// Verifies every function defined in `module`.
+pub fn verify_module(module: &Module) -> Vec<Diagnostic> {
+    module
+        .functions()
+        .iter()
+        .flat_map(|(_, func)| verify_function(func))
+        .collect()
+}
+
+/// Verifies `func`. Prototypes are always well formed.
+pub fn verify_function(func: &Function) -> Vec<Diagnostic> {
+    if func.is_prototype() {
+        return vec![];
+    }
+    let mut verifier = Verifier::new(func);
+    verifier.run();
+    verifier.diags
+}
+
+struct Verifier<'a> {
+    func: &'a Function,
+    /// The block containing each instruction and its position in the block.
+    position: FxHashMap<InstructionId, (BasicBlockId, usize)>,
+    diags: Vec<Diagnostic>,
+}
+
+impl<'a> Verifier<'a> {
+    fn new(func: &'a Function) -> Self {
+        let mut position = FxHashMap::default();
+        for block_id in func.layout.block_iter() {
+            for (i, inst_id) in func.layout.inst_iter(block_id).enumerate() {
+                position.insert(inst_id, (block_id, i));
+            }
+        }
+        Self {
+            func,
+            position,
+            diags: vec![],
+        }
+    }
+
+    fn run(&mut self) {
+        self.check_blocks();
+        self.check_cfg();
+
+        let dom_tree = DominatorTree::new(self.func);
+        for block_id in self.func.layout.block_iter() {
+            for inst_id in self.func.layout.inst_iter(block_id) {
+                self.check_phi(block_id, inst_id);
+                self.check_uses(&dom_tree, block_id, inst_id);
+                self.check_types(block_id, inst_id);
+            }
+        }
+
+        self.check_users_map();
+    }
+
+    fn report(
+        &mut self,
+        block: Option<BasicBlockId>,
+        inst: Option<InstructionId>,
+        kind: DiagnosticKind,
+    ) {
+        self.diags.push(Diagnostic {
+            func: self.func.name.clone(),
+            block,
+            inst,
+            kind,
+        })
+    }
+
+    fn check_blocks(&mut self) {
+        for block_id in self.func.layout.block_iter() {
+            let insts: Vec<InstructionId> = self.func.layout.inst_iter(block_id).collect();
+            if insts.is_empty() {
+                self.report(Some(block_id), None, DiagnosticKind::EmptyBlock);
+                continue;
+            }
+
+            let mut seen_non_phi = false;
+            for (i, &inst_id) in insts.iter().enumerate() {
+                let inst = self.func.data.inst_ref(inst_id);
+                let at = (Some(block_id), Some(inst_id));
+                if inst.parent != block_id {
+                    self.report(at.0, at.1, DiagnosticKind::ParentMismatch);
+                }
+                if inst.opcode == Opcode::Invalid {
+                    self.report(at.0, at.1, DiagnosticKind::InvalidInstruction);
+                }
+                if inst.opcode.is_terminator() && i + 1 != insts.len() {
+                    self.report(at.0, at.1, DiagnosticKind::TerminatorNotAtEnd);
+                }
+                if inst.opcode.is_phi() && seen_non_phi {
+                    self.report(at.0, at.1, DiagnosticKind::PhiNotAtBeginning);
+                }
+                seen_non_phi |= !inst.opcode.is_phi();
+            }
+
+            let last = *insts.last().unwrap();
+            if !self.func.data.inst_ref(last).opcode.is_terminator() {
+                self.report(
+                    Some(block_id),
+                    Some(last),
+                    DiagnosticKind::MissingTerminator,
+                );
+            }
+        }
+    }
+
+    /// Checks that `preds` and `succs` of every block agree with the terminators.
+    fn check_cfg(&mut self) {
+        let mut preds: FxHashMap<BasicBlockId, FxHashSet<BasicBlockId>> = FxHashMap::default();
+        for block_id in self.func.layout.block_iter() {
+            let succs: FxHashSet<BasicBlockId> = self
+                .terminator_of(block_id)
+                .map_or(FxHashSet::default(), |term| {
+                    term.operand.blocks().iter().copied().collect()
+                });
+            for &succ in &succs {
+                preds.entry(succ).or_default().insert(block_id);
+            }
+            if &succs != self.func.data.block_ref(block_id).succs() {
+                self.report(Some(block_id), None, DiagnosticKind::SuccsMismatch);
+            }
+        }
+        for block_id in self.func.layout.block_iter() {
+            let expected = preds.remove(&block_id).unwrap_or_default();
+            if &expected != self.func.data.block_ref(block_id).preds() {
+                self.report(Some(block_id), None, DiagnosticKind::PredsMismatch);
+            }
+        }
+    }
+
+    fn check_phi(&mut self, block_id: BasicBlockId, inst_id: InstructionId) {
+        let phi = match self.func.data.inst_ref(inst_id).operand.as_phi() {
+            Some(phi) => phi,
+            None => return,
+        };
+        if phi.args.len() != phi.blocks.len() {
+            let (args, blocks) = (phi.args.len(), phi.blocks.len());
+            self.report(
+                Some(block_id),
+                Some(inst_id),
+                DiagnosticKind::PhiArityMismatch { args, blocks },
+            );
+        }
+        let incoming: FxHashSet<BasicBlockId> = phi.blocks.iter().copied().collect();
+        if &incoming != self.func.data.block_ref(block_id).preds() {
+            self.report(
+                Some(block_id),
+                Some(inst_id),
+                DiagnosticKind::PhiIncomingMismatch,
+            );
+        }
+    }
+
+    /// Checks that every instruction used by `inst_id` is defined in the function and
+    /// dominates the use. Uses in unreachable blocks are not checked for dominance.
+    fn check_uses(
+        &mut self,
+        dom_tree: &DominatorTree<BasicBlock>,
+        block_id: BasicBlockId,
+        inst_id: InstructionId,
+    ) {
+        let inst = self.func.data.inst_ref(inst_id);
+        let (_, use_pos) = self.position[&inst_id];
+
+        // For phi nodes, the use takes place at the end of the incoming block.
+        let uses: Vec<(ValueId, Option<BasicBlockId>)> = match inst.operand.as_phi() {
+            Some(phi) => phi
+                .args
+                .iter()
+                .zip(phi.blocks.iter())
+                .map(|(&arg, &block)| (arg, Some(block)))
+                .collect(),
+            None => inst.operand.args().iter().map(|&arg| (arg, None)).collect(),
+        };
+
+        for (arg, incoming) in uses {
+            let def_id = match self.func.data.value_ref(arg) {
+                Value::Instruction(def_id) => *def_id,
+                _ => continue,
+            };
+            let (def_block, def_pos) = match self.position.get(&def_id) {
+                Some(&pos) => pos,
+                None => {
+                    self.report(
+                        Some(block_id),
+                        Some(inst_id),
+                        DiagnosticKind::UndefinedValue(def_id.index()),
+                    );
+                    continue;
+                }
+            };
+            let dominated = match incoming {
+                Some(pred) => {
+                    dom_tree.level_of(pred).is_none() || dom_tree.dominates(def_block, pred)
+                }
+                None if def_block == block_id => def_pos < use_pos,
+                None => {
+                    dom_tree.level_of(block_id).is_none() || dom_tree.dominates(def_block, block_id)
+                }
+            };
+            if !dominated {
+                self.report(
+                    Some(block_id),
+                    Some(inst_id),
+                    DiagnosticKind::NotDominated(def_id.index()),
+                );
+            }
+        }
+    }
+
+    /// Checks that operand types agree with the types recorded in the instruction.
+    fn check_types(&mut self, block_id: BasicBlockId, inst_id: InstructionId) {
+        let inst = self.func.data.inst_ref(inst_id);
+        let mut expected: Vec<(ValueId, Type)> = vec![];
+        let mut result_ty = None;
+
+        match &inst.operand {
+            Operand::IntBinary(i) => {
+                expected.extend(i.args.iter().map(|&arg| (arg, i.ty)));
+                result_ty = Some(i.ty);
+            }
+            Operand::FloatBinary(f) => {
+                expected.extend(f.args.iter().map(|&arg| (arg, f.ty)));
+                result_ty = Some(f.ty);
+            }
+            Operand::FloatUnary(f) => {
+                expected.push((f.arg, f.ty));
+                result_ty = Some(f.ty);
+            }
+            Operand::ICmp(i) => {
+                expected.extend(i.args.iter().map(|&arg| (arg, i.ty)));
+                result_ty = Some(types::I1);
+            }
+            Operand::FCmp(f) => {
+                expected.extend(f.args.iter().map(|&arg| (arg, f.ty)));
+                result_ty = Some(types::I1);
+            }
+            Operand::Cast(c) => {
+                expected.push((c.arg, c.tys[0]));
+                result_ty = Some(c.tys[1]);
+            }
+            Operand::Load(l) => {
+                expected.push((l.addr, l.tys[1]));
+                result_ty = Some(l.tys[0]);
+            }
+            Operand::Store(s) => expected.extend(s.args.iter().copied().zip(s.tys)),
+            Operand::Phi(p) => {
+                expected.extend(p.args.iter().map(|&arg| (arg, p.ty)));
+                result_ty = Some(p.ty);
+            }
+            Operand::Call(_) | Operand::Invoke(_) => {
+                let (args, tys) = (inst.operand.args(), inst.operand.types());
+                expected.extend(args[1..].iter().copied().zip(tys[1..].iter().copied()));
+                result_ty = Some(tys[0]);
+            }
+            Operand::Resume(r) => expected.push((r.arg, r.ty)),
+            Operand::CondBr(c) => expected.push((c.arg, types::I1)),
+            Operand::Switch(s) => {
+                expected.extend(s.args.iter().copied().zip(s.tys.iter().copied()))
+            }
+            Operand::Ret(r) => {
+                expected.extend(r.val.map(|val| (val, r.ty)));
+                if !self.is_same_type(r.ty, self.func.result_ty) {
+                    self.report_type_mismatch(block_id, inst_id, self.func.result_ty, r.ty);
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(ty) = result_ty {
+            if !self.is_same_type(inst.ty, ty) {
+                self.report_type_mismatch(block_id, inst_id, ty, inst.ty);
+            }
+        }
+
+        for (val, ty) in expected {
+            if let Some(found) = self.value_ty(val) {
+                if !self.is_same_type(found, ty) {
+                    self.report_type_mismatch(block_id, inst_id, ty, found);
+                }
+            }
+        }
+    }
+
+    /// Checks that `users_map` records exactly the uses found in the function.
+    fn check_users_map(&mut self) {
+        for block_id in self.func.layout.block_iter() {
+            for inst_id in self.func.layout.inst_iter(block_id) {
+                for def_id in self.used_insts(inst_id).collect::<Vec<_>>() {
+                    if !self.position.contains_key(&def_id) {
+                        // Already reported as an undefined value.
+                        continue;
+                    }
+                    let recorded = self
+                        .func
+                        .data
+                        .users_map
+                        .get(&def_id)
+                        .is_some_and(|users| users.contains(&inst_id));
+                    if !recorded {
+                        self.report(
+                            Some(block_id),
+                            Some(inst_id),
+                            DiagnosticKind::MissingUser(def_id.index()),
+                        );
+                    }
+                }
+
+                let mut users: Vec<InstructionId> = self
+                    .func
+                    .data
+                    .users_map
+                    .get(&inst_id)
+                    .map_or(vec![], |users| users.iter().copied().collect());
+                users.sort_by_key(|user| user.index());
+                for user in users {
+                    let uses = self.position.contains_key(&user)
+                        && self.used_insts(user).any(|def_id| def_id == inst_id);
+                    if !uses {
+                        self.report(
+                            Some(block_id),
+                            Some(inst_id),
+                            DiagnosticKind::StaleUser(user.index()),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Function types and anonymous struct types are not uniqued, so compare them structurally.
+    fn is_same_type(&self, x: Type, y: Type) -> bool {
+        x == y || self.func.types.to_string(x) == self.func.types.to_string(y)
+    }
+
+    fn report_type_mismatch(
+        &mut self,
+        block_id: BasicBlockId,
+        inst_id: InstructionId,
+        expected: Type,
+        found: Type,
+    ) {
+        let expected = self.func.types.to_string(expected);
+        let found = self.func.types.to_string(found);
+        self.report(
+            Some(block_id),
+            Some(inst_id),
+            DiagnosticKind::TypeMismatch { expected, found },
+        )
+    }
+
+    fn terminator_of(&self, block_id: BasicBlockId) -> Option<&Instruction> {
+        let last = (*self.func.layout.block_node(block_id).last_inst())?;
+        let inst = self.func.data.inst_ref(last);
+        inst.opcode.is_terminator().then_some(inst)
+    }
+
+    fn used_insts(&self, inst_id: InstructionId) -> impl Iterator<Item = InstructionId> + '_ {
+        self.func
+            .data
+            .inst_ref(inst_id)
+            .operand
+            .args()
+            .iter()
+            .filter_map(|&arg| match self.func.data.value_ref(arg) {
+                Value::Instruction(id) => Some(*id),
+                _ => None,
+            })
+    }
+
+    /// Returns the type of `val` if it can be known reliably.
+    /// Global references and constant expressions are not checked.
+    fn value_ty(&self, val: ValueId) -> Option<Type> {
+        match self.func.data.value_ref(val) {
+            Value::Instruction(id) => Some(self.func.data.inst_ref(*id).ty),
+            Value::Argument(arg) => Some(arg.ty),
+            Value::Constant(
+                konst @ (ConstantValue::Int(_)
+                | ConstantValue::Float(_)
+                | ConstantValue::Undef(_)
+                | ConstantValue::Null(_)
+                | ConstantValue::AggregateZero(_)),
+            ) => Some(konst.ty()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        ir::module::{name::Name, Module},
+        pass::{transform::mem2reg::Mem2RegPass, PassManager},
+    };
+
+    fn parse(ir: &str) -> Module {
+        Module::try_from(ir).expect("failed to parse ir")
+    }
+
+    fn kinds(module: &Module) -> Vec<DiagnosticKind> {
+        verify_module(module).into_iter().map(|d| d.kind).collect()
+    }
+
+    #[test]
+    fn well_formed() {
+        let module = parse(
+            r#"
+define dso_local i32 @main(i32 %n) {
+entry:
+  br label %loop
+loop:
+  %i = phi i32 [ 0, %entry ], [ %i.next, %loop ]
+  %i.next = add nsw i32 %i, 1
+  %c = icmp slt i32 %i.next, %n
+  br i1 %c, label %loop, label %exit
+exit:
+  ret i32 %i.next
+}"#,
+        );
+        assert_eq!(kinds(&module), vec![]);
+
+        let mut pm = PassManager::new();
+        pm.add_analysis(VerifierPass);
+        pm.run_analyses_on_module(&module);
+        assert!(pm.get_result::<VerifierResult>().unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn after_mem2reg() {
+        let mut module = parse(
+            r#"
+define dso_local i32 @main(i32 %0) {
+  %2 = alloca i32, align 4
+  %3 = alloca i32, align 4
+  store i32 0, i32* %2, align 4
+  %4 = icmp sgt i32 %0, 1
+  br i1 %4, label %5, label %6
+5:
+  store i32 1, i32* %3, align 4
+  br label %7
+6:
+  store i32 2, i32* %3, align 4
+  br label %7
+7:
+  %8 = load i32, i32* %3, align 4
+  ret i32 %8
+}"#,
+        );
+        let mut pm = PassManager::new();
+        pm.add_transform(Mem2RegPass);
+        pm.run_on_module(&mut module);
+        assert_eq!(kinds(&module), vec![]);
+    }
+
+    #[test]
+    fn missing_terminator() {
+        let mut module = parse(
+            r#"
+define dso_local i32 @main() {
+  %1 = add i32 1, 2
+  ret i32 %1
+}"#,
+        );
+        let func = module.functions_mut().iter_mut().next().unwrap().1;
+        let entry = func.layout.get_entry_block().unwrap();
+        let ret = func.layout.block_node(entry).last_inst().unwrap();
+        func.remove_inst(ret);
+        assert_eq!(kinds(&module), vec![DiagnosticKind::MissingTerminator]);
+    }
+
+    #[test]
+    fn phi_incoming_mismatch() {
+        let module = parse(
+            r#"
+define dso_local i32 @main() {
+entry:
+  br label %a
+a:
+  %p = phi i32 [ 0, %entry ], [ 1, %b ]
+  br label %b
+b:
+  ret i32 %p
+}"#,
+        );
+        assert_eq!(kinds(&module), vec![DiagnosticKind::PhiIncomingMismatch]);
+    }
+
+    #[test]
+    fn not_dominated() {
+        let module = parse(
+            r#"
+define dso_local i32 @main(i1 %c) {
+entry:
+  br i1 %c, label %a, label %b
+a:
+  %x = add i32 1, 2
+  br label %b
+b:
+  %y = add i32 %x, 1
+  ret i32 %y
+}"#,
+        );
+        let diags = verify_module(&module);
+        assert_eq!(diags.len(), 1);
+        assert!(matches!(diags[0].kind, DiagnosticKind::NotDominated(_)));
+    }
+
+    #[test]
+    fn type_mismatch() {
+        let module = parse(
+            r#"
+define dso_local i64 @main(i32 %0) {
+  %2 = add i64 %0, 1
+  ret i64 %2
+}"#,
+        );
+        assert_eq!(
+            kinds(&module),
+            vec![DiagnosticKind::TypeMismatch {
+                expected: "i64".to_string(),
+                found: "i32".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn users_map_inconsistent() {
+        let mut module = parse(
+            r#"
+define dso_local i32 @main() {
+  %1 = add i32 1, 2
+  ret i32 %1
+}"#,
+        );
+        let func = module.functions_mut().iter_mut().next().unwrap().1;
+        for users in func.data.users_map.values_mut() {
+            users.clear();
+        }
+        let diags = verify_module(&module);
+        assert_eq!(diags.len(), 1);
+        assert!(matches!(diags[0].kind, DiagnosticKind::MissingUser(_)));
+        assert!(diags[0].to_string().starts_with("in function `main`"));
+    }
+
+    #[test]
+    fn forward_reference_keeps_type() {
+        // `%i.next` is used before it's defined, so the parser creates a placeholder and replaces
+        // it later with `Instruction::replace`, which has to keep the type.
+        let module = parse(
+            r#"
+define dso_local i32 @main() {
+entry:
+  br label %loop
+loop:
+  %i = phi i32 [ 0, %entry ], [ %i.next, %loop ]
+  %i.next = add nsw i32 %i, 1
+  br label %loop
+}"#,
+        );
+        let func = module.functions().iter().next().unwrap().1;
+        let (_, next) = func
+            .data
+            .instructions
+            .iter()
+            .find(|(_, inst)| inst.dest == Some(Name::Name("i.next".to_string())))
+            .unwrap();
+        assert_eq!(next.ty, types::I32);
+        assert_eq!(kinds(&module), vec![]);
+    }
+
+    #[test]
+    fn unreachable_is_terminator() {
+        assert!(Opcode::Unreachable.is_terminator());
+        let module = parse(
+            r#"
+define dso_local void @main() {
+  unreachable
+}"#,
+        );
+        assert_eq!(kinds(&module), vec![]);
+    }
+
+    #[test]
+    fn switch_successors() {
+        let module = parse(
+            r#"
+define dso_local i32 @main(i32 %x) {
+entry:
+  switch i32 %x, label %default [ i32 1, label %one ]
+one:
+  ret i32 1
+default:
+  ret i32 0
+}"#,
+        );
+        let func = module.functions().iter().next().unwrap().1;
+        let entry = func.layout.get_entry_block().unwrap();
+        let switch = func.layout.block_node(entry).last_inst().unwrap();
+        assert_eq!(func.data.inst_ref(switch).operand.blocks().len(), 2);
+        assert_eq!(kinds(&module), vec![]);
+    }
+
+    #[test]
+    fn mem2reg_phi_type() {
+        let mut module = parse(
+            r#"
+define dso_local i32 @main(i1 %c) {
+entry:
+  %p = alloca i32, align 4
+  br i1 %c, label %a, label %b
+a:
+  store i32 1, i32* %p, align 4
+  br label %exit
+b:
+  store i32 2, i32* %p, align 4
+  br label %exit
+exit:
+  %v = load i32, i32* %p, align 4
+  ret i32 %v
+}"#,
+        );
+        let mut pm = PassManager::new();
+        pm.add_transform(Mem2RegPass);
+        pm.run_on_module(&mut module);
+        let func = module.functions().iter().next().unwrap().1;
+        let (_, phi) = func
+            .data
+            .instructions
+            .iter()
+            .find(|(_, inst)| inst.opcode == Opcode::Phi)
+            .unwrap();
+        assert_eq!(phi.ty, types::I32);
+    }
+}
@@ -1,15 +1,62 @@
 pub mod analysis;
+pub mod pipeline;
 pub mod transform;
 
-use rustc_hash::FxHashMap;
-use std::any::{Any, TypeId};
+use crate::ir::{
+    function::{Function, FunctionId},
+    module::Module,
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+};
 
 pub trait AnalysisPass<T> {
     fn run_on(&self, _: &T, _: &mut Box<dyn Any>) {}
+
+    /// Analyses that must be run before this pass. Their results are passed to
+    /// `run_on_with_results`. Cached results are reused unless a transform invalidated them.
+    fn dependencies(&self) -> Vec<Box<dyn AnalysisPass<T>>> {
+        vec![]
+    }
+
+    /// Same as `run_on`, but can access the results of `dependencies`.
+    fn run_on_with_results(&self, target: &T, _: &AnalysisResults, result: &mut Box<dyn Any>) {
+        self.run_on(target, result)
+    }
+
+    /// Identifies the pass when caching its result.
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
 }
 
 pub trait TransformPass<T> {
     fn run_on(&self, _: &mut T, _: &mut Box<dyn Any>) {}
+
+    /// Same as `run_on`, but returns `false` if the pass is sure it changed nothing.
+    /// Used to run passes to a fixed point and to invalidate cached analysis results.
+    fn run_on_and_report_change(&self, target: &mut T, result: &mut Box<dyn Any>) -> bool {
+        self.run_on(target, result);
+        true
+    }
+
+    /// Analyses that must be run before this pass. Their results are passed to
+    /// `run_on_with_results`.
+    fn dependencies(&self) -> Vec<Box<dyn AnalysisPass<T>>> {
+        vec![]
+    }
+
+    /// Same as `run_on_and_report_change`, but can access the results of `dependencies`.
+    fn run_on_with_results(
+        &self,
+        target: &mut T,
+        _: &AnalysisResults,
+        result: &mut Box<dyn Any>,
+    ) -> bool {
+        self.run_on_and_report_change(target, result)
+    }
 }
 
 pub enum Pass<T> {
@@ -17,16 +64,55 @@ pub enum Pass<T> {
     Transform(Box<dyn TransformPass<T>>),
 }
 
+/// Results of the passes run on a single target, keyed by the type of the result.
+/// Results stay readable after a transform changed the target, but may be stale then.
+#[derive(Default)]
+pub struct AnalysisResults {
+    results: FxHashMap<TypeId, Box<dyn Any>>,
+    /// Analyses whose results are up to date.
+    valid: FxHashSet<&'static str>,
+}
+
 pub struct PassManager<T> {
     passes: Vec<Pass<T>>,
-    results: FxHashMap<TypeId, Box<dyn Any>>,
+    results: AnalysisResults,
+    function_results: FxHashMap<FunctionId, AnalysisResults>,
+    last_function: Option<FunctionId>,
+}
+
+/// Runs function passes on every function of a module, as a module transform.
+/// See `PassManager::<Module>::add_function_passes`.
+pub struct FunctionPasses(RefCell<PassManager<Function>>);
+
+impl AnalysisResults {
+    pub fn get<R: 'static>(&self) -> Option<&R> {
+        self.results
+            .get(&TypeId::of::<R>())
+            .and_then(|result| result.downcast_ref())
+    }
+
+    pub fn is_valid(&self, pass: &str) -> bool {
+        self.valid.contains(pass)
+    }
+
+    /// Marks all results as stale. Stale results can still be read, but analyses required as
+    /// dependencies are run again.
+    pub fn invalidate(&mut self) {
+        self.valid.clear();
+    }
+
+    fn insert(&mut self, result: Box<dyn Any>) {
+        self.results.insert((*result).type_id(), result);
+    }
 }
 
 impl<T> Default for PassManager<T> {
     fn default() -> Self {
         Self {
             passes: vec![],
-            results: FxHashMap::default(),
+            results: AnalysisResults::default(),
+            function_results: FxHashMap::default(),
+            last_function: None,
         }
     }
 }
@@ -48,56 +134,135 @@ impl<T> PassManager<T> {
         self.passes.push(Pass::Transform(Box::new(pass)))
     }
 
-    pub fn run_on(&mut self, target: &mut T) {
-        self.results.clear();
-
-        for pass in &self.passes {
-            let mut result: Box<dyn Any> = Box::new(());
-            match pass {
-                Pass::Analysis(analysis) => analysis.run_on(target, &mut result),
-                Pass::Transform(transform) => transform.run_on(target, &mut result),
-            }
-            self.results.insert((*result).type_id(), result);
-        }
+    /// Runs the passes on `target`. Returns `true` if any transform changed it.
+    pub fn run_on(&mut self, target: &mut T) -> bool {
+        self.last_function = None;
+        self.results = AnalysisResults::default();
+        run_passes(&self.passes, target, &mut self.results)
     }
 
     pub fn run_analyses_on(&mut self, target: &T) {
-        self.results.clear();
+        self.last_function = None;
+        self.results = AnalysisResults::default();
+        run_analyses(&self.passes, target, &mut self.results)
+    }
 
-        for pass in &self.passes {
-            let mut result: Box<dyn Any> = Box::new(());
-            match pass {
-                Pass::Analysis(analysis) => analysis.run_on(target, &mut result),
-                Pass::Transform(_) => {}
-            }
-            self.results.insert((*result).type_id(), result);
+    /// Returns the result of type `R` of the last run. After running on a module, this is the
+    /// result for the last function. Use `get_function_result` for the others.
+    pub fn get_result<R: 'static>(&self) -> Option<&R> {
+        match self.last_function {
+            Some(id) => self.function_results.get(&id).and_then(|r| r.get()),
+            None => self.results.get(),
         }
     }
 
-    pub fn get_result<P: 'static>(&self) -> Option<&P> {
-        self.results
-            .get(&TypeId::of::<P>())
-            .and_then(|result| result.downcast_ref())
+    pub fn results(&self) -> &AnalysisResults {
+        &self.results
     }
 }
 
-use crate::ir::{function::Function, module::Module};
-
 impl PassManager<Function> {
     pub fn run_analyses_on_module(&mut self, module: &Module) {
-        for (_, func) in &module.functions {
-            self.run_analyses_on(func)
+        self.function_results.clear();
+        for (id, func) in &module.functions {
+            let results = self.function_results.entry(id).or_default();
+            run_analyses(&self.passes, func, results);
+            self.last_function = Some(id);
         }
     }
 
-    pub fn run_on_module(&mut self, module: &mut Module) {
-        for (_, func) in &mut module.functions {
+    /// Runs the passes on every function of `module`. Returns `true` if any function changed.
+    pub fn run_on_module(&mut self, module: &mut Module) -> bool {
+        self.function_results.clear();
+        let mut changed = false;
+        for (id, func) in &mut module.functions {
             if func.is_prototype() {
                 continue;
             }
-            self.run_on(func)
+            let results = self.function_results.entry(id).or_default();
+            changed |= run_passes(&self.passes, func, results);
+            self.last_function = Some(id);
+        }
+        changed
+    }
+
+    /// Returns the result of type `R` for the function `id` of the last run on a module.
+    pub fn get_function_result<R: 'static>(&self, id: FunctionId) -> Option<&R> {
+        self.function_results.get(&id).and_then(|r| r.get())
+    }
+
+    pub fn function_results(&self, id: FunctionId) -> Option<&AnalysisResults> {
+        self.function_results.get(&id)
+    }
+}
+
+impl PassManager<Module> {
+    /// Adds a transform running `pm` on every function of the module.
+    pub fn add_function_passes(&mut self, pm: PassManager<Function>) {
+        self.add_transform(FunctionPasses(RefCell::new(pm)))
+    }
+}
+
+impl TransformPass<Module> for FunctionPasses {
+    fn run_on_and_report_change(&self, module: &mut Module, _result: &mut Box<dyn Any>) -> bool {
+        self.0.borrow_mut().run_on_module(module)
+    }
+}
+
+fn run_passes<T>(passes: &[Pass<T>], target: &mut T, results: &mut AnalysisResults) -> bool {
+    let mut changed = false;
+    for pass in passes {
+        match pass {
+            Pass::Analysis(analysis) => run_analysis(analysis.as_ref(), target, results),
+            Pass::Transform(transform) => {
+                changed |= run_transform(transform.as_ref(), target, results)
+            }
         }
     }
+    changed
+}
+
+/// Runs `transform` after its dependencies, invalidating `results` if it changed `target`.
+pub(crate) fn run_transform<T>(
+    transform: &dyn TransformPass<T>,
+    target: &mut T,
+    results: &mut AnalysisResults,
+) -> bool {
+    for dep in transform.dependencies() {
+        require_analysis(dep.as_ref(), target, results)
+    }
+    let mut result: Box<dyn Any> = Box::new(());
+    let changed = transform.run_on_with_results(target, results, &mut result);
+    if changed {
+        results.invalidate();
+    }
+    results.insert(result);
+    changed
+}
+
+fn run_analyses<T>(passes: &[Pass<T>], target: &T, results: &mut AnalysisResults) {
+    for pass in passes {
+        if let Pass::Analysis(analysis) = pass {
+            run_analysis(analysis.as_ref(), target, results)
+        }
+    }
+}
+
+fn run_analysis<T>(analysis: &dyn AnalysisPass<T>, target: &T, results: &mut AnalysisResults) {
+    for dep in analysis.dependencies() {
+        require_analysis(dep.as_ref(), target, results)
+    }
+    let mut result: Box<dyn Any> = Box::new(());
+    analysis.run_on_with_results(target, results, &mut result);
+    results.insert(result);
+    results.valid.insert(analysis.name());
+}
+
+/// Runs `analysis` unless its result is still valid.
+fn require_analysis<T>(analysis: &dyn AnalysisPass<T>, target: &T, results: &mut AnalysisResults) {
+    if !results.is_valid(analysis.name()) {
+        run_analysis(analysis, target, results)
+    }
 }
 
 impl<T> Pass<T> {
@@ -109,10 +274,8 @@ impl<T> Pass<T> {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{
-        ir::{function::Function, module::Module},
-        parser::assembly::module::parse as parse_assembly,
-    };
+    use crate::parser::assembly::module::parse as parse_assembly;
+    use std::{cell::Cell, rc::Rc};
 
     pub struct TestFunctionAnalysisPass {}
     pub struct TestFunctionAnalysisResult(String);
@@ -173,4 +336,112 @@ define dso_local i32 @main() {
             "main"
         );
     }
+
+    /// Counts how many times it has run.
+    pub struct CountingAnalysisPass(Rc<Cell<usize>>);
+    pub struct CountingAnalysisResult(usize);
+
+    impl AnalysisPass<Function> for CountingAnalysisPass {
+        fn run_on(&self, _func: &Function, result: &mut Box<dyn Any>) {
+            self.0.set(self.0.get() + 1);
+            *result = Box::new(CountingAnalysisResult(self.0.get()));
+        }
+    }
+
+    /// Depends on `CountingAnalysisPass` and records the result it sees.
+    pub struct DependentTransformPass {
+        counter: Rc<Cell<usize>>,
+        seen: Rc<RefCell<Vec<usize>>>,
+        changes: bool,
+    }
+
+    impl TransformPass<Function> for DependentTransformPass {
+        fn dependencies(&self) -> Vec<Box<dyn AnalysisPass<Function>>> {
+            vec![Box::new(CountingAnalysisPass(self.counter.clone()))]
+        }
+
+        fn run_on_with_results(
+            &self,
+            _func: &mut Function,
+            results: &AnalysisResults,
+            _result: &mut Box<dyn Any>,
+        ) -> bool {
+            let count = results.get::<CountingAnalysisResult>().unwrap().0;
+            self.seen.borrow_mut().push(count);
+            self.changes
+        }
+    }
+
+    fn two_functions() -> Module {
+        parse_assembly(
+            r#"
+define dso_local i32 @f() {
+  ret i32 1
+}
+
+define dso_local i32 @main() {
+  %1 = call i32 @f()
+  ret i32 %1
+}
+        "#,
+        )
+        .expect("failed to parse IR")
+    }
+
+    #[test]
+    fn per_function_results() {
+        let module = two_functions();
+
+        let mut pm = PassManager::new();
+        pm.add_analysis(TestFunctionAnalysisPass {});
+        pm.run_analyses_on_module(&module);
+
+        for name in ["f", "main"] {
+            let id = module.find_function_by_name(name).unwrap();
+            assert_eq!(
+                pm.get_function_result::<TestFunctionAnalysisResult>(id)
+                    .unwrap()
+                    .0,
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn dependencies() {
+        let mut module = test_module();
+        let counter = Rc::new(Cell::new(0));
+        let seen = Rc::new(RefCell::new(vec![]));
+        let transform = |changes| DependentTransformPass {
+            counter: counter.clone(),
+            seen: seen.clone(),
+            changes,
+        };
+
+        let mut pm = PassManager::new();
+        pm.add_transform(transform(false));
+        // The cached result is reused.
+        pm.add_transform(transform(true));
+        // The previous transform invalidated the result.
+        pm.add_transform(transform(false));
+        assert!(pm.run_on_module(&mut module));
+
+        assert_eq!(*seen.borrow(), vec![1, 1, 2]);
+        assert_eq!(counter.get(), 2);
+    }
+
+    #[test]
+    fn function_passes_in_module() {
+        let mut module = two_functions();
+        let counter = Rc::new(Cell::new(0));
+
+        let mut fpm = PassManager::new();
+        fpm.add_analysis(CountingAnalysisPass(counter.clone()));
+        fpm.add_transform(TestFunctionTransformPass {});
+
+        let mut pm = PassManager::<Module>::new();
+        pm.add_function_passes(fpm);
+        assert!(pm.run_on(&mut module));
+        assert_eq!(counter.get(), 2);
+    }
 }
@@ -0,0 +1,212 @@
+//! Predefined pass pipelines and textual pipeline specifications.
+
+use super::{
+    run_transform,
+    transform::{dce::DCEPass, mem2reg::Mem2RegPass, sccp::SCCPPass},
+    AnalysisResults, Pass, PassManager, TransformPass,
+};
+use crate::ir::function::Function;
+use std::{any::Any, fmt, str::FromStr};
+use thiserror::Error;
+
+/// Default upper bound on the iterations of a `FixedPointPass`.
+pub const DEFAULT_MAX_ITERATIONS: usize = 16;
+
+/// Optimization level, as in `-O0`, `-O1` and `-O2`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OptLevel {
+    #[default]
+    O0,
+    O1,
+    O2,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PipelineError {
+    #[error("unknown pass: {0}")]
+    UnknownPass(String),
+
+    #[error("unknown optimization level: {0}")]
+    UnknownOptLevel(String),
+
+    #[error("unbalanced parentheses in pipeline: {0}")]
+    UnbalancedParens(String),
+
+    #[error("{0} takes no arguments")]
+    UnexpectedArgs(String),
+}
+
+/// Runs a sequence of transform passes repeatedly until none of them changes the target,
+/// or until the iteration limit is reached.
+pub struct FixedPointPass<T> {
+    passes: Vec<Box<dyn TransformPass<T>>>,
+    max_iterations: usize,
+}
+
+impl<T> FixedPointPass<T> {
+    pub fn new(passes: Vec<Box<dyn TransformPass<T>>>) -> Self {
+        Self {
+            passes,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+        }
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn add<P: 'static + TransformPass<T>>(&mut self, pass: P) {
+        self.passes.push(Box::new(pass))
+    }
+}
+
+impl<T> TransformPass<T> for FixedPointPass<T> {
+    fn run_on(&self, target: &mut T, result: &mut Box<dyn Any>) {
+        self.run_on_and_report_change(target, result);
+    }
+
+    fn run_on_and_report_change(&self, target: &mut T, _result: &mut Box<dyn Any>) -> bool {
+        let mut changed_at_all = false;
+        let mut results = AnalysisResults::default();
+        for _ in 0..self.max_iterations {
+            let mut changed = false;
+            for pass in &self.passes {
+                changed |= run_transform(pass.as_ref(), target, &mut results);
+            }
+            if !changed {
+                break;
+            }
+            changed_at_all = true;
+        }
+        changed_at_all
+    }
+}
+
+impl PassManager<Function> {
+    /// Creates a pass manager running the pipeline for `level`:
+    ///
+    /// - `O0`: nothing
+    /// - `O1`: `mem2reg,sccp,dce`
+    /// - `O2`: `mem2reg,fixed-point(sccp,dce)`
+    pub fn with_opt_level(level: OptLevel) -> Self {
+        let mut pm = Self::new();
+        for pass in opt_level_passes(level) {
+            pm.add(Pass::Transform(pass))
+        }
+        pm
+    }
+
+    /// Creates a pass manager from a comma-separated list of passes (e.g. `mem2reg,sccp,dce`).
+    ///
+    /// `fixed-point(...)` runs the enclosed passes until none of them changes the function,
+    /// and `O0`, `O1` and `O2` expand to the pipeline of the level.
+    pub fn from_pipeline(spec: &str) -> Result<Self, PipelineError> {
+        let mut pm = Self::new();
+        for pass in parse_pipeline(spec)? {
+            pm.add(Pass::Transform(pass))
+        }
+        Ok(pm)
+    }
+}
+
+fn opt_level_passes(level: OptLevel) -> Vec<Box<dyn TransformPass<Function>>> {
+    match level {
+        OptLevel::O0 => vec![],
+        OptLevel::O1 => vec![Box::new(Mem2RegPass), Box::new(SCCPPass), Box::new(DCEPass)],
+        OptLevel::O2 => vec![
+            Box::new(Mem2RegPass),
+            Box::new(FixedPointPass::new(vec![
+                Box::new(SCCPPass),
+                Box::new(DCEPass),
+            ])),
+        ],
+    }
+}
+
+fn parse_pipeline(spec: &str) -> Result<Vec<Box<dyn TransformPass<Function>>>, PipelineError> {
+    let mut passes: Vec<Box<dyn TransformPass<Function>>> = vec![];
+    for item in split_top_level(spec)? {
+        let item = item.trim();
+        if item.is_empty() {
+            continue;
+        }
+        let (name, args) = match item.find('(') {
+            Some(i) if item.ends_with(')') => {
+                (item[..i].trim(), Some(&item[i + 1..item.len() - 1]))
+            }
+            Some(_) => return Err(PipelineError::UnbalancedParens(spec.to_string())),
+            None => (item, None),
+        };
+        match (name, args) {
+            ("fixed-point", Some(args)) => {
+                passes.push(Box::new(FixedPointPass::new(parse_pipeline(args)?)))
+            }
+            ("fixed-point", None) => passes.push(Box::new(FixedPointPass::new(vec![]))),
+            (name, Some(_)) if is_known_pass(name) => {
+                return Err(PipelineError::UnexpectedArgs(name.to_string()))
+            }
+            ("mem2reg", None) => passes.push(Box::new(Mem2RegPass)),
+            ("sccp", None) => passes.push(Box::new(SCCPPass)),
+            ("dce", None) => passes.push(Box::new(DCEPass)),
+            (name, None) => match name.parse() {
+                Ok(level) => passes.extend(opt_level_passes(level)),
+                Err(_) => return Err(PipelineError::UnknownPass(name.to_string())),
+            },
+            (name, Some(_)) => return Err(PipelineError::UnknownPass(name.to_string())),
+        }
+    }
+    Ok(passes)
+}
+
+fn is_known_pass(name: &str) -> bool {
+    matches!(name, "mem2reg" | "sccp" | "dce") || name.parse::<OptLevel>().is_ok()
+}
+
+/// Splits `spec` at commas that are not enclosed in parentheses.
+fn split_top_level(spec: &str) -> Result<Vec<&str>, PipelineError> {
+    let mut items = vec![];
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in spec.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Err(PipelineError::UnbalancedParens(spec.to_string())),
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&spec[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err(PipelineError::UnbalancedParens(spec.to_string()));
+    }
+    items.push(&spec[start..]);
+    Ok(items)
+}
+
+impl FromStr for OptLevel {
+    type Err = PipelineError;
+
+    /// Accepts `0`, `O0`, `-O0` and so on.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim_start_matches('-').trim_start_matches('O') {
+            "0" => Ok(Self::O0),
+            "1" => Ok(Self::O1),
+            "2" => Ok(Self::O2),
+            _ => Err(PipelineError::UnknownOptLevel(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for OptLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::O0 => write!(f, "O0"),
+            Self::O1 => write!(f, "O1"),
+            Self::O2 => write!(f, "O2"),
+        }
+    }
+}
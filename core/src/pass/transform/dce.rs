@@ -1,12 +1,18 @@
-use crate::ir::{
-    function::{
-        data::Data,
-        instruction::{InstructionId, Opcode},
-        Function,
+use crate::{
+    ir::{
+        function::{
+            data::Data,
+            instruction::{InstructionId, Opcode},
+            Function,
+        },
+        module::Module,
+        value::Value,
     },
-    module::Module,
-    value::Value,
+    pass::TransformPass,
 };
+use std::any::Any;
+
+pub struct DCEPass;
 
 pub fn run_on_module(module: &mut Module) {
     for (_, function) in module.functions_mut().iter_mut() {
@@ -14,7 +20,8 @@ pub fn run_on_module(module: &mut Module) {
     }
 }
 
-pub fn run_on_function(func: &mut Function) {
+/// Returns `true` if any instruction was removed.
+pub fn run_on_function(func: &mut Function) -> bool {
     let mut worklist = vec![];
     let mut elimination_list = vec![];
 
@@ -24,16 +31,31 @@ pub fn run_on_function(func: &mut Function) {
         }
     }
 
+    let mut changed = !elimination_list.is_empty();
+
     while let Some(inst) = elimination_list.pop() {
         func.remove_inst(inst).unwrap();
     }
 
     while let Some(inst) = worklist.pop() {
         check_if_elimination_possible(&func.data, inst, &mut elimination_list, &mut worklist);
+        changed |= !elimination_list.is_empty();
         while let Some(inst) = elimination_list.pop() {
             func.remove_inst(inst).unwrap();
         }
     }
+
+    changed
+}
+
+impl TransformPass<Function> for DCEPass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        run_on_function(func);
+    }
+
+    fn run_on_and_report_change(&self, func: &mut Function, _result: &mut Box<dyn Any>) -> bool {
+        run_on_function(func)
+    }
 }
 
 fn check_if_elimination_possible(
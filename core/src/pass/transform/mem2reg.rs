@@ -285,7 +285,8 @@ impl<'a> Mem2Reg<'a> {
                                 ty,
                                 args: vec![],
                                 blocks: vec![],
-                            }));
+                            }))
+                            .with_ty(ty);
                         let phi_id = self.func.data.create_inst(phi);
                         self.func.layout.insert_inst_at_start(phi_id, succ_id);
                         added_phis
@@ -1,14 +1,19 @@
 // Sparse Conditional Constant Propagation
 
-use crate::ir::{
-    function::{
-        basic_block::BasicBlockId,
-        instruction::{Br, Instruction, Opcode, Operand},
-        Function,
+use crate::{
+    ir::{
+        function::{
+            basic_block::BasicBlockId,
+            instruction::{Br, Instruction, Opcode, Operand},
+            Function,
+        },
+        value::{ConstantValue, Value},
     },
-    value::{ConstantValue, Value},
+    pass::TransformPass,
 };
-use std::collections::VecDeque;
+use std::{any::Any, collections::VecDeque};
+
+pub struct SCCPPass;
 
 pub struct SCCP<'a> {
     func: &'a mut Function,
@@ -19,7 +24,8 @@ impl<'a> SCCP<'a> {
         Self { func }
     }
 
-    pub fn run(&mut self) {
+    /// Returns `true` if any instruction was folded.
+    pub fn run(&mut self) -> bool {
         let mut foldable = VecDeque::new();
         let mut foldable_condbr = VecDeque::new();
         let mut changed = false;
@@ -74,8 +80,10 @@ impl<'a> SCCP<'a> {
         }
 
         if changed {
-            self.run()
+            self.run();
         }
+
+        changed
     }
 
     fn is_foldable(&self, inst: &Instruction) -> bool {
@@ -141,3 +149,13 @@ impl<'a> SCCP<'a> {
         }
     }
 }
+
+impl TransformPass<Function> for SCCPPass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        SCCP::new(func).run();
+    }
+
+    fn run_on_and_report_change(&self, func: &mut Function, _result: &mut Box<dyn Any>) -> bool {
+        SCCP::new(func).run()
+    }
+}
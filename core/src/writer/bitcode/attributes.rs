@@ -0,0 +1,186 @@
+use super::{
+    bitstream::{chars, Writer},
+    types::TypeTable,
+    Error,
+};
+use crate::{
+    ir::{function::param_attrs::ParameterAttribute, module::attributes::Attribute},
+    parser::bitcode::{
+        attributes::{
+            FUNCTION_INDEX, FUNC_ATTR_KINDS, KIND_ALIGNMENT, KIND_ALLOC_SIZE, KIND_DEREFERENCEABLE,
+            KIND_DEREFERENCEABLE_OR_NULL, KIND_STACK_ALIGNMENT, KIND_STRUCT_RET, PARAM_ATTR_KINDS,
+        },
+        PARAMATTR_BLOCK_ID, PARAMATTR_GROUP_BLOCK_ID,
+    },
+};
+use rustc_hash::FxHashMap;
+
+const PARAMATTR_CODE_ENTRY: u32 = 2;
+const PARAMATTR_GRP_CODE_ENTRY: u32 = 3;
+
+/// Attribute groups and the lists of them referred by functions and call sites.
+#[derive(Default)]
+pub struct AttributeTable {
+    /// (index, encoded attributes) in the order of group ids, which start from 1.
+    groups: Vec<(u64, Vec<u64>)>,
+    group_ids: FxHashMap<(u64, Vec<u64>), u64>,
+    lists: Vec<Vec<u64>>,
+    list_ids: FxHashMap<Vec<u64>, u64>,
+}
+
+impl AttributeTable {
+    /// Adds the attributes of a function or a call site, and returns the id of the list, where `0`
+    /// means no attributes. Function attributes referring to attribute groups (`#N`) are looked up
+    /// in `groups`.
+    pub fn list(
+        &mut self,
+        types: &mut TypeTable,
+        groups: &FxHashMap<u32, Vec<Attribute>>,
+        func: &[Attribute],
+        ret: &[ParameterAttribute],
+        params: &[&[ParameterAttribute]],
+    ) -> Result<u64, Error> {
+        let mut list = vec![];
+        if let Some(id) = self.func_group(groups, func)? {
+            list.push(id)
+        }
+        if let Some(id) = self.param_group(types, 0, ret)? {
+            list.push(id)
+        }
+        for (i, attrs) in params.iter().enumerate() {
+            if let Some(id) = self.param_group(types, i as u64 + 1, attrs)? {
+                list.push(id)
+            }
+        }
+        if list.is_empty() {
+            return Ok(0);
+        }
+        if let Some(&id) = self.list_ids.get(&list) {
+            return Ok(id);
+        }
+        self.lists.push(list.clone());
+        let id = self.lists.len() as u64;
+        self.list_ids.insert(list, id);
+        Ok(id)
+    }
+
+    pub fn write(&self, w: &mut Writer) {
+        if self.lists.is_empty() {
+            return;
+        }
+        w.enter_block(PARAMATTR_GROUP_BLOCK_ID);
+        for (i, (index, attrs)) in self.groups.iter().enumerate() {
+            let ops: Vec<_> = [i as u64 + 1, *index]
+                .into_iter()
+                .chain(attrs.iter().copied())
+                .collect();
+            w.record(PARAMATTR_GRP_CODE_ENTRY, &ops);
+        }
+        w.exit_block();
+        w.enter_block(PARAMATTR_BLOCK_ID);
+        for list in &self.lists {
+            w.record(PARAMATTR_CODE_ENTRY, list);
+        }
+        w.exit_block();
+    }
+
+    fn func_group(
+        &mut self,
+        groups: &FxHashMap<u32, Vec<Attribute>>,
+        attrs: &[Attribute],
+    ) -> Result<Option<u64>, Error> {
+        let mut ops = vec![];
+        for attr in attrs {
+            match attr {
+                Attribute::Ref(id) => {
+                    let attrs = groups.get(id).ok_or_else(|| {
+                        Error::Invalid(format!("undefined attribute group #{}", id))
+                    })?;
+                    for attr in attrs {
+                        encode_func_attr(&mut ops, attr)
+                    }
+                }
+                attr => encode_func_attr(&mut ops, attr),
+            }
+        }
+        Ok(self.group(FUNCTION_INDEX, ops))
+    }
+
+    fn param_group(
+        &mut self,
+        types: &mut TypeTable,
+        index: u64,
+        attrs: &[ParameterAttribute],
+    ) -> Result<Option<u64>, Error> {
+        let mut ops = vec![];
+        for attr in attrs {
+            match attr {
+                ParameterAttribute::SRet(None) => ops.extend([5, KIND_STRUCT_RET]),
+                ParameterAttribute::SRet(Some(ty)) => {
+                    let ty = types.ty(*ty)?;
+                    ops.extend([6, KIND_STRUCT_RET, types.id(ty)])
+                }
+                ParameterAttribute::Alignment(v) => ops.extend([1, KIND_ALIGNMENT, *v]),
+                ParameterAttribute::Dereferenceable(v) => ops.extend([1, KIND_DEREFERENCEABLE, *v]),
+                ParameterAttribute::DereferenceableOrNull(v) => {
+                    ops.extend([1, KIND_DEREFERENCEABLE_OR_NULL, *v])
+                }
+                ParameterAttribute::StringAttribute { kind, value } => {
+                    encode_string_attr(&mut ops, kind, value)
+                }
+                // Attribute groups of parameters are not expressible in the textual form.
+                ParameterAttribute::Ref(_) | ParameterAttribute::UnknownAttribute => {}
+                attr => match PARAM_ATTR_KINDS.iter().find(|(_, a)| a == attr) {
+                    Some((kind, _)) => ops.extend([0, *kind]),
+                    None => log::debug!("parameter attribute without a kind: {:?}", attr),
+                },
+            }
+        }
+        Ok(self.group(index, ops))
+    }
+
+    fn group(&mut self, index: u64, ops: Vec<u64>) -> Option<u64> {
+        if ops.is_empty() {
+            return None;
+        }
+        let key = (index, ops);
+        if let Some(&id) = self.group_ids.get(&key) {
+            return Some(id);
+        }
+        self.groups.push(key.clone());
+        let id = self.groups.len() as u64;
+        self.group_ids.insert(key, id);
+        Some(id)
+    }
+}
+
+fn encode_func_attr(ops: &mut Vec<u64>, attr: &Attribute) {
+    match attr {
+        Attribute::AlignStack(v) => ops.extend([1, KIND_STACK_ALIGNMENT, *v]),
+        Attribute::AllocSize { elt_size, num_elts } => ops.extend([
+            1,
+            KIND_ALLOC_SIZE,
+            ((*elt_size as u64) << 32) | num_elts.unwrap_or(u32::MAX) as u64,
+        ]),
+        Attribute::StringAttribute { kind, value } => encode_string_attr(ops, kind, value),
+        Attribute::Ref(_) => {}
+        attr => match FUNC_ATTR_KINDS.iter().find(|(_, a)| a == attr) {
+            Some((kind, _)) => ops.extend([0, *kind]),
+            None => log::debug!("function attribute without a kind: {:?}", attr),
+        },
+    }
+}
+
+fn encode_string_attr(ops: &mut Vec<u64>, kind: &str, value: &str) {
+    if value.is_empty() {
+        ops.push(3);
+        ops.extend(chars(kind));
+        ops.push(0);
+    } else {
+        ops.push(4);
+        ops.extend(chars(kind));
+        ops.push(0);
+        ops.extend(chars(value));
+        ops.push(0);
+    }
+}
@@ -0,0 +1,165 @@
+const END_BLOCK: u64 = 0;
+const ENTER_SUBBLOCK: u64 = 1;
+const DEFINE_ABBREV: u64 = 2;
+const UNABBREV_RECORD: u64 = 3;
+
+const ENCODING_BLOB: u64 = 5;
+
+/// The width of abbreviation ids at the top level.
+const TOP_LEVEL_ABBREV_WIDTH: u32 = 2;
+/// The width of abbreviation ids in every block written.
+const ABBREV_WIDTH: u32 = 3;
+
+/// Writes records into the LLVM bitstream container format.
+///
+/// Records are emitted unabbreviated, except for blobs which need an abbreviation.
+pub struct Writer {
+    bytes: Vec<u8>,
+    /// The number of bits written.
+    pos: usize,
+    abbrev_width: u32,
+    blocks: Vec<BlockScope>,
+}
+
+struct BlockScope {
+    /// The abbreviation width of the enclosing block.
+    outer_abbrev_width: u32,
+    /// The byte offset of the block length, which is filled when the block ends.
+    len_offset: usize,
+    /// The number of abbreviations defined in the block.
+    num_abbrevs: u64,
+}
+
+impl Writer {
+    pub fn new() -> Self {
+        Self {
+            bytes: vec![],
+            pos: 0,
+            abbrev_width: TOP_LEVEL_ABBREV_WIDTH,
+            blocks: vec![],
+        }
+    }
+
+    /// Writes `bytes` as is. Must be at a byte boundary.
+    pub fn raw(&mut self, bytes: &[u8]) {
+        debug_assert!(self.pos & 7 == 0);
+        self.bytes.extend_from_slice(bytes);
+        self.pos += bytes.len() * 8;
+    }
+
+    pub fn enter_block(&mut self, id: u32) {
+        self.emit(ENTER_SUBBLOCK, self.abbrev_width);
+        self.emit_vbr(id as u64, 8);
+        self.emit_vbr(ABBREV_WIDTH as u64, 4);
+        self.align32();
+        let len_offset = self.bytes.len();
+        self.emit(0, 32);
+        self.blocks.push(BlockScope {
+            outer_abbrev_width: self.abbrev_width,
+            len_offset,
+            num_abbrevs: 0,
+        });
+        self.abbrev_width = ABBREV_WIDTH;
+    }
+
+    pub fn exit_block(&mut self) {
+        let scope = self.blocks.pop().expect("no block to exit");
+        self.emit(END_BLOCK, self.abbrev_width);
+        self.align32();
+        let num_words = (self.bytes.len() - scope.len_offset - 4) as u32 / 4;
+        self.bytes[scope.len_offset..scope.len_offset + 4]
+            .copy_from_slice(&num_words.to_le_bytes());
+        self.abbrev_width = scope.outer_abbrev_width;
+    }
+
+    pub fn record(&mut self, code: u32, ops: &[u64]) {
+        self.emit(UNABBREV_RECORD, self.abbrev_width);
+        self.emit_vbr(code as u64, 6);
+        self.emit_vbr(ops.len() as u64, 6);
+        for &op in ops {
+            self.emit_vbr(op, 6);
+        }
+    }
+
+    /// Writes a record consisting of `code` and `blob`, defining an abbreviation for it.
+    pub fn blob_record(&mut self, code: u32, blob: &[u8]) {
+        let scope = self.blocks.last_mut().expect("blobs must be in a block");
+        let abbrev_id = 4 + scope.num_abbrevs;
+        scope.num_abbrevs += 1;
+
+        // [literal(code), blob]
+        self.emit(DEFINE_ABBREV, self.abbrev_width);
+        self.emit_vbr(2, 5);
+        self.emit(1, 1);
+        self.emit_vbr(code as u64, 8);
+        self.emit(0, 1);
+        self.emit(ENCODING_BLOB, 3);
+
+        self.emit(abbrev_id, self.abbrev_width);
+        self.emit_vbr(blob.len() as u64, 6);
+        self.align32();
+        self.raw(blob);
+        self.align32();
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        debug_assert!(self.blocks.is_empty());
+        self.bytes
+    }
+
+    fn emit(&mut self, mut val: u64, mut width: u32) {
+        while width > 0 {
+            let offset = (self.pos & 7) as u32;
+            if offset == 0 {
+                self.bytes.push(0);
+            }
+            let n = (8 - offset).min(width);
+            *self.bytes.last_mut().unwrap() |= ((val & ((1 << n) - 1)) << offset) as u8;
+            val >>= n;
+            width -= n;
+            self.pos += n as usize;
+        }
+    }
+
+    fn emit_vbr(&mut self, mut val: u64, width: u32) {
+        let hi = 1 << (width - 1);
+        while val >= hi {
+            self.emit((val & (hi - 1)) | hi, width);
+            val >>= width - 1;
+        }
+        self.emit(val, width)
+    }
+
+    fn align32(&mut self) {
+        let pad = (32 - self.pos % 32) % 32;
+        self.emit(0, pad as u32);
+    }
+}
+
+/// Encodes `s` as a sequence of characters, one per operand.
+pub fn chars(s: &str) -> impl Iterator<Item = u64> + '_ {
+    s.bytes().map(|c| c as u64)
+}
+
+/// Encodes `v` as a sign-rotated value, where the sign is kept in the lowest bit.
+pub fn encode_signed(v: i64) -> u64 {
+    if v >= 0 {
+        (v as u64) << 1
+    } else {
+        (v.unsigned_abs() << 1) | 1
+    }
+}
+
+#[test]
+fn write_bits() {
+    use crate::parser::bitcode::bitstream::decode_signed;
+
+    let mut w = Writer::new();
+    w.emit(0b101, 3);
+    w.emit_vbr(100, 4);
+    w.align32();
+    assert_eq!(w.bytes, [0b0110_0101, 0b0000_1110, 0, 0]);
+    for v in [0, 1, -1, 42, -42, i64::MAX, i64::MIN + 1] {
+        assert_eq!(decode_signed(encode_signed(v)), v);
+    }
+}
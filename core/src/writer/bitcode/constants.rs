@@ -0,0 +1,203 @@
+use super::{
+    bitstream::{chars, encode_signed, Writer},
+    types::{TypeRef, TypeTable},
+    Error,
+};
+use crate::{
+    ir::{
+        module::name::Name,
+        types::Typed,
+        value::{ConstantExpr, ConstantFloat, ConstantValue, InlineAsm},
+    },
+    parser::bitcode::CONSTANTS_BLOCK_ID,
+};
+use rustc_hash::FxHashMap;
+
+const SETTYPE: u32 = 1;
+const NULL: u32 = 2;
+const UNDEF: u32 = 3;
+const INTEGER: u32 = 4;
+const FLOAT_: u32 = 6;
+const AGGREGATE: u32 = 7;
+const STRING: u32 = 8;
+const CSTRING: u32 = 9;
+const CE_CAST: u32 = 11;
+const CE_GEP: u32 = 12;
+const CE_INBOUNDS_GEP: u32 = 20;
+const INLINEASM: u32 = 30;
+
+const CAST_BITCAST: u64 = 11;
+
+/// Value ids and types of global variables and functions.
+pub type GlobalValues = FxHashMap<Name, (u64, TypeRef)>;
+
+/// What a [`Constants`] needs to number constants.
+pub struct Context<'a, 'b> {
+    pub types: &'b mut TypeTable<'a>,
+    pub globals: &'b GlobalValues,
+    /// The constants of the module, when numbering the constants of a function.
+    pub module: Option<&'b Constants>,
+}
+
+/// Constants numbered from `first_id`, in the order they're written to a CONSTANTS_BLOCK. A
+/// constant comes after the ones it refers to.
+pub struct Constants {
+    first_id: u64,
+    /// (type, code, operands)
+    records: Vec<(TypeRef, u32, Vec<u64>)>,
+    ids: FxHashMap<(TypeRef, String), u64>,
+}
+
+impl Constants {
+    pub fn new(first_id: u64) -> Self {
+        Self {
+            first_id,
+            records: vec![],
+            ids: FxHashMap::default(),
+        }
+    }
+
+    /// Returns the id that comes right after the constants.
+    pub fn end_id(&self) -> u64 {
+        self.first_id + self.records.len() as u64
+    }
+
+    /// Adds `c` and the constants it refers to. Returns the value id and the type of `c`.
+    pub fn add(&mut self, c: &ConstantValue, ctx: &mut Context) -> Result<(u64, TypeRef), Error> {
+        if let ConstantValue::GlobalRef(name, _) = c {
+            return ctx
+                .globals
+                .get(name)
+                .copied()
+                .ok_or_else(|| Error::Invalid(format!("undefined global value {:?}", name)));
+        }
+
+        let ty = match c {
+            ConstantValue::Expr(ConstantExpr::GetElementPtr { tys, args, .. }) => {
+                let indices = args[1..]
+                    .iter()
+                    .map(|a| a.as_int().map_or(0, |i| i.cast_to_usize()));
+                ctx.types.gep(tys[1], indices)?
+            }
+            c => ctx.types.ty(c.ty())?,
+        };
+        let key = (ty, c.to_string(ctx.types.ir()));
+        if let Some(id) = ctx.module.and_then(|m| m.ids.get(&key)) {
+            return Ok((*id, ty));
+        }
+        if let Some(&id) = self.ids.get(&key) {
+            return Ok((id, ty));
+        }
+
+        let (code, ops) = match c {
+            ConstantValue::Undef(_) => (UNDEF, vec![]),
+            ConstantValue::Null(_) | ConstantValue::AggregateZero(_) => (NULL, vec![]),
+            ConstantValue::Int(i) => (INTEGER, vec![encode_signed(i.cast_to_i64())]),
+            ConstantValue::Float(f) => (FLOAT_, float(f)),
+            ConstantValue::Array(a) if a.is_string => {
+                let bytes: Vec<u64> = a
+                    .elems
+                    .iter()
+                    .map(|e| e.as_int().map_or(0, |i| i.cast_to_i64() as u8 as u64))
+                    .collect();
+                match bytes.split_last() {
+                    Some((0, rest)) if !rest.contains(&0) => (CSTRING, rest.to_vec()),
+                    _ => (STRING, bytes),
+                }
+            }
+            ConstantValue::Array(a) => (AGGREGATE, self.add_all(&a.elems, ctx)?),
+            ConstantValue::Struct(s) => (AGGREGATE, self.add_all(&s.elems, ctx)?),
+            ConstantValue::Expr(ConstantExpr::Bitcast { tys, arg }) => {
+                let from = ctx.types.ty(tys[0])?;
+                let (arg, _) = self.add(arg, ctx)?;
+                (CE_CAST, vec![CAST_BITCAST, ctx.types.id(from), arg])
+            }
+            ConstantValue::Expr(ConstantExpr::GetElementPtr {
+                inbounds,
+                tys,
+                args,
+            }) => {
+                // [pointee type, (type, value)*]
+                let elem = ctx.types.ty(tys[0])?;
+                let mut ops = vec![ctx.types.id(elem)];
+                for arg in args {
+                    let (arg, ty) = self.add(arg, ctx)?;
+                    ops.push(ctx.types.id(ty));
+                    ops.push(arg);
+                }
+                (if *inbounds { CE_INBOUNDS_GEP } else { CE_GEP }, ops)
+            }
+            ConstantValue::GlobalRef(..) => unreachable!(),
+        };
+        Ok((self.push(key, ty, code, ops), ty))
+    }
+
+    /// Adds an inline assembly called through a function of type `func_ty`.
+    pub fn add_inline_asm(
+        &mut self,
+        asm: &InlineAsm,
+        func_ty: TypeRef,
+        ctx: &mut Context,
+    ) -> (u64, TypeRef) {
+        let ty = ctx.types.pointer(func_ty, 0);
+        // The textual parser keeps the asm string in `constraints`, and vice versa.
+        let key = (
+            ty,
+            format!(
+                "asm {} {:?} {:?}",
+                asm.sideeffect, asm.constraints, asm.body
+            ),
+        );
+        if let Some(&id) = self.ids.get(&key) {
+            return (id, ty);
+        }
+        // [fnty, flags, asmlen, asm, constraintslen, constraints]
+        let mut ops = vec![ctx.types.id(func_ty), asm.sideeffect as u64];
+        ops.push(asm.constraints.len() as u64);
+        ops.extend(chars(&asm.constraints));
+        ops.push(asm.body.len() as u64);
+        ops.extend(chars(&asm.body));
+        (self.push(key, ty, INLINEASM, ops), ty)
+    }
+
+    pub fn write(&self, w: &mut Writer, types: &TypeTable) {
+        if self.records.is_empty() {
+            return;
+        }
+        w.enter_block(CONSTANTS_BLOCK_ID);
+        let mut cur_ty = None;
+        for (ty, code, ops) in &self.records {
+            if cur_ty != Some(*ty) {
+                w.record(SETTYPE, &[types.id(*ty)]);
+                cur_ty = Some(*ty);
+            }
+            w.record(*code, ops);
+        }
+        w.exit_block();
+    }
+
+    fn add_all(&mut self, elems: &[ConstantValue], ctx: &mut Context) -> Result<Vec<u64>, Error> {
+        elems
+            .iter()
+            .map(|e| self.add(e, ctx).map(|(id, _)| id))
+            .collect()
+    }
+
+    fn push(&mut self, key: (TypeRef, String), ty: TypeRef, code: u32, ops: Vec<u64>) -> u64 {
+        let id = self.end_id();
+        self.records.push((ty, code, ops));
+        self.ids.insert(key, id);
+        id
+    }
+}
+
+fn float(f: &ConstantFloat) -> Vec<u64> {
+    match *f {
+        ConstantFloat::Half(bits) => vec![bits as u64],
+        ConstantFloat::Float(f) => vec![f.to_bits() as u64],
+        ConstantFloat::Double(f) => vec![f.to_bits()],
+        // The sign and exponent come first, then the significand.
+        ConstantFloat::X86Fp80(bits) => vec![(bits >> 16) as u64, bits as u64 & 0xffff],
+        ConstantFloat::Fp128(bits) => vec![bits as u64, (bits >> 64) as u64],
+    }
+}
@@ -0,0 +1,707 @@
+use super::{
+    attributes::AttributeTable,
+    bitstream::{chars, encode_signed, Writer},
+    constants::{Constants, Context, GlobalValues},
+    metadata::MetadataTable,
+    types::{TypeRef, TypeTable},
+    Error,
+};
+use crate::{
+    ir::{
+        function::{
+            basic_block::BasicBlockId,
+            instruction::{
+                Alloca, Br, Call, Cast, CondBr, ExtractValue, FCmp, FCmpCond, FastMathFlags,
+                FloatBinary, FloatUnary, GetElementPtr, ICmp, ICmpCond, InsertValue, InstructionId,
+                IntBinary, Invoke, LandingPad, Load, Opcode, Operand, Phi, Resume, Ret, Select,
+                Store, Switch,
+            },
+            param_attrs::ParameterAttribute,
+            Function,
+        },
+        module::{attributes::Attribute, name::Name},
+        types::Type,
+        value::{ConstantValue, Value, ValueId},
+    },
+    parser::bitcode::{FUNCTION_BLOCK_ID, METADATA_ATTACHMENT_ID, VALUE_SYMTAB_BLOCK_ID},
+};
+use rustc_hash::FxHashMap;
+
+const DECLAREBLOCKS: u32 = 1;
+const BINOP: u32 = 2;
+const CAST: u32 = 3;
+const RET: u32 = 10;
+const BR: u32 = 11;
+const SWITCH: u32 = 12;
+const INVOKE: u32 = 13;
+const UNREACHABLE: u32 = 15;
+const PHI: u32 = 16;
+const ALLOCA: u32 = 19;
+const LOAD: u32 = 20;
+const EXTRACTVAL: u32 = 26;
+const INSERTVAL: u32 = 27;
+const CMP2: u32 = 28;
+const VSELECT: u32 = 29;
+const CALL: u32 = 34;
+const RESUME: u32 = 39;
+const GEP: u32 = 43;
+const STORE: u32 = 44;
+const LANDINGPAD: u32 = 47;
+const UNOP: u32 = 56;
+
+const VST_ENTRY: u32 = 1;
+const VST_BBENTRY: u32 = 2;
+
+const ATTACHMENT: u32 = 11;
+
+const CALL_EXPLICIT_TYPE: u64 = 1 << 15;
+const INVOKE_EXPLICIT_TYPE: u64 = 1 << 13;
+const ALLOCA_EXPLICIT_TYPE: u64 = 1 << 6;
+
+/// What a function body refers to outside of it.
+pub struct Env<'a, 'b, 'm> {
+    pub types: &'b mut TypeTable<'a>,
+    pub globals: &'b GlobalValues,
+    pub constants: &'b Constants,
+    pub attrs: &'b mut AttributeTable,
+    pub attr_groups: &'m FxHashMap<u32, Vec<Attribute>>,
+    pub metadata: &'b MetadataTable<'m>,
+}
+
+/// A function body encoded into records, which is done before anything is written so that every
+/// type and attribute it uses is known.
+pub struct FunctionBlock {
+    constants: Constants,
+    num_blocks: u64,
+    records: Vec<(u32, Vec<u64>)>,
+    symbols: Vec<(u32, Vec<u64>)>,
+    attachments: Vec<Vec<u64>>,
+}
+
+struct Encoder<'f, 'a, 'b, 'm> {
+    func: &'f Function,
+    env: Env<'a, 'b, 'm>,
+    constants: Constants,
+    first_arg: u64,
+    insts: FxHashMap<InstructionId, (u64, TypeRef)>,
+    blocks: FxHashMap<BasicBlockId, u64>,
+    /// The id of the value the current instruction defines, if any.
+    cur: u64,
+}
+
+impl FunctionBlock {
+    pub fn new(func: &Function, env: Env) -> Result<Self, Error> {
+        let first_arg = env.constants.end_id();
+        let mut e = Encoder {
+            func,
+            env,
+            constants: Constants::new(first_arg + func.params.len() as u64),
+            first_arg,
+            insts: FxHashMap::default(),
+            blocks: FxHashMap::default(),
+            cur: 0,
+        };
+
+        // Number the constants used in the body, then the instructions.
+        for block in func.layout.block_iter() {
+            e.blocks.insert(block, e.blocks.len() as u64);
+            for id in func.layout.inst_iter(block) {
+                e.add_constants(id)?;
+            }
+        }
+        let mut next = e.constants.end_id();
+        for block in func.layout.block_iter() {
+            for id in func.layout.inst_iter(block) {
+                if let Some(ty) = e.value_type(id)? {
+                    e.insts.insert(id, (next, ty));
+                    next += 1;
+                }
+            }
+        }
+
+        let mut records = vec![(DECLAREBLOCKS, vec![e.blocks.len() as u64])];
+        let mut attachments = vec![];
+        e.cur = e.constants.end_id();
+        for (i, id) in func
+            .layout
+            .block_iter()
+            .flat_map(|block| func.layout.inst_iter(block))
+            .enumerate()
+        {
+            records.push(e.instruction(id)?);
+            if e.insts.contains_key(&id) {
+                e.cur += 1;
+            }
+            let attachment = e.attachment(id, i as u64)?;
+            if attachment.len() > 1 {
+                attachments.push(attachment)
+            }
+        }
+
+        Ok(Self {
+            symbols: e.symbols(),
+            num_blocks: e.blocks.len() as u64,
+            constants: e.constants,
+            records,
+            attachments,
+        })
+    }
+
+    pub fn write(&self, w: &mut Writer, types: &TypeTable) {
+        w.enter_block(FUNCTION_BLOCK_ID);
+        self.constants.write(w, types);
+        debug_assert!(self.num_blocks > 0);
+        for (code, ops) in &self.records {
+            w.record(*code, ops);
+        }
+        if !self.symbols.is_empty() {
+            w.enter_block(VALUE_SYMTAB_BLOCK_ID);
+            for (code, ops) in &self.symbols {
+                w.record(*code, ops);
+            }
+            w.exit_block();
+        }
+        if !self.attachments.is_empty() {
+            w.enter_block(METADATA_ATTACHMENT_ID);
+            for ops in &self.attachments {
+                w.record(ATTACHMENT, ops);
+            }
+            w.exit_block();
+        }
+        w.exit_block();
+    }
+}
+
+impl<'a> Env<'a, '_, '_> {
+    fn ctx(&mut self) -> Context<'a, '_> {
+        Context {
+            types: self.types,
+            globals: self.globals,
+            module: Some(self.constants),
+        }
+    }
+}
+
+impl Encoder<'_, '_, '_, '_> {
+    fn add_constants(&mut self, id: InstructionId) -> Result<(), Error> {
+        let inst = self.func.data.inst_ref(id);
+        let args = match &inst.operand {
+            // Indices are written as literals.
+            Operand::ExtractValue(ExtractValue { args, .. }) => &args[..1],
+            Operand::InsertValue(InsertValue { args, .. }) => &args[..2],
+            Operand::Call(Call { args, tys, .. }) | Operand::Invoke(Invoke { args, tys, .. }) => {
+                if let Value::InlineAsm(asm) = self.func.data.value_ref(args[0]) {
+                    let func_ty = self.call_type(tys)?;
+                    self.constants
+                        .add_inline_asm(asm, func_ty, &mut self.env.ctx());
+                }
+                &args[..]
+            }
+            Operand::Alloca(Alloca { num_elements, .. }) => {
+                self.add_constant(num_elements)?;
+                &[]
+            }
+            Operand::LandingPad(LandingPad { catches, .. }) => {
+                for &(_, arg) in catches {
+                    self.add_value(arg)?;
+                }
+                &[]
+            }
+            operand => operand.args(),
+        };
+        for &arg in args {
+            self.add_value(arg)?;
+        }
+        Ok(())
+    }
+
+    fn add_value(&mut self, arg: ValueId) -> Result<(), Error> {
+        if let Value::Constant(c) = self.func.data.value_ref(arg) {
+            self.add_constant(c)?;
+        }
+        Ok(())
+    }
+
+    fn add_constant(&mut self, c: &ConstantValue) -> Result<(u64, TypeRef), Error> {
+        self.constants.add(c, &mut self.env.ctx())
+    }
+
+    /// Returns the type of the value `id` defines, if any.
+    fn value_type(&mut self, id: InstructionId) -> Result<Option<TypeRef>, Error> {
+        let inst = self.func.data.inst_ref(id);
+        match &inst.operand {
+            Operand::Call(Call { tys, .. }) | Operand::Invoke(Invoke { tys, .. }) => {
+                let func_ty = self.call_type(tys)?;
+                let (ret, _) = self.env.types.signature(func_ty).unwrap();
+                Ok((!self.env.types.is_void(ret)).then_some(ret))
+            }
+            Operand::Store(_)
+            | Operand::Br(_)
+            | Operand::CondBr(_)
+            | Operand::Switch(_)
+            | Operand::Ret(_)
+            | Operand::Resume(_)
+            | Operand::Unreachable => Ok(None),
+            Operand::Invalid => Err(Error::Invalid("invalid instruction".to_string())),
+            _ => self.env.types.ty(inst.ty).map(Some),
+        }
+    }
+
+    /// Returns the function type of a call site. `tys[0]` is the whole function type if the
+    /// callee is variadic, or the result type otherwise.
+    fn call_type(&mut self, tys: &[Type]) -> Result<TypeRef, Error> {
+        if self.env.types.ir().is_function(tys[0]) {
+            return self.env.types.ty(tys[0]);
+        }
+        let ret = self.env.types.ty(tys[0])?;
+        let params = self.env.types.tys(&tys[1..])?;
+        Ok(self.env.types.function(ret, params, false))
+    }
+
+    /// Returns the absolute id and the type of `id`.
+    fn value(&mut self, id: ValueId) -> Result<(u64, TypeRef), Error> {
+        match self.func.data.value_ref(id) {
+            Value::Instruction(inst) => self.insts.get(inst).copied().ok_or_else(|| {
+                Error::Invalid("use of an instruction not in the function".to_string())
+            }),
+            Value::Argument(arg) => {
+                let param = self.func.params.get(arg.nth).ok_or_else(|| {
+                    Error::Invalid(format!("use of an undefined argument #{}", arg.nth))
+                })?;
+                Ok((
+                    self.first_arg + arg.nth as u64,
+                    self.env.types.ty(param.ty)?,
+                ))
+            }
+            Value::Constant(c) => self.add_constant(c),
+            Value::InlineAsm(_) => Err(Error::Unsupported(
+                "inline asm used other than as a callee".to_string(),
+            )),
+        }
+    }
+
+    /// Pushes the id of `id` relative to the current instruction.
+    fn push_value(&mut self, ops: &mut Vec<u64>, id: ValueId) -> Result<(), Error> {
+        let (id, _) = self.value(id)?;
+        ops.push((self.cur as u32).wrapping_sub(id as u32) as u64);
+        Ok(())
+    }
+
+    /// Pushes the id of `id` relative to the current instruction, followed by its type if `id` is
+    /// a forward reference.
+    fn push_value_and_type(&mut self, ops: &mut Vec<u64>, id: ValueId) -> Result<(), Error> {
+        let (id, ty) = self.value(id)?;
+        ops.push((self.cur as u32).wrapping_sub(id as u32) as u64);
+        if id >= self.cur {
+            ops.push(self.env.types.id(ty));
+        }
+        Ok(())
+    }
+
+    fn ty(&mut self, ty: Type) -> Result<u64, Error> {
+        let ty = self.env.types.ty(ty)?;
+        Ok(self.env.types.id(ty))
+    }
+
+    fn block(&self, block: BasicBlockId) -> Result<u64, Error> {
+        self.blocks
+            .get(&block)
+            .copied()
+            .ok_or_else(|| Error::Invalid("branch to a block not in the function".to_string()))
+    }
+
+    fn index(&self, id: ValueId) -> Result<u64, Error> {
+        match self.func.data.value_ref(id) {
+            Value::Constant(ConstantValue::Int(i)) => Ok(i.cast_to_usize() as u64),
+            _ => Err(Error::Invalid("non-constant aggregate index".to_string())),
+        }
+    }
+
+    fn instruction(&mut self, id: InstructionId) -> Result<(u32, Vec<u64>), Error> {
+        let inst = self.func.data.inst_ref(id);
+        let mut ops = vec![];
+        let code = match &inst.operand {
+            Operand::IntBinary(IntBinary {
+                nsw,
+                nuw,
+                exact,
+                args,
+                ..
+            }) => {
+                self.push_value_and_type(&mut ops, args[0])?;
+                self.push_value(&mut ops, args[1])?;
+                let (opcode, flags) = match inst.opcode {
+                    Opcode::Add => (0, overflow_flags(*nuw, *nsw)),
+                    Opcode::Sub => (1, overflow_flags(*nuw, *nsw)),
+                    Opcode::Mul => (2, overflow_flags(*nuw, *nsw)),
+                    Opcode::UDiv => (3, *exact as u64),
+                    Opcode::SDiv => (4, *exact as u64),
+                    Opcode::URem => (5, 0),
+                    Opcode::SRem => (6, 0),
+                    Opcode::Shl => (7, overflow_flags(*nuw, *nsw)),
+                    Opcode::LShr => (8, *exact as u64),
+                    Opcode::AShr => (9, *exact as u64),
+                    Opcode::And => (10, 0),
+                    Opcode::Or => (11, 0),
+                    Opcode::Xor => (12, 0),
+                    opcode => return Err(mismatch(opcode)),
+                };
+                ops.push(opcode);
+                if flags != 0 {
+                    ops.push(flags)
+                }
+                BINOP
+            }
+            Operand::FloatBinary(FloatBinary { fmf, args, .. }) => {
+                self.push_value_and_type(&mut ops, args[0])?;
+                self.push_value(&mut ops, args[1])?;
+                ops.push(match inst.opcode {
+                    Opcode::FAdd => 0,
+                    Opcode::FSub => 1,
+                    Opcode::FMul => 2,
+                    Opcode::FDiv => 4,
+                    Opcode::FRem => 6,
+                    opcode => return Err(mismatch(opcode)),
+                });
+                push_fast_math_flags(&mut ops, fmf);
+                BINOP
+            }
+            Operand::FloatUnary(FloatUnary { fmf, arg, .. }) => {
+                self.push_value_and_type(&mut ops, *arg)?;
+                ops.push(0); // fneg
+                push_fast_math_flags(&mut ops, fmf);
+                UNOP
+            }
+            Operand::Cast(Cast { tys, arg }) => {
+                self.push_value_and_type(&mut ops, *arg)?;
+                ops.push(self.ty(tys[1])?);
+                ops.push(cast_opcode(inst.opcode)?);
+                CAST
+            }
+            Operand::GetElementPtr(GetElementPtr {
+                inbounds,
+                tys,
+                args,
+            }) => {
+                ops.push(*inbounds as u64);
+                ops.push(self.ty(tys[0])?);
+                for &arg in args {
+                    self.push_value_and_type(&mut ops, arg)?;
+                }
+                GEP
+            }
+            Operand::ExtractValue(ExtractValue { args, .. }) => {
+                self.push_value_and_type(&mut ops, args[0])?;
+                for &idx in &args[1..] {
+                    ops.push(self.index(idx)?);
+                }
+                EXTRACTVAL
+            }
+            Operand::InsertValue(InsertValue { args, .. }) => {
+                self.push_value_and_type(&mut ops, args[0])?;
+                self.push_value_and_type(&mut ops, args[1])?;
+                for &idx in &args[2..] {
+                    ops.push(self.index(idx)?);
+                }
+                INSERTVAL
+            }
+            Operand::ICmp(ICmp { args, cond, .. }) => {
+                self.push_value_and_type(&mut ops, args[0])?;
+                self.push_value(&mut ops, args[1])?;
+                ops.push(icmp_predicate(*cond));
+                CMP2
+            }
+            Operand::Select(Select { args, .. }) => {
+                self.push_value_and_type(&mut ops, args[1])?;
+                self.push_value(&mut ops, args[2])?;
+                self.push_value_and_type(&mut ops, args[0])?;
+                VSELECT
+            }
+            Operand::FCmp(FCmp {
+                fmf, args, cond, ..
+            }) => {
+                self.push_value_and_type(&mut ops, args[0])?;
+                self.push_value(&mut ops, args[1])?;
+                ops.push(fcmp_predicate(*cond));
+                push_fast_math_flags(&mut ops, fmf);
+                CMP2
+            }
+            Operand::Alloca(Alloca {
+                tys,
+                num_elements,
+                align,
+            }) => {
+                // [allocated type, size type, size, flags]
+                let (size, size_ty) = self.add_constant(num_elements)?;
+                ops.push(self.ty(tys[0])?);
+                ops.push(self.env.types.id(size_ty));
+                ops.push(size);
+                let align = encode_align(*align);
+                ops.push((align & 0x1f) | (align >> 5) << 8 | ALLOCA_EXPLICIT_TYPE);
+                ALLOCA
+            }
+            Operand::Load(Load { tys, addr, align }) => {
+                self.push_value_and_type(&mut ops, *addr)?;
+                ops.push(self.ty(tys[0])?);
+                ops.push(encode_align(*align));
+                ops.push(0); // volatile
+                LOAD
+            }
+            Operand::Store(Store { args, align, .. }) => {
+                self.push_value_and_type(&mut ops, args[1])?;
+                self.push_value_and_type(&mut ops, args[0])?;
+                ops.push(encode_align(*align));
+                ops.push(0); // volatile
+                STORE
+            }
+            Operand::Phi(Phi { ty, args, blocks }) => {
+                ops.push(self.ty(*ty)?);
+                for (&arg, &block) in args.iter().zip(blocks) {
+                    let (id, _) = self.value(arg)?;
+                    ops.push(encode_signed(self.cur as i64 - id as i64));
+                    ops.push(self.block(block)?);
+                }
+                PHI
+            }
+            Operand::Call(Call {
+                args,
+                tys,
+                param_attrs,
+                ret_attrs,
+                func_attrs,
+            }) => {
+                // [attrs, cc, fnty, callee, args...]
+                ops.push(self.call_attrs(func_attrs, ret_attrs, param_attrs)?);
+                ops.push(CALL_EXPLICIT_TYPE);
+                self.push_call(&mut ops, args, tys)?;
+                CALL
+            }
+            Operand::Invoke(Invoke {
+                args,
+                tys,
+                param_attrs,
+                ret_attrs,
+                func_attrs,
+                blocks,
+            }) => {
+                // [attrs, cc, normal, unwind, fnty, callee, args...]
+                ops.push(self.call_attrs(func_attrs, ret_attrs, param_attrs)?);
+                ops.push(INVOKE_EXPLICIT_TYPE);
+                ops.push(self.block(blocks[0])?);
+                ops.push(self.block(blocks[1])?);
+                self.push_call(&mut ops, args, tys)?;
+                INVOKE
+            }
+            Operand::LandingPad(LandingPad {
+                ty,
+                catches,
+                cleanup,
+            }) => {
+                ops.push(self.ty(*ty)?);
+                ops.push(*cleanup as u64);
+                ops.push(catches.len() as u64);
+                for &(_, arg) in catches {
+                    ops.push(0); // catch
+                    self.push_value_and_type(&mut ops, arg)?;
+                }
+                LANDINGPAD
+            }
+            Operand::Resume(Resume { arg, .. }) => {
+                self.push_value_and_type(&mut ops, *arg)?;
+                RESUME
+            }
+            Operand::Ret(Ret { val, .. }) => {
+                if let Some(val) = val {
+                    self.push_value_and_type(&mut ops, *val)?;
+                }
+                RET
+            }
+            Operand::Br(Br { block }) => {
+                ops.push(self.block(*block)?);
+                BR
+            }
+            Operand::CondBr(CondBr { arg, blocks }) => {
+                ops.push(self.block(blocks[0])?);
+                ops.push(self.block(blocks[1])?);
+                self.push_value(&mut ops, *arg)?;
+                BR
+            }
+            Operand::Switch(switch @ Switch { .. }) => {
+                // [condty, cond, default, (case value, block)*]
+                ops.push(self.ty(switch.cond_ty())?);
+                self.push_value(&mut ops, switch.cond())?;
+                ops.push(self.block(switch.default_block())?);
+                for (&case, &block) in switch.cases().iter().zip(switch.blocks()) {
+                    ops.push(self.value(case)?.0);
+                    ops.push(self.block(block)?);
+                }
+                SWITCH
+            }
+            Operand::Unreachable => UNREACHABLE,
+            Operand::Invalid => return Err(Error::Invalid("invalid instruction".to_string())),
+        };
+        Ok((code, ops))
+    }
+
+    fn call_attrs(
+        &mut self,
+        func: &[Attribute],
+        ret: &[ParameterAttribute],
+        params: &[Vec<ParameterAttribute>],
+    ) -> Result<u64, Error> {
+        let params: Vec<_> = params.iter().map(|p| p.as_slice()).collect();
+        self.env
+            .attrs
+            .list(self.env.types, self.env.attr_groups, func, ret, &params)
+    }
+
+    /// Pushes `[fnty, callee, args...]` of a call site.
+    fn push_call(
+        &mut self,
+        ops: &mut Vec<u64>,
+        args: &[ValueId],
+        tys: &[Type],
+    ) -> Result<(), Error> {
+        let func_ty = self.call_type(tys)?;
+        ops.push(self.env.types.id(func_ty));
+        match self.func.data.value_ref(args[0]) {
+            Value::InlineAsm(asm) => {
+                let (id, _) = self
+                    .constants
+                    .add_inline_asm(asm, func_ty, &mut self.env.ctx());
+                ops.push((self.cur as u32).wrapping_sub(id as u32) as u64);
+            }
+            _ => self.push_value_and_type(ops, args[0])?,
+        }
+        let num_params = self.env.types.signature(func_ty).unwrap().1.len();
+        for (i, &arg) in args[1..].iter().enumerate() {
+            if i < num_params {
+                self.push_value(ops, arg)?
+            } else {
+                self.push_value_and_type(ops, arg)?
+            }
+        }
+        Ok(())
+    }
+
+    fn attachment(&self, id: InstructionId, index: u64) -> Result<Vec<u64>, Error> {
+        let inst = self.func.data.inst_ref(id);
+        let mut pairs = inst
+            .metadata
+            .iter()
+            .map(|(kind, meta)| self.env.metadata.attachment(kind, meta))
+            .collect::<Result<Vec<_>, _>>()?;
+        pairs.sort();
+        Ok([index]
+            .into_iter()
+            .chain(pairs.into_iter().flat_map(|(kind, md)| [kind, md]))
+            .collect())
+    }
+
+    /// Returns the value symbol table entries for named arguments, instructions and blocks.
+    fn symbols(&self) -> Vec<(u32, Vec<u64>)> {
+        let entry = |code, id, name: &str| (code, [id].into_iter().chain(chars(name)).collect());
+        let mut symbols = vec![];
+        for (i, param) in self.func.params.iter().enumerate() {
+            if let Name::Name(name) = &param.name {
+                symbols.push(entry(VST_ENTRY, self.first_arg + i as u64, name))
+            }
+        }
+        for block in self.func.layout.block_iter() {
+            for id in self.func.layout.inst_iter(block) {
+                if let (Some(Name::Name(name)), Some((value, _))) =
+                    (&self.func.data.inst_ref(id).dest, self.insts.get(&id))
+                {
+                    symbols.push(entry(VST_ENTRY, *value, name))
+                }
+            }
+            if let Some(Name::Name(name)) = &self.func.data.block_ref(block).name {
+                symbols.push(entry(VST_BBENTRY, self.blocks[&block], name))
+            }
+        }
+        symbols
+    }
+}
+
+/// Encodes an alignment as `log2(align) + 1`, where `0` means no alignment is specified.
+pub fn encode_align(align: u32) -> u64 {
+    match align {
+        0 => 0,
+        align => align.trailing_zeros() as u64 + 1,
+    }
+}
+
+fn overflow_flags(nuw: bool, nsw: bool) -> u64 {
+    nuw as u64 | (nsw as u64) << 1
+}
+
+fn push_fast_math_flags(ops: &mut Vec<u64>, fmf: &FastMathFlags) {
+    let flags = (fmf.nnan as u64) << 1
+        | (fmf.ninf as u64) << 2
+        | (fmf.nsz as u64) << 3
+        | (fmf.arcp as u64) << 4
+        | (fmf.contract as u64) << 5
+        | (fmf.afn as u64) << 6
+        | (fmf.reassoc as u64) << 7;
+    if flags != 0 {
+        ops.push(flags)
+    }
+}
+
+fn cast_opcode(opcode: Opcode) -> Result<u64, Error> {
+    Ok(match opcode {
+        Opcode::Trunc => 0,
+        Opcode::Zext => 1,
+        Opcode::Sext => 2,
+        Opcode::FPToUI => 3,
+        Opcode::FPToSI => 4,
+        Opcode::UIToFP => 5,
+        Opcode::SIToFP => 6,
+        Opcode::FPTrunc => 7,
+        Opcode::FPExt => 8,
+        Opcode::PtrToInt => 9,
+        Opcode::IntToPtr => 10,
+        Opcode::Bitcast => 11,
+        opcode => return Err(mismatch(opcode)),
+    })
+}
+
+fn icmp_predicate(cond: ICmpCond) -> u64 {
+    match cond {
+        ICmpCond::Eq => 32,
+        ICmpCond::Ne => 33,
+        ICmpCond::Ugt => 34,
+        ICmpCond::Uge => 35,
+        ICmpCond::Ult => 36,
+        ICmpCond::Ule => 37,
+        ICmpCond::Sgt => 38,
+        ICmpCond::Sge => 39,
+        ICmpCond::Slt => 40,
+        ICmpCond::Sle => 41,
+    }
+}
+
+fn fcmp_predicate(cond: FCmpCond) -> u64 {
+    match cond {
+        FCmpCond::False => 0,
+        FCmpCond::Oeq => 1,
+        FCmpCond::Ogt => 2,
+        FCmpCond::Oge => 3,
+        FCmpCond::Olt => 4,
+        FCmpCond::Ole => 5,
+        FCmpCond::One => 6,
+        FCmpCond::Ord => 7,
+        FCmpCond::Uno => 8,
+        FCmpCond::Ueq => 9,
+        FCmpCond::Ugt => 10,
+        FCmpCond::Uge => 11,
+        FCmpCond::Ult => 12,
+        FCmpCond::Ule => 13,
+        FCmpCond::Une => 14,
+        FCmpCond::True => 15,
+    }
+}
+
+fn mismatch(opcode: Opcode) -> Error {
+    Error::Invalid(format!("opcode {:?} doesn't match its operand", opcode))
+}
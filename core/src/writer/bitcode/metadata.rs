@@ -0,0 +1,264 @@
+use super::{
+    bitstream::{chars, Writer},
+    constants::{Constants, Context},
+    types::{TypeRef, TypeTable},
+    Error,
+};
+use crate::{
+    ir::module::{metadata::Metadata, name::Name, Module},
+    parser::bitcode::{METADATA_BLOCK_ID, METADATA_KIND_BLOCK_ID},
+};
+use rustc_hash::FxHashMap;
+
+const STRING_OLD: u32 = 1;
+const VALUE: u32 = 2;
+const NODE: u32 = 3;
+const NAME: u32 = 4;
+const DISTINCT_NODE: u32 = 5;
+const KIND: u32 = 6;
+const NAMED_NODE: u32 = 10;
+
+enum Leaf<'m> {
+    String(&'m str),
+    Value(TypeRef, u64),
+}
+
+/// A reference to a metadata, which is either a leaf or a node.
+#[derive(Clone, Copy)]
+enum Ref {
+    Leaf(usize),
+    Node(usize),
+}
+
+/// Metadata of a module and the kinds of metadata attached to instructions.
+///
+/// Strings and values come first, followed by nodes, so that only nodes are forward referenced.
+#[derive(Default)]
+pub struct MetadataTable<'m> {
+    leaves: Vec<Leaf<'m>>,
+    strings: FxHashMap<&'m str, usize>,
+    values: FxHashMap<u64, usize>,
+    /// (operands, is distinct)
+    nodes: Vec<(Vec<Ref>, bool)>,
+    /// `!N` -> node index
+    numbered: FxHashMap<usize, usize>,
+    /// Nodes written in place (e.g. `!{!"a"}` in an operand), keyed by their address.
+    inline: FxHashMap<*const Metadata, usize>,
+    /// (name, node indices)
+    named: Vec<(&'m str, Vec<usize>)>,
+    kinds: Vec<&'m str>,
+    kind_ids: FxHashMap<&'m str, u64>,
+}
+
+impl<'m> MetadataTable<'m> {
+    /// Numbers the metadata of `module`, adding the values they refer to to `constants`.
+    pub fn new(
+        module: &'m Module,
+        constants: &mut Constants,
+        ctx: &mut Context,
+    ) -> Result<Self, Error> {
+        let mut table = Self::default();
+
+        let mut numbers: Vec<_> = module
+            .metas
+            .iter()
+            .filter_map(|(name, meta)| match name {
+                Name::Number(n) => Some((*n, meta)),
+                Name::Name(_) => None,
+            })
+            .collect();
+        numbers.sort_by_key(|(n, _)| *n);
+        for (i, (n, _)) in numbers.iter().enumerate() {
+            table.numbered.insert(*n, i);
+            table.nodes.push((vec![], false));
+        }
+        for (i, (n, meta)) in numbers.into_iter().enumerate() {
+            match meta {
+                Metadata::Node(ops, distinct) => {
+                    let ops = table.operands(ops, constants, ctx)?;
+                    table.nodes[i] = (ops, *distinct)
+                }
+                _ => return Err(Error::Invalid(format!("metadata !{} is not a node", n))),
+            }
+        }
+
+        let named: Vec<_> = module
+            .metas
+            .iter()
+            .filter_map(|(name, meta)| match name {
+                Name::Name(name) => Some((name.as_str(), meta)),
+                Name::Number(_) => None,
+            })
+            .collect();
+        let mut named = named
+            .into_iter()
+            .map(|(name, meta)| match meta {
+                Metadata::Node(ops, _) => Ok((name, ops)),
+                _ => Err(Error::Invalid(format!("metadata !{} is not a node", name))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // Readers number nodes in the order they're reached from named metadata, so this keeps
+        // `!N` as is.
+        named.sort_by_key(|(name, ops)| {
+            let first = ops.iter().find_map(|op| match op {
+                Metadata::Name(Name::Number(n)) => Some(*n),
+                _ => None,
+            });
+            (first, *name)
+        });
+        for (name, ops) in named {
+            let nodes = ops
+                .iter()
+                .map(|op| match op {
+                    Metadata::Name(Name::Number(n)) => table.numbered_node(*n),
+                    _ => Err(Error::Unsupported(format!(
+                        "operand of named metadata !{}",
+                        name
+                    ))),
+                })
+                .collect::<Result<_, _>>()?;
+            table.named.push((name, nodes))
+        }
+
+        Ok(table)
+    }
+
+    /// Registers an attachment of `kind`, whose node may be written in place.
+    pub fn add_attachment(
+        &mut self,
+        kind: &'m str,
+        meta: &'m Metadata,
+        constants: &mut Constants,
+        ctx: &mut Context,
+    ) -> Result<(), Error> {
+        if !self.kind_ids.contains_key(kind) {
+            self.kind_ids.insert(kind, self.kinds.len() as u64);
+            self.kinds.push(kind);
+        }
+        match meta {
+            Metadata::Name(_) | Metadata::Node(..) => {
+                self.operand(meta, constants, ctx).map(|_| ())
+            }
+            _ => Err(Error::Invalid(format!(
+                "!{} is not attached to a node",
+                kind
+            ))),
+        }
+    }
+
+    /// Returns the kind id and the metadata id of an attachment added by `add_attachment`.
+    pub fn attachment(&self, kind: &str, meta: &Metadata) -> Result<(u64, u64), Error> {
+        let node = match meta {
+            Metadata::Name(Name::Number(n)) => self.numbered_node(*n)?,
+            Metadata::Node(..) => self.inline[&(meta as *const _)],
+            _ => {
+                return Err(Error::Invalid(format!(
+                    "!{} is not attached to a node",
+                    kind
+                )))
+            }
+        };
+        Ok((self.kind_ids[kind], self.id(Ref::Node(node))))
+    }
+
+    pub fn write_kinds(&self, w: &mut Writer) {
+        if self.kinds.is_empty() {
+            return;
+        }
+        w.enter_block(METADATA_KIND_BLOCK_ID);
+        for (i, kind) in self.kinds.iter().enumerate() {
+            let ops: Vec<_> = [i as u64].into_iter().chain(chars(kind)).collect();
+            w.record(KIND, &ops);
+        }
+        w.exit_block();
+    }
+
+    pub fn write(&self, w: &mut Writer, types: &TypeTable) {
+        if self.leaves.is_empty() && self.nodes.is_empty() {
+            return;
+        }
+        w.enter_block(METADATA_BLOCK_ID);
+        for leaf in &self.leaves {
+            match leaf {
+                Leaf::String(s) => w.record(STRING_OLD, &chars(s).collect::<Vec<_>>()),
+                Leaf::Value(ty, id) => w.record(VALUE, &[types.id(*ty), *id]),
+            }
+        }
+        for (ops, distinct) in &self.nodes {
+            let ops: Vec<_> = ops.iter().map(|&op| self.id(op) + 1).collect();
+            w.record(if *distinct { DISTINCT_NODE } else { NODE }, &ops);
+        }
+        for (name, nodes) in &self.named {
+            w.record(NAME, &chars(name).collect::<Vec<_>>());
+            let ids: Vec<_> = nodes.iter().map(|&n| self.id(Ref::Node(n))).collect();
+            w.record(NAMED_NODE, &ids);
+        }
+        w.exit_block();
+    }
+
+    fn operands(
+        &mut self,
+        ops: &'m [Metadata],
+        constants: &mut Constants,
+        ctx: &mut Context,
+    ) -> Result<Vec<Ref>, Error> {
+        ops.iter()
+            .map(|op| self.operand(op, constants, ctx))
+            .collect()
+    }
+
+    fn operand(
+        &mut self,
+        meta: &'m Metadata,
+        constants: &mut Constants,
+        ctx: &mut Context,
+    ) -> Result<Ref, Error> {
+        match meta {
+            Metadata::String(s) => {
+                let leaves = &mut self.leaves;
+                let i = *self.strings.entry(s).or_insert_with(|| {
+                    leaves.push(Leaf::String(s));
+                    leaves.len() - 1
+                });
+                Ok(Ref::Leaf(i))
+            }
+            Metadata::Const(c) => {
+                let (id, ty) = constants.add(c, ctx)?;
+                let leaves = &mut self.leaves;
+                let i = *self.values.entry(id).or_insert_with(|| {
+                    leaves.push(Leaf::Value(ty, id));
+                    leaves.len() - 1
+                });
+                Ok(Ref::Leaf(i))
+            }
+            Metadata::Name(Name::Number(n)) => Ok(Ref::Node(self.numbered_node(*n)?)),
+            Metadata::Name(Name::Name(name)) => Err(Error::Invalid(format!(
+                "named metadata !{} used as an operand",
+                name
+            ))),
+            Metadata::Node(ops, distinct) => {
+                if let Some(&i) = self.inline.get(&(meta as *const _)) {
+                    return Ok(Ref::Node(i));
+                }
+                let ops = self.operands(ops, constants, ctx)?;
+                self.nodes.push((ops, *distinct));
+                self.inline.insert(meta, self.nodes.len() - 1);
+                Ok(Ref::Node(self.nodes.len() - 1))
+            }
+        }
+    }
+
+    fn numbered_node(&self, n: usize) -> Result<usize, Error> {
+        self.numbered
+            .get(&n)
+            .copied()
+            .ok_or_else(|| Error::Invalid(format!("undefined metadata !{}", n)))
+    }
+
+    fn id(&self, r: Ref) -> u64 {
+        match r {
+            Ref::Leaf(i) => i as u64,
+            Ref::Node(i) => (self.leaves.len() + i) as u64,
+        }
+    }
+}
@@ -0,0 +1,28 @@
+//! A writer for LLVM bitcode (`.bc`) files.
+//!
+//! The output follows the layout LLVM 14 emits (module version 2 with a string table), so it
+//! can be read by `llvm-dis` and other LLVM tools as well as [`crate::parser::bitcode`].
+
+mod attributes;
+mod bitstream;
+mod constants;
+mod function;
+mod metadata;
+mod module;
+mod types;
+
+use crate::ir::module::Module;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Invalid module: {0}")]
+    Invalid(String),
+    #[error("Unsupported by the bitcode writer: {0}")]
+    Unsupported(String),
+}
+
+/// Serializes `module` into an LLVM bitcode file.
+pub fn write(module: &Module) -> Result<Vec<u8>, Error> {
+    module::write(module)
+}
@@ -0,0 +1,340 @@
+use super::{
+    attributes::AttributeTable,
+    bitstream::{chars, Writer},
+    constants::{Constants, Context, GlobalValues},
+    function::{encode_align, Env, FunctionBlock},
+    metadata::MetadataTable,
+    types::TypeTable,
+    Error,
+};
+use crate::{
+    ir::{
+        function::Function,
+        module::{
+            global_variable::GlobalVariable, linkage::Linkage, name::Name,
+            preemption_specifier::PreemptionSpecifier, unnamed_addr::UnnamedAddr,
+            visibility::Visibility, Module,
+        },
+    },
+    parser::bitcode::{MAGIC, MODULE_BLOCK_ID, STRTAB_BLOCK_ID},
+};
+use std::cmp::Ordering;
+
+const IDENTIFICATION_BLOCK_ID: u32 = 13;
+
+const IDENTIFICATION_STRING: u32 = 1;
+const IDENTIFICATION_EPOCH: u32 = 2;
+
+const VERSION: u32 = 1;
+const TRIPLE: u32 = 2;
+const DATALAYOUT: u32 = 3;
+const SECTIONNAME: u32 = 5;
+const GLOBALVAR: u32 = 7;
+const FUNCTION: u32 = 8;
+const SOURCE_FILENAME: u32 = 16;
+
+const STRTAB_BLOB: u32 = 1;
+
+/// Serializes `module` into a bitcode file.
+pub fn write(module: &Module) -> Result<Vec<u8>, Error> {
+    let mut types = TypeTable::new(&module.types);
+
+    // Global variables and functions come first in the value list.
+    let mut gvs: Vec<_> = module.global_variables.values().collect();
+    gvs.sort_by(|a, b| compare_names(&a.name, &b.name));
+    let funcs: Vec<_> = module.functions.iter().map(|(_, f)| f).collect();
+    let mut globals = GlobalValues::default();
+    for (i, gv) in gvs.iter().enumerate() {
+        let ty = types.ty(gv.ty)?;
+        globals.insert(gv.name.clone(), (i as u64, types.pointer(ty, 0)));
+    }
+    for (i, func) in funcs.iter().enumerate() {
+        let ret = types.ty(func.result_ty)?;
+        let params = func
+            .params
+            .iter()
+            .map(|p| types.ty(p.ty))
+            .collect::<Result<_, _>>()?;
+        let ty = types.function(ret, params, func.is_var_arg);
+        let id = (gvs.len() + i) as u64;
+        globals.insert(function_name(func), (id, types.pointer(ty, 0)));
+    }
+
+    let mut constants = Constants::new(globals.len() as u64);
+    let mut ctx = Context {
+        types: &mut types,
+        globals: &globals,
+        module: None,
+    };
+    let inits = gvs
+        .iter()
+        .map(|gv| match &gv.init {
+            Some(init) => constants.add(init, &mut ctx).map(|(id, _)| id + 1),
+            None => Ok(0),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let personalities = funcs
+        .iter()
+        .map(|func| match &func.personality {
+            Some((_, personality)) => constants.add(personality, &mut ctx).map(|(id, _)| id + 1),
+            None => Ok(0),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut metadata = MetadataTable::new(module, &mut constants, &mut ctx)?;
+    for func in &funcs {
+        for block in func.layout.block_iter() {
+            for id in func.layout.inst_iter(block) {
+                let mut attachments: Vec<_> = func.data.inst_ref(id).metadata.iter().collect();
+                attachments.sort_by_key(|(kind, _)| *kind);
+                for (kind, meta) in attachments {
+                    metadata.add_attachment(kind, meta, &mut constants, &mut ctx)?;
+                }
+            }
+        }
+    }
+
+    let mut attrs = AttributeTable::default();
+    let attr_lists = funcs
+        .iter()
+        .map(|func| {
+            let params: Vec<_> = func.params.iter().map(|p| p.attrs.as_slice()).collect();
+            attrs.list(
+                &mut types,
+                &module.attributes,
+                &func.func_attrs,
+                &func.ret_attrs,
+                &params,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut bodies = vec![];
+    for func in funcs.iter().filter(|func| !func.is_prototype()) {
+        let env = Env {
+            types: &mut types,
+            globals: &globals,
+            constants: &constants,
+            attrs: &mut attrs,
+            attr_groups: &module.attributes,
+            metadata: &metadata,
+        };
+        bodies.push(FunctionBlock::new(func, env)?);
+    }
+
+    let mut sections = vec![];
+    for func in &funcs {
+        if let Some(section) = &func.section {
+            if !sections.contains(section) {
+                sections.push(section.clone())
+            }
+        }
+    }
+
+    let mut w = Writer::new();
+    let mut strtab = vec![];
+    w.raw(MAGIC);
+
+    w.enter_block(IDENTIFICATION_BLOCK_ID);
+    w.record(IDENTIFICATION_STRING, &chars("vicis").collect::<Vec<_>>());
+    w.record(IDENTIFICATION_EPOCH, &[0]);
+    w.exit_block();
+
+    w.enter_block(MODULE_BLOCK_ID);
+    w.record(VERSION, &[2]);
+    attrs.write(&mut w);
+    types.write(&mut w);
+    if !module.target.triple.is_empty() {
+        w.record(TRIPLE, &chars(&module.target.triple).collect::<Vec<_>>());
+    }
+    let datalayout = module.target.datalayout.as_str();
+    if !datalayout.is_empty() {
+        w.record(DATALAYOUT, &chars(datalayout).collect::<Vec<_>>());
+    }
+    for section in &sections {
+        w.record(SECTIONNAME, &chars(section).collect::<Vec<_>>());
+    }
+    for (gv, init) in gvs.iter().zip(inits) {
+        w.record(
+            GLOBALVAR,
+            &global_variable(gv, init, &mut strtab, &mut types)?,
+        );
+    }
+    for ((func, attrs), personality) in funcs.iter().zip(attr_lists).zip(personalities) {
+        let (_, ty) = globals[&function_name(func)];
+        let section = match &func.section {
+            Some(section) => sections.iter().position(|s| s == section).unwrap() as u64 + 1,
+            None => 0,
+        };
+        let (offset, size) = add_string(&mut strtab, &function_name(func));
+        let linkage = match func.linkage {
+            // Functions made by `Function::new` are `common`, which is only valid for variables.
+            Linkage::Common => 0,
+            l => linkage(l),
+        };
+        let visibility = visibility(func.visibility);
+        // [strtab offset, strtab size, type, callingconv, isproto, linkage, paramattrs,
+        //  alignment, section, visibility, gc, unnamed_addr, prologuedata, dllstorageclass,
+        //  comdat, prefixdata, personalityfn, dso_local, addrspace]
+        let ops = [
+            offset,
+            size,
+            types.id(types.pointee(ty).unwrap()),
+            0,
+            func.is_prototype() as u64,
+            linkage,
+            attrs,
+            0,
+            section,
+            visibility,
+            0,
+            unnamed_addr(func.unnamed_addr),
+            0,
+            dll_storage_class(func.linkage),
+            0,
+            0,
+            personality,
+            is_dso_local(
+                func.preemption_specifier == PreemptionSpecifier::DsoLocal,
+                linkage,
+                visibility,
+            ) as u64,
+            0,
+        ];
+        w.record(FUNCTION, &ops);
+    }
+    if !module.source_filename.is_empty() {
+        w.record(
+            SOURCE_FILENAME,
+            &chars(&module.source_filename).collect::<Vec<_>>(),
+        );
+    }
+    constants.write(&mut w, &types);
+    metadata.write_kinds(&mut w);
+    metadata.write(&mut w, &types);
+    for body in &bodies {
+        body.write(&mut w, &types);
+    }
+    w.exit_block();
+
+    w.enter_block(STRTAB_BLOCK_ID);
+    w.blob_record(STRTAB_BLOB, &strtab);
+    w.exit_block();
+
+    Ok(w.finish())
+}
+
+fn global_variable(
+    gv: &GlobalVariable,
+    init: u64,
+    strtab: &mut Vec<u8>,
+    types: &mut TypeTable,
+) -> Result<Vec<u64>, Error> {
+    let (offset, size) = add_string(strtab, &gv.name);
+    let ty = types.ty(gv.ty)?;
+    let linkage = linkage(gv.linkage.unwrap_or(Linkage::External));
+    let visibility = visibility(gv.visibility.unwrap_or(Visibility::Default));
+    // [strtab offset, strtab size, type, flags (constant, explicit type), initid, linkage,
+    //  alignment, section, visibility, threadlocal, unnamed_addr, externally_initialized,
+    //  dllstorageclass, comdat, attributes, dso_local]
+    Ok(vec![
+        offset,
+        size,
+        types.id(ty),
+        gv.is_constant as u64 | 2,
+        init,
+        linkage,
+        encode_align(gv.align),
+        0,
+        visibility,
+        0,
+        unnamed_addr(gv.unnamed_addr),
+        0,
+        gv.linkage.map_or(0, dll_storage_class),
+        0,
+        0,
+        is_dso_local(
+            gv.preemption_specifier == Some(PreemptionSpecifier::DsoLocal),
+            linkage,
+            visibility,
+        ) as u64,
+    ])
+}
+
+/// Appends the name of a global value to the string table. Numbered values have no name.
+fn add_string(strtab: &mut Vec<u8>, name: &Name) -> (u64, u64) {
+    match name {
+        Name::Name(name) => {
+            let offset = strtab.len() as u64;
+            strtab.extend_from_slice(name.as_bytes());
+            (offset, name.len() as u64)
+        }
+        Name::Number(_) => (0, 0),
+    }
+}
+
+/// Returns the name of `func` as it's referred to by [`ConstantValue::GlobalRef`](crate::ir::value::ConstantValue::GlobalRef).
+fn function_name(func: &Function) -> Name {
+    match func.name.parse() {
+        Ok(n) => Name::Number(n),
+        Err(_) => Name::Name(func.name.clone()),
+    }
+}
+
+/// Orders numbered values first, so that they're renumbered in the same order when read back.
+fn compare_names(a: &Name, b: &Name) -> Ordering {
+    match (a, b) {
+        (Name::Number(a), Name::Number(b)) => a.cmp(b),
+        (Name::Number(_), Name::Name(_)) => Ordering::Less,
+        (Name::Name(_), Name::Number(_)) => Ordering::Greater,
+        (Name::Name(a), Name::Name(b)) => a.cmp(b),
+    }
+}
+
+fn linkage(linkage: Linkage) -> u64 {
+    match linkage {
+        Linkage::External | Linkage::DLLImport | Linkage::DLLExport => 0,
+        Linkage::Appending => 2,
+        Linkage::Internal => 3,
+        Linkage::ExternalWeak => 7,
+        Linkage::Common => 8,
+        Linkage::Private | Linkage::Ghost | Linkage::LinkerPrivate | Linkage::LinkerPrivateWeak => {
+            9
+        }
+        Linkage::AvailableExternally => 12,
+        Linkage::WeakAny => 16,
+        Linkage::WeakODR => 17,
+        Linkage::LinkOnceAny => 18,
+        Linkage::LinkOnceODR | Linkage::LinkOnceODRAutoHide => 19,
+    }
+}
+
+fn dll_storage_class(linkage: Linkage) -> u64 {
+    match linkage {
+        Linkage::DLLImport => 1,
+        Linkage::DLLExport => 2,
+        _ => 0,
+    }
+}
+
+fn visibility(visibility: Visibility) -> u64 {
+    match visibility {
+        Visibility::Default => 0,
+        Visibility::Hidden => 1,
+        Visibility::Protected => 2,
+    }
+}
+
+fn unnamed_addr(unnamed_addr: Option<UnnamedAddr>) -> u64 {
+    match unnamed_addr {
+        None => 0,
+        Some(UnnamedAddr::Global) => 1,
+        Some(UnnamedAddr::Local) => 2,
+    }
+}
+
+/// Returns `true` if the value is `dso_local`, which local linkages and non-default visibilities
+/// imply.
+fn is_dso_local(dso_local: bool, linkage: u64, visibility: u64) -> bool {
+    dso_local || matches!(linkage, 3 | 9) || (visibility != 0 && linkage != 7)
+}
@@ -0,0 +1,298 @@
+use super::{
+    bitstream::{chars, Writer},
+    Error,
+};
+use crate::{
+    ir::{
+        module::name::Name,
+        types::{self, CompoundType, Type, Types},
+    },
+    parser::bitcode::TYPE_BLOCK_ID_NEW,
+};
+use rustc_hash::FxHashMap;
+
+const NUMENTRY: u32 = 1;
+const VOID_: u32 = 2;
+const FLOAT_: u32 = 3;
+const DOUBLE_: u32 = 4;
+const INTEGER: u32 = 7;
+const POINTER: u32 = 8;
+const HALF_: u32 = 10;
+const ARRAY: u32 = 11;
+const X86_FP80_: u32 = 13;
+const FP128_: u32 = 14;
+const METADATA: u32 = 16;
+const STRUCT_ANON: u32 = 18;
+const STRUCT_NAME: u32 = 19;
+const STRUCT_NAMED: u32 = 20;
+const FUNCTION: u32 = 21;
+const OPAQUE_POINTER: u32 = 25;
+
+/// A type in a [`TypeTable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TypeRef(usize);
+
+/// A type as written to the bitcode. Unlike [`Types`], structurally equal types are always the same
+/// node, and types the IR doesn't spell out (e.g. the type of a function) can be added.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Node {
+    Void,
+    Half,
+    Float,
+    Double,
+    X86Fp80,
+    Fp128,
+    Metadata,
+    Int(u32),
+    Pointer(TypeRef, u32),
+    OpaquePointer(u32),
+    Array(u32, TypeRef),
+    Function(bool, TypeRef, Vec<TypeRef>),
+    Struct(bool, Vec<TypeRef>),
+    /// A named struct, which is distinct from any other struct.
+    Named(Type),
+}
+
+struct NamedStruct {
+    name: Option<String>,
+    is_packed: bool,
+    elems: Vec<TypeRef>,
+}
+
+pub struct TypeTable<'a> {
+    types: &'a Types,
+    nodes: Vec<Node>,
+    interned: FxHashMap<Node, TypeRef>,
+    cache: FxHashMap<Type, TypeRef>,
+    named: FxHashMap<TypeRef, NamedStruct>,
+    /// Type ids, assigned when a type is complete so that elements come before their aggregate.
+    /// Named structs get an id after their elements, though pointers to them may come earlier.
+    ids: Vec<Option<u64>>,
+    order: Vec<TypeRef>,
+}
+
+impl<'a> TypeTable<'a> {
+    pub fn new(types: &'a Types) -> Self {
+        Self {
+            types,
+            nodes: vec![],
+            interned: FxHashMap::default(),
+            cache: FxHashMap::default(),
+            named: FxHashMap::default(),
+            ids: vec![],
+            order: vec![],
+        }
+    }
+
+    /// Returns the types of the IR being written.
+    pub fn ir(&self) -> &'a Types {
+        self.types
+    }
+
+    /// Adds `ty` to the table.
+    pub fn ty(&mut self, ty: Type) -> Result<TypeRef, Error> {
+        if let Some(&t) = self.cache.get(&ty) {
+            return Ok(t);
+        }
+        let t = match ty {
+            types::VOID => self.node(Node::Void),
+            types::I1 => self.node(Node::Int(1)),
+            types::I8 => self.node(Node::Int(8)),
+            types::I16 => self.node(Node::Int(16)),
+            types::I32 => self.node(Node::Int(32)),
+            types::I64 => self.node(Node::Int(64)),
+            types::HALF => self.node(Node::Half),
+            types::FLOAT => self.node(Node::Float),
+            types::DOUBLE => self.node(Node::Double),
+            types::X86_FP80 => self.node(Node::X86Fp80),
+            types::FP128 => self.node(Node::Fp128),
+            _ if ty.is_primitive() => {
+                return Err(Error::Unsupported(format!("type {:?}", ty)));
+            }
+            _ => {
+                let compound = self
+                    .types
+                    .get(ty)
+                    .ok_or_else(|| Error::Invalid(format!("unknown type {:?}", ty)))?
+                    .clone();
+                match compound {
+                    CompoundType::Pointer(p) => {
+                        let inner = self.ty(p.inner)?;
+                        self.pointer(inner, p.addr_space)
+                    }
+                    CompoundType::OpaquePointer(addr_space) => {
+                        self.node(Node::OpaquePointer(addr_space))
+                    }
+                    CompoundType::Array(a) => {
+                        let inner = self.ty(a.inner)?;
+                        self.node(Node::Array(a.num_elements, inner))
+                    }
+                    CompoundType::Function(f) => {
+                        let ret = self.ty(f.ret)?;
+                        let params = self.tys(&f.params)?;
+                        self.function(ret, params, f.is_var_arg)
+                    }
+                    CompoundType::Struct(s) if s.name.is_none() => {
+                        let elems = self.tys(&s.elems)?;
+                        self.node(Node::Struct(s.is_packed, elems))
+                    }
+                    CompoundType::Struct(s) => {
+                        // Register the struct first, so that it can refer to itself.
+                        let t = self.intern(Node::Named(ty));
+                        self.cache.insert(ty, t);
+                        let elems = self.tys(&s.elems)?;
+                        let name = match s.name {
+                            Some(Name::Name(name)) => Some(name),
+                            _ => None,
+                        };
+                        self.named.insert(
+                            t,
+                            NamedStruct {
+                                name,
+                                is_packed: s.is_packed,
+                                elems,
+                            },
+                        );
+                        self.complete(t);
+                        t
+                    }
+                    CompoundType::Alias(types::VOID) => {
+                        return Err(Error::Invalid("undefined named type".to_string()));
+                    }
+                    CompoundType::Alias(inner) => self.ty(inner)?,
+                    CompoundType::Metadata => self.node(Node::Metadata),
+                }
+            }
+        };
+        self.cache.insert(ty, t);
+        Ok(t)
+    }
+
+    pub fn tys(&mut self, tys: &[Type]) -> Result<Vec<TypeRef>, Error> {
+        tys.iter().map(|&ty| self.ty(ty)).collect()
+    }
+
+    pub fn pointer(&mut self, inner: TypeRef, addr_space: u32) -> TypeRef {
+        self.node(Node::Pointer(inner, addr_space))
+    }
+
+    pub fn function(&mut self, ret: TypeRef, params: Vec<TypeRef>, is_var_arg: bool) -> TypeRef {
+        self.node(Node::Function(is_var_arg, ret, params))
+    }
+
+    /// Returns the pointee of `t` if it's a typed pointer.
+    pub fn pointee(&self, t: TypeRef) -> Option<TypeRef> {
+        match self.nodes[t.0] {
+            Node::Pointer(inner, _) => Some(inner),
+            _ => None,
+        }
+    }
+
+    /// Returns the result and parameter types of `t` if it's a function type.
+    pub fn signature(&self, t: TypeRef) -> Option<(TypeRef, &[TypeRef])> {
+        match &self.nodes[t.0] {
+            Node::Function(_, ret, params) => Some((*ret, params)),
+            _ => None,
+        }
+    }
+
+    pub fn is_void(&self, t: TypeRef) -> bool {
+        self.nodes[t.0] == Node::Void
+    }
+
+    /// Returns the type of a `getelementptr` on `ptr_ty` with constant `indices`.
+    pub fn gep(
+        &mut self,
+        ptr_ty: Type,
+        indices: impl Iterator<Item = usize>,
+    ) -> Result<TypeRef, Error> {
+        let types = self.types;
+        let addr_space = match types.get(ptr_ty).as_deref() {
+            Some(CompoundType::OpaquePointer(_)) => return self.ty(ptr_ty),
+            Some(CompoundType::Pointer(p)) => p.addr_space,
+            _ => return Err(Error::Invalid("getelementptr on a non-pointer".to_string())),
+        };
+        let elem = types
+            .base()
+            .element_at_(ptr_ty, indices)
+            .ok_or_else(|| Error::Invalid("invalid getelementptr indices".to_string()))?;
+        let elem = self.ty(elem)?;
+        Ok(self.pointer(elem, addr_space))
+    }
+
+    /// Returns the type id of `t`.
+    pub fn id(&self, t: TypeRef) -> u64 {
+        self.ids[t.0].expect("incomplete type")
+    }
+
+    pub fn write(&self, w: &mut Writer) {
+        w.enter_block(TYPE_BLOCK_ID_NEW);
+        w.record(NUMENTRY, &[self.order.len() as u64]);
+        for &t in &self.order {
+            match &self.nodes[t.0] {
+                Node::Void => w.record(VOID_, &[]),
+                Node::Half => w.record(HALF_, &[]),
+                Node::Float => w.record(FLOAT_, &[]),
+                Node::Double => w.record(DOUBLE_, &[]),
+                Node::X86Fp80 => w.record(X86_FP80_, &[]),
+                Node::Fp128 => w.record(FP128_, &[]),
+                Node::Metadata => w.record(METADATA, &[]),
+                Node::Int(width) => w.record(INTEGER, &[*width as u64]),
+                Node::Pointer(inner, addr_space) => {
+                    w.record(POINTER, &[self.id(*inner), *addr_space as u64])
+                }
+                Node::OpaquePointer(addr_space) => w.record(OPAQUE_POINTER, &[*addr_space as u64]),
+                Node::Array(n, inner) => w.record(ARRAY, &[*n as u64, self.id(*inner)]),
+                Node::Function(is_var_arg, ret, params) => {
+                    let ops: Vec<_> = [*is_var_arg as u64, self.id(*ret)]
+                        .into_iter()
+                        .chain(params.iter().map(|&p| self.id(p)))
+                        .collect();
+                    w.record(FUNCTION, &ops)
+                }
+                Node::Struct(is_packed, elems) => {
+                    w.record(STRUCT_ANON, &self.struct_ops(*is_packed, elems))
+                }
+                Node::Named(_) => {
+                    let s = &self.named[&t];
+                    if let Some(name) = &s.name {
+                        w.record(STRUCT_NAME, &chars(name).collect::<Vec<_>>());
+                    }
+                    w.record(STRUCT_NAMED, &self.struct_ops(s.is_packed, &s.elems))
+                }
+            }
+        }
+        w.exit_block();
+    }
+
+    fn struct_ops(&self, is_packed: bool, elems: &[TypeRef]) -> Vec<u64> {
+        [is_packed as u64]
+            .into_iter()
+            .chain(elems.iter().map(|&e| self.id(e)))
+            .collect()
+    }
+
+    fn intern(&mut self, node: Node) -> TypeRef {
+        if let Some(&t) = self.interned.get(&node) {
+            return t;
+        }
+        let t = TypeRef(self.nodes.len());
+        self.nodes.push(node.clone());
+        self.interned.insert(node, t);
+        self.ids.push(None);
+        t
+    }
+
+    fn complete(&mut self, t: TypeRef) {
+        if self.ids[t.0].is_none() {
+            self.ids[t.0] = Some(self.order.len() as u64);
+            self.order.push(t);
+        }
+    }
+
+    fn node(&mut self, node: Node) -> TypeRef {
+        let t = self.intern(node);
+        self.complete(t);
+        t
+    }
+}
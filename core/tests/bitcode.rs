@@ -0,0 +1,161 @@
+use std::fs;
+use vicis_core::{ir::module::Module, writer::bitcode};
+
+// The textual parser skips these attachments, so they're dropped before comparison.
+const IGNORED_METADATA: &[&str] = &["nonnull", "range", "srcloc"];
+
+fn sorted_lines(s: String) -> Vec<String> {
+    let mut lines: Vec<_> = s.lines().map(|l| l.to_string()).collect();
+    lines.sort();
+    lines
+}
+
+/// Drops what `llvm-as` doesn't preserve: a missing `source_filename` is replaced with the path
+/// of the input, and attribute groups nobody refers to are discarded.
+fn normalize(lines: Vec<String>) -> Vec<String> {
+    let referred = |group: &str| {
+        lines
+            .iter()
+            .any(|l| !l.starts_with("attributes") && l.contains(&format!("{} ", group)))
+    };
+    lines
+        .iter()
+        .filter(|l| !l.starts_with("source_filename"))
+        .filter(|l| match l.strip_prefix("attributes ") {
+            Some(l) => referred(l.split(' ').next().unwrap()),
+            None => true,
+        })
+        .cloned()
+        .collect()
+}
+
+fn parse_example(name: &str) -> Module {
+    let source = fs::read_to_string(format!("./examples/{}.ll", name)).unwrap();
+    Module::try_from(source.as_str()).unwrap()
+}
+
+fn assert_same_module(actual: &Module, expected: &Module) {
+    let funcs = |m: &Module| -> Vec<String> {
+        m.functions()
+            .iter()
+            .map(|(_, f)| format!("{:?}", f))
+            .collect()
+    };
+    assert_eq!(funcs(actual), funcs(expected));
+    if !expected.source_filename().is_empty() {
+        assert_eq!(actual.source_filename(), expected.source_filename());
+    }
+    assert_eq!(
+        normalize(sorted_lines(format!("{:?}", actual))),
+        normalize(sorted_lines(format!("{:?}", expected)))
+    );
+}
+
+/// Checks that `examples/bitcode/$name.bc`, which is `examples/$name.ll` assembled by `llvm-as`,
+/// is read into the same module as the textual one.
+fn round_trip(name: &str) {
+    let expected = parse_example(name);
+    let bytes = fs::read(format!("./examples/bitcode/{}.bc", name)).unwrap();
+    let mut actual = Module::try_from(bytes.as_slice()).unwrap();
+
+    for (_, func) in actual.functions_mut().iter_mut() {
+        for (_, inst) in func.data.instructions.iter_mut() {
+            inst.metadata
+                .retain(|kind, _| !IGNORED_METADATA.contains(&kind.as_str()));
+        }
+    }
+
+    assert_same_module(&actual, &expected);
+}
+
+/// Checks that `examples/$name.ll` written as bitcode is read back into the same module.
+fn write_round_trip(name: &str) {
+    let expected = parse_example(name);
+    let bytes = bitcode::write(&expected).unwrap();
+    let actual = Module::try_from(bytes.as_slice()).unwrap();
+    assert_same_module(&actual, &expected);
+}
+
+/// Checks that corrupt copies of `examples/bitcode/$name.bc` are rejected without panicking.
+fn corrupt(name: &str) {
+    let bytes = fs::read(format!("./examples/bitcode/{}.bc", name)).unwrap();
+
+    // The last word only holds the padding of the last block.
+    for len in 0..bytes.len() - 3 {
+        assert!(
+            Module::try_from(&bytes[..len]).is_err(),
+            "{} truncated to {} bytes",
+            name,
+            len
+        );
+    }
+
+    // A flipped bit may still give a valid module, e.g. in a string, but must never panic.
+    let mut bytes = bytes;
+    for bit in 0..bytes.len() * 8 {
+        bytes[bit / 8] ^= 1 << (bit % 8);
+        let _ = Module::try_from(bytes.as_slice());
+        bytes[bit / 8] ^= 1 << (bit % 8);
+    }
+}
+
+#[test]
+fn corrupt_bitcode() {
+    for name in ["addsubmul", "loop2"] {
+        corrupt(name)
+    }
+}
+
+macro_rules! generate_test {
+    ($fname:ident, $write_fname:ident, $name:literal) => {
+        #[test]
+        fn $fname() {
+            round_trip($name)
+        }
+
+        #[test]
+        fn $write_fname() {
+            write_round_trip($name)
+        }
+    };
+}
+
+generate_test!(bitcode_addsubmul, write_bitcode_addsubmul, "addsubmul");
+generate_test!(bitcode_ary, write_bitcode_ary, "ary");
+generate_test!(bitcode_br, write_bitcode_br, "br");
+generate_test!(bitcode_call, write_bitcode_call, "call");
+generate_test!(bitcode_cast, write_bitcode_cast, "cast");
+generate_test!(bitcode_cgep, write_bitcode_cgep, "cgep");
+generate_test!(bitcode_cgep2, write_bitcode_cgep2, "cgep2");
+generate_test!(bitcode_dce, write_bitcode_dce, "dce");
+generate_test!(bitcode_float, write_bitcode_float, "float");
+generate_test!(bitcode_gblvar, write_bitcode_gblvar, "gblvar");
+generate_test!(bitcode_hello_cc, write_bitcode_hello_cc, "hello.cc");
+generate_test!(bitcode_icmp, write_bitcode_icmp, "icmp");
+generate_test!(bitcode_intops, write_bitcode_intops, "intops");
+generate_test!(bitcode_load, write_bitcode_load, "load");
+generate_test!(bitcode_loop, write_bitcode_loop, "loop");
+generate_test!(bitcode_loop2, write_bitcode_loop2, "loop2");
+generate_test!(bitcode_manyargs, write_bitcode_manyargs, "manyargs");
+generate_test!(bitcode_metadata, write_bitcode_metadata, "metadata");
+generate_test!(bitcode_node, write_bitcode_node, "node");
+generate_test!(bitcode_phi, write_bitcode_phi, "phi");
+generate_test!(bitcode_phi_loop, write_bitcode_phi_loop, "phi_loop");
+generate_test!(bitcode_printf, write_bitcode_printf, "printf");
+generate_test!(bitcode_puts, write_bitcode_puts, "puts");
+generate_test!(bitcode_ret42, write_bitcode_ret42, "ret42");
+generate_test!(
+    bitcode_rustc_fn_call,
+    write_bitcode_rustc_fn_call,
+    "rustc_fn_call"
+);
+generate_test!(
+    bitcode_rustc_hello,
+    write_bitcode_rustc_hello,
+    "rustc_hello"
+);
+generate_test!(
+    bitcode_rustc_minimum,
+    write_bitcode_rustc_minimum,
+    "rustc_minimum"
+);
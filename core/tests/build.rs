@@ -1,4 +1,15 @@
-use vicis_core::ir::{function::builder::Builder, module::Module, types::I32};
+use vicis_core::{
+    ir::{
+        function::{
+            builder::Builder,
+            instruction::{ICmpCond, IntBinary, Operand},
+            Parameter,
+        },
+        module::Module,
+        types::{ArrayType, I32, I64},
+    },
+    pass::analysis::verifier::verify_module,
+};
 
 #[test]
 fn build() {
@@ -16,3 +27,120 @@ fn build() {
 
     insta::assert_debug_snapshot!(module);
 }
+
+#[test]
+fn build_loop() {
+    let mut module = Module::default();
+
+    let func_id = module.create_function("sum", I32, vec![Parameter::new(I32)], false);
+    let func = &mut module.functions_mut()[func_id];
+
+    let mut builder = Builder::new(func);
+    let entry = builder.create_block();
+    let header = builder.create_block();
+    let body = builder.create_block();
+    let exit = builder.create_block();
+    let n = builder.param(0);
+    let zero = builder.value(0i32);
+    let one = builder.value(1i32);
+
+    builder.switch_to_block(entry);
+    let sum_ptr = builder.inst().alloca(I32);
+    builder.inst().store(zero, sum_ptr);
+    builder.inst().br(header);
+
+    builder.switch_to_block(header);
+    let i = builder.inst().phi(I32, &[(zero, entry)]);
+    let cond = builder.inst().icmp(ICmpCond::Slt, i, n);
+    builder.inst().cond_br(cond, body, exit);
+
+    builder.switch_to_block(body);
+    let sum = builder.inst().load(I32, sum_ptr);
+    let sum = builder.inst().nsw().add(sum, i);
+    builder.inst().store(sum, sum_ptr);
+    let next = builder.inst().nsw().add(i, one);
+    builder.inst().add_incoming(i, next, body);
+    builder.inst().br(header);
+
+    builder.switch_to_block(exit);
+    let sum = builder.inst().load(I32, sum_ptr);
+    builder.inst().ret(sum);
+
+    let func = &module.functions()[func_id];
+    assert_eq!(func.data.block_ref(header).preds().len(), 2);
+    assert!(verify_module(&module).is_empty());
+    insta::assert_debug_snapshot!(module);
+}
+
+#[test]
+fn build_switch() {
+    let mut module = Module::default();
+    let arr_ty = module.types.base_mut().array(ArrayType::new(I32, 4));
+
+    let func_id = module.create_function("select", I64, vec![Parameter::new(I32)], false);
+    let func = &mut module.functions_mut()[func_id];
+
+    let mut builder = Builder::new(func);
+    let entry = builder.create_block();
+    let case = builder.create_block();
+    let default = builder.create_block();
+    let x = builder.param(0);
+    let zero = builder.value(0i64);
+    let one = builder.value(1i32);
+
+    builder.switch_to_block(entry);
+    let arr = builder.inst().alloca(arr_ty);
+    builder.inst().switch(x, default, &[(one, case)]);
+
+    builder.switch_to_block(case);
+    let idx = builder.inst().sext(x, I64);
+    let elem = builder.inst().inbounds().gep(arr_ty, arr, &[zero, idx]);
+    let val = builder.inst().load(I32, elem);
+    let val = builder.inst().sext(val, I64);
+    builder.inst().ret(val);
+
+    builder.switch_to_block(default);
+    builder.inst().unreachable();
+
+    let func = &module.functions()[func_id];
+    assert_eq!(func.data.block_ref(entry).succs().len(), 2);
+    assert!(verify_module(&module).is_empty());
+    insta::assert_debug_snapshot!(module);
+}
+
+#[test]
+fn build_flags() {
+    let mut module = Module::default();
+
+    let func_id = module.create_function("f", I32, vec![Parameter::new(I32)], false);
+    let func = &mut module.functions_mut()[func_id];
+
+    let mut builder = Builder::new(func);
+    let entry = builder.create_block();
+    let x = builder.param(0);
+    let two = builder.value(2i32);
+
+    builder.switch_to_block(entry);
+    let mut inst = builder.inst().nsw();
+    let a = inst.add(x, two);
+    let b = inst.add(a, two);
+    let c = builder.inst().nsw().nuw().exact().sdiv(b, two);
+    let d = builder.inst().exact().mul(c, two);
+    builder.inst().ret(d);
+
+    let func = &module.functions()[func_id];
+    let flags = |v| {
+        let id = *func.data.value_ref(v).as_inst().unwrap();
+        match func.data.inst_ref(id).operand {
+            Operand::IntBinary(IntBinary {
+                nsw, nuw, exact, ..
+            }) => (nsw, nuw, exact),
+            _ => unreachable!(),
+        }
+    };
+    assert_eq!(flags(a), (true, false, false));
+    assert_eq!(flags(b), (false, false, false));
+    assert_eq!(flags(c), (false, false, true));
+    assert_eq!(flags(d), (false, false, false));
+    assert!(verify_module(&module).is_empty());
+}
@@ -0,0 +1,114 @@
+use std::{any::Any, cell::RefCell, rc::Rc};
+use vicis_core::{
+    ir::module::Module,
+    pass::{
+        analysis::call_graph::{CallGraph, CallGraphPass},
+        transform::mem2reg::Mem2RegPass,
+        AnalysisPass, AnalysisResults, PassManager, TransformPass,
+    },
+};
+
+const SOURCE: &str = r#"
+declare i32 @ext(i32)
+
+define i32 @f(i32 %x) {
+entry:
+  %y = call i32 @ext(i32 %x)
+  ret i32 %y
+}
+
+define i32 @g(i32 %x) {
+entry:
+  %c = icmp sgt i32 %x, 0
+  br i1 %c, label %rec, label %exit
+rec:
+  %x1 = sub i32 %x, 1
+  %r = call i32 @g(i32 %x1)
+  ret i32 %r
+exit:
+  ret i32 0
+}
+
+define i32 @unused() {
+entry:
+  ret i32 0
+}
+
+define i32 @main() {
+entry:
+  %a = call i32 @f(i32 1)
+  %b = call i32 @g(i32 %a)
+  %c = call i32 @f(i32 %b)
+  ret i32 %c
+}
+"#;
+
+#[test]
+fn call_graph() {
+    let module = Module::try_from(SOURCE).unwrap();
+    let id = |name| module.find_function_by_name(name).unwrap();
+
+    let mut pm = PassManager::new();
+    pm.add_analysis(CallGraphPass);
+    pm.run_analyses_on(&module);
+    let graph = pm.get_result::<CallGraph>().unwrap();
+
+    assert_eq!(graph.callees(id("main")), &[id("f"), id("g")]);
+    assert_eq!(graph.callees(id("f")), &[id("ext")]);
+    assert_eq!(graph.callers(id("f")), &[id("main")]);
+    assert!(graph.callers(id("unused")).is_empty());
+    assert!(graph.callers(id("main")).is_empty());
+    assert!(graph.is_recursive(id("g")));
+    assert!(!graph.is_recursive(id("main")));
+}
+
+/// Collects the functions not reachable from `main`, as a global DCE would.
+struct UnreachableFunctionsPass(Rc<RefCell<Vec<String>>>);
+
+impl TransformPass<Module> for UnreachableFunctionsPass {
+    fn dependencies(&self) -> Vec<Box<dyn AnalysisPass<Module>>> {
+        vec![Box::new(CallGraphPass)]
+    }
+
+    fn run_on_with_results(
+        &self,
+        module: &mut Module,
+        results: &AnalysisResults,
+        _result: &mut Box<dyn Any>,
+    ) -> bool {
+        let graph = results.get::<CallGraph>().unwrap();
+        let main = module.find_function_by_name("main").unwrap();
+        let mut reachable = vec![main];
+        let mut i = 0;
+        while i < reachable.len() {
+            for &callee in graph.callees(reachable[i]) {
+                if !reachable.contains(&callee) {
+                    reachable.push(callee)
+                }
+            }
+            i += 1;
+        }
+        for (id, func) in module.functions() {
+            if !reachable.contains(&id) {
+                self.0.borrow_mut().push(func.name().clone())
+            }
+        }
+        false
+    }
+}
+
+#[test]
+fn module_pass_with_dependency() {
+    let mut module = Module::try_from(SOURCE).unwrap();
+    let unreachable = Rc::new(RefCell::new(vec![]));
+
+    let mut fpm = PassManager::new();
+    fpm.add_transform(Mem2RegPass);
+    let mut pm = PassManager::<Module>::new();
+    pm.add_function_passes(fpm);
+    pm.add_transform(UnreachableFunctionsPass(unreachable.clone()));
+    pm.run_on(&mut module);
+
+    assert_eq!(*unreachable.borrow(), vec!["unused".to_string()]);
+    assert!(pm.results().is_valid(CallGraphPass.name()));
+}
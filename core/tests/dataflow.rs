@@ -0,0 +1,185 @@
+use rustc_hash::FxHashSet;
+use vicis_core::{
+    ir::{
+        function::{
+            basic_block::{BasicBlock, BasicBlockId},
+            instruction::{InstructionId, Opcode},
+            Function,
+        },
+        module::{name::Name, Module},
+        value::ValueId,
+    },
+    pass::analysis::{
+        dataflow::{reverse_post_order, Analysis, Dataflow, Direction, IntersectionSet, UnionSet},
+        dom_tree::DominatorTree,
+    },
+};
+
+const LOOP: &str = r#"
+define i32 @f(i32 %n) {
+entry:
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %entry ], [ %next, %body ]
+  %c = icmp slt i32 %i, %n
+  br i1 %c, label %body, label %exit
+
+body:
+  %next = add i32 %i, 1
+  br label %loop
+
+exit:
+  ret i32 %i
+}
+"#;
+
+/// Live instructions.
+struct Liveness<'a>(&'a Function);
+
+impl Analysis<BasicBlock> for Liveness<'_> {
+    type Fact = UnionSet<InstructionId>;
+
+    const DIRECTION: Direction = Direction::Backward;
+
+    fn boundary(&self) -> Self::Fact {
+        UnionSet::default()
+    }
+
+    fn init(&self) -> Self::Fact {
+        UnionSet::default()
+    }
+
+    fn transfer(&self, block: BasicBlockId, live_out: &Self::Fact) -> Self::Fact {
+        let func = self.0;
+        let mut live = live_out.clone();
+        let insts: Vec<_> = func.layout.inst_iter(block).collect();
+        for &id in insts.iter().rev() {
+            live.0.remove(&id);
+            let inst = func.data.inst_ref(id);
+            // Operands of phi nodes are live out of the predecessors.
+            if inst.opcode != Opcode::Phi {
+                live.0.extend(used_insts(func, inst.operand.args()));
+            }
+        }
+        live
+    }
+
+    fn transfer_edge(&self, succ: BasicBlockId, pred: BasicBlockId, fact: &mut Self::Fact) {
+        let func = self.0;
+        for id in func.layout.inst_iter(succ) {
+            let inst = func.data.inst_ref(id);
+            if inst.opcode != Opcode::Phi {
+                continue;
+            }
+            let blocks = inst.operand.blocks();
+            let args = inst.operand.args();
+            for (arg, block) in args.iter().zip(blocks) {
+                if *block == pred {
+                    fact.0.extend(used_insts(func, &[*arg]));
+                }
+            }
+        }
+    }
+}
+
+/// Dominators of each block, which is a "must" forward analysis.
+struct Dominators;
+
+impl Analysis<BasicBlock> for Dominators {
+    type Fact = IntersectionSet<BasicBlockId>;
+
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn boundary(&self) -> Self::Fact {
+        IntersectionSet::Set(FxHashSet::default())
+    }
+
+    fn init(&self) -> Self::Fact {
+        IntersectionSet::Full
+    }
+
+    fn transfer(&self, block: BasicBlockId, input: &Self::Fact) -> Self::Fact {
+        match input {
+            IntersectionSet::Full => IntersectionSet::Full,
+            IntersectionSet::Set(set) => {
+                let mut set = set.clone();
+                set.insert(block);
+                IntersectionSet::Set(set)
+            }
+        }
+    }
+}
+
+fn used_insts<'a>(
+    func: &'a Function,
+    args: &'a [ValueId],
+) -> impl Iterator<Item = InstructionId> + 'a {
+    args.iter()
+        .filter_map(|&arg| func.data.value_ref(arg).as_inst().copied())
+}
+
+fn inst(func: &Function, name: &str) -> InstructionId {
+    func.data
+        .instructions
+        .iter()
+        .find(|(_, inst)| inst.dest == Some(Name::Name(name.to_string())))
+        .unwrap()
+        .0
+}
+
+fn block(func: &Function, name: &str) -> BasicBlockId {
+    func.layout
+        .block_iter()
+        .find(|&b| func.data.basic_blocks[b].name == Some(Name::Name(name.to_string())))
+        .unwrap()
+}
+
+fn set(ids: &[InstructionId]) -> UnionSet<InstructionId> {
+    UnionSet(ids.iter().copied().collect())
+}
+
+#[test]
+fn liveness() {
+    let module = Module::try_from(LOOP).unwrap();
+    let func = &module.functions()[module.find_function_by_name("f").unwrap()];
+    let live = Dataflow::new(func, &Liveness(func));
+    let (i, next) = (inst(func, "i"), inst(func, "next"));
+
+    assert_eq!(live.exit_of(block(func, "entry")), Some(&set(&[])));
+    assert_eq!(live.entry_of(block(func, "loop")), Some(&set(&[])));
+    assert_eq!(live.exit_of(block(func, "loop")), Some(&set(&[i])));
+    assert_eq!(live.entry_of(block(func, "body")), Some(&set(&[i])));
+    assert_eq!(live.exit_of(block(func, "body")), Some(&set(&[next])));
+    assert_eq!(live.entry_of(block(func, "exit")), Some(&set(&[i])));
+    assert_eq!(live.exit_of(block(func, "exit")), Some(&set(&[])));
+}
+
+#[test]
+fn dominators() {
+    let module = Module::try_from(LOOP).unwrap();
+    let func = &module.functions()[module.find_function_by_name("f").unwrap()];
+    let doms = Dataflow::new(func, &Dominators);
+    let dom_tree = DominatorTree::new(func);
+
+    for x in func.layout.block_iter() {
+        for y in func.layout.block_iter() {
+            assert_eq!(
+                doms.exit_of(y).unwrap().contains(&x),
+                dom_tree.dominates(x, y),
+            );
+        }
+    }
+}
+
+#[test]
+fn rpo() {
+    let module = Module::try_from(LOOP).unwrap();
+    let func = &module.functions()[module.find_function_by_name("f").unwrap()];
+    let order = reverse_post_order(func);
+    assert_eq!(order.len(), 4);
+    assert_eq!(order[0], block(func, "entry"));
+    assert_eq!(order[1], block(func, "loop"));
+    assert!(order[2..].contains(&block(func, "body")));
+    assert!(order[2..].contains(&block(func, "exit")));
+}
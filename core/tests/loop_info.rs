@@ -0,0 +1,141 @@
+use vicis_core::{
+    ir::{
+        function::{
+            basic_block::{BasicBlock, BasicBlockId},
+            Function,
+        },
+        module::{name::Name, Module},
+    },
+    pass::{
+        analysis::loop_info::{LoopInfo, LoopInfoPass},
+        PassManager,
+    },
+};
+
+const NESTED: &str = r#"
+define void @f(i32 %n) {
+entry:
+  br label %outer
+
+outer:
+  %i = phi i32 [ 0, %entry ], [ %i.next, %outer.latch ]
+  br label %inner
+
+inner:
+  %j = phi i32 [ 0, %outer ], [ %j.next, %inner ]
+  %j.next = add i32 %j, 1
+  %c = icmp slt i32 %j.next, %n
+  br i1 %c, label %inner, label %outer.latch
+
+outer.latch:
+  %i.next = add i32 %i, 1
+  %d = icmp slt i32 %i.next, %n
+  br i1 %d, label %outer, label %exit
+
+exit:
+  ret void
+}
+"#;
+
+fn block(func: &Function, name: &str) -> BasicBlockId {
+    func.layout
+        .block_iter()
+        .find(|&b| func.data.basic_blocks[b].name == Some(Name::Name(name.to_string())))
+        .unwrap()
+}
+
+#[test]
+fn nested_loops() {
+    let module = Module::try_from(NESTED).unwrap();
+    let func = &module.functions()[module.find_function_by_name("f").unwrap()];
+    let loops = LoopInfo::new(func);
+    let [entry, outer, inner, latch, exit] =
+        ["entry", "outer", "inner", "outer.latch", "exit"].map(|name| block(func, name));
+
+    assert_eq!(
+        loops.loops().map(|(_, l)| l.header()).collect::<Vec<_>>(),
+        vec![outer, inner]
+    );
+    assert_eq!(loops.top_level_loops().len(), 1);
+
+    let outer_loop = loops.get(loops.top_level_loops()[0]);
+    assert_eq!(outer_loop.header(), outer);
+    assert_eq!(outer_loop.blocks().len(), 3);
+    assert!(outer_loop.contains(inner) && outer_loop.contains(latch));
+    assert_eq!(
+        outer_loop.latches().iter().collect::<Vec<_>>(),
+        vec![&latch]
+    );
+    assert_eq!(
+        outer_loop.exiting_blocks().iter().collect::<Vec<_>>(),
+        vec![&latch]
+    );
+    assert_eq!(
+        outer_loop.exit_blocks().iter().collect::<Vec<_>>(),
+        vec![&exit]
+    );
+    assert_eq!(outer_loop.preheader(), Some(entry));
+    assert_eq!(outer_loop.depth(), 1);
+
+    let inner_id = loops.loop_of(inner).unwrap();
+    let inner_loop = loops.get(inner_id);
+    assert_eq!(inner_loop.header(), inner);
+    assert_eq!(inner_loop.blocks().len(), 1);
+    assert_eq!(
+        inner_loop.latches().iter().collect::<Vec<_>>(),
+        vec![&inner]
+    );
+    assert_eq!(
+        inner_loop.exit_blocks().iter().collect::<Vec<_>>(),
+        vec![&latch]
+    );
+    assert_eq!(inner_loop.preheader(), Some(outer));
+    assert_eq!(inner_loop.parent(), Some(loops.top_level_loops()[0]));
+    assert_eq!(outer_loop.children(), &[inner_id]);
+    assert_eq!(inner_loop.depth(), 2);
+
+    assert_eq!(loops.depth_of(entry), 0);
+    assert_eq!(loops.depth_of(outer), 1);
+    assert_eq!(loops.depth_of(inner), 2);
+    assert_eq!(loops.depth_of(latch), 1);
+    assert_eq!(loops.depth_of(exit), 0);
+    assert!(loops.is_header(outer) && loops.is_header(inner) && !loops.is_header(latch));
+}
+
+#[test]
+fn no_preheader() {
+    let module = Module::try_from(
+        r#"
+define void @f(i1 %c) {
+entry:
+  br i1 %c, label %a, label %header
+
+a:
+  br label %header
+
+header:
+  br i1 %c, label %header, label %exit
+
+exit:
+  ret void
+}
+"#,
+    )
+    .unwrap();
+    let func = &module.functions()[module.find_function_by_name("f").unwrap()];
+    let loops = LoopInfo::new(func);
+    let header = block(func, "header");
+    let id = loops.loop_of(header).unwrap();
+    assert_eq!(loops.get(id).header(), header);
+    assert_eq!(loops.get(id).preheader(), None);
+}
+
+#[test]
+fn loop_info_pass() {
+    let mut module = Module::try_from(NESTED).unwrap();
+    let mut pm = PassManager::new();
+    pm.add_analysis(LoopInfoPass);
+    pm.run_on_module(&mut module);
+    let loops = pm.get_result::<LoopInfo<BasicBlock>>().unwrap();
+    assert_eq!(loops.loops().count(), 2);
+}
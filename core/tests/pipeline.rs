@@ -0,0 +1,139 @@
+use std::{any::Any, cell::Cell, rc::Rc};
+use vicis_core::{
+    ir::{function::Function, module::Module},
+    pass::{
+        pipeline::{FixedPointPass, OptLevel, PipelineError},
+        PassManager, TransformPass,
+    },
+};
+
+const SOURCE: &str = r#"
+define dso_local i32 @main() {
+entry:
+  %x = alloca i32, align 4
+  store i32 2, i32* %x, align 4
+  %a = load i32, i32* %x, align 4
+  %b = mul nsw i32 %a, 3
+  %c = icmp slt i32 %b, 10
+  br i1 %c, label %then, label %else
+then:
+  %d = add nsw i32 %b, 1
+  br label %exit
+else:
+  %e = sub nsw i32 %b, 1
+  br label %exit
+exit:
+  %r = phi i32 [ %d, %then ], [ %e, %else ]
+  %unused = add nsw i32 %r, %a
+  ret i32 %r
+}"#;
+
+fn run(mut pm: PassManager<Function>) -> Module {
+    let mut module = Module::try_from(SOURCE).expect("failed to parse ir");
+    pm.run_on_module(&mut module);
+    module
+}
+
+#[test]
+fn o0() {
+    let module = run(PassManager::with_opt_level(OptLevel::O0));
+    assert_eq!(
+        format!("{:?}", module),
+        format!("{:?}", Module::try_from(SOURCE).unwrap())
+    );
+}
+
+#[test]
+fn o1() {
+    insta::assert_debug_snapshot!(run(PassManager::with_opt_level(OptLevel::O1)));
+}
+
+#[test]
+fn o2() {
+    insta::assert_debug_snapshot!(run(PassManager::with_opt_level(OptLevel::O2)));
+}
+
+#[test]
+fn spec() {
+    let o2 = format!("{:?}", run(PassManager::with_opt_level(OptLevel::O2)));
+    for spec in [
+        "mem2reg,fixed-point(sccp,dce)",
+        " mem2reg , fixed-point( sccp, dce ) ",
+        "O2",
+        "mem2reg,sccp,dce,,sccp,dce",
+    ] {
+        let pm = PassManager::from_pipeline(spec).unwrap();
+        assert_eq!(format!("{:?}", run(pm)), o2, "{}", spec);
+    }
+}
+
+#[test]
+fn spec_errors() {
+    assert_eq!(
+        PassManager::from_pipeline("mem2reg,foo").err(),
+        Some(PipelineError::UnknownPass("foo".into()))
+    );
+    assert_eq!(
+        PassManager::from_pipeline("fixed-point(sccp,dce").err(),
+        Some(PipelineError::UnbalancedParens(
+            "fixed-point(sccp,dce".into()
+        ))
+    );
+    assert_eq!(
+        PassManager::from_pipeline("dce)").err(),
+        Some(PipelineError::UnbalancedParens("dce)".into()))
+    );
+    assert_eq!(
+        PassManager::from_pipeline("sccp(dce)").err(),
+        Some(PipelineError::UnexpectedArgs("sccp".into()))
+    );
+}
+
+#[test]
+fn opt_level_from_str() {
+    assert_eq!("0".parse(), Ok(OptLevel::O0));
+    assert_eq!("O1".parse(), Ok(OptLevel::O1));
+    assert_eq!("-O2".parse(), Ok(OptLevel::O2));
+    assert_eq!(
+        "3".parse::<OptLevel>(),
+        Err(PipelineError::UnknownOptLevel("3".into()))
+    );
+}
+
+/// Reports a change for the first `changes` runs.
+struct CountingPass {
+    runs: Rc<Cell<usize>>,
+    changes: usize,
+}
+
+impl TransformPass<Function> for CountingPass {
+    fn run_on_and_report_change(&self, _: &mut Function, _: &mut Box<dyn Any>) -> bool {
+        self.runs.set(self.runs.get() + 1);
+        self.runs.get() <= self.changes
+    }
+}
+
+#[test]
+fn fixed_point() {
+    let runs = Rc::new(Cell::new(0));
+    let mut pm = PassManager::new();
+    pm.add_transform(FixedPointPass::new(vec![Box::new(CountingPass {
+        runs: runs.clone(),
+        changes: 3,
+    })]));
+    run(pm);
+    // Three changing iterations and one that confirms the fixed point.
+    assert_eq!(runs.get(), 4);
+
+    let runs = Rc::new(Cell::new(0));
+    let mut pm = PassManager::new();
+    pm.add_transform(
+        FixedPointPass::new(vec![Box::new(CountingPass {
+            runs: runs.clone(),
+            changes: usize::MAX,
+        })])
+        .with_max_iterations(5),
+    );
+    run(pm);
+    assert_eq!(runs.get(), 5);
+}
@@ -0,0 +1,185 @@
+extern crate structopt;
+extern crate vicis_core;
+extern crate vicis_interpreter;
+
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    process,
+};
+use structopt::StructOpt;
+use vicis_core::ir::module::{name::Name, Module};
+use vicis_interpreter::{
+    generic_value::GenericValue,
+    interpreter::{self, Breakpoint, Command, Debugger, Stop},
+};
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "debugger")]
+pub struct Opt {
+    pub ir_file: String,
+
+    #[structopt(long = "load")]
+    pub libs: Vec<String>,
+
+    /// Check every memory access in a simulated address space
+    #[structopt(long = "sandbox")]
+    pub sandbox: bool,
+}
+
+const HELP: &str = "\
+b <func>             break on entry to <func>
+b <func>:<block>     break at the start of <block> (e.g. main:entry, main:4)
+b <func>#<n>         break at instruction #<n>
+d [<n>]              delete breakpoint <n>, or all of them
+info                 list breakpoints
+s                    step into calls
+n                    step over calls
+f                    run until the current function returns
+c                    continue
+p [<frame>] <name>   print %value or @global, in the <frame>th frame from the top
+bt                   backtrace
+x <ptr> [<len>]      dump <len> bytes at a %value, @global or hex address (unchecked
+                     without --sandbox)
+l                    show the current instruction
+q                    quit";
+
+fn main() {
+    env_logger::init();
+    color_backtrace::install();
+    let opt = Opt::from_args();
+    let ir = fs::read_to_string(opt.ir_file).expect("failed to load *.ll file");
+    let module = Module::try_from(ir.as_str()).expect("failed to parse LLVM Assembly");
+    let main = module
+        .find_function_by_name("main")
+        .expect("failed to lookup 'main'");
+
+    let debugger = Debugger::new(repl).with_breakpoint(Breakpoint::Function("main".into()));
+    let mut ctx = interpreter::ContextBuilder::new(&module)
+        .with_libs(opt.libs)
+        .with_debugger(debugger);
+    if opt.sandbox {
+        ctx = ctx.with_sandbox();
+    }
+    let ctx = ctx.build().expect("failed to create interpreter context");
+
+    match interpreter::run_function(&ctx, main, vec![]) {
+        Ok(ret) => println!("returned {:?}", ret),
+        Err(e) => {
+            eprintln!("{}", e.report(&module));
+            process::exit(1)
+        }
+    }
+}
+
+fn repl(stop: &mut Stop) -> Command {
+    if let Some(n) = stop.breakpoint {
+        println!("breakpoint {}", n);
+    }
+    println!("{}", stop.current().location(stop.ctx));
+
+    let stdin = io::stdin();
+    loop {
+        print!("(dbg) ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            process::exit(0)
+        }
+        let args: Vec<&str> = line.split_whitespace().collect();
+        match args.as_slice() {
+            [] => continue,
+            ["s"] => return Command::Step,
+            ["n"] => return Command::Next,
+            ["f"] => return Command::Finish,
+            ["c"] => return Command::Continue,
+            ["q"] => process::exit(0),
+            ["l"] => println!("{}", stop.current().location(stop.ctx)),
+            ["bt"] => print!("{}", stop.backtrace()),
+            ["info"] => {
+                for (i, bp) in stop.breakpoints.iter().enumerate() {
+                    println!("{}: {:?}", i, bp)
+                }
+            }
+            ["b", spec] => match parse_breakpoint(stop, spec) {
+                Some(bp) => stop.breakpoints.push(bp),
+                None => println!("no such location: {}", spec),
+            },
+            ["d"] => stop.breakpoints.clear(),
+            ["d", n] => match n.parse::<usize>() {
+                Ok(n) if n < stop.breakpoints.len() => {
+                    stop.breakpoints.remove(n);
+                }
+                _ => println!("no such breakpoint: {}", n),
+            },
+            ["p", name] => print_value(stop, 0, name),
+            ["p", frame, name] => match frame.parse() {
+                Ok(frame) => print_value(stop, frame, name),
+                Err(_) => println!("invalid frame: {}", frame),
+            },
+            ["x", ptr] => dump_memory(stop, ptr, "16"),
+            ["x", ptr, len] => dump_memory(stop, ptr, len),
+            _ => println!("{}", HELP),
+        }
+    }
+}
+
+fn parse_breakpoint(stop: &Stop, spec: &str) -> Option<Breakpoint> {
+    let module = stop.ctx.module;
+    if let Some((func, block)) = spec.split_once(':') {
+        let label = match block.parse() {
+            Ok(n) => Name::Number(n),
+            Err(_) => Name::Name(block.to_string()),
+        };
+        return Some(Breakpoint::Block(func.to_string(), label));
+    }
+    if let Some((func, n)) = spec.split_once('#') {
+        let n: usize = n.parse().ok()?;
+        let f = &module.functions()[module.find_function_by_name(func)?];
+        let (id, _) = f.data.instructions.iter().find(|(id, _)| id.index() == n)?;
+        return Some(Breakpoint::Instruction(func.to_string(), id));
+    }
+    module.find_function_by_name(spec)?;
+    Some(Breakpoint::Function(spec.to_string()))
+}
+
+fn print_value(stop: &Stop, frame: usize, name: &str) {
+    match stop.frame(frame).map(|f| f.value(stop.ctx, name)) {
+        Some(Ok(val)) => println!("{} = {}", name, format_value(val)),
+        Some(Err(e)) => println!("{}", e),
+        None => println!("no frame #{}", frame),
+    }
+}
+
+fn dump_memory(stop: &Stop, ptr: &str, len: &str) {
+    let ptr = match ptr.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok().map(|p| p as *mut u8),
+        None => stop.value(ptr).ok().and_then(|v| v.to_ptr()),
+    };
+    let (ptr, len) = match (ptr, len.parse()) {
+        (Some(ptr), Ok(len)) => (ptr, len),
+        _ => return println!("usage: x <ptr> [<len>]"),
+    };
+    // SAFETY: The memory to dump is up to the user, who is warned that an invalid address is not
+    // checked without the sandbox.
+    match unsafe { stop.read_memory(ptr, len) } {
+        Ok(bytes) => {
+            for (i, line) in bytes.chunks(16).enumerate() {
+                let hex: Vec<String> = line.iter().map(|b| format!("{:02x}", b)).collect();
+                println!("{:p}: {}", ptr.wrapping_add(i * 16), hex.join(" "));
+            }
+        }
+        Err(e) => println!("{}", e),
+    }
+}
+
+fn format_value(val: GenericValue) -> String {
+    match val {
+        GenericValue::Ptr(p) => format!("{:p}", p),
+        GenericValue::Id(_) => "<function>".to_string(),
+        v => match v.sext_to_i64() {
+            Some(i) => i.to_string(),
+            None => format!("{:?}", v),
+        },
+    }
+}
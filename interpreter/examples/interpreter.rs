@@ -14,6 +14,22 @@ pub struct Opt {
 
     #[structopt(long = "load")]
     pub libs: Vec<String>,
+
+    /// Check every memory access in a simulated address space
+    #[structopt(long = "sandbox")]
+    pub sandbox: bool,
+
+    /// Print execution counts per function and block to stderr
+    #[structopt(long = "profile")]
+    pub profile: bool,
+
+    /// Print the last <trace> executed instructions and calls to stderr
+    #[structopt(long = "trace", default_value = "0")]
+    pub trace: usize,
+
+    /// Write the module annotated with the branch weights observed to a file
+    #[structopt(long = "annotate")]
+    pub annotate: Option<String>,
 }
 
 fn main() {
@@ -21,14 +37,40 @@ fn main() {
     color_backtrace::install();
     let opt = Opt::from_args();
     let ir = fs::read_to_string(opt.ir_file).expect("failed to load *.ll file");
-    let module = Module::try_from(ir.as_str()).expect("failed to parse LLVM Assembly");
+    let mut module = Module::try_from(ir.as_str()).expect("failed to parse LLVM Assembly");
     let main = module
         .find_function_by_name("main")
         .expect("failed to lookup 'main'");
-    let ctx = interpreter::ContextBuilder::new(&module)
-        .with_libs(opt.libs)
-        .build()
-        .expect("failed to create interpreter context");
+    let mut ctx = interpreter::ContextBuilder::new(&module).with_libs(opt.libs);
+    if opt.sandbox {
+        ctx = ctx.with_sandbox();
+    }
+    if opt.profile || opt.trace > 0 || opt.annotate.is_some() {
+        ctx = ctx.with_profiler(interpreter::Profiler::new().with_trace(opt.trace));
+    }
+    let ctx = ctx.build().expect("failed to create interpreter context");
     let ret = interpreter::run_function(&ctx, main, vec![]);
-    process::exit(ret.expect("unknown error").sext_to_i64().unwrap_or(0) as i32)
+    let profile = ctx.profile().map(|p| p.clone());
+    drop(ctx);
+
+    if let Some(profile) = profile {
+        if opt.trace > 0 {
+            eprint!("{}", profile.dump_trace(&module));
+        }
+        if opt.profile {
+            eprint!("{}", profile.report(&module));
+        }
+        if let Some(path) = opt.annotate {
+            profile.annotate(&mut module);
+            fs::write(path, format!("{:?}", module)).expect("failed to write *.ll file");
+        }
+    }
+
+    match ret {
+        Ok(ret) => process::exit(ret.sext_to_i64().unwrap_or(0) as i32),
+        Err(e) => {
+            eprintln!("{}", e.report(&module));
+            process::exit(1)
+        }
+    }
 }
@@ -10,6 +10,8 @@ pub enum GenericValue {
     Int64(i64),
     Ptr(*mut u8),
     Id([u8; 16]),
+    /// The `{ i8*, i32 }` value of a `landingpad`: the exception object and the selector.
+    LandingPad(*mut u8, i32),
 }
 
 impl GenericValue {
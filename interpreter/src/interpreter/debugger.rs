@@ -0,0 +1,266 @@
+//! A debugger for interpreted code.
+//!
+//! A `Debugger` attached to a `Context` is consulted before every instruction. When it stops at
+//! a breakpoint or after a step, its handler is given a `Stop` to inspect the call stack and the
+//! memory, and returns a `Command` that tells how to resume.
+
+use super::{frame::StackFrame, Context, InterpErrorKind};
+use crate::generic_value::GenericValue;
+use rustc_hash::FxHashMap;
+use std::fmt::Write;
+use vicis_core::ir::{
+    function::{basic_block::BasicBlockId, instruction::InstructionId, Function, FunctionId},
+    module::name::Name,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Stops on entry to the function.
+    Function(String),
+    /// Stops at the first instruction of the block labeled with the name in the function.
+    Block(String, Name),
+    Instruction(String, InstructionId),
+}
+
+/// How to resume after a stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Runs until the next breakpoint.
+    Continue,
+    /// Stops at the next instruction, entering calls.
+    Step,
+    /// Stops at the next instruction of the current function, stepping over calls.
+    Next,
+    /// Runs until the current function returns to its caller.
+    Finish,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Mode {
+    Continue,
+    Step,
+    /// Stops at a depth of the call stack or shallower.
+    Next(usize),
+    /// Stops at a depth of the call stack shallower than this.
+    Finish(usize),
+}
+
+type Handler = Box<dyn FnMut(&mut Stop) -> Command>;
+
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    handler: Handler,
+    mode: Mode,
+}
+
+/// A stack frame as seen by the debugger.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub func_id: FunctionId,
+    pub block: BasicBlockId,
+    /// The instruction about to be executed, or the call being executed in callers.
+    pub inst: InstructionId,
+    values: FxHashMap<InstructionId, GenericValue>,
+    args: Vec<GenericValue>,
+}
+
+/// The state of the interpreter at a stop.
+pub struct Stop<'a> {
+    pub ctx: &'a Context<'a>,
+    /// The call stack, outermost first.
+    pub frames: Vec<Frame>,
+    /// The index of the breakpoint stopped at, if any.
+    pub breakpoint: Option<usize>,
+    /// The breakpoints, which may be changed before resuming.
+    pub breakpoints: &'a mut Vec<Breakpoint>,
+}
+
+impl Debugger {
+    pub fn new<F: FnMut(&mut Stop) -> Command + 'static>(handler: F) -> Self {
+        Self {
+            breakpoints: vec![],
+            handler: Box::new(handler),
+            mode: Mode::Continue,
+        }
+    }
+
+    pub fn with_breakpoint(mut self, breakpoint: Breakpoint) -> Self {
+        self.breakpoints.push(breakpoint);
+        self
+    }
+
+    /// Stops at the first instruction executed.
+    pub fn stop_at_start(mut self) -> Self {
+        self.mode = Mode::Step;
+        self
+    }
+
+    /// Called before `inst` of `frame` is executed.
+    pub(super) fn on_instruction(
+        &mut self,
+        ctx: &Context,
+        frame: &StackFrame,
+        inst: InstructionId,
+    ) {
+        let depth = frame.depth;
+        let breakpoint = self
+            .breakpoints
+            .iter()
+            .position(|bp| bp.is_hit(frame.func, inst));
+        let stop = breakpoint.is_some()
+            || match self.mode {
+                Mode::Continue => false,
+                Mode::Step => true,
+                Mode::Next(d) => depth <= d,
+                Mode::Finish(d) => depth < d,
+            };
+        if !stop {
+            return;
+        }
+
+        // Frames are captured only here, as copying the values of every caller on each call would
+        // slow down the execution even with no breakpoint hit.
+        let mut stop = Stop {
+            ctx,
+            frames: frame.backtrace(inst),
+            breakpoint,
+            breakpoints: &mut self.breakpoints,
+        };
+        self.mode = match (self.handler)(&mut stop) {
+            Command::Continue => Mode::Continue,
+            Command::Step => Mode::Step,
+            Command::Next => Mode::Next(depth),
+            Command::Finish => Mode::Finish(depth),
+        };
+    }
+}
+
+impl Breakpoint {
+    fn is_hit(&self, func: &Function, inst: InstructionId) -> bool {
+        let is_first_of = |block: BasicBlockId| func.layout.inst_iter(block).next() == Some(inst);
+        match self {
+            Self::Function(name) => {
+                name == func.name() && func.layout.get_entry_block().is_some_and(is_first_of)
+            }
+            Self::Block(name, label) => {
+                let block = func.data.inst_ref(inst).parent;
+                name == func.name()
+                    && func.data.block_ref(block).name.as_ref() == Some(label)
+                    && is_first_of(block)
+            }
+            Self::Instruction(name, id) => name == func.name() && *id == inst,
+        }
+    }
+}
+
+impl Frame {
+    pub(super) fn new(
+        func_id: FunctionId,
+        block: BasicBlockId,
+        inst: InstructionId,
+        values: FxHashMap<InstructionId, GenericValue>,
+        args: Vec<GenericValue>,
+    ) -> Self {
+        Self {
+            func_id,
+            block,
+            inst,
+            values,
+            args,
+        }
+    }
+
+    /// Returns the value of an instruction or an argument named like `%x` or `%3`, or the
+    /// address of a global variable named like `@g`.
+    pub fn value(&self, ctx: &Context, name: &str) -> Result<GenericValue, InterpErrorKind> {
+        let unresolved = || InterpErrorKind::UnresolvedSymbol(name.to_string());
+        if let Some(global) = name.strip_prefix('@') {
+            return ctx
+                .globals
+                .get(&Name::Name(global.to_string()))
+                .copied()
+                .ok_or_else(unresolved);
+        }
+        let name = parse_name(name.strip_prefix('%').unwrap_or(name));
+        let func = &ctx.module.functions()[self.func_id];
+        if let Some(nth) = func.params.iter().position(|p| p.name == name) {
+            return self
+                .args
+                .get(nth)
+                .copied()
+                .ok_or(InterpErrorKind::UndefinedValue);
+        }
+        let (id, _) = func
+            .data
+            .instructions
+            .iter()
+            .find(|(_, inst)| inst.dest.as_ref() == Some(&name))
+            .ok_or_else(unresolved)?;
+        self.values
+            .get(&id)
+            .copied()
+            .ok_or(InterpErrorKind::UndefinedValue)
+    }
+
+    /// Describes where the frame is, e.g. ``@main, %entry: `%1 = add i32 %0, 1` ``.
+    pub fn location(&self, ctx: &Context) -> String {
+        let func = &ctx.module.functions()[self.func_id];
+        let block = match &func.data.block_ref(self.block).name {
+            Some(name) => format!("%{}", name),
+            None => format!("block #{}", self.block.index()),
+        };
+        format!(
+            "@{}, {}: `{}`",
+            func.name(),
+            block,
+            func.data
+                .inst_ref(self.inst)
+                .display(&func.data, &func.types)
+        )
+    }
+}
+
+impl Stop<'_> {
+    /// Returns the frame stopped in.
+    pub fn current(&self) -> &Frame {
+        self.frames.last().unwrap()
+    }
+
+    /// Returns the `n`th frame counting outward from the current one.
+    pub fn frame(&self, n: usize) -> Option<&Frame> {
+        self.frames.iter().rev().nth(n)
+    }
+
+    /// Returns the value named `name` in the current frame. See `Frame::value`.
+    pub fn value(&self, name: &str) -> Result<GenericValue, InterpErrorKind> {
+        self.current().value(self.ctx, name)
+    }
+
+    /// Renders the call stack, innermost first.
+    pub fn backtrace(&self) -> String {
+        let mut bt = String::new();
+        for (i, frame) in self.frames.iter().rev().enumerate() {
+            writeln!(bt, "#{} {}", i, frame.location(self.ctx)).unwrap();
+        }
+        bt
+    }
+
+    /// Reads `len` bytes of memory at `ptr`. Uninitialized bytes in the sandbox read as is.
+    ///
+    /// # Safety
+    ///
+    /// Without the sandbox, `ptr` must be valid for reads of `len` bytes. With the sandbox, any
+    /// `ptr` is safe, and an error is returned if it is not allocated.
+    pub unsafe fn read_memory(&self, ptr: *mut u8, len: usize) -> Result<Vec<u8>, InterpErrorKind> {
+        let mut buf = vec![0; len];
+        self.ctx.peek(ptr, &mut buf)?;
+        Ok(buf)
+    }
+}
+
+fn parse_name(name: &str) -> Name {
+    match name.parse() {
+        Ok(n) => Name::Number(n),
+        Err(_) => Name::Name(name.to_string()),
+    }
+}
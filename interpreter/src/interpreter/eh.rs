@@ -0,0 +1,228 @@
+//! Emulation of the Itanium C++ ABI exception handling runtime (`__cxa_*`) for interpreted code.
+//!
+//! Exceptions never leave the interpreter: a throw unwinds the interpreted frames up to the
+//! nearest `invoke` whose `landingpad` catches the exception.
+
+use super::{frame::Caller, run_frame, sandbox::AllocSite, Context, Exit, InterpErrorKind};
+use crate::generic_value::GenericValue;
+use rustc_hash::FxHashMap;
+use std::alloc;
+use vicis_core::ir::{
+    function::FunctionId,
+    module::name::Name,
+    value::{ConstantStruct, ConstantValue},
+};
+
+/// Exceptions in flight or being handled.
+#[derive(Default)]
+pub struct ExceptionState {
+    exceptions: FxHashMap<*mut u8, Exception>,
+    /// Exceptions whose handlers are running, innermost last.
+    caught: Vec<*mut u8>,
+    /// Typeinfos numbered by `llvm.eh.typeid.for`. The selector of a typeinfo is its index + 1.
+    type_ids: Vec<*mut u8>,
+}
+
+struct Exception {
+    layout: alloc::Layout,
+    tinfo: *mut u8,
+    dtor: GenericValue,
+    handlers: usize,
+    rethrown: bool,
+}
+
+impl ExceptionState {
+    /// Returns the selector `llvm.eh.typeid.for` gives for `tinfo`.
+    pub fn type_id_for(&mut self, tinfo: *mut u8) -> i32 {
+        let idx = match self.type_ids.iter().position(|&t| t == tinfo) {
+            Some(idx) => idx,
+            None => {
+                self.type_ids.push(tinfo);
+                self.type_ids.len() - 1
+            }
+        };
+        idx as i32 + 1
+    }
+
+    /// Returns the typeinfo `exn` was thrown with.
+    pub fn tinfo_of(&self, exn: *mut u8) -> Option<*mut u8> {
+        self.exceptions.get(&exn).map(|e| e.tinfo)
+    }
+}
+
+/// Handles calls to the C++ runtime functions that take part in exception handling.
+/// Returns `None` if `name` is not one of them.
+pub(super) fn call(
+    ctx: &Context,
+    name: &str,
+    args: &[GenericValue],
+    caller: Caller,
+) -> Option<Result<Exit, InterpErrorKind>> {
+    let ptr_arg = |i: usize| {
+        args.get(i).and_then(|a| a.to_ptr()).ok_or_else(|| {
+            InterpErrorKind::TypeMismatch(format!("arguments of {}: {:?}", name, args))
+        })
+    };
+    let ret = |val| Ok(Exit::Return(val));
+
+    Some(match name {
+        "__cxa_allocate_exception" => args
+            .first()
+            .and_then(|a| a.zext_to_u64())
+            .ok_or_else(|| InterpErrorKind::TypeMismatch(format!("arguments of {}", name)))
+            .and_then(|size| {
+                let layout = alloc::Layout::from_size_align(size.max(1) as usize, 16)
+                    .map_err(|e| InterpErrorKind::InvalidIr(e.to_string()))?;
+                let exn =
+                    ctx.allocate(layout.size(), layout.align(), AllocSite::Exception, false)?;
+                ctx.eh.borrow_mut().exceptions.insert(
+                    exn,
+                    Exception {
+                        layout,
+                        tinfo: std::ptr::null_mut(),
+                        dtor: GenericValue::Ptr(std::ptr::null_mut()),
+                        handlers: 0,
+                        rethrown: false,
+                    },
+                );
+                ret(GenericValue::Ptr(exn))
+            }),
+        "__cxa_free_exception" => ptr_arg(0)
+            .and_then(|exn| free_exception(ctx, exn))
+            .map(|_| Exit::Return(GenericValue::Void)),
+        "__cxa_throw" => ptr_arg(0).and_then(|exn| {
+            let tinfo = ptr_arg(1)?;
+            let mut eh = ctx.eh.borrow_mut();
+            let e = eh.exceptions.get_mut(&exn).ok_or_else(|| {
+                InterpErrorKind::InvalidIr(
+                    "thrown object was not allocated by __cxa_allocate_exception".into(),
+                )
+            })?;
+            e.tinfo = tinfo;
+            e.dtor = args.get(2).copied().unwrap_or(GenericValue::Void);
+            Ok(Exit::Unwind(exn))
+        }),
+        "__cxa_rethrow" => {
+            let mut eh = ctx.eh.borrow_mut();
+            match eh.caught.last().copied() {
+                Some(exn) => match eh.exceptions.get_mut(&exn) {
+                    Some(e) => {
+                        e.rethrown = true;
+                        Ok(Exit::Unwind(exn))
+                    }
+                    None => Err(InterpErrorKind::InvalidIr(
+                        "rethrown object was not allocated by __cxa_allocate_exception".into(),
+                    )),
+                },
+                None => Err(InterpErrorKind::InvalidIr(
+                    "__cxa_rethrow called with no exception being handled".into(),
+                )),
+            }
+        }
+        // The exception object and the unwind header are the same pointer in the interpreter.
+        "__cxa_get_exception_ptr" => ptr_arg(0).map(|exn| Exit::Return(GenericValue::Ptr(exn))),
+        "__cxa_begin_catch" => ptr_arg(0).map(|exn| {
+            let mut eh = ctx.eh.borrow_mut();
+            if let Some(e) = eh.exceptions.get_mut(&exn) {
+                e.handlers += 1;
+                e.rethrown = false;
+            }
+            eh.caught.push(exn);
+            Exit::Return(GenericValue::Ptr(exn))
+        }),
+        "__cxa_end_catch" => {
+            let exn = ctx.eh.borrow_mut().caught.pop();
+            match exn {
+                Some(exn) => end_catch(ctx, exn, caller).map(|_| Exit::Return(GenericValue::Void)),
+                None => Err(InterpErrorKind::InvalidIr(
+                    "__cxa_end_catch called with no exception being handled".into(),
+                )),
+            }
+        }
+        name if name.starts_with("llvm.eh.typeid.for") => ptr_arg(0)
+            .map(|tinfo| Exit::Return(GenericValue::Int32(ctx.eh.borrow_mut().type_id_for(tinfo)))),
+        _ => return None,
+    })
+}
+
+fn end_catch(ctx: &Context, exn: *mut u8, caller: Caller) -> Result<(), InterpErrorKind> {
+    let dtor = {
+        let mut eh = ctx.eh.borrow_mut();
+        let e = match eh.exceptions.get_mut(&exn) {
+            Some(e) => e,
+            None => return Ok(()),
+        };
+        e.handlers = e.handlers.saturating_sub(1);
+        if e.handlers > 0 || e.rethrown {
+            return Ok(());
+        }
+        e.dtor
+    };
+    if let Some(&dtor) = dtor.to_id::<FunctionId>() {
+        run_frame(ctx, dtor, vec![GenericValue::Ptr(exn)], caller).map_err(|e| e.kind)?;
+    }
+    free_exception(ctx, exn)
+}
+
+fn free_exception(ctx: &Context, exn: *mut u8) -> Result<(), InterpErrorKind> {
+    let exception = ctx.eh.borrow_mut().exceptions.remove(&exn);
+    match exception {
+        Some(e) => ctx.deallocate(exn, e.layout),
+        None => Ok(()),
+    }
+}
+
+/// Returns `true` if a catch clause for `clause` catches an exception thrown with `thrown`.
+/// A null `clause` catches everything. Classes derived through single inheritance are caught
+/// by their base classes.
+pub(super) fn catches(ctx: &Context, thrown: *mut u8, clause: *mut u8) -> bool {
+    if clause.is_null() {
+        return true;
+    }
+    let mut tinfo = Some(thrown);
+    // Guard against cycles in a malformed typeinfo graph.
+    for _ in 0..64 {
+        match tinfo {
+            Some(t) if t == clause => return true,
+            Some(t) => tinfo = base_class_of(ctx, t),
+            None => return false,
+        }
+    }
+    false
+}
+
+/// Returns the typeinfo of the base class if `tinfo` is an `__si_class_type_info` defined in
+/// the module, i.e. `{ vtable, name, base }`.
+fn base_class_of(ctx: &Context, tinfo: *mut u8) -> Option<*mut u8> {
+    let name = global_name_of(ctx, tinfo)?;
+    let gv = ctx.module.global_variables().get(name)?;
+    match &gv.init {
+        Some(ConstantValue::Struct(ConstantStruct { elems, .. })) if elems.len() == 3 => {
+            if !refers_to(&elems[0], "__si_class_type_info") {
+                return None;
+            }
+            ctx.eval_const(&elems[2]).ok()?.to_ptr()
+        }
+        _ => None,
+    }
+}
+
+/// Returns the name of the global variable at `ptr`.
+pub(super) fn global_name_of<'a>(ctx: &'a Context, ptr: *mut u8) -> Option<&'a Name> {
+    ctx.globals
+        .iter()
+        .find(|(_, v)| v.to_ptr() == Some(ptr))
+        .map(|(name, _)| name)
+}
+
+fn refers_to(val: &ConstantValue, needle: &str) -> bool {
+    use vicis_core::ir::value::ConstantExpr;
+    match val {
+        ConstantValue::GlobalRef(name, _) => name.to_string().is_some_and(|n| n.contains(needle)),
+        ConstantValue::Expr(ConstantExpr::Bitcast { arg, .. }) => refers_to(arg, needle),
+        ConstantValue::Expr(ConstantExpr::GetElementPtr { args, .. }) => {
+            args.first().is_some_and(|a| refers_to(a, needle))
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,119 @@
+use super::MemoryError;
+use std::fmt::{self, Write};
+use thiserror::Error;
+use vicis_core::ir::{
+    function::{instruction::InstructionId, Function},
+    module::Module,
+};
+
+/// An error raised while interpreting a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterpError {
+    /// Name of the function being executed. Empty if no function was being executed,
+    /// e.g. while building a `Context`.
+    pub func: String,
+    /// The instruction being executed, if any.
+    pub inst: Option<InstructionId>,
+    pub kind: InterpErrorKind,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum InterpErrorKind {
+    #[error("unsupported instruction: {0}")]
+    UnsupportedInstruction(String),
+
+    #[error("unsupported constant: {0}")]
+    UnsupportedConstant(String),
+
+    #[error("unresolved symbol: {0}")]
+    UnresolvedSymbol(String),
+
+    #[error("type mismatch: {0}")]
+    TypeMismatch(String),
+
+    #[error("division by zero")]
+    DivisionByZero,
+
+    #[error("out-of-bounds access: {0}")]
+    OutOfBounds(String),
+
+    #[error("use of undefined value")]
+    UndefinedValue,
+
+    #[error("invalid IR: {0}")]
+    InvalidIr(String),
+
+    #[error(transparent)]
+    Memory(#[from] MemoryError),
+
+    #[error("uncaught exception of type {0}")]
+    UncaughtException(String),
+
+    #[error("failed to load library: {0}")]
+    Library(String),
+}
+
+impl InterpError {
+    pub fn new(func: &Function, inst: Option<InstructionId>, kind: InterpErrorKind) -> Self {
+        Self {
+            func: func.name().clone(),
+            inst,
+            kind,
+        }
+    }
+
+    /// Creates an error raised outside of any function.
+    pub fn global(kind: InterpErrorKind) -> Self {
+        Self {
+            func: String::new(),
+            inst: None,
+            kind,
+        }
+    }
+
+    /// Renders the error along with the instruction it was raised at, e.g.
+    ///
+    /// ```text
+    /// error: out-of-bounds write of 4 bytes at offset 8 of `%1 = alloca [2 x i32], i32 1, align 4` in @main (8 bytes)
+    ///   --> @main, instruction #2
+    ///    |
+    ///    |   store i32 0, i32* %2, align 4
+    /// ```
+    pub fn report(&self, module: &Module) -> String {
+        let mut report = format!("error: {}", self.kind);
+        let func = match module.find_function_by_name(&self.func) {
+            Some(id) => &module.functions()[id],
+            None => return report,
+        };
+        write!(report, "\n  --> @{}", self.func).unwrap();
+        if let Some(inst) = self.inst {
+            write!(
+                report,
+                ", instruction #{}\n   |\n   |   {}",
+                inst.index(),
+                func.data.inst_ref(inst).display(&func.data, &func.types)
+            )
+            .unwrap();
+        }
+        report
+    }
+}
+
+impl fmt::Display for InterpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if !self.func.is_empty() {
+            write!(f, " in @{}", self.func)?;
+        }
+        if let Some(inst) = self.inst {
+            write!(f, " at instruction #{}", inst.index())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InterpError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.kind)
+    }
+}
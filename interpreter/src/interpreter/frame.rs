@@ -1,27 +1,53 @@
 use rustc_hash::FxHashMap;
 
-use super::Context;
+use super::{debugger::Frame, Context, InterpErrorKind};
 use crate::generic_value::GenericValue;
-use std::ptr;
 use vicis_core::ir::{
-    function::{instruction::InstructionId, Function},
-    value::{ConstantExpr, ConstantInt, ConstantValue, Value, ValueId},
+    function::{instruction::InstructionId, Function, FunctionId},
+    value::{Value, ValueId},
 };
 
-pub struct StackFrame<'a> {
+/// The frame of an interpreted function that called another, and the call instruction.
+pub type Caller<'a, 'f> = Option<(&'f StackFrame<'a, 'f>, InstructionId)>;
+
+pub struct StackFrame<'a, 'f> {
     pub ctx: &'a Context<'a>,
+    pub func_id: FunctionId,
     pub func: &'a Function,
     val_map: FxHashMap<InstructionId, GenericValue>,
     args: Vec<GenericValue>,
+    /// Memory allocated by `alloca`s, which dies when the frame returns. Only kept in the sandbox.
+    allocas: Vec<*mut u8>,
+    caller: Caller<'a, 'f>,
+    /// The number of interpreted frames below this one.
+    pub depth: usize,
 }
 
-impl<'a> StackFrame<'a> {
-    pub fn new(ctx: &'a Context<'a>, func: &'a Function, args: Vec<GenericValue>) -> Self {
+impl<'a, 'f> StackFrame<'a, 'f> {
+    pub fn new(
+        ctx: &'a Context<'a>,
+        func_id: FunctionId,
+        args: Vec<GenericValue>,
+        caller: Caller<'a, 'f>,
+    ) -> Self {
+        if let Some(profiler) = &ctx.profiler {
+            profiler.borrow_mut().enter_function(func_id);
+        }
         Self {
             ctx,
-            func,
+            func_id,
+            func: &ctx.module.functions()[func_id],
             val_map: FxHashMap::default(),
             args,
+            allocas: vec![],
+            caller,
+            depth: caller.map_or(0, |(frame, _)| frame.depth + 1),
+        }
+    }
+
+    pub fn add_alloca(&mut self, ptr: *mut u8) {
+        if self.ctx.sandbox.is_some() {
+            self.allocas.push(ptr)
         }
     }
 
@@ -33,69 +59,52 @@ impl<'a> StackFrame<'a> {
         self.val_map.insert(id, val);
     }
 
-    pub fn get_val(&self, id: ValueId) -> Option<GenericValue> {
+    /// Captures the frame for the debugger, executing `inst`.
+    fn snapshot(&self, inst: InstructionId) -> Frame {
+        Frame::new(
+            self.func_id,
+            self.func.data.inst_ref(inst).parent,
+            inst,
+            self.val_map.clone(),
+            self.args.clone(),
+        )
+    }
+
+    /// Captures the call stack up to the frame for the debugger, outermost first.
+    pub fn backtrace(&self, inst: InstructionId) -> Vec<Frame> {
+        let mut frames = vec![self.snapshot(inst)];
+        let mut caller = self.caller;
+        while let Some((frame, inst)) = caller {
+            frames.push(frame.snapshot(inst));
+            caller = frame.caller;
+        }
+        frames.reverse();
+        frames
+    }
+
+    pub fn get_val(&self, id: ValueId) -> Result<GenericValue, InterpErrorKind> {
         match self.func.data.value_ref(id) {
-            Value::Instruction(id) => self.get_inst_val(*id),
-            Value::Argument(a) => self.args.get(a.nth).copied(),
-            Value::Constant(konst) => self.get_val_from_const(konst),
-            _ => None,
+            Value::Instruction(id) => self
+                .get_inst_val(*id)
+                .ok_or(InterpErrorKind::UndefinedValue),
+            Value::Argument(a) => self
+                .args
+                .get(a.nth)
+                .copied()
+                .ok_or(InterpErrorKind::UndefinedValue),
+            Value::Constant(konst) => self.ctx.eval_const(konst),
+            v => Err(InterpErrorKind::UnsupportedConstant(format!("{:?}", v))),
         }
     }
+}
 
-    fn get_val_from_const(&self, konst: &ConstantValue) -> Option<GenericValue> {
-        match konst {
-            ConstantValue::Null(ty) if ty.is_pointer(&self.ctx.module.types) => {
-                Some(GenericValue::Ptr(ptr::null_mut()))
-            }
-            ConstantValue::Int(ConstantInt::Int1(i)) => Some(GenericValue::Int1(*i)),
-            ConstantValue::Int(ConstantInt::Int8(i)) => Some(GenericValue::Int8(*i)),
-            ConstantValue::Int(ConstantInt::Int32(i)) => Some(GenericValue::Int32(*i)),
-            ConstantValue::Int(ConstantInt::Int64(i)) => Some(GenericValue::Int64(*i)),
-            ConstantValue::GlobalRef(name, _) => {
-                if let Some(f) = self
-                    .ctx
-                    .module
-                    .find_function_by_name(name.to_string().unwrap())
-                {
-                    return Some(GenericValue::id(f));
-                }
-                self.ctx.globals.get(name).copied()
-            }
-            ConstantValue::Expr(ConstantExpr::GetElementPtr { args, tys, .. }) => match args[0] {
-                ConstantValue::GlobalRef(ref name, _) => {
-                    assert!(matches!(args[1], ConstantValue::Int(i) if i.is_zero()));
-                    let n = match args[2] {
-                        ConstantValue::Int(i) => i.cast_to_i64(),
-                        _ => todo!(),
-                    };
-                    match self.ctx.globals.get(name).copied()? {
-                        GenericValue::Ptr(v) => {
-                            let types = &self.ctx.module.types;
-                            if tys[0].is_struct(&self.ctx.module.types) {
-                                let layout = self
-                                    .ctx
-                                    .module
-                                    .target()
-                                    .datalayout
-                                    .new_struct_layout_for(&self.ctx.module.types, tys[0])
-                                    .unwrap();
-                                let offset = layout.get_elem_offset(n as usize).unwrap();
-                                Some(GenericValue::Ptr(unsafe { v.add(offset) }))
-                            } else {
-                                // Array
-                                let ty = types.get_element(tys[0]).unwrap();
-                                let sz = self.ctx.module.target().datalayout.get_size_of(types, ty)
-                                    as i64;
-                                Some(GenericValue::Ptr(((v as i64) + n * sz) as *mut u8))
-                            }
-                        }
-                        x => Some(x),
-                    }
-                }
-                _ => todo!(),
-            },
-            ConstantValue::Expr(ConstantExpr::Bitcast { arg, .. }) => self.get_val_from_const(arg),
-            _ => todo!(),
+impl Drop for StackFrame<'_, '_> {
+    fn drop(&mut self) {
+        if let Some(sandbox) = &self.ctx.sandbox {
+            sandbox.borrow_mut().retire(&self.allocas)
+        }
+        if let Some(profiler) = &self.ctx.profiler {
+            profiler.borrow_mut().leave_function()
         }
     }
 }
@@ -1,24 +1,41 @@
+mod debugger;
+mod eh;
+mod error;
 mod frame;
+mod profiler;
+mod sandbox;
 
 extern crate libffi;
 extern crate libloading;
 
 use super::generic_value::GenericValue;
-use frame::StackFrame;
+pub use debugger::{Breakpoint, Command, Debugger, Frame, Stop};
+use eh::ExceptionState;
+pub use error::{InterpError, InterpErrorKind};
+use frame::{Caller, StackFrame};
+pub use profiler::{Event, FunctionProfile, Profile, Profiler};
 use rustc_hash::FxHashMap;
-use std::{alloc, ffi, os::raw::c_void, ptr};
+pub use sandbox::{Access, MemoryError};
+use sandbox::{AllocSite, Sandbox};
+use std::{
+    cell::{Ref, RefCell},
+    ffi,
+    os::raw::c_void,
+    ptr, slice,
+};
 use vicis_core::ir::{
     function::{
         basic_block::BasicBlockId,
         instruction::{
-            Alloca, Br, Call, Cast, CondBr, GetElementPtr, ICmp, ICmpCond, InstructionId,
-            IntBinary, Invoke, Load, Opcode, Operand, Phi, Ret, Store,
+            Alloca, Br, Call, Cast, CondBr, ExtractValue, GetElementPtr, ICmp, ICmpCond,
+            InsertValue, InstructionId, IntBinary, Invoke, Load, Opcode, Operand, Phi, Resume, Ret,
+            Store,
         },
         Function, FunctionId,
     },
     module::{linkage::Linkage, name::Name, Module},
     types::{self, Type, Typed, Types},
-    value::{ConstantArray, ConstantStruct, ConstantValue, ValueId},
+    value::{ConstantArray, ConstantExpr, ConstantInt, ConstantStruct, ConstantValue, ValueId},
 };
 
 /// An execution context for interpreters.
@@ -26,6 +43,10 @@ pub struct Context<'a> {
     pub module: &'a Module,
     globals: FxHashMap<Name, GenericValue>,
     libs: Vec<libloading::Library>,
+    eh: RefCell<ExceptionState>,
+    sandbox: Option<RefCell<Sandbox>>,
+    debugger: Option<RefCell<Debugger>>,
+    profiler: Option<RefCell<Profiler>>,
 }
 
 /// A builder for `Context`.
@@ -33,30 +54,84 @@ pub struct ContextBuilder<'a> {
     module: &'a Module,
     globals: FxHashMap<Name, GenericValue>,
     libs: Vec<Result<libloading::Library, libloading::Error>>,
+    /// The memory limit of the sandbox, if enabled.
+    sandbox: Option<usize>,
+    debugger: Option<Debugger>,
+    profiler: Option<Profiler>,
+}
+
+/// How a function exited.
+pub(crate) enum Exit {
+    Return(GenericValue),
+    /// An exception (identified by its object) is propagating to the caller.
+    Unwind(*mut u8),
 }
 
 pub fn run_function(
     ctx: &Context,
     func_id: FunctionId,
     args: Vec<GenericValue>,
-) -> Option<GenericValue> {
+) -> Result<GenericValue, InterpError> {
+    match run_frame(ctx, func_id, args, None)? {
+        Exit::Return(val) => Ok(val),
+        Exit::Unwind(exn) => {
+            let tinfo = ctx.eh.borrow().tinfo_of(exn);
+            let ty = match tinfo.and_then(|tinfo| eh::global_name_of(ctx, tinfo)) {
+                Some(name) => format!("@{}", name.as_string()),
+                None => format!("{:?}", tinfo.unwrap_or(exn)),
+            };
+            Err(InterpError::new(
+                &ctx.module.functions()[func_id],
+                None,
+                InterpErrorKind::UncaughtException(ty),
+            ))
+        }
+    }
+}
+
+pub(crate) fn run_frame(
+    ctx: &Context,
+    func_id: FunctionId,
+    args: Vec<GenericValue>,
+    caller: Caller,
+) -> Result<Exit, InterpError> {
     let func = &ctx.module.functions()[func_id];
 
     if func.is_prototype() {
-        return Some(call_external_func(ctx, func, &args));
+        return call_prototype(ctx, func, &args, caller)
+            .map_err(|kind| InterpError::new(func, None, kind));
     }
 
-    let mut frame = StackFrame::new(ctx, func, args);
-    let mut block = func.layout.first_block?;
+    let mut frame = StackFrame::new(ctx, func_id, args, caller);
+    let mut block = func.layout.first_block.ok_or_else(|| {
+        InterpError::new(
+            func,
+            None,
+            InterpErrorKind::InvalidIr("function has no blocks".into()),
+        )
+    })?;
     let mut last_block = block; // TODO: We need a more elegant way.
 
+    // The value the `landingpad` of the block being unwound to produces.
+    let mut landing = None;
+
     'main: loop {
+        if let Some(profiler) = &ctx.profiler {
+            profiler.borrow_mut().enter_block(block);
+        }
         for (inst_id, inst) in func
             .layout
             .inst_iter(block)
             .into_iter()
             .map(|id| (id, func.data.inst_ref(id)))
         {
+            let err = |kind| InterpError::new(func, Some(inst_id), kind);
+            if let Some(debugger) = &ctx.debugger {
+                debugger.borrow_mut().on_instruction(ctx, &frame, inst_id);
+            }
+            if let Some(profiler) = &ctx.profiler {
+                profiler.borrow_mut().on_instruction(inst_id);
+            }
             match &inst.operand {
                 Operand::Alloca(Alloca {
                     tys,
@@ -92,18 +167,49 @@ pub fn run_function(
                     tys,
                     args,
                 }) => run_gep(&mut frame, inst_id, tys, args),
-                Operand::Call(Call { tys, args, .. }) => run_call(&mut frame, inst_id, tys, args),
+                Operand::ExtractValue(ExtractValue { ty: _, args }) => {
+                    run_extract_value(&mut frame, inst_id, args)
+                }
+                Operand::InsertValue(InsertValue { tys: _, args }) => {
+                    run_insert_value(&mut frame, inst_id, args)
+                }
+                Operand::Call(Call { tys, args, .. }) => {
+                    if let Some(exn) = run_call(&mut frame, inst_id, tys, args)? {
+                        return Ok(Exit::Unwind(exn));
+                    }
+                    Ok(())
+                }
                 Operand::Invoke(Invoke {
                     tys, args, blocks, ..
                 }) => {
-                    run_call(&mut frame, inst_id, tys, args);
-                    // TODO: Add support for exception label.
-                    last_block = block;
-                    block = blocks[0];
+                    if let Some(exn) = run_call(&mut frame, inst_id, tys, args)? {
+                        match landing_selector(&frame, blocks[1], exn).map_err(err)? {
+                            Some(selector) => {
+                                landing = Some(GenericValue::LandingPad(exn, selector));
+                                last_block = block;
+                                block = blocks[1];
+                            }
+                            // No clause of the landing pad applies. Keep unwinding.
+                            None => return Ok(Exit::Unwind(exn)),
+                        }
+                    } else {
+                        last_block = block;
+                        block = blocks[0];
+                    }
                     continue 'main;
                 }
+                Operand::LandingPad(_) => landing
+                    .take()
+                    .map(|val| frame.set_inst_val(inst_id, val))
+                    .ok_or_else(|| {
+                        InterpErrorKind::InvalidIr("landingpad reached without unwinding".into())
+                    }),
+                Operand::Resume(Resume { ty: _, arg }) => match frame.get_val(*arg).map_err(err)? {
+                    GenericValue::LandingPad(exn, _) => return Ok(Exit::Unwind(exn)),
+                    val => Err(InterpErrorKind::TypeMismatch(format!("resume {:?}", val))),
+                },
                 Operand::CondBr(CondBr { arg, blocks }) => {
-                    let arg = frame.get_val(*arg).unwrap();
+                    let arg = frame.get_val(*arg).map_err(err)?;
                     last_block = block;
                     block = blocks[if matches!(arg, GenericValue::Int1(true)) {
                         0
@@ -117,16 +223,22 @@ pub fn run_function(
                     block = *b;
                     continue 'main;
                 }
-                Operand::Ret(Ret { val, .. }) if val.is_none() => return Some(GenericValue::Void),
+                Operand::Ret(Ret { val, .. }) if val.is_none() => {
+                    return Ok(Exit::Return(GenericValue::Void))
+                }
                 Operand::Ret(Ret {
                     ty: _,
                     val: Some(val),
-                }) => {
-                    let val = frame.get_val(*val).unwrap();
-                    return Some(val);
+                }) => return frame.get_val(*val).map(Exit::Return).map_err(err),
+                Operand::Unreachable => {
+                    Err(InterpErrorKind::InvalidIr("reached unreachable".into()))
                 }
-                _ => todo!("{:?}", inst.opcode),
+                _ => Err(InterpErrorKind::UnsupportedInstruction(format!(
+                    "{:?}",
+                    inst.opcode
+                ))),
             }
+            .map_err(err)?;
         }
 
         if let Some(next) = func.layout.next_block_of(block) {
@@ -138,7 +250,43 @@ pub fn run_function(
         break;
     }
 
-    panic!("reached end of function without terminator");
+    Err(InterpError::new(
+        func,
+        None,
+        InterpErrorKind::InvalidIr("reached end of function without terminator".into()),
+    ))
+}
+
+/// Returns the selector the `landingpad` of `block` produces for `exn`, or `None` if the
+/// landing pad neither catches `exn` nor has a cleanup clause.
+fn landing_selector(
+    frame: &StackFrame,
+    block: BasicBlockId,
+    exn: *mut u8,
+) -> Result<Option<i32>, InterpErrorKind> {
+    let func = frame.func;
+    let pad = func
+        .layout
+        .inst_iter(block)
+        .find_map(|id| match &func.data.inst_ref(id).operand {
+            Operand::LandingPad(pad) => Some(pad),
+            _ => None,
+        })
+        .ok_or_else(|| InterpErrorKind::InvalidIr("unwind destination has no landingpad".into()))?;
+    let thrown =
+        frame.ctx.eh.borrow().tinfo_of(exn).ok_or_else(|| {
+            InterpErrorKind::InvalidIr("unwinding with an unknown exception".into())
+        })?;
+    for &(_, clause) in &pad.catches {
+        let clause = frame.get_val(clause)?;
+        let clause = clause
+            .to_ptr()
+            .ok_or_else(|| InterpErrorKind::TypeMismatch(format!("catch clause {:?}", clause)))?;
+        if eh::catches(frame.ctx, thrown, clause) {
+            return Ok(Some(frame.ctx.eh.borrow_mut().type_id_for(clause)));
+        }
+    }
+    Ok(if pad.cleanup { Some(0) } else { None })
 }
 
 // Instructions
@@ -149,22 +297,28 @@ fn run_alloca(
     tys: &[Type],
     num_elements: &ConstantValue,
     align: u32,
-) {
+) -> Result<(), InterpErrorKind> {
     let alloc_ty = tys[0];
+    let num_elements = num_elements.as_int().ok_or_else(|| {
+        InterpErrorKind::UnsupportedConstant(num_elements.to_string(&frame.func.types))
+    })?;
     let alloc_sz = frame
         .ctx
         .module
         .target()
         .datalayout
         .get_size_of(&frame.func.types, alloc_ty)
-        * num_elements.as_int().unwrap().cast_to_usize();
+        * num_elements.cast_to_usize();
     let alloc_align = if align > 0 { align } else { 8 } as usize;
-    let ptr = unsafe {
-        alloc::alloc_zeroed(
-            alloc::Layout::from_size_align(alloc_sz, alloc_align).expect("layout err"),
-        )
-    };
+    let ptr = frame.ctx.allocate(
+        alloc_sz,
+        alloc_align,
+        AllocSite::Stack(frame.func_id, id),
+        false,
+    )?;
+    frame.add_alloca(ptr);
     frame.set_inst_val(id, GenericValue::Ptr(ptr));
+    Ok(())
 }
 
 fn run_phi(
@@ -173,206 +327,422 @@ fn run_phi(
     id: InstructionId,
     args: &[ValueId],
     blocks: &[BasicBlockId],
-) {
+) -> Result<(), InterpErrorKind> {
     let idx = blocks
         .iter()
-        .position(|&block| block == last_block)
-        .unwrap(); // TODO: It may be slow to iterate over blocks.
-    let val = frame.get_val(args[idx]).unwrap();
+        .position(|&block| block == last_block) // TODO: It may be slow to iterate over blocks.
+        .ok_or_else(|| {
+            InterpErrorKind::InvalidIr("no incoming value for the predecessor".into())
+        })?;
+    let val = frame.get_val(args[idx])?;
     frame.set_inst_val(id, val);
+    Ok(())
 }
 
-fn run_store(frame: &mut StackFrame, _tys: &[Type], args: &[ValueId], _align: u32) {
+fn run_store(
+    frame: &mut StackFrame,
+    _tys: &[Type],
+    args: &[ValueId],
+    _align: u32,
+) -> Result<(), InterpErrorKind> {
     let src = args[0];
     let dst = args[1];
-    let dst = frame.get_val(dst).unwrap().to_ptr().unwrap();
-    let src = frame.get_val(src).unwrap();
+    let dst = to_ptr(frame.get_val(dst)?)?;
+    let src = frame.get_val(src)?;
     match src {
-        GenericValue::Int1(i) => unsafe { *(dst as *mut i8) = i as i8 },
-        GenericValue::Int8(i) => unsafe { *(dst as *mut i8) = i },
-        GenericValue::Int32(i) => unsafe { *(dst as *mut i32) = i },
-        GenericValue::Int64(i) => unsafe { *(dst as *mut i64) = i },
-        GenericValue::Ptr(p) => unsafe { *(dst as *mut *mut u8) = p },
-        t => todo!("{:?}", t),
+        GenericValue::Int1(i) => frame.ctx.write(dst, &[i as u8]),
+        GenericValue::Int8(i) => frame.ctx.write(dst, &i.to_ne_bytes()),
+        GenericValue::Int16(i) => frame.ctx.write(dst, &i.to_ne_bytes()),
+        GenericValue::Int32(i) => frame.ctx.write(dst, &i.to_ne_bytes()),
+        GenericValue::Int64(i) => frame.ctx.write(dst, &i.to_ne_bytes()),
+        GenericValue::Ptr(p) => frame.ctx.write(dst, &(p as usize).to_ne_bytes()),
+        t => Err(InterpErrorKind::UnsupportedInstruction(format!(
+            "store of {:?}",
+            t
+        ))),
     }
 }
 
-fn run_load(frame: &mut StackFrame, id: InstructionId, tys: &[Type], addr: ValueId, _align: u32) {
+fn run_load(
+    frame: &mut StackFrame,
+    id: InstructionId,
+    tys: &[Type],
+    addr: ValueId,
+    _align: u32,
+) -> Result<(), InterpErrorKind> {
     let ty = tys[0];
-    let addr = frame.get_val(addr).unwrap().to_ptr().unwrap();
+    let addr = to_ptr(frame.get_val(addr)?)?;
+    fn read<const N: usize>(ctx: &Context, addr: *mut u8) -> Result<[u8; N], InterpErrorKind> {
+        let mut buf = [0; N];
+        ctx.read(addr, &mut buf)?;
+        Ok(buf)
+    }
+    let ctx = frame.ctx;
     let val = match ty {
-        types::I8 => GenericValue::Int8(unsafe { *(addr as *const i8) }),
-        types::I16 => GenericValue::Int16(unsafe { *(addr as *const i16) }),
-        types::I32 => GenericValue::Int32(unsafe { *(addr as *const i32) }),
-        types::I64 => GenericValue::Int64(unsafe { *(addr as *const i64) }),
+        types::I1 => GenericValue::Int1(read::<1>(ctx, addr)?[0] != 0),
+        types::I8 => GenericValue::Int8(i8::from_ne_bytes(read(ctx, addr)?)),
+        types::I16 => GenericValue::Int16(i16::from_ne_bytes(read(ctx, addr)?)),
+        types::I32 => GenericValue::Int32(i32::from_ne_bytes(read(ctx, addr)?)),
+        types::I64 => GenericValue::Int64(i64::from_ne_bytes(read(ctx, addr)?)),
         _ if ty.is_pointer(&frame.func.types) => {
-            GenericValue::Ptr(unsafe { *(addr as *const *mut u8) })
+            GenericValue::Ptr(usize::from_ne_bytes(read(ctx, addr)?) as *mut u8)
+        }
+        ty => {
+            return Err(InterpErrorKind::UnsupportedInstruction(format!(
+                "load of {}",
+                frame.func.types.to_string(ty)
+            )))
         }
-        ty => todo!("Unsupported load type: {}", frame.func.types.to_string(ty)),
     };
     frame.set_inst_val(id, val);
+    Ok(())
 }
 
-fn run_int_binary(frame: &mut StackFrame, id: InstructionId, opcode: Opcode, args: &[ValueId]) {
-    let x = frame.get_val(args[0]).unwrap();
-    let y = frame.get_val(args[1]).unwrap();
-    match opcode {
-        Opcode::Add => frame.set_inst_val(id, add(x, y).unwrap()),
-        Opcode::Sub => frame.set_inst_val(id, sub(x, y).unwrap()),
-        Opcode::Mul => frame.set_inst_val(id, mul(x, y).unwrap()),
-        Opcode::SDiv => frame.set_inst_val(id, sdiv(x, y).unwrap()),
-        Opcode::SRem => frame.set_inst_val(id, srem(x, y).unwrap()),
-        Opcode::Shl => frame.set_inst_val(id, shl(x, y).unwrap()),
-        Opcode::AShr => frame.set_inst_val(id, ashr(x, y).unwrap()),
-        Opcode::And => frame.set_inst_val(id, and(x, y).unwrap()),
-        op => todo!("{:?}", op),
+fn run_int_binary(
+    frame: &mut StackFrame,
+    id: InstructionId,
+    opcode: Opcode,
+    args: &[ValueId],
+) -> Result<(), InterpErrorKind> {
+    let x = frame.get_val(args[0])?;
+    let y = frame.get_val(args[1])?;
+    let val = match opcode {
+        Opcode::Add => add(x, y),
+        Opcode::Sub => sub(x, y),
+        Opcode::Mul => mul(x, y),
+        Opcode::SDiv => sdiv(x, y)?,
+        Opcode::SRem => srem(x, y)?,
+        Opcode::Shl => shl(x, y),
+        Opcode::AShr => ashr(x, y),
+        Opcode::And => and(x, y),
+        op => return Err(InterpErrorKind::UnsupportedInstruction(format!("{:?}", op))),
     };
+    frame.set_inst_val(id, val.ok_or_else(|| mismatch(opcode, x, y))?);
+    Ok(())
 }
 
-fn run_icmp(frame: &mut StackFrame, id: InstructionId, args: &[ValueId], cond: ICmpCond) {
-    let x = frame.get_val(args[0]).unwrap();
-    let y = frame.get_val(args[1]).unwrap();
+fn run_icmp(
+    frame: &mut StackFrame,
+    id: InstructionId,
+    args: &[ValueId],
+    cond: ICmpCond,
+) -> Result<(), InterpErrorKind> {
+    let x = frame.get_val(args[0])?;
+    let y = frame.get_val(args[1])?;
     let res = match cond {
-        ICmpCond::Eq => eq(x, y).unwrap(),
-        ICmpCond::Ne => ne(x, y).unwrap(),
-        ICmpCond::Ugt => ugt(x, y).unwrap(),
-        ICmpCond::Uge => uge(x, y).unwrap(),
-        ICmpCond::Ult => ult(x, y).unwrap(),
-        ICmpCond::Ule => ule(x, y).unwrap(),
-        ICmpCond::Slt => slt(x, y).unwrap(),
-        ICmpCond::Sle => sle(x, y).unwrap(),
-        ICmpCond::Sgt => sgt(x, y).unwrap(),
-        ICmpCond::Sge => sge(x, y).unwrap(),
+        ICmpCond::Eq => eq(x, y),
+        ICmpCond::Ne => ne(x, y),
+        ICmpCond::Ugt => ugt(x, y),
+        ICmpCond::Uge => uge(x, y),
+        ICmpCond::Ult => ult(x, y),
+        ICmpCond::Ule => ule(x, y),
+        ICmpCond::Slt => slt(x, y),
+        ICmpCond::Sle => sle(x, y),
+        ICmpCond::Sgt => sgt(x, y),
+        ICmpCond::Sge => sge(x, y),
     };
-    frame.set_inst_val(id, res);
+    frame.set_inst_val(id, res.ok_or_else(|| mismatch(Opcode::ICmp, x, y))?);
+    Ok(())
 }
 
-fn run_cast(frame: &mut StackFrame, id: InstructionId, opcode: Opcode, tys: &[Type], arg: ValueId) {
+fn run_cast(
+    frame: &mut StackFrame,
+    id: InstructionId,
+    opcode: Opcode,
+    tys: &[Type],
+    arg: ValueId,
+) -> Result<(), InterpErrorKind> {
     let _from = tys[0];
     let to = tys[1];
-    let arg = frame.get_val(arg).unwrap();
+    let arg = frame.get_val(arg)?;
+    let unsupported = || {
+        InterpErrorKind::UnsupportedInstruction(format!(
+            "{:?} to {}",
+            opcode,
+            frame.func.types.to_string(to)
+        ))
+    };
+    let mismatch = || InterpErrorKind::TypeMismatch(format!("{:?} of {:?}", opcode, arg));
     let val = match opcode {
         Opcode::Sext => {
-            let arg = arg.sext_to_i64().unwrap();
+            let arg = arg.sext_to_i64().ok_or_else(mismatch)?;
             match to {
                 types::I32 => GenericValue::Int32(arg as i32),
                 types::I64 => GenericValue::Int64(arg),
-                _ => todo!(),
+                _ => return Err(unsupported()),
             }
         }
         Opcode::Trunc => {
-            let arg = arg.sext_to_i64().unwrap();
+            let arg = arg.sext_to_i64().ok_or_else(mismatch)?;
             match to {
-                types::I1 => GenericValue::Int1(arg != 0),
+                types::I1 => GenericValue::Int1(arg & 1 != 0),
                 types::I8 => GenericValue::Int8(arg as i8),
+                types::I16 => GenericValue::Int16(arg as i16),
                 types::I32 => GenericValue::Int32(arg as i32),
                 types::I64 => GenericValue::Int64(arg),
-                _ => todo!(),
+                _ => return Err(unsupported()),
             }
         }
         Opcode::Bitcast => {
-            assert!(matches!(arg, GenericValue::Ptr(_)));
-            assert!(to.is_pointer(&frame.func.types));
+            if !matches!(arg, GenericValue::Ptr(_)) {
+                return Err(mismatch());
+            }
+            if !to.is_pointer(&frame.func.types) {
+                return Err(unsupported());
+            }
             arg
         }
         Opcode::Zext => {
-            let arg = arg.zext_to_u64().unwrap();
+            let arg = arg.zext_to_u64().ok_or_else(mismatch)?;
             match to {
                 types::I32 => GenericValue::Int32(arg as i32),
                 types::I64 => GenericValue::Int64(arg as i64),
-                _ => todo!(),
+                _ => return Err(unsupported()),
             }
         }
-        t => todo!("cast {:?}", t),
+        _ => return Err(unsupported()),
     };
-    frame.set_inst_val(id, val)
+    frame.set_inst_val(id, val);
+    Ok(())
+}
+
+fn run_gep(
+    frame: &mut StackFrame,
+    id: InstructionId,
+    tys: &[Type],
+    args: &[ValueId],
+) -> Result<(), InterpErrorKind> {
+    let arg = to_ptr(frame.get_val(args[0])?)?;
+    let indices = args[1..]
+        .iter()
+        .map(|&idx| match frame.get_val(idx)? {
+            GenericValue::Int32(idx) => Ok(idx as i64),
+            GenericValue::Int64(idx) => Ok(idx),
+            idx => Err(InterpErrorKind::TypeMismatch(format!(
+                "gep index {:?}",
+                idx
+            ))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let total = gep_offset(frame.ctx.module, tys[0], tys[1], &indices)?;
+    frame.set_inst_val(id, GenericValue::Ptr(arg.wrapping_offset(total)));
+    Ok(())
 }
 
-fn run_gep(frame: &mut StackFrame, id: InstructionId, tys: &[Type], args: &[ValueId]) {
-    let arg = frame.get_val(args[0]).unwrap().to_ptr().unwrap();
+/// Computes the byte offset `getelementptr` adds to a pointer of type `base_ty` pointing to
+/// `src_elem_ty`.
+fn gep_offset(
+    module: &Module,
+    src_elem_ty: Type,
+    base_ty: Type,
+    indices: &[i64],
+) -> Result<isize, InterpErrorKind> {
+    let types = &module.types;
+    let dl = &module.target().datalayout;
     let mut total = 0;
-    let mut cur_ty = tys[1];
-    let dl = &frame.ctx.module.target().datalayout;
-    for &idx in &args[1..] {
-        let idx = match frame.get_val(idx).unwrap() {
-            GenericValue::Int32(idx) => idx as isize,
-            GenericValue::Int64(idx) => idx as isize,
-            _ => panic!(),
-        };
-        if cur_ty.is_struct(&frame.func.types) {
+    let mut cur_ty = base_ty;
+    for (i, &idx) in indices.iter().enumerate() {
+        let idx = idx as isize;
+        if cur_ty.is_struct(types) {
+            let out_of_bounds = || {
+                InterpErrorKind::OutOfBounds(format!(
+                    "element {} of {}",
+                    idx,
+                    types.to_string(cur_ty)
+                ))
+            };
             let sl = dl
-                .new_struct_layout_for(&frame.func.types, cur_ty)
+                .new_struct_layout_for(types, cur_ty)
                 .expect("cur_ty must be struct");
-            let offset = sl.get_elem_offset(idx as usize).unwrap() as isize;
-            let inner = frame
-                .func
-                .types
+            let offset = sl.get_elem_offset(idx as usize).ok_or_else(out_of_bounds)? as isize;
+            let inner = types
                 .base()
                 .element_at(cur_ty, idx as usize)
-                .unwrap();
+                .ok_or_else(out_of_bounds)?;
             total += offset;
             cur_ty = inner;
         } else {
-            let inner = frame.func.types.get_element(cur_ty).unwrap();
-            total += dl.get_size_of(&frame.func.types, inner) as isize * idx;
+            // The first index steps over the source element type. (The base pointer may be
+            // opaque.)
+            let inner = if i == 0 {
+                src_elem_ty
+            } else {
+                types.get_element(cur_ty).ok_or_else(|| {
+                    InterpErrorKind::TypeMismatch(format!("gep into {}", types.to_string(cur_ty)))
+                })?
+            };
+            total += dl.get_size_of(types, inner) as isize * idx;
             cur_ty = inner;
         }
     }
-    frame.set_inst_val(id, GenericValue::Ptr(unsafe { arg.offset(total) }));
+    Ok(total)
+}
+
+fn run_extract_value(
+    frame: &mut StackFrame,
+    id: InstructionId,
+    args: &[ValueId],
+) -> Result<(), InterpErrorKind> {
+    let agg = frame.get_val(args[0])?;
+    let idx = frame.get_val(args[1])?;
+    let val = match (agg, idx, args.len()) {
+        (GenericValue::LandingPad(exn, _), GenericValue::Int32(0), 2) => GenericValue::Ptr(exn),
+        (GenericValue::LandingPad(_, sel), GenericValue::Int32(1), 2) => GenericValue::Int32(sel),
+        _ => {
+            return Err(InterpErrorKind::UnsupportedInstruction(format!(
+                "extractvalue from {:?}",
+                agg
+            )))
+        }
+    };
+    frame.set_inst_val(id, val);
+    Ok(())
 }
 
-fn run_call(frame: &mut StackFrame, id: InstructionId, _tys: &[Type], args: &[ValueId]) {
-    let callee = frame.get_val(args[0]).unwrap();
+fn run_insert_value(
+    frame: &mut StackFrame,
+    id: InstructionId,
+    args: &[ValueId],
+) -> Result<(), InterpErrorKind> {
+    let agg = frame.get_val(args[0])?;
+    let elt = frame.get_val(args[1])?;
+    let idx = frame.get_val(args[2])?;
+    let val = match (agg, elt, idx, args.len()) {
+        (GenericValue::LandingPad(_, sel), GenericValue::Ptr(exn), GenericValue::Int32(0), 3) => {
+            GenericValue::LandingPad(exn, sel)
+        }
+        (GenericValue::LandingPad(exn, _), GenericValue::Int32(sel), GenericValue::Int32(1), 3) => {
+            GenericValue::LandingPad(exn, sel)
+        }
+        _ => {
+            return Err(InterpErrorKind::UnsupportedInstruction(format!(
+                "insertvalue into {:?}",
+                agg
+            )))
+        }
+    };
+    frame.set_inst_val(id, val);
+    Ok(())
+}
+
+/// Returns the exception being thrown if the callee unwinds.
+fn run_call(
+    frame: &mut StackFrame,
+    id: InstructionId,
+    _tys: &[Type],
+    args: &[ValueId],
+) -> Result<Option<*mut u8>, InterpError> {
+    let err = |kind| InterpError::new(frame.func, Some(id), kind);
+    let callee = frame.get_val(args[0]).map_err(err)?;
     let args: Vec<GenericValue> = args[1..]
         .iter()
-        .map(|&a| frame.get_val(a).unwrap())
-        .collect();
-    let func_id = callee.to_id::<FunctionId>().unwrap();
-    if let Some(ret) = run_function(frame.ctx, *func_id, args) {
-        match ret {
-            GenericValue::Void => {}
-            v => frame.set_inst_val(id, v),
+        .map(|&a| frame.get_val(a))
+        .collect::<Result<_, _>>()
+        .map_err(err)?;
+    let func_id = *callee.to_id::<FunctionId>().ok_or_else(|| {
+        err(InterpErrorKind::TypeMismatch(format!(
+            "callee {:?} is not a function",
+            callee
+        )))
+    })?;
+    let callee = &frame.ctx.module.functions()[func_id];
+    let ret = if callee.is_prototype() {
+        if let Some(profiler) = &frame.ctx.profiler {
+            profiler.borrow_mut().external_call(func_id);
         }
+        // Report errors in external calls at the call site.
+        call_prototype(frame.ctx, callee, &args, Some((frame, id))).map_err(err)?
+    } else {
+        run_frame(frame.ctx, func_id, args, Some((frame, id)))?
+    };
+    match ret {
+        Exit::Return(GenericValue::Void) => {}
+        Exit::Return(v) => frame.set_inst_val(id, v),
+        Exit::Unwind(exn) => return Ok(Some(exn)),
+    }
+    Ok(None)
+}
+
+fn to_ptr(val: GenericValue) -> Result<*mut u8, InterpErrorKind> {
+    match val {
+        GenericValue::Ptr(p) if p.is_null() => Err(InterpErrorKind::OutOfBounds(
+            "null pointer dereference".into(),
+        )),
+        GenericValue::Ptr(p) => Ok(p),
+        v => Err(InterpErrorKind::TypeMismatch(format!(
+            "expected a pointer, found {:?}",
+            v
+        ))),
     }
 }
 
+fn mismatch(opcode: Opcode, x: GenericValue, y: GenericValue) -> InterpErrorKind {
+    InterpErrorKind::TypeMismatch(format!("{:?} of {:?} and {:?}", opcode, x, y))
+}
+
 // Utils
 
 fn add(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
     match (x, y) {
-        (GenericValue::Int8(x), GenericValue::Int8(y)) => Some(GenericValue::Int8(x + y)),
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int32(x + y)),
-        (GenericValue::Int64(x), GenericValue::Int64(y)) => Some(GenericValue::Int64(x + y)),
+        (GenericValue::Int8(x), GenericValue::Int8(y)) => {
+            Some(GenericValue::Int8(x.wrapping_add(y)))
+        }
+        (GenericValue::Int32(x), GenericValue::Int32(y)) => {
+            Some(GenericValue::Int32(x.wrapping_add(y)))
+        }
+        (GenericValue::Int64(x), GenericValue::Int64(y)) => {
+            Some(GenericValue::Int64(x.wrapping_add(y)))
+        }
         _ => None,
     }
 }
 
 fn sub(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
     match (x, y) {
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int32(x - y)),
+        (GenericValue::Int32(x), GenericValue::Int32(y)) => {
+            Some(GenericValue::Int32(x.wrapping_sub(y)))
+        }
         _ => None,
     }
 }
 
 fn mul(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
     match (x, y) {
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int32(x * y)),
-        (GenericValue::Int64(x), GenericValue::Int64(y)) => Some(GenericValue::Int64(x * y)),
+        (GenericValue::Int32(x), GenericValue::Int32(y)) => {
+            Some(GenericValue::Int32(x.wrapping_mul(y)))
+        }
+        (GenericValue::Int64(x), GenericValue::Int64(y)) => {
+            Some(GenericValue::Int64(x.wrapping_mul(y)))
+        }
         _ => None,
     }
 }
 
-fn sdiv(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
+fn sdiv(x: GenericValue, y: GenericValue) -> Result<Option<GenericValue>, InterpErrorKind> {
     match (x, y) {
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int32(x / y)),
-        _ => None,
+        (GenericValue::Int32(_), GenericValue::Int32(0))
+        | (GenericValue::Int64(_), GenericValue::Int64(0)) => Err(InterpErrorKind::DivisionByZero),
+        (GenericValue::Int32(x), GenericValue::Int32(y)) => {
+            Ok(Some(GenericValue::Int32(x.wrapping_div(y))))
+        }
+        (GenericValue::Int64(x), GenericValue::Int64(y)) => {
+            Ok(Some(GenericValue::Int64(x.wrapping_div(y))))
+        }
+        _ => Ok(None),
     }
 }
 
-fn srem(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
+fn srem(x: GenericValue, y: GenericValue) -> Result<Option<GenericValue>, InterpErrorKind> {
     match (x, y) {
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int32(x % y)),
-        _ => None,
+        (GenericValue::Int32(_), GenericValue::Int32(0))
+        | (GenericValue::Int64(_), GenericValue::Int64(0)) => Err(InterpErrorKind::DivisionByZero),
+        (GenericValue::Int32(x), GenericValue::Int32(y)) => {
+            Ok(Some(GenericValue::Int32(x.wrapping_rem(y))))
+        }
+        (GenericValue::Int64(x), GenericValue::Int64(y)) => {
+            Ok(Some(GenericValue::Int64(x.wrapping_rem(y))))
+        }
+        _ => Ok(None),
     }
 }
 
@@ -390,8 +760,12 @@ fn shl(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
 
 fn ashr(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
     match (x, y) {
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int32(x >> y)),
-        (GenericValue::Int64(x), GenericValue::Int64(y)) => Some(GenericValue::Int64(x >> y)),
+        (GenericValue::Int32(x), GenericValue::Int32(y)) => {
+            Some(GenericValue::Int32(x.wrapping_shr(y as u32)))
+        }
+        (GenericValue::Int64(x), GenericValue::Int64(y)) => {
+            Some(GenericValue::Int64(x.wrapping_shr(y as u32)))
+        }
         _ => None,
     }
 }
@@ -485,9 +859,36 @@ impl<'a> ContextBuilder<'a> {
             module,
             globals: FxHashMap::default(),
             libs: vec![],
+            sandbox: None,
+            debugger: None,
+            profiler: None,
         }
     }
 
+    /// Runs the module in a simulated address space that checks every memory access.
+    pub fn with_sandbox(self) -> Self {
+        self.with_sandbox_limit(sandbox::DEFAULT_LIMIT)
+    }
+
+    /// Runs the module in a sandbox like `with_sandbox`, where live allocations may take up at
+    /// most `limit` bytes. Heap allocations beyond it return null, and others fail with
+    /// `MemoryError::OutOfMemory`.
+    pub fn with_sandbox_limit(mut self, limit: usize) -> Self {
+        self.sandbox = Some(limit);
+        self
+    }
+
+    pub fn with_debugger(mut self, debugger: Debugger) -> Self {
+        self.debugger = Some(debugger);
+        self
+    }
+
+    /// Collects a `Profile` of the execution, available from `Context::profile`.
+    pub fn with_profiler(mut self, profiler: Profiler) -> Self {
+        self.profiler = Some(profiler);
+        self
+    }
+
     pub fn with_lib<T: AsRef<ffi::OsStr>>(mut self, lib: T) -> Self {
         self.libs.push(unsafe { libloading::Library::new(lib) });
         self
@@ -500,16 +901,25 @@ impl<'a> ContextBuilder<'a> {
         self
     }
 
-    pub fn build(self) -> Result<Context<'a>, libloading::Error> {
+    pub fn build(self) -> Result<Context<'a>, InterpError> {
         let mut ctx = Context {
             module: self.module,
             globals: self.globals,
-            libs: self.libs.into_iter().collect::<Result<_, _>>()?,
+            libs: self
+                .libs
+                .into_iter()
+                .collect::<Result<_, _>>()
+                .map_err(|e| InterpError::global(InterpErrorKind::Library(e.to_string())))?,
+            eh: RefCell::new(ExceptionState::default()),
+            sandbox: self.sandbox.map(|limit| RefCell::new(Sandbox::new(limit))),
+            debugger: self.debugger.map(RefCell::new),
+            profiler: self.profiler.map(RefCell::new),
         };
 
-        let mut ctor = None;
+        let mut ctors = vec![];
         let dl = &ctx.module.target().datalayout;
 
+        // Place all the global variables first so that initializers can refer to any of them.
         for (name, gv) in ctx.module.global_variables() {
             let sz = dl.get_size_of(&ctx.module.types, gv.ty);
             let align = if gv.align > 0 { gv.align } else { 8 } as usize;
@@ -519,84 +929,234 @@ impl<'a> ContextBuilder<'a> {
                 Some(Linkage::External) | Some(Linkage::ExternalWeak)
             ) && !special
             {
-                let p = *ctx
-                    .lookup::<*mut u8>(name.as_string().as_str())
-                    .expect("external not found");
-                ctx.globals.insert(name.clone(), GenericValue::Ptr(p));
-                continue;
-            }
-            let ptr = unsafe {
-                alloc::alloc_zeroed(alloc::Layout::from_size_align(sz, align).expect("layout err"))
-            };
-            if let Some(init) = &gv.init {
-                match init {
-                    // Handle 'llvm.global_ctors'
-                    ConstantValue::Array(ConstantArray {
-                        is_string: false,
-                        elems,
-                        ..
-                    }) if matches!(gv.name, Name::Name(ref name) if name == "llvm.global_ctors") => {
-                        {
-                            assert!(gv.ty.is_array(&ctx.module.types));
-                            let strukt = ctx.module.types.get_element(gv.ty).unwrap();
-                            let t0 = ctx.module.types.base().element_at(strukt, 0).unwrap();
-                            let t1 = ctx.module.types.base().element_at(strukt, 1).unwrap();
-                            let t2 = ctx.module.types.base().element_at(strukt, 2).unwrap();
-                            assert!(t0.is_i32()); // i32
-                            assert!(t1.is_pointer(&ctx.module.types)); // void ()*
-                            assert!(t2.is_pointer(&ctx.module.types)); // i8*
-                            assert!(elems.len() == 1);
-                        }
-                        if let ConstantValue::Struct(ConstantStruct { elems, .. }) = &elems[0] {
-                            if let ConstantValue::GlobalRef(name, _) = &elems[1] {
-                                ctor = ctx.module.find_function_by_name(name.as_string());
-                            }
-                        } else {
-                            todo!()
-                        }
+                let p = ctx.lookup::<*mut u8>(name.as_string().as_str()).map(|p| *p);
+                // Typeinfos of the C++ runtime only need a unique address unless a library
+                // provides them.
+                let is_rtti = ["_ZTI", "_ZTV", "_ZTS"]
+                    .iter()
+                    .any(|prefix| name.as_string().starts_with(prefix));
+                match p {
+                    Some(p) if ctx.sandbox.is_none() => {
+                        ctx.globals.insert(name.clone(), GenericValue::Ptr(p));
+                        continue;
+                    }
+                    // Take a snapshot of the variable into the sandbox. Writes from either side
+                    // are not visible to the other.
+                    Some(p) => {
+                        let ptr = ctx
+                            .allocate(sz, align, AllocSite::Global(name.clone()), true)
+                            .and_then(|ptr| {
+                                ctx.write(ptr, unsafe { slice::from_raw_parts(p, sz) })?;
+                                Ok(ptr)
+                            })
+                            .map_err(InterpError::global)?;
+                        ctx.globals.insert(name.clone(), GenericValue::Ptr(ptr));
+                        continue;
+                    }
+                    None if is_rtti => {}
+                    None => {
+                        return Err(InterpError::global(InterpErrorKind::UnresolvedSymbol(
+                            format!("{:?}", name),
+                        )))
                     }
-                    _ => init_memory(&ctx, init, ptr as *mut i8),
                 }
             }
+            let ptr = ctx
+                .allocate(sz, align, AllocSite::Global(name.clone()), true)
+                .map_err(InterpError::global)?;
             ctx.globals.insert(name.clone(), GenericValue::Ptr(ptr));
         }
 
-        if let Some(ctor) = ctor {
-            run_function(&ctx, ctor, vec![]);
+        for (name, gv) in ctx.module.global_variables() {
+            let init = match &gv.init {
+                Some(init) => init,
+                None => continue,
+            };
+            match init {
+                // Handle 'llvm.global_ctors'
+                ConstantValue::Array(ConstantArray {
+                    is_string: false,
+                    elems,
+                    ..
+                }) if matches!(gv.name, Name::Name(ref name) if name == "llvm.global_ctors") => {
+                    ctors = global_ctors(&ctx, gv.ty, elems).map_err(InterpError::global)?;
+                }
+                _ => {
+                    let ptr = ctx.globals[name].to_ptr().unwrap();
+                    init_memory(&ctx, init, ptr).map_err(InterpError::global)?
+                }
+            }
+        }
+
+        for ctor in ctors {
+            run_function(&ctx, ctor, vec![])?;
         }
 
         Ok(ctx)
     }
 }
 
-fn init_memory(ctx: &Context, val: &ConstantValue, ptr: *mut i8) {
+/// Returns the constructors listed in `llvm.global_ctors` of type `ty`, in the order they must run:
+/// by priority, and in the order of `elems` for the same priority.
+fn global_ctors(
+    ctx: &Context,
+    ty: Type,
+    elems: &[ConstantValue],
+) -> Result<Vec<FunctionId>, InterpErrorKind> {
+    let types = &ctx.module.types;
+    let malformed = || {
+        InterpErrorKind::InvalidIr(format!(
+            "malformed llvm.global_ctors of type {}",
+            types.to_string(ty)
+        ))
+    };
+    if !ty.is_array(types) {
+        return Err(malformed());
+    }
+    // { i32, void ()*, i8* }
+    let strukt = types.get_element(ty).ok_or_else(malformed)?;
+    let field = |i| types.base().element_at(strukt, i);
+    let is_ptr = |t: Option<Type>| t.is_some_and(|t| t.is_pointer(types));
+    if !(field(0).is_some_and(|t| t.is_i32()) && is_ptr(field(1)) && is_ptr(field(2))) {
+        return Err(malformed());
+    }
+
+    let mut ctors = vec![];
+    for elem in elems {
+        let unsupported = || InterpErrorKind::UnsupportedConstant(elem.to_string(types));
+        let fields = match elem {
+            ConstantValue::Struct(ConstantStruct { elems, .. }) => elems,
+            _ => return Err(unsupported()),
+        };
+        let priority = match fields.first() {
+            Some(ConstantValue::Int(ConstantInt::Int32(priority))) => *priority,
+            _ => return Err(unsupported()),
+        };
+        let name = match fields.get(1) {
+            Some(ConstantValue::GlobalRef(name, _)) => name,
+            _ => return Err(unsupported()),
+        };
+        let ctor = ctx
+            .module
+            .find_function_by_name(name.as_string())
+            .ok_or_else(|| InterpErrorKind::UnresolvedSymbol(format!("{:?}", name)))?;
+        ctors.push((priority, ctor));
+    }
+    // A stable sort keeps the order of constructors with the same priority.
+    ctors.sort_by_key(|&(priority, _)| priority);
+    Ok(ctors.into_iter().map(|(_, ctor)| ctor).collect())
+}
+
+fn init_memory(ctx: &Context, val: &ConstantValue, ptr: *mut u8) -> Result<(), InterpErrorKind> {
     let dl = &ctx.module.target().datalayout;
     match val {
         ConstantValue::Array(ConstantArray { elems, elem_ty, .. }) => {
             let sz = dl.get_size_of(&ctx.module.types, *elem_ty);
             for (i, e) in elems.iter().enumerate() {
-                init_memory(ctx, e, unsafe { ptr.add(sz * i) as *mut i8 });
+                init_memory(ctx, e, ptr.wrapping_add(sz * i))?;
             }
         }
         ConstantValue::Struct(ConstantStruct { ty, elems, .. }) => {
             let layout = dl.new_struct_layout_for(&ctx.module.types, *ty).unwrap();
             for (i, e) in elems.iter().enumerate() {
                 let offset = layout.get_elem_offset(i).unwrap();
-                init_memory(ctx, e, unsafe { ptr.add(offset) as *mut i8 });
+                init_memory(ctx, e, ptr.wrapping_add(offset))?;
             }
         }
         ConstantValue::Undef(_) | ConstantValue::Null(_) | ConstantValue::AggregateZero(_) => {
             // Already zeroed.
             // unsafe { ptr::write_bytes(ptr, 0, sz) };
         }
-        ConstantValue::Int(i) => unsafe {
-            ptr::copy_nonoverlapping(i.as_ptr(), ptr, dl.get_size_of(&ctx.module.types, i.ty()))
+        ConstantValue::Int(i) => {
+            let sz = dl.get_size_of(&ctx.module.types, i.ty());
+            ctx.write(ptr, unsafe {
+                slice::from_raw_parts(i.as_ptr() as *const u8, sz)
+            })?
+        }
+        ConstantValue::Float(f) => {
+            let sz = dl.get_size_of(&ctx.module.types, f.ty());
+            ctx.write(ptr, unsafe {
+                slice::from_raw_parts(f.as_ptr() as *const u8, sz)
+            })?
+        }
+        ConstantValue::GlobalRef(_, _) | ConstantValue::Expr(_) => match ctx.eval_const(val)? {
+            GenericValue::Ptr(p) => ctx.write(ptr, &(p as usize).to_ne_bytes())?,
+            // Interpreted functions have no address. Leave them null.
+            GenericValue::Id(_) => {}
+            _ => {
+                return Err(InterpErrorKind::UnsupportedConstant(
+                    val.to_string(&ctx.module.types),
+                ))
+            }
         },
-        ConstantValue::GlobalRef(_, _) | ConstantValue::Expr(_) => todo!(),
     }
+    Ok(())
 }
 
 impl<'a> Context<'a> {
+    /// Returns the profile collected so far, if a `Profiler` is attached.
+    pub fn profile(&self) -> Option<Ref<'_, Profile>> {
+        self.profiler
+            .as_ref()
+            .map(|p| Ref::map(p.borrow(), Profiler::profile))
+    }
+
+    /// Evaluates a constant appearing in the module.
+    pub fn eval_const(&self, konst: &ConstantValue) -> Result<GenericValue, InterpErrorKind> {
+        let unsupported =
+            || InterpErrorKind::UnsupportedConstant(konst.to_string(&self.module.types));
+        let types = &self.module.types;
+        match konst {
+            ConstantValue::Null(ty) if ty.is_pointer(types) => {
+                Ok(GenericValue::Ptr(ptr::null_mut()))
+            }
+            ConstantValue::Int(ConstantInt::Int1(i)) => Ok(GenericValue::Int1(*i)),
+            ConstantValue::Int(ConstantInt::Int8(i)) => Ok(GenericValue::Int8(*i)),
+            ConstantValue::Int(ConstantInt::Int16(i)) => Ok(GenericValue::Int16(*i)),
+            ConstantValue::Int(ConstantInt::Int32(i)) => Ok(GenericValue::Int32(*i)),
+            ConstantValue::Int(ConstantInt::Int64(i)) => Ok(GenericValue::Int64(*i)),
+            ConstantValue::Undef(ty) => match *ty {
+                types::I1 => Ok(GenericValue::Int1(false)),
+                types::I8 => Ok(GenericValue::Int8(0)),
+                types::I16 => Ok(GenericValue::Int16(0)),
+                types::I32 => Ok(GenericValue::Int32(0)),
+                types::I64 => Ok(GenericValue::Int64(0)),
+                ty if ty.is_pointer(types) => Ok(GenericValue::Ptr(ptr::null_mut())),
+                ty if is_landingpad_ty(types, ty) => {
+                    Ok(GenericValue::LandingPad(ptr::null_mut(), 0))
+                }
+                _ => Err(unsupported()),
+            },
+            ConstantValue::GlobalRef(name, _) => {
+                if let Some(f) = name
+                    .to_string()
+                    .and_then(|name| self.module.find_function_by_name(name))
+                {
+                    return Ok(GenericValue::id(f));
+                }
+                self.globals
+                    .get(name)
+                    .copied()
+                    .ok_or_else(|| InterpErrorKind::UnresolvedSymbol(format!("{:?}", name)))
+            }
+            ConstantValue::Expr(ConstantExpr::GetElementPtr { args, tys, .. }) => {
+                let base = match self.eval_const(&args[0])? {
+                    GenericValue::Ptr(p) => p,
+                    _ => return Err(unsupported()),
+                };
+                let indices = args[1..]
+                    .iter()
+                    .map(|idx| idx.as_int().map(|i| i.cast_to_i64()))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(unsupported)?;
+                let offset = gep_offset(self.module, tys[0], tys[1], &indices)?;
+                Ok(GenericValue::Ptr(base.wrapping_offset(offset)))
+            }
+            ConstantValue::Expr(ConstantExpr::Bitcast { arg, .. }) => self.eval_const(arg),
+            _ => Err(unsupported()),
+        }
+    }
+
     fn lookup<T>(&self, name: &str) -> Option<libloading::Symbol<T>> {
         self.libs
             .iter()
@@ -604,17 +1164,51 @@ impl<'a> Context<'a> {
     }
 }
 
-fn ffitype(ty: Type, types: &Types) -> libffi::low::ffi_type {
+/// Returns `true` if `ty` is `{ i8*, i32 }` (or `{ ptr, i32 }`), the type of `landingpad`s.
+fn is_landingpad_ty(types: &Types, ty: Type) -> bool {
+    let base = types.base();
+    ty.is_struct(types)
+        && matches!(base.element_at(ty, 0), Some(t) if t.is_pointer(types))
+        && base.element_at(ty, 1) == Some(types::I32)
+        && base.element_at(ty, 2).is_none()
+}
+
+fn ffitype(ty: Type, types: &Types) -> Result<libffi::low::ffi_type, InterpErrorKind> {
     match ty {
-        types::VOID => unsafe { libffi::low::types::void },
-        types::I32 => unsafe { libffi::low::types::sint32 },
-        types::I64 => unsafe { libffi::low::types::sint64 },
-        ty if ty.is_pointer(types) => unsafe { libffi::low::types::pointer },
-        e => panic!("{:?}", e),
+        types::VOID => Ok(unsafe { libffi::low::types::void }),
+        types::I32 => Ok(unsafe { libffi::low::types::sint32 }),
+        types::I64 => Ok(unsafe { libffi::low::types::sint64 }),
+        ty if ty.is_pointer(types) => Ok(unsafe { libffi::low::types::pointer }),
+        ty => Err(unsupported_external_type(ty, types)),
+    }
+}
+
+fn unsupported_external_type(ty: Type, types: &Types) -> InterpErrorKind {
+    InterpErrorKind::UnsupportedInstruction(format!("external call with {}", types.to_string(ty)))
+}
+
+/// Calls a function declared but not defined in the module.
+/// `caller` is the frame calling `func`, in which functions called back by `func` run.
+fn call_prototype(
+    ctx: &Context,
+    func: &Function,
+    args: &[GenericValue],
+    caller: Caller,
+) -> Result<Exit, InterpErrorKind> {
+    if let Some(ret) = sandbox::call(ctx, func.name(), args) {
+        return ret.map(Exit::Return);
+    }
+    if let Some(exit) = eh::call(ctx, func.name(), args, caller) {
+        return exit;
     }
+    call_external_func(ctx, func, args).map(Exit::Return)
 }
 
-fn call_external_func(ctx: &Context, func: &Function, args: &[GenericValue]) -> GenericValue {
+fn call_external_func(
+    ctx: &Context,
+    func: &Function,
+    args: &[GenericValue],
+) -> Result<GenericValue, InterpErrorKind> {
     if let Some(ret) = call_intrinsic_func(ctx, func, args) {
         return ret;
     }
@@ -646,6 +1240,7 @@ fn call_external_func(ctx: &Context, func: &Function, args: &[GenericValue]) ->
                 new_args.push(i as *mut _ as *mut c_void)
             }
             GenericValue::Ptr(ref mut p) => {
+                *p = ctx.host_addr(*p)?;
                 args_ty.push(unsafe { &mut libffi::low::types::pointer as *mut _ });
                 new_args.push(&mut *p as *mut _ as *mut c_void);
             }
@@ -661,18 +1256,26 @@ fn call_external_func(ctx: &Context, func: &Function, args: &[GenericValue]) ->
                     new_args.push(&mut *tmps.last_mut().unwrap() as *mut _ as *mut c_void);
                     continue;
                 }
-                todo!();
-                // args_ty.push(unsafe { &mut libffi::low::types::pointer as *mut _ });
-                // new_args.push(&mut 0 as *mut _ as *mut c_void);
+                return Err(InterpErrorKind::TypeMismatch(format!(
+                    "cannot pass {:?} to external function",
+                    arg
+                )));
+            }
+            e => {
+                return Err(InterpErrorKind::UnsupportedInstruction(format!(
+                    "external call with {:?}",
+                    e
+                )))
             }
-            e => todo!("{:?}", e),
         }
     }
 
-    let mut ret_ty = ffitype(func.result_ty, &func.types);
+    let mut ret_ty = ffitype(func.result_ty, &func.types)?;
     let mut cif: libffi::low::ffi_cif = Default::default();
     let prms_len = func.params.len();
-    let func1 = ctx.lookup::<unsafe extern "C" fn()>(func.name()).unwrap();
+    let func1 = ctx
+        .lookup::<unsafe extern "C" fn()>(func.name())
+        .ok_or_else(|| InterpErrorKind::UnresolvedSymbol(func.name().clone()))?;
     let func1 = libffi::low::CodePtr(unsafe { func1.into_raw() }.into_raw());
 
     unsafe {
@@ -685,7 +1288,7 @@ fn call_external_func(ctx: &Context, func: &Function, args: &[GenericValue]) ->
             args_ty.as_mut_ptr(),
         )
     }
-    .unwrap();
+    .map_err(|e| InterpErrorKind::InvalidIr(format!("{:?}", e)))?;
 
     let ret = match func.result_ty {
         types::VOID => {
@@ -702,55 +1305,68 @@ fn call_external_func(ctx: &Context, func: &Function, args: &[GenericValue]) ->
         }
         ty if ty.is_pointer(&func.types) => {
             let r: *mut u8 = unsafe { libffi::low::call(&mut cif, func1, new_args.as_mut_ptr()) };
-            GenericValue::Ptr(r)
+            GenericValue::Ptr(ctx.sandbox_addr(r))
         }
-        _ => panic!(),
+        ty => return Err(unsupported_external_type(ty, &func.types)),
     };
 
     #[cfg(debug_assertions)]
     log::debug!("external exit: {}", func.name);
 
-    ret
+    Ok(ret)
 }
 
 fn call_intrinsic_func(
-    _ctx: &Context,
+    ctx: &Context,
     func: &Function,
     args: &[GenericValue],
-) -> Option<GenericValue> {
-    fn llvm_memcpy_p0i8_p0i8_i64(args: &[GenericValue]) -> GenericValue {
-        let dst = args[0].to_ptr().unwrap();
-        let src = args[1].to_ptr().unwrap();
-        let len = args[2].to_i64().unwrap();
-        let _is_volatile = args[3].to_i1().unwrap();
-        unsafe { ptr::copy_nonoverlapping(src, dst, len as usize) }
-        GenericValue::Void
-    }
-
-    fn llvm_memset_p0i8_i64(args: &[GenericValue]) -> GenericValue {
-        let dst = args[0].to_ptr().unwrap();
-        let val = args[1].to_i8().unwrap();
-        let len = args[2].to_i64().unwrap();
-        let _is_volatile = args[3].to_i1().unwrap();
-        unsafe { ptr::write_bytes(dst, val as u8, len as usize) }
-        GenericValue::Void
-    }
-
-    let funcs: FxHashMap<&'static str, fn(&[GenericValue]) -> GenericValue> = vec![
+) -> Option<Result<GenericValue, InterpErrorKind>> {
+    /// Returns `None` if the arguments do not match the signature.
+    type Intrinsic = fn(&Context, &[GenericValue]) -> Option<Result<GenericValue, InterpErrorKind>>;
+
+    fn llvm_memcpy_p0i8_p0i8_i64(
+        ctx: &Context,
+        args: &[GenericValue],
+    ) -> Option<Result<GenericValue, InterpErrorKind>> {
+        let dst = args.first()?.to_ptr()?;
+        let src = args.get(1)?.to_ptr()?;
+        let len = args.get(2)?.to_i64()?;
+        let _is_volatile = args.get(3)?.to_i1()?;
+        Some(ctx.copy(dst, src, len as usize).map(|_| GenericValue::Void))
+    }
+
+    fn llvm_memset_p0i8_i64(
+        ctx: &Context,
+        args: &[GenericValue],
+    ) -> Option<Result<GenericValue, InterpErrorKind>> {
+        let dst = args.first()?.to_ptr()?;
+        let val = args.get(1)?.to_i8()?;
+        let len = args.get(2)?.to_i64()?;
+        let _is_volatile = args.get(3)?.to_i1()?;
+        Some(
+            ctx.fill(dst, val as u8, len as usize)
+                .map(|_| GenericValue::Void),
+        )
+    }
+
+    let funcs: FxHashMap<&'static str, Intrinsic> = vec![
         (
             "llvm.memcpy.p0i8.p0i8.i64",
-            llvm_memcpy_p0i8_p0i8_i64 as fn(&[GenericValue]) -> GenericValue,
+            llvm_memcpy_p0i8_p0i8_i64 as Intrinsic,
         ),
         ("llvm.memset.p0i8.i64", llvm_memset_p0i8_i64),
     ]
     .into_iter()
     .collect();
 
-    if let Some(func) = funcs.get(func.name().as_str()) {
-        return Some(func(args));
-    }
-
-    None
+    let intrinsic = funcs.get(func.name().as_str())?;
+    Some(intrinsic(ctx, args).unwrap_or_else(|| {
+        Err(InterpErrorKind::TypeMismatch(format!(
+            "arguments of {}: {:?}",
+            func.name(),
+            args
+        )))
+    }))
 }
 
 extern "C" fn dummy_func() {
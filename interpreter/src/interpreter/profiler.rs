@@ -0,0 +1,334 @@
+//! Execution profiling for interpreted code.
+//!
+//! A `Profiler` attached to a `Context` counts how many times each instruction, block and edge
+//! between blocks is executed, how often each function is called and how many instructions it
+//! executes, and optionally keeps a trace of the execution. The resulting `Profile` can be
+//! attached to a module as `!prof` branch weights for passes to consume.
+
+use rustc_hash::FxHashMap;
+use std::{collections::VecDeque, fmt::Write};
+use vicis_core::ir::{
+    function::{
+        basic_block::BasicBlockId,
+        instruction::{CondBr, InstructionId, Invoke, Operand, Switch},
+        Function, FunctionId,
+    },
+    module::{metadata::Metadata, Module},
+    value::{ConstantInt, ConstantValue},
+};
+
+pub struct Profiler {
+    profile: Profile,
+    /// The number of events the trace keeps. Zero disables tracing.
+    trace_limit: usize,
+    /// Activations of interpreted functions, innermost last.
+    stack: Vec<Activation>,
+    /// The number of instructions executed so far.
+    executed: u64,
+}
+
+struct Activation {
+    func_id: FunctionId,
+    /// The block being executed.
+    block: Option<BasicBlockId>,
+    /// `executed` when the function was entered.
+    start: u64,
+}
+
+/// Execution counts collected by a `Profiler`.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub instructions: FxHashMap<(FunctionId, InstructionId), u64>,
+    pub blocks: FxHashMap<(FunctionId, BasicBlockId), u64>,
+    /// How many times control flowed from the first block to the second.
+    pub edges: FxHashMap<(FunctionId, BasicBlockId, BasicBlockId), u64>,
+    pub functions: FxHashMap<FunctionId, FunctionProfile>,
+    /// The most recent events, oldest first. Empty unless tracing is enabled.
+    pub trace: VecDeque<Event>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FunctionProfile {
+    pub calls: u64,
+    /// Instructions executed in the function itself.
+    pub instructions: u64,
+    /// Instructions executed in the function and the functions it called. An activation nested
+    /// in another activation of the same function is not counted twice.
+    pub inclusive_instructions: u64,
+}
+
+/// An entry of the execution trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Enter(FunctionId),
+    Instruction(FunctionId, InstructionId),
+    /// The function returned or unwound.
+    Leave(FunctionId),
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            profile: Profile::default(),
+            trace_limit: 0,
+            stack: vec![],
+            executed: 0,
+        }
+    }
+
+    /// Keeps a trace of the last `limit` events.
+    pub fn with_trace(mut self, limit: usize) -> Self {
+        self.trace_limit = limit;
+        self
+    }
+
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// Called when an interpreted function is entered.
+    pub(super) fn enter_function(&mut self, func_id: FunctionId) {
+        self.profile.functions.entry(func_id).or_default().calls += 1;
+        self.stack.push(Activation {
+            func_id,
+            block: None,
+            start: self.executed,
+        });
+        self.record(Event::Enter(func_id));
+    }
+
+    /// Called when the function entered last returns or unwinds.
+    pub(super) fn leave_function(&mut self) {
+        let act = match self.stack.pop() {
+            Some(act) => act,
+            None => return,
+        };
+        if !self.stack.iter().any(|a| a.func_id == act.func_id) {
+            self.profile
+                .functions
+                .entry(act.func_id)
+                .or_default()
+                .inclusive_instructions += self.executed - act.start;
+        }
+        self.record(Event::Leave(act.func_id));
+    }
+
+    /// Called when an external function is called.
+    pub(super) fn external_call(&mut self, func_id: FunctionId) {
+        self.profile.functions.entry(func_id).or_default().calls += 1;
+        self.record(Event::Enter(func_id));
+        self.record(Event::Leave(func_id));
+    }
+
+    /// Called when the current function starts executing `block`.
+    pub(super) fn enter_block(&mut self, block: BasicBlockId) {
+        let act = match self.stack.last_mut() {
+            Some(act) => act,
+            None => return,
+        };
+        let func_id = act.func_id;
+        if let Some(from) = act.block.replace(block) {
+            *self
+                .profile
+                .edges
+                .entry((func_id, from, block))
+                .or_default() += 1;
+        }
+        *self.profile.blocks.entry((func_id, block)).or_default() += 1;
+    }
+
+    /// Called before `inst` of the current function is executed.
+    pub(super) fn on_instruction(&mut self, inst: InstructionId) {
+        let func_id = match self.stack.last() {
+            Some(act) => act.func_id,
+            None => return,
+        };
+        self.executed += 1;
+        self.profile
+            .functions
+            .entry(func_id)
+            .or_default()
+            .instructions += 1;
+        *self
+            .profile
+            .instructions
+            .entry((func_id, inst))
+            .or_default() += 1;
+        self.record(Event::Instruction(func_id, inst));
+    }
+
+    fn record(&mut self, event: Event) {
+        if self.trace_limit == 0 {
+            return;
+        }
+        if self.profile.trace.len() == self.trace_limit {
+            self.profile.trace.pop_front();
+        }
+        self.profile.trace.push_back(event);
+    }
+}
+
+impl Profile {
+    pub fn instruction_count(&self, func_id: FunctionId, inst: InstructionId) -> u64 {
+        self.instructions
+            .get(&(func_id, inst))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn block_count(&self, func_id: FunctionId, block: BasicBlockId) -> u64 {
+        self.blocks.get(&(func_id, block)).copied().unwrap_or(0)
+    }
+
+    pub fn edge_count(&self, func_id: FunctionId, from: BasicBlockId, to: BasicBlockId) -> u64 {
+        self.edges.get(&(func_id, from, to)).copied().unwrap_or(0)
+    }
+
+    /// Returns how many times each successor of the terminator `inst` was taken, in the order
+    /// of `!prof` branch weights. Returns `None` if `inst` has less than two successors or was
+    /// never executed.
+    pub fn branch_weights(
+        &self,
+        func_id: FunctionId,
+        func: &Function,
+        inst: InstructionId,
+    ) -> Option<Vec<u64>> {
+        let succs = successors(&func.data.inst_ref(inst).operand)?;
+        if succs.len() < 2 || self.instruction_count(func_id, inst) == 0 {
+            return None;
+        }
+        let from = func.data.inst_ref(inst).parent;
+        Some(
+            succs
+                .iter()
+                .map(|&to| self.edge_count(func_id, from, to))
+                .collect(),
+        )
+    }
+
+    /// Attaches `!prof !{!"branch_weights", ...}` to every executed branch in `module`, which
+    /// must be the module the profile was collected from.
+    pub fn annotate(&self, module: &mut Module) {
+        for (func_id, func) in module.functions_mut().iter_mut() {
+            let weights: Vec<_> = func
+                .data
+                .instructions
+                .iter()
+                .filter_map(|(id, _)| Some((id, self.branch_weights(func_id, func, id)?)))
+                .collect();
+            for (id, weights) in weights {
+                func.data
+                    .inst_ref_mut(id)
+                    .metadata
+                    .insert("prof".to_string(), branch_weights_metadata(&weights));
+            }
+        }
+    }
+
+    /// Renders the per-function counts, hottest first, followed by the hottest blocks.
+    pub fn report(&self, module: &Module) -> String {
+        let mut funcs: Vec<_> = self.functions.iter().collect();
+        funcs.sort_by_key(|(id, p)| (std::cmp::Reverse(p.inclusive_instructions), id.index()));
+        let mut report = format!(
+            "{:>10} {:>12} {:>12}  function\n",
+            "calls", "instructions", "inclusive"
+        );
+        for (&id, p) in funcs {
+            writeln!(
+                report,
+                "{:>10} {:>12} {:>12}  @{}",
+                p.calls,
+                p.instructions,
+                p.inclusive_instructions,
+                module.functions()[id].name()
+            )
+            .unwrap();
+        }
+
+        let mut blocks: Vec<_> = self.blocks.iter().collect();
+        blocks.sort_by_key(|((func_id, block), &n)| {
+            (std::cmp::Reverse(n), func_id.index(), block.index())
+        });
+        writeln!(report, "\n{:>10}  block", "count").unwrap();
+        for (&(func_id, block), n) in blocks.into_iter().take(10) {
+            let func = &module.functions()[func_id];
+            writeln!(
+                report,
+                "{:>10}  @{}, {}",
+                n,
+                func.name(),
+                block_name(func, block)
+            )
+            .unwrap();
+        }
+        report
+    }
+
+    /// Renders the trace with one line per event, indented by call depth.
+    pub fn dump_trace(&self, module: &Module) -> String {
+        let mut dump = String::new();
+        let mut depth = 0usize;
+        for event in &self.trace {
+            match *event {
+                Event::Enter(func_id) => {
+                    let name = module.functions()[func_id].name();
+                    writeln!(dump, "{:indent$}-> @{}", "", name, indent = depth * 2).unwrap();
+                    depth += 1;
+                }
+                Event::Instruction(func_id, inst) => {
+                    let func = &module.functions()[func_id];
+                    writeln!(
+                        dump,
+                        "{:indent$}{}",
+                        "",
+                        func.data.inst_ref(inst).display(&func.data, &func.types),
+                        indent = depth * 2
+                    )
+                    .unwrap();
+                }
+                Event::Leave(func_id) => {
+                    depth = depth.saturating_sub(1);
+                    let name = module.functions()[func_id].name();
+                    writeln!(dump, "{:indent$}<- @{}", "", name, indent = depth * 2).unwrap();
+                }
+            }
+        }
+        dump
+    }
+}
+
+fn successors(operand: &Operand) -> Option<&[BasicBlockId]> {
+    match operand {
+        Operand::CondBr(CondBr { blocks, .. }) => Some(blocks),
+        Operand::Invoke(Invoke { blocks, .. }) | Operand::Switch(Switch { blocks, .. }) => {
+            Some(blocks)
+        }
+        _ => None,
+    }
+}
+
+/// Makes `!{!"branch_weights", i32 w0, i32 w1, ...}`, scaling the weights down to fit in `i32`.
+fn branch_weights_metadata(weights: &[u64]) -> Metadata {
+    let max = weights.iter().copied().max().unwrap_or(0);
+    let scale = max / i32::MAX as u64 + 1;
+    let mut node = vec![Metadata::String("branch_weights".to_string())];
+    node.extend(
+        weights
+            .iter()
+            .map(|&w| Metadata::Const(ConstantValue::Int(ConstantInt::Int32((w / scale) as i32)))),
+    );
+    Metadata::Node(node, false)
+}
+
+fn block_name(func: &Function, block: BasicBlockId) -> String {
+    match &func.data.block_ref(block).name {
+        Some(name) => format!("%{}", name),
+        None => format!("block #{}", block.index()),
+    }
+}
@@ -0,0 +1,560 @@
+//! A managed memory model for the interpreter.
+//!
+//! When enabled with `ContextBuilder::with_sandbox`, allocations live in a simulated address space
+//! instead of the host heap. Every load and store is checked against the bounds, the lifetime and
+//! the initialization state of the allocation it falls in, so that a buggy program is reported
+//! instead of corrupting the interpreter.
+//!
+//! Pointers passed to external functions are translated to the host memory backing their
+//! allocations. Pointers stored in memory are not, so external functions can only follow
+//! pointers into the sandbox one level deep.
+
+use super::{Context, InterpErrorKind};
+use crate::generic_value::GenericValue;
+use std::{alloc, collections::BTreeMap, fmt, ptr};
+use thiserror::Error;
+use vicis_core::ir::{
+    function::{instruction::InstructionId, FunctionId},
+    module::{name::Name, Module},
+};
+
+/// The lowest address handed out. Keeps null and small integers cast to pointers unallocated.
+const BASE_ADDRESS: usize = 0x1000;
+
+/// The unallocated gap after every allocation, so that small overflows never reach the next one.
+const REDZONE: usize = 16;
+
+/// The number of bytes live allocations may take up at a time, unless set by
+/// `ContextBuilder::with_sandbox_limit`.
+pub const DEFAULT_LIMIT: usize = 1 << 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MemoryError {
+    #[error("out-of-bounds {access} of {len} bytes at offset {offset} of {alloc} ({size} bytes)")]
+    OutOfBounds {
+        access: Access,
+        offset: usize,
+        len: usize,
+        size: usize,
+        alloc: String,
+    },
+
+    #[error("{access} of {alloc} after its function returned")]
+    UseAfterReturn { access: Access, alloc: String },
+
+    #[error("{access} of {alloc} after it was freed")]
+    UseAfterFree { access: Access, alloc: String },
+
+    #[error("read of uninitialized memory at offset {offset} of {alloc}")]
+    UninitializedRead { offset: usize, alloc: String },
+
+    #[error("{access} of {len} bytes at {addr:#x}, which is not allocated")]
+    Unallocated {
+        access: Access,
+        addr: usize,
+        len: usize,
+    },
+
+    #[error("free of {addr:#x}, which is not the start of a heap allocation")]
+    InvalidFree { addr: usize },
+
+    #[error("double free of {alloc}")]
+    DoubleFree { alloc: String },
+
+    #[error("allocation of {size} bytes exceeds the sandbox limit of {limit} bytes")]
+    OutOfMemory { size: usize, limit: usize },
+}
+
+/// What created an allocation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AllocSite {
+    /// An `alloca` instruction.
+    Stack(FunctionId, InstructionId),
+    Global(Name),
+    /// A call to a heap allocation function such as `malloc`.
+    Heap(&'static str),
+    Exception,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Live,
+    /// The frame of the `alloca` has returned.
+    Returned,
+    Freed,
+}
+
+struct Allocation {
+    size: usize,
+    site: AllocSite,
+    state: State,
+    bytes: Vec<u8>,
+    /// Whether each byte has been written.
+    init: Vec<bool>,
+}
+
+/// The simulated address space.
+pub struct Sandbox {
+    allocs: BTreeMap<usize, Allocation>,
+    next: usize,
+    /// The number of bytes live allocations may take up.
+    limit: usize,
+    /// The number of bytes live allocations take up.
+    used: usize,
+}
+
+impl Sandbox {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            allocs: BTreeMap::new(),
+            next: BASE_ADDRESS,
+            limit,
+            used: 0,
+        }
+    }
+
+    /// Allocates `size` bytes. Addresses are never reused, so that accesses through dangling
+    /// pointers can be told apart from accesses to newer allocations.
+    ///
+    /// Fails if the allocation would exceed the limit, or the host can't back it.
+    pub fn allocate(
+        &mut self,
+        size: usize,
+        align: usize,
+        site: AllocSite,
+        init: bool,
+    ) -> Result<usize, MemoryError> {
+        let out_of_memory = MemoryError::OutOfMemory {
+            size,
+            limit: self.limit,
+        };
+        if size > self.limit - self.used {
+            return Err(out_of_memory);
+        }
+        let align = align.max(1);
+        let addr = self
+            .next
+            .checked_next_multiple_of(align)
+            .ok_or_else(|| out_of_memory.clone())?;
+        let next = addr
+            .checked_add(size)
+            .and_then(|end| end.checked_add(REDZONE))
+            .ok_or_else(|| out_of_memory.clone())?;
+
+        let mut bytes = Vec::new();
+        let mut init_ = Vec::new();
+        bytes
+            .try_reserve_exact(size)
+            .and_then(|_| init_.try_reserve_exact(size))
+            .map_err(|_| out_of_memory)?;
+        bytes.resize(size, 0);
+        init_.resize(size, init);
+
+        self.next = next;
+        self.used += size;
+        self.allocs.insert(
+            addr,
+            Allocation {
+                size,
+                site,
+                state: State::Live,
+                bytes,
+                init: init_,
+            },
+        );
+        Ok(addr)
+    }
+
+    /// Ends the lifetime of the allocations at `addrs`, all of which are `alloca`s of a frame
+    /// that is returning.
+    pub fn retire(&mut self, addrs: &[*mut u8]) {
+        for addr in addrs {
+            if let Some(alloc) = self.allocs.get_mut(&(*addr as usize)) {
+                self.used -= alloc.size;
+                alloc.kill(State::Returned)
+            }
+        }
+    }
+
+    /// Frees the heap allocation at `addr`.
+    pub fn free(&mut self, module: &Module, addr: usize) -> Result<(), MemoryError> {
+        let alloc = match self.allocs.get_mut(&addr) {
+            Some(alloc) if matches!(alloc.site, AllocSite::Heap(_) | AllocSite::Exception) => alloc,
+            _ => return Err(MemoryError::InvalidFree { addr }),
+        };
+        if alloc.state == State::Freed {
+            let site = alloc.site.clone();
+            return Err(MemoryError::DoubleFree {
+                alloc: Self::describe(module, &site),
+            });
+        }
+        self.used -= alloc.size;
+        alloc.kill(State::Freed);
+        Ok(())
+    }
+
+    pub fn read(&self, module: &Module, addr: usize, buf: &mut [u8]) -> Result<(), MemoryError> {
+        let (base, alloc) = self.check(module, addr, buf.len(), Access::Read)?;
+        let offset = addr - base;
+        let range = offset..offset + buf.len();
+        if let Some(i) = alloc.init[range.clone()].iter().position(|init| !init) {
+            return Err(MemoryError::UninitializedRead {
+                offset: offset + i,
+                alloc: Self::describe(module, &alloc.site),
+            });
+        }
+        buf.copy_from_slice(&alloc.bytes[range]);
+        Ok(())
+    }
+
+    /// Reads memory like `read`, but regardless of whether it is initialized.
+    pub fn peek(&self, module: &Module, addr: usize, buf: &mut [u8]) -> Result<(), MemoryError> {
+        let (base, alloc) = self.check(module, addr, buf.len(), Access::Read)?;
+        let offset = addr - base;
+        buf.copy_from_slice(&alloc.bytes[offset..offset + buf.len()]);
+        Ok(())
+    }
+
+    pub fn write(&mut self, module: &Module, addr: usize, data: &[u8]) -> Result<(), MemoryError> {
+        let (range, alloc) = self.check_mut(module, addr, data.len(), Access::Write)?;
+        alloc.bytes[range.clone()].copy_from_slice(data);
+        alloc.init[range].fill(true);
+        Ok(())
+    }
+
+    /// Copies `len` bytes along with their initialization state, as `memcpy` does.
+    pub fn copy(
+        &mut self,
+        module: &Module,
+        dst: usize,
+        src: usize,
+        len: usize,
+    ) -> Result<(), MemoryError> {
+        let (base, alloc) = self.check(module, src, len, Access::Read)?;
+        let range = src - base..src - base + len;
+        let bytes = alloc.bytes[range.clone()].to_vec();
+        let init = alloc.init[range].to_vec();
+        let (range, alloc) = self.check_mut(module, dst, len, Access::Write)?;
+        alloc.bytes[range.clone()].copy_from_slice(&bytes);
+        alloc.init[range].copy_from_slice(&init);
+        Ok(())
+    }
+
+    pub fn fill(
+        &mut self,
+        module: &Module,
+        dst: usize,
+        val: u8,
+        len: usize,
+    ) -> Result<(), MemoryError> {
+        let (range, alloc) = self.check_mut(module, dst, len, Access::Write)?;
+        alloc.bytes[range.clone()].fill(val);
+        alloc.init[range].fill(true);
+        Ok(())
+    }
+
+    /// Returns the host address backing `addr`, to be passed to an external function.
+    /// Addresses outside of the sandbox (e.g. returned by an external function) are kept as is.
+    ///
+    /// The external function may write to the allocation, so the rest of it is regarded as
+    /// initialized from then on.
+    pub fn host_addr(&mut self, module: &Module, addr: usize) -> Result<usize, MemoryError> {
+        if !(BASE_ADDRESS..self.next).contains(&addr) {
+            return Ok(addr);
+        }
+        let (range, alloc) = self.check_mut(module, addr, 0, Access::Read)?;
+        alloc.init[range.start..].fill(true);
+        Ok(alloc.bytes.as_ptr() as usize + range.start)
+    }
+
+    /// The inverse of `host_addr`.
+    pub fn sandbox_addr(&self, addr: usize) -> usize {
+        self.allocs
+            .iter()
+            .filter(|(_, alloc)| alloc.state == State::Live)
+            .find_map(|(&base, alloc)| {
+                let start = alloc.bytes.as_ptr() as usize;
+                (start..=start + alloc.size)
+                    .contains(&addr)
+                    .then(|| base + addr - start)
+            })
+            .unwrap_or(addr)
+    }
+
+    /// Finds the live allocation `addr..addr + len` lies in.
+    fn check(
+        &self,
+        module: &Module,
+        addr: usize,
+        len: usize,
+        access: Access,
+    ) -> Result<(usize, &Allocation), MemoryError> {
+        let (&base, alloc) = self
+            .allocs
+            .range(..=addr)
+            .next_back()
+            .ok_or(MemoryError::Unallocated { access, addr, len })?;
+        match alloc.state {
+            State::Live => {}
+            State::Returned => {
+                return Err(MemoryError::UseAfterReturn {
+                    access,
+                    alloc: Self::describe(module, &alloc.site),
+                })
+            }
+            State::Freed => {
+                return Err(MemoryError::UseAfterFree {
+                    access,
+                    alloc: Self::describe(module, &alloc.site),
+                })
+            }
+        }
+        let offset = addr - base;
+        if offset + len > alloc.size {
+            return Err(MemoryError::OutOfBounds {
+                access,
+                offset,
+                len,
+                size: alloc.size,
+                alloc: Self::describe(module, &alloc.site),
+            });
+        }
+        Ok((base, alloc))
+    }
+
+    fn check_mut(
+        &mut self,
+        module: &Module,
+        addr: usize,
+        len: usize,
+        access: Access,
+    ) -> Result<(std::ops::Range<usize>, &mut Allocation), MemoryError> {
+        let (base, _) = self.check(module, addr, len, access)?;
+        let offset = addr - base;
+        Ok((offset..offset + len, self.allocs.get_mut(&base).unwrap()))
+    }
+
+    fn describe(module: &Module, site: &AllocSite) -> String {
+        match site {
+            AllocSite::Stack(func, inst) => {
+                let func = &module.functions()[*func];
+                format!(
+                    "`{}` in @{}",
+                    func.data.inst_ref(*inst).display(&func.data, &func.types),
+                    func.name()
+                )
+            }
+            AllocSite::Global(name) => format!("global @{}", name.as_string()),
+            AllocSite::Heap(func) => format!("heap allocation by @{}", func),
+            AllocSite::Exception => "exception object".to_string(),
+        }
+    }
+}
+
+impl Allocation {
+    fn kill(&mut self, state: State) {
+        self.state = state;
+        self.bytes = vec![];
+        self.init = vec![];
+    }
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read => write!(f, "read"),
+            Self::Write => write!(f, "write"),
+        }
+    }
+}
+
+// Memory accesses of the interpreter. They go to the host memory unless the sandbox is enabled.
+impl Context<'_> {
+    pub(super) fn allocate(
+        &self,
+        size: usize,
+        align: usize,
+        site: AllocSite,
+        init: bool,
+    ) -> Result<*mut u8, InterpErrorKind> {
+        match &self.sandbox {
+            Some(sandbox) => Ok(sandbox.borrow_mut().allocate(size, align, site, init)? as *mut u8),
+            None => {
+                let layout = alloc::Layout::from_size_align(size, align)
+                    .map_err(|e| InterpErrorKind::InvalidIr(e.to_string()))?;
+                Ok(unsafe { alloc::alloc_zeroed(layout) })
+            }
+        }
+    }
+
+    /// Frees memory allocated by `allocate` with `layout`.
+    pub(super) fn deallocate(
+        &self,
+        ptr: *mut u8,
+        layout: alloc::Layout,
+    ) -> Result<(), InterpErrorKind> {
+        match &self.sandbox {
+            Some(sandbox) => Ok(sandbox.borrow_mut().free(self.module, ptr as usize)?),
+            None => {
+                unsafe { alloc::dealloc(ptr, layout) };
+                Ok(())
+            }
+        }
+    }
+
+    pub(super) fn read(&self, ptr: *mut u8, buf: &mut [u8]) -> Result<(), InterpErrorKind> {
+        match &self.sandbox {
+            Some(sandbox) => Ok(sandbox.borrow().read(self.module, ptr as usize, buf)?),
+            None => {
+                unsafe { ptr::copy_nonoverlapping(ptr, buf.as_mut_ptr(), buf.len()) };
+                Ok(())
+            }
+        }
+    }
+
+    pub(super) fn peek(&self, ptr: *mut u8, buf: &mut [u8]) -> Result<(), InterpErrorKind> {
+        match &self.sandbox {
+            Some(sandbox) => Ok(sandbox.borrow().peek(self.module, ptr as usize, buf)?),
+            None => self.read(ptr, buf),
+        }
+    }
+
+    pub(super) fn write(&self, ptr: *mut u8, data: &[u8]) -> Result<(), InterpErrorKind> {
+        match &self.sandbox {
+            Some(sandbox) => Ok(sandbox
+                .borrow_mut()
+                .write(self.module, ptr as usize, data)?),
+            None => {
+                unsafe { ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len()) };
+                Ok(())
+            }
+        }
+    }
+
+    pub(super) fn copy(
+        &self,
+        dst: *mut u8,
+        src: *mut u8,
+        len: usize,
+    ) -> Result<(), InterpErrorKind> {
+        match &self.sandbox {
+            Some(sandbox) => {
+                Ok(sandbox
+                    .borrow_mut()
+                    .copy(self.module, dst as usize, src as usize, len)?)
+            }
+            None => {
+                unsafe { ptr::copy_nonoverlapping(src, dst, len) };
+                Ok(())
+            }
+        }
+    }
+
+    pub(super) fn fill(&self, dst: *mut u8, val: u8, len: usize) -> Result<(), InterpErrorKind> {
+        match &self.sandbox {
+            Some(sandbox) => Ok(sandbox
+                .borrow_mut()
+                .fill(self.module, dst as usize, val, len)?),
+            None => {
+                unsafe { ptr::write_bytes(dst, val, len) };
+                Ok(())
+            }
+        }
+    }
+
+    pub(super) fn host_addr(&self, ptr: *mut u8) -> Result<*mut u8, InterpErrorKind> {
+        match &self.sandbox {
+            Some(sandbox) => {
+                Ok(sandbox.borrow_mut().host_addr(self.module, ptr as usize)? as *mut u8)
+            }
+            None => Ok(ptr),
+        }
+    }
+
+    pub(super) fn sandbox_addr(&self, ptr: *mut u8) -> *mut u8 {
+        match &self.sandbox {
+            Some(sandbox) => sandbox.borrow().sandbox_addr(ptr as usize) as *mut u8,
+            None => ptr,
+        }
+    }
+}
+
+/// Handles calls to the C and C++ heap allocation functions while the sandbox is enabled.
+/// Returns `None` if `name` is not one of them.
+pub(super) fn call(
+    ctx: &Context,
+    name: &str,
+    args: &[GenericValue],
+) -> Option<Result<GenericValue, InterpErrorKind>> {
+    let sandbox = ctx.sandbox.as_ref()?;
+    let mismatch = || InterpErrorKind::TypeMismatch(format!("arguments of {}: {:?}", name, args));
+    let int_arg = |i: usize| {
+        args.get(i)
+            .and_then(|a| a.zext_to_u64())
+            .map(|a| a as usize)
+            .ok_or_else(mismatch)
+    };
+    let ptr_arg = |i: usize| {
+        args.get(i)
+            .and_then(|a| a.to_ptr())
+            .map(|a| a as usize)
+            .ok_or_else(mismatch)
+    };
+    let alloc = |site: &'static str, size: usize, init: bool| {
+        sandbox
+            .borrow_mut()
+            .allocate(size, 16, AllocSite::Heap(site), init)
+    };
+    // The C functions return null if the memory can't be allocated.
+    let c_alloc = |site: &'static str, size: Option<usize>, init: bool| {
+        let addr = size.and_then(|size| alloc(site, size, init).ok());
+        GenericValue::Ptr(addr.unwrap_or(0) as *mut u8)
+    };
+    let free = |addr: usize| {
+        if addr != 0 {
+            sandbox.borrow_mut().free(ctx.module, addr)?;
+        }
+        Ok(GenericValue::Void)
+    };
+
+    Some(match name {
+        "malloc" => int_arg(0).map(|size| c_alloc("malloc", Some(size), false)),
+        "calloc" => int_arg(0)
+            .and_then(|n| Ok(n.checked_mul(int_arg(1)?)))
+            .map(|size| c_alloc("calloc", size, true)),
+        "_Znwm" | "_Znam" => int_arg(0).and_then(|size| {
+            Ok(GenericValue::Ptr(
+                alloc("operator new", size, false)? as *mut u8
+            ))
+        }),
+        "free" | "_ZdlPv" | "_ZdaPv" => ptr_arg(0).and_then(free),
+        "realloc" => ptr_arg(0).and_then(|old| {
+            let size = int_arg(1)?;
+            let new = c_alloc("realloc", Some(size), false);
+            // The old allocation is left as is if the new one fails.
+            if old != 0 && !new.to_ptr().unwrap().is_null() {
+                let mut sandbox = sandbox.borrow_mut();
+                let old_size = sandbox
+                    .allocs
+                    .get(&old)
+                    .filter(|a| a.state == State::Live)
+                    .map_or(0, |a| a.size);
+                sandbox.copy(
+                    ctx.module,
+                    new.to_ptr().unwrap() as usize,
+                    old,
+                    old_size.min(size),
+                )?;
+                sandbox.free(ctx.module, old)?;
+            }
+            Ok(new)
+        }),
+        _ => return None,
+    })
+}
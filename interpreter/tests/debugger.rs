@@ -0,0 +1,161 @@
+use std::{cell::RefCell, rc::Rc};
+use vicis_core::ir::module::{name::Name, Module};
+use vicis_interpreter::{
+    generic_value::GenericValue,
+    interpreter::{self, Breakpoint, Command, Debugger, Stop},
+};
+
+const ASM: &str = r#"
+define dso_local i32 @add(i32 %a, i32 %b) {
+entry:
+  %sum = add nsw i32 %a, %b
+  ret i32 %sum
+}
+
+define dso_local i32 @main() {
+entry:
+  %p = alloca i32, align 4
+  store i32 40, i32* %p, align 4
+  %x = load i32, i32* %p, align 4
+  %y = call i32 @add(i32 %x, i32 2)
+  br label %exit
+
+exit:
+  ret i32 %y
+}
+"#;
+
+#[test]
+fn function_breakpoint() {
+    let log = run(
+        |dbg| dbg.with_breakpoint(Breakpoint::Function("add".into())),
+        |stop| {
+            format!(
+                "{:?} {:?}\n{}",
+                stop.value("%a"),
+                stop.value("%b"),
+                stop.backtrace()
+            )
+        },
+        |_| Command::Continue,
+    );
+    insta::assert_snapshot!(log.join("---\n"));
+}
+
+#[test]
+fn caller_values() {
+    let log = run(
+        |dbg| dbg.with_breakpoint(Breakpoint::Function("add".into())),
+        |stop| format!("{:?}", stop.frame(1).unwrap().value(stop.ctx, "%x")),
+        |_| Command::Continue,
+    );
+    assert_eq!(log, vec!["Ok(Int32(40))"]);
+}
+
+#[test]
+fn block_breakpoint() {
+    let log = run(
+        |dbg| dbg.with_breakpoint(Breakpoint::Block("main".into(), Name::Name("exit".into()))),
+        |stop| format!("{:?}", stop.value("%y")),
+        |_| Command::Continue,
+    );
+    assert_eq!(log, vec!["Ok(Int32(42))"]);
+}
+
+#[test]
+fn instruction_breakpoint_and_memory() {
+    let module = Module::try_from(ASM).unwrap();
+    let main = &module.functions()[module.find_function_by_name("main").unwrap()];
+    let (load, _) = main
+        .data
+        .instructions
+        .iter()
+        .find(|(_, inst)| inst.dest == Some(Name::Name("x".into())))
+        .unwrap();
+    let log = Rc::new(RefCell::new(vec![]));
+    let log_ = log.clone();
+    let debugger = Debugger::new(move |stop| {
+        let ptr = stop.value("%p").unwrap().to_ptr().unwrap();
+        // SAFETY: `%p` points to a live `i32`.
+        log_.borrow_mut()
+            .push(unsafe { stop.read_memory(ptr, 4) }.unwrap());
+        // Stop only once.
+        stop.breakpoints.clear();
+        Command::Continue
+    })
+    .with_breakpoint(Breakpoint::Instruction("main".into(), load));
+    let ctx = interpreter::ContextBuilder::new(&module)
+        .with_debugger(debugger)
+        .build()
+        .unwrap();
+    let main = module.find_function_by_name("main").unwrap();
+    assert_eq!(
+        interpreter::run_function(&ctx, main, vec![]),
+        Ok(GenericValue::Int32(42))
+    );
+    assert_eq!(*log.borrow(), vec![40i32.to_ne_bytes().to_vec()]);
+}
+
+#[test]
+fn step() {
+    let log = run(
+        |dbg| dbg.stop_at_start(),
+        |stop| stop.current().location(stop.ctx),
+        |_| Command::Step,
+    );
+    insta::assert_snapshot!(log.join("\n"));
+}
+
+#[test]
+fn next() {
+    let log = run(
+        |dbg| dbg.stop_at_start(),
+        |stop| stop.current().location(stop.ctx),
+        |_| Command::Next,
+    );
+    insta::assert_snapshot!(log.join("\n"));
+}
+
+#[test]
+fn finish() {
+    let log = run(
+        |dbg| dbg.with_breakpoint(Breakpoint::Function("add".into())),
+        |stop| stop.current().location(stop.ctx),
+        |n| {
+            if n == 0 {
+                Command::Finish
+            } else {
+                Command::Continue
+            }
+        },
+    );
+    insta::assert_snapshot!(log.join("\n"));
+}
+
+/// Runs `main` of `ASM` with a debugger, logging what `inspect` returns at every stop.
+/// `command` is given the number of stops so far.
+fn run(
+    setup: impl FnOnce(Debugger) -> Debugger,
+    inspect: impl Fn(&Stop) -> String + 'static,
+    command: impl Fn(usize) -> Command + 'static,
+) -> Vec<String> {
+    let module = Module::try_from(ASM).unwrap();
+    let log = Rc::new(RefCell::new(vec![]));
+    let log_ = log.clone();
+    let debugger = setup(Debugger::new(move |stop| {
+        let n = log_.borrow().len();
+        log_.borrow_mut().push(inspect(stop));
+        command(n)
+    }));
+    let ctx = interpreter::ContextBuilder::new(&module)
+        .with_debugger(debugger)
+        .build()
+        .unwrap();
+    let main = module.find_function_by_name("main").unwrap();
+    assert_eq!(
+        interpreter::run_function(&ctx, main, vec![]),
+        Ok(GenericValue::Int32(42))
+    );
+    let log = log.borrow().clone();
+    log
+}
@@ -1,7 +1,10 @@
 use std::os::raw::c_char;
 
-use vicis_core::ir::module::Module;
-use vicis_interpreter::{generic_value::GenericValue, interpreter};
+use vicis_core::ir::{function::instruction::InstructionId, module::Module};
+use vicis_interpreter::{
+    generic_value::GenericValue,
+    interpreter::{self, Access, InterpError, InterpErrorKind, MemoryError},
+};
 
 #[test]
 fn exec() {
@@ -623,6 +626,599 @@ declare void @llvm.memcpy.p0i8.p0i8.i64(i8* noalias nocapture writeonly, i8* noa
     assert_eq!(rc, GenericValue::Int32(15));
 }
 
+#[test]
+fn opaque_ptr() {
+    let asm = r#"
+%struct.S = type { i32, ptr }
+
+define dso_local i32 @main() {
+  %1 = alloca [4 x i32], align 16
+  %2 = alloca %struct.S, align 8
+  %3 = getelementptr inbounds [4 x i32], ptr %1, i64 0, i64 2
+  store i32 42, ptr %3, align 8
+  %4 = getelementptr inbounds %struct.S, ptr %2, i32 0, i32 1
+  store ptr %3, ptr %4, align 8
+  %5 = load ptr, ptr %4, align 8
+  %6 = getelementptr inbounds i32, ptr %5, i64 -1
+  store i32 1, ptr %6, align 4
+  %7 = getelementptr inbounds [4 x i32], ptr %1, i64 0, i64 1
+  %8 = load i32, ptr %7, align 4
+  %9 = load i32, ptr %5, align 4
+  %10 = add nsw i32 %8, %9
+  ret i32 %10
+}
+"#;
+    assert_eq!(run(asm, vec![]), GenericValue::Int32(43));
+}
+
+#[test]
+fn global_ctors_in_priority_order() {
+    let asm = r#"
+@x = dso_local global i32 1, align 4
+@llvm.global_ctors = appending global [3 x { i32, ptr, ptr }] [{ i32, ptr, ptr } { i32 65535, ptr @add, ptr null }, { i32, ptr, ptr } { i32 101, ptr @mul, ptr null }, { i32, ptr, ptr } { i32 65535, ptr @sub, ptr null }]
+
+define internal void @add() {
+  %1 = load i32, ptr @x, align 4
+  %2 = add i32 %1, 2
+  store i32 %2, ptr @x, align 4
+  ret void
+}
+
+define internal void @mul() {
+  %1 = load i32, ptr @x, align 4
+  %2 = mul i32 %1, 10
+  store i32 %2, ptr @x, align 4
+  ret void
+}
+
+define internal void @sub() {
+  %1 = load i32, ptr @x, align 4
+  %2 = sub i32 %1, 5
+  store i32 %2, ptr @x, align 4
+  ret void
+}
+
+define dso_local i32 @main() {
+  %1 = load i32, ptr @x, align 4
+  ret i32 %1
+}
+"#;
+    assert_eq!(run(asm, vec![]), GenericValue::Int32(7));
+}
+
+#[test]
+fn error_malformed_global_ctors() {
+    let asm = r#"
+@llvm.global_ctors = appending global [1 x { i32, ptr, ptr }] [{ i32, ptr, ptr } { i32 65535, ptr null, ptr null }]
+
+define dso_local i32 @main() {
+  ret i32 0
+}
+"#;
+    let module = Module::try_from(asm).unwrap();
+    let err = interpreter::ContextBuilder::new(&module)
+        .build()
+        .err()
+        .unwrap();
+    assert!(matches!(err.kind, InterpErrorKind::UnsupportedConstant(_)));
+}
+
+#[test]
+fn error_unsupported_instruction() {
+    let asm = r#"
+define dso_local i32 @main() {
+  %1 = add i32 1, 2
+  %2 = xor i32 %1, 3
+  ret i32 %2
+}
+"#;
+    let (err, insts) = run_err(asm);
+    assert_eq!(err.func, "main");
+    assert_eq!(err.inst, Some(insts[1]));
+    assert_eq!(
+        err.kind,
+        InterpErrorKind::UnsupportedInstruction("xor".into())
+    );
+}
+
+#[test]
+fn error_division_by_zero() {
+    let asm = r#"
+define dso_local i32 @div(i32 %x, i32 %y) {
+  %1 = sdiv i32 %x, %y
+  ret i32 %1
+}
+
+define dso_local i32 @main() {
+  %1 = call i32 @div(i32 1, i32 0)
+  ret i32 %1
+}
+"#;
+    let (err, _) = run_err(asm);
+    assert_eq!(err.func, "div");
+    assert_eq!(err.kind, InterpErrorKind::DivisionByZero);
+    assert!(err
+        .to_string()
+        .starts_with("division by zero in @div at instruction #"));
+}
+
+#[test]
+fn error_unresolved_symbol() {
+    let asm = r#"
+declare i32 @no_such_function(i32)
+
+define dso_local i32 @main() {
+  %1 = call i32 @no_such_function(i32 1)
+  ret i32 %1
+}
+"#;
+    let (err, insts) = run_err(asm);
+    assert_eq!(err.func, "main");
+    assert_eq!(err.inst, Some(insts[0]));
+    assert_eq!(
+        err.kind,
+        InterpErrorKind::UnresolvedSymbol("no_such_function".into())
+    );
+}
+
+#[test]
+fn error_type_mismatch() {
+    let asm = r#"
+define dso_local i32 @main() {
+  %1 = icmp slt i64 1, 2
+  ret i32 0
+}
+"#;
+    let (err, insts) = run_err(asm);
+    assert_eq!(err.inst, Some(insts[0]));
+    assert!(matches!(err.kind, InterpErrorKind::TypeMismatch(_)));
+}
+
+#[test]
+fn error_null_dereference() {
+    let asm = r#"
+define dso_local i32 @main() {
+  %1 = load i32, i32* null, align 4
+  ret i32 %1
+}
+"#;
+    let (err, insts) = run_err(asm);
+    assert_eq!(err.inst, Some(insts[0]));
+    assert!(matches!(err.kind, InterpErrorKind::OutOfBounds(_)));
+}
+
+const EH_DECLS: &str = r#"
+@_ZTIi = external constant i8*
+@_ZTId = external constant i8*
+
+declare i8* @__cxa_allocate_exception(i64)
+declare void @__cxa_throw(i8*, i8*, i8*)
+declare i8* @__cxa_begin_catch(i8*)
+declare void @__cxa_end_catch()
+declare i32 @__gxx_personality_v0(...)
+declare i32 @llvm.eh.typeid.for(i8*)
+
+define dso_local void @throw_int(i32 %0) {
+  %2 = call i8* @__cxa_allocate_exception(i64 4)
+  %3 = bitcast i8* %2 to i32*
+  store i32 %0, i32* %3, align 16
+  call void @__cxa_throw(i8* %2, i8* bitcast (i8** @_ZTIi to i8*), i8* null)
+  unreachable
+}
+"#;
+
+#[test]
+fn exception_caught_by_invoke() {
+    let asm = r#"
+define dso_local i32 @main() personality i8* bitcast (i32 (...)* @__gxx_personality_v0 to i8*) {
+entry:
+  invoke void @throw_int(i32 41)
+          to label %cont unwind label %lpad
+
+cont:
+  ret i32 0
+
+lpad:
+  %0 = landingpad { i8*, i32 }
+          catch i8* bitcast (i8** @_ZTId to i8*)
+          catch i8* bitcast (i8** @_ZTIi to i8*)
+  %1 = extractvalue { i8*, i32 } %0, 0
+  %2 = extractvalue { i8*, i32 } %0, 1
+  %3 = call i32 @llvm.eh.typeid.for(i8* bitcast (i8** @_ZTIi to i8*))
+  %4 = icmp eq i32 %2, %3
+  br i1 %4, label %catch, label %other
+
+catch:
+  %5 = call i8* @__cxa_begin_catch(i8* %1)
+  %6 = bitcast i8* %5 to i32*
+  %7 = load i32, i32* %6, align 4
+  call void @__cxa_end_catch()
+  %8 = add nsw i32 %7, 1
+  ret i32 %8
+
+other:
+  ret i32 -1
+}
+"#;
+    assert_eq!(
+        run(&format!("{}{}", EH_DECLS, asm), vec![]),
+        GenericValue::Int32(42)
+    );
+}
+
+#[test]
+fn exception_cleanup_and_resume() {
+    let asm = r#"
+@cleaned = global i32 0, align 4
+
+define dso_local void @f() personality i8* bitcast (i32 (...)* @__gxx_personality_v0 to i8*) {
+entry:
+  invoke void @throw_int(i32 7)
+          to label %cont unwind label %lpad
+
+cont:
+  ret void
+
+lpad:
+  %0 = landingpad { i8*, i32 }
+          cleanup
+  store i32 1, i32* @cleaned, align 4
+  resume { i8*, i32 } %0
+}
+
+define dso_local void @g() {
+  call void @f()
+  ret void
+}
+
+define dso_local i32 @main() personality i8* bitcast (i32 (...)* @__gxx_personality_v0 to i8*) {
+entry:
+  invoke void @g()
+          to label %cont unwind label %lpad
+
+cont:
+  ret i32 0
+
+lpad:
+  %0 = landingpad { i8*, i32 }
+          catch i8* null
+  %1 = extractvalue { i8*, i32 } %0, 0
+  %2 = call i8* @__cxa_begin_catch(i8* %1)
+  %3 = bitcast i8* %2 to i32*
+  %4 = load i32, i32* %3, align 4
+  call void @__cxa_end_catch()
+  %5 = load i32, i32* @cleaned, align 4
+  %6 = mul nsw i32 %4, %5
+  ret i32 %6
+}
+"#;
+    assert_eq!(
+        run(&format!("{}{}", EH_DECLS, asm), vec![]),
+        GenericValue::Int32(7)
+    );
+}
+
+#[test]
+fn exception_caught_by_base_class() {
+    let asm = r#"
+@_ZTVN10__cxxabiv117__class_type_infoE = external global i8*
+@_ZTVN10__cxxabiv120__si_class_type_infoE = external global i8*
+@_ZTS4Base = constant [6 x i8] c"4Base\00", align 1
+@_ZTI4Base = constant { i8*, i8* } { i8* bitcast (i8** getelementptr inbounds (i8*, i8** @_ZTVN10__cxxabiv117__class_type_infoE, i64 2) to i8*), i8* getelementptr inbounds ([6 x i8], [6 x i8]* @_ZTS4Base, i32 0, i32 0) }, align 8
+@_ZTS7Derived = constant [9 x i8] c"7Derived\00", align 1
+@_ZTI7Derived = constant { i8*, i8*, i8* } { i8* bitcast (i8** getelementptr inbounds (i8*, i8** @_ZTVN10__cxxabiv120__si_class_type_infoE, i64 2) to i8*), i8* getelementptr inbounds ([9 x i8], [9 x i8]* @_ZTS7Derived, i32 0, i32 0), i8* bitcast ({ i8*, i8* }* @_ZTI4Base to i8*) }, align 8
+
+define dso_local i32 @main() personality i8* bitcast (i32 (...)* @__gxx_personality_v0 to i8*) {
+entry:
+  %0 = call i8* @__cxa_allocate_exception(i64 1)
+  invoke void @__cxa_throw(i8* %0, i8* bitcast ({ i8*, i8*, i8* }* @_ZTI7Derived to i8*), i8* null)
+          to label %cont unwind label %lpad
+
+cont:
+  unreachable
+
+lpad:
+  %1 = landingpad { i8*, i32 }
+          catch i8* bitcast (i8** @_ZTIi to i8*)
+          catch i8* bitcast ({ i8*, i8* }* @_ZTI4Base to i8*)
+  %2 = extractvalue { i8*, i32 } %1, 1
+  %3 = call i32 @llvm.eh.typeid.for(i8* bitcast ({ i8*, i8* }* @_ZTI4Base to i8*))
+  %4 = icmp eq i32 %2, %3
+  br i1 %4, label %catch, label %other
+
+catch:
+  ret i32 1
+
+other:
+  ret i32 0
+}
+"#;
+    assert_eq!(
+        run(&format!("{}{}", EH_DECLS, asm), vec![]),
+        GenericValue::Int32(1)
+    );
+}
+
+#[test]
+fn exception_uncaught() {
+    let asm = r#"
+define dso_local i32 @main() personality i8* bitcast (i32 (...)* @__gxx_personality_v0 to i8*) {
+entry:
+  invoke void @throw_int(i32 1)
+          to label %cont unwind label %lpad
+
+cont:
+  ret i32 0
+
+lpad:
+  %0 = landingpad { i8*, i32 }
+          catch i8* bitcast (i8** @_ZTId to i8*)
+  ret i32 -1
+}
+"#;
+    let (err, _) = run_err(&format!("{}{}", EH_DECLS, asm));
+    assert_eq!(
+        err.kind,
+        InterpErrorKind::UncaughtException("@_ZTIi".to_string())
+    );
+    assert_eq!(err.func, "main");
+}
+
+#[test]
+fn exception_rethrow_foreign_object() {
+    let asm = r#"
+declare void @__cxa_rethrow()
+
+define dso_local i32 @main() {
+  %1 = alloca i32, align 4
+  %2 = bitcast i32* %1 to i8*
+  %3 = call i8* @__cxa_begin_catch(i8* %2)
+  call void @__cxa_rethrow()
+  ret i32 0
+}
+"#;
+    let (err, insts) = run_err(&format!("{}{}", EH_DECLS, asm));
+    assert_eq!(err.func, "main");
+    assert_eq!(err.inst, Some(insts[3]));
+    assert!(matches!(err.kind, InterpErrorKind::InvalidIr(_)));
+}
+
+#[test]
+fn sandbox_runs_valid_program() {
+    let asm = r#"
+declare i8* @malloc(i64)
+declare void @free(i8*)
+declare void @llvm.memset.p0i8.i64(i8*, i8, i64, i1)
+
+define dso_local i32 @main() {
+  %1 = call i8* @malloc(i64 16)
+  call void @llvm.memset.p0i8.i64(i8* %1, i8 1, i64 16, i1 false)
+  %2 = bitcast i8* %1 to i32*
+  %3 = getelementptr inbounds i32, i32* %2, i64 3
+  store i32 41, i32* %3, align 4
+  %4 = load i32, i32* %3, align 4
+  %5 = load i8, i8* %1, align 1
+  %6 = sext i8 %5 to i32
+  %7 = add nsw i32 %4, %6
+  call void @free(i8* %1)
+  ret i32 %7
+}
+"#;
+    assert_eq!(run_sandboxed(asm, "main"), Ok(GenericValue::Int32(42)));
+}
+
+#[test]
+fn sandbox_external_call() {
+    let asm = r#"
+      @.str = private unnamed_addr constant [3 x i8] c"11\00", align 1
+      @.str.1 = private unnamed_addr constant [3 x i8] c"%d\00", align 1
+      define i32 @f() #0 {
+        %a = alloca i32, align 8
+        %b = call i32 (i8*, i8*, ...) @sscanf(i8* getelementptr inbounds ([3 x i8], [3 x i8]* @.str, i64 0, i64 0),
+                                              i8* getelementptr inbounds ([3 x i8], [3 x i8]* @.str.1, i64 0, i64 0), i32* %a)
+        %c = load i32, i32* %a, align 8
+        ret i32 %c
+      }
+      declare i32 @sscanf(i8*, i8*, ...)
+      "#;
+    assert_eq!(run_sandboxed(asm, "f"), Ok(GenericValue::Int32(11)));
+}
+
+#[test]
+fn sandbox_out_of_bounds() {
+    let asm = r#"
+define dso_local i32 @main() {
+  %1 = alloca [2 x i32], align 4
+  %2 = getelementptr inbounds [2 x i32], [2 x i32]* %1, i64 0, i64 2
+  store i32 0, i32* %2, align 4
+  ret i32 0
+}
+"#;
+    let module = Module::try_from(asm).unwrap();
+    let ctx = interpreter::ContextBuilder::new(&module)
+        .with_sandbox()
+        .build()
+        .unwrap();
+    let main = module.find_function_by_name("main").unwrap();
+    let err = interpreter::run_function(&ctx, main, vec![]).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        InterpErrorKind::Memory(MemoryError::OutOfBounds {
+            access: Access::Write,
+            offset: 8,
+            len: 4,
+            size: 8,
+            ..
+        })
+    ));
+    assert_eq!(
+        err.report(&module),
+        "error: out-of-bounds write of 4 bytes at offset 8 of `%1 = alloca [2 x i32], i32 1, align 4` in @main (8 bytes)
+  --> @main, instruction #2
+   |
+   |   store i32 0, i32* %2, align 4"
+    );
+}
+
+#[test]
+fn sandbox_use_after_return() {
+    let asm = r#"
+define dso_local i32* @f() {
+  %1 = alloca i32, align 4
+  store i32 1, i32* %1, align 4
+  ret i32* %1
+}
+
+define dso_local i32 @main() {
+  %1 = call i32* @f()
+  %2 = load i32, i32* %1, align 4
+  ret i32 %2
+}
+"#;
+    let err = run_sandboxed(asm, "main").unwrap_err();
+    assert!(matches!(
+        err.kind,
+        InterpErrorKind::Memory(MemoryError::UseAfterReturn {
+            access: Access::Read,
+            ..
+        })
+    ));
+    assert_eq!(
+        err.to_string(),
+        "read of `%1 = alloca i32, i32 1, align 4` in @f after its function returned in @main at instruction #1"
+    );
+}
+
+#[test]
+fn sandbox_uninitialized_read() {
+    let asm = r#"
+define dso_local i32 @main() {
+  %1 = alloca i64, align 8
+  %2 = bitcast i64* %1 to i32*
+  store i32 1, i32* %2, align 4
+  %3 = load i64, i64* %1, align 8
+  %4 = trunc i64 %3 to i32
+  ret i32 %4
+}
+"#;
+    let err = run_sandboxed(asm, "main").unwrap_err();
+    assert!(matches!(
+        err.kind,
+        InterpErrorKind::Memory(MemoryError::UninitializedRead { offset: 4, .. })
+    ));
+}
+
+#[test]
+fn sandbox_use_after_free() {
+    let asm = r#"
+declare i8* @malloc(i64)
+declare void @free(i8*)
+
+define dso_local i32 @main() {
+  %1 = call i8* @malloc(i64 1)
+  store i8 1, i8* %1, align 1
+  call void @free(i8* %1)
+  %2 = load i8, i8* %1, align 1
+  %3 = sext i8 %2 to i32
+  ret i32 %3
+}
+"#;
+    let err = run_sandboxed(asm, "main").unwrap_err();
+    assert_eq!(
+        err.kind,
+        InterpErrorKind::Memory(MemoryError::UseAfterFree {
+            access: Access::Read,
+            alloc: "heap allocation by @malloc".to_string()
+        })
+    );
+}
+
+#[test]
+fn sandbox_double_free() {
+    let asm = r#"
+declare i8* @malloc(i64)
+declare void @free(i8*)
+
+define dso_local i32 @main() {
+  %1 = call i8* @malloc(i64 4)
+  call void @free(i8* %1)
+  call void @free(i8* %1)
+  ret i32 0
+}
+"#;
+    let err = run_sandboxed(asm, "main").unwrap_err();
+    assert!(matches!(
+        err.kind,
+        InterpErrorKind::Memory(MemoryError::DoubleFree { .. })
+    ));
+}
+
+#[test]
+fn sandbox_failed_allocations_return_null() {
+    let asm = r#"
+declare i8* @malloc(i64)
+declare i8* @calloc(i64, i64)
+
+define dso_local i32 @main() {
+  %1 = call i8* @malloc(i64 -1)
+  %2 = icmp eq i8* %1, null
+  %3 = call i8* @calloc(i64 4611686018427387904, i64 8)
+  %4 = icmp eq i8* %3, null
+  %5 = zext i1 %2 to i32
+  %6 = zext i1 %4 to i32
+  %7 = add i32 %5, %6
+  ret i32 %7
+}
+"#;
+    assert_eq!(run_sandboxed(asm, "main").unwrap(), GenericValue::Int32(2));
+}
+
+#[test]
+fn sandbox_limit() {
+    let asm = r#"
+declare i8* @malloc(i64)
+declare void @free(i8*)
+
+define dso_local i32 @main() {
+  %1 = call i8* @malloc(i64 768)
+  call void @free(i8* %1)
+  %2 = call i8* @malloc(i64 768)
+  %3 = alloca [512 x i8], align 1
+  ret i32 0
+}
+"#;
+    let module = Module::try_from(asm).unwrap();
+    let ctx = interpreter::ContextBuilder::new(&module)
+        .with_sandbox_limit(1024)
+        .build()
+        .unwrap();
+    let main = module.find_function_by_name("main").unwrap();
+    let err = interpreter::run_function(&ctx, main, vec![]).unwrap_err();
+    assert_eq!(
+        err.kind,
+        InterpErrorKind::Memory(MemoryError::OutOfMemory {
+            size: 512,
+            limit: 1024
+        })
+    );
+}
+
+/// Runs `main` expecting an error. Also returns the instructions of `main` in order.
+#[cfg(test)]
+fn run_err(asm: &str) -> (InterpError, Vec<InstructionId>) {
+    let module = Module::try_from(asm).unwrap();
+    let ctx = interpreter::ContextBuilder::new(&module).build().unwrap();
+    let main = module.find_function_by_name("main").unwrap();
+    let func = &module.functions()[main];
+    let insts = func
+        .layout
+        .block_iter()
+        .flat_map(|block| func.layout.inst_iter(block))
+        .collect();
+    let err = interpreter::run_function(&ctx, main, vec![]).unwrap_err();
+    (err, insts)
+}
+
 #[cfg(test)]
 fn run(asm: &str, args: Vec<GenericValue>) -> GenericValue {
     let module = Module::try_from(asm).unwrap();
@@ -634,20 +1230,35 @@ fn run(asm: &str, args: Vec<GenericValue>) -> GenericValue {
 #[cfg(test)]
 fn run_libc(asm: &str, fname: &str, args: Vec<GenericValue>) -> GenericValue {
     let module = Module::try_from(asm).unwrap();
-    let mut ctx_builder = interpreter::ContextBuilder::new(&module);
+    let ctx = with_libc(interpreter::ContextBuilder::new(&module))
+        .build()
+        .unwrap();
+    let main = module.find_function_by_name(fname).unwrap();
+    interpreter::run_function(&ctx, main, args).unwrap()
+}
+
+#[cfg(test)]
+fn run_sandboxed(asm: &str, fname: &str) -> Result<GenericValue, InterpError> {
+    let module = Module::try_from(asm).unwrap();
+    let ctx = with_libc(interpreter::ContextBuilder::new(&module).with_sandbox())
+        .build()
+        .unwrap();
+    let main = module.find_function_by_name(fname).unwrap();
+    interpreter::run_function(&ctx, main, vec![])
+}
+
+#[cfg(test)]
+fn with_libc(ctx_builder: interpreter::ContextBuilder) -> interpreter::ContextBuilder {
     #[cfg(target_os = "macos")]
     {
-        ctx_builder = ctx_builder.with_lib("libc.dylib");
+        ctx_builder.with_lib("libc.dylib")
     }
     #[cfg(target_os = "linux")]
     {
-        ctx_builder = ctx_builder.with_lib("libc.so.6");
+        ctx_builder.with_lib("libc.so.6")
     }
     #[cfg(target_os = "windows")]
     {
-        ctx_builder = ctx_builder.with_lib("msvcrt.dll");
+        ctx_builder.with_lib("msvcrt.dll")
     }
-    let ctx = ctx_builder.build().unwrap();
-    let main = module.find_function_by_name(fname).unwrap();
-    interpreter::run_function(&ctx, main, args).unwrap()
 }
@@ -0,0 +1,174 @@
+use vicis_core::ir::module::{metadata::Metadata, name::Name, Module};
+use vicis_interpreter::{
+    generic_value::GenericValue,
+    interpreter::{self, FunctionProfile, Profile, Profiler},
+};
+
+const ASM: &str = r#"
+define dso_local i32 @sum(i32 %n) {
+entry:
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %entry ], [ %i1, %loop ]
+  %acc = phi i32 [ 0, %entry ], [ %acc1, %loop ]
+  %acc1 = add nsw i32 %acc, %i
+  %i1 = add nsw i32 %i, 1
+  %c = icmp slt i32 %i1, %n
+  br i1 %c, label %loop, label %exit
+
+exit:
+  ret i32 %acc1
+}
+
+define dso_local i32 @fact(i32 %n) {
+entry:
+  %c = icmp sle i32 %n, 1
+  br i1 %c, label %base, label %rec
+
+base:
+  ret i32 1
+
+rec:
+  %m = sub nsw i32 %n, 1
+  %f = call i32 @fact(i32 %m)
+  %r = mul nsw i32 %n, %f
+  ret i32 %r
+}
+
+define dso_local i32 @main() {
+entry:
+  %x = call i32 @sum(i32 4)
+  %y = call i32 @fact(i32 3)
+  %z = add nsw i32 %x, %y
+  ret i32 %z
+}
+"#;
+
+#[test]
+fn function_counts() {
+    let module = Module::try_from(ASM).unwrap();
+    let profile = run(&module, Profiler::new());
+    let counts = |name| profile.functions[&module.find_function_by_name(name).unwrap()];
+
+    assert_eq!(
+        counts("main"),
+        FunctionProfile {
+            calls: 1,
+            instructions: 4,
+            inclusive_instructions: 4 + 26 + 15,
+        }
+    );
+    assert_eq!(
+        counts("sum"),
+        FunctionProfile {
+            calls: 1,
+            instructions: 26,
+            inclusive_instructions: 26,
+        }
+    );
+    // fact(3) -> fact(2) -> fact(1). The nested activations are included in the outermost one.
+    assert_eq!(
+        counts("fact"),
+        FunctionProfile {
+            calls: 3,
+            instructions: 15,
+            inclusive_instructions: 15,
+        }
+    );
+}
+
+#[test]
+fn block_and_instruction_counts() {
+    let module = Module::try_from(ASM).unwrap();
+    let profile = run(&module, Profiler::new());
+    let sum_id = module.find_function_by_name("sum").unwrap();
+    let sum = &module.functions()[sum_id];
+    let block = |name: &str| {
+        sum.layout
+            .block_iter()
+            .find(|&b| sum.data.block_ref(b).name == Some(Name::Name(name.into())))
+            .unwrap()
+    };
+
+    assert_eq!(profile.block_count(sum_id, block("entry")), 1);
+    assert_eq!(profile.block_count(sum_id, block("loop")), 4);
+    assert_eq!(profile.block_count(sum_id, block("exit")), 1);
+    assert_eq!(profile.edge_count(sum_id, block("entry"), block("loop")), 1);
+    assert_eq!(profile.edge_count(sum_id, block("loop"), block("loop")), 3);
+    assert_eq!(profile.edge_count(sum_id, block("loop"), block("exit")), 1);
+    for inst in sum.layout.inst_iter(block("loop")) {
+        assert_eq!(profile.instruction_count(sum_id, inst), 4);
+    }
+}
+
+#[test]
+fn branch_weights() {
+    let mut module = Module::try_from(ASM).unwrap();
+    let profile = run(&module, Profiler::new());
+    profile.annotate(&mut module);
+
+    let weights = |name| {
+        let func = &module.functions()[module.find_function_by_name(name).unwrap()];
+        let mut weights: Vec<_> = func
+            .data
+            .instructions
+            .iter()
+            .filter_map(|(_, inst)| inst.metadata.get("prof"))
+            .map(|m| format!("{}", m.display(&module.types)))
+            .collect();
+        weights.sort();
+        weights
+    };
+    assert_eq!(
+        weights("sum"),
+        vec![r#"!{!"branch_weights", i32 3, i32 1}"#]
+    );
+    assert_eq!(
+        weights("fact"),
+        vec![r#"!{!"branch_weights", i32 1, i32 2}"#]
+    );
+    assert!(weights("main").is_empty());
+
+    // The annotated module can be read back.
+    let module = Module::try_from(format!("{:?}", module).as_str()).unwrap();
+    let sum = &module.functions()[module.find_function_by_name("sum").unwrap()];
+    assert!(sum.data.instructions.iter().any(|(_, inst)| matches!(
+        inst.metadata.get("prof"),
+        Some(Metadata::Node(list, false)) if list.len() == 3
+    )));
+}
+
+#[test]
+fn trace() {
+    let module = Module::try_from(ASM).unwrap();
+    let profile = run(&module, Profiler::new().with_trace(usize::MAX));
+    insta::assert_snapshot!(profile.dump_trace(&module));
+
+    let profile = run(&module, Profiler::new().with_trace(3));
+    assert_eq!(
+        profile.dump_trace(&module),
+        "%z = add nsw i32 %x, %y\nret i32 %z\n<- @main\n"
+    );
+}
+
+#[test]
+fn report() {
+    let module = Module::try_from(ASM).unwrap();
+    let profile = run(&module, Profiler::new());
+    insta::assert_snapshot!(profile.report(&module));
+}
+
+fn run(module: &Module, profiler: Profiler) -> Profile {
+    let ctx = interpreter::ContextBuilder::new(module)
+        .with_profiler(profiler)
+        .build()
+        .unwrap();
+    let main = module.find_function_by_name("main").unwrap();
+    assert_eq!(
+        interpreter::run_function(&ctx, main, vec![]),
+        Ok(GenericValue::Int32(12))
+    );
+    let profile = ctx.profile().unwrap().clone();
+    profile
+}
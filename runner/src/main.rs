@@ -7,7 +7,10 @@ use rand::Rng;
 use std::{fs, io::Write, process};
 use structopt::StructOpt;
 use vicis_codegen::codegen::{isa::x86_64::X86_64, lower::compile_module};
-use vicis_core::ir::module;
+use vicis_core::{
+    ir::module,
+    pass::{pipeline::OptLevel, PassManager},
+};
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "i")]
@@ -16,12 +19,30 @@ pub struct Opt {
 
     #[structopt(long = "load")]
     pub libs: Vec<String>,
+
+    #[structopt(
+        short = "O",
+        default_value = "0",
+        help = "Optimization level (0, 1 or 2)"
+    )]
+    pub opt_level: OptLevel,
+
+    #[structopt(
+        long = "passes",
+        help = "Comma-separated list of passes to run instead of the -O pipeline"
+    )]
+    pub passes: Option<String>,
 }
 
 fn main() {
     let opt = Opt::from_args();
     let ir = fs::read_to_string(opt.ir_file.as_str()).expect("failed to load *.ll file");
-    let module = module::parse_assembly(ir.as_str()).expect("failed to parse LLVM Assembly");
+    let mut module = module::parse_assembly(ir.as_str()).expect("failed to parse LLVM Assembly");
+    let mut pm = match opt.passes {
+        Some(passes) => PassManager::from_pipeline(&passes).expect("invalid pipeline"),
+        None => PassManager::with_opt_level(opt.opt_level),
+    };
+    pm.run_on_module(&mut module);
     let module = compile_module(X86_64, &module).expect("failed to compile module");
     let asm_file_name = unique_file_name("s");
     let mut output =